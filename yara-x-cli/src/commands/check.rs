@@ -2,19 +2,22 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{fs, io};
 
-use anyhow::Context;
+use anyhow::bail;
 use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
 use crossterm::tty::IsTty;
+use serde_json::{json, Value};
 use superconsole::{Component, Line, Lines, Span};
 use yansi::Color::{Green, Red, Yellow};
-use yara_x_parser::{Parser, SourceCode};
+use yara_x::{CompileErrorInfo, Compiler, LintPass, LintReport};
+use yara_x_parser::{ast, SourceCode, UnusedPatternAction, Warning};
 
+use crate::commands::{json_to_variable, read_globals};
 use crate::walk::Message;
 use crate::{help, walk};
 
 pub fn check() -> Command {
     super::command("check")
-        .about("Check if source files are syntactically correct")
+        .about("Check if source files are correct")
         .long_about(help::CHECK_LONG_HELP)
         .arg(
             arg!(<RULES_PATH>)
@@ -40,6 +43,45 @@ pub fn check() -> Command {
                 .required(false)
                 .value_parser(value_parser!(u8).range(1..)),
         )
+        .arg(
+            arg!(-m --"required-meta" <IDENTIFIER>)
+                .help("Warn about rules that don't have the given metadata")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(-D --"define" <VAR_VALUE>)
+                .help("Define an external variable (VAR=VALUE)")
+                .long_help(help::CHECK_DEFINE_LONG_HELP)
+                .value_parser(parse_define)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--"define-file" <VARS_FILE>)
+                .help(
+                    "Define external variables with the values in a JSON file",
+                )
+                .long_help(help::DEFINE_FILE_LONG_HELP)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-i --"ignore" <CODE>)
+                .help("Ignore warnings with the given code")
+                .long_help(help::CHECK_IGNORE_LONG_HELP)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--"fail-on" <SEVERITY>)
+                .help("Minimum severity that makes the command fail")
+                .long_help(help::CHECK_FAIL_ON_LONG_HELP)
+                .value_parser(["error", "warning", "never"])
+                .default_value("error"),
+        )
+        .arg(
+            arg!(-o --"output-format" <FORMAT>)
+                .help("Output format")
+                .value_parser(["text", "ndjson"])
+                .default_value("text"),
+        )
 }
 
 pub fn exec_check(args: &ArgMatches) -> anyhow::Result<()> {
@@ -47,6 +89,50 @@ pub fn exec_check(args: &ArgMatches) -> anyhow::Result<()> {
     let max_depth = args.get_one::<u16>("max-depth");
     let filters = args.get_many::<String>("filter");
     let num_threads = args.get_one::<u8>("threads");
+    let output_format =
+        args.get_one::<String>("output-format").unwrap().as_str();
+
+    let required_meta: Vec<String> = args
+        .get_many::<String>("required-meta")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+
+    let ignored: Vec<&str> = args
+        .get_many::<String>("ignore")
+        .into_iter()
+        .flatten()
+        .map(|code| code.as_str())
+        .collect();
+
+    let mut globals = args
+        .get_one::<PathBuf>("define-file")
+        .map(|path| read_globals(path))
+        .transpose()?
+        .unwrap_or_default();
+
+    // Variables defined with `--define` override the ones with the same
+    // name in the file.
+    for (ident, value) in
+        args.get_many::<(String, Value)>("define").into_iter().flatten()
+    {
+        globals.retain(|(i, _)| i != ident);
+        globals.push((ident.clone(), value.clone()));
+    }
+
+    // Make sure that variables are valid before checking any file.
+    let mut compiler = Compiler::new();
+    for (ident, value) in globals.iter() {
+        compiler.define_global(ident, json_to_variable(value))?;
+    }
+
+    let fail_on = match args.get_one::<String>("fail-on").unwrap().as_str() {
+        "error" => Some(Severity::Error),
+        "warning" => Some(Severity::Warning),
+        "never" => None,
+        _ => unreachable!(),
+    };
 
     let mut w = walk::ParDirWalker::new();
 
@@ -67,67 +153,272 @@ pub fn exec_check(args: &ArgMatches) -> anyhow::Result<()> {
         w.filter("**/*.yar").filter("**/*.yara");
     }
 
+    // Number of files with diagnostics at or above the `--fail-on`
+    // severity.
+    let failed_files = AtomicUsize::new(0);
+    let failed_files_ref = &failed_files;
+    let required_meta_ref = &required_meta;
+    let ignored_ref = &ignored;
+    let globals_ref = &globals;
+
     w.walk(
         rules_path,
         CheckState::new(),
         || {},
         |file_path, state, output, _| {
-            let src = fs::read(file_path.clone())
-                .with_context(|| {
-                    format!("can not read `{}`", file_path.display())
-                })
-                .unwrap();
+            let src = match fs::read(&file_path) {
+                Ok(src) => src,
+                Err(err) => {
+                    failed_files_ref.fetch_add(1, Ordering::Relaxed);
+                    output
+                        .send(Message::Error(format!(
+                            "{} can not read `{}`: {}",
+                            Red.paint("error:").bold(),
+                            file_path.display(),
+                            err
+                        )))
+                        .unwrap();
+                    return;
+                }
+            };
 
             let src = SourceCode::from(src.as_slice())
                 .with_origin(file_path.as_os_str().to_str().unwrap());
 
-            let mut lines = Vec::new();
+            let diagnostics = check_source(
+                src,
+                globals_ref,
+                required_meta_ref,
+                ignored_ref,
+                output_format == "text" && io::stdout().is_tty(),
+            );
 
-            match Parser::new()
-                .colorize_errors(io::stdout().is_tty())
-                .build_ast(src)
-            {
-                Ok(ast) => {
-                    if ast.warnings.is_empty() {
-                        state.files_passed.fetch_add(1, Ordering::Relaxed);
-                        lines.push(format!(
-                            "[ {} ] {}",
-                            Green.paint("PASS").bold(),
-                            file_path.display()
-                        ));
-                    } else {
-                        state
-                            .warnings
-                            .fetch_add(ast.warnings.len(), Ordering::Relaxed);
-                        lines.push(format!(
-                            "[ {} ] {}",
-                            Yellow.paint("WARN").bold(),
-                            file_path.display()
-                        ));
-                        for warning in ast.warnings {
-                            lines.push(warning.to_string());
-                        }
-                    }
+            let max_severity =
+                diagnostics.iter().map(|diagnostic| diagnostic.severity).max();
+
+            match max_severity {
+                None => {
+                    state.files_passed.fetch_add(1, Ordering::Relaxed);
                 }
-                Err(err) => {
+                Some(Severity::Warning) => {
+                    state
+                        .warnings
+                        .fetch_add(diagnostics.len(), Ordering::Relaxed);
+                }
+                Some(Severity::Error) => {
                     state.errors.fetch_add(1, Ordering::Relaxed);
-                    lines.push(format!(
-                        "[ {} ] {}\n{}",
-                        Red.paint("FAIL").bold(),
-                        file_path.display(),
-                        err,
-                    ));
                 }
+            }
+
+            if let (Some(max_severity), Some(fail_on)) =
+                (max_severity, fail_on)
+            {
+                if max_severity >= fail_on {
+                    failed_files_ref.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            if output_format == "ndjson" {
+                for diagnostic in diagnostics {
+                    let line = json!({
+                        "file": file_path.display().to_string(),
+                        "severity": diagnostic.severity.as_str(),
+                        "code": diagnostic.code,
+                        "title": diagnostic.title(),
+                        "report": diagnostic.report,
+                    });
+                    output.send(Message::Info(line.to_string())).unwrap();
+                }
+                return;
+            }
+
+            let status = match max_severity {
+                None => Green.paint("PASS").bold(),
+                Some(Severity::Warning) => Yellow.paint("WARN").bold(),
+                Some(Severity::Error) => Red.paint("FAIL").bold(),
             };
 
+            let mut lines =
+                vec![format!("[ {} ] {}", status, file_path.display())];
+
+            for diagnostic in diagnostics {
+                lines.push(diagnostic.report);
+            }
+
             output.send(Message::Info(lines.join("\n"))).unwrap();
         },
     )
     .unwrap();
 
+    let failed_files = failed_files.load(Ordering::Relaxed);
+
+    if failed_files > 0 {
+        bail!("{} file(s) didn't pass the check", failed_files);
+    }
+
     Ok(())
 }
 
+/// Compiles a source file and returns the errors and warnings found.
+///
+/// Compilation stops at the first error, so there's at most one error
+/// in the result. Warnings produced before the error are included too,
+/// except the ones with a code in `ignored`. Errors are never ignored.
+fn check_source(
+    src: SourceCode,
+    globals: &[(String, Value)],
+    required_meta: &[String],
+    ignored: &[&str],
+    colorize: bool,
+) -> Vec<Diagnostic> {
+    let mut compiler = Compiler::new().colorize_errors(colorize);
+
+    compiler.unused_patterns(UnusedPatternAction::Warn);
+
+    // Variables were already validated, defining them can't fail.
+    for (ident, value) in globals {
+        compiler.define_global(ident, json_to_variable(value)).unwrap();
+    }
+
+    if !required_meta.is_empty() {
+        compiler.add_lint_pass(RequiredMeta(required_meta.to_vec()));
+    }
+
+    let error = compiler.add_source(src).err();
+    let rules = compiler.build();
+
+    let mut diagnostics: Vec<Diagnostic> = rules
+        .warnings()
+        .iter()
+        .map(|warning| Diagnostic {
+            severity: Severity::Warning,
+            // Warnings produced by lint passes use the name of the pass as
+            // their code.
+            code: match warning {
+                Warning::LintWarning { lint_pass, .. } => lint_pass.clone(),
+                warning => warning.code().to_string(),
+            },
+            report: warning.to_string(),
+        })
+        .filter(|diagnostic| !ignored.contains(&diagnostic.code.as_str()))
+        .collect();
+
+    if let Some(error) = error {
+        let code = match &error {
+            yara_x::Error::ParseError(err) => err.info().code().to_string(),
+            yara_x::Error::CompileError(err) => match err.info() {
+                CompileErrorInfo::LintError { lint_pass, .. } => {
+                    lint_pass.clone()
+                }
+                info => info.code().to_string(),
+            },
+            yara_x::Error::TemplateError(_) => "template_error".to_string(),
+        };
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code,
+            report: error.to_string(),
+        });
+    }
+
+    diagnostics
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// An error or warning found while checking a source file.
+struct Diagnostic {
+    severity: Severity,
+    /// Code that identifies the type of error or warning (e.g:
+    /// `unused_pattern`, `slow_pattern`). For diagnostics produced by lint
+    /// passes this is the name of the pass (e.g: `required_meta`).
+    code: String,
+    /// Detailed report, as produced by the compiler.
+    report: String,
+}
+
+impl Diagnostic {
+    /// Returns the first line in the report without the severity prefix.
+    fn title(&self) -> &str {
+        let first_line = self.report.lines().next().unwrap_or_default();
+        first_line
+            .strip_prefix(self.severity.as_str())
+            .and_then(|title| title.strip_prefix(": "))
+            .unwrap_or(first_line)
+    }
+}
+
+/// Lint pass that warns about rules without some required metadata.
+struct RequiredMeta(Vec<String>);
+
+impl LintPass for RequiredMeta {
+    fn name(&self) -> &str {
+        "required_meta"
+    }
+
+    fn check_rule(&self, rule: &ast::Rule, report: &mut LintReport) {
+        for required in self.0.iter() {
+            let found = rule
+                .meta
+                .iter()
+                .flatten()
+                .any(|meta| meta.identifier.name == required.as_str());
+
+            if !found {
+                report.warning(
+                    format!(
+                        "rule `{}` doesn't have the required metadata `{}`",
+                        rule.identifier.name, required
+                    ),
+                    format!("metadata `{}` is missing", required),
+                    rule.identifier.span,
+                );
+            }
+        }
+    }
+}
+
+/// Parses a variable definition like `VAR=VALUE`.
+///
+/// `true` and `false` are booleans, values that can be parsed as an integer
+/// or float are numbers, and anything else is a string. Values enclosed in
+/// double quotes are always strings.
+fn parse_define(arg: &str) -> Result<(String, Value), String> {
+    let (ident, value) = match arg.split_once('=') {
+        Some((ident, value)) if !ident.is_empty() => (ident, value),
+        _ => return Err("must be in the form VAR=VALUE".to_string()),
+    };
+
+    let value = if let Some(s) =
+        value.strip_prefix('"').and_then(|value| value.strip_suffix('"'))
+    {
+        Value::from(s)
+    } else if let Ok(b) = value.parse::<bool>() {
+        Value::from(b)
+    } else if let Ok(i) = value.parse::<i64>() {
+        Value::from(i)
+    } else if let Ok(f) = value.parse::<f64>() {
+        Value::from(f)
+    } else {
+        Value::from(value)
+    };
+
+    Ok((ident.to_string(), value))
+}
+
 struct CheckState {
     files_passed: AtomicUsize,
     warnings: AtomicUsize,
@@ -180,3 +471,130 @@ impl Component for CheckState {
         Ok(Lines(vec![res]))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use yara_x_parser::SourceCode;
+
+    use super::{check_source, parse_define, Diagnostic, Severity};
+
+    fn check(
+        src: &str,
+        globals: &[(String, Value)],
+        required_meta: &[&str],
+        ignored: &[&str],
+    ) -> Vec<Diagnostic> {
+        let required_meta: Vec<String> =
+            required_meta.iter().map(|m| m.to_string()).collect();
+
+        check_source(
+            SourceCode::from(src),
+            globals,
+            required_meta.as_slice(),
+            ignored,
+            false,
+        )
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(Severity, &str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.code.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lint_pass_codes() {
+        let diagnostics = check(
+            r#"rule test { strings: $a = "foo" condition: true }"#,
+            &[],
+            &["author"],
+            &[],
+        );
+
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                (Severity::Warning, "required_meta"),
+                (Severity::Warning, "unused_pattern")
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore() {
+        // Warnings can be ignored.
+        let diagnostics = check(
+            r#"rule test { strings: $a = "foo" condition: true }"#,
+            &[],
+            &["author"],
+            &["required_meta"],
+        );
+
+        assert_eq!(
+            codes(&diagnostics),
+            [(Severity::Warning, "unused_pattern")]
+        );
+
+        // Errors can't be ignored.
+        let diagnostics = check(
+            r#"rule test { condition: foo }"#,
+            &[],
+            &[],
+            &["unknown_identifier"],
+        );
+
+        assert_eq!(
+            codes(&diagnostics),
+            [(Severity::Error, "unknown_identifier")]
+        );
+    }
+
+    #[test]
+    fn globals() {
+        let src = r#"rule test { condition: foo == "bar" and size > 1 }"#;
+
+        assert_eq!(
+            codes(&check(src, &[], &[], &[])),
+            [(Severity::Error, "unknown_identifier")]
+        );
+
+        let globals = vec![
+            parse_define("foo=bar").unwrap(),
+            parse_define("size=10").unwrap(),
+        ];
+
+        assert!(check(src, globals.as_slice(), &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn define() {
+        assert_eq!(
+            parse_define("a=true").unwrap(),
+            ("a".to_string(), Value::from(true))
+        );
+        assert_eq!(
+            parse_define("a=-10").unwrap(),
+            ("a".to_string(), Value::from(-10))
+        );
+        assert_eq!(
+            parse_define("a=0.5").unwrap(),
+            ("a".to_string(), Value::from(0.5))
+        );
+        assert_eq!(
+            parse_define("a=foo=bar").unwrap(),
+            ("a".to_string(), Value::from("foo=bar"))
+        );
+        assert_eq!(
+            parse_define(r#"a="10""#).unwrap(),
+            ("a".to_string(), Value::from("10"))
+        );
+        assert_eq!(
+            parse_define("a=").unwrap(),
+            ("a".to_string(), Value::from(""))
+        );
+        assert!(parse_define("a").is_err());
+        assert!(parse_define("=1").is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 
 use anyhow::Context;
 use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
 
-use crate::commands::compile_rules;
+use crate::commands::{compile_rules, read_globals, read_key};
+use crate::help;
 
 pub fn compile() -> Command {
     super::command("compile")
@@ -16,26 +18,72 @@ pub fn compile() -> Command {
                 .action(ArgAction::Append),
         )
         .arg(
-            arg!(<OUTPUT_PATH>)
+            arg!(-o --"output" <OUTPUT_PATH>)
                 .help("Path to file with compiled results")
+                .default_value("output.yrx")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"sign-key" <KEY_FILE>)
+                .help("Sign the compiled rules with the ed25519 secret key in KEY_FILE")
+                .long_help(help::SIGN_KEY_LONG_HELP)
                 .value_parser(value_parser!(PathBuf)),
         )
         .arg(
             arg!(--"path-as-namespace")
                 .help("Use file path as rule namespace"),
         )
+        .arg(
+            arg!(--"profile")
+                .help("Print the rules that take most of the compile time or are likely to be slow"),
+        )
+        .arg(
+            arg!(--"cache-dir" <DIR>)
+                .help("Reuse compiled rules stored in the given directory if the rules didn't change")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"define-file" <VARS_FILE>)
+                .help("Define external variables with the values in a JSON file")
+                .long_help(help::DEFINE_FILE_LONG_HELP)
+                .value_parser(value_parser!(PathBuf)),
+        )
 }
 
 pub fn exec_compile(args: &ArgMatches) -> anyhow::Result<()> {
     let rules_path = args.get_many::<PathBuf>("RULES_PATH").unwrap();
-    let output_path = args.get_one::<PathBuf>("OUTPUT_PATH").unwrap();
+    let output_path = args.get_one::<PathBuf>("output").unwrap();
+    let sign_key = args
+        .get_one::<PathBuf>("sign-key")
+        .map(|path| read_key::<32>(path))
+        .transpose()?;
     let path_as_namespace = args.get_flag("path-as-namespace");
+    let profile = args.get_flag("profile");
+    let cache_dir = args.get_one::<PathBuf>("cache-dir");
+    let globals = args
+        .get_one::<PathBuf>("define-file")
+        .map(|path| read_globals(path))
+        .transpose()?
+        .unwrap_or_default();
 
-    let rules = compile_rules(rules_path, path_as_namespace)?;
+    let rules = compile_rules(
+        rules_path,
+        path_as_namespace,
+        profile,
+        cache_dir,
+        globals.as_slice(),
+    )?;
 
     let output_file = File::create(output_path).with_context(|| {
         format!("can not write `{}`", output_path.display())
     })?;
 
-    Ok(rules.serialize_into(&output_file)?)
+    match sign_key {
+        Some(secret_key) => {
+            let bytes = rules.serialize_signed(&secret_key)?;
+            BufWriter::new(output_file).write_all(bytes.as_slice())?;
+            Ok(())
+        }
+        None => Ok(rules.serialize_into(BufWriter::new(output_file))?),
+    }
 }
@@ -1,10 +1,11 @@
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 
 use anyhow::Context;
 use clap::{arg, value_parser, ArgMatches, Command};
 
-use yara_x::Compiler;
+use yara_x::{AtomDetails, Compiler};
 use yara_x_parser::{Parser, SourceCode};
 
 pub fn ast() -> Command {
@@ -29,6 +30,16 @@ pub fn wasm() -> Command {
         )
 }
 
+pub fn debug_compile() -> Command {
+    super::command("compile")
+        .about("Print the condition IR, the atoms and the Pike VM code for each rule in a YARA source file")
+        .arg(
+            arg!(<RULES_PATH>)
+                .help("Path to YARA source file")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
 pub fn debug() -> Command {
     super::command("debug")
         .about("Debug utilities")
@@ -36,12 +47,14 @@ pub fn debug() -> Command {
         .hide(true)
         .subcommand(ast())
         .subcommand(wasm())
+        .subcommand(debug_compile())
 }
 
 pub fn exec_debug(args: &ArgMatches) -> anyhow::Result<()> {
     match args.subcommand() {
         Some(("ast", args)) => exec_ast(args),
         Some(("wasm", args)) => exec_wasm(args),
+        Some(("compile", args)) => exec_debug_compile(args),
         _ => unreachable!(),
     }
 }
@@ -83,3 +96,73 @@ fn exec_wasm(args: &ArgMatches) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn exec_debug_compile(args: &ArgMatches) -> anyhow::Result<()> {
+    let rules_path = args.get_one::<PathBuf>("RULES_PATH").unwrap();
+
+    let src = fs::read(rules_path)
+        .with_context(|| format!("can not read `{}`", rules_path.display()))?;
+
+    let src = SourceCode::from(src.as_slice())
+        .with_origin(rules_path.as_os_str().to_str().unwrap());
+
+    let mut compiler = Compiler::new().colorize_errors(true);
+
+    // The IR for each rule's condition is printed while the rules are
+    // being compiled.
+    compiler.set_ir_writer(io::stdout());
+    compiler.add_source(src)?;
+
+    let rules = compiler.build();
+
+    for rule in rules.iter() {
+        println!("PATTERNS {}:{}", rule.namespace(), rule.name());
+        for pattern in rule.patterns() {
+            println!("  PATTERN {}", pattern.identifier());
+            let atoms: Vec<_> = pattern
+                .atom_details()
+                .map(|atom| {
+                    let code = atom_code(&atom);
+                    (atom, code)
+                })
+                .collect();
+            for (i, (atom, code)) in atoms.iter().enumerate() {
+                println!(
+                    "    ATOM \"{}\" quality: {}, backtrack: {}{}",
+                    atom.bytes().escape_ascii(),
+                    atom.quality(),
+                    atom.backtrack(),
+                    if atom.is_exact() { ", exact" } else { "" },
+                );
+                // Atoms extracted from the same portion of a pattern share
+                // the same code, which is printed only once after the last
+                // of them.
+                if atoms.get(i + 1).map_or(false, |(_, next)| next == code) {
+                    continue;
+                }
+                let (fwd_code, bck_code) = code;
+                if let Some(code) = fwd_code {
+                    println!("      FORWARD CODE");
+                    print_code(code.as_str());
+                }
+                if let Some(code) = bck_code {
+                    println!("      BACKWARD CODE");
+                    print_code(code.as_str());
+                }
+            }
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn atom_code(atom: &AtomDetails) -> (Option<String>, Option<String>) {
+    (atom.fwd_code(), atom.bck_code())
+}
+
+fn print_code(code: &str) {
+    for line in code.lines() {
+        println!("        {line}");
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use protobuf::reflect::{ReflectFieldRef, ReflectValueRef};
+use protobuf::MessageDyn;
+use serde_json::{json, Map, Value};
+use yansi::Paint;
+
+use yara_x::{Compiler, Scanner};
+
+use crate::help;
+
+pub fn dump() -> Command {
+    super::command("dump")
+        .about("Show the data produced by YARA modules for a file")
+        .arg(
+            arg!(<FILE>)
+                .help("Path to the file that will be passed to the modules")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-m --"module" <MODULE>)
+                .help("Show the data produced by the given module(s)")
+                .long_help(help::DUMP_MODULE_LONG_HELP)
+                .value_delimiter(',')
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(-o --"output-format" <FORMAT>)
+                .help("Output format")
+                .value_parser(["json", "yaml"])
+                .default_value("json"),
+        )
+}
+
+pub fn exec_dump(args: &ArgMatches) -> anyhow::Result<()> {
+    let file_path = args.get_one::<PathBuf>("FILE").unwrap();
+    let output_format =
+        args.get_one::<String>("output-format").unwrap().as_str();
+
+    let available_modules = yara_x::module_names();
+
+    let modules: Vec<&str> = match args.get_many::<String>("module") {
+        Some(modules) => modules.map(|m| m.as_str()).collect(),
+        // Modules used for testing YARA-X itself are not interesting for
+        // users, they are shown only when explicitly requested.
+        None => available_modules
+            .iter()
+            .copied()
+            .filter(|m| !m.starts_with("test_proto"))
+            .collect(),
+    };
+
+    for module in modules.iter() {
+        if !available_modules.contains(module) {
+            bail!(
+                "unknown module `{}`, available modules are: {}",
+                Paint::new(module).bold(),
+                available_modules.join(", ")
+            );
+        }
+    }
+
+    // Modules produce their data only when they are imported by some rule,
+    // so a rule that imports every requested module is compiled.
+    let mut src = String::new();
+
+    for module in modules.iter() {
+        src.push_str(format!("import \"{}\"\n", module).as_str());
+    }
+
+    src.push_str("rule dump { condition: false }\n");
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_source(src.as_str())?;
+
+    let rules = compiler.build();
+
+    let data = fs::read(file_path)
+        .with_context(|| format!("can not read `{}`", file_path.display()))?;
+
+    let mut scanner = Scanner::new(&rules)?;
+    let results = scanner.scan(data.as_slice())?;
+
+    let mut outputs = Map::new();
+
+    for (module, output) in results.module_outputs() {
+        let output = message_to_json(output);
+        // When modules were not explicitly requested, those that didn't
+        // recognize the file are omitted.
+        if args.contains_id("module") || !is_empty_value(&output) {
+            outputs.insert(module.to_string(), output);
+        }
+    }
+
+    let outputs = Value::Object(outputs);
+
+    match output_format {
+        "json" => println!("{}", serde_json::to_string_pretty(&outputs)?),
+        "yaml" => {
+            let mut output = String::new();
+            write_yaml(&mut output, &outputs, 0);
+            print!("{output}");
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Converts a protobuf message into a JSON object.
+///
+/// Fields that are not set are not included in the object. Enum fields
+/// are represented by the name of their value, and bytes fields by a
+/// string where non-printable characters are escaped.
+fn message_to_json(msg: &dyn MessageDyn) -> Value {
+    let mut result = Map::new();
+
+    for field in msg.descriptor_dyn().fields() {
+        let value = match field.get_reflect(msg) {
+            ReflectFieldRef::Optional(value) => match value.value() {
+                Some(value) => value_to_json(value),
+                None => continue,
+            },
+            ReflectFieldRef::Repeated(items) => {
+                if items.is_empty() {
+                    continue;
+                }
+                Value::Array(items.into_iter().map(value_to_json).collect())
+            }
+            ReflectFieldRef::Map(items) => {
+                if items.is_empty() {
+                    continue;
+                }
+                Value::Object(
+                    items
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let key = match value_to_json(key) {
+                                Value::String(s) => s,
+                                key => key.to_string(),
+                            };
+                            (key, value_to_json(value))
+                        })
+                        .collect(),
+                )
+            }
+        };
+        result.insert(field.name().to_string(), value);
+    }
+
+    Value::Object(result)
+}
+
+/// Returns true if the value is `false`, zero, an empty string, or an
+/// array or object where all values are empty too.
+///
+/// Most modules produce some data even for files they don't recognize,
+/// like `is_pdf: false`. These outputs are entirely made of empty values.
+fn is_empty_value(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Bool(b) => !b,
+        Value::Number(n) => n.as_f64() == Some(0.0),
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.iter().all(is_empty_value),
+        Value::Object(map) => map.values().all(is_empty_value),
+    }
+}
+
+fn value_to_json(value: ReflectValueRef) -> Value {
+    match value {
+        ReflectValueRef::U32(v) => json!(v),
+        ReflectValueRef::U64(v) => json!(v),
+        ReflectValueRef::I32(v) => json!(v),
+        ReflectValueRef::I64(v) => json!(v),
+        ReflectValueRef::F32(v) => json!(v),
+        ReflectValueRef::F64(v) => json!(v),
+        ReflectValueRef::Bool(v) => json!(v),
+        ReflectValueRef::String(v) => json!(v),
+        ReflectValueRef::Bytes(v) => json!(v.escape_ascii().to_string()),
+        ReflectValueRef::Enum(descriptor, v) => {
+            match descriptor.value_by_number(v) {
+                Some(value) => json!(value.name()),
+                None => json!(v),
+            }
+        }
+        ReflectValueRef::Message(msg) => message_to_json(&*msg),
+    }
+}
+
+/// Writes a JSON value into `output` in YAML format.
+///
+/// `indent` is the indentation level of the value, as a number of spaces.
+fn write_yaml(output: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if map.is_empty() => output.push_str("{}\n"),
+        Value::Array(items) if items.is_empty() => output.push_str("[]\n"),
+        Value::Object(map) => {
+            for (key, value) in map {
+                output
+                    .push_str(format!("{pad}{}:", yaml_string(key)).as_str());
+                write_yaml_item(output, value, indent);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                output.push_str(format!("{pad}-").as_str());
+                write_yaml_item(output, item, indent);
+            }
+        }
+        Value::String(s) => {
+            output.push_str(format!("{}\n", yaml_string(s)).as_str())
+        }
+        value => output.push_str(format!("{value}\n").as_str()),
+    }
+}
+
+/// Writes a value that follows a map key or an array dash.
+///
+/// Non-empty maps and arrays start in the next line, with one more level of
+/// indentation. Other values are written in the same line.
+fn write_yaml_item(output: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            output.push('\n');
+            write_yaml(output, value, indent + 2);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            output.push('\n');
+            write_yaml(output, value, indent + 2);
+        }
+        value => {
+            output.push(' ');
+            write_yaml(output, value, indent);
+        }
+    }
+}
+
+/// Returns a string as a YAML scalar, which is quoted only if necessary.
+fn yaml_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c))
+        && !s
+            .starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+        && !matches!(
+            s,
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off"
+        );
+
+    if plain {
+        s.to_string()
+    } else {
+        // JSON strings are valid YAML double-quoted scalars.
+        Value::String(s.to_string()).to_string()
+    }
+}
@@ -1,33 +1,272 @@
+use std::env;
 use std::fs;
-use std::fs::File;
-use std::io::{stdin, stdout};
-use std::path::PathBuf;
+use std::io::{stdin, stdout, Read, Write};
+use std::path::{Path, PathBuf};
 
+use anyhow::{bail, Context};
 use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
-use yara_x_fmt::Formatter;
+use serde::Deserialize;
+use yansi::Color::{Cyan, Green, Red};
+use yansi::Paint;
+use yara_x_fmt::{Formatter, NewlineStyle};
+
+use crate::help;
+
+/// Name of the configuration file for the formatter.
+const CONFIG_FILE: &str = ".yarafmt.toml";
 
 pub fn fmt() -> Command {
-    super::command("fmt").about("Format source files").arg(
-        arg!(<RULES_PATH>)
-            .help("Path to YARA source file or directory")
-            .value_parser(value_parser!(PathBuf))
-            .action(ArgAction::Append),
-    )
+    super::command("fmt")
+        .about("Format source files")
+        .long_about(help::FMT_LONG_HELP)
+        .arg(
+            arg!(<RULES_PATH>)
+                .help("Path to YARA source file or directory")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(-c --"check")
+                .help("Don't modify files, fail if some file is not formatted"),
+        )
+        .arg(
+            arg!(-d --"diff")
+                .help("Don't modify files, show the changes that formatting would make")
+                .conflicts_with("check"),
+        )
+        .arg(
+            arg!(--"config" <CONFIG_FILE>)
+                .help("Use the given configuration file")
+                .long_help(help::FMT_CONFIG_LONG_HELP)
+                .value_parser(value_parser!(PathBuf)),
+        )
 }
 
 pub fn exec_fmt(args: &ArgMatches) -> anyhow::Result<()> {
     let rules_path = args.get_many::<PathBuf>("RULES_PATH");
-    let formatter = Formatter::new();
+    let check = args.get_flag("check");
+    let diff = args.get_flag("diff");
+
+    let config_path = match args.get_one::<PathBuf>("config") {
+        Some(path) => Some(path.clone()),
+        None => find_config()?,
+    };
+
+    let formatter = match config_path {
+        Some(path) => load_config(&path)?,
+        None => Formatter::new(),
+    };
+
+    let mut inputs = Vec::new();
 
     if let Some(files) = rules_path {
         for file in files {
-            let input = fs::read(file.as_path())?;
-            let output = File::create(file.as_path())?;
-            formatter.format(input.as_slice(), output)?;
+            let input = fs::read(file.as_path()).with_context(|| {
+                format!("can not read `{}`", file.display())
+            })?;
+            inputs.push((Some(file), input));
         }
     } else {
-        formatter.format(stdin(), stdout())?;
+        let mut input = Vec::new();
+        stdin().read_to_end(&mut input)?;
+        inputs.push((None, input));
+    }
+
+    let mut unformatted = 0;
+
+    for (file, input) in inputs {
+        let mut output = Vec::new();
+        formatter.format(input.as_slice(), &mut output)?;
+
+        if check || diff {
+            if input == output {
+                continue;
+            }
+            unformatted += 1;
+            let name = file
+                .map(|file| file.display().to_string())
+                .unwrap_or_else(|| "<stdin>".to_string());
+            if diff {
+                print_diff(
+                    name.as_str(),
+                    String::from_utf8_lossy(&input).as_ref(),
+                    String::from_utf8_lossy(&output).as_ref(),
+                );
+            } else {
+                println!("{}", name);
+            }
+        } else if let Some(file) = file {
+            // Files that are already formatted are not written again, so
+            // that their modification time doesn't change.
+            if input != output {
+                fs::write(file, output).with_context(|| {
+                    format!("can not write `{}`", file.display())
+                })?;
+            }
+        } else {
+            stdout().write_all(output.as_slice())?;
+        }
+    }
+
+    if unformatted > 0 {
+        bail!("{} file(s) are not formatted", unformatted);
     }
 
     Ok(())
 }
+
+/// Formatter options read from a configuration file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    indent_spaces: Option<u8>,
+    align_metadata: Option<bool>,
+    align_patterns: Option<bool>,
+    newline_style: Option<NewlineStyleConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum NewlineStyleConfig {
+    Auto,
+    Unix,
+    Windows,
+    Native,
+}
+
+/// Looks for a configuration file in the current directory and its
+/// ancestors, returning the path of the first one found.
+fn find_config() -> anyhow::Result<Option<PathBuf>> {
+    Ok(env::current_dir()?
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file()))
+}
+
+/// Creates a formatter with the options in the given configuration file.
+fn load_config(path: &Path) -> anyhow::Result<Formatter> {
+    let config = fs::read_to_string(path)
+        .with_context(|| format!("can not read `{}`", path.display()))?;
+
+    let config: Config =
+        toml::from_str(config.as_str()).with_context(|| {
+            format!("invalid config file `{}`", path.display())
+        })?;
+
+    let mut formatter = Formatter::new();
+
+    if let Some(n) = config.indent_spaces {
+        formatter = formatter.indent_spaces(n);
+    }
+
+    if let Some(yes) = config.align_metadata {
+        formatter = formatter.align_metadata(yes);
+    }
+
+    if let Some(yes) = config.align_patterns {
+        formatter = formatter.align_patterns(yes);
+    }
+
+    if let Some(style) = config.newline_style {
+        formatter = formatter.newline_style(match style {
+            NewlineStyleConfig::Auto => NewlineStyle::Auto,
+            NewlineStyleConfig::Unix => NewlineStyle::Unix,
+            NewlineStyleConfig::Windows => NewlineStyle::Windows,
+            NewlineStyleConfig::Native => NewlineStyle::Native,
+        });
+    }
+
+    Ok(formatter)
+}
+
+/// Number of unchanged lines shown before and after each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Prints the differences between the original and the formatted code in
+/// unified diff format.
+///
+/// Lines are compared including their line terminators, so changes in the
+/// newline style are reported as changes too.
+fn print_diff(name: &str, original: &str, formatted: &str) {
+    let original: Vec<&str> = original.split_inclusive('\n').collect();
+    let formatted: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let lines = diff::slice(original.as_slice(), formatted.as_slice());
+
+    // Line numbers in the original and formatted code for each item in
+    // `lines`, starting at 1.
+    let mut line_numbers = Vec::with_capacity(lines.len());
+    let (mut left, mut right) = (1, 1);
+
+    for line in lines.iter() {
+        line_numbers.push((left, right));
+        match line {
+            diff::Result::Left(_) => left += 1,
+            diff::Result::Right(_) => right += 1,
+            diff::Result::Both(_, _) => {
+                left += 1;
+                right += 1;
+            }
+        }
+    }
+
+    // Group changed lines in hunks, each hunk is a range of indexes in
+    // `lines`. Changes that are close enough share the same hunk.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if matches!(line, diff::Result::Both(_, _)) {
+            continue;
+        }
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let end = (i + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    println!("{}", Paint::new(format!("--- {}", name)).bold());
+    println!("{}", Paint::new(format!("+++ {}", name)).bold());
+
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+
+        let left_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, diff::Result::Right(_)))
+            .count();
+
+        let right_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, diff::Result::Left(_)))
+            .count();
+
+        let (left_start, right_start) = line_numbers[start];
+
+        println!(
+            "{}",
+            Cyan.paint(format!(
+                "@@ -{},{} +{},{} @@",
+                left_start, left_len, right_start, right_len
+            ))
+        );
+
+        for line in hunk {
+            let (prefix, l) = match line {
+                diff::Result::Left(l) => ("-", l),
+                diff::Result::Right(r) => ("+", r),
+                diff::Result::Both(l, _) => (" ", l),
+            };
+            let text =
+                format!("{}{}", prefix, l.strip_suffix('\n').unwrap_or(l));
+            match line {
+                diff::Result::Left(_) => println!("{}", Red.paint(text)),
+                diff::Result::Right(_) => println!("{}", Green.paint(text)),
+                diff::Result::Both(_, _) => println!("{}", text),
+            }
+            if !l.ends_with('\n') {
+                println!("\\ No newline at end of file");
+            }
+        }
+    }
+}
@@ -1,24 +1,30 @@
 mod check;
 mod compile;
 mod debug;
+mod dump;
 mod fmt;
 mod scan;
 
 pub use check::*;
 pub use compile::*;
 pub use debug::*;
+pub use dump::*;
 pub use fmt::*;
 pub use scan::*;
 
 use std::fs;
 use std::io::stdout;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use clap::Command;
 use crossterm::tty::IsTty;
+use serde_json::Value;
 
-use yara_x::{Compiler, Rules};
+use yara_x::{
+    CacheKey, CompilationCache, Compiler, ProfilingData, Rules, Variable,
+};
 use yara_x_parser::SourceCode;
 
 use crate::walk::DirWalker;
@@ -37,17 +43,21 @@ pub fn command(name: &'static str) -> Command {
 pub fn compile_rules<'a, P>(
     paths: P,
     path_as_namespace: bool,
+    profile: bool,
+    cache_dir: Option<&PathBuf>,
+    globals: &[(String, Value)],
 ) -> Result<Rules, anyhow::Error>
 where
     P: Iterator<Item = &'a PathBuf>,
 {
-    let mut compiler: Compiler<'_> =
-        Compiler::new().colorize_errors(stdout().is_tty());
-
     let mut w = DirWalker::new();
 
     w.filter("**/*.yar").filter("**/*.yara");
 
+    // Source files are read before compiling any of them, as all of them
+    // are needed for computing the cache key.
+    let mut sources = Vec::new();
+
     for path in paths {
         w.walk(
             path,
@@ -56,15 +66,7 @@ where
                     format!("can not read `{}`", file_path.display())
                 })?;
 
-                let src = SourceCode::from(src.as_slice())
-                    .with_origin(file_path.as_os_str().to_str().unwrap());
-
-                if path_as_namespace {
-                    compiler
-                        .new_namespace(file_path.to_string_lossy().as_ref());
-                }
-
-                compiler.add_source(src)?;
+                sources.push((file_path.to_string_lossy().to_string(), src));
 
                 Ok(())
             },
@@ -72,11 +74,237 @@ where
         );
     }
 
+    let namespace = |path: &str| {
+        if path_as_namespace {
+            path.to_string()
+        } else {
+            "default".to_string()
+        }
+    };
+
+    let cache = cache_dir
+        .map(|dir| {
+            CompilationCache::new(dir).with_context(|| {
+                format!("can not create cache in `{}`", dir.display())
+            })
+        })
+        .transpose()?;
+
+    let mut key = CacheKey::new();
+
+    for (path, src) in sources.iter() {
+        key.add_source(
+            namespace(path).as_str(),
+            &SourceCode::from(src.as_slice()).with_origin(path),
+        );
+    }
+
+    for (ident, value) in globals {
+        key.add_setting(
+            format!("global:{}", ident).as_str(),
+            value.to_string().as_str(),
+        );
+    }
+
+    // When profiling the rules must be compiled, even if they are cached.
+    if let Some(cache) = cache.as_ref().filter(|_| !profile) {
+        if let Some(rules) = cache.get(&key) {
+            return Ok(rules);
+        }
+    }
+
+    let mut compiler: Compiler<'_> =
+        Compiler::new().colorize_errors(stdout().is_tty());
+
+    for (ident, value) in globals {
+        compiler.define_global(ident, json_to_variable(value))?;
+    }
+
+    // Even if the whole set of rules is not in the cache, the code for the
+    // regexps in source files that didn't change can be reused. When
+    // profiling the regexps are compiled from scratch, so that the time
+    // reported for each rule is accurate.
+    if let Some(cache) = cache.as_ref().filter(|_| !profile) {
+        compiler.set_cache(cache.clone());
+    }
+
+    let mut failed = false;
+
+    for (path, src) in sources.iter() {
+        let src = SourceCode::from(src.as_slice()).with_origin(path);
+
+        if path_as_namespace {
+            compiler.new_namespace(namespace(path).as_str());
+        }
+
+        if let Err(err) = compiler.add_source(src) {
+            eprintln!("{}", err);
+            failed = true;
+        }
+    }
+
+    if profile {
+        print_profiling_data(compiler.profiling_data());
+    }
+
     let rules = compiler.build();
 
     for warning in rules.warnings() {
         eprintln!("{}", warning);
     }
 
+    // Rules are not cached if some source file had errors, otherwise the
+    // errors wouldn't be reported the next time.
+    if let Some(cache) = cache.filter(|_| !failed) {
+        cache.insert(&key, &rules)?;
+    }
+
     Ok(rules)
 }
+
+/// Reads the external variables defined in a JSON file.
+///
+/// The file must contain a JSON object where keys are the identifiers of
+/// the variables, and values are their initial values. Values must be
+/// booleans, integers, floats or strings, which determine the type of the
+/// variable.
+pub fn read_globals(path: &Path) -> anyhow::Result<Vec<(String, Value)>> {
+    let content = fs::read(path)
+        .with_context(|| format!("can not read `{}`", path.display()))?;
+
+    let globals: Value = serde_json::from_slice(content.as_slice())
+        .with_context(|| format!("invalid JSON in `{}`", path.display()))?;
+
+    let globals = match globals {
+        Value::Object(globals) => globals,
+        _ => bail!("`{}` must contain a JSON object", path.display()),
+    };
+
+    let mut result = Vec::with_capacity(globals.len());
+
+    for (ident, value) in globals {
+        if !matches!(
+            value,
+            Value::Bool(_) | Value::Number(_) | Value::String(_)
+        ) {
+            bail!(
+                "invalid value for `{}` in `{}`, expecting a boolean, number or string",
+                ident,
+                path.display()
+            );
+        }
+        result.push((ident, value));
+    }
+
+    Ok(result)
+}
+
+/// Converts a value returned by [`read_globals`] into a [`Variable`].
+///
+/// Numbers that can be represented as a 64-bit signed integer are
+/// converted to integer variables, the remaining ones are converted to
+/// float variables.
+pub fn json_to_variable(value: &Value) -> Variable {
+    match value {
+        Value::Bool(b) => Variable::from(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Variable::from(i),
+            None => Variable::from(n.as_f64().unwrap()),
+        },
+        Value::String(s) => Variable::from(s.as_str()),
+        _ => unreachable!(),
+    }
+}
+
+/// Reads an ed25519 key from a file.
+///
+/// The file can contain either the raw `N` bytes of the key, or the key
+/// encoded as `N*2` hexadecimal digits, optionally surrounded by spaces and
+/// newlines.
+pub fn read_key<const N: usize>(path: &Path) -> anyhow::Result<[u8; N]> {
+    let content = fs::read(path)
+        .with_context(|| format!("can not read `{}`", path.display()))?;
+
+    if let Ok(key) = <[u8; N]>::try_from(content.as_slice()) {
+        return Ok(key);
+    }
+
+    let hex = String::from_utf8_lossy(content.as_slice());
+    let hex = hex.trim();
+
+    if hex.len() == N * 2 && hex.is_ascii() {
+        let mut key = [0_u8; N];
+        let decoded =
+            key.iter_mut().enumerate().all(
+                |(i, byte)| match u8::from_str_radix(
+                    &hex[i * 2..i * 2 + 2],
+                    16,
+                ) {
+                    Ok(b) => {
+                        *byte = b;
+                        true
+                    }
+                    Err(_) => false,
+                },
+            );
+        if decoded {
+            return Ok(key);
+        }
+    }
+
+    bail!(
+        "`{}` must contain a key of {} bytes, either raw or hex-encoded",
+        path.display(),
+        N
+    )
+}
+
+/// Prints the rules that account for most of the compile time and most of
+/// the estimated scan cost.
+fn print_profiling_data(profiling_data: &ProfilingData) {
+    let compile_time: Duration =
+        profiling_data.sources().iter().map(|s| s.compile_time()).sum();
+
+    eprintln!(
+        "compiled {} rules from {} sources in {:?}",
+        profiling_data.rules().len(),
+        profiling_data.sources().len(),
+        compile_time
+    );
+
+    let top_rules = profiling_data.top_rules_by_compile_time(80.0);
+
+    eprintln!(
+        "\nthese {} rules account for 80% of compile time:",
+        top_rules.len()
+    );
+
+    for rule in top_rules {
+        eprintln!(
+            "  {:>12?}  {}:{}",
+            rule.compile_time(),
+            rule.namespace(),
+            rule.name()
+        );
+    }
+
+    let top_rules = profiling_data.top_rules_by_cost(80.0);
+
+    eprintln!(
+        "\nthese {} rules account for 80% of likely scan cost:",
+        top_rules.len()
+    );
+
+    for rule in top_rules {
+        let cost = rule.cost();
+        eprintln!(
+            "  {:>12}  {}:{} (atoms: {}, worst atom quality: {}{})",
+            cost.score(),
+            rule.namespace(),
+            rule.name(),
+            cost.num_atoms(),
+            cost.min_atom_quality().map_or("-".to_string(), |q| q.to_string()),
+            if cost.has_unbounded_loops() { ", unbounded loops" } else { "" }
+        );
+    }
+}
@@ -1,27 +1,46 @@
 use std::cmp::min;
+use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::Instant;
 
-use anyhow::bail;
+use anyhow::{bail, Context};
 use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use serde_json::{json, Map, Value};
 use superconsole::style::Stylize;
 use superconsole::{Component, Line, Lines, Span};
-use yansi::Color::{Cyan, Red};
+use yansi::Color::{Cyan, Red, Yellow};
 use yansi::Paint;
-use yara_x::{Rule, Rules, Scanner};
+use yara_x::{
+    ConsoleLevel, ConsoleMessage, MetaValue, Rule, Rules, ScanError,
+    ScanResults, Scanner, SkipReason, SkippedRegion,
+};
 
-use crate::commands::compile_rules;
+use crate::commands::{
+    compile_rules, json_to_variable, read_globals, read_key,
+};
 use crate::walk::Message;
 use crate::{help, walk};
 
 pub fn scan() -> Command {
     super::command("scan")
-        .about("Scan a file or directory")
+        .about("Scan a file, a directory or a running process")
+        .override_usage(
+            "yr scan [OPTIONS] <RULES_PATH>... <PATH>\n    \
+             yr scan [OPTIONS] <RULES_PATH>... --pid <PID>\n    \
+             yr scan [OPTIONS] <RULES_PATH>... --process-name <NAME>",
+        )
+        // RULES_PATH is not required because when scanning a process
+        // there's no PATH, and the last rules path is taken as PATH. See
+        // `exec_scan`.
+        .allow_missing_positional(true)
         .arg(
-            arg!(<RULES_PATH>)
+            arg!([RULES_PATH])
                 .help("Path to YARA source file")
                 .value_parser(value_parser!(PathBuf))
                 .action(ArgAction::Append),
@@ -31,6 +50,18 @@ pub fn scan() -> Command {
                 .help("Path to the file or directory that will be scanned")
                 .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            arg!(--"pid" <PID>)
+                .help("Scan the memory of the process with the given PID")
+                .long_help(help::PROCESS_LONG_HELP)
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            arg!(--"process-name" <NAME>)
+                .help("Scan the memory of the processes with the given name")
+                .long_help(help::PROCESS_LONG_HELP)
+                .conflicts_with("pid"),
+        )
         .arg(arg!(-e - -"print-namespace").help("Print rule namespace"))
         .arg(
             arg!(-s - -"print-strings").help(
@@ -43,6 +74,13 @@ pub fn scan() -> Command {
                 .value_parser(value_parser!(usize)),
         )
         .arg(arg!(-n - -"negate").help("Print non-satisfied rules only"))
+        .arg(
+            arg!(-o --"output-format" <FORMAT>)
+                .help("Output format for results")
+                .long_help(help::OUTPUT_FORMAT_LONG_HELP)
+                .value_parser(["text", "json", "ndjson", "sarif"])
+                .default_value("text"),
+        )
         .arg(
             arg!(--"path-as-namespace")
                 .help("Use file path as rule namespace"),
@@ -52,10 +90,58 @@ pub fn scan() -> Command {
                 .help("Tells that RULES_PATH is a file with compiled rules")
                 .long_help(help::COMPILED_RULES_HELP),
         )
+        .arg(
+            arg!(--"verify-key" <KEY_FILE>)
+                .help("Verify the signature of compiled rules with the ed25519 public key in KEY_FILE")
+                .long_help(help::VERIFY_KEY_LONG_HELP)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"cache-dir" <DIR>)
+                .help("Reuse compiled rules stored in the given directory if the rules didn't change")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-r --"recursive" [MAX_DEPTH])
+                .help("Walk directories recursively up to a given depth")
+                .long_help(help::RECURSIVE_LONG_HELP)
+                .require_equals(true)
+                .value_parser(value_parser!(u16)),
+        )
+        .arg(
+            arg!(-i --"include" <PATTERN>)
+                .help("Scan only the files that match the given pattern")
+                .long_help(help::INCLUDE_LONG_HELP)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--"exclude" <PATTERN>)
+                .help("Don't scan files that match the given pattern")
+                .long_help(help::EXCLUDE_LONG_HELP)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(-L --"follow-symlinks")
+                .help("Follow symbolic links while walking directories"),
+        )
         .arg(
             arg!(-z --"skip-larger" <FILE_SIZE>)
                 .help("Skip files larger than the given size")
-                .value_parser(value_parser!(u64)),
+                .long_help(help::SKIP_LARGER_LONG_HELP)
+                .value_parser(parse_size),
+        )
+        .arg(
+            arg!(--"define-file" <VARS_FILE>)
+                .help("Define external variables with the values in a JSON file")
+                .long_help(help::DEFINE_FILE_LONG_HELP)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(-x --"module-data" <MODULE_FILE>)
+                .help("Pass FILE's content as extra data to MODULE (MODULE=FILE)")
+                .long_help(help::MODULE_DATA_LONG_HELP)
+                .value_parser(parse_module_data)
+                .action(ArgAction::Append),
         )
         .arg(
             arg!(-p --"threads" <NUM_THREADS>)
@@ -67,35 +153,188 @@ pub fn scan() -> Command {
 }
 
 pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
-    let mut rules_path = args.get_many::<PathBuf>("RULES_PATH").unwrap();
+    let mut rules_paths: Vec<&PathBuf> =
+        args.get_many::<PathBuf>("RULES_PATH").into_iter().flatten().collect();
     let path = args.get_one::<PathBuf>("PATH").unwrap();
+    let pid = args.get_one::<u32>("pid");
+    let process_name = args.get_one::<String>("process-name");
+
+    // PATH can't be used while scanning a process, in that case the last
+    // positional argument, which is taken as PATH, is actually a rules
+    // path.
+    let path = if pid.is_some() || process_name.is_some() {
+        rules_paths.push(path);
+        None
+    } else {
+        Some(path)
+    };
+
+    if rules_paths.is_empty() {
+        bail!("the following required argument was not provided: <PATH>");
+    }
+
+    let mut rules_path = rules_paths.into_iter();
     let compiled_rules = args.get_flag("compiled-rules");
     let num_threads = args.get_one::<u8>("threads");
     let print_namespace = args.get_flag("print-namespace");
     let print_strings = args.get_flag("print-strings");
     let print_strings_limit = args.get_one::<usize>("print-strings-limit");
     let path_as_namespace = args.get_flag("path-as-namespace");
+    let cache_dir = args.get_one::<PathBuf>("cache-dir");
     let skip_larger = args.get_one::<u64>("skip-larger");
     let negate = args.get_flag("negate");
+    let print_strings_limit = if print_strings || print_strings_limit.is_some()
+    {
+        Some(*print_strings_limit.unwrap_or(&120))
+    } else {
+        None
+    };
+    let max_depth = args.get_one::<u16>("recursive");
+    let follow_symlinks = args.get_flag("follow-symlinks");
+    let include = args.get_many::<String>("include");
+    let exclude = args.get_many::<String>("exclude");
+    let output_format =
+        args.get_one::<String>("output-format").unwrap().as_str();
+    let globals = args
+        .get_one::<PathBuf>("define-file")
+        .map(|path| read_globals(path))
+        .transpose()?
+        .unwrap_or_default();
+    let module_data = args
+        .get_many::<(String, PathBuf)>("module-data")
+        .into_iter()
+        .flatten()
+        .map(|(module, path)| {
+            if !yara_x::module_names().contains(&module.as_str()) {
+                bail!(
+                    "unknown module `{}` in '{}'",
+                    Paint::new(module).bold(),
+                    Paint::new("--module-data").bold()
+                );
+            }
+            let data = fs::read(path).with_context(|| {
+                format!("can't read module data from {}", path.display())
+            })?;
+            Ok((module.as_str(), data))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
-    let rules = if compiled_rules {
-        if rules_path.len() > 1 {
+    let verify_key = args
+        .get_one::<PathBuf>("verify-key")
+        .map(|path| read_key::<32>(path))
+        .transpose()?;
+
+    if rules_path.len() > 1 && (compiled_rules || verify_key.is_some()) {
+        bail!(
+            "can't use '{}' with more than one RULES_PATH",
+            Paint::new(if compiled_rules {
+                "--compiled-rules"
+            } else {
+                "--verify-key"
+            })
+            .bold()
+        );
+    }
+
+    // When there's a single RULES_PATH pointing to a file, the file may
+    // contain either source code or compiled rules, which are detected
+    // by looking at the file's header.
+    let compiled_data = match rules_path.clone().next() {
+        Some(path) if rules_path.len() == 1 && path.is_file() => {
+            let data = read_compiled_rules(path)?;
+            if compiled_rules && data.is_none() {
+                bail!("`{}` doesn't contain compiled rules", path.display());
+            }
+            data
+        }
+        _ => None,
+    };
+
+    let deserialized = compiled_data.is_some();
+
+    let rules = match (compiled_data, verify_key) {
+        (Some(data), Some(public_key)) => {
+            Rules::deserialize_verified(data.as_slice(), &public_key)?
+        }
+        (Some(data), None) => Rules::deserialize(data.as_slice())?,
+        (None, Some(_)) => {
+            bail!(
+                "'{}' can be used only with compiled rules",
+                Paint::new("--verify-key").bold()
+            );
+        }
+        (None, None) if compiled_rules => {
             bail!(
-                "can't use '{}' with more than one RULES_PATH",
-                Paint::new("--compiled-rules").bold()
+                "`{}` doesn't contain compiled rules",
+                rules_path.next().unwrap().display()
             );
         }
+        (None, None) => compile_rules(
+            rules_path,
+            path_as_namespace,
+            false,
+            cache_dir,
+            globals.as_slice(),
+        )?,
+    };
+
+    // Deserialized rules may have been produced by a version of YARA-X that
+    // is not compatible with this one. Also, compiled rules don't know the
+    // values in the define file, and they may not even declare the
+    // variables. So, a scanner is created first for reporting any error
+    // before the scan starts. After this, creating the scanners used by
+    // each thread and setting the values in them can't fail.
+    if deserialized || !globals.is_empty() {
+        let mut scanner = Scanner::new(&rules)?;
+        for (ident, value) in globals.iter() {
+            scanner.set_global(ident, json_to_variable(value))?;
+        }
+    }
 
-        // TODO: implement Rules::deserialize_from reader
-        let mut file = File::open(rules_path.next().unwrap())?;
-        let mut data = Vec::new();
-        File::read_to_end(&mut file, &mut data)?;
-        Rules::deserialize(data.as_slice())?
+    // The processes to scan, when a PID or a process name is given instead
+    // of a path.
+    let pids = if let Some(pid) = pid {
+        Some(vec![*pid])
+    } else if let Some(name) = process_name {
+        let pids = find_processes(name)?;
+        if pids.is_empty() {
+            bail!("no process named `{}`", name);
+        }
+        Some(pids)
     } else {
-        compile_rules(rules_path, path_as_namespace)?
+        None
     };
 
+    if let Some(pids) = pids {
+        let mut scanner = Scanner::new(&rules)?;
+        for (ident, value) in globals.iter() {
+            scanner.set_global(ident, json_to_variable(value))?;
+        }
+        for (module, data) in module_data.iter() {
+            scanner.set_module_data(module, data);
+        }
+        scanner.console_log(|msg| eprintln!("{}", console_line(msg)));
+        return scan_processes(
+            &rules,
+            &mut scanner,
+            &pids,
+            output_format,
+            negate,
+            print_namespace,
+            print_strings_limit,
+        );
+    }
+
     let rules_ref = &rules;
+    let globals_ref = &globals;
+
+    // Results for the `json` and `sarif` output formats, which are printed
+    // after all files are scanned.
+    let results = Mutex::new(Vec::new());
+    let results_ref = &results;
+
+    // PATH is present, as the processes were scanned otherwise.
+    let path = path.unwrap();
 
     let mut w = walk::ParDirWalker::new();
 
@@ -107,21 +346,67 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
         w.metadata_filter(|metadata| metadata.len() <= *max_file_size);
     }
 
+    if let Some(max_depth) = max_depth {
+        w.max_depth(*max_depth as usize);
+    }
+
+    w.follow_symlinks(follow_symlinks);
+
+    for pattern in include.into_iter().flatten() {
+        w.include(pattern);
+    }
+
+    for pattern in exclude.into_iter().flatten() {
+        w.exclude(pattern);
+    }
+
     w.walk(
         path,
         ScanState::new(),
-        || Scanner::new(rules_ref),
+        || {
+            let mut scanner = Scanner::new(rules_ref).unwrap();
+            for (ident, value) in globals_ref.iter() {
+                scanner.set_global(ident, json_to_variable(value)).unwrap();
+            }
+            for (module, data) in module_data.iter() {
+                scanner.set_module_data(module, data);
+            }
+            scanner
+        },
         |file_path, state, output, scanner| {
+            let console_output = output.clone();
+
+            scanner.console_log(move |msg| {
+                let line = console_line(msg);
+                // With structured output formats stdout contains only the
+                // results, so console messages go to stderr.
+                let msg = if output_format == "text" {
+                    Message::Info(line)
+                } else {
+                    Message::Error(line)
+                };
+                console_output.send(msg).unwrap();
+            });
+
             let scan_results = scanner.scan_file(&file_path);
 
             if let Err(err) = scan_results {
-                output
-                    .send(Message::Error(format!(
-                        "{} {}",
+                state.num_errors.fetch_add(1, Ordering::Relaxed);
+                // Errors while opening or mapping the file already contain
+                // the file's path.
+                let msg = match err {
+                    ScanError::OpenError { .. }
+                    | ScanError::MapError { .. } => {
+                        format!("{} {}", Red.paint("error:").bold(), err)
+                    }
+                    _ => format!(
+                        "{} can not scan `{}`: {}",
                         Red.paint("error:").bold(),
+                        file_path.display(),
                         err
-                    )))
-                    .unwrap();
+                    ),
+                };
+                output.send(Message::Error(msg)).unwrap();
                 return;
             }
 
@@ -139,59 +424,585 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
                 state.num_matching_files.fetch_add(1, Ordering::Relaxed);
             }
 
-            for matching_rule in matching_rules {
-                let line = if print_namespace {
-                    format!(
-                        "{}:{} {}",
-                        Cyan.paint(matching_rule.namespace()).bold(),
-                        Cyan.paint(matching_rule.name()).bold(),
-                        file_path.display(),
-                    )
-                } else {
-                    format!(
-                        "{} {}",
-                        Cyan.paint(matching_rule.name()).bold(),
-                        file_path.display()
-                    )
-                };
-
-                output.send(Message::Info(line)).unwrap();
+            let target = file_path.display().to_string();
 
-                if print_strings || print_strings_limit.is_some() {
-                    let limit = print_strings_limit.unwrap_or(&120);
-                    for p in matching_rule.patterns() {
-                        for m in p.matches() {
-                            let mut msg = format!(
-                                "{:#x}:{}:{}: ",
-                                m.range.start,
-                                m.range.len(),
-                                p.identifier(),
-                            );
-
-                            for b in &m.data[..min(m.data.len(), *limit)] {
-                                for c in b.escape_ascii() {
-                                    msg.push_str(
-                                        format!("{}", c as char).as_str(),
-                                    );
-                                }
-                            }
-
-                            output.send(Message::Info(msg)).unwrap();
-                        }
-                    }
+            match output_format {
+                "json" | "sarif" if !matching_rules.is_empty() => {
+                    results_ref.lock().unwrap().push(file_results(
+                        &target,
+                        &scan_results,
+                        &matching_rules,
+                        print_strings_limit,
+                    ));
+                    return;
                 }
+                "ndjson" if !matching_rules.is_empty() => {
+                    let results = file_results(
+                        &target,
+                        &scan_results,
+                        &matching_rules,
+                        print_strings_limit,
+                    );
+                    output.send(Message::Info(results.to_string())).unwrap();
+                    return;
+                }
+                "text" => {}
+                _ => return,
+            }
+
+            for line in text_results(
+                &target,
+                &scan_results,
+                &matching_rules,
+                print_namespace,
+                print_strings_limit,
+            ) {
+                output.send(Message::Info(line)).unwrap();
             }
         },
     )
     .unwrap();
 
+    match output_format {
+        "json" => {
+            let results = results.into_inner().unwrap();
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        }
+        "sarif" => {
+            let results = results.into_inner().unwrap();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&sarif(
+                    &rules, &results, negate
+                ))?
+            );
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Scans the memory of the given processes, printing the results as they
+/// are produced, except for the `json` and `sarif` formats, which are
+/// printed at the end. Processes are identified as `pid:<PID>` in the
+/// results.
+fn scan_processes(
+    rules: &Rules,
+    scanner: &mut Scanner,
+    pids: &[u32],
+    output_format: &str,
+    negate: bool,
+    print_namespace: bool,
+    print_strings_limit: Option<usize>,
+) -> anyhow::Result<()> {
+    let mut results = Vec::new();
+    let mut num_errors = 0;
+    let mut permission_denied = false;
+
+    for pid in pids {
+        let scan_results = match scanner.scan_process(*pid) {
+            Ok(scan_results) => scan_results,
+            Err(err) => {
+                num_errors += 1;
+                eprintln!("{} {}", Red.paint("error:").bold(), err);
+                if let ScanError::ProcessError { source, .. } = &err {
+                    permission_denied |=
+                        source.kind() == io::ErrorKind::PermissionDenied;
+                }
+                continue;
+            }
+        };
+
+        let matching_rules: Vec<Rule> = if negate {
+            scan_results.non_matching_rules().collect()
+        } else {
+            scan_results.matching_rules().collect()
+        };
+
+        let target = format!("pid:{}", pid);
+
+        if let Some(warning) =
+            skipped_regions_warning(&target, scan_results.skipped_regions())
+        {
+            eprintln!("{} {}", Yellow.paint("warning:").bold(), warning);
+        }
+
+        match output_format {
+            "json" | "sarif" if !matching_rules.is_empty() => {
+                results.push(file_results(
+                    &target,
+                    &scan_results,
+                    &matching_rules,
+                    print_strings_limit,
+                ));
+            }
+            "ndjson" if !matching_rules.is_empty() => {
+                let results = file_results(
+                    &target,
+                    &scan_results,
+                    &matching_rules,
+                    print_strings_limit,
+                );
+                println!("{}", results);
+            }
+            "text" => {
+                for line in text_results(
+                    &target,
+                    &scan_results,
+                    &matching_rules,
+                    print_namespace,
+                    print_strings_limit,
+                ) {
+                    println!("{}", line);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match output_format {
+        "json" => println!("{}", serde_json::to_string_pretty(&results)?),
+        "sarif" => println!(
+            "{}",
+            serde_json::to_string_pretty(&sarif(rules, &results, negate))?
+        ),
+        _ => {}
+    }
+
+    if permission_denied {
+        eprintln!(
+            "{} reading the memory of other processes requires running as \
+             root, or the CAP_SYS_PTRACE capability. Processes of the same \
+             user may be restricted by /proc/sys/kernel/yama/ptrace_scope",
+            Yellow.paint("note:").bold(),
+        );
+    }
+
+    if num_errors > 0 {
+        bail!("{} process(es) could not be scanned", num_errors);
+    }
+
     Ok(())
 }
 
+/// Returns a message that summarizes the memory regions of a process that
+/// were not scanned, or `None` if all of them were scanned.
+fn skipped_regions_warning(
+    target: &str,
+    skipped: &[SkippedRegion],
+) -> Option<String> {
+    if skipped.is_empty() {
+        return None;
+    }
+
+    let count = |reason| skipped.iter().filter(|r| r.reason == reason).count();
+
+    let reasons = [
+        (SkipReason::LimitReached, "exceeded the maximum memory per process"),
+        (SkipReason::TooLarge, "were too large"),
+        (SkipReason::Unreadable, "could not be read"),
+    ]
+    .into_iter()
+    .filter_map(|(reason, description)| match count(reason) {
+        0 => None,
+        n => Some(format!("{} {}", n, description)),
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    Some(format!(
+        "{} memory region(s) of {} were not scanned ({} bytes): {}",
+        skipped.len(),
+        target,
+        skipped.iter().map(|r| r.size).sum::<u64>(),
+        reasons
+    ))
+}
+
+/// Returns the PIDs of the processes with the given name, which is
+/// compared with the name of the process' executable, and with the first
+/// argument in its command line, without the directory.
+#[cfg(target_os = "linux")]
+fn find_processes(name: &str) -> anyhow::Result<Vec<u32>> {
+    let mut pids = Vec::new();
+
+    for entry in fs::read_dir("/proc").context("can not list processes")? {
+        let entry = entry?;
+
+        let pid = match entry.file_name().to_str().map(str::parse::<u32>) {
+            Some(Ok(pid)) if pid != std::process::id() => pid,
+            _ => continue,
+        };
+
+        // Processes may exit while they are being listed.
+        let comm =
+            fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+        let cmdline =
+            fs::read(entry.path().join("cmdline")).unwrap_or_default();
+
+        let argv0 = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+        let argv0 = String::from_utf8_lossy(argv0);
+        let argv0 = argv0.rsplit('/').next().unwrap_or_default();
+
+        if comm.trim_end() == name || argv0 == name {
+            pids.push(pid);
+        }
+    }
+
+    pids.sort_unstable();
+
+    Ok(pids)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_processes(_name: &str) -> anyhow::Result<Vec<u32>> {
+    bail!("scanning processes is supported only in Linux")
+}
+
+/// Returns a message emitted by the `console` module as a line of text.
+fn console_line(msg: &ConsoleMessage) -> String {
+    let level = match msg.level() {
+        ConsoleLevel::Info => Cyan.paint("info:").bold(),
+        ConsoleLevel::Warning => Yellow.paint("warning:").bold(),
+        ConsoleLevel::Error => Red.paint("error:").bold(),
+    };
+    match msg.rule_name() {
+        Some(rule_name) => format!("{} {}: {}", level, rule_name, msg.text()),
+        None => format!("{} {}", level, msg.text()),
+    }
+}
+
+/// Returns the lines printed by the `text` output format for a scanned
+/// file or process.
+///
+/// Each rule is followed by its matches when `data_limit` is not `None`.
+/// Matches in processes have the address instead of the offset, and they
+/// are followed by the permissions and the path of the memory region
+/// where they are.
+fn text_results(
+    target: &str,
+    scan_results: &ScanResults,
+    rules: &[Rule],
+    print_namespace: bool,
+    data_limit: Option<usize>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for rule in rules {
+        if print_namespace {
+            lines.push(format!(
+                "{}:{} {}",
+                Cyan.paint(rule.namespace()).bold(),
+                Cyan.paint(rule.name()).bold(),
+                target,
+            ));
+        } else {
+            lines.push(format!(
+                "{} {}",
+                Cyan.paint(rule.name()).bold(),
+                target
+            ));
+        }
+
+        let limit = match data_limit {
+            Some(limit) => limit,
+            None => continue,
+        };
+
+        for p in rule.patterns() {
+            for m in p.matches() {
+                let data = escape(&m.data[..min(m.data.len(), limit)]);
+                let line = match scan_results.memory_region(m.range.start) {
+                    Some(region) => format!(
+                        "{:#x}:{}:{}: {} ({} {})",
+                        region.address_of(m.range.start),
+                        m.range.len(),
+                        p.identifier(),
+                        data,
+                        region.permissions,
+                        region.path.as_deref().unwrap_or("[anonymous]"),
+                    ),
+                    None => format!(
+                        "{:#x}:{}:{}: {}",
+                        m.range.start,
+                        m.range.len(),
+                        p.identifier(),
+                        data,
+                    ),
+                };
+                lines.push(line);
+            }
+        }
+    }
+
+    lines
+}
+
+/// Returns the content of the file at `path` if it contains compiled rules,
+/// or `None` if it doesn't.
+fn read_compiled_rules(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("can not read `{}`", path.display()))?;
+
+    // Read only the header first, so that source files are not read twice.
+    let mut header = Vec::new();
+    (&mut file).take(16).read_to_end(&mut header)?;
+
+    if !Rules::is_serialized(header.as_slice()) {
+        return Ok(None);
+    }
+
+    let mut data = header;
+    file.read_to_end(&mut data)?;
+
+    Ok(Some(data))
+}
+
+/// Escapes non-printable characters in matched data.
+fn escape(data: &[u8]) -> String {
+    data.escape_ascii().to_string()
+}
+
+/// Returns the results for a scanned file or process in the form used by
+/// the `json` and `ndjson` output formats.
+///
+/// The data that matched each pattern is included only when `data_limit`
+/// is not `None`, and it is limited to that number of bytes. Matches in
+/// processes have the address where they are, and the memory region that
+/// contains them.
+fn file_results(
+    target: &str,
+    scan_results: &ScanResults,
+    rules: &[Rule],
+    data_limit: Option<usize>,
+) -> Value {
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|rule| {
+            let meta: Map<String, Value> = rule
+                .metadata()
+                .map(|(ident, value)| {
+                    let value = match value {
+                        MetaValue::Bool(b) => json!(b),
+                        MetaValue::Integer(i) => json!(i),
+                        MetaValue::Float(f) => json!(f),
+                        MetaValue::String(s) => json!(s),
+                    };
+                    (ident.to_string(), value)
+                })
+                .collect();
+
+            let patterns: Vec<Value> = rule
+                .patterns()
+                .filter_map(|pattern| {
+                    let matches: Vec<Value> = pattern
+                        .matches()
+                        .map(|m| {
+                            let mut result = json!({
+                                "offset": m.range.start,
+                                "length": m.range.len(),
+                            });
+                            if let Some(xor_key) = m.xor_key {
+                                result["xor_key"] = json!(xor_key);
+                            }
+                            if let Some(region) =
+                                scan_results.memory_region(m.range.start)
+                            {
+                                result["address"] =
+                                    json!(region.address_of(m.range.start));
+                                result["region"] = json!({
+                                    "address": region.address,
+                                    "size": region.size,
+                                    "permissions": region.permissions,
+                                    "path": region.path,
+                                });
+                            }
+                            if let Some(limit) = data_limit {
+                                let data = &m.data[..min(m.data.len(), limit)];
+                                result["data"] = json!(escape(data));
+                                result["hex"] = json!(data
+                                    .iter()
+                                    .map(|b| format!("{:02x}", b))
+                                    .collect::<String>());
+                            }
+                            result
+                        })
+                        .collect();
+
+                    if matches.is_empty() {
+                        None
+                    } else {
+                        Some(json!({
+                            "identifier": pattern.identifier(),
+                            "matches": matches,
+                        }))
+                    }
+                })
+                .collect();
+
+            json!({
+                "identifier": rule.name(),
+                "namespace": rule.namespace(),
+                "tags": rule.tags().collect::<Vec<_>>(),
+                "meta": meta,
+                "patterns": patterns,
+            })
+        })
+        .collect();
+
+    json!({
+        "path": target,
+        "rules": rules,
+    })
+}
+
+/// Converts the results produced by [`file_results`] into a SARIF log.
+///
+/// Each rule is described in the tool's driver, and there's a result for
+/// every rule that matched a file, with a location for each match.
+fn sarif(rules: &Rules, results: &[Value], negate: bool) -> Value {
+    let rule_id = |namespace: &str, name: &str| format!("{namespace}:{name}");
+
+    let descriptors: Vec<Value> = rules
+        .iter()
+        .filter(|rule| !rule.is_private())
+        .map(|rule| {
+            json!({
+                "id": rule_id(rule.namespace(), rule.name()),
+                "name": rule.name(),
+                "properties": {
+                    "tags": rule.tags().collect::<Vec<_>>(),
+                },
+            })
+        })
+        .collect();
+
+    // Index of each rule in `descriptors`, by rule ID.
+    let rule_indexes: HashMap<&str, usize> = descriptors
+        .iter()
+        .enumerate()
+        .filter_map(|(index, descriptor)| {
+            descriptor["id"].as_str().map(|id| (id, index))
+        })
+        .collect();
+
+    let mut sarif_results = Vec::new();
+
+    for file in results {
+        let uri = &file["path"];
+        for rule in file["rules"].as_array().into_iter().flatten() {
+            let name = rule["identifier"].as_str().unwrap_or_default();
+            let id =
+                rule_id(rule["namespace"].as_str().unwrap_or_default(), name);
+
+            let mut locations: Vec<Value> = rule["patterns"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|pattern| {
+                    pattern["matches"].as_array().into_iter().flatten()
+                })
+                .map(|m| {
+                    json!({
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": {
+                                "byteOffset": m["offset"],
+                                "byteLength": m["length"],
+                            },
+                        },
+                    })
+                })
+                .collect();
+
+            // Rules that matched without any pattern match still point to
+            // the file.
+            if locations.is_empty() {
+                locations.push(json!({
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                    },
+                }));
+            }
+
+            let text = if negate {
+                format!("rule `{name}` did not match")
+            } else {
+                format!("rule `{name}` matched")
+            };
+
+            let mut result = json!({
+                "ruleId": id,
+                "message": { "text": text },
+                "locations": locations,
+                "properties": { "meta": rule["meta"] },
+            });
+
+            if let Some(index) = rule_indexes.get(id.as_str()) {
+                result["ruleIndex"] = json!(index);
+            }
+
+            sarif_results.push(result);
+        }
+    }
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "yara-x",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/VirusTotal/yara-x",
+                    "rules": descriptors,
+                },
+            },
+            "results": sarif_results,
+        }],
+    })
+}
+
+/// Parses a `--module-data` argument in the form `MODULE=FILE`.
+fn parse_module_data(arg: &str) -> Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((module, path)) if !module.is_empty() && !path.is_empty() => {
+            Ok((module.to_string(), PathBuf::from(path)))
+        }
+        _ => Err("must be in the form MODULE=FILE".to_string()),
+    }
+}
+
+/// Parses a file size like `100`, `10KB`, `5MB` or `1GB`.
+///
+/// Units are case-insensitive, and they are powers of 1024. The `B` at the
+/// end of the unit is optional (e.g: `10K` is the same as `10KB`).
+fn parse_size(arg: &str) -> Result<u64, String> {
+    let arg = arg.trim();
+    let digits = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(digits);
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("unknown unit `{}`", unit.trim())),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| "invalid size".to_string())
+}
+
 struct ScanState {
     start: Instant,
     num_scanned_files: AtomicUsize,
     num_matching_files: AtomicUsize,
+    num_errors: AtomicUsize,
 }
 
 impl ScanState {
@@ -200,6 +1011,7 @@ impl ScanState {
             start: Instant::now(),
             num_scanned_files: AtomicUsize::new(0),
             num_matching_files: AtomicUsize::new(0),
+            num_errors: AtomicUsize::new(0),
         }
     }
 }
@@ -238,7 +1050,17 @@ impl Component for ScanState {
                 } else {
                     Span::new_styled(matched.green().bold())?
                 };
-                Line::from_iter([matched, Span::new_styled(scanned.bold())?])
+                let mut line = Line::from_iter([
+                    matched,
+                    Span::new_styled(scanned.bold())?,
+                ]);
+                let num_errors = self.num_errors.load(Ordering::Relaxed);
+                if num_errors > 0 {
+                    line.push(Span::new_styled(
+                        format!(", {} error(s).", num_errors).red().bold(),
+                    )?);
+                }
+                line
             }
         };
         Ok(Lines(vec![res]))
@@ -1,7 +1,49 @@
-pub const CHECK_LONG_HELP: &str = r#"Check if YARA source files are syntactically correct
+pub const CHECK_LONG_HELP: &str = r#"Check if YARA source files are correct
+
+Each file is parsed and compiled, reporting syntax and semantic errors, as well
+as warnings about potential issues like slow patterns or patterns that are not
+used in the condition. With `--required-meta` rules that don't have some
+metadata are reported too.
 
 If <PATH> is a directory, all files with extensions `yar` and `yara` will be
-checked. The `--filter` option allows changing this behavior."#;
+checked. The `--filter` option allows changing this behavior.
+
+The command exits with a non-zero status if some file has errors. This can be
+changed with `--fail-on`."#;
+
+pub const CHECK_FAIL_ON_LONG_HELP: &str = r#"Minimum severity that makes the command fail
+
+With `error` (the default) the command fails only if some file has errors, with
+`warning` it also fails if some file has warnings. With `never` the command
+doesn't fail regardless of the errors and warnings found."#;
+
+pub const CHECK_IGNORE_LONG_HELP: &str = r#"Ignore warnings with the given code
+
+Each error and warning has a code that identifies its type, like `unused_pattern`
+or `slow_pattern`. Warnings produced by `--required-meta` have the code
+`required_meta`. Codes are included in the output when using `--output-format
+ndjson`. This option can be used multiple times.
+
+Only warnings can be ignored, files with errors always fail the check.
+
+Examples:
+
+--ignore=unused_pattern
+--ignore=slow_pattern --ignore=invariant_boolean_expression"#;
+
+pub const CHECK_DEFINE_LONG_HELP: &str = r#"Define an external variable (VAR=VALUE)
+
+Rules that use external variables can't be compiled unless the variables are
+defined. The type of the variable is determined by its value: `true` and `false`
+are booleans, integer and float numbers are integers and floats respectively,
+anything else is a string. Values enclosed in double quotes are always strings.
+This option can be used multiple times, and takes precedence over the values
+in `--define-file`.
+
+Examples:
+
+--define=is_executable=true
+--define=max_size=1024 --define=file_type=pe"#;
 
 pub const THREADS_LONG_HELP: &str = r#"Use the specified number of threads
 
@@ -38,4 +80,175 @@ The absense of this options is equivalent to using this:
 pub const COMPILED_RULES_HELP: &str = r#"Indicates that <RULES_PATH> is a file containing compiled rules
 
 YARA rules can be compiled with the `yr compile` command. The file produced by
-this command can be passed later to `yr scan` by using this flag."#;
+this command can be passed later to `yr scan`. Compiled rules are detected
+automatically, this flag only makes `yr scan` fail if <RULES_PATH> doesn't
+contain compiled rules."#;
+
+pub const SIGN_KEY_LONG_HELP: &str = r#"Sign the compiled rules with the ed25519 secret key in KEY_FILE
+
+KEY_FILE must contain the 32 bytes of the secret key, either raw or encoded as
+64 hexadecimal digits. The signature is embedded in the output file, and can be
+verified with the corresponding public key by using `yr scan --verify-key`."#;
+
+pub const VERIFY_KEY_LONG_HELP: &str = r#"Verify the signature of compiled rules with the ed25519 public key in KEY_FILE
+
+KEY_FILE must contain the 32 bytes of the public key, either raw or encoded as
+64 hexadecimal digits. The scan is aborted if the rules are not signed, or if
+they were not signed with the corresponding secret key."#;
+
+pub const MODULE_DATA_LONG_HELP: &str = r#"Pass FILE's content as extra data to MODULE
+
+The argument must be in the form MODULE=FILE. Some modules don't extract their
+data from the scanned file, but from some external source. For instance, the
+`cuckoo` module expects the JSON report produced by the Cuckoo sandbox:
+
+--module-data=cuckoo=report.json
+
+This option can be used more than once for passing data to multiple modules."#;
+
+pub const DEFINE_FILE_LONG_HELP: &str = r#"Define external variables with the values in a JSON file
+
+The file must contain a JSON object where each key is the name of a variable,
+and its value is the variable's value. Values can be booleans, integers, floats
+or strings, and the type of each variable is determined by its value. For
+example:
+
+{
+  "is_executable": true,
+  "file_type": "pe",
+  "max_size": 1024,
+  "threshold": 0.75
+}
+
+When scanning compiled rules, the variables must have been defined while
+compiling the rules, and their types must match the ones in the file."#;
+
+pub const DUMP_MODULE_LONG_HELP: &str = r#"Show the data produced by the given module(s)
+
+Multiple modules can be separated by commas, or this option can be used more
+than once:
+
+--module=zip,pdf
+--module=zip --module=pdf
+
+When this option is not used, only the modules that recognize the file are
+shown. Modules that don't produce any data for the file, or whose data only
+says that the file is not recognized (e.g: `is_pdf: false`), are omitted."#;
+
+pub const OUTPUT_FORMAT_LONG_HELP: &str = r#"Output format for results
+
+Available formats are:
+
+text    human-readable text, one line per matching rule (default).
+
+json    a JSON array with an object per matching file, printed when all files
+        have been scanned. Each object has the file's path and the rules that
+        matched, with their namespace, tags, metadata and pattern matches.
+
+ndjson  the same objects produced by `json`, one per line, printed as soon as
+        each file is scanned.
+
+sarif   a SARIF 2.1.0 log, with a result per matching rule and file.
+
+The data that matched each pattern is included in `json` and `ndjson` only
+when `--print-strings` or `--print-strings-limit` are used."#;
+
+pub const FMT_LONG_HELP: &str = r#"Format source files
+
+By default files are modified in place. With `--check` files are not modified,
+the names of the files that are not correctly formatted are printed instead,
+and the command fails if there's any. `--diff` is similar, but it prints the
+changes that formatting would make.
+
+The formatter options are read from a `.yarafmt.toml` file located in the
+current directory or any of its ancestors. See `--config` for details."#;
+
+pub const FMT_CONFIG_LONG_HELP: &str = r#"Use the given configuration file
+
+When this option is not used, the configuration is read from a `.yarafmt.toml`
+file located in the current directory or any of its ancestors. If no such file
+exists, the default options are used.
+
+Example configuration file with the default options:
+
+# Number of spaces for each indentation level.
+indent_spaces = 2
+# Align the equal signs in metadata definitions.
+align_metadata = false
+# Align the equal signs in pattern definitions.
+align_patterns = true
+# Newline style, one of "auto", "unix", "windows" or "native". With "auto"
+# the style is determined by the first newline in the original file.
+newline_style = "unix""#;
+
+pub const RECURSIVE_LONG_HELP: &str = r#"Walk directories recursively up to a given depth
+
+This is ignored if <PATH> is not a directory. Directories are always walked
+recursively, by default with no depth limit. This option limits the depth, for
+instance, with --recursive=0 only the files directly contained in <PATH> are
+scanned, but subdirectories are not traversed. When <MAX_DEPTH> is omitted the
+depth is not limited, which is the same as not using this option."#;
+
+pub const INCLUDE_LONG_HELP: &str = r#"Scan only the files that match the given pattern
+
+This is ignored if <PATH> is not a directory. Patterns are relative to <PATH>
+and can contain the following wildcards:
+
+?      matches any single character.
+
+*      matches any sequence of characters, except the path separator.
+
+**     matches any sequence of characters, including the path separator.
+
+[...]  matches any character inside the brackets. Can also specify ranges of
+       characters (e.g. [0-9], [a-z])
+
+[!...] is the negation of [...]
+
+This option can be used more than once with different patterns. In such cases
+files matching any of the patterns will be scanned.
+
+Examples:
+
+--include=*.exe
+--include=**/*.{exe,dll}"#;
+
+pub const EXCLUDE_LONG_HELP: &str = r#"Don't scan files that match the given pattern
+
+This is ignored if <PATH> is not a directory. Patterns have the same syntax
+described in `--include`, and files that match any of them are not scanned
+even if they match some `--include` pattern. This option can be used more than
+once.
+
+Examples:
+
+--exclude=*.log
+--exclude=**/.git/**"#;
+
+pub const PROCESS_LONG_HELP: &str = r#"Scan the memory of running processes instead of a file
+
+With --pid the process with the given PID is scanned, and with --process-name
+all the processes whose executable has the given name are scanned. <PATH> must
+not be used together with these options.
+
+Examples:
+
+yr scan rules.yar --pid 1234
+yr scan rules.yar --process-name firefox
+
+The readable memory regions of each process are scanned as a whole. Matches
+are reported with the address where they are within the process, and the
+permissions and the path of the region that contains them. In the results,
+processes are identified as `pid:<PID>`.
+
+At most 2GB are read from each process. The regions that exceed that limit,
+and the ones that are too large or can't be read, are not scanned, and a
+warning tells how many of them were skipped.
+
+This is supported only in Linux. Reading the memory of processes that belong
+to other users requires running as root, or the CAP_SYS_PTRACE capability."#;
+
+pub const SKIP_LARGER_LONG_HELP: &str = r#"Skip files larger than the given size
+
+The size is a number of bytes, optionally followed by one of the units KB, MB
+or GB (e.g. 512KB, 10MB, 1GB). Units are powers of 1024."#;
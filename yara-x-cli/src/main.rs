@@ -42,6 +42,7 @@ fn main() -> anyhow::Result<()> {
             commands::compile(),
             commands::check(),
             commands::debug(),
+            commands::dump(),
             commands::fmt(),
         ])
         .get_matches_from(wild::args());
@@ -57,6 +58,7 @@ fn main() -> anyhow::Result<()> {
     let result = match args.subcommand() {
         Some(("debug", args)) => commands::exec_debug(args),
         Some(("check", args)) => commands::exec_check(args),
+        Some(("dump", args)) => commands::exec_dump(args),
         Some(("fmt", args)) => commands::exec_fmt(args),
         Some(("scan", args)) => commands::exec_scan(args),
         Some(("compile", args)) => commands::exec_compile(args),
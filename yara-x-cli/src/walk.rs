@@ -32,13 +32,21 @@ use yansi::Color::Red;
 /// ```
 pub struct DirWalker<'a> {
     filters: Vec<String>,
+    exclusions: Vec<String>,
     max_depth: Option<usize>,
+    follow_symlinks: bool,
     metadata_filter: Option<Box<dyn Fn(Metadata) -> bool + Send + 'a>>,
 }
 
 impl<'a> DirWalker<'a> {
     pub fn new() -> Self {
-        Self { filters: Vec::new(), max_depth: None, metadata_filter: None }
+        Self {
+            filters: Vec::new(),
+            exclusions: Vec::new(),
+            max_depth: None,
+            follow_symlinks: false,
+            metadata_filter: None,
+        }
     }
 
     /// Adds a glob pattern that controls which files will be processed.
@@ -71,6 +79,39 @@ impl<'a> DirWalker<'a> {
         self
     }
 
+    /// Adds a glob pattern that controls which files will be processed,
+    /// relative to the walked directory.
+    ///
+    /// This is like [`DirWalker::filter`], but the pattern is anchored to
+    /// the walked directory. For instance, `*.txt` matches only the files
+    /// directly contained in the walked directory, while with
+    /// [`DirWalker::filter`] it matches files at any depth.
+    pub fn include(&mut self, pattern: &str) -> &mut Self {
+        self.filters.push(anchored(pattern));
+        self
+    }
+
+    /// Adds a glob pattern for files that won't be processed.
+    ///
+    /// Files with a path that matches any of the exclusion patterns are
+    /// not processed, even if they match some filter. Patterns have the
+    /// same syntax described in [`DirWalker::filter`], and they are
+    /// anchored to the walked directory like in [`DirWalker::include`].
+    pub fn exclude(&mut self, pattern: &str) -> &mut Self {
+        self.exclusions.push(anchored(pattern));
+        self
+    }
+
+    /// Specifies whether symbolic links must be followed.
+    ///
+    /// When symbolic links are followed, they are processed as if they were
+    /// the files or directories they point to. By default symbolic links
+    /// are ignored.
+    pub fn follow_symlinks(&mut self, yes: bool) -> &mut Self {
+        self.follow_symlinks = yes;
+        self
+    }
+
     /// Sets a filter based in file metadata.
     ///
     /// The specidifed function receives tha file metadata associated with a
@@ -130,16 +171,21 @@ impl<'a> DirWalker<'a> {
             }
         };
 
-        let mut builder = if self.filters.is_empty() {
-            globwalk::GlobWalkerBuilder::from_patterns(path, &["**"])
+        let mut patterns = if self.filters.is_empty() {
+            vec!["**".to_string()]
         } else {
-            globwalk::GlobWalkerBuilder::from_patterns(
-                path,
-                self.filters.iter().as_ref(),
-            )
+            self.filters.clone()
         };
 
-        builder = builder.file_type(FileType::FILE);
+        // Patterns starting with `!` exclude the files that match them.
+        for exclusion in self.exclusions.iter() {
+            patterns.push(format!("!{}", exclusion));
+        }
+
+        let mut builder =
+            globwalk::GlobWalkerBuilder::from_patterns(path, &patterns)
+                .file_type(FileType::FILE)
+                .follow_links(self.follow_symlinks);
 
         if let Some(max_depth) = self.max_depth {
             builder = builder.max_depth(max_depth + 1);
@@ -172,6 +218,17 @@ impl<'a> DirWalker<'a> {
     }
 }
 
+/// Returns the given glob pattern with a leading `/`, which makes the pattern
+/// relative to the directory being walked. Otherwise, patterns without a path
+/// separator, like `*.txt`, match files at any depth.
+fn anchored(pattern: &str) -> String {
+    if pattern.starts_with('/') {
+        pattern.to_string()
+    } else {
+        format!("/{}", pattern)
+    }
+}
+
 /// Walks a path recursively and runs a given function for each file.
 ///
 /// <br>
@@ -213,7 +270,7 @@ impl<'a> DirWalker<'a> {
 ///     // function. A mutable reference to this value is passed as the
 ///     // last argument to the next function.
 ///     || {
-///         scanner.Scanner::new(rules)
+///         Scanner::new(rules).unwrap()
 ///     },
 ///     // This function is called for each file, `state` is a reference to
 ///     // the initial state (it's type is `&S`), `output` is of type
@@ -261,6 +318,31 @@ impl<'a> ParDirWalker<'a> {
         self
     }
 
+    /// Adds a glob pattern that controls which files will be processed,
+    /// relative to the walked directory.
+    ///
+    /// See [`DirWalker::include`] for details.
+    pub fn include(&mut self, pattern: &str) -> &mut Self {
+        self.walker.include(pattern);
+        self
+    }
+
+    /// Adds a glob pattern for files that won't be processed.
+    ///
+    /// See [`DirWalker::exclude`] for details.
+    pub fn exclude(&mut self, pattern: &str) -> &mut Self {
+        self.walker.exclude(pattern);
+        self
+    }
+
+    /// Specifies whether symbolic links must be followed.
+    ///
+    /// See [`DirWalker::follow_symlinks`] for details.
+    pub fn follow_symlinks(&mut self, yes: bool) -> &mut Self {
+        self.walker.follow_symlinks(yes);
+        self
+    }
+
     pub fn metadata_filter(
         &mut self,
         filter: impl Fn(Metadata) -> bool + Send + 'a,
@@ -408,3 +490,106 @@ impl Message {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::DirWalker;
+
+    /// Creates a directory with the following files, returning its path:
+    ///
+    /// ```text
+    /// a.txt
+    /// b.bin
+    /// sub/c.txt
+    /// sub/deep/d.txt
+    /// ```
+    fn create_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "yr-walk-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        fs::create_dir_all(root.join("sub/deep")).unwrap();
+
+        for file in ["a.txt", "b.bin", "sub/c.txt", "sub/deep/d.txt"] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+
+        root
+    }
+
+    /// Walks `root` and returns the paths of the files found, relative to
+    /// `root` and sorted.
+    fn walk(walker: &DirWalker, root: &Path) -> Vec<String> {
+        let mut files = Vec::new();
+
+        walker.walk(
+            root,
+            |path| {
+                files.push(
+                    path.strip_prefix(root.canonicalize().unwrap())
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                );
+                Ok(())
+            },
+            |err| panic!("{}", err),
+        );
+
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn filters() {
+        let root = create_tree("filters");
+
+        assert_eq!(
+            walk(&DirWalker::new(), &root),
+            ["a.txt", "b.bin", "sub/c.txt", "sub/deep/d.txt"]
+        );
+
+        // Filters without a path separator match files at any depth.
+        assert_eq!(
+            walk(DirWalker::new().filter("*.txt"), &root),
+            ["a.txt", "sub/c.txt", "sub/deep/d.txt"]
+        );
+
+        // Included patterns are anchored to the walked directory, and `*`
+        // doesn't cross path separators, so only the files directly
+        // contained in the walked directory match.
+        assert_eq!(walk(DirWalker::new().include("*.txt"), &root), ["a.txt"]);
+
+        assert_eq!(
+            walk(DirWalker::new().include("**/*.txt"), &root),
+            ["a.txt", "sub/c.txt", "sub/deep/d.txt"]
+        );
+
+        assert_eq!(
+            walk(DirWalker::new().include("sub/*.txt"), &root),
+            ["sub/c.txt"]
+        );
+
+        assert_eq!(
+            walk(DirWalker::new().filter("*.txt").exclude("*.txt"), &root),
+            ["sub/c.txt", "sub/deep/d.txt"]
+        );
+
+        assert_eq!(
+            walk(DirWalker::new().exclude("sub/**"), &root),
+            ["a.txt", "b.bin"]
+        );
+
+        assert_eq!(
+            walk(DirWalker::new().filter("**/*.txt").max_depth(1), &root),
+            ["a.txt", "sub/c.txt"]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -14,6 +14,7 @@ where
 {
     input: T,
     indent_level: i16,
+    indent_spaces: u8,
     output_buffer: VecDeque<Token<'a>>,
 }
 
@@ -21,8 +22,13 @@ impl<'a, T> AddIndentationSpaces<'a, T>
 where
     T: TokenStream<'a>,
 {
-    pub fn new(input: T) -> Self {
-        Self { input, indent_level: 0, output_buffer: VecDeque::new() }
+    pub fn new(input: T, indent_spaces: u8) -> Self {
+        Self {
+            input,
+            indent_level: 0,
+            indent_spaces,
+            output_buffer: VecDeque::new(),
+        }
     }
 }
 
@@ -51,9 +57,9 @@ where
                 Token::Newline => {
                     self.output_buffer.push_back(Token::Newline);
                     for _ in 0..self.indent_level {
-                        // Indent with two spaces per level
-                        self.output_buffer.push_back(Token::Whitespace);
-                        self.output_buffer.push_back(Token::Whitespace);
+                        for _ in 0..self.indent_spaces {
+                            self.output_buffer.push_back(Token::Whitespace);
+                        }
                     }
                     return self.output_buffer.pop_front();
                 }
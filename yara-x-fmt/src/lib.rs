@@ -56,8 +56,29 @@ pub enum Error {
     ParseError(#[from] yara_x_parser::Error),
 }
 
+/// Style of the newlines in the formatted code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NewlineStyle {
+    /// Use the same newlines as the input, which are determined by the
+    /// first newline found in it. If the input doesn't contain newlines,
+    /// `\n` is used.
+    Auto,
+    /// Use `\n` as newline.
+    #[default]
+    Unix,
+    /// Use `\r\n` as newline.
+    Windows,
+    /// Use `\r\n` in Windows and `\n` in all other platforms.
+    Native,
+}
+
 /// Formats YARA source code automatically.
-pub struct Formatter {}
+pub struct Formatter {
+    indent_spaces: u8,
+    align_metadata: bool,
+    align_patterns: bool,
+    newline_style: NewlineStyle,
+}
 
 impl Default for Formatter {
     fn default() -> Self {
@@ -69,7 +90,47 @@ impl Default for Formatter {
 impl Formatter {
     /// Creates a new formatter.
     pub fn new() -> Self {
-        Formatter {}
+        Formatter {
+            indent_spaces: 2,
+            align_metadata: false,
+            align_patterns: true,
+            newline_style: NewlineStyle::default(),
+        }
+    }
+
+    /// Sets the number of spaces used for each indentation level.
+    ///
+    /// The default value is 2.
+    pub fn indent_spaces(mut self, n: u8) -> Self {
+        self.indent_spaces = n;
+        self
+    }
+
+    /// Specifies whether the equal signs in metadata definitions must be
+    /// aligned.
+    ///
+    /// When enabled, each metadata definition is also placed in its own
+    /// line. The default value is `false`.
+    pub fn align_metadata(mut self, yes: bool) -> Self {
+        self.align_metadata = yes;
+        self
+    }
+
+    /// Specifies whether the equal signs in pattern definitions must be
+    /// aligned.
+    ///
+    /// The default value is `true`.
+    pub fn align_patterns(mut self, yes: bool) -> Self {
+        self.align_patterns = yes;
+        self
+    }
+
+    /// Sets the style of the newlines in the formatted code.
+    ///
+    /// The default value is [`NewlineStyle::Unix`].
+    pub fn newline_style(mut self, style: NewlineStyle) -> Self {
+        self.newline_style = style;
+        self
     }
 
     /// Reads YARA source code from `input` and write it into `output` after
@@ -94,21 +155,33 @@ impl Formatter {
         let cst =
             parser.build_cst(buf.as_str())?.comments(true).whitespaces(true);
 
+        let newline = match self.newline_style {
+            NewlineStyle::Unix => "\n",
+            NewlineStyle::Windows => "\r\n",
+            NewlineStyle::Native if cfg!(windows) => "\r\n",
+            NewlineStyle::Native => "\n",
+            NewlineStyle::Auto => match buf.find('\n') {
+                Some(i) if buf[..i].ends_with('\r') => "\r\n",
+                _ => "\n",
+            },
+        };
+
         // Generate a stream of tokens from the CST.
         let tokens = tokens::Tokens::new(cst);
 
-        Formatter::formatter(tokens)
-            .write_to(output)
+        self.formatter(tokens)
+            .write_to(output, newline)
             .map_err(Error::WriteError)
     }
 }
 
 // Private API for formatter.
 impl Formatter {
-    fn formatter<'a, I>(input: I) -> impl TokenStream<'a> + 'a
+    fn formatter<'a, I>(&self, input: I) -> impl TokenStream<'a> + 'a
     where
         I: TokenStream<'a> + 'a,
     {
+        let align_metadata = self.align_metadata;
         let tokens = comments::CommentProcessor::new(input);
 
         // Remove all whitespaces from the original source.
@@ -369,6 +442,18 @@ impl Formatter {
                 },
                 processor::actions::newline,
             )
+            // Add newline in front of metadata identifiers when metadata
+            // definitions are aligned, as alignment requires each
+            // definition to be in its own line.
+            .add_rule(
+                move |ctx| {
+                    align_metadata
+                        && ctx.in_rule(GrammarRule::meta_def, false)
+                        && ctx.token(1).is(*IDENTIFIER)
+                        && ctx.token(-1).is_not(*NEWLINE)
+                },
+                processor::actions::newline,
+            )
             // Add newline before the closing brace at the end of rule.
             .add_rule(
                 |ctx| {
@@ -411,9 +496,21 @@ impl Formatter {
         let tokens = Self::add_spacing(tokens);
 
         let tokens = Self::align_comments_in_hex_patterns(tokens);
-        let tokens = Self::align_patterns(tokens);
+        let tokens = Self::align_defs(
+            tokens,
+            GrammarRule::pattern_defs,
+            GrammarRule::pattern_def,
+            self.align_patterns,
+        );
+        let tokens = Self::align_defs(
+            tokens,
+            GrammarRule::meta_defs,
+            GrammarRule::meta_def,
+            self.align_metadata,
+        );
 
-        let tokens = indentation::AddIndentationSpaces::new(tokens);
+        let tokens =
+            indentation::AddIndentationSpaces::new(tokens, self.indent_spaces);
         let tokens = trailing_spaces::RemoveTrailingSpaces::new(tokens);
 
         tokens
@@ -538,8 +635,8 @@ impl Formatter {
             )
     }
 
-    /// Aligns the equals signs in pattern definitions. For example, for this
-    /// input..
+    /// Aligns the equals signs in pattern or metadata definitions. For
+    /// example, for this input..
     ///
     /// rule foo {
     ///   strings:
@@ -561,28 +658,40 @@ impl Formatter {
     ///     true
     /// }
     ///
+    /// `defs` is the grammar rule that contains the definitions (e.g:
+    /// `pattern_defs`) and `def` the grammar rule for each individual
+    /// definition (e.g: `pattern_def`). When `enabled` is false the input
+    /// is returned as is.
+    ///
     /// The input must must contain at least one newline character after each
-    /// pattern definition.
-    fn align_patterns<'a, I>(input: I) -> impl TokenStream<'a> + 'a
+    /// definition.
+    fn align_defs<'a, I>(
+        input: I,
+        defs: GrammarRule,
+        def: GrammarRule,
+        enabled: bool,
+    ) -> impl TokenStream<'a> + 'a
     where
         I: TokenStream<'a> + 'a,
     {
         // First insert the alignment markers at the appropriate places...
         let input_with_markers = processor::Processor::new(input)
             .add_rule(
-                |ctx| ctx.token(-1).eq(&Begin(GrammarRule::pattern_defs)),
+                move |ctx| enabled && ctx.token(-1).eq(&Begin(defs)),
                 processor::actions::insert(AlignmentBlockBegin),
             )
             .add_rule(
-                |ctx| {
-                    ctx.token(1).eq(&End(GrammarRule::pattern_defs))
+                move |ctx| {
+                    enabled
+                        && ctx.token(1).eq(&End(defs))
                         && ctx.token(-1).neq(&AlignmentBlockEnd)
                 },
                 processor::actions::insert(AlignmentBlockEnd),
             )
             .add_rule(
-                |ctx| {
-                    ctx.in_rule(GrammarRule::pattern_def, false)
+                move |ctx| {
+                    enabled
+                        && ctx.in_rule(def, false)
                         && ctx.token(1).eq(&EQUAL)
                         && ctx.token(-1).neq(&AlignmentMarker)
                 },
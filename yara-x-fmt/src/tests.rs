@@ -6,7 +6,7 @@ use std::{fs, str};
 use pretty_assertions::assert_eq;
 
 use crate::tokens::{TokenStream, Tokens};
-use crate::Formatter;
+use crate::{Formatter, NewlineStyle};
 use yara_x_parser::Parser;
 
 #[test]
@@ -35,7 +35,7 @@ fn spacer() {
         let mut output = Vec::new();
         let tokens = Tokens::new(Parser::new().build_cst(t.0).unwrap());
 
-        Formatter::add_spacing(tokens).write_to(&mut output).unwrap();
+        Formatter::add_spacing(tokens).write_to(&mut output, "\n").unwrap();
         assert_eq!(str::from_utf8(&output).unwrap(), t.1);
     }
 }
@@ -73,3 +73,36 @@ fn format() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[test]
+fn format_options() -> Result<(), anyhow::Error> {
+    let input = "rule test {\r\n  meta: a = 1 long_name = \"foo\" \
+                 strings: $a = \"foo\" $long_name = \"bar\" \
+                 condition: all of them }";
+
+    let mut output = Cursor::new(Vec::new());
+
+    Formatter::new()
+        .indent_spaces(4)
+        .align_metadata(true)
+        .align_patterns(false)
+        .newline_style(NewlineStyle::Auto)
+        .format(input.as_bytes(), &mut output)?;
+
+    assert_eq!(
+        String::from_utf8(output.into_inner())?,
+        "rule test {\r
+    meta:\r
+        a         = 1\r
+        long_name = \"foo\"\r
+    strings:\r
+        $a = \"foo\"\r
+        $long_name = \"bar\"\r
+    condition:\r
+        all of them\r
+}\r
+"
+    );
+
+    Ok(())
+}
@@ -353,11 +353,13 @@ impl<'a> Token<'a> {
             | GrammarRule::k_DEFINED
             | GrammarRule::k_ENDSWITH
             | GrammarRule::k_ENTRYPOINT
+            | GrammarRule::k_EXPORT
             | GrammarRule::k_FALSE
             | GrammarRule::k_FILESIZE
             | GrammarRule::k_FOR
             | GrammarRule::k_FULLWORD
             | GrammarRule::k_GLOBAL
+            | GrammarRule::k_HEXASCII
             | GrammarRule::k_ICONTAINS
             | GrammarRule::k_IENDSWITH
             | GrammarRule::k_IEQUALS
@@ -372,12 +374,16 @@ impl<'a> Token<'a> {
             | GrammarRule::k_OF
             | GrammarRule::k_OR
             | GrammarRule::k_PRIVATE
+            | GrammarRule::k_ROT13
             | GrammarRule::k_RULE
             | GrammarRule::k_STARTSWITH
+            | GrammarRule::k_STEP
             | GrammarRule::k_STRINGS
             | GrammarRule::k_THEM
             | GrammarRule::k_TRUE
+            | GrammarRule::k_UTF7
             | GrammarRule::k_WIDE
+            | GrammarRule::k_WITH
             | GrammarRule::k_XOR => Token::Keyword(src),
             // Punctuation.
             GrammarRule::ASTERISK
@@ -416,8 +422,9 @@ impl<'a> Token<'a> {
 /// A token stream is a sequence of tokens that can be iterated or written
 /// to anything implementing the [`std::io::Write`] trait.
 pub(crate) trait TokenStream<'a>: Iterator<Item = Token<'a>> {
-    /// Write the tokens in text form to the given writer.
-    fn write_to<W>(self, mut w: W) -> std::io::Result<()>
+    /// Write the tokens in text form to the given writer, using `newline`
+    /// as the line terminator.
+    fn write_to<W>(self, mut w: W, newline: &str) -> std::io::Result<()>
     where
         Self: Sized,
         W: std::io::Write,
@@ -426,7 +433,7 @@ pub(crate) trait TokenStream<'a>: Iterator<Item = Token<'a>> {
         for token in self {
             match token {
                 Token::Newline => {
-                    w.write_all("\n".as_bytes())?;
+                    w.write_all(newline.as_bytes())?;
                     col_num = 0;
                 }
                 Token::Whitespace
@@ -458,7 +465,7 @@ pub(crate) trait TokenStream<'a>: Iterator<Item = Token<'a>> {
                     // need to add the line-break and the corresponding
                     // indentation.
                     for line in lines {
-                        w.write_all("\n".as_bytes())?;
+                        w.write_all(newline.as_bytes())?;
                         w.write_all(
                             " ".repeat(message_col as usize).as_bytes(),
                         )?;
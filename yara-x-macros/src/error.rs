@@ -27,6 +27,12 @@ pub(crate) fn impl_error_macro(
         syn::Data::Enum(data_enum) => impl_enum_error_macro(data_enum)?,
     };
 
+    // Each variant is identified by a code, which is the variant's name
+    // in snake case (e.g. `DuplicateTag` is identified by `duplicate_tag`).
+    let codes = variants
+        .iter()
+        .map(|variant| variant.to_string().to_case(Case::Snake));
+
     let (impl_generics, ty_generics, where_clause) =
         input.generics.split_for_impl();
 
@@ -36,6 +42,14 @@ pub(crate) fn impl_error_macro(
         #[automatically_derived]
         impl #impl_generics #name #ty_generics #where_clause {
             #(#funcs)*
+
+            /// Returns a code that uniquely identifies the type of this
+            /// error or warning (e.g. `duplicate_rule`, `slow_pattern`).
+            pub fn code(&self) -> &'static str {
+                match self {
+                    #(Self::#variants { .. } => #codes),*
+                }
+            }
         }
 
         #[automatically_derived]
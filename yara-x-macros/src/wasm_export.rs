@@ -34,7 +34,8 @@ impl<'ast> FuncSignatureParser<'ast> {
             "i32" | "i64" => Ok(Cow::Borrowed("i")),
             "f32" | "f64" => Ok(Cow::Borrowed("f")),
             "bool" => Ok(Cow::Borrowed("b")),
-            "PatternId" | "RuleId" => Ok(Cow::Borrowed("i")),
+            "RuleId" => Ok(Cow::Borrowed("i")),
+            "PatternId" => Ok(Cow::Borrowed("p")),
             "RegexpId" => Ok(Cow::Borrowed("r")),
             "RuntimeString" => Ok(Cow::Borrowed("s")),
             type_ident => Err(syn::Error::new_spanned(
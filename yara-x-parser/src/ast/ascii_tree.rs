@@ -35,6 +35,10 @@ pub(crate) fn rule_ascii_tree(rule: &Rule) -> Tree {
 
     let mut modifiers = Vec::new();
 
+    if rule.flags.contains(RuleFlag::Export) {
+        modifiers.push("export");
+    }
+
     if rule.flags.contains(RuleFlag::Private) {
         modifiers.push("private");
     }
@@ -200,9 +204,8 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                             vec![expr_ascii_tree(&anchor_at.expr)],
                         )],
                     ),
-                    MatchAnchor::In(anchor_in) => Node(
-                        format!("{} in (<start>, <end>)", s.identifier.name),
-                        vec![
+                    MatchAnchor::In(anchor_in) => {
+                        let mut children = vec![
                             Node(
                                 "<start>".to_string(),
                                 vec![expr_ascii_tree(
@@ -215,8 +218,29 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                                     &anchor_in.range.upper_bound,
                                 )],
                             ),
-                        ],
-                    ),
+                        ];
+                        if let Some(step) = &anchor_in.range.step {
+                            children.push(Node(
+                                "<step>".to_string(),
+                                vec![expr_ascii_tree(step)],
+                            ));
+                            Node(
+                                format!(
+                                    "{} in (<start>, <end>, <step>)",
+                                    s.identifier.name
+                                ),
+                                children,
+                            )
+                        } else {
+                            Node(
+                                format!(
+                                    "{} in (<start>, <end>)",
+                                    s.identifier.name
+                                ),
+                                children,
+                            )
+                        }
+                    }
                 }
             } else {
                 Leaf(vec![s.identifier.name.to_string()])
@@ -228,10 +252,11 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                     format!("{} in <range>", s.name),
                     vec![Node(
                         "<range>".to_string(),
-                        vec![
-                            expr_ascii_tree(&range.lower_bound),
-                            expr_ascii_tree(&range.upper_bound),
-                        ],
+                        [&range.lower_bound, &range.upper_bound]
+                            .into_iter()
+                            .chain(range.step.as_ref())
+                            .map(expr_ascii_tree)
+                            .collect(),
                     )],
                 )
             } else {
@@ -303,6 +328,12 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                     "<items: boolean_expr_set>".to_string(),
                     set.iter().map(expr_ascii_tree).collect(),
                 ),
+                OfItems::RuleSet(set) => Node(
+                    "<items: rule_set>".to_string(),
+                    vec![Leaf(
+                        set.iter().map(|s| s.identifier.to_string()).collect(),
+                    )],
+                ),
             };
 
             let mut children = vec![
@@ -335,8 +366,17 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                                 &anchor_in.range.upper_bound,
                             )],
                         ));
-                        "<quantifier> of <items> in (<start>..<end>)"
-                            .to_string()
+                        if let Some(step) = &anchor_in.range.step {
+                            children.push(Node(
+                                "<step>".to_string(),
+                                vec![expr_ascii_tree(step)],
+                            ));
+                            "<quantifier> of <items> in (<start>..<end> step <step>)"
+                                .to_string()
+                        } else {
+                            "<quantifier> of <items> in (<start>..<end>)"
+                                .to_string()
+                        }
                     }
                 }
             } else {
@@ -389,7 +429,15 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                         "<end>".to_string(),
                         vec![expr_ascii_tree(&range.upper_bound)],
                     ));
-                    "for <quantifier> <vars> in (<start>..<end>) : ( <condition> )".to_string()
+                    if let Some(step) = &range.step {
+                        children.push(Node(
+                            "<step>".to_string(),
+                            vec![expr_ascii_tree(step)],
+                        ));
+                        "for <quantifier> <vars> in (<start>..<end> step <step>) : ( <condition> )".to_string()
+                    } else {
+                        "for <quantifier> <vars> in (<start>..<end>) : ( <condition> )".to_string()
+                    }
                 }
                 Iterable::ExprTuple(args) => {
                     let labelled_args: Vec<(String, &Expr)> = args
@@ -427,6 +475,23 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
 
             Node(node_title, children)
         }
+        Expr::With(w) => {
+            let mut children = Vec::new();
+
+            for declaration in w.declarations.iter() {
+                children.push(Node(
+                    format!("{} = <expr>", declaration.ident.name),
+                    vec![expr_ascii_tree(&declaration.expression)],
+                ));
+            }
+
+            children.push(Node(
+                "<condition>".to_string(),
+                vec![expr_ascii_tree(&w.condition)],
+            ));
+
+            Node("with <declarations> : ( <condition> )".to_string(), children)
+        }
     }
 }
 
@@ -84,6 +84,7 @@ bitmask! {
     flags RuleFlag {
         Private = 0x01,
         Global = 0x02,
+        Export = 0x04,
     }
 }
 
@@ -179,6 +180,12 @@ impl<'src> PatternModifiers<'src> {
         PatternModifiersIter { iter: self.modifiers.values() }
     }
 
+    /// Returns the modifier with the given name, if present.
+    #[inline]
+    pub(crate) fn get(&self, name: &str) -> Option<&PatternModifier<'src>> {
+        self.modifiers.get(name)
+    }
+
     #[inline]
     pub fn ascii(&self) -> Option<&PatternModifier<'src>> {
         self.modifiers.get("ascii")
@@ -213,6 +220,21 @@ impl<'src> PatternModifiers<'src> {
     pub fn xor(&self) -> Option<&PatternModifier<'src>> {
         self.modifiers.get("xor")
     }
+
+    #[inline]
+    pub fn rot13(&self) -> Option<&PatternModifier<'src>> {
+        self.modifiers.get("rot13")
+    }
+
+    #[inline]
+    pub fn hexascii(&self) -> Option<&PatternModifier<'src>> {
+        self.modifiers.get("hexascii")
+    }
+
+    #[inline]
+    pub fn utf7(&self) -> Option<&PatternModifier<'src>> {
+        self.modifiers.get("utf7")
+    }
 }
 
 /// Iterator that returns all the modifiers in a [`PatternModifiers`].
@@ -241,6 +263,9 @@ pub enum PatternModifier<'src> {
     Base64 { span: Span, alphabet: Option<&'src str> },
     Base64Wide { span: Span, alphabet: Option<&'src str> },
     Xor { span: Span, start: u8, end: u8 },
+    Rot13 { span: Span },
+    HexAscii { span: Span },
+    Utf7 { span: Span },
 }
 
 impl PatternModifier<'_> {
@@ -254,6 +279,9 @@ impl PatternModifier<'_> {
             PatternModifier::Base64 { .. } => "base64",
             PatternModifier::Base64Wide { .. } => "base64wide",
             PatternModifier::Xor { .. } => "xor",
+            PatternModifier::Rot13 { .. } => "rot13",
+            PatternModifier::HexAscii { .. } => "hexascii",
+            PatternModifier::Utf7 { .. } => "utf7",
         }
     }
 }
@@ -299,6 +327,15 @@ impl Display for PatternModifier<'_> {
                     write!(f, "xor({}-{})", start, end)
                 }
             }
+            PatternModifier::Rot13 { .. } => {
+                write!(f, "rot13")
+            }
+            PatternModifier::HexAscii { .. } => {
+                write!(f, "hexascii")
+            }
+            PatternModifier::Utf7 { .. } => {
+                write!(f, "utf7")
+            }
         }
     }
 }
@@ -568,6 +605,9 @@ pub enum Expr<'src> {
 
     /// A `for <quantifier> <vars> in ...` expression. (e.g. `for all i in (1..100) : ( ... )`)
     ForIn(Box<ForIn<'src>>),
+
+    /// A `with <declarations> : ( ... )` expression. (e.g. `with n = pe.number_of_sections : ( ... )`)
+    With(Box<With<'src>>),
 }
 
 /// A pattern match expression (e.g. `$a`, `$b at 0`, `$c in (0..10)`).
@@ -598,12 +638,14 @@ pub struct At<'src> {
     pub expr: Expr<'src>,
 }
 
-/// A pair of values conforming a range (e.g. `(0..10)`).
+/// A pair of values conforming a range (e.g. `(0..10)`), with an optional
+/// step (e.g. `(0..10 step 2)`).
 #[derive(Debug, HasSpan)]
 pub struct Range<'src> {
     pub span: Span,
     pub lower_bound: Expr<'src>,
     pub upper_bound: Expr<'src>,
+    pub step: Option<Expr<'src>>,
 }
 
 /// In expressions like `$a in (0..10)`, this structs represents the anchor
@@ -851,11 +893,61 @@ pub struct ForIn<'src> {
     pub condition: Expr<'src>,
 }
 
+/// A `with` expression (e.g `with n = foo.bar() : (..)`)
+#[derive(Debug, HasSpan)]
+pub struct With<'src> {
+    pub span: Span,
+    pub declarations: Vec<WithDeclaration<'src>>,
+    pub condition: Expr<'src>,
+}
+
+/// Each of the declarations in a `with` expression (e.g. `n = foo.bar()`).
+#[derive(Debug, HasSpan)]
+pub struct WithDeclaration<'src> {
+    pub span: Span,
+    pub ident: Ident<'src>,
+    pub expression: Expr<'src>,
+}
+
 /// Items in a `of` expression.
 #[derive(Debug)]
 pub enum OfItems<'src> {
     PatternSet(PatternSet<'src>),
     BoolExprTuple(Vec<Expr<'src>>),
+    RuleSet(Vec<RuleSetItem<'src>>),
+}
+
+/// Each individual item in a set of rules.
+///
+/// In the rule set `(foo, bar*, tag:baz)`, `foo`, `bar*` and `tag:baz` are
+/// represented by a [`RuleSetItem`]. Items can be a rule identifier, a rule
+/// identifier ending in a wildcard, which matches all the rules that start
+/// with the given prefix, or a tag prefixed with `tag:`, which matches all
+/// the rules with that tag.
+#[derive(Debug, HasSpan)]
+pub struct RuleSetItem<'src> {
+    pub span: Span,
+    pub identifier: &'src str,
+}
+
+impl RuleSetItem<'_> {
+    /// Returns true if a rule with the given identifier and tags matches
+    /// this [`RuleSetItem`].
+    ///
+    /// For example, rules `foo` and `foobar` both match the [`RuleSetItem`]
+    /// for `foo*`, and any rule with tag `bar` matches `tag:bar`.
+    pub fn matches<'a, T>(&self, ident: &str, tags: T) -> bool
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        if let Some(tag) = self.identifier.strip_prefix("tag:") {
+            tags.into_iter().any(|t| t == tag)
+        } else if let Some(prefix) = self.identifier.strip_suffix('*') {
+            ident.starts_with(prefix)
+        } else {
+            ident == self.identifier
+        }
+    }
 }
 
 /// A quantifier used in `for` and `of` expressions.
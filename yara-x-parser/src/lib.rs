@@ -28,6 +28,7 @@ extern crate core;
 
 pub mod ast;
 pub mod cst;
+pub mod modifiers;
 pub use parser::*;
 
 #[doc(inline)]
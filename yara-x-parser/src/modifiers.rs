@@ -0,0 +1,197 @@
+/*! Validation of pattern modifiers.
+
+This module allows checking whether a set of modifiers can be applied to a
+pattern without parsing a whole YARA rule. This is useful for rule editors
+and other tools that need to validate user input as it's being typed. The
+parser uses the same rules while building the AST, so any set of modifiers
+accepted by [`validate_modifiers`] is also accepted by the parser, and vice
+versa.
+
+# Example
+
+```
+use yara_x_parser::modifiers::{validate_modifiers, ModifierIssue, PatternType};
+
+assert!(validate_modifiers(PatternType::Text, ["ascii", "wide"]).is_empty());
+
+assert_eq!(
+    validate_modifiers(PatternType::Text, ["xor", "nocase"]),
+    vec![ModifierIssue::Incompatible {
+        modifier1: "xor".to_string(),
+        modifier2: "nocase".to_string(),
+    }]
+);
+```
+*/
+
+use std::fmt::{Display, Formatter};
+
+/// Names of all the existing pattern modifiers.
+pub const MODIFIERS: &[&str] = &[
+    "ascii",
+    "wide",
+    "nocase",
+    "private",
+    "fullword",
+    "base64",
+    "base64wide",
+    "xor",
+    "rot13",
+    "hexascii",
+    "utf7",
+];
+
+/// Pairs of modifiers that can't be used together.
+pub(crate) const INCOMPATIBLE_MODIFIERS: &[(&str, &str)] = &[
+    ("xor", "nocase"),
+    ("base64", "nocase"),
+    ("base64wide", "nocase"),
+    ("base64", "fullword"),
+    ("base64wide", "fullword"),
+    ("base64", "xor"),
+    ("base64wide", "xor"),
+    ("rot13", "xor"),
+    ("rot13", "base64"),
+    ("rot13", "base64wide"),
+    ("hexascii", "xor"),
+    ("hexascii", "base64"),
+    ("hexascii", "base64wide"),
+    ("utf7", "xor"),
+    ("utf7", "base64"),
+    ("utf7", "base64wide"),
+];
+
+/// Types of patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternType {
+    /// A text pattern (e.g: `$a = "foo"`).
+    Text,
+    /// A hex pattern (e.g: `$a = { 01 02 03 }`).
+    Hex,
+    /// A regular expression (e.g: `$a = /foo/`).
+    Regexp,
+}
+
+impl PatternType {
+    /// Returns true if the pattern type accepts the given modifier.
+    ///
+    /// Returns false if the modifier doesn't exist.
+    pub fn accepts(&self, modifier: &str) -> bool {
+        match self {
+            PatternType::Text => MODIFIERS.contains(&modifier),
+            PatternType::Regexp => matches!(
+                modifier,
+                "private" | "ascii" | "wide" | "nocase" | "fullword"
+            ),
+            PatternType::Hex => modifier == "private",
+        }
+    }
+}
+
+impl Display for PatternType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternType::Text => write!(f, "text pattern"),
+            PatternType::Hex => write!(f, "hex pattern"),
+            PatternType::Regexp => write!(f, "regexp"),
+        }
+    }
+}
+
+/// Each of the problems that [`validate_modifiers`] can find in a set of
+/// modifiers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModifierIssue {
+    /// The modifier doesn't exist.
+    Unknown { modifier: String },
+    /// The modifier exists, but it can't be applied to the pattern type.
+    NotAccepted { modifier: String, pattern_type: PatternType },
+    /// The modifier appears more than once.
+    Duplicate { modifier: String },
+    /// The two modifiers can't be used together.
+    Incompatible { modifier1: String, modifier2: String },
+}
+
+impl Display for ModifierIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModifierIssue::Unknown { modifier } => {
+                write!(f, "unknown modifier `{}`", modifier)
+            }
+            ModifierIssue::NotAccepted { modifier, pattern_type } => {
+                write!(
+                    f,
+                    "modifier `{}` can't be applied to a {}",
+                    modifier, pattern_type
+                )
+            }
+            ModifierIssue::Duplicate { modifier } => {
+                write!(f, "duplicate modifier `{}`", modifier)
+            }
+            ModifierIssue::Incompatible { modifier1, modifier2 } => {
+                write!(
+                    f,
+                    "modifiers `{}` and `{}` can't be used together",
+                    modifier1, modifier2
+                )
+            }
+        }
+    }
+}
+
+/// Validates a set of modifiers for a pattern of the given type.
+///
+/// Modifiers are specified by name, without arguments (e.g: `xor` instead
+/// of `xor(1-255)`). Returns all the issues found, in the order in which
+/// the modifiers appear. Incompatibilities are reported at the second
+/// modifier of the pair, with `modifier1` being the one that appears first.
+/// An empty vector means that the modifiers are valid.
+pub fn validate_modifiers<'a, I>(
+    pattern_type: PatternType,
+    modifiers: I,
+) -> Vec<ModifierIssue>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut issues = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+
+    for modifier in modifiers {
+        if !MODIFIERS.contains(&modifier) {
+            issues.push(ModifierIssue::Unknown {
+                modifier: modifier.to_string(),
+            });
+        } else if seen.contains(&modifier) {
+            issues.push(ModifierIssue::Duplicate {
+                modifier: modifier.to_string(),
+            });
+        } else {
+            if !pattern_type.accepts(modifier) {
+                issues.push(ModifierIssue::NotAccepted {
+                    modifier: modifier.to_string(),
+                    pattern_type,
+                });
+            }
+            for previous in seen.iter() {
+                if incompatible(previous, modifier) {
+                    issues.push(ModifierIssue::Incompatible {
+                        modifier1: previous.to_string(),
+                        modifier2: modifier.to_string(),
+                    });
+                }
+            }
+            seen.push(modifier);
+        }
+    }
+
+    issues
+}
+
+/// Returns true if the two modifiers can't be used together, regardless of
+/// their order.
+fn incompatible(modifier1: &str, modifier2: &str) -> bool {
+    INCOMPATIBLE_MODIFIERS.iter().any(|(a, b)| {
+        (*a == modifier1 && *b == modifier2)
+            || (*a == modifier2 && *b == modifier1)
+    })
+}
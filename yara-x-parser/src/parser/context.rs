@@ -2,6 +2,7 @@ use std::collections::{HashMap, HashSet};
 
 use crate::ast::{Ident, Span};
 use crate::cst::CSTNode;
+use crate::parser::UnusedPatternAction;
 use crate::report::ReportBuilder;
 use crate::warnings::Warning;
 
@@ -28,6 +29,10 @@ pub(crate) struct Context<'src, 'rb> {
     /// the unused ones.
     pub(crate) unused_patterns: HashSet<&'src str>,
 
+    /// What to do with the patterns left in `unused_patterns` after the
+    /// condition is parsed.
+    pub(crate) unused_pattern_action: UnusedPatternAction,
+
     /// Boolean that indicates if the parser is currently inside the expression
     /// of a `for .. of .. : (<expr>)` statement.
     pub(crate) inside_for_of: bool,
@@ -48,6 +53,7 @@ impl<'src, 'rb> Context<'src, 'rb> {
             inside_for_of: false,
             declared_patterns: HashMap::new(),
             unused_patterns: HashSet::new(),
+            unused_pattern_action: UnusedPatternAction::default(),
             current_pattern: None,
             report_builder,
             warnings: Vec::new(),
@@ -1,7 +1,7 @@
 /*! Functions for converting a CST into an AST. */
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 use std::iter::Iterator;
 use std::str;
 
@@ -14,7 +14,10 @@ use pest::pratt_parser::{Assoc, Op, PrattParser};
 
 use crate::ast::*;
 use crate::cst::*;
-use crate::parser::{Context, Error, ErrorInfo, GrammarRule};
+use crate::modifiers::{PatternType, INCOMPATIBLE_MODIFIERS};
+use crate::parser::{
+    Context, Error, ErrorInfo, GrammarRule, UnusedPatternAction,
+};
 use crate::warnings::Warning;
 
 macro_rules! expect {
@@ -196,30 +199,6 @@ fn create_binary_expr<'src>(
     }
 }
 
-lazy_static! {
-    // Map that indicates which modifiers are accepted by each type of patterns.
-    // For example, the `private` modifier is accepted by text patterns, hex patterns
-    // and regexps, while `base64` is only accepted by text patterns.
-    static ref ACCEPTED_MODIFIERS: HashMap<&'static str, Vec<GrammarRule>> =
-        HashMap::from([
-            (
-                "private",
-                vec![
-                    GrammarRule::string_lit,
-                    GrammarRule::regexp,
-                    GrammarRule::hex_pattern,
-                ],
-            ),
-            ("ascii", vec![GrammarRule::string_lit, GrammarRule::regexp]),
-            ("wide", vec![GrammarRule::string_lit, GrammarRule::regexp]),
-            ("nocase", vec![GrammarRule::string_lit, GrammarRule::regexp]),
-            ("fullword", vec![GrammarRule::string_lit, GrammarRule::regexp]),
-            ("base64", vec![GrammarRule::string_lit]),
-            ("base64wide", vec![GrammarRule::string_lit]),
-            ("xor", vec![GrammarRule::string_lit]),
-        ]);
-}
-
 /// Check if the set of modifiers for a pattern are valid.
 ///
 /// Certain modifiers can't be used in conjunction, and this function
@@ -229,22 +208,23 @@ fn check_pattern_modifiers(
     rule_type: GrammarRule,
     modifiers: &PatternModifiers,
 ) -> Result<(), Error> {
-    let xor = modifiers.xor();
-    let nocase = modifiers.nocase();
-    let fullword = modifiers.fullword();
-    let base64 = modifiers.base64();
-    let base64wide = modifiers.base64wide();
+    let pattern_type = match rule_type {
+        GrammarRule::string_lit => PatternType::Text,
+        GrammarRule::hex_pattern => PatternType::Hex,
+        GrammarRule::regexp => PatternType::Regexp,
+        _ => unreachable!(),
+    };
 
     for modifier in modifiers.iter() {
-        if !ACCEPTED_MODIFIERS[modifier.as_text()].contains(&rule_type) {
-            let error_detail = match rule_type {
-                GrammarRule::hex_pattern => {
+        if !pattern_type.accepts(modifier.as_text()) {
+            let error_detail = match pattern_type {
+                PatternType::Hex => {
                     "this modifier can't be applied to a hex pattern"
                 }
-                GrammarRule::regexp => {
+                PatternType::Regexp => {
                     "this modifier can't be applied to a regexp"
                 }
-                _ => unreachable!(),
+                PatternType::Text => unreachable!(),
             };
 
             return Err(Error::from(ErrorInfo::invalid_modifier(
@@ -255,18 +235,10 @@ fn check_pattern_modifiers(
         }
     }
 
-    let invalid_combinations = [
-        ("xor", xor, "nocase", nocase),
-        ("base64", base64, "nocase", nocase),
-        ("base64wide", base64wide, "nocase", nocase),
-        ("base64", base64, "fullword", fullword),
-        ("base64wide", base64wide, "fullword", fullword),
-        ("base64", base64, "xor", xor),
-        ("base64wide", base64wide, "xor", xor),
-    ];
-
-    for (name1, modifier1, name2, modifier2) in invalid_combinations {
-        if let (Some(modifier1), Some(modifier2)) = (modifier1, modifier2) {
+    for (name1, name2) in INCOMPATIBLE_MODIFIERS {
+        if let (Some(modifier1), Some(modifier2)) =
+            (modifiers.get(name1), modifiers.get(name2))
+        {
             return Err(Error::from(ErrorInfo::invalid_modifier_combination(
                 ctx.report_builder,
                 name1.to_string(),
@@ -345,8 +317,8 @@ fn rule_from_cst<'src>(
     let mut node = children.next().unwrap();
     let mut flags = RuleFlags::none();
 
-    // Process rule modifiers if any (i.e: private, global). The CST for the
-    // modifiers looks like:
+    // Process rule modifiers if any (i.e: export, private, global). The CST
+    // for the modifiers looks like:
     //
     // rule_mods
     // ├─ k_PRIVATE "private"
@@ -357,6 +329,7 @@ fn rule_from_cst<'src>(
             match modifier.as_rule() {
                 GrammarRule::k_PRIVATE => flags.set(RuleFlag::Private),
                 GrammarRule::k_GLOBAL => flags.set(RuleFlag::Global),
+                GrammarRule::k_EXPORT => flags.set(RuleFlag::Export),
                 parser_rule => {
                     panic!("unexpected rule modifier {:?}", parser_rule)
                 }
@@ -427,7 +400,7 @@ fn rule_from_cst<'src>(
     // Process the `strings` (a.k.a `patterns) section if any.
     // `ctx.declared_patterns` and `ctx.unused_patterns` will be populated
     // with the declared patterns.
-    let patterns = if let GrammarRule::pattern_defs = node.as_rule() {
+    let mut patterns = if let GrammarRule::pattern_defs = node.as_rule() {
         let patterns = patterns_from_cst(ctx, node)?;
         node = children.next().unwrap();
         Some(patterns)
@@ -449,15 +422,49 @@ fn rule_from_cst<'src>(
 
     // Any identifier left in ctx.unused_pattern is not being
     // used in the condition.
-    let unused_pattern = ctx.unused_patterns.drain().next();
+    if ctx.unused_pattern_action == UnusedPatternAction::Error {
+        let unused_pattern = ctx.unused_patterns.drain().next();
 
-    if let Some(ident) = unused_pattern {
-        let ident = ctx.declared_patterns.get(ident).unwrap();
-        return Err(Error::from(ErrorInfo::unused_pattern(
-            ctx.report_builder,
-            ident.name.to_string(),
-            ident.span,
-        )));
+        if let Some(ident) = unused_pattern {
+            let ident = ctx.declared_patterns.get(ident).unwrap();
+            return Err(Error::from(ErrorInfo::unused_pattern(
+                ctx.report_builder,
+                ident.name.to_string(),
+                ident.span,
+            )));
+        }
+    } else if !ctx.unused_patterns.is_empty() {
+        let strip = ctx.unused_pattern_action == UnusedPatternAction::Strip;
+
+        // If some pattern is unused, the rule must have patterns.
+        let declared = patterns.as_mut().unwrap();
+
+        // Warnings are raised in the same order in which the patterns were
+        // declared.
+        for pattern in declared.iter() {
+            let ident = pattern.identifier();
+            if ctx.unused_patterns.contains(&ident.name[1..]) {
+                ctx.warnings.push(Warning::unused_pattern(
+                    ctx.report_builder,
+                    ident.name.to_string(),
+                    ident.span,
+                    strip.then(|| {
+                        "the pattern was removed from the rule".to_string()
+                    }),
+                ));
+            }
+        }
+
+        if strip {
+            declared.retain(|pattern| {
+                !ctx.unused_patterns.contains(&pattern.identifier().name[1..])
+            });
+            if declared.is_empty() {
+                patterns = None;
+            }
+        }
+
+        ctx.unused_patterns.clear();
     }
 
     // Clear `declared_patterns` so that the next call to `rule_from_cst`
@@ -743,6 +750,15 @@ fn pattern_mods_from_cst<'src>(
             GrammarRule::k_NOCASE => {
                 PatternModifier::Nocase { span: ctx.span(&node) }
             }
+            GrammarRule::k_ROT13 => {
+                PatternModifier::Rot13 { span: ctx.span(&node) }
+            }
+            GrammarRule::k_HEXASCII => {
+                PatternModifier::HexAscii { span: ctx.span(&node) }
+            }
+            GrammarRule::k_UTF7 => {
+                PatternModifier::Utf7 { span: ctx.span(&node) }
+            }
             GrammarRule::k_XOR => {
                 let mut lower_bound = 0;
                 let mut upper_bound = 255;
@@ -1055,32 +1071,8 @@ fn boolean_term_from_cst<'src>(
         }
         GrammarRule::pattern_ident => {
             let ident = children.next().unwrap();
-            let ident_name = ident.as_str();
             let anchor = anchor_from_cst(ctx, children)?;
 
-            // The use of `$` in the condition doesn't mean that all anonymous
-            // pattern identifiers are used. Anonymous pattern identifiers are
-            // considered used when the `them` keyword is used, or when the
-            // pattern `$*` appears in a pattern identifiers tuple.
-            if ident_name != "$" {
-                if ctx.declared_patterns.get(&ident_name[1..]).is_none() {
-                    return Err(Error::from(ErrorInfo::unknown_pattern(
-                        ctx.report_builder,
-                        ident_name.to_string(),
-                        ctx.span(&ident),
-                    )));
-                }
-                ctx.unused_patterns.remove(&ident_name[1..]);
-            }
-            // `$` used outside a `for .. of` statement, that's invalid.
-            else if !ctx.inside_for_of {
-                return Err(Error::from(ErrorInfo::syntax_error(
-                    ctx.report_builder,
-                    "this `$` is outside of the condition of a `for .. of` statement".to_string(),
-                    ctx.span(&ident),
-                )));
-            }
-
             Expr::PatternMatch(Box::new(PatternMatch {
                 // TODO: this is not the best way of computing the span for
                 // PatternMatch, as this covers the space that can follow, like
@@ -1089,7 +1081,7 @@ fn boolean_term_from_cst<'src>(
                 //   ^^^^^^^^^^^^^^^
                 // The best way is using the anchor's span end.
                 span: boolean_term_span,
-                identifier: ident_from_cst(ctx, ident),
+                identifier: pattern_ident_from_cst(ctx, ident)?,
                 anchor,
             }))
         }
@@ -1118,6 +1110,9 @@ fn boolean_term_from_cst<'src>(
         GrammarRule::for_expr => {
             for_expr_from_cst(ctx, children.next().unwrap())?
         }
+        GrammarRule::with_expr => {
+            with_expr_from_cst(ctx, children.next().unwrap())?
+        }
         _ => unreachable!(),
     };
 
@@ -1358,6 +1353,44 @@ fn indexing_expr_from_cst<'src>(
     Ok(Expr::Lookup(Box::new(Lookup::new(primary, index, span))))
 }
 
+/// Given a CST node corresponding to the grammar rule `pattern_ident`,
+/// returns the corresponding [`Ident`], after making sure that the pattern
+/// was declared and marking it as used.
+fn pattern_ident_from_cst<'src>(
+    ctx: &mut Context<'src, '_>,
+    ident: CSTNode<'src>,
+) -> Result<Ident<'src>, Error> {
+    expect!(ident, GrammarRule::pattern_ident);
+
+    let ident_name = ident.as_str();
+
+    // The use of `$` in the condition doesn't mean that all anonymous
+    // pattern identifiers are used. Anonymous pattern identifiers are
+    // considered used when the `them` keyword is used, or when the
+    // pattern `$*` appears in a pattern identifiers tuple.
+    if ident_name != "$" {
+        if ctx.declared_patterns.get(&ident_name[1..]).is_none() {
+            return Err(Error::from(ErrorInfo::unknown_pattern(
+                ctx.report_builder,
+                ident_name.to_string(),
+                ctx.span(&ident),
+            )));
+        }
+        ctx.unused_patterns.remove(&ident_name[1..]);
+    }
+    // `$` used outside a `for .. of` statement, that's invalid.
+    else if !ctx.inside_for_of {
+        return Err(Error::from(ErrorInfo::syntax_error(
+            ctx.report_builder,
+            "this `$` is outside of the condition of a `for .. of` statement"
+                .to_string(),
+            ctx.span(&ident),
+        )));
+    }
+
+    Ok(ident_from_cst(ctx, ident))
+}
+
 fn func_call_from_cst<'src>(
     ctx: &mut Context<'src, '_>,
     func_call_expr: CSTNode<'src>,
@@ -1388,6 +1421,17 @@ fn func_call_from_cst<'src>(
             GrammarRule::expr => {
                 args.push(expr_from_cst(ctx, node)?);
             }
+            // ... if the node is a pattern identifier, add it to the function
+            // arguments as a pattern match without anchor. The compiler
+            // decides whether the function receives the pattern itself or
+            // the result of the match.
+            GrammarRule::pattern_ident => {
+                args.push(Expr::PatternMatch(Box::new(PatternMatch {
+                    span: ctx.span(&node),
+                    identifier: pattern_ident_from_cst(ctx, node)?,
+                    anchor: None,
+                })));
+            }
             // ... if the node is a comma separating the arguments, do
             // nothing and continue.
             GrammarRule::COMMA => {}
@@ -1430,12 +1474,22 @@ fn range_from_cst<'src>(
 
     let upper_bound = expr_from_cst(ctx, children.next().unwrap())?;
 
-    expect!(children.next().unwrap(), GrammarRule::RPAREN);
+    let step = match children.next().unwrap() {
+        node if node.as_rule() == GrammarRule::k_STEP => {
+            let step = expr_from_cst(ctx, children.next().unwrap())?;
+            expect!(children.next().unwrap(), GrammarRule::RPAREN);
+            Some(step)
+        }
+        node => {
+            expect!(node, GrammarRule::RPAREN);
+            None
+        }
+    };
 
     // Make sure that there are no more nodes.
     assert!(children.next().is_none());
 
-    Ok(Range { span, lower_bound, upper_bound })
+    Ok(Range { span, lower_bound, upper_bound, step })
 }
 
 /// From a CST node corresponding to the grammar rule `of_expr`, returns
@@ -1467,6 +1521,9 @@ fn of_expr_from_cst<'src>(
         GrammarRule::boolean_expr_tuple => {
             OfItems::BoolExprTuple(boolean_expr_tuple_from_cst(ctx, node)?)
         }
+        GrammarRule::rule_set => {
+            OfItems::RuleSet(rule_set_from_cst(ctx, node))
+        }
         rule => unreachable!("{:?}", rule),
     };
 
@@ -1571,6 +1628,59 @@ fn for_expr_from_cst<'src>(
     Ok(expr)
 }
 
+/// From a CST node corresponding to the grammar rule `with_expr`, returns
+/// an [`Expr`] describing the `with` statement.
+fn with_expr_from_cst<'src>(
+    ctx: &mut Context<'src, '_>,
+    with_expr: CSTNode<'src>,
+) -> Result<Expr<'src>, Error> {
+    expect!(with_expr, GrammarRule::with_expr);
+
+    let span = ctx.span(&with_expr);
+    let mut children = with_expr.into_inner();
+
+    // The statement starts with the `with` keyword...
+    expect!(children.next().unwrap(), GrammarRule::k_WITH);
+
+    // ...and then follows one or more declarations separated by commas.
+    let mut declarations = Vec::new();
+
+    for node in children.by_ref() {
+        match node.as_rule() {
+            GrammarRule::with_declaration => {
+                let span = ctx.span(&node);
+                let mut children = node.into_inner();
+
+                let ident = children.next().unwrap();
+                expect!(ident, GrammarRule::ident);
+                expect!(children.next().unwrap(), GrammarRule::EQUAL);
+
+                declarations.push(WithDeclaration {
+                    span,
+                    ident: Ident::new(ident.as_str(), ctx.span(&ident)),
+                    expression: boolean_expr_from_cst(
+                        ctx,
+                        children.next().unwrap(),
+                    )?,
+                });
+            }
+            GrammarRule::COMMA => {}
+            GrammarRule::COLON => {
+                break;
+            }
+            rule => unreachable!("{:?}", rule),
+        }
+    }
+
+    expect!(children.next().unwrap(), GrammarRule::LPAREN);
+
+    let condition = boolean_expr_from_cst(ctx, children.next().unwrap())?;
+
+    expect!(children.next().unwrap(), GrammarRule::RPAREN);
+
+    Ok(Expr::With(Box::new(With { span, declarations, condition })))
+}
+
 fn anchor_from_cst<'src>(
     ctx: &mut Context<'src, '_>,
     mut iter: impl Iterator<Item = CSTNode<'src>>,
@@ -1686,6 +1796,37 @@ fn pattern_ident_tuple<'src>(
     Ok(result)
 }
 
+/// From a CST node corresponding to the grammar rule `rule_set`, returns
+/// a vector of [`RuleSetItem`].
+fn rule_set_from_cst<'src>(
+    ctx: &mut Context<'src, '_>,
+    rule_set: CSTNode<'src>,
+) -> Vec<RuleSetItem<'src>> {
+    expect!(rule_set, GrammarRule::rule_set);
+
+    let mut children = rule_set.into_inner();
+
+    // The set should start with an opening parenthesis.
+    expect!(children.next().unwrap(), GrammarRule::LPAREN);
+
+    let mut result = Vec::new();
+
+    for node in children {
+        match node.as_rule() {
+            GrammarRule::rule_set_item => {
+                result.push(RuleSetItem {
+                    span: ctx.span(&node),
+                    identifier: node.as_str(),
+                });
+            }
+            GrammarRule::COMMA | GrammarRule::RPAREN => {}
+            rule => unreachable!("{:?}", rule),
+        }
+    }
+
+    result
+}
+
 /// From a CST node corresponding to the grammar rule `boolean_expr_tuple`, returns
 /// a vector of [`Expr`].
 fn boolean_expr_tuple_from_cst<'src>(
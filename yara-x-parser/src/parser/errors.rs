@@ -306,11 +306,13 @@ impl ErrorInfo {
             GrammarRule::k_BASE64 => "`base64`",
             GrammarRule::k_BASE64WIDE => "`base64wide`",
             GrammarRule::k_CONDITION => "`condition`",
+            GrammarRule::k_EXPORT => "`export`",
             GrammarRule::k_FALSE => "`false`",
             GrammarRule::k_FILESIZE => "`filesize`",
             GrammarRule::k_FOR => "`for`",
             GrammarRule::k_FULLWORD => "`fullword`",
             GrammarRule::k_GLOBAL => "`global`",
+            GrammarRule::k_HEXASCII => "`hexascii`",
             GrammarRule::k_IMPORT => "`import`",
             GrammarRule::k_IN => "`in`",
             GrammarRule::k_META => "`meta`",
@@ -318,10 +320,14 @@ impl ErrorInfo {
             GrammarRule::k_NOT => "`not`",
             GrammarRule::k_OF => "`of`",
             GrammarRule::k_PRIVATE => "`private`",
+            GrammarRule::k_ROT13 => "`rot13`",
             GrammarRule::k_RULE => "`rule`",
+            GrammarRule::k_STEP => "`step`",
             GrammarRule::k_STRINGS => "`strings`",
             GrammarRule::k_THEM => "`them`",
             GrammarRule::k_TRUE => "`true`",
+            GrammarRule::k_UTF7 => "`utf7`",
+            GrammarRule::k_WITH => "`with`",
             GrammarRule::k_WIDE => "`wide`",
             GrammarRule::k_XOR => "`xor`",
 
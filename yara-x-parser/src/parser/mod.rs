@@ -69,6 +69,17 @@ impl<'src> SourceCode<'src> {
         }
     }
 
+    /// Returns the string that describes the origin of the source code, if
+    /// it was set with [`SourceCode::with_origin`].
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Returns the source code as raw bytes.
+    pub fn raw(&self) -> &'src [u8] {
+        self.raw.as_bytes()
+    }
+
     /// Make sure that the source code is valid UTF-8. If that's the case
     /// sets the `valid` field, if not, returns an error.
     fn validate_utf8(&mut self) -> Result<(), bstr::Utf8Error> {
@@ -102,12 +113,27 @@ impl<'src> From<&'src [u8]> for SourceCode<'src> {
     }
 }
 
+/// Action taken by the parser when a rule declares a pattern that is not
+/// used in the rule's condition.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnusedPatternAction {
+    /// Unused patterns produce an error. This is the default.
+    #[default]
+    Error,
+    /// Unused patterns produce a warning, but they are kept in the rule.
+    Warn,
+    /// Unused patterns produce a warning and they are removed from the rule,
+    /// so that they don't need to be searched for while scanning.
+    Strip,
+}
+
 /// Receives YARA source code and produces either a Concrete Syntax Tree (CST)
 /// or an Abstract Syntax Tree (AST).
 #[derive(Default)]
 pub struct Parser<'a> {
     external_report_builder: Option<&'a ReportBuilder>,
     own_report_builder: ReportBuilder,
+    unused_pattern_action: UnusedPatternAction,
 }
 
 impl<'a> Parser<'a> {
@@ -128,9 +154,21 @@ impl<'a> Parser<'a> {
         Self {
             external_report_builder: None,
             own_report_builder: ReportBuilder::new(),
+            unused_pattern_action: UnusedPatternAction::default(),
         }
     }
 
+    /// Specifies what to do with patterns that are declared by a rule but
+    /// not used in its condition. By default they produce an error, see
+    /// [`UnusedPatternAction`] for the alternatives.
+    pub fn unused_patterns(
+        &mut self,
+        action: UnusedPatternAction,
+    ) -> &mut Self {
+        self.unused_pattern_action = action;
+        self
+    }
+
     /// Specifies whether the parser should produce colorful error messages.
     ///
     /// Colorized error messages contain ANSI escape sequences that make them
@@ -192,6 +230,8 @@ impl<'a> Parser<'a> {
 
         let mut ctx = Context::new(report_builder);
 
+        ctx.unused_pattern_action = self.unused_pattern_action;
+
         let (imports, rules) = ast_from_cst(&mut ctx, root.into_inner())?;
 
         Ok(AST { source: src, imports, rules, warnings: ctx.warnings })
@@ -916,6 +916,30 @@ rule test {
    │ 
    │ Note: these two modifiers can't be used together
 ───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  strings: 
+    $a = "foo" rot13 xor
+  condition:
+    $a
+}
+"#,
+            r#"error: invalid modifier combination: `rot13` `xor`
+   ╭─[line:4:16]
+   │
+ 4 │     $a = "foo" rot13 xor
+   │                ──┬── ─┬─  
+   │                  ╰──────── `rot13` modifier used here
+   │                       │   
+   │                       ╰─── `xor` modifier used here
+   │ 
+   │ Note: these two modifiers can't be used together
+───╯
 "#,
         ),
         ////////////////////////////////////////////////////////////
@@ -1075,6 +1099,57 @@ rule test {
    │                        ┬  
    │                        ╰── invalid modifier
 ───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  strings:
+    $a = "foo"
+  condition:
+    #a in (0..1 ]
+}
+"#,
+            r#"error: syntax error
+   ╭─[line:6:17]
+   │
+ 6 │     #a in (0..1 ]
+   │                 │ 
+   │                 ╰─ expected `step`, closing parenthesis `)`, dot `.`, opening bracket `[`, opening parenthesis `(`, or operator
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    with foo = true (foo)
+}
+"#,
+            r#"error: syntax error
+   ╭─[line:4:21]
+   │
+ 4 │     with foo = true (foo)
+   │                     │ 
+   │                     ╰─ expected colon `:`, comma `,`, or operator
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"export foo rule test { condition: true }"#,
+            r#"error: syntax error
+   ╭─[line:1:8]
+   │
+ 1 │ export foo rule test { condition: true }
+   │        │ 
+   │        ╰─ expected `global`, `private`, or `rule`
+───╯
 "#,
         ),
     ];
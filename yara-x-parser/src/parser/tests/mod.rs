@@ -1,5 +1,6 @@
 use pretty_assertions::assert_eq;
 
+use crate::modifiers::{validate_modifiers, ModifierIssue, PatternType};
 use crate::parser::Parser;
 
 #[cfg(feature = "ascii-tree")]
@@ -56,6 +57,64 @@ fn pathological_case() {
         .is_err());
 }
 
+#[test]
+fn modifiers() {
+    assert!(validate_modifiers(PatternType::Hex, ["private"]).is_empty());
+    assert!(validate_modifiers(
+        PatternType::Text,
+        ["xor", "wide", "ascii", "private", "fullword"]
+    )
+    .is_empty());
+
+    assert_eq!(
+        validate_modifiers(
+            PatternType::Regexp,
+            ["foo", "wide", "wide", "base64", "nocase", "fullword"]
+        ),
+        vec![
+            ModifierIssue::Unknown { modifier: "foo".to_string() },
+            ModifierIssue::Duplicate { modifier: "wide".to_string() },
+            ModifierIssue::NotAccepted {
+                modifier: "base64".to_string(),
+                pattern_type: PatternType::Regexp
+            },
+            ModifierIssue::Incompatible {
+                modifier1: "base64".to_string(),
+                modifier2: "nocase".to_string()
+            },
+            ModifierIssue::Incompatible {
+                modifier1: "base64".to_string(),
+                modifier2: "fullword".to_string()
+            },
+        ]
+    );
+
+    // Issues follow the order of the modifiers, not the order of the
+    // table of incompatible modifiers.
+    assert_eq!(
+        validate_modifiers(PatternType::Text, ["nocase", "base64", "xor"]),
+        vec![
+            ModifierIssue::Incompatible {
+                modifier1: "nocase".to_string(),
+                modifier2: "base64".to_string()
+            },
+            ModifierIssue::Incompatible {
+                modifier1: "nocase".to_string(),
+                modifier2: "xor".to_string()
+            },
+            ModifierIssue::Incompatible {
+                modifier1: "base64".to_string(),
+                modifier2: "xor".to_string()
+            },
+        ]
+    );
+
+    assert_eq!(
+        validate_modifiers(PatternType::Hex, ["nocase"])[0].to_string(),
+        "modifier `nocase` can't be applied to a hex pattern"
+    );
+}
+
 mod ast;
 mod cst;
 mod errors;
@@ -121,6 +121,26 @@ impl ReportBuilder {
         self
     }
 
+    /// Creates a new [`ReportBuilder`] with the same settings as this one,
+    /// but that assigns source IDs starting at the one this report builder
+    /// will assign after registering `skip` more sources.
+    ///
+    /// This allows parsing multiple source files in parallel, each with
+    /// its own report builder. If the source files are later registered
+    /// with this report builder in the same order, they get the same
+    /// [`SourceId`] they had while being parsed, so the spans in their
+    /// ASTs remain valid.
+    pub fn fork(&self, skip: u32) -> Self {
+        Self {
+            with_colors: self.with_colors,
+            current_source_id: Cell::new(None),
+            next_source_id: Cell::new(SourceId(
+                self.next_source_id.get().0 + skip,
+            )),
+            cache: RefCell::new(Cache { data: HashMap::new() }),
+        }
+    }
+
     /// Returns the [`SourceId`] for the most recently registered source file.
     pub(crate) fn current_source_id(&self) -> Option<SourceId> {
         self.current_source_id.get()
@@ -7,7 +7,7 @@ use crate::report::ReportType;
 
 /// A warning raised while parsing YARA rules.
 #[rustfmt::skip]
-#[derive(Error)]
+#[derive(Error, Clone)]
 pub enum Warning {
     #[warning("consecutive jumps in hex pattern `{pattern_ident}`")]
     #[label("these consecutive jumps will be treated as {coalesced_jump}", jumps_span)]
@@ -73,10 +73,63 @@ pub enum Warning {
         i_span: Span,
     },
 
+    #[warning("rule `{rule_ident}` depends on itself")]
+    #[label("`{rule_ident}` is used in its own condition", span)]
+    #[note(note)]
+    CircularRuleDependency {
+        detailed_report: String,
+        rule_ident: String,
+        span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("unused pattern `{pattern_ident}`")]
+    #[label("this pattern was not used in the condition", pattern_ident_span)]
+    #[note(note)]
+    UnusedPattern {
+        detailed_report: String,
+        pattern_ident: String,
+        pattern_ident_span: Span,
+        note: Option<String>,
+    },
+
     #[warning("slow pattern")]
     #[label("this pattern may slow down the scan", span)]
     SlowPattern {
         detailed_report: String,
         span: Span,
+    },
+
+    #[warning("lossy comparison between integer and float")]
+    #[label("{reason}", span)]
+    #[note(note)]
+    LossyComparison {
+        detailed_report: String,
+        reason: String,
+        span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("`not` expression is never true when `{field}` is undefined")]
+    #[label("`{field}` is undefined if module `{module}` has no data for it", span)]
+    #[note(note)]
+    NegatedUndefinedField {
+        detailed_report: String,
+        field: String,
+        module: String,
+        span: Span,
+        note: Option<String>,
+    },
+
+    #[warning("{message}")]
+    #[label("{label}", span)]
+    #[note(note)]
+    LintWarning {
+        detailed_report: String,
+        lint_pass: String,
+        message: String,
+        label: String,
+        span: Span,
+        note: Option<String>,
     }
 }
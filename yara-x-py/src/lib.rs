@@ -140,14 +140,16 @@ struct Scanner {
 impl Scanner {
     /// Creates a new [`Scanner`] with a given set of [`Rules`].
     #[new]
-    fn new(rules: Py<Rules>) -> Self {
+    fn new(rules: Py<Rules>) -> PyResult<Self> {
         Python::with_gil(|py| {
             let rules_ref: &'static yrx::Rules = {
                 let rules = rules.borrow(py);
                 let rules_ptr: *const yrx::Rules = &rules.deref().inner.rules;
                 unsafe { &*rules_ptr }
             };
-            Self { _rules: rules, inner: yrx::Scanner::new(rules_ref) }
+            let inner = yrx::Scanner::new(rules_ref)
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            Ok(Self { _rules: rules, inner })
         })
     }
 
@@ -241,7 +243,8 @@ impl Rules {
     /// Scans in-memory data with these rules.
     #[pyo3(signature = (data))]
     fn scan(&self, data: &[u8]) -> PyResult<Py<PyTuple>> {
-        let mut scanner = yrx::Scanner::new(&self.inner.rules);
+        let mut scanner = yrx::Scanner::new(&self.inner.rules)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
         let scan_results = scanner
             .scan(data)
             .map_err(|err| PyValueError::new_err(err.to_string()))?;
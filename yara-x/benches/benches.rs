@@ -24,7 +24,7 @@ macro_rules! gen_bench {
 
             group.bench_function("yara-x", |b| {
                 let rules = yara_x::compile($rule).unwrap();
-                let mut scanner = yara_x::Scanner::new(&rules);
+                let mut scanner = yara_x::Scanner::new(&rules).unwrap();
 
                 b.iter(|| {
                     scanner.scan($data);
@@ -0,0 +1,271 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bincode::Options;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use yara_x_parser::SourceCode;
+
+use crate::compiler::Rules;
+use crate::re;
+use crate::re::compiler::RegexpAtom;
+use crate::SerializationError;
+
+/// Identifies a set of inputs to the compiler.
+///
+/// The key is a SHA-256 digest computed over the version of this crate and
+/// every piece of information added to the key, like source codes and the
+/// namespaces they belong to. Two keys are equal only if the same inputs were
+/// added to both of them in the same order.
+///
+/// Source code is not the only thing that affects the result of the
+/// compilation. Any other setting used while compiling the rules, like
+/// global variables or the compiler's policy, must be added to the key with
+/// [`CacheKey::add_setting`].
+#[derive(Clone)]
+pub struct CacheKey {
+    hasher: Sha256,
+}
+
+impl CacheKey {
+    /// Creates a new key.
+    pub fn new() -> Self {
+        let mut key = Self { hasher: Sha256::new() };
+        key.update(env!("CARGO_PKG_VERSION").as_bytes());
+        key
+    }
+
+    /// Adds a source code, together with the namespace it is added to.
+    pub fn add_source(
+        &mut self,
+        namespace: &str,
+        src: &SourceCode,
+    ) -> &mut Self {
+        self.update(b"source");
+        self.update(namespace.as_bytes());
+        self.update(src.origin().unwrap_or_default().as_bytes());
+        self.update(src.raw());
+        self
+    }
+
+    /// Adds some setting that affects the result of the compilation.
+    pub fn add_setting(&mut self, name: &str, value: &str) -> &mut Self {
+        self.update(b"setting");
+        self.update(name.as_bytes());
+        self.update(value.as_bytes());
+        self
+    }
+
+    /// Returns the key as an hex string.
+    pub fn to_hex(&self) -> String {
+        let mut hex = String::with_capacity(64);
+        for b in self.hasher.clone().finalize() {
+            write!(hex, "{:02x}", b).unwrap();
+        }
+        hex
+    }
+
+    /// Adds a length-prefixed piece of data to the digest. The length
+    /// prefix guarantees that different sequences of inputs produce
+    /// different digests, even if their concatenations are equal.
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update((data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+    }
+}
+
+impl Default for CacheKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An on-disk cache of compiled rules.
+///
+/// Compiling a large set of rules can take a considerable amount of time.
+/// This cache stores the compiled rules in a directory, indexed by a
+/// [`CacheKey`] that identifies the inputs to the compiler. If the inputs
+/// don't change, the compiled rules can be loaded from the cache instead of
+/// compiling them again.
+///
+/// Notice that the cache works at the level of the whole set of rules.
+/// Changing any source file changes the key, and the whole set of rules
+/// must be compiled again. However, when the cache is passed to the
+/// compiler with [`crate::Compiler::set_cache`], the compiler also stores
+/// the code produced for the regular expressions and hex patterns in each
+/// source file, which is usually the most expensive part of the
+/// compilation. These per-file fragments are indexed by the content of the
+/// source file alone, so they are reused when a file is compiled again,
+/// even if other files in the set changed, and the code they contain is
+/// relocated while linking it into the new rules. Rule conditions and the
+/// WASM module are still compiled for the whole set of rules, as the code
+/// generated for them refers to IDs that are global to the whole set.
+///
+/// # Example
+///
+/// ```no_run
+/// # use yara_x::{CacheKey, CompilationCache, Compiler};
+/// # use yara_x_parser::SourceCode;
+/// let cache = CompilationCache::new("/tmp/yara-x-cache")?;
+/// let src = SourceCode::from("rule foo { condition: true }");
+///
+/// let mut key = CacheKey::new();
+/// key.add_source("default", &src);
+///
+/// let rules = match cache.get(&key) {
+///     Some(rules) => rules,
+///     None => {
+///         let mut compiler = Compiler::new();
+///         compiler.add_source(src)?;
+///         let rules = compiler.build();
+///         cache.insert(&key, &rules)?;
+///         rules
+///     }
+/// };
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct CompilationCache {
+    dir: PathBuf,
+}
+
+impl CompilationCache {
+    /// Creates a cache that stores compiled rules in the given directory.
+    /// The directory is created if it doesn't exist.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, io::Error> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self { dir: dir.as_ref().to_path_buf() })
+    }
+
+    /// Returns the compiled rules associated to the given key, if they are
+    /// in the cache.
+    ///
+    /// Entries that can't be read or deserialized, for instance because they
+    /// were produced by an incompatible version, are treated as missing.
+    /// Like any other deserialized rules, the returned rules don't contain
+    /// the warnings produced while compiling them.
+    pub fn get(&self, key: &CacheKey) -> Option<Rules> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        Rules::deserialize(bytes).ok()
+    }
+
+    /// Stores compiled rules in the cache, associated to the given key.
+    ///
+    /// The entry is written into a temporary file which is then renamed, so
+    /// that other processes using the same cache never see partially
+    /// written entries.
+    pub fn insert(
+        &self,
+        key: &CacheKey,
+        rules: &Rules,
+    ) -> Result<(), SerializationError> {
+        self.write(self.path(key), rules.serialize()?)
+    }
+
+    /// Returns the fragment stored for the given source code, if any.
+    ///
+    /// Like with [`CompilationCache::get`], fragments that can't be read
+    /// or deserialized are treated as missing.
+    pub(crate) fn get_fragment(&self, src: &SourceCode) -> Option<Fragment> {
+        let bytes = fs::read(self.fragment_path(src)).ok()?;
+        bincode::DefaultOptions::new()
+            .with_varint_encoding()
+            .deserialize(bytes.as_slice())
+            .ok()
+    }
+
+    /// Stores the fragment produced while compiling the given source code.
+    pub(crate) fn insert_fragment(
+        &self,
+        src: &SourceCode,
+        fragment: &Fragment,
+    ) -> Result<(), SerializationError> {
+        self.write(
+            self.fragment_path(src),
+            bincode::DefaultOptions::new()
+                .with_varint_encoding()
+                .serialize(fragment)?,
+        )
+    }
+
+    /// Writes an entry in the way described in [`CompilationCache::insert`].
+    fn write(
+        &self,
+        path: PathBuf,
+        bytes: Vec<u8>,
+    ) -> Result<(), SerializationError> {
+        let tmp_path =
+            path.with_extension(format!("tmp{}", std::process::id()));
+
+        fs::write(&tmp_path, bytes)?;
+
+        if let Err(err) = fs::rename(&tmp_path, &path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(key.to_hex()).with_extension("yarc")
+    }
+
+    fn fragment_path(&self, src: &SourceCode) -> PathBuf {
+        let mut key = CacheKey::new();
+        key.update(b"fragment");
+        key.update(src.raw());
+        self.dir.join(key.to_hex()).with_extension("yarf")
+    }
+}
+
+/// Code produced for the regular expressions in a source file.
+///
+/// The code for a regexp depends only on the regexp itself, so each entry
+/// is indexed by a digest of the regexp's HIR, not by its position in the
+/// source file. The code locations in the atoms are relative to the start
+/// of the forward and backward code, the compiler relocates them when the
+/// code is linked into the rules.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Fragment {
+    regexps: FxHashMap<[u8; 32], CompiledRegexp>,
+    #[serde(skip)]
+    modified: bool,
+}
+
+impl Fragment {
+    /// Returns the digest that identifies a regexp within the fragment.
+    pub fn digest(hir: &re::hir::Hir) -> [u8; 32] {
+        Sha256::digest(format!("{:?}", hir)).into()
+    }
+
+    /// Returns the code for the regexp with the given digest, if any.
+    pub fn get(&self, digest: &[u8; 32]) -> Option<&CompiledRegexp> {
+        self.regexps.get(digest)
+    }
+
+    /// Adds the code for the regexp with the given digest.
+    pub fn insert(&mut self, digest: [u8; 32], regexp: CompiledRegexp) {
+        self.regexps.insert(digest, regexp);
+        self.modified = true;
+    }
+
+    /// Returns true if some regexp was added after the fragment was
+    /// created or loaded from the cache.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+}
+
+/// Forward code, backward code and atoms produced for a regexp by
+/// [`re::compiler::Compiler::compile`].
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct CompiledRegexp {
+    pub fwd_code: Vec<u8>,
+    pub bck_code: Vec<u8>,
+    pub atoms: Vec<RegexpAtom>,
+}
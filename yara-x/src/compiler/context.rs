@@ -9,7 +9,8 @@ use yara_x_parser::report::ReportBuilder;
 use yara_x_parser::Warning;
 
 use crate::compiler::{
-    ir, IdentId, LiteralId, PatternId, RegexpId, RuleId, RuleInfo,
+    ir, CompilerPolicy, IdentId, IntegerOverflow, LiteralId, PatternId,
+    RegexpId, RuleId, RuleInfo,
 };
 use crate::string_pool::{BStringPool, StringPool};
 use crate::symbols::{StackedSymbolTable, SymbolLookup};
@@ -49,6 +50,27 @@ pub(in crate::compiler) struct Context<'a, 'src, 'sym> {
     /// Rule that is being compiled.
     pub current_rule: &'a RuleInfo,
 
+    /// Rules used by the condition of the rule being compiled.
+    pub current_rule_deps: Vec<RuleId>,
+
+    /// Modules used by the condition of the rule being compiled.
+    pub current_module_deps: Vec<IdentId>,
+
+    /// Number of iterations of each `for .. in <range>` loop found in the
+    /// condition of the rule being compiled, `None` if the number of
+    /// iterations is not known at compile time.
+    pub current_rule_loops: Vec<Option<u64>>,
+
+    /// Number of times that `filesize` has been used so far in the condition
+    /// of the rule being compiled.
+    pub filesize_refs: usize,
+
+    /// Restrictions imposed on the rules being compiled.
+    pub policy: &'a CompilerPolicy,
+
+    /// Behavior of integer arithmetic operations that overflow.
+    pub integer_overflow: IntegerOverflow,
+
     /// IR nodes for patterns defined in the rule being compiled.
     pub current_rule_patterns: &'a mut FxHashMap<PatternId, ir::Pattern<'src>>,
 
@@ -17,9 +17,10 @@ use walrus::{InstrSeqBuilder, ValType};
 use yara_x_parser::ast::{RuleFlag, RuleFlags};
 
 use crate::compiler::ir::{
-    Expr, ForIn, ForOf, Iterable, MatchAnchor, Of, OfItems, Quantifier,
+    Expr, ForIn, ForOf, Iterable, MatchAnchor, Of, OfItems, Quantifier, Range,
+    With,
 };
-use crate::compiler::{Context, RuleId, Var, VarStackFrame};
+use crate::compiler::{Context, IntegerOverflow, RuleId, Var, VarStackFrame};
 use crate::symbols::SymbolKind;
 use crate::types::{Array, Map, Type, TypeValue, Value};
 use crate::utils::cast;
@@ -27,8 +28,8 @@ use crate::wasm;
 use crate::wasm::builder::WasmModuleBuilder;
 use crate::wasm::string::RuntimeString;
 use crate::wasm::{
-    LOOKUP_INDEXES_END, LOOKUP_INDEXES_START, MATCHING_RULES_BITMAP_BASE,
-    VARS_STACK_START,
+    WasmExport, LOOKUP_INDEXES_END, LOOKUP_INDEXES_START,
+    MATCHING_RULES_BITMAP_BASE, VARS_STACK_START,
 };
 
 /// This macro emits the code for the left and right operands of some
@@ -62,7 +63,7 @@ macro_rules! emit_operands {
 }
 
 macro_rules! emit_arithmetic_op {
-    ($ctx:ident, $instr:ident, $operands:expr, $int_op:tt, $float_op:tt) => {{
+    ($ctx:ident, $instr:ident, $operands:expr, $int_op:tt, $float_op:tt, $saturating_fn:expr, $checked_fn:expr) => {{
         // If any of the operands is float, this is a float operation.
         let is_float =
             $operands.iter().any(|op| matches!(op.ty(), Type::Float));
@@ -84,7 +85,13 @@ macro_rules! emit_arithmetic_op {
                 }
                 $instr.binop(BinaryOp::$float_op);
             } else {
-                $instr.binop(BinaryOp::$int_op);
+                emit_integer_op(
+                    $ctx,
+                    $instr,
+                    BinaryOp::$int_op,
+                    &$saturating_fn,
+                    &$checked_fn,
+                );
             }
         }
     }};
@@ -180,6 +187,12 @@ pub(super) fn emit_rule_condition(
         builder.new_rule()
     };
 
+    // Set the `current_rule` global variable to the ID of this rule, so
+    // that the scanner knows which rule was being evaluated if the scan is
+    // aborted while evaluating its condition.
+    instr.i32_const(rule_id.0);
+    instr.global_set(ctx.wasm_symbols.current_rule);
+
     // Emit WASM code for the rule's condition.
     catch_undef(ctx, &mut instr, |ctx, instr| {
         emit_bool_expr(ctx, instr, condition);
@@ -280,6 +293,14 @@ fn emit_expr(ctx: &mut Context, instr: &mut InstrSeqBuilder, expr: &mut Expr) {
                     let signature =
                         &func.signatures()[ctx.current_signature.unwrap()];
 
+                    // Functions in nested structures (e.g:
+                    // `foo.bar.func()`) leave the indexes of the structures
+                    // in `lookup_stack`. They are not used by the function,
+                    // and must be discarded so that they don't interfere
+                    // with later lookups.
+                    ctx.lookup_stack.clear();
+                    ctx.lookup_start = None;
+
                     if signature.result_may_be_undef {
                         emit_call_and_handle_undef(
                             ctx,
@@ -500,7 +521,13 @@ fn emit_expr(ctx: &mut Context, instr: &mut InstrSeqBuilder, expr: &mut Expr) {
                     // is implemented as i64.sub(0, x).
                     instr.i64_const(0);
                     emit_expr(ctx, instr, operand);
-                    instr.binop(BinaryOp::I64Sub);
+                    emit_integer_op(
+                        ctx,
+                        instr,
+                        BinaryOp::I64Sub,
+                        &wasm::export__saturating_sub,
+                        &wasm::export__checked_sub,
+                    );
                 }
                 _ => unreachable!(),
             };
@@ -513,13 +540,37 @@ fn emit_expr(ctx: &mut Context, instr: &mut InstrSeqBuilder, expr: &mut Expr) {
             instr.binop(BinaryOp::I64Xor);
         }
         Expr::Add { operands } => {
-            emit_arithmetic_op!(ctx, instr, operands, I64Add, F64Add);
+            emit_arithmetic_op!(
+                ctx,
+                instr,
+                operands,
+                I64Add,
+                F64Add,
+                wasm::export__saturating_add,
+                wasm::export__checked_add
+            );
         }
         Expr::Sub { operands } => {
-            emit_arithmetic_op!(ctx, instr, operands, I64Sub, F64Sub);
+            emit_arithmetic_op!(
+                ctx,
+                instr,
+                operands,
+                I64Sub,
+                F64Sub,
+                wasm::export__saturating_sub,
+                wasm::export__checked_sub
+            );
         }
         Expr::Mul { operands } => {
-            emit_arithmetic_op!(ctx, instr, operands, I64Mul, F64Mul);
+            emit_arithmetic_op!(
+                ctx,
+                instr,
+                operands,
+                I64Mul,
+                F64Mul,
+                wasm::export__saturating_mul,
+                wasm::export__checked_mul
+            );
         }
         Expr::Div { operands } => {
             let mut operands = operands.iter_mut();
@@ -744,10 +795,22 @@ fn emit_expr(ctx: &mut Context, instr: &mut InstrSeqBuilder, expr: &mut Expr) {
             }
         },
 
+        Expr::With(with) => {
+            emit_with(ctx, instr, with);
+        }
+
         Expr::FuncCall(fn_call) => {
-            // Emit the arguments first.
-            for expr in fn_call.args.iter_mut() {
-                emit_expr(ctx, instr, expr);
+            let func = fn_call.callable.type_value().as_func();
+            let signature = &func.signatures()[fn_call.signature_index];
+
+            // Emit the arguments first. Arguments of type pattern are passed
+            // as the pattern's ID, not as the result of the pattern match.
+            for (expr, arg) in fn_call.args.iter_mut().zip(&signature.args) {
+                if matches!(arg, TypeValue::Pattern) {
+                    emit_pattern_id(ctx, instr, expr);
+                } else {
+                    emit_expr(ctx, instr, expr);
+                }
             }
 
             let previous =
@@ -761,6 +824,33 @@ fn emit_expr(ctx: &mut Context, instr: &mut InstrSeqBuilder, expr: &mut Expr) {
     }
 }
 
+/// Emits the code that pushes the ID of the pattern referenced by a pattern
+/// match expression (e.g: `$a` or `$`) into the stack.
+fn emit_pattern_id(
+    ctx: &mut Context,
+    instr: &mut InstrSeqBuilder,
+    expr: &mut Expr,
+) {
+    // The function receiving the pattern ID most likely needs the pattern's
+    // matches.
+    emit_lazy_pattern_search(ctx, instr);
+
+    match expr {
+        Expr::PatternMatch { pattern_id, .. } => {
+            instr.i32_const((*pattern_id).into());
+        }
+        Expr::PatternMatchVar { symbol, .. } => match symbol.kind() {
+            SymbolKind::WasmVar(var) => {
+                load_var(ctx, instr, *var);
+                // load_var returns a I64, convert it to I32.
+                instr.unop(UnaryOp::I32WrapI64);
+            }
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
 /// Emits code that checks if the pattern search phase has not been executed
 /// yet, and do it in that case.
 fn emit_lazy_pattern_search(ctx: &mut Context, instr: &mut InstrSeqBuilder) {
@@ -835,8 +925,7 @@ fn emit_pattern_match(
             );
         }
         MatchAnchor::In(range) => {
-            emit_expr(ctx, instr, &mut range.lower_bound);
-            emit_expr(ctx, instr, &mut range.upper_bound);
+            emit_range(ctx, instr, range);
             instr.call(
                 ctx.function_id(wasm::export__is_pat_match_in.mangled_name),
             );
@@ -875,8 +964,7 @@ fn emit_pattern_count(
 
     match range {
         Some(range) => {
-            emit_expr(ctx, instr, &mut range.lower_bound);
-            emit_expr(ctx, instr, &mut range.upper_bound);
+            emit_range(ctx, instr, range);
             instr.call(
                 ctx.function_id(wasm::export__pat_matches_in.mangled_name),
             );
@@ -1345,8 +1433,7 @@ fn emit_of_pattern_set(
                     ));
                 }
                 MatchAnchor::In(range) => {
-                    emit_expr(ctx, instr, &mut range.lower_bound);
-                    emit_expr(ctx, instr, &mut range.upper_bound);
+                    emit_range(ctx, instr, range);
                     instr.call(ctx.function_id(
                         wasm::export__is_pat_match_in.mangled_name,
                     ));
@@ -1459,6 +1546,13 @@ fn emit_for_in_range(
     // The only variable contains the loop's next item.
     let next_item = for_in.variables[0];
 
+    // If the range has a step, it is stored in a variable, as it's used
+    // after each iteration for computing the next item.
+    let step = range
+        .step
+        .is_some()
+        .then(|| for_in.stack_frame.new_var(Type::Integer));
+
     emit_for(
         ctx,
         instr,
@@ -1497,6 +1591,22 @@ fn emit_for_in_range(
             set_var(ctx, instr, next_item, |ctx, instr| {
                 instr.local_get(ctx.wasm_symbols.i64_tmp);
             });
+
+            if let Some(step) = step {
+                set_var(ctx, instr, step, |ctx, instr| {
+                    emit_range_step(ctx, instr, &mut range.step);
+                });
+                // Set n = (n - 1) / step + 1;
+                set_var(ctx, instr, n, |ctx, instr| {
+                    load_var(ctx, instr, n);
+                    instr.i64_const(1);
+                    instr.binop(BinaryOp::I64Sub);
+                    load_var(ctx, instr, step);
+                    instr.binop(BinaryOp::I64DivS);
+                    instr.i64_const(1);
+                    instr.binop(BinaryOp::I64Add);
+                });
+            }
         },
         // Before each iteration.
         |_, _, _| {},
@@ -1505,8 +1615,17 @@ fn emit_for_in_range(
             emit_bool_expr(ctx, instr, &mut for_in.condition);
         },
         // After each iteration.
-        |ctx, instr, _| {
-            incr_var(ctx, instr, next_item);
+        |ctx, instr, _| match step {
+            Some(step) => {
+                set_var(ctx, instr, next_item, |ctx, instr| {
+                    load_var(ctx, instr, next_item);
+                    load_var(ctx, instr, step);
+                    instr.binop(BinaryOp::I64Add);
+                });
+            }
+            None => {
+                incr_var(ctx, instr, next_item);
+            }
         },
     );
 }
@@ -1534,15 +1653,17 @@ fn emit_for_in_array(
     instr: &mut InstrSeqBuilder,
     for_in: &mut ForIn,
 ) {
-    // A `for` loop in an array has exactly one variable.
-    assert_eq!(for_in.variables.len(), 1);
+    // A `for` loop in an array has either one variable with the loop's next
+    // item, or two variables with the next item's index and the item itself.
+    let (next_index, next_item) = match for_in.variables.as_slice() {
+        [next_item] => (None, *next_item),
+        [next_index, next_item] => (Some(*next_index), *next_item),
+        _ => unreachable!(),
+    };
 
     let expr = cast!(&mut for_in.iterable, Iterable::Expr);
     let array = expr.type_value().as_array();
 
-    // The only variable contains the loop's next item.
-    let next_item = for_in.variables[0];
-
     // When values in the array are structs, `next_item` is a host-side
     // variable. For every other type it is a WASM-side variable.
     let wasm_side_next_item = !matches!(next_item.ty, Type::Struct);
@@ -1583,6 +1704,14 @@ fn emit_for_in_array(
         },
         // Before each iteration.
         |ctx, instr, i| {
+            // If the loop has an index variable, copy the current iteration
+            // number into it.
+            if let Some(next_index) = next_index {
+                set_var(ctx, instr, next_index, |ctx, instr| {
+                    load_var(ctx, instr, i);
+                });
+            }
+
             // The next lookup operation starts at the local variable
             // `array_var`.
             ctx.lookup_start = Some(array_var);
@@ -1741,6 +1870,20 @@ fn emit_for_in_expr_tuple(
     );
 }
 
+/// Emits the code for a `with` expression.
+///
+/// The expression in each declaration is evaluated only once, and its result
+/// is stored in the corresponding variable before evaluating the condition.
+fn emit_with(ctx: &mut Context, instr: &mut InstrSeqBuilder, with: &mut With) {
+    for (var, expr) in with.declarations.iter_mut() {
+        set_var(ctx, instr, *var, |ctx, instr| {
+            emit_expr(ctx, instr, expr);
+        });
+    }
+
+    emit_bool_expr(ctx, instr, &mut with.condition);
+}
+
 /// Emits a `for` loop.
 ///
 /// This function allows creating different types of `for` loops by receiving
@@ -2576,6 +2719,85 @@ fn throw_undef(ctx: &Context, instr: &mut InstrSeqBuilder) {
     instr.br(innermost_handler.1);
 }
 
+/// Emits the code that pushes the lower bound, upper bound and step of a
+/// range into the stack. If the range doesn't have a step, the step is 1.
+fn emit_range(
+    ctx: &mut Context,
+    instr: &mut InstrSeqBuilder,
+    range: &mut Range,
+) {
+    emit_expr(ctx, instr, &mut range.lower_bound);
+    emit_expr(ctx, instr, &mut range.upper_bound);
+    emit_range_step(ctx, instr, &mut range.step);
+}
+
+/// Emits the code that pushes the step of a range into the stack, or 1 if
+/// the range doesn't have a step.
+///
+/// Steps must be positive integers. Steps known at compile time are checked
+/// by the compiler, but the remaining ones are checked at scan time, and
+/// the result is undefined if they are zero or negative.
+fn emit_range_step(
+    ctx: &mut Context,
+    instr: &mut InstrSeqBuilder,
+    step: &mut Option<Box<Expr>>,
+) {
+    match step {
+        Some(step) => {
+            emit_expr(ctx, instr, step);
+            if !matches!(
+                step.type_value(),
+                TypeValue::Integer(Value::Const(_))
+            ) {
+                instr.local_tee(ctx.wasm_symbols.i64_tmp);
+                instr.i64_const(0);
+                instr.binop(BinaryOp::I64LeS);
+                instr.if_else(
+                    None,
+                    |then_| {
+                        throw_undef(ctx, then_);
+                    },
+                    |_| {},
+                );
+                instr.local_get(ctx.wasm_symbols.i64_tmp);
+            }
+        }
+        None => {
+            instr.i64_const(1);
+        }
+    }
+}
+
+/// Emits the code for an integer addition, subtraction or multiplication,
+/// taking into account the overflow behavior configured in the compiler.
+///
+/// `op` is the WASM instruction used when the result wraps around on
+/// overflow, `saturating_fn` and `checked_fn` are the functions called when
+/// the result saturates or is undefined, respectively.
+fn emit_integer_op(
+    ctx: &Context,
+    instr: &mut InstrSeqBuilder,
+    op: BinaryOp,
+    saturating_fn: &WasmExport,
+    checked_fn: &WasmExport,
+) {
+    match ctx.integer_overflow {
+        IntegerOverflow::Wrap => {
+            instr.binop(op);
+        }
+        IntegerOverflow::Saturate => {
+            instr.call(ctx.function_id(saturating_fn.mangled_name));
+        }
+        IntegerOverflow::Undefined | IntegerOverflow::Error => {
+            emit_call_and_handle_undef(
+                ctx,
+                instr,
+                ctx.function_id(checked_fn.mangled_name),
+            );
+        }
+    }
+}
+
 /// Similar to [`throw_undef`], but throws the exception if the top of the
 /// stack is zero. If the top of the stack is non-zero, calling this function
 /// is a no-op.
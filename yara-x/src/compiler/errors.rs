@@ -9,6 +9,8 @@ use yara_x_parser::report::ReportBuilder;
 use yara_x_parser::report::ReportType;
 use yara_x_parser::Error as ParseError;
 
+use crate::compiler::TemplateError;
+
 /// Errors returned while serializing/deserializing compiled rules.
 #[derive(Error, Debug)]
 pub enum SerializationError {
@@ -18,10 +20,38 @@ pub enum SerializationError {
     #[error("invalid YARA-X compiled rules file")]
     InvalidEncoding(#[from] bincode::Error),
 
+    #[error("compiled rules have format version {found}, but the supported versions are {min_supported} to {supported}")]
+    IncompatibleVersion { found: u32, min_supported: u32, supported: u32 },
+
+    #[error("compiled rules use unsupported features (flags: {0:#x})")]
+    UnsupportedFeatures(u32),
+
+    #[error("compiled rules are not signed")]
+    MissingSignature,
+
+    #[error("invalid signature for compiled rules")]
+    InvalidSignature,
+
+    #[error("invalid public key")]
+    InvalidPublicKey,
+
     #[error(transparent)]
     IoError(#[from] io::Error),
 }
 
+/// Errors returned by [`crate::Compiler::ext_module_schema`].
+#[derive(Error, Debug)]
+pub enum ExtSchemaError {
+    #[error("invalid protobuf descriptor: {0}")]
+    InvalidDescriptor(String),
+
+    #[error("message `{0}` not found in protobuf descriptor")]
+    UnknownMessage(String),
+
+    #[error("the schema of the `ext` module can't be changed after the module is imported")]
+    AlreadyImported,
+}
+
 /// Error returned by [`crate::Compiler::emit_wasm_file`].
 #[derive(Error, Debug)]
 #[error(transparent)]
@@ -36,6 +66,9 @@ pub enum Error {
 
     #[error(transparent)]
     CompileError(#[from] CompileError),
+
+    #[error(transparent)]
+    TemplateError(#[from] TemplateError),
 }
 
 /// Error produced while compiling rules.
@@ -193,6 +226,45 @@ pub enum CompileErrorInfo {
         non_global_rule_usage_span: Span,
     },
 
+    #[error("empty rule set")]
+    #[label("no rule declared before this one matches `{item}`", span)]
+    EmptyRuleSet { detailed_report: String, item: String, span: Span },
+
+    #[error("module `{identifier}` is forbidden by policy")]
+    #[label("this module can't be imported", span)]
+    ForbiddenModule { detailed_report: String, identifier: String, span: Span },
+
+    #[error("loop depends on `filesize`")]
+    #[label(
+        "the number of iterations of this loop depends on the size of the scanned data",
+        span
+    )]
+    UnboundedLoop { detailed_report: String, span: Span },
+
+    #[error("rule `{rule}` has too many patterns")]
+    #[label(
+        "this rule has {num_patterns} patterns, the policy allows at most {max_patterns}",
+        span
+    )]
+    TooManyPatterns {
+        detailed_report: String,
+        rule: String,
+        num_patterns: usize,
+        max_patterns: usize,
+        span: Span,
+    },
+
+    #[error(
+        "rule `{rule}` doesn't have the required metadata `{identifier}`"
+    )]
+    #[label("metadata `{identifier}` is required by policy", span)]
+    MissingMetadata {
+        detailed_report: String,
+        rule: String,
+        identifier: String,
+        span: Span,
+    },
+
     #[error("invalid regular expression")]
     #[label("{error}", span)]
     InvalidRegexp { detailed_report: String, error: String, span: Span },
@@ -207,4 +279,36 @@ pub enum CompileErrorInfo {
         quantifier1_span: Span,
         quantifier2_span: Span,
     },
+
+    #[error("integer overflow")]
+    #[label(
+        "the result of this expression doesn't fit in a 64-bits integer",
+        span
+    )]
+    ArithmeticOverflow { detailed_report: String, span: Span },
+
+    #[error("rule `{rule}` is not exported")]
+    #[label("`{rule}` belongs to namespace `{namespace}`", span)]
+    #[label("`{rule}` declared here", rule_span, style = "note")]
+    #[note(note)]
+    UnexportedRule {
+        detailed_report: String,
+        rule: String,
+        namespace: String,
+        span: Span,
+        rule_span: Span,
+        note: Option<String>,
+    },
+
+    #[error("{message}")]
+    #[label("{label}", span)]
+    #[note(note)]
+    LintError {
+        detailed_report: String,
+        lint_pass: String,
+        message: String,
+        label: String,
+        span: Span,
+        note: Option<String>,
+    },
 }
@@ -0,0 +1,75 @@
+/*! Support for the `ext` module, whose structure is defined by the user.
+
+The `ext` module allows exposing external metadata about the scanned files,
+like detections from other engines or submission details, without adding a
+new module to the crate. The structure of the module is described by a
+protobuf `FileDescriptorSet` provided to [`crate::Compiler::ext_module_schema`],
+and the data for each scanned file is a protobuf message serialized according
+to that schema, which is provided with [`crate::Scanner::set_module_data`].
+*/
+
+use protobuf::descriptor::FileDescriptorSet;
+use protobuf::reflect::{FileDescriptor, MessageDescriptor};
+use protobuf::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::ExtSchemaError;
+
+/// Name of the module whose structure is defined by the user.
+pub(crate) const EXT_MODULE: &str = "ext";
+
+/// Schema of the `ext` module.
+///
+/// The schema is stored in the compiled rules, so that the scanner can
+/// decode the data provided for the module.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ExtSchema {
+    /// A `FileDescriptorSet` serialized in protobuf format.
+    descriptor_set: Vec<u8>,
+    /// Fully qualified name of the message that describes the module's
+    /// top-level structure (e.g: `my.package.Metadata`).
+    root_message: String,
+}
+
+impl ExtSchema {
+    /// Creates a new schema, making sure that the descriptor is valid and
+    /// contains the root message.
+    pub fn new(
+        descriptor_set: &[u8],
+        root_message: &str,
+    ) -> Result<Self, ExtSchemaError> {
+        let schema = Self {
+            descriptor_set: descriptor_set.to_vec(),
+            root_message: root_message.trim_start_matches('.').to_string(),
+        };
+        schema.root_descriptor()?;
+        Ok(schema)
+    }
+
+    /// Returns the descriptor of the module's root message.
+    pub fn root_descriptor(
+        &self,
+    ) -> Result<MessageDescriptor, ExtSchemaError> {
+        let descriptor_set =
+            FileDescriptorSet::parse_from_bytes(&self.descriptor_set)
+                .map_err(|err| {
+                    ExtSchemaError::InvalidDescriptor(err.to_string())
+                })?;
+
+        let files = FileDescriptor::new_dynamic_fds(descriptor_set.file, &[])
+            .map_err(|err| {
+                ExtSchemaError::InvalidDescriptor(err.to_string())
+            })?;
+
+        // message_by_full_name expects a dot (.) at the beginning of the
+        // name.
+        let full_name = format!(".{}", self.root_message);
+
+        files
+            .iter()
+            .find_map(|file| file.message_by_full_name(&full_name))
+            .ok_or_else(|| {
+                ExtSchemaError::UnknownMessage(self.root_message.clone())
+            })
+    }
+}
@@ -1,7 +1,9 @@
 /*! Functions for converting an AST into an IR. */
 
 use itertools::Itertools;
+use protobuf::reflect::Syntax;
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::iter;
 use std::ops::{Deref, RangeInclusive};
 use std::rc::Rc;
@@ -14,9 +16,13 @@ use crate::compiler::ir::hex2hir::hex_pattern_hir_from_ast;
 use crate::compiler::ir::{
     Expr, ForIn, ForOf, FuncCall, Iterable, LiteralPattern, Lookup,
     MatchAnchor, Of, OfItems, Pattern, PatternFlagSet, PatternFlags,
-    Quantifier, Range, RegexpPattern,
+    Quantifier, Range, RegexpPattern, With,
 };
-use crate::compiler::{CompileError, CompileErrorInfo, Context, PatternId};
+use crate::compiler::{
+    CompileError, CompileErrorInfo, Context, IntegerOverflow, PatternId,
+    RuleId, Var, VarStackFrame,
+};
+use crate::modules::BUILTIN_MODULES;
 use crate::re;
 use crate::re::parser::Error;
 use crate::symbols::{Symbol, SymbolKind, SymbolLookup, SymbolTable};
@@ -74,6 +80,18 @@ pub(in crate::compiler) fn text_pattern_from_ast<'src>(
         flags.set(PatternFlags::Fullword);
     }
 
+    if pattern.modifiers.rot13().is_some() {
+        flags.set(PatternFlags::Rot13);
+    }
+
+    if pattern.modifiers.hexascii().is_some() {
+        flags.set(PatternFlags::HexAscii);
+    }
+
+    if pattern.modifiers.utf7().is_some() {
+        flags.set(PatternFlags::Utf7);
+    }
+
     let xor_range = match pattern.modifiers.xor() {
         Some(ast::PatternModifier::Xor { start, end, .. }) => {
             flags.set(PatternFlags::Xor);
@@ -200,7 +218,10 @@ pub(in crate::compiler) fn expr_from_ast(
 ) -> Result<Expr, CompileError> {
     match expr {
         ast::Expr::Entrypoint { .. } => Ok(Expr::Entrypoint),
-        ast::Expr::Filesize { .. } => Ok(Expr::Filesize),
+        ast::Expr::Filesize { .. } => {
+            ctx.filesize_refs += 1;
+            Ok(Expr::Filesize)
+        }
 
         ast::Expr::True { .. } => {
             Ok(Expr::Const { type_value: TypeValue::Bool(Value::Const(true)) })
@@ -281,6 +302,7 @@ pub(in crate::compiler) fn expr_from_ast(
         ast::Expr::Of(of) => of_expr_from_ast(ctx, of),
         ast::Expr::ForOf(for_of) => for_of_expr_from_ast(ctx, for_of),
         ast::Expr::ForIn(for_in) => for_in_expr_from_ast(ctx, for_in),
+        ast::Expr::With(with) => with_expr_from_ast(ctx, with),
         ast::Expr::FuncCall(fn_call) => func_call_from_ast(ctx, fn_call),
 
         ast::Expr::FieldAccess(expr) => {
@@ -331,6 +353,34 @@ pub(in crate::compiler) fn expr_from_ast(
                 ctx.symbol_table.lookup(ident.name)
             };
 
+            // If the identifier is a rule declared in some other namespace
+            // the rule can't be used because it wasn't exported. Rules from
+            // the current namespace and exported rules are always found in
+            // the symbol table.
+            if symbol.is_none() && current_struct.is_none() {
+                if let Some(rule) = ctx.rules.iter().rev().find(|rule| {
+                    ctx.ident_pool.get(rule.ident_id) == Some(ident.name)
+                }) {
+                    return Err(CompileError::from(
+                        CompileErrorInfo::unexported_rule(
+                            ctx.report_builder,
+                            ident.name.to_string(),
+                            ctx.ident_pool
+                                .get(rule.namespace_ident_id)
+                                .unwrap()
+                                .to_string(),
+                            ident.span(),
+                            rule.ident_span,
+                            Some(format!(
+                                "rules from other namespaces can be used only \
+                                 if they are declared as `export rule {}`",
+                                ident.name
+                            )),
+                        ),
+                    ));
+                }
+            }
+
             if symbol.is_none() {
                 return Err(CompileError::from(
                     CompileErrorInfo::unknown_identifier(
@@ -368,6 +418,38 @@ pub(in crate::compiler) fn expr_from_ast(
                         ),
                     ));
                 }
+                // Rules can only use rules that were defined before them,
+                // the only possible cycle is a rule that uses itself.
+                if usize::from(*rule_id) == ctx.rules.len() - 1 {
+                    ctx.warnings.push(Warning::circular_rule_dependency(
+                        ctx.report_builder,
+                        ident.name.to_string(),
+                        ident.span(),
+                        Some(
+                            "the result of a rule is unknown while its \
+                             condition is being evaluated, so this is \
+                             always false"
+                                .to_string(),
+                        ),
+                    ));
+                }
+                if !ctx.current_rule_deps.contains(rule_id) {
+                    ctx.current_rule_deps.push(*rule_id);
+                }
+            }
+
+            // Keep track of the modules used by the rule. Modules are the
+            // only structures that can be found at the top level of the
+            // symbol table, besides global variables.
+            if current_struct.is_none()
+                && matches!(symbol.kind(), SymbolKind::FieldIndex(_))
+                && matches!(symbol.type_value(), TypeValue::Struct(_))
+                && BUILTIN_MODULES.contains_key(ident.name)
+            {
+                let module_id = ctx.ident_pool.get_or_intern(ident.name);
+                if !ctx.current_module_deps.contains(&module_id) {
+                    ctx.current_module_deps.push(module_id);
+                }
             }
 
             let type_value = symbol.type_value();
@@ -614,6 +696,12 @@ fn of_expr_from_ast(
             let num_items = tuple.len();
             (OfItems::BoolExprTuple(tuple), num_items)
         }
+        // `x of (foo, bar*, tag:baz)`
+        ast::OfItems::RuleSet(rule_set) => {
+            let tuple = rule_set_from_ast(ctx, rule_set)?;
+            let num_items = tuple.len();
+            (OfItems::BoolExprTuple(tuple), num_items)
+        }
         // `x of them`, `x of ($a*, $b)`
         ast::OfItems::PatternSet(pattern_set) => {
             let pattern_ids = pattern_set_from_ast(ctx, pattern_set);
@@ -622,6 +710,24 @@ fn of_expr_from_ast(
         }
     };
 
+    // If the set of patterns is empty, `any of them` is always false and
+    // `none of them` is always true.
+    if num_items == 0 {
+        let value = match quantifier {
+            Quantifier::Any => Some(false),
+            Quantifier::None => Some(true),
+            _ => None,
+        };
+        if let Some(value) = value {
+            ctx.warnings.push(Warning::invariant_boolean_expression(
+                ctx.report_builder,
+                value,
+                of.span(),
+                Some("the rule doesn't define any pattern".to_string()),
+            ));
+        }
+    }
+
     // If the quantifier expression is greater than the number of items,
     // the `of` expression is always false.
     if let Quantifier::Expr(expr) = &quantifier {
@@ -694,6 +800,105 @@ fn of_expr_from_ast(
     Ok(Expr::Of(Box::new(Of { quantifier, items, anchor, stack_frame })))
 }
 
+/// Resolves a set of rules into a tuple of boolean expressions, one for each
+/// rule in the set.
+///
+/// The set is resolved at compile time, and only contains rules that belong
+/// to the current namespace and were declared before the current rule. As
+/// rules can't use rules declared after them, the only possible cycle is a
+/// rule that matches its own rule set. In that case the rule is excluded
+/// from the set and a warning is raised.
+fn rule_set_from_ast(
+    ctx: &mut Context,
+    rule_set: &[ast::RuleSetItem],
+) -> Result<Vec<Expr>, CompileError> {
+    let rules = ctx.rules;
+    let current_rule_id = RuleId::from(rules.len() - 1);
+    let mut rule_ids: Vec<RuleId> = Vec::new();
+
+    for item in rule_set {
+        let mut matched = false;
+
+        for (rule_id, rule) in rules.iter().enumerate() {
+            let rule_id = RuleId::from(rule_id);
+
+            if rule.namespace_id != ctx.current_rule.namespace_id {
+                continue;
+            }
+
+            let ident = ctx.ident_pool.get(rule.ident_id).unwrap();
+            let tags =
+                rule.tags.iter().map(|tag| ctx.ident_pool.get(*tag).unwrap());
+
+            if !item.matches(ident, tags) {
+                continue;
+            }
+
+            if rule_id == current_rule_id {
+                ctx.warnings.push(Warning::circular_rule_dependency(
+                    ctx.report_builder,
+                    ident.to_string(),
+                    item.span(),
+                    Some(
+                        "the rule is excluded from the set, as its result is \
+                         unknown while its condition is being evaluated"
+                            .to_string(),
+                    ),
+                ));
+                continue;
+            }
+
+            // Global rules can't depend on non-global rules. See the
+            // comment in `expr_from_ast` for details.
+            if ctx.current_rule.is_global && !rule.is_global {
+                return Err(CompileError::from(
+                    CompileErrorInfo::wrong_rule_dependency(
+                        ctx.report_builder,
+                        ctx.ident_pool
+                            .get(ctx.current_rule.ident_id)
+                            .unwrap()
+                            .to_string(),
+                        ident.to_string(),
+                        ctx.current_rule.ident_span,
+                        rule.ident_span,
+                        item.span(),
+                    ),
+                ));
+            }
+
+            matched = true;
+
+            if !rule_ids.contains(&rule_id) {
+                rule_ids.push(rule_id);
+            }
+        }
+
+        if !matched {
+            return Err(CompileError::from(CompileErrorInfo::empty_rule_set(
+                ctx.report_builder,
+                item.identifier.to_string(),
+                item.span(),
+            )));
+        }
+    }
+
+    for rule_id in rule_ids.iter() {
+        if !ctx.current_rule_deps.contains(rule_id) {
+            ctx.current_rule_deps.push(*rule_id);
+        }
+    }
+
+    Ok(rule_ids
+        .into_iter()
+        .map(|rule_id| Expr::Ident {
+            symbol: Symbol::new(
+                TypeValue::Bool(Value::Unknown),
+                SymbolKind::Rule(rule_id),
+            ),
+        })
+        .collect())
+}
+
 fn for_of_expr_from_ast(
     ctx: &mut Context,
     for_of: &ast::ForOf,
@@ -757,6 +962,12 @@ fn for_in_expr_from_ast(
                 .clone_without_value()]
         }
         Iterable::Expr(expr) => match expr.type_value() {
+            // Arrays can be iterated with a single variable that receives
+            // each item, or with two variables that receive the item's
+            // index and the item itself, as in `for any i, s in arr : (..)`.
+            TypeValue::Array(array) if for_in.variables.len() > 1 => {
+                vec![TypeValue::Integer(Value::Unknown), array.deputy()]
+            }
             TypeValue::Array(array) => vec![array.deputy()],
             TypeValue::Map(map) => match map.as_ref() {
                 Map::IntegerKeys { .. } => {
@@ -791,8 +1002,9 @@ fn for_in_expr_from_ast(
     }
 
     // Create stack frame with capacity for the loop variables, plus 4
-    // temporary variables used for controlling the loop.
-    let mut stack_frame = ctx.vars.new_frame(loop_vars.len() as i32 + 4);
+    // temporary variables used for controlling the loop, and another one
+    // for the array being iterated or the step of the range.
+    let mut stack_frame = ctx.vars.new_frame(loop_vars.len() as i32 + 5);
     let mut symbols = SymbolTable::new();
     let mut variables = Vec::new();
 
@@ -852,13 +1064,111 @@ fn for_in_expr_from_ast(
     })))
 }
 
+fn with_expr_from_ast(
+    ctx: &mut Context,
+    with: &ast::With,
+) -> Result<Expr, CompileError> {
+    // Create stack frame with capacity for the declared variables.
+    let mut stack_frame = ctx.vars.new_frame(with.declarations.len() as i32);
+    let symbols = Rc::new(RefCell::new(SymbolTable::new()));
+
+    // Put the declared variables into scope. The symbol table is initially
+    // empty, and each variable is added to it after processing its
+    // declaration, so each declaration can use the variables declared
+    // before it, as in `with a = 1, b = a + 1 : (..)`.
+    ctx.symbol_table.push(symbols.clone());
+
+    let result =
+        with_declarations_from_ast(ctx, with, &mut stack_frame, &symbols)
+            .and_then(|declarations| {
+                let condition = expr_from_ast(ctx, &with.condition)?;
+                warn_if_not_bool(ctx, condition.ty(), with.condition.span());
+                Ok(Expr::With(Box::new(With { declarations, condition })))
+            });
+
+    // Leaving the condition's scope. Remove the declared variables.
+    ctx.symbol_table.pop();
+
+    ctx.vars.unwind(&stack_frame);
+
+    result
+}
+
+fn with_declarations_from_ast(
+    ctx: &mut Context,
+    with: &ast::With,
+    stack_frame: &mut VarStackFrame,
+    symbols: &RefCell<SymbolTable>,
+) -> Result<Vec<(Var, Expr)>, CompileError> {
+    let mut declarations = Vec::with_capacity(with.declarations.len());
+
+    for declaration in with.declarations.iter() {
+        let expr = expr_from_ast(ctx, &declaration.expression)?;
+
+        // Only values that can be stored in WASM-side variables can be
+        // bound to a name.
+        check_type(
+            ctx,
+            expr.ty(),
+            declaration.expression.span(),
+            &[Type::Integer, Type::Float, Type::Bool, Type::String],
+        )?;
+
+        let var = stack_frame.new_var(expr.ty());
+
+        symbols.borrow_mut().insert(
+            declaration.ident.name,
+            Symbol::new(
+                expr.type_value().clone_without_value(),
+                SymbolKind::WasmVar(var),
+            ),
+        );
+
+        declarations.push((var, expr));
+    }
+
+    Ok(declarations)
+}
+
 fn iterable_from_ast(
     ctx: &mut Context,
     iter: &ast::Iterable,
 ) -> Result<Iterable, CompileError> {
     match iter {
         ast::Iterable::Range(range) => {
-            Ok(Iterable::Range(range_from_ast(ctx, range)?))
+            let filesize_refs = ctx.filesize_refs;
+            let span = range.span;
+            let range = range_from_ast(ctx, range)?;
+            // If `filesize` was used while processing the range, the range
+            // depends on the size of the scanned data.
+            if ctx.policy.forbid_unbounded_loops
+                && ctx.filesize_refs > filesize_refs
+            {
+                return Err(CompileError::from(
+                    CompileErrorInfo::unbounded_loop(ctx.report_builder, span),
+                ));
+            }
+            // Keep track of the number of iterations, which is known only
+            // if both bounds and the step are constant.
+            let step = match &range.step {
+                Some(step) => step.type_value(),
+                None => TypeValue::Integer(Value::Const(1)),
+            };
+            ctx.current_rule_loops.push(
+                match (
+                    range.lower_bound.type_value(),
+                    range.upper_bound.type_value(),
+                    step,
+                ) {
+                    (
+                        TypeValue::Integer(Value::Const(lower)),
+                        TypeValue::Integer(Value::Const(upper)),
+                        TypeValue::Integer(Value::Const(step)),
+                    ) => Some((upper - lower) as u64 / step as u64 + 1),
+                    _ => None,
+                },
+            );
+            Ok(Iterable::Range(range))
         }
         ast::Iterable::Expr(expr) => {
             let span = expr.span();
@@ -931,6 +1241,13 @@ fn range_from_ast(
     let upper_bound =
         Box::new(non_negative_integer_from_ast(ctx, &range.upper_bound)?);
 
+    let step = match &range.step {
+        Some(step) => {
+            Some(Box::new(integer_in_range_from_ast(ctx, step, 1..=i64::MAX)?))
+        }
+        None => None,
+    };
+
     // If both the lower and upper bounds are known at compile time, make sure
     // that lower_bound <= upper_bound. If they are not know (because they are
     // variables, for example) we can't raise an error at compile time but it
@@ -948,7 +1265,7 @@ fn range_from_ast(
         }
     }
 
-    Ok(Range { lower_bound, upper_bound })
+    Ok(Range { lower_bound, upper_bound, step })
 }
 
 fn non_negative_integer_from_ast(
@@ -1082,19 +1399,34 @@ fn func_call_from_ast(
         .map(|arg| expr_from_ast(ctx, arg))
         .collect::<Result<Vec<Expr>, CompileError>>()?;
 
-    let arg_types: Vec<Type> = args.iter().map(|arg| arg.ty()).collect();
-
     let mut expected_args = Vec::new();
     let mut matching_signature = None;
     let func = type_value.as_func();
 
     // Determine if any of the signatures for the called function matches
-    // the provided arguments.
+    // the provided arguments. Arguments of type pattern accept a pattern
+    // identifier without anchor (e.g: `$a` or `$`), which otherwise would
+    // be a boolean expression.
     for (i, signature) in func.signatures().iter().enumerate() {
         let expected_arg_types: Vec<Type> =
             signature.args.iter().map(|arg| arg.ty()).collect();
 
-        if arg_types == expected_arg_types {
+        let args_match = args.len() == expected_arg_types.len()
+            && iter::zip(&args, &expected_arg_types).all(|(arg, expected)| {
+                match expected {
+                    Type::Pattern => matches!(
+                        arg,
+                        Expr::PatternMatch { anchor: MatchAnchor::None, .. }
+                            | Expr::PatternMatchVar {
+                                anchor: MatchAnchor::None,
+                                ..
+                            }
+                    ),
+                    ty => arg.ty() == *ty,
+                }
+            });
+
+        if args_match {
             matching_signature = Some((i, signature.result.clone()));
             break;
         }
@@ -1266,6 +1598,302 @@ fn re_error_to_compile_error(
     }
 }
 
+/// Produce a warning if the value of a boolean expression is known at
+/// compile time, which means that the expression is always true or always
+/// false. `ast` is the expression in AST form, and `expr` is its IR.
+///
+/// No warning is produced for the `true` and `false` keywords and other
+/// literals, as they are obviously constant. Neither for `and`, `or` and
+/// `not` expressions, as their operands are checked individually and the
+/// warning is produced for the operand that makes the whole expression
+/// invariant.
+pub(in crate::compiler) fn warn_if_invariant(
+    ctx: &mut Context,
+    expr: &Expr,
+    ast: &ast::Expr,
+) {
+    if matches!(
+        ast,
+        ast::Expr::True { .. }
+            | ast::Expr::False { .. }
+            | ast::Expr::LiteralInteger(_)
+            | ast::Expr::LiteralFloat(_)
+            | ast::Expr::LiteralString(_)
+            | ast::Expr::And(_)
+            | ast::Expr::Or(_)
+            | ast::Expr::Not(_)
+    ) {
+        return;
+    }
+
+    if let Some(value) = expr.const_bool() {
+        ctx.warnings.push(Warning::invariant_boolean_expression(
+            ctx.report_builder,
+            value,
+            ast.span(),
+            None,
+        ));
+    }
+}
+
+/// Produce a warning for `not` expressions that can never be true because
+/// their operand depends on a module field that may be undefined.
+///
+/// Modules don't set every field for every scanned file, for instance, a
+/// module that parses some file format leaves most fields undefined for
+/// files in other formats. An expression that uses an undefined field is
+/// undefined too, and so is the `not` expression that contains it. As
+/// undefined is equivalent to false, something like `not foo.is_dll()` is
+/// false for files that `foo` doesn't parse, while the author probably
+/// expected it to be true.
+///
+/// No warning is produced if the `not` expression is an operand of an `and`
+/// expression that also has a non-negated operand that uses the same module
+/// (e.g: `foo.is_valid and not foo.is_dll()`), as that operand acts as a
+/// guard that makes the whole `and` false when the module has no data.
+/// The same applies to `defined` expressions in `and` operands, and to
+/// `not defined` expressions in `or` operands. Modules defined with proto3
+/// are ignored, as their fields always have a value.
+pub(in crate::compiler) fn warn_if_negated_undefined_field(
+    ctx: &mut Context,
+    expr: &ast::Expr,
+) {
+    let mut guards = Vec::new();
+    negated_undefined_field(ctx, expr, &mut guards);
+}
+
+fn negated_undefined_field<'src>(
+    ctx: &mut Context,
+    expr: &ast::Expr<'src>,
+    guards: &mut Vec<&'src str>,
+) {
+    match expr {
+        ast::Expr::And(operands) => {
+            let num_guards = guards.len();
+            for operand in operands.operands() {
+                let guard = match operand {
+                    ast::Expr::Defined(defined) => {
+                        module_field(ctx, &defined.operand)
+                    }
+                    ast::Expr::Not(_) => None,
+                    _ => module_field(ctx, operand),
+                };
+                if let Some((module, _, _)) = guard {
+                    guards.push(module);
+                }
+            }
+            for operand in operands.operands() {
+                negated_undefined_field(ctx, operand, guards);
+            }
+            guards.truncate(num_guards);
+        }
+        ast::Expr::Or(operands) => {
+            // In `not defined foo.bar or not foo.bar` the first operand
+            // handles the case in which `foo.bar` is undefined.
+            let num_guards = guards.len();
+            for operand in operands.operands() {
+                if let ast::Expr::Not(not) = operand {
+                    if let ast::Expr::Defined(defined) = &not.operand {
+                        if let Some((module, _, _)) =
+                            module_field(ctx, &defined.operand)
+                        {
+                            guards.push(module);
+                        }
+                    }
+                }
+            }
+            for operand in operands.operands() {
+                negated_undefined_field(ctx, operand, guards);
+            }
+            guards.truncate(num_guards);
+        }
+        ast::Expr::Not(not) => match module_field(ctx, &not.operand) {
+            Some((module, field, field_span)) => {
+                if !guards.contains(&module) {
+                    let note = format!(
+                        "`not` is undefined when its operand is undefined, \
+                         use `not defined {0} or not ...` if this should be \
+                         true when `{0}` is undefined",
+                        field
+                    );
+                    ctx.warnings.push(Warning::negated_undefined_field(
+                        ctx.report_builder,
+                        field,
+                        module.to_string(),
+                        field_span,
+                        Some(note),
+                    ));
+                }
+            }
+            None => negated_undefined_field(ctx, &not.operand, guards),
+        },
+        _ => {}
+    }
+}
+
+/// If the expression uses a field or function from a module, returns the
+/// module's name, the path of the field (e.g: `foo.bar.baz`) and its span.
+/// Only the first of such fields is returned. `and` and `or` expressions
+/// are not inspected, as they are never undefined, even if some of their
+/// operands are. Neither are `defined` expressions, loops and `with`
+/// expressions.
+fn module_field<'src>(
+    ctx: &Context,
+    expr: &ast::Expr<'src>,
+) -> Option<(&'src str, String, Span)> {
+    match expr {
+        ast::Expr::FieldAccess(_) | ast::Expr::FuncCall(_) => {
+            let mut path = String::new();
+            let module = field_path(expr, &mut path)?;
+            let symbol = ctx.symbol_table.lookup(module)?;
+            let is_module = matches!(symbol.kind(), SymbolKind::FieldIndex(_))
+                && matches!(symbol.type_value(), TypeValue::Struct(_));
+            let module_info = BUILTIN_MODULES.get(module)?;
+            let syntax =
+                module_info.root_struct_descriptor.file_descriptor().syntax();
+            if is_module && syntax == Syntax::Proto2 {
+                Some((module, path, expr.span()))
+            } else {
+                None
+            }
+        }
+        ast::Expr::Lookup(lookup) => module_field(ctx, &lookup.primary)
+            .or_else(|| module_field(ctx, &lookup.index)),
+        ast::Expr::Not(expr)
+        | ast::Expr::Minus(expr)
+        | ast::Expr::BitwiseNot(expr) => module_field(ctx, &expr.operand),
+        ast::Expr::Add(expr)
+        | ast::Expr::Sub(expr)
+        | ast::Expr::Mul(expr)
+        | ast::Expr::Div(expr)
+        | ast::Expr::Mod(expr) => {
+            expr.operands().find_map(|operand| module_field(ctx, operand))
+        }
+        ast::Expr::Shl(expr)
+        | ast::Expr::Shr(expr)
+        | ast::Expr::BitwiseAnd(expr)
+        | ast::Expr::BitwiseOr(expr)
+        | ast::Expr::BitwiseXor(expr)
+        | ast::Expr::Eq(expr)
+        | ast::Expr::Ne(expr)
+        | ast::Expr::Lt(expr)
+        | ast::Expr::Gt(expr)
+        | ast::Expr::Le(expr)
+        | ast::Expr::Ge(expr)
+        | ast::Expr::Contains(expr)
+        | ast::Expr::IContains(expr)
+        | ast::Expr::StartsWith(expr)
+        | ast::Expr::IStartsWith(expr)
+        | ast::Expr::EndsWith(expr)
+        | ast::Expr::IEndsWith(expr)
+        | ast::Expr::IEquals(expr)
+        | ast::Expr::Matches(expr) => module_field(ctx, &expr.lhs)
+            .or_else(|| module_field(ctx, &expr.rhs)),
+        _ => None,
+    }
+}
+
+/// Writes into `path` the textual representation of a chain of field
+/// accesses, lookups and function calls (e.g: `foo.bar[0].baz()`), and
+/// returns the identifier at the root of the chain. Returns `None` if the
+/// expression is not such a chain.
+fn field_path<'src>(
+    expr: &ast::Expr<'src>,
+    path: &mut String,
+) -> Option<&'src str> {
+    match expr {
+        ast::Expr::Ident(ident) => {
+            path.push_str(ident.name);
+            Some(ident.name)
+        }
+        ast::Expr::FieldAccess(expr) => {
+            let root = field_path(&expr.lhs, path)?;
+            path.push('.');
+            field_path(&expr.rhs, path)?;
+            Some(root)
+        }
+        ast::Expr::Lookup(lookup) => {
+            let root = field_path(&lookup.primary, path)?;
+            path.push_str("[..]");
+            Some(root)
+        }
+        ast::Expr::FuncCall(fn_call) => {
+            let root = field_path(&fn_call.callable, path)?;
+            path.push_str("()");
+            Some(root)
+        }
+        _ => None,
+    }
+}
+
+/// Returns an error if the integer overflow behavior is
+/// [`IntegerOverflow::Error`], and the expression is an arithmetic operation
+/// with constant operands that overflows.
+fn check_overflow(
+    ctx: &mut Context,
+    expr: &Expr,
+    span: Span,
+) -> Result<(), CompileError> {
+    if ctx.integer_overflow == IntegerOverflow::Error && expr.overflows() {
+        return Err(CompileError::from(
+            CompileErrorInfo::arithmetic_overflow(ctx.report_builder, span),
+        ));
+    }
+    Ok(())
+}
+
+/// Raises a warning if an integer is compared with a float in a way that
+/// can produce unexpected results.
+///
+/// Integers are converted to float before comparing them with a float, but
+/// integers with an absolute value larger than 2^53 can't be represented
+/// exactly as a float. Also, integers are never equal to a float with a
+/// fractional part. `equality` indicates whether the comparison is `==` or
+/// `!=`.
+fn warn_if_lossy_comparison(
+    ctx: &mut Context,
+    lhs: &Expr,
+    rhs: &Expr,
+    lhs_span: Span,
+    rhs_span: Span,
+    equality: bool,
+) {
+    let (int, int_span, float, float_span) = match (lhs.ty(), rhs.ty()) {
+        (Type::Integer, Type::Float) => (lhs, lhs_span, rhs, rhs_span),
+        (Type::Float, Type::Integer) => (rhs, rhs_span, lhs, lhs_span),
+        _ => return,
+    };
+
+    if let TypeValue::Integer(Value::Const(value)) = int.type_value() {
+        if value.unsigned_abs() > 1 << 53 {
+            ctx.warnings.push(Warning::lossy_comparison(
+                ctx.report_builder,
+                format!("`{}` can't be represented exactly as a float", value),
+                int_span,
+                Some(
+                    "integers are converted to float before comparing them with a float"
+                        .to_string(),
+                ),
+            ));
+            return;
+        }
+    }
+
+    if equality {
+        if let TypeValue::Float(Value::Const(value)) = float.type_value() {
+            if value.fract() != 0.0 {
+                ctx.warnings.push(Warning::lossy_comparison(
+                    ctx.report_builder,
+                    "this float has a fractional part, it is never equal to an integer"
+                        .to_string(),
+                    float_span,
+                    None,
+                ));
+            }
+        }
+    }
+}
+
 /// Produce a warning if the expression is not boolean.
 pub(in crate::compiler) fn warn_if_not_bool(
     ctx: &mut Context,
@@ -1304,6 +1932,7 @@ macro_rules! gen_unary_op {
             ctx: &mut Context,
             expr: &ast::UnaryExpr,
         ) -> Result<Expr, CompileError> {
+            let expr_span = expr.span;
             let operand = Box::new(expr_from_ast(ctx, &expr.operand)?);
 
             // The `not` operator accepts integers, floats and strings because
@@ -1316,15 +1945,17 @@ macro_rules! gen_unary_op {
             )?;
 
             let check_fn:
-                Option<fn(&mut Context, &Expr, Span) -> Result<(), CompileError>>
+                Option<fn(&mut Context, &Expr, &ast::Expr) -> Result<(), CompileError>>
                 = $check_fn;
 
             if let Some(check_fn) = check_fn {
-                check_fn(ctx, &operand, expr.operand.span())?;
+                check_fn(ctx, &operand, &expr.operand)?;
             }
 
             let expr = Expr::$variant { operand };
 
+            check_overflow(ctx, &expr, expr_span)?;
+
             if cfg!(feature = "constant-folding") {
                 Ok(expr.fold())
             } else {
@@ -1423,14 +2054,14 @@ macro_rules! gen_n_ary_operation {
                 .collect::<Result<Vec<Expr>, CompileError>>()?;
 
             let check_fn:
-                Option<fn(&mut Context, &Expr, Span) -> Result<(), CompileError>>
+                Option<fn(&mut Context, &Expr, &ast::Expr) -> Result<(), CompileError>>
                 = $check_fn;
 
             // Make sure that all operands have one of the accepted types.
             for (hir, ast) in iter::zip(operands_hir.iter(), expr.operands()) {
                 check_type2(ctx, ast, hir.ty(), accepted_types)?;
                 if let Some(check_fn) = check_fn {
-                    check_fn(ctx, hir, ast.span())?;
+                    check_fn(ctx, hir, ast)?;
                 }
             }
 
@@ -1466,8 +2097,11 @@ macro_rules! gen_n_ary_operation {
                 }
             }
 
+            let expr_span = expr.first().span().combine(&expr.last().span());
             let expr = Expr::$variant { operands: operands_hir };
 
+            check_overflow(ctx, &expr, expr_span)?;
+
             if cfg!(feature = "constant-folding") {
                 Ok(expr.fold())
             } else {
@@ -1491,8 +2125,9 @@ gen_unary_op!(
     // If operands are not boolean they are casted to boolean.
     Type::Bool | Type::Integer | Type::Float | Type::String,
     // Raise warning if the operand is not bool.
-    Some(|ctx, operand, span| {
-        warn_if_not_bool(ctx, operand.ty(), span);
+    Some(|ctx, operand, ast| {
+        warn_if_not_bool(ctx, operand.ty(), ast.span());
+        warn_if_invariant(ctx, operand, ast);
         Ok(())
     })
 );
@@ -1506,8 +2141,9 @@ gen_n_ary_operation!(
     // All operand types can be mixed in a boolean operation, as they
     // are casted to boolean anyways.
     Type::Bool | Type::Integer | Type::Float | Type::String,
-    Some(|ctx, operand, span| {
-        warn_if_not_bool(ctx, operand.ty(), span);
+    Some(|ctx, operand, ast| {
+        warn_if_not_bool(ctx, operand.ty(), ast.span());
+        warn_if_invariant(ctx, operand, ast);
         Ok(())
     })
 );
@@ -1521,8 +2157,9 @@ gen_n_ary_operation!(
     // All operand types can be mixed in a boolean operation, as they
     // are casted to boolean anyways.
     Type::Bool | Type::Integer | Type::Float | Type::String,
-    Some(|ctx, operand, span| {
-        warn_if_not_bool(ctx, operand.ty(), span);
+    Some(|ctx, operand, ast| {
+        warn_if_not_bool(ctx, operand.ty(), ast.span());
+        warn_if_invariant(ctx, operand, ast);
         Ok(())
     })
 );
@@ -1643,7 +2280,10 @@ gen_binary_op!(
     // Integers can be compared with floats, but strings can be
     // compared only with another string.
     Type::Integer | Type::Float,
-    None
+    Some(|ctx, lhs, rhs, lhs_span, rhs_span| {
+        warn_if_lossy_comparison(ctx, lhs, rhs, lhs_span, rhs_span, true);
+        Ok(())
+    })
 );
 
 gen_binary_op!(
@@ -1654,7 +2294,10 @@ gen_binary_op!(
     // Integers can be compared with floats, but strings can be
     // compared only with another string.
     Type::Integer | Type::Float,
-    None
+    Some(|ctx, lhs, rhs, lhs_span, rhs_span| {
+        warn_if_lossy_comparison(ctx, lhs, rhs, lhs_span, rhs_span, true);
+        Ok(())
+    })
 );
 
 gen_binary_op!(
@@ -1665,7 +2308,10 @@ gen_binary_op!(
     // Integers can be compared with floats, but strings can be
     // compared only with another string.
     Type::Integer | Type::Float,
-    None
+    Some(|ctx, lhs, rhs, lhs_span, rhs_span| {
+        warn_if_lossy_comparison(ctx, lhs, rhs, lhs_span, rhs_span, false);
+        Ok(())
+    })
 );
 
 gen_binary_op!(
@@ -1676,7 +2322,10 @@ gen_binary_op!(
     // Integers can be compared with floats, but strings can be
     // compared only with another string.
     Type::Integer | Type::Float,
-    None
+    Some(|ctx, lhs, rhs, lhs_span, rhs_span| {
+        warn_if_lossy_comparison(ctx, lhs, rhs, lhs_span, rhs_span, false);
+        Ok(())
+    })
 );
 
 gen_binary_op!(
@@ -1687,7 +2336,10 @@ gen_binary_op!(
     // Integers can be compared with floats, but strings can be
     // compared only with another string.
     Type::Integer | Type::Float,
-    None
+    Some(|ctx, lhs, rhs, lhs_span, rhs_span| {
+        warn_if_lossy_comparison(ctx, lhs, rhs, lhs_span, rhs_span, false);
+        Ok(())
+    })
 );
 
 gen_binary_op!(
@@ -1698,7 +2350,10 @@ gen_binary_op!(
     // Integers can be compared with floats, but strings can be
     // compared only with another string.
     Type::Integer | Type::Float,
-    None
+    Some(|ctx, lhs, rhs, lhs_span, rhs_span| {
+        warn_if_lossy_comparison(ctx, lhs, rhs, lhs_span, rhs_span, false);
+        Ok(())
+    })
 );
 
 gen_string_op!(contains_expr_from_ast, Contains);
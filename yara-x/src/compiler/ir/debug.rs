@@ -0,0 +1,342 @@
+/*! Implements [`Debug`] for the IR.
+
+The IR for an expression is printed as a tree, where each node is a line
+that starts with the node's name, followed by the node's children with an
+additional level of indentation. For instance, the IR for the condition
+`$a at 0 and filesize < 100` looks like:
+
+```text
+and
+  pattern_match PatternId(0)
+    at
+      const integer(0)
+  lt
+    filesize
+    const integer(100)
+```
+*/
+
+use std::fmt::{Debug, Formatter, Result};
+
+use crate::compiler::ir::{
+    Expr, Iterable, MatchAnchor, OfItems, Quantifier, Range,
+};
+use crate::symbols::{Symbol, SymbolKind};
+
+impl Debug for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_expr(f, self, 0)
+    }
+}
+
+/// Writes a line with the given indentation level.
+fn write_line(f: &mut Formatter<'_>, level: usize, text: &str) -> Result {
+    writeln!(f, "{:indent$}{}", "", text, indent = level * 2)
+}
+
+fn write_expr(f: &mut Formatter<'_>, expr: &Expr, level: usize) -> Result {
+    let write_all = |f: &mut Formatter<'_>, name: &str, exprs: &[&Expr]| {
+        write_line(f, level, name)?;
+        for expr in exprs {
+            write_expr(f, expr, level + 1)?;
+        }
+        Ok(())
+    };
+
+    match expr {
+        Expr::Const { type_value } => {
+            write_line(f, level, format!("const {:?}", type_value).as_str())
+        }
+        Expr::Filesize => write_line(f, level, "filesize"),
+        Expr::Entrypoint => write_line(f, level, "entrypoint"),
+
+        Expr::Not { operand } => write_all(f, "not", &[operand]),
+        Expr::Minus { operand } => write_all(f, "minus", &[operand]),
+        Expr::BitwiseNot { operand } => {
+            write_all(f, "bitwise_not", &[operand])
+        }
+        Expr::Defined { operand } => write_all(f, "defined", &[operand]),
+
+        Expr::And { operands } => write_all(f, "and", &refs(operands)),
+        Expr::Or { operands } => write_all(f, "or", &refs(operands)),
+        Expr::Add { operands } => write_all(f, "add", &refs(operands)),
+        Expr::Sub { operands } => write_all(f, "sub", &refs(operands)),
+        Expr::Mul { operands } => write_all(f, "mul", &refs(operands)),
+        Expr::Div { operands } => write_all(f, "div", &refs(operands)),
+        Expr::Mod { operands } => write_all(f, "mod", &refs(operands)),
+
+        Expr::BitwiseAnd { lhs, rhs } => {
+            write_all(f, "bitwise_and", &[lhs, rhs])
+        }
+        Expr::BitwiseOr { lhs, rhs } => {
+            write_all(f, "bitwise_or", &[lhs, rhs])
+        }
+        Expr::BitwiseXor { lhs, rhs } => {
+            write_all(f, "bitwise_xor", &[lhs, rhs])
+        }
+        Expr::Shl { lhs, rhs } => write_all(f, "shl", &[lhs, rhs]),
+        Expr::Shr { lhs, rhs } => write_all(f, "shr", &[lhs, rhs]),
+        Expr::Eq { lhs, rhs } => write_all(f, "eq", &[lhs, rhs]),
+        Expr::Ne { lhs, rhs } => write_all(f, "ne", &[lhs, rhs]),
+        Expr::Lt { lhs, rhs } => write_all(f, "lt", &[lhs, rhs]),
+        Expr::Gt { lhs, rhs } => write_all(f, "gt", &[lhs, rhs]),
+        Expr::Le { lhs, rhs } => write_all(f, "le", &[lhs, rhs]),
+        Expr::Ge { lhs, rhs } => write_all(f, "ge", &[lhs, rhs]),
+        Expr::Contains { lhs, rhs } => write_all(f, "contains", &[lhs, rhs]),
+        Expr::IContains { lhs, rhs } => write_all(f, "icontains", &[lhs, rhs]),
+        Expr::StartsWith { lhs, rhs } => {
+            write_all(f, "startswith", &[lhs, rhs])
+        }
+        Expr::IStartsWith { lhs, rhs } => {
+            write_all(f, "istartswith", &[lhs, rhs])
+        }
+        Expr::EndsWith { lhs, rhs } => write_all(f, "endswith", &[lhs, rhs]),
+        Expr::IEndsWith { lhs, rhs } => write_all(f, "iendswith", &[lhs, rhs]),
+        Expr::IEquals { lhs, rhs } => write_all(f, "iequals", &[lhs, rhs]),
+        Expr::Matches { lhs, rhs } => write_all(f, "matches", &[lhs, rhs]),
+        Expr::FieldAccess { lhs, rhs } => {
+            write_all(f, "field_access", &[lhs, rhs])
+        }
+
+        Expr::Ident { symbol } => write_line(
+            f,
+            level,
+            format!("ident {}", symbol_to_string(symbol)).as_str(),
+        ),
+
+        Expr::PatternMatch { pattern_id, anchor } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_match {:?}", pattern_id).as_str(),
+            )?;
+            write_anchor(f, anchor, level + 1)
+        }
+        Expr::PatternMatchVar { symbol, anchor } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_match {}", symbol_to_string(symbol)).as_str(),
+            )?;
+            write_anchor(f, anchor, level + 1)
+        }
+        Expr::PatternCount { pattern_id, range } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_count {:?}", pattern_id).as_str(),
+            )?;
+            write_range(f, range.as_ref(), level + 1)
+        }
+        Expr::PatternCountVar { symbol, range } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_count {}", symbol_to_string(symbol)).as_str(),
+            )?;
+            write_range(f, range.as_ref(), level + 1)
+        }
+        Expr::PatternOffset { pattern_id, index } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_offset {:?}", pattern_id).as_str(),
+            )?;
+            write_index(f, index.as_deref(), level + 1)
+        }
+        Expr::PatternOffsetVar { symbol, index } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_offset {}", symbol_to_string(symbol))
+                    .as_str(),
+            )?;
+            write_index(f, index.as_deref(), level + 1)
+        }
+        Expr::PatternLength { pattern_id, index } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_length {:?}", pattern_id).as_str(),
+            )?;
+            write_index(f, index.as_deref(), level + 1)
+        }
+        Expr::PatternLengthVar { symbol, index } => {
+            write_line(
+                f,
+                level,
+                format!("pattern_length {}", symbol_to_string(symbol))
+                    .as_str(),
+            )?;
+            write_index(f, index.as_deref(), level + 1)
+        }
+
+        Expr::FuncCall(fn_call) => {
+            write_line(
+                f,
+                level,
+                format!("fn_call signature={}", fn_call.signature_index)
+                    .as_str(),
+            )?;
+            write_expr(f, &fn_call.callable, level + 1)?;
+            for arg in fn_call.args.iter() {
+                write_expr(f, arg, level + 1)?;
+            }
+            Ok(())
+        }
+
+        Expr::Of(of) => {
+            write_line(f, level, "of")?;
+            write_quantifier(f, &of.quantifier, level + 1)?;
+            match &of.items {
+                OfItems::PatternSet(patterns) => write_line(
+                    f,
+                    level + 1,
+                    format!("patterns {:?}", patterns).as_str(),
+                )?,
+                OfItems::BoolExprTuple(exprs) => {
+                    write_line(f, level + 1, "tuple")?;
+                    for expr in exprs {
+                        write_expr(f, expr, level + 2)?;
+                    }
+                }
+            }
+            write_anchor(f, &of.anchor, level + 1)
+        }
+
+        Expr::ForOf(for_of) => {
+            write_line(
+                f,
+                level,
+                format!("for_of var={}", for_of.variable.index).as_str(),
+            )?;
+            write_quantifier(f, &for_of.quantifier, level + 1)?;
+            write_line(
+                f,
+                level + 1,
+                format!("patterns {:?}", for_of.pattern_set).as_str(),
+            )?;
+            write_expr(f, &for_of.condition, level + 1)
+        }
+
+        Expr::ForIn(for_in) => {
+            let vars: Vec<String> = for_in
+                .variables
+                .iter()
+                .map(|var| var.index.to_string())
+                .collect();
+            write_line(
+                f,
+                level,
+                format!("for_in vars={}", vars.join(",")).as_str(),
+            )?;
+            write_quantifier(f, &for_in.quantifier, level + 1)?;
+            match &for_in.iterable {
+                Iterable::Range(range) => {
+                    write_range(f, Some(range), level + 1)?
+                }
+                Iterable::ExprTuple(exprs) => {
+                    write_line(f, level + 1, "tuple")?;
+                    for expr in exprs {
+                        write_expr(f, expr, level + 2)?;
+                    }
+                }
+                Iterable::Expr(expr) => write_expr(f, expr, level + 1)?,
+            }
+            write_expr(f, &for_in.condition, level + 1)
+        }
+
+        Expr::With(with) => {
+            write_line(f, level, "with")?;
+            for (var, expr) in with.declarations.iter() {
+                write_line(
+                    f,
+                    level + 1,
+                    format!("var={}", var.index).as_str(),
+                )?;
+                write_expr(f, expr, level + 2)?;
+            }
+            write_expr(f, &with.condition, level + 1)
+        }
+
+        Expr::Lookup(lookup) => {
+            write_all(f, "lookup", &[&lookup.primary, &lookup.index])
+        }
+    }
+}
+
+fn write_anchor(
+    f: &mut Formatter<'_>,
+    anchor: &MatchAnchor,
+    level: usize,
+) -> Result {
+    match anchor {
+        MatchAnchor::None => Ok(()),
+        MatchAnchor::At(expr) => {
+            write_line(f, level, "at")?;
+            write_expr(f, expr, level + 1)
+        }
+        MatchAnchor::In(range) => write_range(f, Some(range), level),
+    }
+}
+
+fn write_range(
+    f: &mut Formatter<'_>,
+    range: Option<&Range>,
+    level: usize,
+) -> Result {
+    if let Some(range) = range {
+        write_line(f, level, "range")?;
+        write_expr(f, &range.lower_bound, level + 1)?;
+        write_expr(f, &range.upper_bound, level + 1)?;
+        if let Some(step) = &range.step {
+            write_line(f, level + 1, "step")?;
+            write_expr(f, step, level + 2)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_index(
+    f: &mut Formatter<'_>,
+    index: Option<&Expr>,
+    level: usize,
+) -> Result {
+    if let Some(index) = index {
+        write_line(f, level, "index")?;
+        write_expr(f, index, level + 1)?;
+    }
+    Ok(())
+}
+
+fn write_quantifier(
+    f: &mut Formatter<'_>,
+    quantifier: &Quantifier,
+    level: usize,
+) -> Result {
+    match quantifier {
+        Quantifier::None => write_line(f, level, "none"),
+        Quantifier::All => write_line(f, level, "all"),
+        Quantifier::Any => write_line(f, level, "any"),
+        Quantifier::Percentage(expr) => {
+            write_line(f, level, "percentage")?;
+            write_expr(f, expr, level + 1)
+        }
+        Quantifier::Expr(expr) => write_expr(f, expr, level),
+    }
+}
+
+fn symbol_to_string(symbol: &Symbol) -> String {
+    let kind = match symbol.kind() {
+        SymbolKind::WasmVar(var) => format!("wasm_var({})", var.index),
+        SymbolKind::HostVar(var) => format!("host_var({})", var.index),
+        SymbolKind::FieldIndex(index) => format!("field({})", index),
+        SymbolKind::Rule(rule_id) => format!("{:?}", rule_id),
+        SymbolKind::Func(_) => "func".to_string(),
+    };
+    format!("{} {:?}", kind, symbol.type_value())
+}
+
+fn refs(exprs: &[Expr]) -> Vec<&Expr> {
+    exprs.iter().collect()
+}
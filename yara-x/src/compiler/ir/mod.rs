@@ -33,7 +33,7 @@ use std::borrow::Cow;
 use std::ops::RangeInclusive;
 
 use bitmask::bitmask;
-use bstr::BStr;
+use bstr::{BStr, ByteSlice};
 use serde::{Deserialize, Serialize};
 
 use crate::compiler::context::{Var, VarStackFrame};
@@ -43,11 +43,14 @@ use crate::types::{Type, TypeValue, Value};
 
 pub(in crate::compiler) use ast2ir::expr_from_ast;
 pub(in crate::compiler) use ast2ir::patterns_from_ast;
+pub(in crate::compiler) use ast2ir::warn_if_invariant;
+pub(in crate::compiler) use ast2ir::warn_if_negated_undefined_field;
 pub(in crate::compiler) use ast2ir::warn_if_not_bool;
 
 use crate::re;
 
 mod ast2ir;
+mod debug;
 mod hex2hir;
 pub mod mask;
 
@@ -74,6 +77,9 @@ bitmask! {
         Fullword             = 0x0040,
         Private              = 0x0080,
         NonAnchorable        = 0x0100,
+        Rot13                = 0x0200,
+        HexAscii             = 0x0400,
+        Utf7                 = 0x0800,
     }
 }
 
@@ -420,6 +426,9 @@ pub(in crate::compiler) enum Expr {
     /// A `for <quantifier> <vars> in ...` expression. (e.g. `for all i in (1..100) : ( ... )`)
     ForIn(Box<ForIn>),
 
+    /// A `with <declarations> : ( ... )` expression. (e.g. `with n = pe.number_of_sections : ( ... )`)
+    With(Box<With>),
+
     /// Array or dictionary lookup expression (e.g. `array[1]`, `dict["key"]`)
     Lookup(Box<Lookup>),
 }
@@ -473,6 +482,14 @@ pub(in crate::compiler) struct ForIn {
     pub stack_frame: VarStackFrame,
 }
 
+/// A `with` expression (e.g `with n = foo.bar() : (..)`)
+pub(in crate::compiler) struct With {
+    /// Each declared variable, together with the expression that produces
+    /// its value.
+    pub declarations: Vec<(Var, Expr)>,
+    pub condition: Expr,
+}
+
 /// A quantifier used in `for` and `of` expressions.
 pub(in crate::compiler) enum Quantifier {
     None,
@@ -518,10 +535,12 @@ pub(in crate::compiler) enum OfItems {
     BoolExprTuple(Vec<Expr>),
 }
 
-/// A pair of values conforming a range (e.g. `(0..10)`).
+/// A pair of values conforming a range (e.g. `(0..10)`), with an optional
+/// step (e.g. `(0..10 step 2)`).
 pub(in crate::compiler) struct Range {
     pub lower_bound: Box<Expr>,
     pub upper_bound: Box<Expr>,
+    pub step: Option<Box<Expr>>,
 }
 
 /// Possible iterable expressions that can use in a [`ForIn`].
@@ -558,7 +577,8 @@ impl Expr {
             | Expr::PatternMatchVar { .. }
             | Expr::Of(_)
             | Expr::ForOf(_)
-            | Expr::ForIn(_) => Type::Bool,
+            | Expr::ForIn(_)
+            | Expr::With(_) => Type::Bool,
 
             Expr::Minus { operand, .. } => match operand.ty() {
                 Type::Integer => Type::Integer,
@@ -626,7 +646,8 @@ impl Expr {
             | Expr::PatternMatchVar { .. }
             | Expr::Of(_)
             | Expr::ForOf(_)
-            | Expr::ForIn(_) => TypeValue::Bool(Value::Unknown),
+            | Expr::ForIn(_)
+            | Expr::With(_) => TypeValue::Bool(Value::Unknown),
 
             Expr::Minus { operand, .. } => match operand.ty() {
                 Type::Integer => TypeValue::Integer(Value::Unknown),
@@ -669,37 +690,146 @@ impl Expr {
         }
     }
 
+    /// Returns the value of the expression if it's a constant.
+    ///
+    /// Integers, floats and strings are casted to bool, returns `None` if
+    /// the expression is not a constant or can't be casted to bool.
+    pub fn const_bool(&self) -> Option<bool> {
+        match self {
+            Expr::Const { type_value } => match type_value {
+                TypeValue::Bool(Value::Const(b)) => Some(*b),
+                TypeValue::Integer(Value::Const(i)) => Some(*i != 0),
+                TypeValue::Float(Value::Const(f)) => Some(*f != 0.0),
+                TypeValue::String(Value::Const(s)) => Some(!s.is_empty()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the expression if it's an integer constant.
+    fn const_integer(&self) -> Option<i64> {
+        match self {
+            Expr::Const {
+                type_value: TypeValue::Integer(Value::Const(i)),
+            } => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the expression if it's a string constant.
+    fn const_string(&self) -> Option<&BStr> {
+        match self {
+            Expr::Const { type_value: TypeValue::String(Value::Const(s)) } => {
+                Some(s.as_bstr())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the expression if it's a numeric constant.
+    fn const_number(&self) -> Option<Number> {
+        match self {
+            Expr::Const {
+                type_value: TypeValue::Integer(Value::Const(i)),
+            } => Some(Number::Integer(*i)),
+            Expr::Const { type_value: TypeValue::Float(Value::Const(f)) } => {
+                Some(Number::Float(*f))
+            }
+            _ => None,
+        }
+    }
+
+    /// Creates a constant boolean expression.
+    fn bool_const(b: bool) -> Self {
+        Expr::Const { type_value: TypeValue::Bool(Value::Const(b)) }
+    }
+
+    /// Creates a constant integer expression.
+    fn integer_const(i: i64) -> Self {
+        Expr::Const { type_value: TypeValue::Integer(Value::Const(i)) }
+    }
+
+    /// Creates a constant expression from a number.
+    fn number_const(n: Number) -> Self {
+        match n {
+            Number::Integer(i) => Expr::integer_const(i),
+            Number::Float(f) => {
+                Expr::Const { type_value: TypeValue::Float(Value::Const(f)) }
+            }
+        }
+    }
+
+    /// Returns true if the expression is an integer addition, subtraction,
+    /// multiplication or negation where all operands are constant, and the
+    /// result overflows.
+    pub fn overflows(&self) -> bool {
+        let all_constant = |operands: &[Expr]| {
+            operands.iter().all(|op| op.const_number().is_some())
+        };
+        match self {
+            Expr::Minus { operand } => {
+                matches!(operand.const_number(), Some(Number::Integer(i)) if i == i64::MIN)
+            }
+            Expr::Add { operands } => {
+                all_constant(operands)
+                    && fold_arithmetic(operands, i64::checked_add, |a, b| {
+                        a + b
+                    })
+                    .is_none()
+            }
+            Expr::Sub { operands } => {
+                all_constant(operands)
+                    && fold_arithmetic(operands, i64::checked_sub, |a, b| {
+                        a - b
+                    })
+                    .is_none()
+            }
+            Expr::Mul { operands } => {
+                all_constant(operands)
+                    && fold_arithmetic(operands, i64::checked_mul, |a, b| {
+                        a * b
+                    })
+                    .is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// Folds the expression if its value can be determined at compile time,
+    /// returning a constant expression in that case. If the value can't be
+    /// determined at compile time, the expression is returned unchanged.
+    ///
+    /// The result of folding an expression must be exactly the same as the
+    /// result produced while evaluating the expression at scan time. For
+    /// instance, divisions by zero are not folded, as they produce an
+    /// undefined result at scan time. Integer operations that overflow are
+    /// not folded either, as their result depends on the overflow behavior
+    /// configured in the compiler (see [`crate::IntegerOverflow`]).
     pub fn fold(self) -> Self {
         match self {
+            Expr::Not { operand } => match operand.const_bool() {
+                Some(b) => Expr::bool_const(!b),
+                None => Expr::Not { operand },
+            },
             Expr::And { mut operands } => {
                 // Retain the operands whose value is unknown or false, and
                 // remove those that are known to be true. True values in
                 // the list of operands don't alter the result of the AND
                 // operation.
-                operands.retain(|op| {
-                    !op.type_value()
-                        .cast_to_bool()
-                        .try_as_bool()
-                        .unwrap_or(false)
-                });
+                operands.retain(|op| !op.const_bool().unwrap_or(false));
 
                 // No operands left, all were true and therefore the AND is
                 // also true.
                 if operands.is_empty() {
-                    return Expr::Const {
-                        // TODO: create a method in TypeValue for making this simpler?
-                        type_value: TypeValue::Bool(Value::Const(true)),
-                    };
+                    return Expr::bool_const(true);
                 }
 
                 // If any of the remaining operands is constant it has to be
                 // false because true values were removed, the result is false
                 // regardless of the operands with unknown values.
-                if operands.iter().any(|op| op.type_value().is_const()) {
-                    return Expr::Const {
-                        // TODO: create a method in TypeValue for making this simpler?
-                        type_value: TypeValue::Bool(Value::Const(false)),
-                    };
+                if operands.iter().any(|op| op.const_bool().is_some()) {
+                    return Expr::bool_const(false);
                 }
 
                 Expr::And { operands }
@@ -709,63 +839,285 @@ impl Expr {
                 // remove those that are known to be false. False values in
                 // the list of operands don't alter the result of the OR
                 // operation.
-                operands.retain(|op| {
-                    op.type_value()
-                        .cast_to_bool()
-                        .try_as_bool()
-                        .unwrap_or(true)
-                });
+                operands.retain(|op| op.const_bool().unwrap_or(true));
 
                 // No operands left, all were false and therefore the OR is
                 // also false.
                 if operands.is_empty() {
-                    return Expr::Const {
-                        // TODO: create a method in TypeValue for making this simpler?
-                        type_value: TypeValue::Bool(Value::Const(false)),
-                    };
+                    return Expr::bool_const(false);
                 }
 
                 // If any of the remaining operands is constant it has to be
                 // true because false values were removed, the result is true
                 // regardless of the operands with unknown values.
-                if operands.iter().any(|op| op.type_value().is_const()) {
-                    return Expr::Const {
-                        // TODO: create a method in TypeValue for making this simpler?
-                        type_value: TypeValue::Bool(Value::Const(true)),
-                    };
+                if operands.iter().any(|op| op.const_bool().is_some()) {
+                    return Expr::bool_const(true);
                 }
 
                 Expr::Or { operands }
             }
-
+            Expr::Minus { operand } => match operand.const_number() {
+                Some(Number::Integer(i)) => match i.checked_neg() {
+                    Some(i) => Expr::integer_const(i),
+                    None => Expr::Minus { operand },
+                },
+                Some(Number::Float(f)) => {
+                    Expr::number_const(Number::Float(-f))
+                }
+                None => Expr::Minus { operand },
+            },
             Expr::Add { operands } => {
-                let mut sum = 0.0;
-                let mut is_float = false;
-                for operand in &operands {
-                    match operand.type_value() {
-                        TypeValue::Integer(Value::Const(v)) => {
-                            sum += v as f64;
-                        }
-                        TypeValue::Float(Value::Const(v)) => {
-                            is_float = true;
-                            sum += v;
-                        }
-                        _ => return Expr::Add { operands },
-                    }
+                match fold_arithmetic(&operands, i64::checked_add, |a, b| {
+                    a + b
+                }) {
+                    Some(n) => Expr::number_const(n),
+                    None => Expr::Add { operands },
+                }
+            }
+            Expr::Sub { operands } => {
+                match fold_arithmetic(&operands, i64::checked_sub, |a, b| {
+                    a - b
+                }) {
+                    Some(n) => Expr::number_const(n),
+                    None => Expr::Sub { operands },
+                }
+            }
+            Expr::Mul { operands } => {
+                match fold_arithmetic(&operands, i64::checked_mul, |a, b| {
+                    a * b
+                }) {
+                    Some(n) => Expr::number_const(n),
+                    None => Expr::Mul { operands },
+                }
+            }
+            Expr::Div { operands } => {
+                // Integer divisions by zero are not folded, their result is
+                // undefined.
+                match fold_arithmetic(&operands, i64::checked_div, |a, b| {
+                    a / b
+                }) {
+                    Some(n) => Expr::number_const(n),
+                    None => Expr::Div { operands },
+                }
+            }
+            Expr::Mod { operands } => {
+                match fold_arithmetic(&operands, i64::checked_rem, |a, b| {
+                    a % b
+                }) {
+                    Some(n) => Expr::number_const(n),
+                    None => Expr::Mod { operands },
+                }
+            }
+            Expr::BitwiseNot { operand } => match operand.const_integer() {
+                Some(i) => Expr::integer_const(!i),
+                None => Expr::BitwiseNot { operand },
+            },
+            Expr::BitwiseAnd { lhs, rhs } => {
+                match (lhs.const_integer(), rhs.const_integer()) {
+                    (Some(a), Some(b)) => Expr::integer_const(a & b),
+                    _ => Expr::BitwiseAnd { lhs, rhs },
+                }
+            }
+            Expr::BitwiseOr { lhs, rhs } => {
+                match (lhs.const_integer(), rhs.const_integer()) {
+                    (Some(a), Some(b)) => Expr::integer_const(a | b),
+                    _ => Expr::BitwiseOr { lhs, rhs },
+                }
+            }
+            Expr::BitwiseXor { lhs, rhs } => {
+                match (lhs.const_integer(), rhs.const_integer()) {
+                    (Some(a), Some(b)) => Expr::integer_const(a ^ b),
+                    _ => Expr::BitwiseXor { lhs, rhs },
                 }
-                if is_float {
-                    Expr::Const {
-                        type_value: TypeValue::Float(Value::Const(sum)),
+            }
+            // In YARA shifting by 64 bits or more produces zero. See the
+            // comment in `emit_shift_op` for details.
+            Expr::Shl { lhs, rhs } => {
+                match (lhs.const_integer(), rhs.const_integer()) {
+                    (Some(_), Some(b)) if b >= 64 => Expr::integer_const(0),
+                    (Some(a), Some(b)) => {
+                        Expr::integer_const(a.wrapping_shl(b as u32))
                     }
-                } else {
-                    Expr::Const {
-                        type_value: TypeValue::Integer(Value::Const(
-                            sum as i64,
-                        )),
+                    _ => Expr::Shl { lhs, rhs },
+                }
+            }
+            Expr::Shr { lhs, rhs } => {
+                match (lhs.const_integer(), rhs.const_integer()) {
+                    (Some(_), Some(b)) if b >= 64 => Expr::integer_const(0),
+                    (Some(a), Some(b)) => {
+                        Expr::integer_const(a.wrapping_shr(b as u32))
                     }
+                    _ => Expr::Shr { lhs, rhs },
+                }
+            }
+            Expr::Eq { lhs, rhs } => {
+                match fold_comparison(&lhs, &rhs, i64::eq, f64::eq, BStr::eq) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Eq { lhs, rhs },
+                }
+            }
+            Expr::Ne { lhs, rhs } => {
+                match fold_comparison(&lhs, &rhs, i64::ne, f64::ne, BStr::ne) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Ne { lhs, rhs },
+                }
+            }
+            Expr::Lt { lhs, rhs } => {
+                match fold_comparison(&lhs, &rhs, i64::lt, f64::lt, BStr::lt) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Lt { lhs, rhs },
+                }
+            }
+            Expr::Gt { lhs, rhs } => {
+                match fold_comparison(&lhs, &rhs, i64::gt, f64::gt, BStr::gt) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Gt { lhs, rhs },
+                }
+            }
+            Expr::Le { lhs, rhs } => {
+                match fold_comparison(&lhs, &rhs, i64::le, f64::le, BStr::le) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Le { lhs, rhs },
+                }
+            }
+            Expr::Ge { lhs, rhs } => {
+                match fold_comparison(&lhs, &rhs, i64::ge, f64::ge, BStr::ge) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Ge { lhs, rhs },
+                }
+            }
+            Expr::Contains { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, false, |a, b| {
+                    a.contains_str(b)
+                }) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::Contains { lhs, rhs },
+                }
+            }
+            Expr::IContains { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, true, |a, b| {
+                    a.contains_str(b)
+                }) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::IContains { lhs, rhs },
+                }
+            }
+            Expr::StartsWith { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, false, |a, b| {
+                    a.starts_with_str(b)
+                }) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::StartsWith { lhs, rhs },
+                }
+            }
+            Expr::IStartsWith { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, true, |a, b| {
+                    a.starts_with_str(b)
+                }) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::IStartsWith { lhs, rhs },
+                }
+            }
+            Expr::EndsWith { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, false, |a, b| {
+                    a.ends_with_str(b)
+                }) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::EndsWith { lhs, rhs },
+                }
+            }
+            Expr::IEndsWith { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, true, |a, b| {
+                    a.ends_with_str(b)
+                }) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::IEndsWith { lhs, rhs },
+                }
+            }
+            Expr::IEquals { lhs, rhs } => {
+                match fold_string_op(&lhs, &rhs, true, |a, b| a == b) {
+                    Some(b) => Expr::bool_const(b),
+                    None => Expr::IEquals { lhs, rhs },
                 }
             }
             _ => self,
         }
     }
 }
+
+/// A numeric constant, used during constant folding.
+#[derive(Clone, Copy)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_float(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+/// Folds an arithmetic operation if all the operands are constant.
+///
+/// Operands are processed from left to right. Integer operands are combined
+/// with `int_op`, until a float operand is found, from that point on the
+/// operation continues with floats. This mimics the code emitted for
+/// arithmetic operations. Returns `None` if some of the operands is not
+/// constant, or if `int_op` returns `None`.
+fn fold_arithmetic(
+    operands: &[Expr],
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Option<Number> {
+    let mut operands = operands.iter();
+    let mut result = operands.next()?.const_number()?;
+    for operand in operands {
+        result = match (result, operand.const_number()?) {
+            (Number::Integer(a), Number::Integer(b)) => {
+                Number::Integer(int_op(a, b)?)
+            }
+            (a, b) => Number::Float(float_op(a.as_float(), b.as_float())),
+        };
+    }
+    Some(result)
+}
+
+/// Folds a comparison if both operands are constant.
+///
+/// Integers are compared with floats by converting the integer to float.
+fn fold_comparison(
+    lhs: &Expr,
+    rhs: &Expr,
+    int_op: fn(&i64, &i64) -> bool,
+    float_op: fn(&f64, &f64) -> bool,
+    str_op: fn(&BStr, &BStr) -> bool,
+) -> Option<bool> {
+    if let (Some(a), Some(b)) = (lhs.const_string(), rhs.const_string()) {
+        return Some(str_op(a, b));
+    }
+    match (lhs.const_number()?, rhs.const_number()?) {
+        (Number::Integer(a), Number::Integer(b)) => Some(int_op(&a, &b)),
+        (a, b) => Some(float_op(&a.as_float(), &b.as_float())),
+    }
+}
+
+/// Folds a string operation (e.g: `contains`, `startswith`) if both operands
+/// are constant.
+fn fold_string_op(
+    lhs: &Expr,
+    rhs: &Expr,
+    case_insensitive: bool,
+    op: fn(&BStr, &BStr) -> bool,
+) -> Option<bool> {
+    let lhs = lhs.const_string()?;
+    let rhs = rhs.const_string()?;
+    if case_insensitive {
+        Some(op(lhs.to_lowercase().as_bstr(), rhs.to_lowercase().as_bstr()))
+    } else {
+        Some(op(lhs, rhs))
+    }
+}
@@ -0,0 +1,461 @@
+/*! Support for reading compiled rules produced by previous versions.
+
+Each time the layout of [`Rules`] changes, the format version in the header
+of serialized rules is incremented. The structures in this module mirror the
+layout of [`Rules`] in previous versions of the format, and they can be
+converted into the current [`Rules`], filling the missing information with
+default values.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::rules::{deserialize_wasm_mod, serialize_wasm_mod};
+use crate::compiler::{
+    IdentId, LiteralId, MetaValueInfo, NamespaceId, PatternId, PatternInfo,
+    PatternKind, RegexpId, RuleId, RuleInfo, Rules, SubPattern,
+    SubPatternAtom, SubPatternId,
+};
+use crate::string_pool::{BStringPool, StringPool};
+
+/// Layout of [`Rules`] in version 1 of the format.
+///
+/// Version 1 files don't have a version number in their header, the
+/// `YARA-X` magic is followed directly by the serialized rules. Compared
+/// to the current version, version 1 doesn't have information about rule
+/// tags, metadata and pattern kinds and modifiers. Pattern kinds are
+/// inferred while converting the rules, see [`infer_patterns`].
+#[derive(Serialize, Deserialize)]
+pub(in crate::compiler) struct RulesV1 {
+    ident_pool: StringPool<IdentId>,
+    regexp_pool: StringPool<RegexpId>,
+    lit_pool: BStringPool<LiteralId>,
+    #[serde(
+        serialize_with = "serialize_wasm_mod",
+        deserialize_with = "deserialize_wasm_mod"
+    )]
+    wasm_mod: wasmtime::Module,
+    imported_modules: Vec<IdentId>,
+    rules: Vec<RuleInfoV1>,
+    num_patterns: usize,
+    sub_patterns: Vec<(PatternId, SubPattern)>,
+    sub_patterns_anchored_at_0: Vec<SubPatternId>,
+    atoms: Vec<SubPatternAtom>,
+    re_code: Vec<u8>,
+    serialized_globals: Vec<u8>,
+}
+
+/// Layout of [`RuleInfo`] in version 1 of the format.
+#[derive(Serialize, Deserialize)]
+struct RuleInfoV1 {
+    namespace_id: NamespaceId,
+    namespace_ident_id: IdentId,
+    ident_id: IdentId,
+    patterns: Vec<(IdentId, PatternId)>,
+    is_global: bool,
+    is_private: bool,
+}
+
+impl From<RulesV1> for Rules {
+    fn from(rules: RulesV1) -> Self {
+        Rules {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules
+                .rules
+                .into_iter()
+                .map(|rule| RuleInfo {
+                    namespace_id: rule.namespace_id,
+                    namespace_ident_id: rule.namespace_ident_id,
+                    ident_id: rule.ident_id,
+                    ident_span: Default::default(),
+                    tags: Vec::new(),
+                    metadata: Vec::new(),
+                    patterns: rule.patterns,
+                    depends_on_rules: Vec::new(),
+                    depends_on_modules: Vec::new(),
+                    is_global: rule.is_global,
+                    is_private: rule.is_private,
+                    // Source code locations are not available in version 1.
+                    source: None,
+                })
+                .collect(),
+            num_patterns: rules.num_patterns,
+            patterns: infer_patterns(rules.num_patterns, &rules.sub_patterns),
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+            ext_schema: None,
+            ac: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Returns information about patterns in rules from version 1 of the format,
+/// which doesn't store it.
+///
+/// The kind of each pattern is inferred from its sub-patterns. Patterns that
+/// are searched as regular expressions are regexps, and the remaining ones
+/// are text patterns. Hex patterns end up in one group or the other. The
+/// modifiers are unknown, so they are left empty.
+fn infer_patterns(
+    num_patterns: usize,
+    sub_patterns: &[(PatternId, SubPattern)],
+) -> Vec<PatternInfo> {
+    let mut patterns =
+        vec![
+            PatternInfo { kind: PatternKind::Text, modifiers: Vec::new() };
+            num_patterns
+        ];
+
+    for (pattern_id, sub_pattern) in sub_patterns {
+        if let SubPattern::Regexp { .. }
+        | SubPattern::RegexpChainHead { .. }
+        | SubPattern::RegexpChainTail { .. } = sub_pattern
+        {
+            let pattern_id: usize = (*pattern_id).into();
+            if let Some(pattern) = patterns.get_mut(pattern_id) {
+                pattern.kind = PatternKind::Regexp;
+            }
+        }
+    }
+
+    patterns
+}
+
+#[cfg(test)]
+impl From<Rules> for RulesV1 {
+    fn from(rules: Rules) -> Self {
+        RulesV1 {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules
+                .rules
+                .into_iter()
+                .map(|rule| RuleInfoV1 {
+                    namespace_id: rule.namespace_id,
+                    namespace_ident_id: rule.namespace_ident_id,
+                    ident_id: rule.ident_id,
+                    patterns: rule.patterns,
+                    is_global: rule.is_global,
+                    is_private: rule.is_private,
+                })
+                .collect(),
+            num_patterns: rules.num_patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+        }
+    }
+}
+
+/// Layout of [`Rules`] in version 2 of the format.
+///
+/// Compared to the current version, version 2 doesn't have information
+/// about the dependencies between rules, and between rules and modules.
+#[derive(Serialize, Deserialize)]
+pub(in crate::compiler) struct RulesV2 {
+    ident_pool: StringPool<IdentId>,
+    regexp_pool: StringPool<RegexpId>,
+    lit_pool: BStringPool<LiteralId>,
+    #[serde(
+        serialize_with = "serialize_wasm_mod",
+        deserialize_with = "deserialize_wasm_mod"
+    )]
+    wasm_mod: wasmtime::Module,
+    imported_modules: Vec<IdentId>,
+    rules: Vec<RuleInfoV2>,
+    num_patterns: usize,
+    patterns: Vec<PatternInfo>,
+    sub_patterns: Vec<(PatternId, SubPattern)>,
+    sub_patterns_anchored_at_0: Vec<SubPatternId>,
+    atoms: Vec<SubPatternAtom>,
+    re_code: Vec<u8>,
+    serialized_globals: Vec<u8>,
+}
+
+/// Layout of [`RuleInfo`] in version 2 of the format.
+#[derive(Serialize, Deserialize)]
+struct RuleInfoV2 {
+    namespace_id: NamespaceId,
+    namespace_ident_id: IdentId,
+    ident_id: IdentId,
+    tags: Vec<IdentId>,
+    metadata: Vec<(IdentId, MetaValueInfo)>,
+    patterns: Vec<(IdentId, PatternId)>,
+    is_global: bool,
+    is_private: bool,
+}
+
+impl From<RulesV2> for Rules {
+    fn from(rules: RulesV2) -> Self {
+        Rules {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules
+                .rules
+                .into_iter()
+                .map(|rule| RuleInfo {
+                    namespace_id: rule.namespace_id,
+                    namespace_ident_id: rule.namespace_ident_id,
+                    ident_id: rule.ident_id,
+                    ident_span: Default::default(),
+                    tags: rule.tags,
+                    metadata: rule.metadata,
+                    patterns: rule.patterns,
+                    // Dependencies are not available in version 2.
+                    depends_on_rules: Vec::new(),
+                    depends_on_modules: Vec::new(),
+                    is_global: rule.is_global,
+                    is_private: rule.is_private,
+                    // Source code locations are not available in version 2.
+                    source: None,
+                })
+                .collect(),
+            num_patterns: rules.num_patterns,
+            patterns: rules.patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+            ext_schema: None,
+            ac: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl From<Rules> for RulesV2 {
+    fn from(rules: Rules) -> Self {
+        RulesV2 {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules
+                .rules
+                .into_iter()
+                .map(|rule| RuleInfoV2 {
+                    namespace_id: rule.namespace_id,
+                    namespace_ident_id: rule.namespace_ident_id,
+                    ident_id: rule.ident_id,
+                    tags: rule.tags,
+                    metadata: rule.metadata,
+                    patterns: rule.patterns,
+                    is_global: rule.is_global,
+                    is_private: rule.is_private,
+                })
+                .collect(),
+            num_patterns: rules.num_patterns,
+            patterns: rules.patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+        }
+    }
+}
+
+/// Layout of [`Rules`] in version 3 of the format.
+///
+/// Compared to the current version, version 3 doesn't have information
+/// about the location of each rule in the source code.
+#[derive(Serialize, Deserialize)]
+pub(in crate::compiler) struct RulesV3 {
+    ident_pool: StringPool<IdentId>,
+    regexp_pool: StringPool<RegexpId>,
+    lit_pool: BStringPool<LiteralId>,
+    #[serde(
+        serialize_with = "serialize_wasm_mod",
+        deserialize_with = "deserialize_wasm_mod"
+    )]
+    wasm_mod: wasmtime::Module,
+    imported_modules: Vec<IdentId>,
+    rules: Vec<RuleInfoV3>,
+    num_patterns: usize,
+    patterns: Vec<PatternInfo>,
+    sub_patterns: Vec<(PatternId, SubPattern)>,
+    sub_patterns_anchored_at_0: Vec<SubPatternId>,
+    atoms: Vec<SubPatternAtom>,
+    re_code: Vec<u8>,
+    serialized_globals: Vec<u8>,
+}
+
+/// Layout of [`RuleInfo`] in version 3 of the format.
+#[derive(Serialize, Deserialize)]
+struct RuleInfoV3 {
+    namespace_id: NamespaceId,
+    namespace_ident_id: IdentId,
+    ident_id: IdentId,
+    tags: Vec<IdentId>,
+    metadata: Vec<(IdentId, MetaValueInfo)>,
+    patterns: Vec<(IdentId, PatternId)>,
+    depends_on_rules: Vec<RuleId>,
+    depends_on_modules: Vec<IdentId>,
+    is_global: bool,
+    is_private: bool,
+}
+
+impl From<RulesV3> for Rules {
+    fn from(rules: RulesV3) -> Self {
+        Rules {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules
+                .rules
+                .into_iter()
+                .map(|rule| RuleInfo {
+                    namespace_id: rule.namespace_id,
+                    namespace_ident_id: rule.namespace_ident_id,
+                    ident_id: rule.ident_id,
+                    ident_span: Default::default(),
+                    tags: rule.tags,
+                    metadata: rule.metadata,
+                    patterns: rule.patterns,
+                    depends_on_rules: rule.depends_on_rules,
+                    depends_on_modules: rule.depends_on_modules,
+                    is_global: rule.is_global,
+                    is_private: rule.is_private,
+                    // Source code locations are not available in version 3.
+                    source: None,
+                })
+                .collect(),
+            num_patterns: rules.num_patterns,
+            patterns: rules.patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+            ext_schema: None,
+            ac: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl From<Rules> for RulesV3 {
+    fn from(rules: Rules) -> Self {
+        RulesV3 {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules
+                .rules
+                .into_iter()
+                .map(|rule| RuleInfoV3 {
+                    namespace_id: rule.namespace_id,
+                    namespace_ident_id: rule.namespace_ident_id,
+                    ident_id: rule.ident_id,
+                    tags: rule.tags,
+                    metadata: rule.metadata,
+                    patterns: rule.patterns,
+                    depends_on_rules: rule.depends_on_rules,
+                    depends_on_modules: rule.depends_on_modules,
+                    is_global: rule.is_global,
+                    is_private: rule.is_private,
+                })
+                .collect(),
+            num_patterns: rules.num_patterns,
+            patterns: rules.patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+        }
+    }
+}
+
+/// Layout of [`Rules`] in version 4 of the format.
+///
+/// Compared to the current version, version 4 doesn't have the schema of
+/// the `ext` module.
+#[derive(Serialize, Deserialize)]
+pub(in crate::compiler) struct RulesV4 {
+    ident_pool: StringPool<IdentId>,
+    regexp_pool: StringPool<RegexpId>,
+    lit_pool: BStringPool<LiteralId>,
+    #[serde(
+        serialize_with = "serialize_wasm_mod",
+        deserialize_with = "deserialize_wasm_mod"
+    )]
+    wasm_mod: wasmtime::Module,
+    imported_modules: Vec<IdentId>,
+    rules: Vec<RuleInfo>,
+    num_patterns: usize,
+    patterns: Vec<PatternInfo>,
+    sub_patterns: Vec<(PatternId, SubPattern)>,
+    sub_patterns_anchored_at_0: Vec<SubPatternId>,
+    atoms: Vec<SubPatternAtom>,
+    re_code: Vec<u8>,
+    serialized_globals: Vec<u8>,
+}
+
+impl From<RulesV4> for Rules {
+    fn from(rules: RulesV4) -> Self {
+        Rules {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules.rules,
+            num_patterns: rules.num_patterns,
+            patterns: rules.patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+            // The `ext` module can't have a schema in version 4.
+            ext_schema: None,
+            ac: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl From<Rules> for RulesV4 {
+    fn from(rules: Rules) -> Self {
+        RulesV4 {
+            ident_pool: rules.ident_pool,
+            regexp_pool: rules.regexp_pool,
+            lit_pool: rules.lit_pool,
+            wasm_mod: rules.wasm_mod,
+            imported_modules: rules.imported_modules,
+            rules: rules.rules,
+            num_patterns: rules.num_patterns,
+            patterns: rules.patterns,
+            sub_patterns: rules.sub_patterns,
+            sub_patterns_anchored_at_0: rules.sub_patterns_anchored_at_0,
+            atoms: rules.atoms,
+            re_code: rules.re_code,
+            serialized_globals: rules.serialized_globals,
+        }
+    }
+}
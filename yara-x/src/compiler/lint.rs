@@ -0,0 +1,98 @@
+use yara_x_parser::ast;
+use yara_x_parser::ast::Span;
+
+/// A custom semantic check that is applied to every rule being compiled.
+///
+/// Lint passes allow enforcing house rules, like naming conventions,
+/// mandatory metadata or banned constructs, during the same compilation
+/// pass that produces the [`crate::Rules`]. Each lint pass receives the
+/// AST of every rule after the rule's condition has been successfully
+/// analyzed by the compiler, and it can report any number of errors and
+/// warnings. Errors make the compilation fail, while warnings are added to
+/// the ones produced by the compiler.
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::{Compiler, LintPass, LintReport};
+/// # use yara_x_parser::ast;
+/// struct LowercaseNames;
+///
+/// impl LintPass for LowercaseNames {
+///     fn name(&self) -> &str {
+///         "lowercase_names"
+///     }
+///
+///     fn check_rule(&self, rule: &ast::Rule, report: &mut LintReport) {
+///         if rule.identifier.name.chars().any(|c| c.is_uppercase()) {
+///             report.error(
+///                 "rule names must be lowercase",
+///                 "this name has uppercase characters",
+///                 rule.identifier.span,
+///             );
+///         }
+///     }
+/// }
+///
+/// let mut compiler = Compiler::new();
+///
+/// compiler.add_lint_pass(LowercaseNames);
+///
+/// assert!(compiler.add_source("rule foo { condition: true }").is_ok());
+/// assert!(compiler.add_source("rule Bar { condition: true }").is_err());
+/// ```
+pub trait LintPass {
+    /// Returns the name of this lint pass, which is included in the errors
+    /// and warnings reported by it.
+    fn name(&self) -> &str;
+
+    /// Checks a rule, reporting any issue found in `report`.
+    fn check_rule(&self, rule: &ast::Rule, report: &mut LintReport);
+}
+
+/// Errors and warnings reported by a [`LintPass`] while checking a rule.
+#[derive(Debug, Default)]
+pub struct LintReport {
+    pub(in crate::compiler) errors: Vec<LintDiagnostic>,
+    pub(in crate::compiler) warnings: Vec<LintDiagnostic>,
+}
+
+impl LintReport {
+    /// Reports an error. `message` is the main error message, while `label`
+    /// is the text shown next to the code pointed by `span`.
+    pub fn error<M: Into<String>, L: Into<String>>(
+        &mut self,
+        message: M,
+        label: L,
+        span: Span,
+    ) {
+        self.errors.push(LintDiagnostic {
+            message: message.into(),
+            label: label.into(),
+            span,
+        });
+    }
+
+    /// Reports a warning. `message` is the main warning message, while
+    /// `label` is the text shown next to the code pointed by `span`.
+    pub fn warning<M: Into<String>, L: Into<String>>(
+        &mut self,
+        message: M,
+        label: L,
+        span: Span,
+    ) {
+        self.warnings.push(LintDiagnostic {
+            message: message.into(),
+            label: label.into(),
+            span,
+        });
+    }
+}
+
+/// An error or warning reported by a [`LintPass`].
+#[derive(Debug)]
+pub(in crate::compiler) struct LintDiagnostic {
+    pub message: String,
+    pub label: String,
+    pub span: Span,
+}
@@ -6,12 +6,13 @@ module implements the YARA compiler.
 
 use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::io::Write;
 use std::ops::RangeInclusive;
 use std::path::Path;
 use std::rc::Rc;
-#[cfg(feature = "logging")]
-use std::time::Instant;
-use std::{fmt, iter, u32};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{fmt, iter, mem, u32};
 
 use bincode::Options;
 use bitmask::bitmask;
@@ -25,13 +26,14 @@ use serde::{Deserialize, Serialize};
 use walrus::FunctionId;
 
 use yara_x_parser::ast;
-use yara_x_parser::ast::{HasSpan, Ident, RuleFlag, Span};
+use yara_x_parser::ast::{HasSpan, Ident, RuleFlag, Span, AST};
 use yara_x_parser::report::ReportBuilder;
 use yara_x_parser::warnings::Warning;
-use yara_x_parser::{Parser, SourceCode};
+use yara_x_parser::{Parser, SourceCode, UnusedPatternAction};
 
 use crate::compiler::base64::base64_patterns;
 use crate::compiler::emit::emit_rule_condition;
+use crate::compiler::transform::transformed_literals;
 use crate::compiler::{Context, VarStack};
 use crate::modules::BUILTIN_MODULES;
 use crate::string_pool::{BStringPool, StringPool};
@@ -46,23 +48,47 @@ use crate::wasm::{WasmSymbols, WASM_EXPORTS};
 
 pub(crate) use crate::compiler::atoms::*;
 pub(crate) use crate::compiler::context::*;
+pub(crate) use crate::compiler::ext::*;
 pub(crate) use crate::compiler::ir::*;
 
+#[doc(inline)]
+pub use crate::compiler::cache::*;
 #[doc(inline)]
 pub use crate::compiler::errors::*;
-
+#[doc(inline)]
+pub use crate::compiler::lint::*;
+#[doc(inline)]
+pub use crate::compiler::overflow::*;
+#[doc(inline)]
+pub use crate::compiler::policy::*;
+#[doc(inline)]
+pub use crate::compiler::profiling::*;
 #[doc(inline)]
 pub use crate::compiler::rules::*;
+#[doc(inline)]
+pub use crate::compiler::stats::*;
+#[doc(inline)]
+pub use crate::compiler::template::*;
 use crate::compiler::SubPattern::{Regexp, RegexpChainHead, RegexpChainTail};
 use crate::re;
 use crate::re::hir::ChainedPattern;
 
 mod atoms;
+mod cache;
 mod context;
 mod emit;
 mod errors;
+mod ext;
 mod ir;
+mod legacy;
+mod lint;
+mod overflow;
+mod policy;
+mod profiling;
 mod rules;
+mod stats;
+mod template;
+mod transform;
 
 pub mod base64;
 #[cfg(test)]
@@ -79,7 +105,7 @@ mod tests;
 /// ```rust
 /// # use yara_x;
 /// let rules = yara_x::compile("rule test { condition: true }").unwrap();
-/// let mut scanner = yara_x::Scanner::new(&rules);
+/// let mut scanner = yara_x::Scanner::new(&rules).unwrap();
 /// let results = scanner.scan("Lorem ipsum".as_bytes()).unwrap();
 /// assert_eq!(results.matching_rules().len(), 1);
 /// ```
@@ -92,6 +118,23 @@ where
     Ok(compiler.build())
 }
 
+/// The patterns of each rule in an AST, converted to IR.
+type RulePatterns<'src> = Vec<Result<Vec<Pattern<'src>>, CompileError>>;
+
+/// Converts the patterns of every rule in `ast` from AST to IR.
+///
+/// The conversion doesn't depend on the state of the compiler, which allows
+/// doing it in parallel with other source codes.
+fn rule_patterns<'src>(
+    report_builder: &ReportBuilder,
+    ast: &AST<'src>,
+) -> RulePatterns<'src> {
+    ast.rules
+        .iter()
+        .map(|rule| patterns_from_ast(report_builder, rule.patterns.as_ref()))
+        .collect()
+}
+
 /// Structure that contains information about a rule namespace.
 ///
 /// Includes NamespaceId, the IdentId corresponding to the namespace's
@@ -149,6 +192,12 @@ pub struct Compiler<'a> {
     /// defining new global variables.
     global_symbols: Rc<RefCell<SymbolTable>>,
 
+    /// Symbol table that contains the rules declared with the `export`
+    /// modifier. This symbol table is right above `global_symbols` in the
+    /// `symbol_table`'s stack, and it's shared by all namespaces, which
+    /// makes exported rules visible to every namespace.
+    exported_symbols: Rc<RefCell<SymbolTable>>,
+
     /// Information about the current namespace (i.e: the namespace that will
     /// contain any new rules added via a call to `add_sources`.
     current_namespace: Namespace,
@@ -195,6 +244,10 @@ pub struct Compiler<'a> {
     /// The [`PatternId`] for the pattern being processed.
     current_pattern_id: PatternId,
 
+    /// A vector with information about all the patterns from all the rules.
+    /// A [`PatternId`] is an index in this vector.
+    patterns: Vec<PatternInfo>,
+
     /// A vector with all the sub-patterns from all the rules. A
     /// [`SubPatternId`] is an index in this vector.
     sub_patterns: Vec<(PatternId, SubPattern)>,
@@ -228,8 +281,39 @@ pub struct Compiler<'a> {
     /// Structure where each field corresponds to some global identifier.
     globals_struct: Struct,
 
+    /// Schema of the `ext` module, if provided with
+    /// [`Compiler::ext_module_schema`].
+    ext_schema: Option<ExtSchema>,
+
     /// Warnings generated while compiling the rules.
     warnings: Vec<Warning>,
+
+    /// Restrictions imposed on the rules being compiled.
+    policy: CompilerPolicy,
+
+    /// Behavior of integer arithmetic operations that overflow.
+    integer_overflow: IntegerOverflow,
+
+    /// What to do with patterns that are not used in the rule's condition.
+    unused_pattern_action: UnusedPatternAction,
+
+    /// Custom lint passes applied to every rule.
+    lint_passes: Vec<Box<dyn LintPass>>,
+
+    /// Writer where the IR for rule conditions is written, if any. See
+    /// [`Compiler::set_ir_writer`].
+    ir_writer: Option<Box<dyn Write>>,
+
+    /// Cache where the code produced for the regexps in each source file
+    /// is stored. See [`Compiler::set_cache`].
+    cache: Option<CompilationCache>,
+
+    /// Fragment with the code for the regexps in the source file being
+    /// compiled. It is `None` when no cache is used.
+    fragment: Option<Fragment>,
+
+    /// Profiling information collected while compiling the rules.
+    profiling: ProfilingData,
 }
 
 impl<'a> Compiler<'a> {
@@ -241,19 +325,37 @@ impl<'a> Compiler<'a> {
         // Add symbols for built-in functions like uint8, uint16, etc.
         let global_symbols = symbol_table.push_new();
 
-        for export in WASM_EXPORTS.iter().filter(|e| e.public) {
-            let func = Rc::new(Func::with_signature(FuncSignature::from(
-                export.mangled_name.to_string(),
-            )));
+        // Functions exported by YARA modules are public too, but they are
+        // added to the module's structure when the module is imported, not
+        // here. Overloaded functions have multiple signatures.
+        let mut functions: FxHashMap<&'static str, Func> =
+            FxHashMap::default();
+
+        for export in WASM_EXPORTS.iter().filter(|e| e.public && e.builtin()) {
+            let signature =
+                FuncSignature::from(export.mangled_name.to_string());
+
+            if let Some(function) = functions.get_mut(export.name) {
+                function.add_signature(signature)
+            } else {
+                functions.insert(export.name, Func::with_signature(signature));
+            }
+        }
 
+        for (name, func) in functions.drain() {
+            let func = Rc::new(func);
             let symbol = Symbol::new(
                 TypeValue::Func(func.clone()),
                 SymbolKind::Func(func),
             );
 
-            global_symbols.borrow_mut().insert(export.name, symbol);
+            global_symbols.borrow_mut().insert(name, symbol);
         }
 
+        // Add the symbol table for exported rules on top of the global
+        // symbols.
+        let exported_symbols = symbol_table.push_new();
+
         // Create the default namespace. Rule identifiers will be added to this
         // namespace, unless the user defines some namespace explicitly by calling
         // `Compiler::new_namespace`.
@@ -264,10 +366,11 @@ impl<'a> Compiler<'a> {
         };
 
         // At this point the symbol table (which is a stacked symbol table) has
-        // two layers, the global symbols at the bottom, and the default
-        // namespace on top of it. Calls to `Compiler::new_namespace` replace
-        // the top layer (default namespace) with a new one, but the bottom
-        // layer remains, so the global symbols are shared by all namespaces.
+        // three layers, the global symbols at the bottom, the exported rules
+        // in the middle, and the default namespace on top of them. Calls to
+        // `Compiler::new_namespace` replace the top layer (default namespace)
+        // with a new one, but the other layers remain, so the global symbols
+        // and exported rules are shared by all namespaces.
 
         // Create a WASM module builder. This object is used for building the
         // WASM module that will execute the rule conditions.
@@ -282,6 +385,7 @@ impl<'a> Compiler<'a> {
         Self {
             ident_pool,
             global_symbols,
+            exported_symbols,
             symbol_table,
             wasm_mod,
             wasm_symbols,
@@ -290,7 +394,16 @@ impl<'a> Compiler<'a> {
             current_pattern_id: PatternId(0),
             current_namespace: default_namespace,
             warnings: Vec::new(),
+            policy: CompilerPolicy::default(),
+            integer_overflow: IntegerOverflow::default(),
+            unused_pattern_action: UnusedPatternAction::default(),
+            lint_passes: Vec::new(),
+            ir_writer: None,
+            cache: None,
+            fragment: None,
+            profiling: ProfilingData::default(),
             rules: Vec::new(),
+            patterns: Vec::new(),
             sub_patterns: Vec::new(),
             sub_patterns_anchored_at_start: Vec::new(),
             atoms: Vec::new(),
@@ -298,6 +411,7 @@ impl<'a> Compiler<'a> {
             imported_modules: Vec::new(),
             modules_struct: Struct::new(),
             globals_struct: Struct::new(),
+            ext_schema: None,
             report_builder: ReportBuilder::new(),
             lit_pool: BStringPool::new(),
             regexp_pool: StringPool::new(),
@@ -311,14 +425,207 @@ impl<'a> Compiler<'a> {
     where
         S: Into<SourceCode<'src>>,
     {
-        // Convert `src` into an instance of `SourceCode` if it is something
-        // else, like a &str.
-        let src = src.into();
+        let start = Instant::now();
 
         // Parse the source code and build the Abstract Syntax Tree.
-        let mut ast = Parser::new()
+        let ast = Parser::new()
             .set_report_builder(&self.report_builder)
-            .build_ast(src)?;
+            .unused_patterns(self.unused_pattern_action)
+            .build_ast(src.into())?;
+
+        let patterns = rule_patterns(&self.report_builder, &ast);
+
+        self.add_ast(ast, patterns, start.elapsed())?;
+
+        Ok(self)
+    }
+
+    /// Adds multiple YARA source codes to be compiled in the given namespace.
+    ///
+    /// This is equivalent to calling [`Compiler::new_namespace`] followed by
+    /// [`Compiler::add_source`] for each of the source codes in order, but
+    /// parsing and the conversion of patterns to their intermediate
+    /// representation are done in parallel using all the available CPU
+    /// cores, which can significantly reduce the compilation time of large
+    /// sets of rules. Rule conditions are converted sequentially, in the
+    /// order in which the source codes were provided, because they can
+    /// refer to rules declared in previous source codes. Identifiers, atoms
+    /// and WASM code are also added in that order, so the resulting
+    /// [`Rules`] are exactly the same that would be obtained by adding the
+    /// source codes one by one.
+    ///
+    /// If `namespace` is the current namespace the source codes are added
+    /// to it, and the rules declared there remain visible. If an error
+    /// occurs, the source codes that precede the one that produced the error
+    /// remain added to the compiler, while the ones that follow it are
+    /// ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .add_sources(
+    ///         "my_namespace",
+    ///         [
+    ///             "rule foo { condition: true }",
+    ///             "rule bar { condition: foo }",
+    ///         ],
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(compiler.build().iter().count(), 2);
+    /// ```
+    pub fn add_sources<'src, I, S>(
+        &mut self,
+        namespace: &str,
+        sources: I,
+    ) -> Result<&mut Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<SourceCode<'src>>,
+    {
+        let sources: Vec<SourceCode> =
+            sources.into_iter().map(|src| src.into()).collect();
+
+        if self.ident_pool.get(self.current_namespace.ident_id)
+            != Some(namespace)
+        {
+            self.new_namespace(namespace);
+        }
+
+        if sources.is_empty() {
+            return Ok(self);
+        }
+
+        let num_threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(sources.len());
+
+        let chunk_size = (sources.len() + num_threads - 1) / num_threads;
+        let unused_pattern_action = self.unused_pattern_action;
+
+        // Each thread parses a chunk of consecutive source codes using its
+        // own report builder, and converts the patterns in the resulting
+        // ASTs to IR. The report builders are forked from the compiler's
+        // report builder in a way that guarantees that each source code gets
+        // the same source ID it would get if it was registered with the
+        // compiler's report builder. This is important because the spans in
+        // the ASTs refer to the source code by its ID.
+        let parsed: Vec<_> = thread::scope(|s| {
+            let workers: Vec<_> = sources
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let report_builder =
+                        self.report_builder.fork((i * chunk_size) as u32);
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|src| {
+                                let start = Instant::now();
+                                let ast = Parser::new()
+                                    .set_report_builder(&report_builder)
+                                    .unused_patterns(unused_pattern_action)
+                                    .build_ast(src.clone())
+                                    .map_err(Error::from)
+                                    .map(|ast| {
+                                        let patterns = rule_patterns(
+                                            &report_builder,
+                                            &ast,
+                                        );
+                                        (ast, patterns)
+                                    });
+                                (ast, start.elapsed())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        for (src, (parsed, elapsed)) in sources.iter().zip(parsed) {
+            // The source code must be registered with the compiler's report
+            // builder, as it may be needed for reporting errors and warnings
+            // found while compiling the AST.
+            self.report_builder.register_source(src);
+            let (ast, patterns) = parsed?;
+            self.add_ast(ast, patterns, elapsed)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Adds multiple instances of a [`RuleTemplate`] to be compiled.
+    ///
+    /// Each item in `instances` is the list of parameters for a single
+    /// instance of the template, as `(name, value)` pairs. The template
+    /// is rendered once per instance, and the resulting source codes are
+    /// added to the current namespace with [`Compiler::add_sources`].
+    ///
+    /// The template must produce rules with different names for each
+    /// instance, which is usually achieved by using some parameter as part
+    /// of the rule's name.
+    pub fn add_template<'p, I, P>(
+        &mut self,
+        template: &RuleTemplate,
+        instances: I,
+    ) -> Result<&mut Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: IntoIterator<Item = (&'p str, Variable)>,
+    {
+        let sources = instances
+            .into_iter()
+            .map(|params| template.render(params))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let namespace = self
+            .ident_pool
+            .get(self.current_namespace.ident_id)
+            .unwrap()
+            .to_string();
+
+        self.add_sources(
+            namespace.as_str(),
+            sources.iter().map(|src| {
+                let src = SourceCode::from(src.as_str());
+                match template.origin() {
+                    Some(origin) => src.with_origin(origin),
+                    None => src,
+                }
+            }),
+        )
+    }
+
+    /// Compiles the AST produced by parsing some source code. `patterns`
+    /// contains the patterns of each rule in the AST already converted to
+    /// IR, as returned by [`rule_patterns`]. `parse_time` is the time spent
+    /// parsing the source code and converting the patterns, which is
+    /// included in the profiling information.
+    fn add_ast<'src>(
+        &mut self,
+        mut ast: AST<'src>,
+        patterns: RulePatterns<'src>,
+        parse_time: Duration,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let origin = ast.source.origin().map(|origin| origin.to_string());
+
+        // Load the code for the regexps in this source file, if it was
+        // compiled before.
+        self.fragment = self
+            .cache
+            .as_ref()
+            .map(|cache| cache.get_fragment(&ast.source).unwrap_or_default());
 
         // Process import statements. Checks that all imported modules
         // actually exist, and raise warnings in case of duplicated
@@ -326,17 +633,63 @@ impl<'a> Compiler<'a> {
         // symbol to the current namespace.
         self.process_imports(&ast.imports)?;
 
+        // The origin of the source code is stored in compiled rules, so
+        // that errors occurring at scan time can tell where the rule came
+        // from.
+        let origin_id = origin
+            .as_deref()
+            .map(|origin| self.lit_pool.get_or_intern(origin));
+
+        // Byte offsets where each line in the source code starts, used for
+        // translating the rule spans into line and column numbers.
+        let line_starts = iter::once(0)
+            .chain(ast.source.raw().find_iter("\n").map(|pos| pos + 1))
+            .collect::<Vec<_>>();
+
         // Iterate over the list of declared rules and verify that their
         // conditions are semantically valid. For each rule add a symbol
         // to the current namespace.
-        for rule in &ast.rules {
-            self.process_rule(rule)?;
+        for (rule, patterns) in iter::zip(&ast.rules, patterns) {
+            let span = rule.identifier.span;
+            let line = line_starts.partition_point(|s| *s <= span.start());
+            let line_start = line_starts[line - 1];
+            let column =
+                ast.source.raw()[line_start..span.start()].chars().count() + 1;
+
+            self.process_rule(
+                rule,
+                patterns,
+                SourceRef {
+                    origin: origin_id,
+                    line: line as u32,
+                    column: column as u32,
+                    start: span.start() as u32,
+                    end: span.end() as u32,
+                },
+            )?;
         }
 
         // Transfer the warnings generated by the parser to the compiler
         self.warnings.append(&mut ast.warnings);
 
-        Ok(self)
+        // Store the code for the regexps that were compiled from scratch.
+        // Failing to update the cache is not an error, the source file will
+        // be compiled again the next time.
+        if let (Some(cache), Some(fragment)) =
+            (self.cache.as_ref(), self.fragment.take())
+        {
+            if fragment.is_modified() {
+                let _ = cache.insert_fragment(&ast.source, &fragment);
+            }
+        }
+
+        self.profiling.sources.push(SourceProfile {
+            origin,
+            compile_time: parse_time + start.elapsed(),
+            num_rules: ast.rules.len(),
+        });
+
+        Ok(())
     }
 
     /// Defines a global variable and sets its initial value.
@@ -377,6 +730,34 @@ impl<'a> Compiler<'a> {
         Ok(self)
     }
 
+    /// Defines the structure of the `ext` module.
+    ///
+    /// The `ext` module allows exposing external metadata about the scanned
+    /// files (e.g: detections, submission details, first-seen dates) to the
+    /// rules. The structure of the module is described by `descriptor_set`,
+    /// which is a protobuf `FileDescriptorSet` in serialized form, like the
+    /// ones produced by `protoc --descriptor_set_out`. `root_message` is the
+    /// fully qualified name of the message that describes the module's
+    /// top-level structure.
+    ///
+    /// When scanning, the data for the module must be provided with
+    /// [`crate::Scanner::set_module_data`], as a message of type
+    /// `root_message` serialized in protobuf format.
+    ///
+    /// The schema must be defined before calling [`Compiler::add_source`]
+    /// with some YARA rule that imports the `ext` module.
+    pub fn ext_module_schema(
+        &mut self,
+        descriptor_set: &[u8],
+        root_message: &str,
+    ) -> Result<&mut Self, ExtSchemaError> {
+        if self.modules_struct.field_by_name(EXT_MODULE).is_some() {
+            return Err(ExtSchemaError::AlreadyImported);
+        }
+        self.ext_schema = Some(ExtSchema::new(descriptor_set, root_message)?);
+        Ok(self)
+    }
+
     /// Creates a new namespace.
     ///
     /// Further calls to [`Compiler::add_source`] will put the rules under the
@@ -411,6 +792,22 @@ impl<'a> Compiler<'a> {
     ///
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
+    ///
+    /// Rules declared with the `export` modifier are the exception, they are
+    /// visible to every namespace. Private rules can be exported too, which
+    /// allows sharing helper rules across namespaces without reporting them
+    /// as matches.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// assert!(Compiler::new()
+    ///     .add_source("export private rule foo {condition: true}")?
+    ///     .new_namespace("bar")
+    ///     .add_source("rule bar {condition: foo}")
+    ///     .is_ok());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
     pub fn new_namespace(&mut self, namespace: &str) -> &mut Self {
         // Remove the symbol table corresponding to the previous namespace.
         self.symbol_table.pop().expect("expecting a namespace");
@@ -428,45 +825,15 @@ impl<'a> Compiler<'a> {
     /// Builds the source code previously added to the compiler.
     ///
     /// This function consumes the compiler and returns an instance of
-    /// [`Rules`].
+    /// [`Rules`]. If you want to keep adding more source code after building
+    /// the rules, use [`Compiler::snapshot`] instead.
     pub fn build(self) -> Rules {
         // Finish building the WASM module.
         let wasm_mod = self.wasm_mod.build().emit_wasm();
 
-        #[cfg(feature = "logging")]
-        let start = Instant::now();
-
-        // Compile the WASM module for the current platform. This panics
-        // if the WASM code is invalid, which should not happen as the code is
-        // emitted by YARA itself. If this ever happens is probably because
-        // wrong WASM code is being emitted.
-        let compiled_wasm_mod = wasmtime::Module::from_binary(
-            &crate::wasm::ENGINE,
-            wasm_mod.as_slice(),
-        )
-        .expect("WASM module is not valid");
-
-        #[cfg(feature = "logging")]
-        info!("WASM module build time: {:?}", Instant::elapsed(&start));
-
-        // The structure that contains the global variables is serialized before
-        // being passed to the `Rules` struct. This is because we want `Rules`
-        // to be `Send`, so that it can be shared with scanners running in
-        // different threads. In order for `Rules` to be `Send`, it can't
-        // contain fields that are not `Send`. As `Struct` is not `Send` we
-        // can't have a `Struct` field in `Rules`, so what we have a `Vec<u8>`
-        // with a serialized version of the struct.
-        //
-        // An alternative is changing the `Rc` in some variants of `TypeValue`
-        // to `Arc`, as the root cause that prevents `Struct` from being `Send`
-        // is the use of `Rc` in `TypeValue`.
-        let serialized_globals = bincode::DefaultOptions::new()
-            .serialize(&self.globals_struct)
-            .expect("failed to serialize global variables");
-
         let mut rules = Rules {
-            serialized_globals,
-            wasm_mod: compiled_wasm_mod,
+            serialized_globals: Self::serialize_globals(&self.globals_struct),
+            wasm_mod: Self::compile_wasm_mod(wasm_mod.as_slice()),
             ac: None,
             num_patterns: self.next_pattern_id.0 as usize,
             ident_pool: self.ident_pool,
@@ -474,10 +841,12 @@ impl<'a> Compiler<'a> {
             lit_pool: self.lit_pool,
             imported_modules: self.imported_modules,
             rules: self.rules,
+            patterns: self.patterns,
             sub_patterns: self.sub_patterns,
             sub_patterns_anchored_at_0: self.sub_patterns_anchored_at_start,
             atoms: self.atoms,
             re_code: self.re_code,
+            ext_schema: self.ext_schema,
             warnings: self.warnings,
         };
 
@@ -486,6 +855,164 @@ impl<'a> Compiler<'a> {
         rules
     }
 
+    /// Builds the source code added to the compiler so far, without
+    /// consuming the compiler.
+    ///
+    /// This allows adding more source code with [`Compiler::add_source`]
+    /// after the rules have been built, and calling this function again for
+    /// obtaining a new set of [`Rules`] that includes both the old and the
+    /// new rules. Only the newly added source code is parsed and compiled,
+    /// the rules added before, together with the atoms and literals
+    /// extracted from their patterns, are reused as they are.
+    ///
+    /// This is useful for services that receive a continuous stream of new
+    /// rules, and don't want to compile the whole set of rules every time
+    /// a new rule is added.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x;
+    /// let mut compiler = yara_x::Compiler::new();
+    ///
+    /// compiler.add_source("rule foo { strings: $a = \"foo\" condition: $a }")?;
+    /// let rules = compiler.snapshot();
+    /// assert_eq!(yara_x::Scanner::new(&rules)?.scan(b"foobar")?.matching_rules().len(), 1);
+    ///
+    /// compiler.add_source("rule bar { strings: $a = \"bar\" condition: $a }")?;
+    /// let rules = compiler.snapshot();
+    /// assert_eq!(yara_x::Scanner::new(&rules)?.scan(b"foobar")?.matching_rules().len(), 2);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn snapshot(&mut self) -> Rules {
+        let wasm_mod = self.wasm_mod.emit_wasm();
+
+        let mut rules = Rules {
+            serialized_globals: Self::serialize_globals(&self.globals_struct),
+            wasm_mod: Self::compile_wasm_mod(wasm_mod.as_slice()),
+            ac: None,
+            num_patterns: self.next_pattern_id.0 as usize,
+            ident_pool: self.ident_pool.clone(),
+            regexp_pool: self.regexp_pool.clone(),
+            lit_pool: self.lit_pool.clone(),
+            imported_modules: self.imported_modules.clone(),
+            rules: self.rules.clone(),
+            patterns: self.patterns.clone(),
+            sub_patterns: self.sub_patterns.clone(),
+            sub_patterns_anchored_at_0: self
+                .sub_patterns_anchored_at_start
+                .clone(),
+            atoms: self.atoms.clone(),
+            re_code: self.re_code.clone(),
+            ext_schema: self.ext_schema.clone(),
+            warnings: self.warnings.clone(),
+        };
+
+        rules.build_ac_automaton();
+
+        rules
+    }
+
+    /// Sets the policy that rules must comply with.
+    ///
+    /// The policy applies only to source code added after calling this
+    /// function. Rules that violate the policy are rejected with an error.
+    /// See [`CompilerPolicy`] for details.
+    pub fn policy(&mut self, policy: CompilerPolicy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Specifies the behavior of integer arithmetic operations that
+    /// overflow.
+    ///
+    /// By default the result wraps around. This applies only to source code
+    /// added after calling this function. See [`IntegerOverflow`] for
+    /// details.
+    pub fn integer_overflow(
+        &mut self,
+        behavior: IntegerOverflow,
+    ) -> &mut Self {
+        self.integer_overflow = behavior;
+        self
+    }
+
+    /// Specifies what to do with patterns that are declared by a rule but
+    /// not used in its condition.
+    ///
+    /// By default unused patterns produce an error. With
+    /// [`UnusedPatternAction::Warn`] they produce a warning instead, and
+    /// with [`UnusedPatternAction::Strip`] they also are removed from the
+    /// rule, so that they don't take space in the Aho-Corasick automaton.
+    /// This applies only to source code added after calling this function.
+    pub fn unused_patterns(
+        &mut self,
+        action: UnusedPatternAction,
+    ) -> &mut Self {
+        self.unused_pattern_action = action;
+        self
+    }
+
+    /// Adds a custom lint pass that will check every rule added to the
+    /// compiler after calling this function.
+    ///
+    /// Lint passes are applied in the same order they were added. See
+    /// [`LintPass`] for details.
+    pub fn add_lint_pass<L: LintPass + 'static>(
+        &mut self,
+        lint_pass: L,
+    ) -> &mut Self {
+        self.lint_passes.push(Box::new(lint_pass));
+        self
+    }
+
+    /// Sets a writer where the compiler writes the intermediate
+    /// representation (IR) of each rule's condition.
+    ///
+    /// The IR is written after the condition has been analyzed and
+    /// optimized, right before the code for the condition is emitted. This
+    /// is intended for debugging purposes only, the format of the IR is
+    /// not stable and may change between versions.
+    pub fn set_ir_writer<W: Write + 'static>(&mut self, w: W) -> &mut Self {
+        self.ir_writer = Some(Box::new(w));
+        self
+    }
+
+    /// Sets a cache where the compiler stores the code produced for the
+    /// regular expressions and hex patterns in each source file.
+    ///
+    /// When a source file that was compiled before is added again, the code
+    /// for its regexps is taken from the cache instead of compiling them
+    /// again. See [`CompilationCache`] for details.
+    pub fn set_cache(&mut self, cache: CompilationCache) -> &mut Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Returns profiling information collected while compiling the rules
+    /// added so far.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.add_source(r#"rule foo { strings: $a = "foo" condition: $a }"#)?;
+    ///
+    /// let profiling_data = compiler.profiling_data();
+    /// let rule = &profiling_data.rules()[0];
+    ///
+    /// assert_eq!(rule.name(), "foo");
+    /// assert_eq!(rule.cost().num_atoms(), 1);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn profiling_data(&self) -> &ProfilingData {
+        &self.profiling
+    }
+
     /// Specifies whether the compiler should produce colorful error messages.
     ///
     /// Colorized error messages contain ANSI escape sequences that make them
@@ -510,6 +1037,86 @@ impl<'a> Compiler<'a> {
 }
 
 impl<'a> Compiler<'a> {
+    /// Compiles the WASM module for the current platform.
+    fn compile_wasm_mod(wasm_mod: &[u8]) -> wasmtime::Module {
+        #[cfg(feature = "logging")]
+        let start = Instant::now();
+
+        // Compile the WASM module for the current platform. This panics
+        // if the WASM code is invalid, which should not happen as the code is
+        // emitted by YARA itself. If this ever happens is probably because
+        // wrong WASM code is being emitted.
+        let compiled_wasm_mod =
+            wasmtime::Module::from_binary(&crate::wasm::ENGINE, wasm_mod)
+                .expect("WASM module is not valid");
+
+        #[cfg(feature = "logging")]
+        info!("WASM module build time: {:?}", Instant::elapsed(&start));
+
+        compiled_wasm_mod
+    }
+
+    /// Serializes the structure that contains the global variables.
+    fn serialize_globals(globals_struct: &Struct) -> Vec<u8> {
+        // The structure that contains the global variables is serialized before
+        // being passed to the `Rules` struct. This is because we want `Rules`
+        // to be `Send`, so that it can be shared with scanners running in
+        // different threads. In order for `Rules` to be `Send`, it can't
+        // contain fields that are not `Send`. As `Struct` is not `Send` we
+        // can't have a `Struct` field in `Rules`, so what we have a `Vec<u8>`
+        // with a serialized version of the struct.
+        //
+        // An alternative is changing the `Rc` in some variants of `TypeValue`
+        // to `Arc`, as the root cause that prevents `Struct` from being `Send`
+        // is the use of `Rc` in `TypeValue`.
+        bincode::DefaultOptions::new()
+            .serialize(globals_struct)
+            .expect("failed to serialize global variables")
+    }
+
+    /// Checks if the rule complies with the policy, returns an error if it
+    /// doesn't. Only the restrictions that apply to the rule as a whole are
+    /// checked here, the remaining ones are checked while processing imports
+    /// and rule conditions.
+    fn check_policy(&self, rule: &ast::Rule) -> Result<(), CompileError> {
+        let num_patterns = rule.patterns.as_ref().map_or(0, |p| p.len());
+
+        if let Some(max_patterns) = self.policy.max_patterns_per_rule {
+            if num_patterns > max_patterns {
+                return Err(CompileError::from(
+                    CompileErrorInfo::too_many_patterns(
+                        &self.report_builder,
+                        rule.identifier.name.to_string(),
+                        num_patterns,
+                        max_patterns,
+                        rule.identifier.span,
+                    ),
+                ));
+            }
+        }
+
+        for required in self.policy.required_metadata.iter() {
+            let found = rule
+                .meta
+                .iter()
+                .flatten()
+                .any(|meta| meta.identifier.name == required.as_str());
+
+            if !found {
+                return Err(CompileError::from(
+                    CompileErrorInfo::missing_metadata(
+                        &self.report_builder,
+                        rule.identifier.name.to_string(),
+                        required.clone(),
+                        rule.identifier.span,
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Check if another rule, module or variable has the given identifier and
     /// return an error in that case.
     fn check_for_existing_identifier(
@@ -553,14 +1160,43 @@ impl<'a> Compiler<'a> {
         self.lit_pool.get_or_intern(literal_bytes)
     }
 
-    fn process_rule(&mut self, rule: &ast::Rule) -> Result<(), CompileError> {
+    fn process_rule<'src>(
+        &mut self,
+        rule: &ast::Rule<'src>,
+        patterns: Result<Vec<Pattern<'src>>, CompileError>,
+        source: SourceRef,
+    ) -> Result<(), CompileError> {
+        let start = Instant::now();
+        let atoms_start = self.atoms.len();
+
         // Check if another rule, module or variable has the same identifier
         // and return an error in that case.
         self.check_for_existing_identifier(&rule.identifier)?;
 
-        // Convert the patterns from AST to IR.
-        let patterns =
-            patterns_from_ast(&self.report_builder, rule.patterns.as_ref())?;
+        // Exported rules are visible to every namespace, so they can't have
+        // the same identifier than a rule declared in some other namespace.
+        if rule.flags.contains(RuleFlag::Export) {
+            if let Some(existing_rule) = self.rules.iter().find(|r| {
+                self.ident_pool.get(r.ident_id) == Some(rule.identifier.name)
+            }) {
+                return Err(CompileError::from(
+                    CompileErrorInfo::duplicate_rule(
+                        &self.report_builder,
+                        rule.identifier.name.to_string(),
+                        rule.identifier.span,
+                        existing_rule.ident_span,
+                    ),
+                ));
+            }
+        }
+
+        // Make sure that the rule complies with the policy.
+        self.check_policy(rule)?;
+
+        // The patterns were converted from AST to IR in advance, but errors
+        // are reported at this point, after checking the rule's identifier
+        // and policy, as if the conversion was done here.
+        let patterns = patterns?;
 
         let num_patterns: usize = patterns.len();
 
@@ -584,6 +1220,52 @@ impl<'a> Compiler<'a> {
             patterns_map.insert(pattern_id, pattern);
         }
 
+        for pattern in rule.patterns.iter().flatten() {
+            let (kind, modifiers) = match pattern {
+                ast::Pattern::Text(p) => (PatternKind::Text, &p.modifiers),
+                ast::Pattern::Hex(p) => (PatternKind::Hex, &p.modifiers),
+                ast::Pattern::Regexp(p) => (PatternKind::Regexp, &p.modifiers),
+            };
+            self.patterns.push(PatternInfo {
+                kind,
+                modifiers: modifiers
+                    .iter()
+                    .map(|m| self.lit_pool.get_or_intern(m.to_string()))
+                    .collect(),
+            });
+        }
+
+        let tags = rule
+            .tags
+            .iter()
+            .flatten()
+            .sorted()
+            .map(|tag| self.ident_pool.get_or_intern(tag))
+            .collect();
+
+        let metadata = rule
+            .meta
+            .iter()
+            .flatten()
+            .map(|meta| {
+                let value = match meta.value {
+                    ast::MetaValue::Bool(b) => MetaValueInfo::Bool(b),
+                    ast::MetaValue::Integer(i) => MetaValueInfo::Integer(i),
+                    ast::MetaValue::Float(f) => MetaValueInfo::Float(f),
+                    ast::MetaValue::String(s) => {
+                        // The string includes the enclosing quotes, which
+                        // are removed.
+                        let s = s
+                            .strip_prefix('"')
+                            .and_then(|s| s.strip_suffix('"'))
+                            .unwrap_or(s);
+                        MetaValueInfo::String(self.lit_pool.get_or_intern(s))
+                    }
+                };
+                (self.ident_pool.get_or_intern(meta.identifier.name), value)
+            })
+            .collect();
+
         let rule_id = RuleId(self.rules.len() as i32);
 
         self.rules.push(RuleInfo {
@@ -591,9 +1273,14 @@ impl<'a> Compiler<'a> {
             namespace_ident_id: self.current_namespace.ident_id,
             ident_id: self.ident_pool.get_or_intern(rule.identifier.name),
             ident_span: rule.identifier.span,
+            tags,
+            metadata,
             patterns: ident_and_pattern_ids,
+            depends_on_rules: Vec::new(),
+            depends_on_modules: Vec::new(),
             is_global: rule.flags.contains(RuleFlag::Global),
             is_private: rule.flags.contains(RuleFlag::Private),
+            source: Some(source),
         });
 
         // Create a new symbol of bool type for the rule.
@@ -609,11 +1296,19 @@ impl<'a> Compiler<'a> {
             .symbols
             .as_ref()
             .borrow_mut()
-            .insert(rule.identifier.name, new_symbol);
+            .insert(rule.identifier.name, new_symbol.clone());
 
         // No other symbol with the same identifier should exist.
         assert!(existing_symbol.is_none());
 
+        // Exported rules are also inserted in the symbol table shared by
+        // all namespaces.
+        if rule.flags.contains(RuleFlag::Export) {
+            self.exported_symbols
+                .borrow_mut()
+                .insert(rule.identifier.name, new_symbol);
+        }
+
         let mut ctx = Context {
             current_struct: None,
             current_signature: None,
@@ -625,6 +1320,12 @@ impl<'a> Compiler<'a> {
             rules: &self.rules,
             current_rule: self.rules.last().unwrap(),
             current_rule_patterns: &mut patterns_map,
+            current_rule_deps: Vec::new(),
+            current_module_deps: Vec::new(),
+            filesize_refs: 0,
+            current_rule_loops: Vec::new(),
+            policy: &self.policy,
+            integer_overflow: self.integer_overflow,
             wasm_symbols: &self.wasm_symbols,
             wasm_exports: &self.wasm_exports,
             warnings: &mut self.warnings,
@@ -637,6 +1338,54 @@ impl<'a> Compiler<'a> {
         let mut condition = expr_from_ast(&mut ctx, &rule.condition)?;
 
         warn_if_not_bool(&mut ctx, condition.ty(), rule.condition.span());
+        warn_if_invariant(&mut ctx, &condition, &rule.condition);
+        warn_if_negated_undefined_field(&mut ctx, &rule.condition);
+
+        // Once the rule's condition has been analyzed, apply the custom
+        // lint passes. Errors reported by any of them are returned right
+        // away, while warnings are added to the compiler's warnings.
+        for lint_pass in self.lint_passes.iter() {
+            let mut report = LintReport::default();
+
+            lint_pass.check_rule(rule, &mut report);
+
+            let note =
+                Some(format!("reported by lint pass `{}`", lint_pass.name()));
+
+            for warning in report.warnings {
+                ctx.warnings.push(Warning::lint_warning(
+                    ctx.report_builder,
+                    lint_pass.name().to_string(),
+                    warning.message,
+                    warning.label,
+                    warning.span,
+                    note.clone(),
+                ));
+            }
+
+            if let Some(error) = report.errors.into_iter().next() {
+                return Err(CompileError::from(CompileErrorInfo::lint_error(
+                    ctx.report_builder,
+                    lint_pass.name().to_string(),
+                    error.message,
+                    error.label,
+                    error.span,
+                    note,
+                )));
+            }
+        }
+
+        if let Some(w) = self.ir_writer.as_mut() {
+            // Errors while writing the IR are ignored, they shouldn't
+            // prevent the rule from being compiled.
+            let _ = writeln!(
+                w,
+                "RULE {}:{}\n{:?}",
+                ctx.ident_pool.get(self.current_namespace.ident_id).unwrap(),
+                rule.identifier.name,
+                condition
+            );
+        }
 
         emit_rule_condition(
             &mut ctx,
@@ -650,8 +1399,20 @@ impl<'a> Compiler<'a> {
         // be empty.
         assert_eq!(ctx.vars.used, 0);
 
+        let mut rule_deps = mem::take(&mut ctx.current_rule_deps);
+        let mut module_deps = mem::take(&mut ctx.current_module_deps);
+        let loop_iterations = mem::take(&mut ctx.current_rule_loops);
+
         drop(ctx);
 
+        rule_deps.sort();
+        module_deps.sort_by_key(|id| self.ident_pool.get(*id).unwrap());
+
+        let rule_info = self.rules.last_mut().unwrap();
+
+        rule_info.depends_on_rules = rule_deps;
+        rule_info.depends_on_modules = module_deps;
+
         let patterns_with_span = iter::zip(
             patterns_map,
             rule.patterns.iter().flatten().map(|p| p.span()),
@@ -671,10 +1432,38 @@ impl<'a> Compiler<'a> {
 
         self.next_pattern_id.incr(num_patterns);
 
+        self.profiling.rules.push(RuleProfile {
+            namespace: self
+                .ident_pool
+                .get(self.current_namespace.ident_id)
+                .unwrap()
+                .to_string(),
+            name: rule.identifier.name.to_string(),
+            compile_time: start.elapsed(),
+            cost: RuleCost {
+                atom_qualities: self.atoms[atoms_start..]
+                    .iter()
+                    .map(|atom| atom_quality(atom.as_slice()))
+                    .collect(),
+                loop_iterations,
+            },
+        });
+
         Ok(())
     }
 
     fn process_literal_pattern(&mut self, pattern: LiteralPattern) {
+        // Modifiers like `rot13` or `utf7` change the text searched for,
+        // each of the resulting literals is processed as if it were the
+        // text of the pattern.
+        for text in
+            transformed_literals(pattern.flags, pattern.text.as_bytes())
+        {
+            self.process_literal_text(&pattern, text.as_bytes());
+        }
+    }
+
+    fn process_literal_text(&mut self, pattern: &LiteralPattern, text: &[u8]) {
         let full_word = pattern.flags.contains(PatternFlags::Fullword);
         let mut flags = SubPatternFlagSet::none();
 
@@ -691,7 +1480,7 @@ impl<'a> Compiler<'a> {
         let wide_pattern;
 
         if pattern.flags.contains(PatternFlags::Wide) {
-            wide_pattern = make_wide(pattern.text.as_bytes());
+            wide_pattern = make_wide(text);
             main_patterns.push((
                 wide_pattern.as_slice(),
                 best_atom_from_slice(
@@ -705,11 +1494,8 @@ impl<'a> Compiler<'a> {
 
         if pattern.flags.contains(PatternFlags::Ascii) {
             main_patterns.push((
-                pattern.text.as_bytes(),
-                best_atom_from_slice(
-                    pattern.text.as_bytes(),
-                    DESIRED_ATOM_SIZE,
-                ),
+                text,
+                best_atom_from_slice(text, DESIRED_ATOM_SIZE),
                 flags,
             ));
         }
@@ -1150,38 +1936,69 @@ impl<'a> Compiler<'a> {
         hir: &re::hir::Hir,
         span: Span,
     ) -> Result<Vec<re::compiler::RegexpAtom>, CompileError> {
-        let re_compiler = re::compiler::Compiler::new();
+        let digest = self.fragment.as_ref().map(|_| Fragment::digest(hir));
 
-        let r = match re_compiler.compile(hir) {
-            Ok(r) => r,
-            Err(re::compiler::Error::TooLarge) => {
-                return Err(CompileError::from(
-                    CompileErrorInfo::invalid_regexp(
-                        &self.report_builder,
-                        "regexp is too large".to_string(),
-                        span,
-                    ),
-                ))
+        let cached = digest
+            .as_ref()
+            .and_then(|digest| self.fragment.as_ref()?.get(digest));
+
+        let r = match cached {
+            Some(r) => r.clone(),
+            None => {
+                let re_compiler = re::compiler::Compiler::new();
+
+                let (forward_code, backward_code, atoms) =
+                    match re_compiler.compile(hir) {
+                        Ok(r) => r,
+                        Err(re::compiler::Error::TooLarge) => {
+                            return Err(CompileError::from(
+                                CompileErrorInfo::invalid_regexp(
+                                    &self.report_builder,
+                                    "regexp is too large".to_string(),
+                                    span,
+                                ),
+                            ))
+                        }
+                    };
+
+                let r = CompiledRegexp {
+                    fwd_code: forward_code.into_inner(),
+                    bck_code: backward_code.into_inner(),
+                    atoms,
+                };
+
+                if let (Some(fragment), Some(digest)) =
+                    (self.fragment.as_mut(), digest)
+                {
+                    fragment.insert(digest, r.clone());
+                }
+
+                r
             }
         };
 
-        let (forward_code, backward_code, mut atoms) = r;
+        let CompiledRegexp {
+            fwd_code: mut forward_code,
+            bck_code: mut backward_code,
+            mut atoms,
+        } = r;
 
         // `fwd_code` will contain the offset within the `re_code` vector
         // where the forward code resides.
         let fwd_code = self.re_code.len();
-        self.re_code.append(&mut forward_code.into_inner());
+        self.re_code.append(&mut forward_code);
 
         // `bck_code` will contain the offset within the `re_code` vector
         // where the backward code resides.
         let bck_code = self.re_code.len();
-        self.re_code.append(&mut backward_code.into_inner());
+        self.re_code.append(&mut backward_code);
 
         let mut slow_pattern = false;
 
         // The forward and backward code locations in each atom are relative
-        // to the start of the code generated for this regexp. Here we make
-        // them relative to the start of `re_code`.
+        // to the start of the code generated for this regexp, no matter if
+        // it was compiled now or taken from the cache. Here we make them
+        // relative to the start of `re_code`.
         for atom in atoms.iter_mut() {
             atom.code_loc.fwd += fwd_code;
             atom.code_loc.bck += bck_code;
@@ -1270,17 +2087,60 @@ impl<'a> Compiler<'a> {
             }
 
             let module = module.unwrap();
+            let module_name = import.module_name.as_str();
+
+            if self.policy.forbidden_modules.contains(module_name) {
+                return Err(CompileError::from(
+                    CompileErrorInfo::forbidden_module(
+                        &self.report_builder,
+                        module_name.to_string(),
+                        import.span(),
+                    ),
+                ));
+            }
+
+            // If the module was already imported by some previously added
+            // source code, the structure that describes the module already
+            // exists. In that case the module only needs to be added to the
+            // symbol table for the current namespace.
+            if let Some(field) = self.modules_struct.field_by_name(module_name)
+            {
+                let symbol = Symbol::new(
+                    field.type_value.clone(),
+                    SymbolKind::FieldIndex(
+                        self.modules_struct.index_of(module_name),
+                    ),
+                );
+
+                self.current_namespace
+                    .symbols
+                    .as_ref()
+                    .borrow_mut()
+                    .insert(module_name, symbol);
+
+                continue;
+            }
 
             // Yes, the module exists, add it module to the list of imported
             // modules and the symbol table.
-            let module_name = import.module_name.as_str();
 
             self.imported_modules
                 .push(self.ident_pool.get_or_intern(module_name));
 
+            // The structure of the `ext` module is defined by the user. If
+            // the user provided a schema for the module, use it instead of
+            // the module's built-in structure.
+            let root_struct_descriptor = match &self.ext_schema {
+                Some(schema) if module_name == EXT_MODULE => {
+                    // The schema was validated by `ext_module_schema`.
+                    schema.root_descriptor().unwrap()
+                }
+                _ => module.root_struct_descriptor.clone(),
+            };
+
             // Create the structure that describes the module.
             let mut module_struct = Struct::from_proto_descriptor_and_msg(
-                &module.root_struct_descriptor,
+                &root_struct_descriptor,
                 None,
                 true,
             );
@@ -1472,7 +2332,10 @@ impl From<LiteralId> for u64 {
 pub(crate) struct NamespaceId(i32);
 
 /// ID associated to each rule.
-#[derive(Copy, Clone, Debug)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
 pub(crate) struct RuleId(i32);
 
 impl From<i32> for RuleId {
@@ -1663,7 +2526,7 @@ impl SubPatternFlagSet {
 /// Also, each [`Atom`] is associated to a [`SubPattern`]. When the atom is
 /// found in the scanned data by the Aho-Corasick algorithm, the scanner
 /// verifies that the sub-pattern actually matches.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum SubPattern {
     Literal {
         pattern: LiteralId,
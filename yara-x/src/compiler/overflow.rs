@@ -0,0 +1,34 @@
+/// Behavior of integer additions, subtractions, multiplications and
+/// negations that overflow.
+///
+/// Integers in YARA are 64-bits signed integers. By default, operations that
+/// overflow wrap around, for instance, `0x7fffffffffffffff + 1` is equal to
+/// `-0x8000000000000000`. This is set with [`crate::Compiler::integer_overflow`].
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::{Compiler, IntegerOverflow};
+/// let mut compiler = Compiler::new();
+///
+/// compiler.integer_overflow(IntegerOverflow::Error);
+///
+/// assert!(compiler
+///     .add_source(r#"rule foo { condition: 0x7fffffffffffffff + 1 < 0 }"#)
+///     .is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntegerOverflow {
+    /// The result wraps around at the boundary of the type. This is the
+    /// default, and it's how YARA has always behaved.
+    #[default]
+    Wrap,
+    /// The result is clamped to the minimum or maximum integer value.
+    Saturate,
+    /// The result is undefined.
+    Undefined,
+    /// Overflows in expressions that can be evaluated at compile time, like
+    /// `0x7fffffffffffffff + 1`, are compilation errors. Overflows that
+    /// occur at scan time produce an undefined result.
+    Error,
+}
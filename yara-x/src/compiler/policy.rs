@@ -0,0 +1,78 @@
+use rustc_hash::FxHashSet;
+
+/// Restrictions imposed on the rules accepted by the compiler.
+///
+/// A policy is useful when compiling rules that come from untrusted sources,
+/// as it allows rejecting rules that use some feature considered unsafe or
+/// too expensive in a given environment. Rules that violate the policy
+/// produce a compilation error explaining the reason.
+///
+/// By default the policy doesn't impose any restriction.
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::{Compiler, CompilerPolicy};
+/// let mut compiler = Compiler::new();
+///
+/// compiler.policy(
+///     CompilerPolicy::new()
+///         .forbid_module("console")
+///         .max_patterns_per_rule(100)
+///         .require_metadata("author"),
+/// );
+///
+/// assert!(compiler
+///     .add_source(r#"rule foo { condition: true }"#)
+///     .is_err());
+///
+/// assert!(compiler
+///     .add_source(r#"rule foo { meta: author = "bar" condition: true }"#)
+///     .is_ok());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CompilerPolicy {
+    pub(in crate::compiler) forbidden_modules: FxHashSet<String>,
+    pub(in crate::compiler) forbid_unbounded_loops: bool,
+    pub(in crate::compiler) max_patterns_per_rule: Option<usize>,
+    pub(in crate::compiler) required_metadata: Vec<String>,
+}
+
+impl CompilerPolicy {
+    /// Creates a new policy that doesn't impose any restriction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forbids importing the module with the given name.
+    pub fn forbid_module(mut self, module: &str) -> Self {
+        self.forbidden_modules.insert(module.to_string());
+        self
+    }
+
+    /// Forbids `for` loops that iterate over a range whose bounds depend on
+    /// `filesize`, like in `for any i in (0..filesize) : ( ... )`.
+    ///
+    /// The number of iterations in such loops grows with the size of the
+    /// scanned data, which can make the scan of large files very slow.
+    pub fn forbid_unbounded_loops(mut self, yes: bool) -> Self {
+        self.forbid_unbounded_loops = yes;
+        self
+    }
+
+    /// Sets the maximum number of patterns that a single rule can define.
+    pub fn max_patterns_per_rule(mut self, n: usize) -> Self {
+        self.max_patterns_per_rule = Some(n);
+        self
+    }
+
+    /// Requires every rule to have a metadata entry with the given
+    /// identifier (e.g: `author`, `date`, `hash`).
+    ///
+    /// This function can be called multiple times for requiring multiple
+    /// metadata entries.
+    pub fn require_metadata(mut self, identifier: &str) -> Self {
+        self.required_metadata.push(identifier.to_string());
+        self
+    }
+}
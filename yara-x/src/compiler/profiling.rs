@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+/// Quality of the best atoms that can be extracted from a pattern. Atoms
+/// with this quality or better are considered to have no cost.
+const GOOD_ATOM_QUALITY: i32 = 88;
+
+/// Number of iterations assumed for loops whose number of iterations is not
+/// known at compile time.
+const UNKNOWN_LOOP_ITERATIONS: u64 = 10_000;
+
+/// Profiling information collected while compiling rules.
+///
+/// This is returned by [`crate::Compiler::profiling_data`]. It contains the
+/// time spent compiling each source file and each rule, as well as a rough
+/// estimate of how costly each rule will be at scan time.
+#[derive(Clone, Debug, Default)]
+pub struct ProfilingData {
+    pub(in crate::compiler) sources: Vec<SourceProfile>,
+    pub(in crate::compiler) rules: Vec<RuleProfile>,
+}
+
+impl ProfilingData {
+    /// Profiling information for each source code added to the compiler,
+    /// in the order in which they were added.
+    pub fn sources(&self) -> &[SourceProfile] {
+        self.sources.as_slice()
+    }
+
+    /// Profiling information for each rule, in the order in which they were
+    /// compiled.
+    pub fn rules(&self) -> &[RuleProfile] {
+        self.rules.as_slice()
+    }
+
+    /// Returns the smallest set of rules that account for at least the given
+    /// percentage of the total compilation time, sorted by compilation time
+    /// in descending order.
+    ///
+    /// For example, `top_rules_by_compile_time(80.0)` returns the rules
+    /// that account for 80% of the time spent compiling rules.
+    pub fn top_rules_by_compile_time(
+        &self,
+        percentage: f64,
+    ) -> Vec<&RuleProfile> {
+        self.top_rules(percentage, |rule| rule.compile_time.as_nanos() as f64)
+    }
+
+    /// Returns the smallest set of rules that account for at least the given
+    /// percentage of the total estimated scan cost, sorted by estimated cost
+    /// in descending order.
+    ///
+    /// See [`RuleCost::score`] for details about how the cost is estimated.
+    pub fn top_rules_by_cost(&self, percentage: f64) -> Vec<&RuleProfile> {
+        self.top_rules(percentage, |rule| rule.cost.score() as f64)
+    }
+
+    fn top_rules<F>(&self, percentage: f64, key: F) -> Vec<&RuleProfile>
+    where
+        F: Fn(&RuleProfile) -> f64,
+    {
+        let total: f64 = self.rules.iter().map(&key).sum();
+        let threshold = total * percentage.clamp(0.0, 100.0) / 100.0;
+
+        let mut rules: Vec<&RuleProfile> = self.rules.iter().collect();
+
+        rules.sort_by(|a, b| key(b).total_cmp(&key(a)));
+
+        let mut accumulated = 0.0;
+        let mut result = Vec::new();
+
+        for rule in rules {
+            if accumulated >= threshold {
+                break;
+            }
+            accumulated += key(rule);
+            result.push(rule);
+        }
+
+        result
+    }
+}
+
+/// Profiling information about a source code added to the compiler.
+#[derive(Clone, Debug)]
+pub struct SourceProfile {
+    pub(in crate::compiler) origin: Option<String>,
+    pub(in crate::compiler) compile_time: Duration,
+    pub(in crate::compiler) num_rules: usize,
+}
+
+impl SourceProfile {
+    /// The origin of the source code (usually a file path), if it was
+    /// specified with [`yara_x_parser::SourceCode::with_origin`].
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Time spent parsing and compiling the source code.
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// Number of rules in the source code.
+    pub fn num_rules(&self) -> usize {
+        self.num_rules
+    }
+}
+
+/// Profiling information about a rule.
+#[derive(Clone, Debug)]
+pub struct RuleProfile {
+    pub(in crate::compiler) namespace: String,
+    pub(in crate::compiler) name: String,
+    pub(in crate::compiler) compile_time: Duration,
+    pub(in crate::compiler) cost: RuleCost,
+}
+
+impl RuleProfile {
+    /// Namespace the rule belongs to.
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_str()
+    }
+
+    /// Name of the rule.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Time spent compiling the rule. This doesn't include the time spent
+    /// parsing the source code, nor the time spent building the final
+    /// [`crate::Rules`].
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// Static estimate of the rule's cost at scan time.
+    pub fn cost(&self) -> &RuleCost {
+        &self.cost
+    }
+}
+
+/// Static estimate of the cost of a rule at scan time.
+///
+/// The estimate is based on the quality of the atoms extracted from the
+/// rule's patterns and the number of iterations of the loops in the rule's
+/// condition. Low quality atoms (e.g: short atoms, or atoms with common
+/// bytes like zeroes) tend to appear very often in the scanned data, which
+/// means that the corresponding patterns must be verified very often.
+#[derive(Clone, Debug, Default)]
+pub struct RuleCost {
+    pub(in crate::compiler) atom_qualities: Vec<i32>,
+    pub(in crate::compiler) loop_iterations: Vec<Option<u64>>,
+}
+
+impl RuleCost {
+    /// Number of atoms extracted from the rule's patterns.
+    pub fn num_atoms(&self) -> usize {
+        self.atom_qualities.len()
+    }
+
+    /// Quality of the worst atom extracted from the rule's patterns, or
+    /// `None` if the rule doesn't have atoms. Higher is better.
+    pub fn min_atom_quality(&self) -> Option<i32> {
+        self.atom_qualities.iter().min().cloned()
+    }
+
+    /// Maximum number of iterations among all the `for .. in <range>` loops
+    /// in the rule's condition, or `None` if the rule doesn't have such
+    /// loops.
+    ///
+    /// Loops whose number of iterations is not known at compile time (e.g:
+    /// `for any i in (0..filesize) : (...)`) are not taken into account,
+    /// use [`RuleCost::has_unbounded_loops`] for them.
+    pub fn max_loop_iterations(&self) -> Option<u64> {
+        self.loop_iterations.iter().flatten().max().cloned()
+    }
+
+    /// True if the rule's condition contains some `for .. in <range>` loop
+    /// whose number of iterations is not known at compile time.
+    pub fn has_unbounded_loops(&self) -> bool {
+        self.loop_iterations.iter().any(|i| i.is_none())
+    }
+
+    /// A score that summarizes the estimated cost of the rule. The score
+    /// doesn't have units, it's only useful for comparing rules with each
+    /// other, higher means more costly. The exact formula may change in
+    /// future versions.
+    pub fn score(&self) -> u64 {
+        let atoms_cost: u64 = self
+            .atom_qualities
+            .iter()
+            .map(|q| (GOOD_ATOM_QUALITY - q).max(1) as u64)
+            .sum();
+
+        let loops_cost: u64 = self
+            .loop_iterations
+            .iter()
+            .map(|i| i.unwrap_or(UNKNOWN_LOOP_ITERATIONS))
+            .sum();
+
+        atoms_cost + loops_cost
+    }
+}
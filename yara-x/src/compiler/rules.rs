@@ -1,9 +1,14 @@
+use std::fmt::{Display, Formatter};
 use std::io::{BufWriter, Write};
+use std::ops::Range;
 #[cfg(feature = "logging")]
 use std::time::Instant;
 
 use aho_corasick::AhoCorasick;
 use bincode::Options;
+use bitmask::bitmask;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use itertools::Itertools;
 #[cfg(feature = "logging")]
 use log::*;
 use regex::bytes::{Regex, RegexBuilder};
@@ -13,16 +18,53 @@ use yara_x_parser::ast::Span;
 use yara_x_parser::Warning;
 
 use crate::compiler::atoms::{make_wide, Atom};
+use crate::compiler::legacy::{RulesV1, RulesV2, RulesV3, RulesV4};
 use crate::compiler::{
-    IdentId, Imports, LiteralId, NamespaceId, PatternId, RegexpId, RuleId,
-    SubPattern, SubPatternId,
+    ExtSchema, IdentId, Imports, LiteralId, NamespaceId, PatternId, RegexpId,
+    RuleId, RulesStats, SubPattern, SubPatternId,
 };
 use crate::re::compiler::RegexpAtom;
-use crate::re::instr::{BckCodeLoc, FwdCodeLoc};
+use crate::re::instr::{disassemble, BckCodeLoc, CodeLoc, FwdCodeLoc};
 use crate::string_pool::{BStringPool, StringPool};
 use crate::types::{Regexp, Struct};
 use crate::SerializationError;
 
+/// Magic bytes at the start of serialized rules.
+const MAGIC: &[u8] = b"YARA-X";
+
+/// Version of the format used for serializing rules. This must be
+/// incremented every time the layout of [`Rules`] changes.
+///
+/// The header of serialized rules consists of [`MAGIC`], followed by a
+/// zero byte, the format version and the format flags, both as 32-bits
+/// little-endian integers. Version 1 didn't have a version number nor flags,
+/// its header consisted only in the magic bytes. This is why the zero byte
+/// is required, it distinguishes the version 1 header from newer ones, as
+/// in version 1 the magic bytes are followed by a non-zero byte.
+const FORMAT_VERSION: u32 = 5;
+
+/// Oldest version of the format that can be deserialized.
+const MIN_FORMAT_VERSION: u32 = 1;
+
+/// Magic bytes at the start of serialized rules that have an embedded
+/// signature. These bytes are followed by the 64 bytes of the ed25519
+/// signature, and then by the unsigned rules as produced by
+/// [`Rules::serialize`]. The signature covers the unsigned rules.
+const SIGNED_MAGIC: &[u8] = b"YARA-X-SIGNED";
+
+bitmask! {
+    /// Flags stored in the header of serialized rules.
+    ///
+    /// These flags indicate features that were used while producing the
+    /// rules. Rules that have flags not known by the current version of the
+    /// format can't be deserialized.
+    #[derive(Debug)]
+    pub mask FormatFlagSet: u32 where flags FormatFlags {
+        /// The rules were compiled with constant folding enabled.
+        ConstantFolding = 0x01,
+    }
+}
+
 /// A set of YARA rules in compiled form.
 ///
 /// This is the result from [`crate::Compiler::build`].
@@ -63,6 +105,10 @@ pub struct Rules {
     /// [`PatternId`] +  1.
     pub(in crate::compiler) num_patterns: usize,
 
+    /// Vector with information about each pattern. A [`PatternId`] is an
+    /// index in this vector.
+    pub(in crate::compiler) patterns: Vec<PatternInfo>,
+
     /// Vector with all the sub-patterns from all rules. A [`SubPatternId`]
     /// is an index in this vector. Each pattern is composed of one or more
     /// sub-patterns, if any of the sub-patterns matches, the pattern matches.
@@ -97,6 +143,10 @@ pub struct Rules {
     /// at compile time using [`crate::compiler::Compiler`].
     pub(in crate::compiler) serialized_globals: Vec<u8>,
 
+    /// Schema of the `ext` module, if it was provided with
+    /// [`crate::Compiler::ext_module_schema`].
+    pub(in crate::compiler) ext_schema: Option<ExtSchema>,
+
     /// Aho-Corasick automaton containing the atoms extracted from the patterns.
     /// This allows to search for all the atoms in the scanned data at the same
     /// time in an efficient manner. The automaton is not serialized during when
@@ -123,38 +173,183 @@ impl Rules {
         }
     }
 
+    /// Returns the schema of the `ext` module, if any.
+    pub(crate) fn ext_schema(&self) -> Option<&ExtSchema> {
+        self.ext_schema.as_ref()
+    }
+
     /// Warnings produced while compiling these rules.
     pub fn warnings(&self) -> &[Warning] {
         self.warnings.as_slice()
     }
 
+    /// Returns statistics about the rules, like the number of distinct
+    /// literal strings, the number of states in the Aho-Corasick automaton,
+    /// or the size of each section in the serialized rules.
+    ///
+    /// This is useful for understanding where the space goes when dealing
+    /// with very large sets of rules. Notice that computing the statistics
+    /// involves serializing the rules, which can be slow.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x;
+    /// let rules = yara_x::compile(r#"
+    ///     rule foo { strings: $a = "foobar" condition: $a }
+    ///     rule bar { strings: $a = "foobar" condition: $a }"#).unwrap();
+    ///
+    /// let stats = rules.stats().unwrap();
+    ///
+    /// assert_eq!(stats.num_patterns(), 2);
+    /// assert_eq!(stats.num_literals(), 1);
+    /// ```
+    pub fn stats(&self) -> Result<RulesStats, SerializationError> {
+        RulesStats::new(self)
+    }
+
+    /// An iterator that yields the names of the namespaces that contain at
+    /// least one rule, in the order in which they were created.
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .dedup_by(|a, b| a.namespace_id == b.namespace_id)
+            .map(|rule| self.ident_pool.get(rule.namespace_ident_id).unwrap())
+    }
+
+    /// An iterator that yields all the rules, in the order in which they were
+    /// added to the compiler.
+    ///
+    /// This allows inspecting the compiled rules without having access to
+    /// their source code.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x;
+    /// let rules = yara_x::compile(r#"
+    ///     rule foo : bar baz {
+    ///         meta:
+    ///             author = "qux"
+    ///         strings:
+    ///             $a = "foobar" wide
+    ///         condition:
+    ///             $a
+    ///     }"#).unwrap();
+    ///
+    /// let rule = rules.iter().next().unwrap();
+    ///
+    /// assert_eq!(rule.name(), "foo");
+    /// assert_eq!(rule.tags().collect::<Vec<_>>(), vec!["bar", "baz"]);
+    /// assert_eq!(rule.patterns().next().unwrap().identifier(), "$a");
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = RuleDetails<'_>> {
+        self.rules.iter().enumerate().map(|(i, rule_info)| RuleDetails {
+            rules: self,
+            rule_id: RuleId::from(i),
+            rule_info,
+        })
+    }
+
+    /// Returns the rule identified by `rule_id`.
+    fn rule_details(&self, rule_id: RuleId) -> RuleDetails<'_> {
+        RuleDetails {
+            rules: self,
+            rule_id,
+            rule_info: self.rules.get(usize::from(rule_id)).unwrap(),
+        }
+    }
+
+    /// Returns true if `bytes` look like rules produced by
+    /// [`Rules::serialize`] or [`Rules::serialize_signed`].
+    ///
+    /// Only the header is checked, this doesn't guarantee that the rules can
+    /// be deserialized. This is useful for telling apart compiled rules from
+    /// YARA source code, which never starts with the same bytes.
+    pub fn is_serialized<B>(bytes: B) -> bool
+    where
+        B: AsRef<[u8]>,
+    {
+        // Signed rules start with `YARA-X-SIGNED`, which also starts with
+        // the magic bytes of unsigned rules.
+        bytes.as_ref().starts_with(MAGIC)
+    }
+
     /// Deserializes the rules from a sequence of bytes produced by
     /// [`Rules::serialize`].
+    ///
+    /// Rules produced by [`Rules::serialize_signed`] are also accepted, but
+    /// their signature is not verified. Use [`Rules::deserialize_verified`]
+    /// if the signature must be verified.
     pub fn deserialize<B>(bytes: B) -> Result<Self, SerializationError>
     where
         B: AsRef<[u8]>,
     {
         let bytes = bytes.as_ref();
-        let magic = b"YARA-X";
 
-        if bytes.len() < magic.len() || &bytes[0..magic.len()] != magic {
-            return Err(SerializationError::InvalidFormat);
-        }
-
-        #[cfg(feature = "logging")]
-        let start = Instant::now();
+        let bytes = match Self::split_signature(bytes) {
+            Some((_, unsigned_bytes)) => unsigned_bytes,
+            None => bytes,
+        };
 
-        // Skip the magic and deserialize the remaining data.
-        let mut rules = bincode::DefaultOptions::new()
-            .with_varint_encoding()
-            .deserialize::<Self>(&bytes[magic.len()..])?;
+        Self::deserialize_unsigned(bytes)
+    }
 
-        #[cfg(feature = "logging")]
-        info!("Deserialization time: {:?}", Instant::elapsed(&start));
+    /// Deserializes the rules from a sequence of bytes produced by
+    /// [`Rules::serialize_signed`], verifying that the embedded ed25519
+    /// signature is valid for the given public key.
+    ///
+    /// Returns [`SerializationError::MissingSignature`] if the rules are not
+    /// signed, and [`SerializationError::InvalidSignature`] if the signature
+    /// doesn't match, which means that the rules were signed with a different
+    /// key or were modified after being signed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x;
+    /// let secret_key = [1; 32];
+    /// let public_key = yara_x::Rules::public_key(&secret_key);
+    ///
+    /// let rules = yara_x::compile("rule test { condition: true }")?;
+    /// let signed = rules.serialize_signed(&secret_key)?;
+    ///
+    /// assert!(yara_x::Rules::deserialize_verified(&signed, &public_key).is_ok());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deserialize_verified<B>(
+        bytes: B,
+        public_key: &[u8; 32],
+    ) -> Result<Self, SerializationError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let (signature, unsigned_bytes) =
+            Self::split_signature(bytes.as_ref())
+                .ok_or(SerializationError::MissingSignature)?;
 
-        rules.build_ac_automaton();
+        Self::verify_signature(unsigned_bytes, signature, public_key)?;
+        Self::deserialize_unsigned(unsigned_bytes)
+    }
 
-        Ok(rules)
+    /// Deserializes the rules from a sequence of bytes produced by
+    /// [`Rules::serialize`], verifying them against a detached ed25519
+    /// signature.
+    ///
+    /// The signature must have been computed over the whole sequence of
+    /// bytes, for instance by using [`Rules::sign`].
+    pub fn deserialize_verified_detached<B>(
+        bytes: B,
+        signature: &[u8; 64],
+        public_key: &[u8; 32],
+    ) -> Result<Self, SerializationError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = bytes.as_ref();
+        Self::verify_signature(bytes, signature, public_key)?;
+        Self::deserialize_unsigned(bytes)
     }
 
     /// Serializes the rules as a sequence of bytes.
@@ -167,6 +362,30 @@ impl Rules {
         Ok(bytes.into_inner().unwrap())
     }
 
+    /// Serializes the rules as a sequence of bytes that includes an ed25519
+    /// signature produced with the given secret key.
+    ///
+    /// The [`Rules`] can be restored back, after verifying their signature,
+    /// by passing the bytes to [`Rules::deserialize_verified`] together with
+    /// the public key corresponding to `secret_key`.
+    pub fn serialize_signed(
+        &self,
+        secret_key: &[u8; 32],
+    ) -> Result<Vec<u8>, SerializationError> {
+        let unsigned_bytes = self.serialize()?;
+        let signature = Self::sign(unsigned_bytes.as_slice(), secret_key);
+
+        let mut bytes = Vec::with_capacity(
+            SIGNED_MAGIC.len() + signature.len() + unsigned_bytes.len(),
+        );
+
+        bytes.extend_from_slice(SIGNED_MAGIC);
+        bytes.extend_from_slice(signature.as_slice());
+        bytes.extend_from_slice(unsigned_bytes.as_slice());
+
+        Ok(bytes)
+    }
+
     /// Serializes the rules and writes the bytes into a `writer`.
     pub fn serialize_into<W>(
         &self,
@@ -175,8 +394,17 @@ impl Rules {
     where
         W: Write,
     {
+        #[allow(unused_mut)]
+        let mut flags = FormatFlagSet::none();
+
+        #[cfg(feature = "constant-folding")]
+        flags.set(FormatFlags::ConstantFolding);
+
         // Write file header.
-        writer.write_all(b"YARA-X")?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[0])?;
+        writer.write_all(FORMAT_VERSION.to_le_bytes().as_slice())?;
+        writer.write_all((*flags).to_le_bytes().as_slice())?;
 
         // Serialize rules.
         Ok(bincode::DefaultOptions::new()
@@ -184,6 +412,101 @@ impl Rules {
             .serialize_into(writer, self)?)
     }
 
+    /// Computes the ed25519 signature for a sequence of bytes produced by
+    /// [`Rules::serialize`].
+    ///
+    /// The returned signature can be distributed alongside the serialized
+    /// rules, and verified with [`Rules::deserialize_verified_detached`].
+    pub fn sign(bytes: &[u8], secret_key: &[u8; 32]) -> [u8; 64] {
+        SigningKey::from_bytes(secret_key).sign(bytes).to_bytes()
+    }
+
+    /// Returns the ed25519 public key that corresponds to the given secret
+    /// key.
+    pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+        SigningKey::from_bytes(secret_key).verifying_key().to_bytes()
+    }
+
+    /// Deserializes rules that don't have an embedded signature.
+    fn deserialize_unsigned(bytes: &[u8]) -> Result<Self, SerializationError> {
+        let bytes = bytes
+            .strip_prefix(MAGIC)
+            .ok_or(SerializationError::InvalidFormat)?;
+
+        let (version, bytes) = match bytes.first() {
+            Some(0) => {
+                if bytes.len() < 9 {
+                    return Err(SerializationError::InvalidFormat);
+                }
+                let version =
+                    u32::from_le_bytes(bytes[1..5].try_into().unwrap());
+                let flags =
+                    u32::from_le_bytes(bytes[5..9].try_into().unwrap());
+                if flags & !*FormatFlagSet::all() != 0 {
+                    return Err(SerializationError::UnsupportedFeatures(
+                        flags,
+                    ));
+                }
+                (version, &bytes[9..])
+            }
+            // In version 1 the magic is followed directly by the rules,
+            // without neither version nor flags.
+            _ => (1, bytes),
+        };
+
+        #[cfg(feature = "logging")]
+        let start = Instant::now();
+
+        let options = bincode::DefaultOptions::new().with_varint_encoding();
+
+        let mut rules = match version {
+            FORMAT_VERSION => options.deserialize::<Self>(bytes)?,
+            1 => Rules::from(options.deserialize::<RulesV1>(bytes)?),
+            2 => Rules::from(options.deserialize::<RulesV2>(bytes)?),
+            3 => Rules::from(options.deserialize::<RulesV3>(bytes)?),
+            4 => Rules::from(options.deserialize::<RulesV4>(bytes)?),
+            _ => {
+                return Err(SerializationError::IncompatibleVersion {
+                    found: version,
+                    min_supported: MIN_FORMAT_VERSION,
+                    supported: FORMAT_VERSION,
+                })
+            }
+        };
+
+        #[cfg(feature = "logging")]
+        info!("Deserialization time: {:?}", Instant::elapsed(&start));
+
+        rules.build_ac_automaton();
+
+        Ok(rules)
+    }
+
+    /// If `bytes` contains signed rules, returns the signature and the rest
+    /// of the bytes, which correspond to the unsigned rules. Returns `None`
+    /// if the rules are not signed.
+    fn split_signature(bytes: &[u8]) -> Option<(&[u8; 64], &[u8])> {
+        let bytes = bytes.strip_prefix(SIGNED_MAGIC)?;
+        if bytes.len() < 64 {
+            return None;
+        }
+        let (signature, unsigned_bytes) = bytes.split_at(64);
+        Some((signature.try_into().unwrap(), unsigned_bytes))
+    }
+
+    fn verify_signature(
+        bytes: &[u8],
+        signature: &[u8; 64],
+        public_key: &[u8; 32],
+    ) -> Result<(), SerializationError> {
+        let public_key = VerifyingKey::from_bytes(public_key)
+            .map_err(|_| SerializationError::InvalidPublicKey)?;
+
+        public_key
+            .verify_strict(bytes, &Signature::from_bytes(signature))
+            .map_err(|_| SerializationError::InvalidSignature)
+    }
+
     /// Returns a [`RuleInfo`] given its [`RuleId`].
     ///
     /// # Panics
@@ -193,6 +516,16 @@ impl Rules {
         self.rules.get(rule_id.0 as usize).unwrap()
     }
 
+    /// Returns the location in the source code of the rule identified by
+    /// the given [`RuleId`].
+    ///
+    /// # Panics
+    ///
+    /// If no rule with such [`RuleId`] exists.
+    pub(crate) fn rule_location(&self, rule_id: RuleId) -> RuleLocation {
+        self.rule_details(rule_id).location()
+    }
+
     /// Returns an slice with the individual rules that were compiled.
     #[inline]
     pub(crate) fn rules(&self) -> &[RuleInfo] {
@@ -305,6 +638,18 @@ impl Rules {
         &self.ident_pool
     }
 
+    /// Returns the value of a metadata entry.
+    pub(crate) fn meta_value(&self, value: &MetaValueInfo) -> MetaValue<'_> {
+        match value {
+            MetaValueInfo::Bool(b) => MetaValue::Bool(*b),
+            MetaValueInfo::Integer(i) => MetaValue::Integer(*i),
+            MetaValueInfo::Float(f) => MetaValue::Float(*f),
+            MetaValueInfo::String(lit_id) => {
+                MetaValue::String(self.lit_pool.get_str(*lit_id).unwrap())
+            }
+        }
+    }
+
     #[inline]
     pub(crate) fn globals(&self) -> Struct {
         bincode::DefaultOptions::new()
@@ -318,7 +663,7 @@ impl Rules {
     }
 }
 
-fn serialize_wasm_mod<S>(
+pub(in crate::compiler) fn serialize_wasm_mod<S>(
     wasm_mod: &wasmtime::Module,
     serializer: S,
 ) -> Result<S::Ok, S::Error>
@@ -347,7 +692,7 @@ where
 }
 
 /// Information about each of the individual rules included in [`Rules`].
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct RuleInfo {
     /// The ID of the namespace the rule belongs to.
     pub(crate) namespace_id: NamespaceId,
@@ -360,12 +705,471 @@ pub(crate) struct RuleInfo {
     /// compilation phase, but not during the scan phase.
     #[serde(skip)]
     pub(crate) ident_span: Span,
+    /// Tags associated to the rule.
+    pub(crate) tags: Vec<IdentId>,
+    /// Metadata associated to the rule.
+    pub(crate) metadata: Vec<(IdentId, MetaValueInfo)>,
     /// Vector with all the patterns defined by this rule.
     pub(crate) patterns: Vec<(IdentId, PatternId)>,
+    /// Rules used in the condition of this rule, sorted by [`RuleId`].
+    pub(crate) depends_on_rules: Vec<RuleId>,
+    /// Modules used in the condition of this rule, sorted alphabetically.
+    pub(crate) depends_on_modules: Vec<IdentId>,
     /// True if the rule is global.
     pub(crate) is_global: bool,
     /// True if the rule is private.
     pub(crate) is_private: bool,
+    /// Location of the rule in the source code. This is `None` for rules
+    /// deserialized from a previous version of the format.
+    pub(crate) source: Option<SourceRef>,
+}
+
+/// Compact representation of the location of a rule in the source code.
+///
+/// This is stored in compiled rules, and it allows reporting the location
+/// of a rule in errors that occur at scan time, when the source code is
+/// not available anymore. See [`RuleLocation`].
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct SourceRef {
+    /// Origin of the source code (usually a file path) as a literal in the
+    /// literals pool, or `None` if the origin is unknown.
+    pub(crate) origin: Option<LiteralId>,
+    /// Line where the rule identifier starts, starting at 1.
+    pub(crate) line: u32,
+    /// Column where the rule identifier starts, starting at 1.
+    pub(crate) column: u32,
+    /// Byte offset where the rule identifier starts.
+    pub(crate) start: u32,
+    /// Byte offset where the rule identifier ends.
+    pub(crate) end: u32,
+}
+
+/// Value of a metadata entry in compiled form.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum MetaValueInfo {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(LiteralId),
+}
+
+/// Information about each of the individual patterns included in [`Rules`].
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct PatternInfo {
+    /// The kind of pattern.
+    pub(crate) kind: PatternKind,
+    /// Modifiers associated to the pattern, as they appear in the source code
+    /// (e.g: `wide`, `xor(1-255)`). Each modifier is stored in the literals
+    /// pool.
+    pub(crate) modifiers: Vec<LiteralId>,
+}
+
+/// Each of the possible kinds of patterns.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum PatternKind {
+    /// A text pattern, like `$a = "foo"`.
+    Text,
+    /// A hex pattern, like `$a = { 01 02 03 }`.
+    Hex,
+    /// A regular expression, like `$a = /foo.*bar/`.
+    Regexp,
+}
+
+/// Value associated to a metadata entry in a rule.
+#[derive(Debug, PartialEq)]
+pub enum MetaValue<'r> {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    /// The string as it appears in the source code, without the enclosing
+    /// quotes. Escape sequences are not processed.
+    String(&'r str),
+}
+
+/// Describes a rule included in a set of compiled [`Rules`].
+///
+/// This is the type returned by [`Rules::iter`].
+pub struct RuleDetails<'r> {
+    rules: &'r Rules,
+    rule_id: RuleId,
+    rule_info: &'r RuleInfo,
+}
+
+impl<'r> RuleDetails<'r> {
+    /// Returns the rule's name.
+    pub fn name(&self) -> &'r str {
+        self.rules.ident_pool.get(self.rule_info.ident_id).unwrap()
+    }
+
+    /// Returns the rule's namespace.
+    pub fn namespace(&self) -> &'r str {
+        self.rules.ident_pool.get(self.rule_info.namespace_ident_id).unwrap()
+    }
+
+    /// Returns true if the rule is global.
+    pub fn is_global(&self) -> bool {
+        self.rule_info.is_global
+    }
+
+    /// Returns true if the rule is private.
+    pub fn is_private(&self) -> bool {
+        self.rule_info.is_private
+    }
+
+    /// Returns the location of the rule in the source code it was compiled
+    /// from.
+    ///
+    /// Rules deserialized from a previous version of the compiled rules
+    /// format don't include the origin of the source code nor the rule's
+    /// position within it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// # use yara_x_parser::SourceCode;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.add_source(
+    ///     SourceCode::from("\nrule foo { condition: true }")
+    ///         .with_origin("foo.yar"),
+    /// )?;
+    ///
+    /// let rules = compiler.build();
+    /// let location = rules.iter().next().unwrap().location();
+    ///
+    /// assert_eq!(location.origin(), Some("foo.yar"));
+    /// assert_eq!(location.line(), Some(2));
+    /// assert_eq!(location.column(), Some(6));
+    /// assert_eq!(location.span(), Some(6..9));
+    /// assert_eq!(location.to_string(), "rule `foo` at foo.yar:2:6");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn location(&self) -> RuleLocation {
+        let source = self.rule_info.source.as_ref();
+        RuleLocation {
+            namespace: self.namespace().to_string(),
+            rule: self.name().to_string(),
+            origin: source.and_then(|source| source.origin).map(|origin| {
+                self.rules.lit_pool.get_str(origin).unwrap().to_string()
+            }),
+            position: source.map(|source| {
+                (
+                    source.line as usize,
+                    source.column as usize,
+                    source.start as usize..source.end as usize,
+                )
+            }),
+        }
+    }
+
+    /// Returns the tags associated to the rule, in alphabetical order.
+    pub fn tags(&self) -> impl Iterator<Item = &'r str> {
+        let ident_pool = &self.rules.ident_pool;
+        self.rule_info.tags.iter().map(|tag| ident_pool.get(*tag).unwrap())
+    }
+
+    /// Returns the metadata associated to the rule, in the same order in
+    /// which they appear in the source code.
+    pub fn metadata(&self) -> impl Iterator<Item = (&'r str, MetaValue<'r>)> {
+        let rules = self.rules;
+        self.rule_info.metadata.iter().map(|(ident_id, value)| {
+            (rules.ident_pool.get(*ident_id).unwrap(), rules.meta_value(value))
+        })
+    }
+
+    /// Returns the patterns defined by the rule.
+    pub fn patterns(&self) -> impl Iterator<Item = PatternDetails<'r>> {
+        let rules = self.rules;
+        self.rule_info.patterns.iter().map(|(ident_id, pattern_id)| {
+            PatternDetails {
+                rules,
+                ident_id: *ident_id,
+                pattern_id: *pattern_id,
+            }
+        })
+    }
+
+    /// Returns the rules used in the condition of this rule.
+    ///
+    /// Only direct dependencies are returned, the rules used by these rules
+    /// are not included. Rules deserialized from a previous version of the
+    /// compiled rules format don't include information about dependencies,
+    /// and this function returns no rules for them.
+    pub fn dependencies(&self) -> impl Iterator<Item = RuleDetails<'r>> {
+        let rules = self.rules;
+        let this_rule_id = self.rule_id;
+        self.rule_info
+            .depends_on_rules
+            .iter()
+            .filter(move |rule_id| **rule_id != this_rule_id)
+            .map(|rule_id| rules.rule_details(*rule_id))
+    }
+
+    /// Returns the names of the modules used in the condition of this rule,
+    /// in alphabetical order.
+    ///
+    /// Modules used by other rules this rule depends on are not included.
+    pub fn modules(&self) -> impl Iterator<Item = &'r str> {
+        let ident_pool = &self.rules.ident_pool;
+        self.rule_info
+            .depends_on_modules
+            .iter()
+            .map(|module| ident_pool.get(*module).unwrap())
+    }
+
+    /// Returns the rules that use this rule in their conditions.
+    ///
+    /// Only the rules that use this rule directly are returned.
+    pub fn dependents(&self) -> impl Iterator<Item = RuleDetails<'r>> {
+        let rules = self.rules;
+        let rule_id = self.rule_id;
+        // A rule can only be used by rules that were defined after it.
+        rules.iter().skip(usize::from(rule_id) + 1).filter(move |rule| {
+            rule.rule_info.depends_on_rules.binary_search(&rule_id).is_ok()
+        })
+    }
+
+    /// Returns all the rules whose results are affected by this rule, either
+    /// because they use this rule directly, or because they use some other
+    /// rule that is affected by it.
+    ///
+    /// This is useful for determining which rules must be reviewed when
+    /// this rule is modified.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x;
+    /// let rules = yara_x::compile(r#"
+    ///     rule a { condition: true }
+    ///     rule b { condition: a }
+    ///     rule c { condition: b }
+    ///     rule d { condition: true }"#).unwrap();
+    ///
+    /// let a = rules.iter().next().unwrap();
+    ///
+    /// assert_eq!(
+    ///     a.transitive_dependents().map(|r| r.name()).collect::<Vec<_>>(),
+    ///     vec!["b", "c"]
+    /// );
+    /// ```
+    pub fn transitive_dependents(
+        &self,
+    ) -> impl Iterator<Item = RuleDetails<'r>> {
+        // As rules can only use rules defined before them, a single pass
+        // over the rules that follow this one is enough.
+        let mut affected = vec![self.rule_id];
+        self.rules.iter().skip(usize::from(self.rule_id) + 1).filter(
+            move |rule| {
+                let is_affected = rule
+                    .rule_info
+                    .depends_on_rules
+                    .iter()
+                    .any(|dep| affected.binary_search(dep).is_ok());
+                if is_affected {
+                    affected.push(rule.rule_id);
+                }
+                is_affected
+            },
+        )
+    }
+}
+
+/// Location of a rule in the source code it was compiled from.
+///
+/// This is returned by [`RuleDetails::location`], and also by
+/// [`crate::Scanner::timeout_rule`] for identifying the rule that was being
+/// evaluated when a scan timed out.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuleLocation {
+    namespace: String,
+    rule: String,
+    origin: Option<String>,
+    position: Option<(usize, usize, Range<usize>)>,
+}
+
+impl RuleLocation {
+    /// Returns the rule's namespace.
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_str()
+    }
+
+    /// Returns the rule's name.
+    pub fn name(&self) -> &str {
+        self.rule.as_str()
+    }
+
+    /// Returns the origin of the source code that contained the rule, as set
+    /// with [`yara_x_parser::SourceCode::with_origin`]. This is usually a
+    /// file path.
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Returns the line where the rule identifier appears, starting at 1.
+    pub fn line(&self) -> Option<usize> {
+        self.position.as_ref().map(|(line, _, _)| *line)
+    }
+
+    /// Returns the column where the rule identifier appears, starting at 1.
+    pub fn column(&self) -> Option<usize> {
+        self.position.as_ref().map(|(_, column, _)| *column)
+    }
+
+    /// Returns the range of bytes within the source code that correspond to
+    /// the rule identifier.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.position.as_ref().map(|(_, _, span)| span.clone())
+    }
+}
+
+impl Display for RuleLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rule `{}`", self.rule)?;
+        match (&self.origin, &self.position) {
+            (Some(origin), Some((line, column, _))) => {
+                write!(f, " at {}:{}:{}", origin, line, column)
+            }
+            (None, Some((line, column, _))) => {
+                write!(f, " at line {}, column {}", line, column)
+            }
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+/// Describes a pattern defined by some rule in a set of compiled [`Rules`].
+pub struct PatternDetails<'r> {
+    rules: &'r Rules,
+    ident_id: IdentId,
+    pattern_id: PatternId,
+}
+
+impl<'r> PatternDetails<'r> {
+    /// Returns the pattern's identifier (e.g: `$a`, `$b`).
+    pub fn identifier(&self) -> &'r str {
+        self.rules.ident_pool.get(self.ident_id).unwrap()
+    }
+
+    /// Returns the kind of pattern.
+    ///
+    /// Version 1 of the compiled rules format doesn't include this
+    /// information. For rules deserialized from that version the kind is
+    /// inferred from the way in which the pattern is searched, which means
+    /// that hex patterns are reported either as text or regexp patterns.
+    pub fn kind(&self) -> PatternKind {
+        self.info().kind
+    }
+
+    /// Returns the modifiers associated to the pattern, as they appear in
+    /// the source code (e.g: `wide`, `nocase`, `xor(1-255)`).
+    pub fn modifiers(&self) -> impl Iterator<Item = &'r str> {
+        let lit_pool = &self.rules.lit_pool;
+        self.info()
+            .modifiers
+            .iter()
+            .map(|lit_id| lit_pool.get_str(*lit_id).unwrap())
+    }
+
+    /// Returns the atoms that were selected for this pattern.
+    ///
+    /// These are the short sequences of bytes that are searched for in the
+    /// scanned data, and which trigger the verification of the full pattern
+    /// when found. Patterns that are anchored at the start of the scanned
+    /// data don't have atoms.
+    pub fn atoms(&self) -> impl Iterator<Item = &'r [u8]> {
+        let rules = self.rules;
+        let pattern_id = self.pattern_id;
+        rules
+            .atoms
+            .iter()
+            .filter(move |atom| {
+                rules.get_sub_pattern(atom.sub_pattern_id).0 == pattern_id
+            })
+            .map(|atom| atom.as_slice())
+    }
+
+    /// Similar to [`PatternDetails::atoms`], but returns detailed
+    /// information about each atom, including its quality and the code
+    /// that verifies the pattern when the atom is found.
+    ///
+    /// This is intended for debugging the performance of patterns.
+    pub fn atom_details(&self) -> impl Iterator<Item = AtomDetails<'r>> {
+        let rules = self.rules;
+        let pattern_id = self.pattern_id;
+        rules
+            .atoms
+            .iter()
+            .filter(move |atom| {
+                rules.get_sub_pattern(atom.sub_pattern_id).0 == pattern_id
+            })
+            .map(move |atom| AtomDetails { rules, atom })
+    }
+
+    fn info(&self) -> &'r PatternInfo {
+        let pattern_id: usize = self.pattern_id.into();
+        self.rules.patterns.get(pattern_id).unwrap()
+    }
+}
+
+/// Describes an atom extracted from a pattern.
+///
+/// This is the type returned by [`PatternDetails::atom_details`].
+pub struct AtomDetails<'r> {
+    rules: &'r Rules,
+    atom: &'r SubPatternAtom,
+}
+
+impl<'r> AtomDetails<'r> {
+    /// Returns the atom's bytes.
+    pub fn bytes(&self) -> &'r [u8] {
+        self.atom.as_slice()
+    }
+
+    /// Returns the atom's quality.
+    ///
+    /// Higher values mean that the atom is less likely to appear in the
+    /// scanned data by chance, which means fewer verifications of the
+    /// pattern.
+    pub fn quality(&self) -> i32 {
+        self.atom.atom.quality()
+    }
+
+    /// Returns true if finding the atom is enough for the pattern to match,
+    /// without any further verification.
+    pub fn is_exact(&self) -> bool {
+        self.atom.is_exact()
+    }
+
+    /// Returns the number of bytes between the start of a match and the
+    /// position where the atom is found.
+    pub fn backtrack(&self) -> usize {
+        self.atom.backtrack()
+    }
+
+    /// Returns the disassembled Pike VM code that verifies the portion of
+    /// the pattern that follows the atom, including the atom itself.
+    ///
+    /// Returns `None` for atoms that are not verified by the Pike VM, like
+    /// those extracted from text patterns.
+    pub fn fwd_code(&self) -> Option<String> {
+        self.atom.fwd_code.map(|loc| {
+            disassemble(self.rules.re_code.as_slice(), loc.location())
+        })
+    }
+
+    /// Returns the disassembled Pike VM code that verifies the portion of
+    /// the pattern that precedes the atom, in reverse order.
+    ///
+    /// Returns `None` for atoms that are not verified by the Pike VM, like
+    /// those extracted from text patterns.
+    pub fn bck_code(&self) -> Option<String> {
+        self.atom.bck_code.map(|loc| {
+            disassemble(self.rules.re_code.as_slice(), loc.location())
+        })
+    }
 }
 
 /// Represents an atom extracted from a pattern and added to the Aho-Corasick
@@ -374,7 +1178,7 @@ pub(crate) struct RuleInfo {
 /// Each time the Aho-Corasick finds one of these atoms, it proceeds to verify
 /// if the corresponding sub-pattern actually matches or not. The verification
 /// process depend on the type of sub-pattern.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct SubPatternAtom {
     /// The [`SubPatternId`] that identifies the sub-pattern this atom
     /// belongs to.
@@ -0,0 +1,142 @@
+use bincode::Options;
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+
+use crate::compiler::Rules;
+use crate::SerializationError;
+
+/// Statistics about compiled rules.
+///
+/// This is returned by [`crate::Rules::stats`]. It helps understanding where
+/// the space goes in large sets of rules, both in memory and in serialized
+/// form.
+#[derive(Clone, Debug)]
+pub struct RulesStats {
+    num_rules: usize,
+    num_patterns: usize,
+    num_sub_patterns: usize,
+    num_literals: usize,
+    literals_size: usize,
+    num_atoms: usize,
+    ac_num_states: usize,
+    ac_memory_usage: usize,
+    serialized_sizes: Vec<(&'static str, usize)>,
+}
+
+impl RulesStats {
+    pub(in crate::compiler) fn new(
+        rules: &Rules,
+    ) -> Result<Self, SerializationError> {
+        // The number of states in the Aho-Corasick automaton is the number
+        // of nodes in the trie built from the atoms, which is the number of
+        // distinct prefixes of the atoms, plus the root node. The automaton
+        // doesn't expose its number of states, so it's computed here.
+        let mut prefixes = FxHashSet::default();
+
+        for atom in rules.atoms.iter() {
+            let bytes = atom.as_slice();
+            for len in 1..=bytes.len() {
+                prefixes.insert(&bytes[..len]);
+            }
+        }
+
+        let wasm_mod_size = rules
+            .wasm_mod
+            .serialize()
+            .map_err(|err| {
+                Box::new(bincode::ErrorKind::Custom(err.to_string()))
+            })?
+            .len();
+
+        let serialized_sizes = vec![
+            ("identifiers", serialized_size(&rules.ident_pool)?),
+            ("regexps", serialized_size(&rules.regexp_pool)?),
+            ("literals", serialized_size(&rules.lit_pool)?),
+            ("wasm_module", wasm_mod_size),
+            ("rules", serialized_size(&rules.rules)?),
+            ("patterns", serialized_size(&rules.patterns)?),
+            ("sub_patterns", serialized_size(&rules.sub_patterns)?),
+            ("atoms", serialized_size(&rules.atoms)?),
+            ("regexp_code", serialized_size(&rules.re_code)?),
+            ("globals", serialized_size(&rules.serialized_globals)?),
+        ];
+
+        Ok(Self {
+            num_rules: rules.rules.len(),
+            num_patterns: rules.num_patterns,
+            num_sub_patterns: rules.sub_patterns.len(),
+            num_literals: rules.lit_pool.len(),
+            literals_size: rules.lit_pool.size(),
+            num_atoms: rules.atoms.len(),
+            ac_num_states: prefixes.len() + 1,
+            ac_memory_usage: rules.ac_automaton().memory_usage(),
+            serialized_sizes,
+        })
+    }
+
+    /// Number of rules, including private and global rules.
+    pub fn num_rules(&self) -> usize {
+        self.num_rules
+    }
+
+    /// Number of patterns across all rules.
+    pub fn num_patterns(&self) -> usize {
+        self.num_patterns
+    }
+
+    /// Number of sub-patterns across all patterns. A single pattern can
+    /// produce multiple sub-patterns, for instance, a text pattern with both
+    /// the `ascii` and `wide` modifiers produces one sub-pattern for each
+    /// variant.
+    pub fn num_sub_patterns(&self) -> usize {
+        self.num_sub_patterns
+    }
+
+    /// Number of distinct literal strings in the literal pool. Literal
+    /// strings that appear multiple times in the rules (e.g: two rules with
+    /// the same pattern) are stored only once.
+    pub fn num_literals(&self) -> usize {
+        self.num_literals
+    }
+
+    /// Total size in bytes of the distinct literal strings in the literal
+    /// pool.
+    pub fn literals_size(&self) -> usize {
+        self.literals_size
+    }
+
+    /// Number of atoms extracted from the patterns.
+    pub fn num_atoms(&self) -> usize {
+        self.num_atoms
+    }
+
+    /// Number of states in the Aho-Corasick automaton used for searching the
+    /// atoms.
+    pub fn ac_num_states(&self) -> usize {
+        self.ac_num_states
+    }
+
+    /// Heap memory in bytes used by the Aho-Corasick automaton.
+    pub fn ac_memory_usage(&self) -> usize {
+        self.ac_memory_usage
+    }
+
+    /// Size in bytes of each section in the rules serialized with
+    /// [`crate::Rules::serialize`], as `(section_name, size)` pairs. The
+    /// sum of all sizes is slightly smaller than the total size of the
+    /// serialized rules, as the header and some minor fields are not
+    /// included.
+    pub fn serialized_sizes(&self) -> &[(&'static str, usize)] {
+        self.serialized_sizes.as_slice()
+    }
+}
+
+/// Returns the size of `value` when serialized with the same options used
+/// by [`crate::Rules::serialize`].
+fn serialized_size<T: Serialize>(
+    value: &T,
+) -> Result<usize, SerializationError> {
+    Ok(bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .serialized_size(value)? as usize)
+}
@@ -0,0 +1,335 @@
+use std::fmt::Write;
+
+use rustc_hash::FxHashMap;
+use thiserror::Error;
+
+use crate::types::TypeValue;
+use crate::variables::{is_valid_identifier, Variable};
+
+/// Errors returned while creating or rendering a [`RuleTemplate`].
+#[derive(Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("unclosed placeholder at offset {0}")]
+    UnclosedPlaceholder(usize),
+
+    #[error("invalid placeholder `{0}`")]
+    InvalidPlaceholder(String),
+
+    #[error("missing value for parameter `{0}`")]
+    MissingParameter(String),
+
+    #[error("template doesn't have a parameter named `{0}`")]
+    UnknownParameter(String),
+
+    #[error("invalid value for `{parameter}`: {reason}")]
+    InvalidValue { parameter: String, reason: String },
+}
+
+/// A YARA source code with placeholders that are replaced with concrete
+/// values for producing multiple rules from the same source.
+///
+/// Placeholders have the form `{{name}}`, and they can appear in most
+/// places where a value is expected: as a literal in the condition, as
+/// the value of a text pattern or metadata entry, inside a string literal,
+/// or as part of an identifier. Values are rendered according to where the
+/// placeholder appears:
+///
+/// * As part of an identifier (e.g: `rule foo_{{id}}`) the value must be a
+///   string composed of letters, digits and underscores, or a non-negative
+///   integer, and it's inserted as is.
+///
+/// * Inside a string literal (e.g: `"foo {{name}}"`) strings are escaped as
+///   required by YARA, while other values are inserted in their textual form.
+///
+/// * Anywhere else, strings are rendered as string literals, including the
+///   quotes, and other values are rendered as the corresponding literal.
+///
+/// Placeholders can't be used inside regular expressions or hex patterns.
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::{Compiler, RuleTemplate};
+/// let template = RuleTemplate::new(r#"
+///     rule match_{{id}} {
+///       strings:
+///         $a = {{pattern}}
+///       condition:
+///         #a >= {{min_count}}
+///     }"#).unwrap();
+///
+/// let mut compiler = Compiler::new();
+///
+/// compiler.add_template(&template, [
+///     vec![("id", "foo".into()), ("pattern", "foo".into()), ("min_count", 1.into())],
+///     vec![("id", "bar".into()), ("pattern", "bar".into()), ("min_count", 2.into())],
+/// ]).unwrap();
+///
+/// assert_eq!(compiler.build().iter().count(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuleTemplate {
+    segments: Vec<Segment>,
+    origin: Option<String>,
+}
+
+/// Each of the pieces a [`RuleTemplate`] is split into.
+#[derive(Clone, Debug)]
+enum Segment {
+    /// Text that is copied verbatim.
+    Text(String),
+    /// A placeholder that is replaced with the value of a parameter.
+    Placeholder { name: String, context: Context },
+}
+
+/// Where a placeholder appears within the source code.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Context {
+    Code,
+    Identifier,
+    StringLiteral,
+}
+
+impl RuleTemplate {
+    /// Creates a new template from YARA source code with placeholders.
+    pub fn new(src: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut in_string = false;
+        let mut in_line_comment = false;
+        let mut in_block_comment = false;
+        let mut chars = src.char_indices().peekable();
+
+        while let Some((offset, c)) = chars.next() {
+            let next = chars.peek().map(|(_, c)| *c);
+
+            if in_line_comment {
+                in_line_comment = c != '\n';
+            } else if in_block_comment {
+                if c == '*' && next == Some('/') {
+                    text.push(c);
+                    chars.next();
+                    in_block_comment = false;
+                    text.push('/');
+                    continue;
+                }
+            } else if in_string && c == '\\' {
+                // Copy the escaped character verbatim, so that `\"` doesn't
+                // end the string.
+                text.push(c);
+                if let Some((_, escaped)) = chars.next() {
+                    text.push(escaped);
+                }
+                continue;
+            } else if c == '"' {
+                in_string = !in_string;
+            } else if !in_string && c == '/' && next == Some('/') {
+                in_line_comment = true;
+            } else if !in_string && c == '/' && next == Some('*') {
+                in_block_comment = true;
+            } else if c == '{' && next == Some('{') {
+                chars.next();
+
+                let start = offset + 2;
+                let end = src[start..]
+                    .find("}}")
+                    .map(|end| start + end)
+                    .ok_or(TemplateError::UnclosedPlaceholder(offset))?;
+
+                let name = src[start..end].trim();
+
+                if !is_valid_identifier(name) {
+                    return Err(TemplateError::InvalidPlaceholder(
+                        name.to_string(),
+                    ));
+                }
+
+                let after = src[end + 2..].chars().next();
+
+                let context = if in_string {
+                    Context::StringLiteral
+                } else if text.ends_with(is_ident_char)
+                    || matches!(after, Some(c) if is_ident_char(c))
+                {
+                    Context::Identifier
+                } else {
+                    Context::Code
+                };
+
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+                segments.push(Segment::Placeholder {
+                    name: name.to_string(),
+                    context,
+                });
+
+                // Skip the placeholder's content and the closing braces.
+                while matches!(chars.peek(), Some((i, _)) if *i < end + 2) {
+                    chars.next();
+                }
+
+                continue;
+            }
+
+            text.push(c);
+        }
+
+        segments.push(Segment::Text(text));
+
+        Ok(Self { segments, origin: None })
+    }
+
+    /// Sets a string that describes the origin of the template. This is
+    /// used as the origin of every source code produced from the template,
+    /// and it usually is the path of the file that contained the template.
+    pub fn with_origin(mut self, origin: &str) -> Self {
+        self.origin = Some(origin.to_string());
+        self
+    }
+
+    /// Returns the origin of the template, if any.
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Returns the names of the parameters used in the template, in the
+    /// order in which they appear for the first time.
+    pub fn parameters(&self) -> impl Iterator<Item = &str> {
+        let mut names: Vec<&str> = Vec::new();
+        for segment in self.segments.iter() {
+            if let Segment::Placeholder { name, .. } = segment {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+        }
+        names.into_iter()
+    }
+
+    /// Produces YARA source code by replacing every placeholder in the
+    /// template with the value of the corresponding parameter.
+    ///
+    /// Every placeholder must have a value, and every value must correspond
+    /// to some placeholder in the template.
+    pub fn render<'a, I>(&self, params: I) -> Result<String, TemplateError>
+    where
+        I: IntoIterator<Item = (&'a str, Variable)>,
+    {
+        let mut values: FxHashMap<&str, TypeValue> = FxHashMap::default();
+
+        for (name, value) in params {
+            if !self.parameters().any(|param| param == name) {
+                return Err(TemplateError::UnknownParameter(name.to_string()));
+            }
+            values.insert(name, value.into());
+        }
+
+        let mut result = String::new();
+
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Text(text) => result.push_str(text),
+                Segment::Placeholder { name, context } => {
+                    let value =
+                        values.get(name.as_str()).ok_or_else(|| {
+                            TemplateError::MissingParameter(name.clone())
+                        })?;
+                    render_value(&mut result, name, value, *context)?;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '$' | '#' | '@' | '!')
+}
+
+fn render_value(
+    result: &mut String,
+    name: &str,
+    value: &TypeValue,
+    context: Context,
+) -> Result<(), TemplateError> {
+    let invalid_value = |reason: &str| TemplateError::InvalidValue {
+        parameter: name.to_string(),
+        reason: reason.to_string(),
+    };
+
+    match (value, context) {
+        (TypeValue::Integer(value), Context::Identifier) => {
+            let value = *value.extract().unwrap();
+            if value < 0 {
+                return Err(invalid_value(
+                    "negative integers can't be used in identifiers",
+                ));
+            }
+            write!(result, "{}", value).unwrap();
+        }
+        (TypeValue::String(value), Context::Identifier) => {
+            let value = value.extract().unwrap();
+            if value.is_empty()
+                || !value
+                    .iter()
+                    .all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+            {
+                return Err(invalid_value(
+                    "only letters, digits and underscores can be used in identifiers",
+                ));
+            }
+            // All bytes are ASCII, so this can't fail.
+            result.push_str(std::str::from_utf8(value).unwrap());
+        }
+        (_, Context::Identifier) => {
+            return Err(invalid_value(
+                "only strings and integers can be used in identifiers",
+            ));
+        }
+        (TypeValue::String(value), Context::StringLiteral) => {
+            escape(result, value.extract().unwrap());
+        }
+        (TypeValue::String(value), Context::Code) => {
+            result.push('"');
+            escape(result, value.extract().unwrap());
+            result.push('"');
+        }
+        (TypeValue::Integer(value), _) => {
+            write!(result, "{}", value.extract().unwrap()).unwrap();
+        }
+        (TypeValue::Float(value), _) => {
+            let value = *value.extract().unwrap();
+            if !value.is_finite() {
+                return Err(invalid_value("float is not finite"));
+            }
+            let start = result.len();
+            write!(result, "{}", value).unwrap();
+            // YARA float literals must have a decimal point.
+            if context == Context::Code && !result[start..].contains('.') {
+                result.push_str(".0");
+            }
+        }
+        (TypeValue::Bool(value), _) => {
+            write!(result, "{}", value.extract().unwrap()).unwrap();
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Appends `s` to `result`, escaping the characters that can't appear
+/// verbatim in a YARA string literal.
+fn escape(result: &mut String, s: &[u8]) {
+    for b in s {
+        match b {
+            b'"' => result.push_str(r#"\""#),
+            b'\\' => result.push_str(r"\\"),
+            b'\n' => result.push_str(r"\n"),
+            b'\r' => result.push_str(r"\r"),
+            b'\t' => result.push_str(r"\t"),
+            0x20..=0x7e => result.push(*b as char),
+            _ => write!(result, r"\x{:02x}", b).unwrap(),
+        }
+    }
+}
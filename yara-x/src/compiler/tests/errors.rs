@@ -360,6 +360,25 @@ rule test {
    │        ─┬  
    │         ╰── this number is out of the allowed range [1-9223372036854775807]
 ───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  strings:
+    $a = "foo"
+  condition: 
+    #a in (0..10 step 0) > 1
+}"#,
+            r#"error: number out of range
+   ╭─[line:6:23]
+   │
+ 6 │     #a in (0..10 step 0) > 1
+   │                       ┬  
+   │                       ╰── this number is out of the allowed range [1-9223372036854775807]
+───╯
 "#,
         ),
         ////////////////////////////////////////////////////////////
@@ -832,6 +851,57 @@ rule test {
 "#,
         ),
         ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule foo {
+  condition:
+    true
+}
+rule test {
+  condition:
+    any of (foo, bar*)
+}
+"#,
+            r#"error: empty rule set
+   ╭─[line:8:18]
+   │
+ 8 │     any of (foo, bar*)
+   │                  ──┬─  
+   │                    ╰─── no rule declared before this one matches `bar*`
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule foo : bar {
+  condition:
+    true
+}
+global rule test {
+  condition:
+    any of (tag:bar)
+}
+"#,
+            r#"error: global rule `test` depends on non-global rule `foo`
+   ╭─[line:8:13]
+   │
+ 2 │ rule foo : bar {
+   │      ─┬─  
+   │       ╰─── non-global rule `foo` declared here
+   │ 
+ 6 │ global rule test {
+   │             ──┬─  
+   │               ╰─── global rule `test` declared here
+   │ 
+ 8 │     any of (tag:bar)
+   │             ───┬───  
+   │                ╰───── `foo` is used in the condition of `test`
+───╯
+"#,
+        ),
         #[cfg(feature = "test_proto2-module")]
         (
             line!(),
@@ -839,17 +909,35 @@ rule test {
 import "test_proto2"
 rule test {
   condition:
-    for all x,y in test_proto2.array_int64 : ( true )
+    for all x,y,z in test_proto2.array_int64 : ( true )
 }
 "#,
             r#"error: assignment mismatch
    ╭─[line:5:13]
    │
- 5 │     for all x,y in test_proto2.array_int64 : ( true )
-   │             ─┬─    ───────────┬───────────  
-   │              ╰────────────────────────────── this expects 2 value(s)
-   │                               │             
-   │                               ╰───────────── this produces 1 value(s)
+ 5 │     for all x,y,z in test_proto2.array_int64 : ( true )
+   │             ──┬──    ───────────┬───────────  
+   │               ╰─────────────────────────────── this expects 3 value(s)
+   │                                 │             
+   │                                 ╰───────────── this produces 2 value(s)
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    with s = "foo", r = /foo/ : ( s matches r )
+}
+"#,
+            r#"error: wrong type
+   ╭─[line:4:26]
+   │
+ 4 │     with s = "foo", r = /foo/ : ( s matches r )
+   │                          ─┬─  
+   │                           ╰─── expression should be `boolean`, `float`, `integer`, or `string`, but is `regexp`
 ───╯
 "#,
         ),
@@ -1086,6 +1174,32 @@ rule test {
    │       (float, float)
    │       (integer, integer)
 ───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "math-module")]
+        (
+            line!(),
+            r#"
+import "math"
+rule test {
+  strings:
+    $a = "foo"
+  condition:
+    $a and math.mean_string_entropy(1) > 2.0
+}
+"#,
+            r#"error: wrong arguments
+   ╭─[line:7:36]
+   │
+ 7 │     $a and math.mean_string_entropy(1) > 2.0
+   │                                    ─┬─  
+   │                                     ╰─── wrong arguments in this call
+   │ 
+   │ Note: accepted argument combinations:
+   │
+   │       (pattern)
+───╯
 "#,
         ),
         ////////////////////////////////////////////////////////////
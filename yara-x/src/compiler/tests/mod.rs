@@ -1,11 +1,20 @@
+use bincode::Options;
 use pretty_assertions::assert_eq;
 use std::mem::size_of;
+use yara_x_parser::{ast, SourceCode, UnusedPatternAction, Warning};
 
+use crate::compiler::legacy::{RulesV1, RulesV2, RulesV3, RulesV4};
 use crate::compiler::{
-    SerializationError, SubPattern, Var, VarStack, VariableError,
+    CompileErrorInfo, CompilerPolicy, Error, ExtSchemaError, RuleTemplate,
+    SerializationError, SubPattern, TemplateError, Var, VarStack,
+    VariableError,
 };
 use crate::types::Type;
-use crate::{compile, Compiler, Rules, Scanner};
+use crate::{
+    compile, CacheKey, CompilationCache, Compiler, IntegerOverflow, LintPass,
+    LintReport, MetaValue, PatternKind, RuleDetails, Rules, ScanError,
+    Scanner,
+};
 
 mod errors;
 mod warnings;
@@ -29,7 +38,7 @@ fn serialization() {
 
     let rules = Rules::deserialize(rules).unwrap();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     assert_eq!(
         scanner
             .scan(b"foo")
@@ -42,6 +51,291 @@ fn serialization() {
     assert_eq!(size_of::<SubPattern>(), 24);
 }
 
+#[test]
+fn serialization_versions() {
+    let rules = compile(r#"rule test { strings: $a = "foo" condition: $a }"#)
+        .unwrap()
+        .serialize()
+        .unwrap();
+
+    // Set the version number to 1000.
+    let mut future_rules = rules.clone();
+    future_rules[7..11].copy_from_slice(1000_u32.to_le_bytes().as_slice());
+
+    assert!(matches!(
+        Rules::deserialize(future_rules).err().unwrap(),
+        SerializationError::IncompatibleVersion { found: 1000, .. }
+    ));
+
+    // Set an unknown flag.
+    let mut future_rules = rules;
+    future_rules[11..15].copy_from_slice(0x8000_u32.to_le_bytes().as_slice());
+
+    assert!(matches!(
+        Rules::deserialize(future_rules).err().unwrap(),
+        SerializationError::UnsupportedFeatures(0x8000)
+    ));
+
+    // Rules serialized with version 1 of the format, which doesn't have
+    // version number in the header.
+    let rules = compile(
+        r#"rule test { strings: $a = "foo" $b = /ba+r/ condition: $a or $b }"#,
+    )
+    .unwrap();
+
+    let mut v1_rules = b"YARA-X".to_vec();
+
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .serialize_into(&mut v1_rules, &RulesV1::from(rules))
+        .unwrap();
+
+    let rules = Rules::deserialize(v1_rules).unwrap();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+
+    let rule = rules.iter().next().unwrap();
+    let mut patterns = rule.patterns();
+    let pattern = patterns.next().unwrap();
+
+    assert_eq!(rule.name(), "test");
+    assert_eq!(pattern.identifier(), "$a");
+    assert_eq!(pattern.kind(), PatternKind::Text);
+
+    let pattern = patterns.next().unwrap();
+
+    assert_eq!(pattern.identifier(), "$b");
+    assert_eq!(pattern.kind(), PatternKind::Regexp);
+
+    // Rules serialized with version 2 of the format, which doesn't have
+    // information about dependencies.
+    let rules = compile(
+        r#"
+        import "test_proto2"
+        rule a { condition: test_proto2.int64_zero == 0 }
+        rule b { condition: a }"#,
+    )
+    .unwrap();
+
+    // Take the header from the current version and set version number to 2.
+    let mut v2_rules = rules.serialize().unwrap()[0..15].to_vec();
+    v2_rules[7..11].copy_from_slice(2_u32.to_le_bytes().as_slice());
+
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .serialize_into(&mut v2_rules, &RulesV2::from(rules))
+        .unwrap();
+
+    let rules = Rules::deserialize(v2_rules).unwrap();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"").unwrap().matching_rules().len(), 2);
+
+    let rule = rules.iter().nth(1).unwrap();
+
+    assert_eq!(rule.name(), "b");
+    assert_eq!(rule.dependencies().count(), 0);
+
+    // Rules serialized with version 3 of the format, which doesn't have
+    // information about the location of rules in the source code.
+    let rules = compile(
+        r#"
+        rule a { condition: true }
+        rule b { condition: a }"#,
+    )
+    .unwrap();
+
+    // Take the header from the current version and set version number to 3.
+    let mut v3_rules = rules.serialize().unwrap()[0..15].to_vec();
+    v3_rules[7..11].copy_from_slice(3_u32.to_le_bytes().as_slice());
+
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .serialize_into(&mut v3_rules, &RulesV3::from(rules))
+        .unwrap();
+
+    let rules = Rules::deserialize(v3_rules).unwrap();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"").unwrap().matching_rules().len(), 2);
+
+    let rule = rules.iter().nth(1).unwrap();
+    let location = rule.location();
+
+    assert_eq!(rule.dependencies().count(), 1);
+    assert_eq!(location.name(), "b");
+    assert_eq!(location.line(), None);
+    assert_eq!(location.to_string(), "rule `b`");
+
+    // Rules serialized with version 4 of the format, which doesn't have
+    // the schema of the `ext` module.
+    let rules = compile(
+        r#"
+        rule a { condition: true }
+        rule b { condition: a }"#,
+    )
+    .unwrap();
+
+    // Take the header from the current version and set version number to 4.
+    let mut v4_rules = rules.serialize().unwrap()[0..15].to_vec();
+    v4_rules[7..11].copy_from_slice(4_u32.to_le_bytes().as_slice());
+
+    bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .serialize_into(&mut v4_rules, &RulesV4::from(rules))
+        .unwrap();
+
+    let rules = Rules::deserialize(v4_rules).unwrap();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"").unwrap().matching_rules().len(), 2);
+    assert!(rules.ext_schema().is_none());
+}
+
+// `testdata/v1.yrx` was produced by compiling `testdata/v1.yar` with the
+// first version of YARA-X that supported compiled rules, which used version
+// 1 of the format. The file contains native code for x86_64.
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn serialization_v1_file() {
+    let rules = Rules::deserialize(include_bytes!("testdata/v1.yrx")).unwrap();
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+    let scan_results = scanner.scan(b"foo bar baz foo").unwrap();
+
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|r| r.name().to_string())
+            .collect::<Vec<_>>(),
+        ["count_in_range", "match_in_range", "of_them", "time_module"]
+    );
+}
+
+#[test]
+fn dependency_graph() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            import "test_proto2"
+            rule a { condition: test_proto2.int64_zero == 0 }
+            rule b { condition: a and a }
+            rule c { condition: b and test_proto2.int64_one == 1 }
+            rule d { condition: a or c }
+            rule e { condition: e }"#,
+        )
+        .unwrap();
+
+    assert_eq!(compiler.warnings.len(), 1);
+    assert!(matches!(
+        compiler.warnings[0],
+        Warning::CircularRuleDependency { .. }
+    ));
+
+    let rules = compiler.build();
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    let rule = |name| rules.iter().find(|r| r.name() == name).unwrap();
+    let names = |rules: Vec<RuleDetails>| {
+        rules.iter().map(|r| r.name().to_string()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        names(rule("a").dependencies().collect()),
+        Vec::<String>::new()
+    );
+    assert_eq!(names(rule("b").dependencies().collect()), vec!["a"]);
+    assert_eq!(names(rule("d").dependencies().collect()), vec!["a", "c"]);
+    assert_eq!(
+        names(rule("e").dependencies().collect()),
+        Vec::<String>::new()
+    );
+
+    assert_eq!(rule("a").modules().collect::<Vec<_>>(), vec!["test_proto2"]);
+    assert_eq!(rule("b").modules().count(), 0);
+    assert_eq!(rule("c").modules().collect::<Vec<_>>(), vec!["test_proto2"]);
+
+    assert_eq!(names(rule("a").dependents().collect()), vec!["b", "d"]);
+    assert_eq!(names(rule("c").dependents().collect()), vec!["d"]);
+    assert_eq!(names(rule("e").dependents().collect()), Vec::<String>::new());
+
+    assert_eq!(
+        names(rule("b").transitive_dependents().collect()),
+        vec!["c", "d"]
+    );
+    assert_eq!(
+        names(rule("d").transitive_dependents().collect()),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn signed_serialization() {
+    let secret_key = [7; 32];
+    let public_key = Rules::public_key(&secret_key);
+    let other_public_key = Rules::public_key(&[8; 32]);
+
+    let rules =
+        compile(r#"rule test { strings: $a = "foo" condition: $a }"#).unwrap();
+
+    let unsigned = rules.serialize().unwrap();
+    let signed = rules.serialize_signed(&secret_key).unwrap();
+
+    assert!(Rules::is_serialized(&unsigned));
+    assert!(Rules::is_serialized(&signed));
+    assert!(!Rules::is_serialized(b"rule test { condition: true }"));
+
+    assert!(Rules::deserialize_verified(&signed, &public_key).is_ok());
+
+    // Signed rules can be deserialized without verifying the signature.
+    assert!(Rules::deserialize(&signed).is_ok());
+
+    assert!(matches!(
+        Rules::deserialize_verified(&signed, &other_public_key).err().unwrap(),
+        SerializationError::InvalidSignature
+    ));
+
+    assert!(matches!(
+        Rules::deserialize_verified(&unsigned, &public_key).err().unwrap(),
+        SerializationError::MissingSignature
+    ));
+
+    // Tamper with the last byte in the signed rules.
+    let mut tampered = signed.clone();
+    *tampered.last_mut().unwrap() ^= 0xff;
+
+    assert!(matches!(
+        Rules::deserialize_verified(&tampered, &public_key).err().unwrap(),
+        SerializationError::InvalidSignature
+    ));
+
+    // Detached signatures.
+    let signature = Rules::sign(&unsigned, &secret_key);
+
+    let rules = Rules::deserialize_verified_detached(
+        &unsigned,
+        &signature,
+        &public_key,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+
+    assert!(matches!(
+        Rules::deserialize_verified_detached(
+            &unsigned,
+            &signature,
+            &other_public_key
+        )
+        .err()
+        .unwrap(),
+        SerializationError::InvalidSignature
+    ));
+}
+
 #[test]
 fn namespaces() {
     // `foo` and `bar` are both in the default namespace, this compiles
@@ -66,6 +360,225 @@ fn namespaces() {
         .is_err());
 }
 
+#[test]
+fn exported_rules() {
+    let mut compiler = Compiler::new();
+
+    // `foo` is private, but it is exported, so `bar` can use it even if
+    // they are in different namespaces.
+    compiler
+        .add_source(
+            r#"
+            export private rule foo { strings: $a = "foo" condition: $a }
+            rule baz { condition: true }"#,
+        )
+        .unwrap()
+        .new_namespace("bar")
+        .add_source("rule bar { condition: foo }")
+        .unwrap();
+
+    // `baz` is not exported, it can't be used from other namespaces.
+    let err = compiler
+        .new_namespace("qux")
+        .add_source("rule qux { condition: baz }")
+        .unwrap_err();
+
+    assert!(err.to_string().starts_with("error: rule `baz` is not exported"));
+
+    // Exported rules share a single namespace, declaring another rule
+    // with the same name is an error.
+    assert!(compiler.add_source("rule foo { condition: true }").is_err());
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(
+        scanner
+            .scan(b"foo")
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.name().to_string())
+            .collect::<Vec<_>>(),
+        vec!["baz", "bar"]
+    );
+
+    assert_eq!(
+        scanner
+            .scan(b"bar")
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.name().to_string())
+            .collect::<Vec<_>>(),
+        vec!["baz"]
+    );
+}
+
+#[test]
+fn snapshots() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            import "test_proto2"
+            global rule global_foo { strings: $a = "foo" condition: $a }
+            rule foo { condition: true }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.snapshot();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"foo bar").unwrap().matching_rules().len(), 2);
+
+    // New rules are added to the same namespace, they can use the existing
+    // ones, and are affected by the global rule.
+    compiler
+        .add_source(
+            r#"
+            import "test_proto2"
+            rule bar { strings: $a = "bar" condition: $a and foo }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.snapshot();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"foo bar").unwrap().matching_rules().len(), 3);
+    assert_eq!(scanner.scan(b"bar").unwrap().matching_rules().len(), 0);
+
+    compiler
+        .new_namespace("other")
+        .add_source(r#"rule bar { strings: $a = "bar" condition: $a }"#)
+        .unwrap();
+
+    // The rules obtained from the final call to `build` include all the
+    // rules added to the compiler.
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"foo bar").unwrap().matching_rules().len(), 4);
+    assert_eq!(scanner.scan(b"bar").unwrap().matching_rules().len(), 1);
+}
+
+#[test]
+fn introspection() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            import "test_proto2"
+            global private rule foo : tag2 tag1 {
+                meta:
+                    author = "foo"
+                    version = 2
+                    ratio = 0.5
+                    enabled = true
+                strings:
+                    $a = "foobar" wide nocase
+                    $b = { 01 02 03 04 }
+                    $c = /qux.*/ fullword
+                    $d = "abcdef" xor(1-2)
+                condition:
+                    any of them
+            }"#,
+        )
+        .unwrap()
+        .new_namespace("bar")
+        .add_source("rule bar { condition: true }")
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert_eq!(rules.namespaces().collect::<Vec<_>>(), vec!["default", "bar"]);
+    assert_eq!(rules.imports().collect::<Vec<_>>(), vec!["test_proto2"]);
+
+    let mut iter = rules.iter();
+    let foo = iter.next().unwrap();
+
+    assert_eq!(foo.name(), "foo");
+    assert_eq!(foo.namespace(), "default");
+    assert!(foo.is_global());
+    assert!(foo.is_private());
+    assert_eq!(foo.tags().collect::<Vec<_>>(), vec!["tag1", "tag2"]);
+    assert_eq!(
+        foo.metadata().collect::<Vec<_>>(),
+        vec![
+            ("author", MetaValue::String("foo")),
+            ("version", MetaValue::Integer(2)),
+            ("ratio", MetaValue::Float(0.5)),
+            ("enabled", MetaValue::Bool(true)),
+        ]
+    );
+
+    let patterns = foo.patterns().collect::<Vec<_>>();
+
+    assert_eq!(patterns.len(), 4);
+    assert_eq!(patterns[0].identifier(), "$a");
+    assert_eq!(patterns[0].kind(), PatternKind::Text);
+    assert_eq!(
+        patterns[0].modifiers().collect::<Vec<_>>(),
+        vec!["nocase", "wide"]
+    );
+    assert!(patterns[0].atoms().count() > 0);
+    assert_eq!(patterns[1].kind(), PatternKind::Hex);
+    assert_eq!(
+        patterns[1].atoms().collect::<Vec<_>>(),
+        vec![[0x01, 0x02, 0x03, 0x04].as_slice()]
+    );
+    assert_eq!(patterns[2].identifier(), "$c");
+    assert_eq!(patterns[2].kind(), PatternKind::Regexp);
+    assert_eq!(patterns[2].modifiers().collect::<Vec<_>>(), vec!["fullword"]);
+    assert_eq!(patterns[3].modifiers().collect::<Vec<_>>(), vec!["xor(1-2)"]);
+
+    let bar = iter.next().unwrap();
+
+    assert_eq!(bar.name(), "bar");
+    assert_eq!(bar.namespace(), "bar");
+    assert!(!bar.is_global());
+    assert_eq!(bar.tags().count(), 0);
+    assert_eq!(bar.metadata().count(), 0);
+    assert_eq!(bar.patterns().count(), 0);
+
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn atom_details() {
+    let rules = crate::compile(
+        r#"
+rule test {
+  strings:
+    $a = "foobar"
+    $b = /abcd.*efgh/
+  condition:
+    $a and $b
+}
+"#,
+    )
+    .unwrap();
+
+    let rule = rules.iter().next().unwrap();
+    let patterns = rule.patterns().collect::<Vec<_>>();
+
+    // Atoms extracted from text patterns are not verified with the
+    // Pike VM, so they don't have code.
+    let atom = patterns[0].atom_details().next().unwrap();
+
+    assert!(atom.quality() > 0);
+    assert!(atom.fwd_code().is_none());
+    assert!(atom.bck_code().is_none());
+
+    let atom = patterns[1].atom_details().next().unwrap();
+
+    assert_eq!(atom.bytes(), b"abcd");
+    assert_eq!(atom.backtrack(), 0);
+    assert!(!atom.is_exact());
+    assert_eq!(atom.fwd_code().unwrap().lines().last(), Some("0001b: MATCH"));
+    assert!(atom.bck_code().unwrap().ends_with("MATCH\n"));
+}
+
 #[test]
 fn var_stack() {
     let mut stack = VarStack::new();
@@ -133,6 +646,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -152,6 +666,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -171,6 +686,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -190,6 +706,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -209,6 +726,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -228,6 +746,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -247,6 +766,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -266,6 +786,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -285,6 +806,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -304,6 +826,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -323,6 +846,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -342,6 +866,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(&[])
             .expect("scan should not fail")
             .matching_rules()
@@ -364,6 +889,7 @@ fn globals() {
 
     assert_eq!(
         Scanner::new(&rules)
+            .unwrap()
             .scan(b"foo")
             .expect("scan should not fail")
             .matching_rules()
@@ -371,3 +897,822 @@ fn globals() {
         2
     );
 }
+
+#[test]
+fn policy() {
+    let policy_error = |policy: CompilerPolicy, src: &str| {
+        let mut compiler = Compiler::new();
+        compiler.policy(policy);
+        match compiler.add_source(src).err() {
+            Some(Error::CompileError(err)) => Some(err),
+            Some(err) => panic!("unexpected error: {}", err),
+            None => None,
+        }
+    };
+
+    let err = policy_error(
+        CompilerPolicy::new().forbid_module("test_proto2"),
+        r#"import "test_proto2" rule test { condition: true }"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        err.info(),
+        CompileErrorInfo::ForbiddenModule { identifier, .. } if identifier == "test_proto2"
+    ));
+
+    assert!(policy_error(
+        CompilerPolicy::new().forbid_module("test_proto2"),
+        r#"import "test_proto3" rule test { condition: true }"#,
+    )
+    .is_none());
+
+    let err = policy_error(
+        CompilerPolicy::new().forbid_unbounded_loops(true),
+        r#"rule test { condition: for any i in (0..filesize - 1) : ( true ) }"#,
+    )
+    .unwrap();
+
+    assert!(matches!(err.info(), CompileErrorInfo::UnboundedLoop { .. }));
+
+    assert!(policy_error(
+        CompilerPolicy::new().forbid_unbounded_loops(true),
+        r#"rule test { condition: for any i in (0..10) : ( filesize > i ) }"#,
+    )
+    .is_none());
+
+    assert!(policy_error(
+        CompilerPolicy::new(),
+        r#"rule test { condition: for any i in (0..filesize) : ( true ) }"#,
+    )
+    .is_none());
+
+    let err = policy_error(
+        CompilerPolicy::new().max_patterns_per_rule(1),
+        r#"rule test { strings: $a = "foo" $b = "bar" condition: all of them }"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        err.info(),
+        CompileErrorInfo::TooManyPatterns {
+            num_patterns: 2,
+            max_patterns: 1,
+            ..
+        }
+    ));
+
+    let err = policy_error(
+        CompilerPolicy::new()
+            .require_metadata("author")
+            .require_metadata("date"),
+        r#"rule test { meta: author = "foo" condition: true }"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        err.info(),
+        CompileErrorInfo::MissingMetadata { identifier, .. } if identifier == "date"
+    ));
+
+    assert_eq!(err.info().code(), "missing_metadata");
+}
+
+#[test]
+fn lint_passes() {
+    struct HouseRules;
+
+    impl LintPass for HouseRules {
+        fn name(&self) -> &str {
+            "house_rules"
+        }
+
+        fn check_rule(&self, rule: &ast::Rule, report: &mut LintReport) {
+            if rule.identifier.name.starts_with("tmp_") {
+                report.warning(
+                    "temporary rule",
+                    "this rule is temporary",
+                    rule.identifier.span,
+                );
+            }
+            if rule.identifier.name.chars().any(|c| c.is_uppercase()) {
+                report.error(
+                    "rule names must be lowercase",
+                    "this name has uppercase characters",
+                    rule.identifier.span,
+                );
+            }
+        }
+    }
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_lint_pass(HouseRules);
+
+    let err = match compiler
+        .add_source("rule Foo { condition: filesize > 10 }")
+        .unwrap_err()
+    {
+        Error::CompileError(err) => err,
+        err => panic!("unexpected error: {}", err),
+    };
+
+    assert!(matches!(
+        err.info(),
+        CompileErrorInfo::LintError { lint_pass, message, note: Some(note), .. }
+            if lint_pass == "house_rules"
+                && message == "rule names must be lowercase"
+                && note == "reported by lint pass `house_rules`"
+    ));
+
+    compiler
+        .new_namespace("bar")
+        .add_source("rule tmp_foo { condition: filesize > 20 }")
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert!(matches!(
+        rules.warnings(),
+        [Warning::LintWarning { lint_pass, message, .. }]
+            if lint_pass == "house_rules" && message == "temporary rule"
+    ));
+}
+
+#[test]
+fn profiling() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            SourceCode::from(
+                r#"
+                rule a { strings: $a = { 00 00 } condition: $a }
+                rule b { strings: $a = "foobar" condition: $a }"#,
+            )
+            .with_origin("foo.yar"),
+        )
+        .unwrap()
+        .new_namespace("bar")
+        .add_source(
+            r#"
+            rule c { condition: for any i in (0..9) : ( uint8(i) == 0 ) }
+            rule d { condition: for any i in (0..filesize) : ( true ) }"#,
+        )
+        .unwrap();
+
+    let profiling_data = compiler.profiling_data();
+
+    assert_eq!(profiling_data.sources().len(), 2);
+    assert_eq!(profiling_data.sources()[0].origin(), Some("foo.yar"));
+    assert_eq!(profiling_data.sources()[0].num_rules(), 2);
+    assert_eq!(profiling_data.sources()[1].origin(), None);
+
+    let rules = profiling_data.rules();
+
+    assert_eq!(rules.len(), 4);
+    assert_eq!(rules[2].namespace(), "bar");
+    assert_eq!(rules[2].name(), "c");
+
+    // The atom `00 00` is worse than the atom extracted from `foobar`.
+    assert!(
+        rules[0].cost().min_atom_quality()
+            < rules[1].cost().min_atom_quality()
+    );
+    assert!(rules[0].cost().score() > rules[1].cost().score());
+
+    assert_eq!(rules[2].cost().num_atoms(), 0);
+    assert_eq!(rules[2].cost().max_loop_iterations(), Some(10));
+    assert!(!rules[2].cost().has_unbounded_loops());
+
+    assert_eq!(rules[3].cost().max_loop_iterations(), None);
+    assert!(rules[3].cost().has_unbounded_loops());
+
+    assert_eq!(
+        profiling_data
+            .top_rules_by_cost(50.0)
+            .iter()
+            .map(|r| r.name())
+            .collect::<Vec<_>>(),
+        vec!["d"]
+    );
+}
+
+#[test]
+fn add_sources() {
+    let sources: Vec<String> = (0..50)
+        .map(|i| {
+            if i == 0 {
+                r#"rule r0 { strings: $a = "foo" condition: $a }"#.to_string()
+            } else {
+                format!(
+                    r#"rule r{i} {{ strings: $a = "r{i}" condition: $a or r{} }}"#,
+                    i - 1
+                )
+            }
+        })
+        .collect();
+
+    let mut parallel = Compiler::new();
+    parallel
+        .add_sources("default", sources.iter().map(|s| s.as_str()))
+        .unwrap();
+
+    let mut sequential = Compiler::new();
+    for src in sources.iter() {
+        sequential.add_source(src.as_str()).unwrap();
+    }
+
+    let parallel = parallel.build();
+    let sequential = sequential.build();
+
+    assert_eq!(
+        parallel.iter().map(|r| r.name()).collect::<Vec<_>>(),
+        sequential.iter().map(|r| r.name()).collect::<Vec<_>>(),
+    );
+
+    assert_eq!(parallel.num_patterns(), sequential.num_patterns());
+    assert_eq!(parallel.atoms.len(), sequential.atoms.len());
+
+    let mut scanner = Scanner::new(&parallel).unwrap();
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 50);
+
+    // Errors must point to the right source code, even after other source
+    // codes were parsed in parallel.
+    let mut compiler = Compiler::new();
+
+    let err = compiler
+        .add_sources(
+            "default",
+            [
+                SourceCode::from("rule a { condition: true }")
+                    .with_origin("a.yar"),
+                SourceCode::from("rule b { condition: true }")
+                    .with_origin("b.yar"),
+                SourceCode::from("rule c { condition: d }")
+                    .with_origin("c.yar"),
+                SourceCode::from("rule d { condition: true }")
+                    .with_origin("d.yar"),
+            ],
+        )
+        .unwrap_err();
+
+    assert!(err.to_string().contains("[c.yar:1:21]"));
+
+    // Errors in patterns, which are converted to IR in parallel, are
+    // reported after errors found earlier in the same rule.
+    let mut compiler = Compiler::new();
+
+    let err = compiler
+        .add_sources(
+            "default",
+            [
+                SourceCode::from("rule a { condition: true }")
+                    .with_origin("a.yar"),
+                SourceCode::from(
+                    r#"rule a { strings: $a = /a{3,1}/ condition: $a }"#,
+                )
+                .with_origin("b.yar"),
+            ],
+        )
+        .unwrap_err();
+
+    assert!(err.to_string().contains("duplicate rule `a`"));
+
+    let err = compiler
+        .add_sources(
+            "default",
+            [SourceCode::from(
+                r#"rule b { strings: $a = /a{3,1}/ condition: $a }"#,
+            )
+            .with_origin("c.yar")],
+        )
+        .unwrap_err();
+
+    assert!(err.to_string().contains("[c.yar:1:"));
+
+    // Rules are added to the given namespace, and rules previously added
+    // to the same namespace are still visible.
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_sources("foo", ["rule a { condition: true }"])
+        .unwrap()
+        .add_sources("foo", ["rule b { condition: a }"])
+        .unwrap()
+        .add_sources("bar", ["rule a { condition: true }"])
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert_eq!(
+        rules.iter().map(|r| (r.namespace(), r.name())).collect::<Vec<_>>(),
+        vec![("foo", "a"), ("foo", "b"), ("bar", "a")]
+    );
+}
+
+#[test]
+fn stats() {
+    let rules = compile(
+        r#"
+        rule a { strings: $a = "foobar" condition: $a }
+        rule b { strings: $a = "foobar" $b = "foobaz" condition: $a and $b }"#,
+    )
+    .unwrap();
+
+    let stats = rules.stats().unwrap();
+
+    assert_eq!(stats.num_rules(), 2);
+    assert_eq!(stats.num_patterns(), 3);
+    assert_eq!(stats.num_sub_patterns(), 3);
+    // `foobar` is stored only once.
+    assert_eq!(stats.num_literals(), 2);
+    assert_eq!(stats.literals_size(), 12);
+    assert!(stats.num_atoms() > 0);
+    assert!(stats.ac_num_states() > 1);
+    assert!(stats.ac_memory_usage() > 0);
+
+    let total: usize =
+        stats.serialized_sizes().iter().map(|(_, size)| size).sum();
+
+    assert!(total > 0);
+    assert!(total <= rules.serialize().unwrap().len());
+}
+
+#[test]
+fn templates() {
+    let template = RuleTemplate::new(
+        r#"
+        // {{not_a_placeholder}}
+        rule test_{{id}} {
+          meta:
+            description = "matches {{name}} at least {{ min }} times"
+          strings:
+            $a = {{name}}
+          condition:
+            #a >= {{min}} and {{ratio}} < 1.0 and {{flag}}
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        template.parameters().collect::<Vec<_>>(),
+        vec!["id", "name", "min", "ratio", "flag"]
+    );
+
+    assert_eq!(
+        template
+            .render([
+                ("id", 1.into()),
+                ("name", "foo \"bar\"\n".into()),
+                ("min", 2.into()),
+                ("ratio", 0_f64.into()),
+                ("flag", true.into()),
+            ])
+            .unwrap(),
+        r#"
+        // {{not_a_placeholder}}
+        rule test_1 {
+          meta:
+            description = "matches foo \"bar\"\n at least 2 times"
+          strings:
+            $a = "foo \"bar\"\n"
+          condition:
+            #a >= 2 and 0.0 < 1.0 and true
+        }"#
+    );
+
+    assert_eq!(
+        template.render([("id", 1.into())]).unwrap_err(),
+        TemplateError::MissingParameter("name".to_string())
+    );
+
+    assert_eq!(
+        template.render([("foo", 1.into())]).unwrap_err(),
+        TemplateError::UnknownParameter("foo".to_string())
+    );
+
+    assert!(matches!(
+        template.render([("id", "foo bar".into())]).unwrap_err(),
+        TemplateError::InvalidValue { .. }
+    ));
+
+    assert_eq!(
+        RuleTemplate::new("rule {{foo").unwrap_err(),
+        TemplateError::UnclosedPlaceholder(5)
+    );
+
+    assert_eq!(
+        RuleTemplate::new("rule {{foo bar}}").unwrap_err(),
+        TemplateError::InvalidPlaceholder("foo bar".to_string())
+    );
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_template(
+            &template,
+            (0..10).map(|i| {
+                vec![
+                    ("id", i.into()),
+                    ("name", format!("foo{i}").into()),
+                    ("min", 1.into()),
+                    ("ratio", 0.5.into()),
+                    ("flag", true.into()),
+                ]
+            }),
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(rules.iter().count(), 10);
+    assert_eq!(
+        scanner
+            .scan(b"foo3 foo7")
+            .unwrap()
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect::<Vec<_>>(),
+        vec!["test_3", "test_7"]
+    );
+}
+
+#[test]
+fn unused_patterns() {
+    let src = r#"
+        rule test {
+          strings:
+            $a = "foo"
+            $b = "bar"
+            $c = "baz"
+          condition:
+            $b
+        }"#;
+
+    assert!(Compiler::new().add_source(src).is_err());
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .unused_patterns(UnusedPatternAction::Warn)
+        .add_source(src)
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert_eq!(rules.warnings().len(), 2);
+    assert!(matches!(
+        rules.warnings()[0],
+        Warning::UnusedPattern { ref pattern_ident, note: None, .. }
+            if pattern_ident == "$a"
+    ));
+    assert_eq!(rules.warnings()[0].code(), "unused_pattern");
+
+    assert_eq!(rules.iter().next().unwrap().patterns().count(), 3);
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .unused_patterns(UnusedPatternAction::Strip)
+        .add_source(src)
+        .unwrap()
+        .add_source(r#"rule test2 { strings: $a = "qux" condition: true }"#)
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert_eq!(rules.warnings().len(), 3);
+    assert!(matches!(
+        rules.warnings()[1],
+        Warning::UnusedPattern { ref pattern_ident, note: Some(_), .. }
+            if pattern_ident == "$c"
+    ));
+    let mut rules_iter = rules.iter();
+
+    assert_eq!(
+        rules_iter
+            .next()
+            .unwrap()
+            .patterns()
+            .map(|p| p.identifier().to_string())
+            .collect::<Vec<_>>(),
+        vec!["$b"]
+    );
+
+    assert_eq!(rules_iter.next().unwrap().patterns().count(), 0);
+    assert_eq!(rules.stats().unwrap().num_patterns(), 1);
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    assert_eq!(scanner.scan(b"bar").unwrap().matching_rules().len(), 2);
+}
+
+#[test]
+fn compilation_cache() {
+    let dir = std::env::temp_dir()
+        .join(format!("yara-x-cache-test-{}", std::process::id()));
+
+    let cache = CompilationCache::new(&dir).unwrap();
+    let src = SourceCode::from("rule test { condition: true }");
+
+    let mut key = CacheKey::new();
+    key.add_source("default", &src);
+
+    assert!(cache.get(&key).is_none());
+
+    let mut compiler = Compiler::new();
+    compiler.add_source(src.clone()).unwrap();
+    cache.insert(&key, &compiler.build()).unwrap();
+
+    let rules = cache.get(&key).unwrap();
+    assert_eq!(rules.iter().next().unwrap().name(), "test");
+
+    // Keys are different if the namespace, the source code or the settings
+    // are different.
+    let mut other_key = CacheKey::new();
+    other_key.add_source("foo", &src);
+    assert_ne!(key.to_hex(), other_key.to_hex());
+
+    let mut other_key = CacheKey::new();
+    other_key.add_source("default", &src.clone().with_origin("foo.yar"));
+    assert_ne!(key.to_hex(), other_key.to_hex());
+
+    let mut other_key = key.clone();
+    other_key.add_setting("global", "foo=1");
+    assert_ne!(key.to_hex(), other_key.to_hex());
+    assert!(cache.get(&other_key).is_none());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compilation_cache_fragments() {
+    let dir = std::env::temp_dir()
+        .join(format!("yara-x-fragments-test-{}", std::process::id()));
+
+    let cache = CompilationCache::new(&dir).unwrap();
+
+    let other = SourceCode::from(
+        r#"rule other { strings: $a = /x[0-9]{2,4}y/ condition: $a }"#,
+    );
+
+    let src = SourceCode::from(
+        r#"rule test {
+             strings:
+               $a = { 61 62 [0-2] 63 64 }
+               $b = /ef.gh/
+             condition:
+               $a and $b
+           }"#,
+    );
+
+    assert!(cache.get_fragment(&src).is_none());
+
+    // Compiling the source code with a cache stores the code produced
+    // for its regexps.
+    let mut compiler = Compiler::new();
+    compiler.set_cache(cache.clone());
+    compiler.add_source(src.clone()).unwrap();
+
+    let fragment = cache.get_fragment(&src).unwrap();
+    assert!(!fragment.is_modified());
+
+    // The code taken from the cache is linked after the code of the
+    // regexps that precede it, and the result must be the same as if
+    // the regexps were compiled from scratch.
+    let mut compiler = Compiler::new();
+    compiler.add_source(other.clone()).unwrap();
+    compiler.set_cache(cache.clone());
+    compiler.add_source(src.clone()).unwrap();
+    let cached = compiler.build();
+
+    let mut compiler = Compiler::new();
+    compiler.add_source(other.clone()).unwrap();
+    compiler.add_source(src.clone()).unwrap();
+    let expected = compiler.build();
+
+    assert_eq!(cached.re_code, expected.re_code);
+    assert_eq!(
+        bincode::DefaultOptions::new().serialize(&cached.atoms).unwrap(),
+        bincode::DefaultOptions::new().serialize(&expected.atoms).unwrap()
+    );
+
+    let mut scanner = Scanner::new(&cached).unwrap();
+    assert_eq!(
+        scanner
+            .scan(b"x123y abXcd efXgh")
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.name().to_string())
+            .collect::<Vec<_>>(),
+        ["other", "test"]
+    );
+
+    // Fragments that can't be deserialized are treated as missing, and
+    // replaced with a new one.
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        std::fs::write(entry.unwrap().path(), b"foo").unwrap();
+    }
+
+    assert!(cache.get_fragment(&src).is_none());
+
+    let mut compiler = Compiler::new();
+    compiler.set_cache(cache.clone());
+    compiler.add_source(src.clone()).unwrap();
+
+    assert!(cache.get_fragment(&src).is_some());
+    assert_eq!(compiler.build().re_code, compile(src).unwrap().re_code);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn integer_overflow() {
+    let src = r#"
+        rule wrap { condition: 0x7fffffffffffffff + filesize < 0 }
+        rule saturate {
+          condition:
+            0x7fffffffffffffff + filesize == 0x7fffffffffffffff and
+            -(filesize - 0x7fffffffffffffff - 2) == 0x7fffffffffffffff and
+            0x7fffffffffffffff * 2 == 0x7fffffffffffffff
+        }
+        rule undefined {
+          condition:
+            not defined (0x7fffffffffffffff + filesize) and
+            not defined (0x7fffffffffffffff * 2) and
+            defined (0x7ffffffffffffffe + filesize)
+        }"#;
+
+    let matching_rules = |behavior: IntegerOverflow| {
+        let mut compiler = Compiler::new();
+        compiler.integer_overflow(behavior).add_source(src).unwrap();
+        let rules = compiler.build();
+        let mut scanner = Scanner::new(&rules).unwrap();
+        scanner
+            .scan(b"x")
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.name().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching_rules(IntegerOverflow::Wrap), vec!["wrap"]);
+    assert_eq!(matching_rules(IntegerOverflow::Saturate), vec!["saturate"]);
+    assert_eq!(matching_rules(IntegerOverflow::Undefined), vec!["undefined"]);
+
+    let mut compiler = Compiler::new();
+
+    // With `IntegerOverflow::Error` overflows at compile time are errors,
+    // while overflows at scan time produce an undefined result.
+    compiler.integer_overflow(IntegerOverflow::Error);
+
+    let err = compiler
+        .add_source(r#"rule test { condition: 0x7fffffffffffffff * 2 > 0 }"#)
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        Error::CompileError(ref err)
+            if matches!(err.info(), CompileErrorInfo::ArithmeticOverflow { .. })
+    ));
+
+    compiler
+        .add_source(
+            r#"rule test_2 { condition: not defined (0x7fffffffffffffff + filesize) }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    assert_eq!(
+        Scanner::new(&rules)
+            .unwrap()
+            .scan(b"x")
+            .unwrap()
+            .matching_rules()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn ext_module() {
+    use protobuf::descriptor::field_descriptor_proto::{Label, Type};
+    use protobuf::descriptor::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet,
+    };
+    use protobuf::reflect::ReflectValueBox;
+    use protobuf::Message;
+
+    let field = |name: &str, number: i32, type_: Type| {
+        let mut field = FieldDescriptorProto::new();
+        field.set_name(name.to_string());
+        field.set_number(number);
+        field.set_label(Label::LABEL_OPTIONAL);
+        field.set_type(type_);
+        field
+    };
+
+    let mut message = DescriptorProto::new();
+    message.set_name("Metadata".to_string());
+    message.field.push(field("detections", 1, Type::TYPE_INT64));
+    message.field.push(field("country", 2, Type::TYPE_STRING));
+
+    let mut file = FileDescriptorProto::new();
+    file.set_name("metadata.proto".to_string());
+    file.set_package("test".to_string());
+    file.message_type.push(message);
+
+    let mut descriptor_set = FileDescriptorSet::new();
+    descriptor_set.file.push(file);
+
+    let descriptor_set = descriptor_set.write_to_bytes().unwrap();
+
+    assert!(matches!(
+        Compiler::new()
+            .ext_module_schema(descriptor_set.as_slice(), "test.Unknown")
+            .err()
+            .unwrap(),
+        ExtSchemaError::UnknownMessage(_)
+    ));
+
+    assert!(matches!(
+        Compiler::new()
+            .ext_module_schema(b"\xff", "test.Metadata")
+            .err()
+            .unwrap(),
+        ExtSchemaError::InvalidDescriptor(_)
+    ));
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .ext_module_schema(descriptor_set.as_slice(), "test.Metadata")
+        .unwrap()
+        .add_source(
+            r#"
+            import "ext"
+            rule a { condition: ext.detections > 2 and ext.country == "ES" }
+            rule b { condition: not defined ext.detections }"#,
+        )
+        .unwrap();
+
+    assert!(matches!(
+        compiler
+            .ext_module_schema(descriptor_set.as_slice(), "test.Metadata")
+            .err()
+            .unwrap(),
+        ExtSchemaError::AlreadyImported
+    ));
+
+    // The schema must survive serialization.
+    let rules =
+        Rules::deserialize(compiler.build().serialize().unwrap()).unwrap();
+
+    let descriptor = rules.ext_schema().unwrap().root_descriptor().unwrap();
+    let mut metadata = descriptor.new_instance();
+
+    descriptor
+        .field_by_name("detections")
+        .unwrap()
+        .set_singular_field(metadata.as_mut(), ReflectValueBox::I64(3));
+
+    descriptor.field_by_name("country").unwrap().set_singular_field(
+        metadata.as_mut(),
+        ReflectValueBox::String("ES".to_string()),
+    );
+
+    let metadata = metadata.write_to_bytes_dyn().unwrap();
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    // Without data for the module all its fields are undefined.
+    let matching_rules: Vec<_> = scanner
+        .scan(b"")
+        .unwrap()
+        .matching_rules()
+        .map(|rule| rule.name().to_string())
+        .collect();
+
+    assert_eq!(matching_rules, vec!["b"]);
+
+    scanner.set_module_data("ext", metadata.as_slice());
+
+    let matching_rules: Vec<_> = scanner
+        .scan(b"")
+        .unwrap()
+        .matching_rules()
+        .map(|rule| rule.name().to_string())
+        .collect();
+
+    assert_eq!(matching_rules, vec!["a"]);
+
+    scanner.set_module_data("ext", b"\xff");
+
+    assert!(matches!(
+        scanner.scan(b"").err().unwrap(),
+        ScanError::InvalidModuleData { .. }
+    ));
+}
@@ -174,6 +174,61 @@ rule test {
    │ 
    │ Note: the expression requires 3 matching patterns out of 2
 ───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    any of them
+}"#,
+            r#"warning: invariant boolean expression
+   ╭─[line:4:5]
+   │
+ 4 │     any of them
+   │     ──────┬─────  
+   │           ╰─────── this expression is always false
+   │ 
+   │ Note: the rule doesn't define any pattern
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    2 + 2 == 4
+}"#,
+            r#"warning: invariant boolean expression
+   ╭─[line:4:5]
+   │
+ 4 │     2 + 2 == 4
+   │     ─────┬────  
+   │          ╰────── this expression is always true
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  strings:
+    $a = "foo"
+  condition:
+    $a and "foo" contains "bar"
+}"#,
+            r#"warning: invariant boolean expression
+   ╭─[line:6:12]
+   │
+ 6 │     $a and "foo" contains "bar"
+   │            ──────────┬─────────  
+   │                      ╰─────────── this expression is always false
+───╯
 "#,
         ),
         ////////////////////////////////////////////////////////////
@@ -388,6 +443,107 @@ rule test {
    │          ───────┬──────  
    │                 ╰──────── this pattern may slow down the scan
 ───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule foo_1 {
+  condition:
+    true
+}
+rule foo_2 {
+  condition:
+    any of (foo_*)
+}"#,
+            r#"warning: rule `foo_2` depends on itself
+   ╭─[line:8:13]
+   │
+ 8 │     any of (foo_*)
+   │             ──┬──  
+   │               ╰──── `foo_2` is used in its own condition
+   │ 
+   │ Note: the rule is excluded from the set, as its result is unknown while its condition is being evaluated
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    filesize * 1.0 < 9007199254740993
+}"#,
+            r#"warning: lossy comparison between integer and float
+   ╭─[line:4:22]
+   │
+ 4 │     filesize * 1.0 < 9007199254740993
+   │                      ────────┬───────  
+   │                              ╰───────── `9007199254740993` can't be represented exactly as a float
+   │ 
+   │ Note: integers are converted to float before comparing them with a float
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    filesize == 1.5
+}"#,
+            r#"warning: lossy comparison between integer and float
+   ╭─[line:4:17]
+   │
+ 4 │     filesize == 1.5
+   │                 ─┬─  
+   │                  ╰─── this float has a fractional part, it is never equal to an integer
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "test_proto2-module")]
+        (
+            line!(),
+            r#"
+import "test_proto2"
+rule test {
+  condition:
+    filesize < 100 and not test_proto2.int64_undef == 1
+}"#,
+            r#"warning: `not` expression is never true when `test_proto2.int64_undef` is undefined
+   ╭─[line:5:28]
+   │
+ 5 │     filesize < 100 and not test_proto2.int64_undef == 1
+   │                            ───────────┬───────────  
+   │                                       ╰───────────── `test_proto2.int64_undef` is undefined if module `test_proto2` has no data for it
+   │ 
+   │ Note: `not` is undefined when its operand is undefined, use `not defined test_proto2.int64_undef or not ...` if this should be true when `test_proto2.int64_undef` is undefined
+───╯
+"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "test_proto2-module")]
+        (
+            line!(),
+            r#"
+import "test_proto2"
+rule test {
+  condition:
+    not (filesize + test_proto2.nested.nested_int64_one > 10)
+}"#,
+            r#"warning: `not` expression is never true when `test_proto2.nested.nested_int64_one` is undefined
+   ╭─[line:5:21]
+   │
+ 5 │     not (filesize + test_proto2.nested.nested_int64_one > 10)
+   │                     ─────────────────┬─────────────────  
+   │                                      ╰─────────────────── `test_proto2.nested.nested_int64_one` is undefined if module `test_proto2` has no data for it
+   │ 
+   │ Note: `not` is undefined when its operand is undefined, use `not defined test_proto2.nested.nested_int64_one or not ...` if this should be true when `test_proto2.nested.nested_int64_one` is undefined
+───╯
 "#,
         ),
     ];
@@ -480,6 +636,67 @@ rule test {
     $a = "foo"
   condition:
     all of ($a*, $a*) at 0
+}"#,
+        ), ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    true and not false
+}"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        (
+            line!(),
+            r#"
+rule test {
+  condition:
+    filesize == 1.0 and filesize < 1.5 and 1.0 * filesize < 9007199254740992
+}"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "test_proto2-module")]
+        (
+            line!(),
+            r#"
+import "test_proto2"
+rule test {
+  condition:
+    test_proto2.int64_one == 1 and not test_proto2.int64_undef == 1
+}"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "test_proto2-module")]
+        (
+            line!(),
+            r#"
+import "test_proto2"
+rule test {
+  condition:
+    not defined test_proto2.int64_undef or not test_proto2.int64_undef == 1
+}"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "test_proto2-module")]
+        (
+            line!(),
+            r#"
+import "test_proto2"
+rule test {
+  condition:
+    not (test_proto2.int64_undef == 1 or filesize > 10)
+}"#,
+        ),
+        ////////////////////////////////////////////////////////////
+        #[cfg(feature = "test_proto3-module")]
+        (
+            line!(),
+            r#"
+import "test_proto3"
+rule test {
+  condition:
+    not test_proto3.int64_one == 1
 }"#,
         ),
     ];
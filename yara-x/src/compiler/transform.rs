@@ -0,0 +1,282 @@
+/*! Transformations applied to text patterns by modifiers like `rot13`.
+
+Some pattern modifiers don't change the way in which a pattern is matched,
+but the text that is actually searched for. For instance, `$a = "foo" rot13`
+searches for `sbb`, which is the result of applying the ROT13 transformation
+to `foo`. These transformations are applied at compile time, and the
+resulting literals are treated exactly as if they had been written in the
+source code, which means that they can be combined with modifiers like
+`wide`, `nocase` and `fullword`.
+
+Adding a new transformation requires adding a variant to [`Transformation`],
+a flag to [`PatternFlags`] and the corresponding modifier to the parser.
+*/
+
+use bstr::{BString, ByteSlice};
+
+use crate::compiler::{PatternFlagSet, PatternFlags};
+
+/// Transformations that can be applied to the text of a pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(in crate::compiler) enum Transformation {
+    /// Rotates each ASCII letter 13 positions in the alphabet.
+    Rot13,
+    /// Encodes each byte as two hex digits, either in lowercase or in
+    /// uppercase.
+    HexAscii,
+    /// Encodes the text in UTF-7, as described in RFC 2152. Produces one
+    /// version where characters are encoded directly when possible, and
+    /// another one where all characters are encoded in modified base64.
+    Utf7,
+}
+
+impl Transformation {
+    /// All the existing transformations, and the flag that enables each of
+    /// them.
+    const ALL: [(Transformation, PatternFlags); 3] = [
+        (Transformation::Rot13, PatternFlags::Rot13),
+        (Transformation::HexAscii, PatternFlags::HexAscii),
+        (Transformation::Utf7, PatternFlags::Utf7),
+    ];
+
+    /// Returns the transformed versions of `text`. Some transformations
+    /// produce more than one version, for example, [`Transformation::HexAscii`]
+    /// produces one version with lowercase digits and another one with
+    /// uppercase digits.
+    pub fn apply(&self, text: &[u8]) -> Vec<BString> {
+        match self {
+            Transformation::Rot13 => vec![rot13(text)],
+            Transformation::HexAscii => {
+                let lowercase = hex_ascii(text, b"0123456789abcdef");
+                let uppercase = hex_ascii(text, b"0123456789ABCDEF");
+                if lowercase == uppercase {
+                    vec![lowercase]
+                } else {
+                    vec![lowercase, uppercase]
+                }
+            }
+            Transformation::Utf7 => {
+                let direct = utf7(text, false);
+                let base64 = utf7(text, true);
+                if direct == base64 {
+                    vec![direct]
+                } else {
+                    vec![direct, base64]
+                }
+            }
+        }
+    }
+}
+
+/// Returns the literals that must be searched for a pattern with the given
+/// text and flags.
+///
+/// If the flags don't include any transformation, the result is the text
+/// itself. Otherwise, the result contains the text transformed by each of
+/// the transformations, without duplicates.
+pub(in crate::compiler) fn transformed_literals(
+    flags: PatternFlagSet,
+    text: &[u8],
+) -> Vec<BString> {
+    let mut literals: Vec<BString> = Vec::new();
+    let mut transformed = false;
+
+    for (transformation, flag) in Transformation::ALL {
+        if flags.contains(flag) {
+            transformed = true;
+            for literal in transformation.apply(text) {
+                if !literals.contains(&literal) {
+                    literals.push(literal);
+                }
+            }
+        }
+    }
+
+    if !transformed {
+        literals.push(BString::from(text));
+    }
+
+    literals
+}
+
+fn rot13(text: &[u8]) -> BString {
+    text.iter()
+        .map(|b| match b {
+            b'a'..=b'z' => (b - b'a' + 13) % 26 + b'a',
+            b'A'..=b'Z' => (b - b'A' + 13) % 26 + b'A',
+            _ => *b,
+        })
+        .collect::<Vec<u8>>()
+        .into()
+}
+
+fn hex_ascii(text: &[u8], digits: &[u8; 16]) -> BString {
+    text.iter()
+        .flat_map(|b| [digits[(b >> 4) as usize], digits[(b & 0xf) as usize]])
+        .collect::<Vec<u8>>()
+        .into()
+}
+
+/// Encodes `text` in UTF-7.
+///
+/// If `text` is valid UTF-8 its characters are encoded, if not, each byte
+/// is treated as a character in the range U+0000 - U+00FF. Characters in
+/// sets D and O from RFC 2152, and whitespaces, are encoded directly, while
+/// the remaining ones are encoded in modified base64. This is the same
+/// approach used by most encoders. However, RFC 2152 allows encoding any
+/// character in modified base64, and some encoders do so. When `all_base64`
+/// is true every character is encoded in modified base64, including the
+/// ones that could have been encoded directly.
+///
+/// When the text ends with modified base64, the final `-` and any partial
+/// base64 character are not included in the result, as they depend on the
+/// characters that follow the text when it is part of a larger string.
+fn utf7(text: &[u8], all_base64: bool) -> BString {
+    const BASE64: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let chars: Vec<char> = match text.to_str() {
+        Ok(s) => s.chars().collect(),
+        Err(_) => text.iter().map(|b| *b as char).collect(),
+    };
+
+    let is_direct = |c: char| {
+        !all_base64
+            && (c.is_ascii_alphanumeric()
+                || "'(),-./:? \t\r\n".contains(c)
+                || "!\"#$%&*;<=>@[]^_`{|}".contains(c))
+    };
+
+    let mut result = Vec::new();
+    let mut in_base64 = false;
+    let mut bits = 0_u32;
+    let mut num_bits = 0;
+
+    for c in chars {
+        if is_direct(c) {
+            if in_base64 {
+                // Flush the remaining bits, padding them with zeroes.
+                if num_bits > 0 {
+                    result.push(
+                        BASE64[((bits << (6 - num_bits)) & 0x3f) as usize],
+                    );
+                    bits = 0;
+                    num_bits = 0;
+                }
+                // The `-` that ends the base64 sequence is required only if
+                // the next character could be confused with base64.
+                if c.is_ascii_alphanumeric() || c == '/' || c == '-' {
+                    result.push(b'-');
+                }
+                in_base64 = false;
+            }
+            result.push(c as u8);
+        } else if c == '+' && !in_base64 && !all_base64 {
+            result.extend_from_slice(b"+-");
+        } else {
+            if !in_base64 {
+                result.push(b'+');
+                in_base64 = true;
+            }
+            let mut buf = [0_u16; 2];
+            for unit in c.encode_utf16(&mut buf) {
+                bits = (bits << 16) | *unit as u32;
+                num_bits += 16;
+                while num_bits >= 6 {
+                    num_bits -= 6;
+                    result.push(BASE64[((bits >> num_bits) & 0x3f) as usize]);
+                }
+                bits &= (1 << num_bits) - 1;
+            }
+        }
+    }
+
+    result.into()
+}
+
+#[cfg(test)]
+mod test {
+    use bstr::BString;
+    use pretty_assertions::assert_eq;
+
+    use super::{transformed_literals, Transformation};
+    use crate::compiler::{PatternFlagSet, PatternFlags};
+
+    #[test]
+    fn rot13() {
+        assert_eq!(
+            Transformation::Rot13.apply(b"Hello, World!"),
+            vec![BString::from("Uryyb, Jbeyq!")]
+        );
+    }
+
+    #[test]
+    fn hex_ascii() {
+        assert_eq!(
+            Transformation::HexAscii.apply(b"foo"),
+            vec![BString::from("666f6f"), BString::from("666F6F")]
+        );
+
+        assert_eq!(
+            Transformation::HexAscii.apply(b"123"),
+            vec![BString::from("313233")]
+        );
+    }
+
+    #[test]
+    fn utf7() {
+        assert_eq!(
+            Transformation::Utf7.apply(b"Hi Mom -\xe2\x98\xba-!"),
+            vec![
+                BString::from("Hi Mom -+Jjo--!"),
+                BString::from("+AEgAaQAgAE0AbwBtACAALSY6AC0AI")
+            ]
+        );
+
+        assert_eq!(
+            Transformation::Utf7.apply("1 + 1 = 2".as_bytes()),
+            vec![
+                BString::from("1 +- 1 = 2"),
+                BString::from("+ADEAIAArACAAMQAgAD0AIAAy")
+            ]
+        );
+
+        assert_eq!(
+            Transformation::Utf7.apply("£1".as_bytes()),
+            vec![BString::from("+AKM-1"), BString::from("+AKMAM")]
+        );
+
+        // ASCII text is encoded directly, and in base64 too.
+        assert_eq!(
+            Transformation::Utf7.apply("hello".as_bytes()),
+            vec![BString::from("hello"), BString::from("+AGgAZQBsAGwAb")]
+        );
+
+        // The final partial base64 character is not included. Both versions
+        // are the same here.
+        assert_eq!(
+            Transformation::Utf7.apply("~".as_bytes()),
+            vec![BString::from("+AH")]
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            transformed_literals(PatternFlagSet::none(), b"foo"),
+            vec![BString::from("foo")]
+        );
+
+        assert_eq!(
+            transformed_literals(
+                PatternFlags::Rot13 | PatternFlags::Utf7,
+                b"foo"
+            ),
+            vec![
+                BString::from("sbb"),
+                BString::from("foo"),
+                BString::from("+AGYAbwBv")
+            ]
+        );
+    }
+}
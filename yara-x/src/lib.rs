@@ -32,7 +32,7 @@ compiler.add_source(r#"
 let rules = compiler.build();
 
 // Create a scanner that uses the compiled rules.
-let mut scanner = yara_x::Scanner::new(&rules);
+let mut scanner = yara_x::Scanner::new(&rules).unwrap();
 
 // Scan some data.
 let results = scanner.scan("Lorem ipsum".as_bytes()).unwrap();
@@ -42,17 +42,43 @@ assert_eq!(results.matching_rules().len(), 1);
 */
 
 pub use compiler::compile;
+pub use compiler::AtomDetails;
+pub use compiler::CacheKey;
+pub use compiler::CompilationCache;
 pub use compiler::CompileError;
 pub use compiler::CompileErrorInfo;
 pub use compiler::Compiler;
+pub use compiler::CompilerPolicy;
 pub use compiler::EmitWasmError;
 pub use compiler::Error;
+pub use compiler::ExtSchemaError;
+pub use compiler::IntegerOverflow;
+pub use compiler::LintPass;
+pub use compiler::LintReport;
+pub use compiler::MetaValue;
+pub use compiler::PatternDetails;
+pub use compiler::PatternKind;
+pub use compiler::ProfilingData;
+pub use compiler::RuleCost;
+pub use compiler::RuleDetails;
+pub use compiler::RuleLocation;
+pub use compiler::RuleProfile;
+pub use compiler::RuleTemplate;
 pub use compiler::Rules;
+pub use compiler::RulesStats;
 pub use compiler::SerializationError;
+pub use compiler::SourceProfile;
+pub use compiler::TemplateError;
 
+pub use modules::module_names;
+
+pub use scanner::ConsoleLevel;
+pub use scanner::ConsoleMessage;
 pub use scanner::Match;
 pub use scanner::Matches;
 pub use scanner::MatchingRules;
+pub use scanner::MemoryRegion;
+pub use scanner::ModuleOutputs;
 pub use scanner::NonMatchingRules;
 pub use scanner::Pattern;
 pub use scanner::Patterns;
@@ -60,6 +86,8 @@ pub use scanner::Rule;
 pub use scanner::ScanError;
 pub use scanner::ScanResults;
 pub use scanner::Scanner;
+pub use scanner::SkipReason;
+pub use scanner::SkippedRegion;
 
 pub use variables::Variable;
 pub use variables::VariableError;
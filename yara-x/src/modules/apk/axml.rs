@@ -0,0 +1,250 @@
+/*! Parser for the binary XML format used by `AndroidManifest.xml`.
+
+Binary XML files are a sequence of chunks. The chunks that matter for
+extracting the manifest's content are the string pool, which contains all
+the strings used in the document, the resource map, which maps attribute
+names to resource IDs, and the start element chunks, which contain the
+elements' names and attributes. Other chunks are ignored.
+*/
+
+use crate::modules::utils::{u16_at, u32_at};
+
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+
+/// Flag in the string pool header indicating that strings are encoded in
+/// UTF-8 instead of UTF-16.
+const UTF8_FLAG: u32 = 0x100;
+
+/// Value used in string references that don't point to any string.
+const NO_ENTRY: u32 = 0xffffffff;
+
+/// Value of an attribute.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Value {
+    String(String),
+    Int(i64),
+}
+
+/// An attribute of an element.
+pub(crate) struct Attribute {
+    name: String,
+    resource_id: Option<u32>,
+    pub value: Value,
+}
+
+impl Attribute {
+    /// Returns true if the attribute has the given name or resource ID.
+    ///
+    /// Attributes in the `android` namespace are identified by their
+    /// resource ID, obfuscated manifests may have empty or misleading
+    /// attribute names, but the resource IDs must be correct.
+    pub fn is(&self, name: &str, resource_id: Option<u32>) -> bool {
+        match (self.resource_id, resource_id) {
+            (Some(id), Some(expected)) => id == expected,
+            _ => self.name == name,
+        }
+    }
+}
+
+/// An XML element, without its children.
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Element {
+    /// Returns the value of the first attribute with the given name or
+    /// resource ID. See [`Attribute::is`].
+    pub fn attribute(
+        &self,
+        name: &str,
+        resource_id: Option<u32>,
+    ) -> Option<&Value> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.is(name, resource_id))
+            .map(|attr| &attr.value)
+    }
+}
+
+/// Parses a binary XML document and returns its elements in document
+/// order. Returns `None` if the data is not a binary XML document.
+pub(crate) fn parse(data: &[u8]) -> Option<Vec<Element>> {
+    if u16_at(data, 0)? != RES_XML_TYPE {
+        return None;
+    }
+
+    let mut strings = Vec::new();
+    let mut resource_ids: &[u8] = &[];
+    let mut elements = Vec::new();
+
+    let mut pos = u16_at(data, 2)? as usize;
+
+    while let (Some(chunk_type), Some(header_size), Some(size)) =
+        (u16_at(data, pos), u16_at(data, pos + 2), u32_at(data, pos + 4))
+    {
+        let header_size = header_size as usize;
+        let size = size as usize;
+
+        let chunk = match data.get(pos..pos.saturating_add(size)) {
+            Some(chunk) if size >= 8 && header_size <= size => chunk,
+            _ => break,
+        };
+
+        match chunk_type {
+            RES_STRING_POOL_TYPE => {
+                strings = parse_string_pool(chunk).unwrap_or_default();
+            }
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_ids = &chunk[header_size..];
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                if let Some(element) = parse_start_element(
+                    chunk,
+                    header_size,
+                    &strings,
+                    resource_ids,
+                ) {
+                    elements.push(element);
+                }
+            }
+            _ => {}
+        }
+
+        pos += size;
+    }
+
+    Some(elements)
+}
+
+fn parse_string_pool(chunk: &[u8]) -> Option<Vec<String>> {
+    let header_size = u16_at(chunk, 2)? as usize;
+    let string_count = u32_at(chunk, 8)?;
+    let flags = u32_at(chunk, 16)?;
+    let strings_start = u32_at(chunk, 20)? as usize;
+
+    let mut strings = Vec::new();
+
+    for i in 0..string_count as usize {
+        let offset = match u32_at(chunk, header_size + i * 4) {
+            Some(offset) => offset as usize,
+            None => break,
+        };
+        let s = chunk.get(strings_start.saturating_add(offset)..);
+        strings.push(
+            s.and_then(|s| {
+                if flags & UTF8_FLAG != 0 {
+                    decode_utf8(s)
+                } else {
+                    decode_utf16(s)
+                }
+            })
+            .unwrap_or_default(),
+        );
+    }
+
+    Some(strings)
+}
+
+/// Decodes a UTF-8 string from the string pool. The string is preceded by
+/// its length in UTF-16 units and its length in bytes, each of them
+/// encoded in 1 or 2 bytes.
+fn decode_utf8(data: &[u8]) -> Option<String> {
+    let read_len = |pos: usize| -> Option<(usize, usize)> {
+        let b = *data.get(pos)? as usize;
+        if b & 0x80 != 0 {
+            Some((((b & 0x7f) << 8) | *data.get(pos + 1)? as usize, 2))
+        } else {
+            Some((b, 1))
+        }
+    };
+
+    let (_, n) = read_len(0)?;
+    let (len, m) = read_len(n)?;
+
+    Some(String::from_utf8_lossy(data.get(n + m..n + m + len)?).into_owned())
+}
+
+/// Decodes a UTF-16 string from the string pool. The string is preceded by
+/// its length in UTF-16 units, encoded in 1 or 2 units.
+fn decode_utf16(data: &[u8]) -> Option<String> {
+    let first = u16_at(data, 0)? as usize;
+    let (len, start): (usize, usize) = if first & 0x8000 != 0 {
+        (((first & 0x7fff) << 16) | u16_at(data, 2)? as usize, 4)
+    } else {
+        (first, 2)
+    };
+
+    let units = data
+        .get(start..start.checked_add(len.checked_mul(2)?)?)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect::<Vec<_>>();
+
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn parse_start_element(
+    chunk: &[u8],
+    header_size: usize,
+    strings: &[String],
+    resource_ids: &[u8],
+) -> Option<Element> {
+    let string = |idx: u32| {
+        if idx == NO_ENTRY {
+            None
+        } else {
+            strings.get(idx as usize).cloned()
+        }
+    };
+
+    // The element's extension follows the chunk header.
+    let ext = header_size;
+    let name = string(u32_at(chunk, ext + 4)?).unwrap_or_default();
+    let attribute_start = u16_at(chunk, ext + 8)? as usize;
+    let attribute_size = u16_at(chunk, ext + 10)? as usize;
+    let attribute_count = u16_at(chunk, ext + 12)? as usize;
+
+    let mut attributes = Vec::new();
+
+    for i in 0..attribute_count {
+        let attr = ext + attribute_start + i * attribute_size;
+
+        let (name_idx, raw_value, data_type, data) = match (
+            u32_at(chunk, attr + 4),
+            u32_at(chunk, attr + 8),
+            chunk.get(attr + 15),
+            u32_at(chunk, attr + 16),
+        ) {
+            (Some(name), Some(raw), Some(data_type), Some(data)) => {
+                (name, raw, *data_type, data)
+            }
+            _ => break,
+        };
+
+        // Values of type string are stored in the string pool, the
+        // remaining types are stored in the attribute itself.
+        let value = match (string(raw_value), data_type) {
+            (Some(s), _) => Value::String(s),
+            (None, 0x03) => Value::String(string(data).unwrap_or_default()),
+            // Decimal and hexadecimal integers.
+            (None, 0x10) | (None, 0x11) => Value::Int(data as i32 as i64),
+            _ => Value::Int(data as i64),
+        };
+
+        // The resource map contains the resource ID of the first N
+        // strings in the string pool, where N is the map's size.
+        let resource_id = u32_at(resource_ids, name_idx as usize * 4);
+
+        attributes.push(Attribute {
+            name: string(name_idx).unwrap_or_default(),
+            resource_id,
+            value,
+        });
+    }
+
+    Some(Element { name, attributes })
+}
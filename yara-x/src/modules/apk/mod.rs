@@ -0,0 +1,443 @@
+use std::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::apk::*;
+use crate::modules::utils::zip::Archive;
+
+mod axml;
+mod signing;
+
+/// Maximum size of `AndroidManifest.xml` after decompression.
+const MAX_MANIFEST_SIZE: usize = 10 * 1024 * 1024;
+
+// Resource IDs of the attributes in the `android` namespace used by the
+// module.
+const ATTR_NAME: u32 = 0x01010003;
+const ATTR_MIN_SDK_VERSION: u32 = 0x0101020c;
+const ATTR_VERSION_CODE: u32 = 0x0101021b;
+const ATTR_VERSION_NAME: u32 = 0x0101021c;
+const ATTR_TARGET_SDK_VERSION: u32 = 0x01010270;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Apk {
+    parse(ctx.scanned_data()).unwrap_or_else(|| {
+        let mut apk = Apk::new();
+        apk.set_is_apk(false);
+        apk
+    })
+}
+
+/// Returns true if the package requests the given permission (e.g:
+/// `android.permission.SEND_SMS`).
+#[module_export]
+fn has_permission(
+    ctx: &ScanContext,
+    permission: RuntimeString,
+) -> Option<bool> {
+    let apk = ctx.module_output::<Apk>()?;
+    let permission = permission.as_bstr(ctx);
+
+    Some(apk.permissions.iter().any(|p| p.as_bytes() == permission))
+}
+
+/// Returns true if the package is signed with a certificate whose SHA-256
+/// digest is the given one. The digest is an hex string, and the comparison
+/// is case-insensitive.
+#[module_export]
+fn has_certificate(ctx: &ScanContext, sha256: RuntimeString) -> Option<bool> {
+    let apk = ctx.module_output::<Apk>()?;
+    let sha256 = sha256.as_bstr(ctx);
+
+    Some(
+        apk.certificates
+            .iter()
+            .any(|cert| cert.sha256().as_bytes().eq_ignore_ascii_case(sha256)),
+    )
+}
+
+/// Parses an APK. Returns `None` if the data is not an APK.
+fn parse(data: &[u8]) -> Option<Apk> {
+    let archive = Archive::parse(data)?;
+    let manifest = archive.entry(b"AndroidManifest.xml")?;
+
+    let elements = archive
+        .read(manifest, MAX_MANIFEST_SIZE)
+        .and_then(|manifest| axml::parse(&manifest))
+        .unwrap_or_default();
+
+    let mut apk = Apk::new();
+
+    apk.set_is_apk(true);
+
+    for element in elements.iter() {
+        let name = match element.attribute("name", Some(ATTR_NAME)) {
+            Some(axml::Value::String(name)) => {
+                Some(component_name(apk.package_name(), name))
+            }
+            _ => None,
+        };
+
+        match element.name.as_str() {
+            "manifest" => {
+                apk.package_name = string(element.attribute("package", None));
+                apk.version_code =
+                    int(element
+                        .attribute("versionCode", Some(ATTR_VERSION_CODE)));
+                apk.version_name = string(
+                    element.attribute("versionName", Some(ATTR_VERSION_NAME)),
+                );
+            }
+            "uses-sdk" => {
+                apk.min_sdk_version = int(element
+                    .attribute("minSdkVersion", Some(ATTR_MIN_SDK_VERSION)));
+                apk.target_sdk_version = int(element.attribute(
+                    "targetSdkVersion",
+                    Some(ATTR_TARGET_SDK_VERSION),
+                ));
+            }
+            "uses-permission" | "uses-permission-sdk-23" => {
+                apk.permissions.extend(string(
+                    element.attribute("name", Some(ATTR_NAME)),
+                ));
+            }
+            "activity" | "activity-alias" => apk.activities.extend(name),
+            "service" => apk.services.extend(name),
+            "receiver" => apk.receivers.extend(name),
+            "provider" => apk.providers.extend(name),
+            _ => {}
+        }
+    }
+
+    let v1 = signing::v1_certificates(&archive);
+    let (v2, v3) = signing::v2_v3_certificates(data, &archive);
+
+    apk.set_has_v1_signature(!v1.is_empty());
+    apk.set_has_v2_signature(!v2.is_empty());
+    apk.set_has_v3_signature(!v3.is_empty());
+
+    for (scheme, certificates) in [(1, v1), (2, v2), (3, v3)] {
+        for cert in certificates {
+            let mut sha256 = String::with_capacity(64);
+            for b in Sha256::digest(&cert) {
+                write!(sha256, "{:02x}", b).unwrap();
+            }
+            let mut certificate = Certificate::new();
+            certificate.set_scheme(scheme);
+            certificate.set_sha256(sha256);
+            apk.certificates.push(certificate);
+        }
+    }
+
+    Some(apk)
+}
+
+/// Returns the fully qualified name of a component declared in the
+/// manifest. Names that start with a dot, or don't contain any dot, are
+/// relative to the package.
+fn component_name(package: &str, name: &str) -> String {
+    if name.starts_with('.') {
+        format!("{}{}", package, name)
+    } else if !name.contains('.') {
+        format!("{}.{}", package, name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn string(value: Option<&axml::Value>) -> Option<String> {
+    match value? {
+        axml::Value::String(s) => Some(s.clone()),
+        axml::Value::Int(i) => Some(i.to_string()),
+    }
+}
+
+fn int(value: Option<&axml::Value>) -> Option<i64> {
+    match value? {
+        axml::Value::String(s) => s.parse().ok(),
+        axml::Value::Int(i) => Some(*i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use sha2::{Digest, Sha256};
+
+    use crate::modules::utils::zip::{build_zip, TestEntry};
+
+    /// Value of the `raw_value` field for attributes whose value is not a
+    /// string.
+    const NO_ENTRY: u32 = 0xffffffff;
+
+    /// An element in the binary XML manifest, with the index of its name in
+    /// [`STRINGS`] and its attributes.
+    type Element<'a> = (u32, &'a [(u32, u32, u32)]);
+
+    const STRINGS: &[&str] = &[
+        "name",
+        "versionCode",
+        "minSdkVersion",
+        "manifest",
+        "package",
+        "com.example.app",
+        "uses-permission",
+        "android.permission.INTERNET",
+        "activity",
+        ".MainActivity",
+        "receiver",
+        "Receiver",
+        "uses-sdk",
+    ];
+
+    /// Builds a binary XML manifest. Attributes are tuples with the index
+    /// of the name in [`STRINGS`], the raw value and the integer value.
+    fn build_manifest() -> Vec<u8> {
+        let mut pool = Vec::new();
+        let mut offsets = Vec::new();
+
+        for s in STRINGS {
+            offsets.extend((pool.len() as u32).to_le_bytes());
+            let units: Vec<u16> = s.encode_utf16().collect();
+            pool.extend((units.len() as u16).to_le_bytes());
+            for unit in units {
+                pool.extend(unit.to_le_bytes());
+            }
+            pool.extend([0, 0]);
+        }
+
+        let mut xml = Vec::new();
+
+        // String pool chunk, with UTF-16 strings.
+        let header_size = 28;
+        xml.extend(0x0001_u16.to_le_bytes());
+        xml.extend((header_size as u16).to_le_bytes());
+        xml.extend(
+            ((header_size + offsets.len() + pool.len()) as u32).to_le_bytes(),
+        );
+        xml.extend((STRINGS.len() as u32).to_le_bytes());
+        xml.extend(0_u32.to_le_bytes());
+        xml.extend(0_u32.to_le_bytes());
+        xml.extend(((header_size + offsets.len()) as u32).to_le_bytes());
+        xml.extend(0_u32.to_le_bytes());
+        xml.extend(offsets);
+        xml.extend(pool);
+
+        // Resource map for the first 3 strings.
+        xml.extend(0x0180_u16.to_le_bytes());
+        xml.extend(8_u16.to_le_bytes());
+        xml.extend(20_u32.to_le_bytes());
+        for id in [0x01010003_u32, 0x0101021b, 0x0101020c] {
+            xml.extend(id.to_le_bytes());
+        }
+
+        let elements: &[Element] = &[
+            (3, &[(4, 5, 5), (1, NO_ENTRY, 42)]),
+            (12, &[(2, NO_ENTRY, 21)]),
+            (6, &[(0, 7, 7)]),
+            (8, &[(0, 9, 9)]),
+            (10, &[(0, 11, 11)]),
+        ];
+
+        for (name, attributes) in elements {
+            xml.extend(0x0102_u16.to_le_bytes());
+            xml.extend(16_u16.to_le_bytes());
+            xml.extend((36 + attributes.len() as u32 * 20).to_le_bytes());
+            xml.extend(1_u32.to_le_bytes());
+            xml.extend(NO_ENTRY.to_le_bytes());
+            xml.extend(NO_ENTRY.to_le_bytes());
+            xml.extend(name.to_le_bytes());
+            xml.extend(20_u16.to_le_bytes());
+            xml.extend(20_u16.to_le_bytes());
+            xml.extend((attributes.len() as u16).to_le_bytes());
+            xml.extend([0; 6]);
+            for (name, raw_value, data) in attributes.iter() {
+                let data_type: u8 =
+                    if *raw_value == NO_ENTRY { 0x10 } else { 0x03 };
+                xml.extend(NO_ENTRY.to_le_bytes());
+                xml.extend(name.to_le_bytes());
+                xml.extend(raw_value.to_le_bytes());
+                xml.extend(8_u16.to_le_bytes());
+                xml.extend([0, data_type]);
+                xml.extend(data.to_le_bytes());
+            }
+        }
+
+        let mut manifest = Vec::new();
+        manifest.extend(0x0003_u16.to_le_bytes());
+        manifest.extend(8_u16.to_le_bytes());
+        manifest.extend((8 + xml.len() as u32).to_le_bytes());
+        manifest.extend(xml);
+        manifest
+    }
+
+    /// Encodes a DER value.
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut value = vec![tag];
+        if content.len() < 0x80 {
+            value.push(content.len() as u8);
+        } else {
+            value.push(0x82);
+            value.extend((content.len() as u16).to_be_bytes());
+        }
+        value.extend(content);
+        value
+    }
+
+    /// Builds a PKCS#7 `SignedData` structure containing a certificate.
+    fn build_pkcs7(cert: &[u8]) -> Vec<u8> {
+        let signed_data = [
+            der(0x02, &[1]),
+            der(0x31, &[]),
+            der(0x30, &der(0x06, &[0x2a, 0x86, 0x48])),
+            der(0xa0, cert),
+            der(0x31, &[]),
+        ]
+        .concat();
+
+        der(
+            0x30,
+            &[
+                der(0x06, &[0x2a, 0x86, 0x48]),
+                der(0xa0, &der(0x30, &signed_data)),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Prefixes some data with its length as a `u32`.
+    fn len_prefixed(data: &[u8]) -> Vec<u8> {
+        [&(data.len() as u32).to_le_bytes()[..], data].concat()
+    }
+
+    /// Builds an APK Signing Block with a v2 signature that contains a
+    /// certificate.
+    fn build_signing_block(cert: &[u8]) -> Vec<u8> {
+        let signed_data = [
+            len_prefixed(&[]),
+            len_prefixed(&len_prefixed(cert)),
+            len_prefixed(&[]),
+        ]
+        .concat();
+
+        let signer =
+            [len_prefixed(&signed_data), len_prefixed(&[]), len_prefixed(&[])]
+                .concat();
+
+        let value = len_prefixed(&len_prefixed(&signer));
+
+        let mut pairs = Vec::new();
+        pairs.extend((4 + value.len() as u64).to_le_bytes());
+        pairs.extend(0x7109871a_u32.to_le_bytes());
+        pairs.extend(value);
+
+        let size = (pairs.len() + 8 + 16) as u64;
+
+        [
+            &size.to_le_bytes()[..],
+            &pairs,
+            &size.to_le_bytes(),
+            b"APK Sig Block 42",
+        ]
+        .concat()
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        Sha256::digest(data).iter().fold(String::new(), |mut s, b| {
+            write!(s, "{:02x}", b).unwrap();
+            s
+        })
+    }
+
+    #[test]
+    fn end2end() {
+        let v1_cert = der(0x30, &der(0x02, &[1]));
+        let v2_cert = der(0x30, &der(0x02, &[2]));
+
+        let manifest = build_manifest();
+        let pkcs7 = build_pkcs7(&v1_cert);
+
+        let apk = build_zip(
+            &[
+                TestEntry::stored("AndroidManifest.xml", &manifest),
+                TestEntry::stored("META-INF/CERT.RSA", &pkcs7),
+                TestEntry::stored("classes.dex", b"dex\n035\x00"),
+            ],
+            &build_signing_block(&v2_cert),
+            "",
+        );
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                format!(
+                    r#"import "apk"
+                rule rule_1 {{
+                  condition:
+                    apk.is_apk and
+                    apk.package_name == "com.example.app" and
+                    apk.version_code == 42 and
+                    apk.min_sdk_version == 21 and
+                    not defined apk.version_name
+                }}
+                rule rule_2 {{
+                  condition:
+                    apk.permissions[0] == "android.permission.INTERNET" and
+                    apk.has_permission("android.permission.INTERNET") and
+                    not apk.has_permission("android.permission.SEND_SMS")
+                }}
+                rule rule_3 {{
+                  condition:
+                    apk.activities[0] == "com.example.app.MainActivity" and
+                    apk.receivers[0] == "com.example.app.Receiver"
+                }}
+                rule rule_4 {{
+                  condition:
+                    apk.has_v1_signature and
+                    apk.has_v2_signature and
+                    not apk.has_v3_signature and
+                    apk.certificates[0].scheme == 1 and
+                    apk.certificates[0].sha256 == "{}" and
+                    apk.certificates[1].scheme == 2 and
+                    apk.has_certificate("{}")
+                }}
+                rule rule_5 {{ condition: not apk.is_apk }}
+                "#,
+                    sha256(&v1_cert),
+                    sha256(&v2_cert).to_uppercase()
+                )
+                .as_str(),
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&apk),
+            ["rule_1", "rule_2", "rule_3", "rule_4"]
+        );
+
+        // A ZIP archive without `AndroidManifest.xml` is not an APK.
+        assert_eq!(
+            matching_rules(&build_zip(
+                &[TestEntry::stored("foo.txt", b"foo")],
+                b"",
+                ""
+            )),
+            ["rule_5"]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+/*! Extraction of signing certificates from APK signatures.
+
+APKs can be signed with multiple schemes at the same time. Scheme v1 is
+the JAR signing scheme, where signatures are PKCS#7 files inside the
+`META-INF` directory. Schemes v2 and v3 store the signatures in the APK
+Signing Block, located right before the ZIP central directory.
+*/
+
+use crate::modules::utils::der::Tlv;
+use crate::modules::utils::zip::Archive;
+
+/// Magic that identifies the APK Signing Block.
+const APK_SIG_BLOCK_MAGIC: &[u8] = b"APK Sig Block 42";
+
+/// ID of the APK Signature Scheme v2 block.
+const V2_BLOCK_ID: u32 = 0x7109871a;
+
+/// ID of the APK Signature Scheme v3 block.
+const V3_BLOCK_ID: u32 = 0xf05368c0;
+
+/// Maximum size of the PKCS#7 files that are parsed.
+const MAX_PKCS7_SIZE: usize = 1024 * 1024;
+
+/// Returns the certificates, in DER format, found in the v1 signatures of
+/// the APK.
+pub(crate) fn v1_certificates(archive: &Archive) -> Vec<Vec<u8>> {
+    let mut certificates = Vec::new();
+
+    for entry in archive.entries.iter() {
+        let is_signature = entry.name.starts_with(b"META-INF/")
+            && [&b".RSA"[..], b".DSA", b".EC"]
+                .iter()
+                .any(|ext| entry.name.ends_with(ext));
+
+        if !is_signature {
+            continue;
+        }
+
+        if let Some(pkcs7) = archive.read(entry, MAX_PKCS7_SIZE) {
+            certificates.extend(
+                pkcs7_certificates(&pkcs7)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|cert| cert.to_vec()),
+            );
+        }
+    }
+
+    certificates
+}
+
+/// Returns the certificates in a PKCS#7 `SignedData` structure.
+///
+/// ```text
+/// ContentInfo ::= SEQUENCE {
+///   contentType OBJECT IDENTIFIER,
+///   content [0] EXPLICIT SignedData }
+///
+/// SignedData ::= SEQUENCE {
+///   version INTEGER,
+///   digestAlgorithms SET,
+///   contentInfo SEQUENCE,
+///   certificates [0] IMPLICIT SET OF Certificate OPTIONAL,
+///   ... }
+/// ```
+fn pkcs7_certificates(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let (content_info, _) = Tlv::parse(data)?;
+    let content = content_info.children().find(|tlv| tlv.tag == 0xa0)?;
+    let (signed_data, _) = Tlv::parse(content.content)?;
+    let certificates = signed_data.children().find(|tlv| tlv.tag == 0xa0)?;
+
+    Some(certificates.children().map(|cert| cert.raw).collect())
+}
+
+/// Returns the certificates, in DER format, found in the signature scheme
+/// v2 and v3 blocks of the APK, in that order.
+pub(crate) fn v2_v3_certificates(
+    data: &[u8],
+    archive: &Archive,
+) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    let mut v2 = Vec::new();
+    let mut v3 = Vec::new();
+
+    for (id, value) in signing_block_pairs(data, archive) {
+        let certificates = match id {
+            V2_BLOCK_ID => &mut v2,
+            V3_BLOCK_ID => &mut v3,
+            _ => continue,
+        };
+        // Both blocks have the same structure up to the certificates, a
+        // sequence of signers where each signer starts with its signed
+        // data, which contains the digests followed by the certificates.
+        let mut signers = match len_prefixed(value) {
+            Some((signers, _)) => signers,
+            None => continue,
+        };
+        while let Some((signer, rest)) = len_prefixed(signers) {
+            signers = rest;
+            let mut certs = match len_prefixed(signer)
+                .and_then(|(signed_data, _)| len_prefixed(signed_data))
+                .and_then(|(_digests, rest)| len_prefixed(rest))
+            {
+                Some((certs, _)) => certs,
+                None => continue,
+            };
+            while let Some((cert, rest)) = len_prefixed(certs) {
+                certificates.push(cert.to_vec());
+                certs = rest;
+            }
+        }
+    }
+
+    (v2, v3)
+}
+
+/// Returns the ID-value pairs in the APK Signing Block.
+///
+/// ```text
+/// size of block in bytes (excluding this field) (uint64)
+/// sequence of uint64-length-prefixed ID-value pairs:
+///   ID (uint32)
+///   value (variable-length)
+/// size of block in bytes, same as the first field (uint64)
+/// magic "APK Sig Block 42" (16 bytes)
+/// ```
+fn signing_block_pairs<'a>(
+    data: &'a [u8],
+    archive: &Archive,
+) -> Vec<(u32, &'a [u8])> {
+    let mut pairs = Vec::new();
+    let block_end = archive.central_directory_offset as usize;
+
+    let footer = match block_end
+        .checked_sub(24)
+        .and_then(|start| data.get(start..block_end))
+    {
+        Some(footer) if footer.ends_with(APK_SIG_BLOCK_MAGIC) => footer,
+        _ => return pairs,
+    };
+
+    let block_size = u64::from_le_bytes(footer[..8].try_into().unwrap());
+
+    let mut pairs_data = match usize::try_from(block_size)
+        .ok()
+        .and_then(|size| block_end.checked_sub(size))
+        .and_then(|start| data.get(start..block_end - 24))
+    {
+        Some(pairs_data) => pairs_data,
+        None => return pairs,
+    };
+
+    while let Some(len) = pairs_data.get(..8).and_then(|len| {
+        usize::try_from(u64::from_le_bytes(len.try_into().unwrap())).ok()
+    }) {
+        let pair = match pairs_data.get(8..8_usize.saturating_add(len)) {
+            Some(pair) if pair.len() >= 4 => pair,
+            _ => break,
+        };
+        pairs.push((
+            u32::from_le_bytes(pair[..4].try_into().unwrap()),
+            &pair[4..],
+        ));
+        pairs_data = &pairs_data[8 + len..];
+    }
+
+    pairs
+}
+
+/// Splits a value prefixed by its length as a `u32`, returning the value
+/// and the data that follows it.
+fn len_prefixed(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u32::from_le_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let value = data.get(4..4_usize.checked_add(len)?)?;
+    Some((value, &data[4 + len..]))
+}
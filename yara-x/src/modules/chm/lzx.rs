@@ -0,0 +1,567 @@
+/*! LZX decompressor for the compressed section of CHM files.
+
+LZX is an LZ77 variant with Huffman coding, where the compressed data is
+a sequence of blocks, each one with its own Huffman trees. The output is
+divided in frames of 32KB, and the decompressor's state is reset every
+certain number of frames, given by the CHM's control data.
+
+The bitstream is formed by 16-bit little-endian words, whose bits are read
+from the most significant to the least significant one.
+
+Translation of x86 CALL instructions (E8 preprocessing) is not supported,
+as CHM files don't use it.
+
+See: https://learn.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-patch/
+*/
+
+/// Size of the frames in the decompressed data.
+pub(crate) const FRAME_SIZE: usize = 32768;
+
+const MIN_MATCH: usize = 2;
+const NUM_CHARS: usize = 256;
+const NUM_PRIMARY_LENGTHS: usize = 7;
+const NUM_SECONDARY_LENGTHS: usize = 249;
+const PRETREE_SIZE: usize = 20;
+const ALIGNED_SIZE: usize = 8;
+const MAX_CODE_LEN: usize = 16;
+
+const BLOCK_VERBATIM: u32 = 1;
+const BLOCK_ALIGNED: u32 = 2;
+const BLOCK_UNCOMPRESSED: u32 = 3;
+
+/// Decompresses `input`, which was compressed with a window of
+/// `2^window_bits` bytes and resetting the state every `reset_interval`
+/// frames. Returns the first `output_len` bytes of the decompressed data,
+/// or `None` if the data is corrupt.
+pub(crate) fn decompress(
+    input: &[u8],
+    window_bits: u32,
+    reset_interval: usize,
+    output_len: usize,
+) -> Option<Vec<u8>> {
+    let num_position_slots = match window_bits {
+        15 => 30,
+        16 => 32,
+        17 => 34,
+        18 => 36,
+        19 => 38,
+        20 => 42,
+        21 => 50,
+        _ => return None,
+    };
+
+    if reset_interval == 0 {
+        return None;
+    }
+
+    let mut lzx = Decoder {
+        bits: BitReader::new(input),
+        output: Vec::with_capacity(output_len),
+        window_size: 1 << window_bits,
+        main_lens: vec![0; NUM_CHARS + num_position_slots * 8],
+        length_lens: vec![0; NUM_SECONDARY_LENGTHS],
+        main: Huffman::default(),
+        length: Huffman::default(),
+        aligned: Huffman::default(),
+        r: [1, 1, 1],
+        block_type: 0,
+        block_remaining: 0,
+        block_odd: false,
+    };
+
+    let (position_base, extra_bits) = position_tables();
+    let mut frame = 0;
+
+    while lzx.output.len() < output_len {
+        if frame % reset_interval == 0 {
+            if lzx.block_remaining != 0 {
+                return None;
+            }
+            lzx.reset()?;
+        }
+
+        let frame_end = ((frame + 1) * FRAME_SIZE).min(output_len);
+
+        while lzx.output.len() < frame_end {
+            if lzx.block_remaining == 0 {
+                lzx.read_block_header()?;
+            }
+
+            let run = lzx.block_remaining.min(frame_end - lzx.output.len());
+            let produced = match lzx.block_type {
+                BLOCK_UNCOMPRESSED => lzx.copy_uncompressed(run)?,
+                _ => lzx.decode_run(run, &position_base, &extra_bits)?,
+            };
+
+            // Matches can go beyond the end of the run, but not beyond the
+            // end of the block.
+            lzx.block_remaining = lzx.block_remaining.checked_sub(produced)?;
+        }
+
+        // The bitstream is aligned to 16 bits at the end of each frame.
+        lzx.bits.align_to_word();
+
+        frame += 1;
+    }
+
+    lzx.output.truncate(output_len);
+    Some(lzx.output)
+}
+
+/// Returns the base offset and the number of extra bits of each position
+/// slot.
+fn position_tables() -> ([usize; 51], [u32; 51]) {
+    let mut position_base = [0; 51];
+    let mut extra_bits = [0; 51];
+
+    for i in 0..51 {
+        extra_bits[i] = if i < 4 { 0 } else { ((i - 2) / 2).min(17) as u32 };
+        if i > 0 {
+            position_base[i] = position_base[i - 1] + (1 << extra_bits[i - 1]);
+        }
+    }
+
+    (position_base, extra_bits)
+}
+
+struct Decoder<'a> {
+    bits: BitReader<'a>,
+    output: Vec<u8>,
+    window_size: usize,
+    /// Code lengths of the main and length trees, which are delta-encoded
+    /// with respect to the ones in the previous block.
+    main_lens: Vec<u8>,
+    length_lens: Vec<u8>,
+    main: Huffman,
+    length: Huffman,
+    aligned: Huffman,
+    /// The three most recent match offsets.
+    r: [usize; 3],
+    block_type: u32,
+    block_remaining: usize,
+    /// True if the current block is uncompressed and has an odd length,
+    /// which means that it's followed by a padding byte.
+    block_odd: bool,
+}
+
+impl<'a> Decoder<'a> {
+    /// Resets the decoder's state, and reads the header at the start of
+    /// the stream.
+    fn reset(&mut self) -> Option<()> {
+        self.r = [1, 1, 1];
+        self.main_lens.fill(0);
+        self.length_lens.fill(0);
+        self.block_type = 0;
+        self.block_remaining = 0;
+
+        // The header has a single bit that indicates if E8 preprocessing
+        // is used.
+        if self.bits.read(1)? != 0 {
+            return None;
+        }
+
+        Some(())
+    }
+
+    fn read_block_header(&mut self) -> Option<()> {
+        if self.block_odd {
+            self.bits.skip_byte()?;
+            self.block_odd = false;
+        }
+
+        self.block_type = self.bits.read(3)?;
+        let high = self.bits.read(16)? as usize;
+        let low = self.bits.read(8)? as usize;
+        self.block_remaining = high << 8 | low;
+
+        match self.block_type {
+            BLOCK_ALIGNED | BLOCK_VERBATIM => {
+                if self.block_type == BLOCK_ALIGNED {
+                    let mut lens = [0; ALIGNED_SIZE];
+                    for len in lens.iter_mut() {
+                        *len = self.bits.read(3)? as u8;
+                    }
+                    self.aligned = Huffman::new(&lens)?;
+                }
+
+                let mut lens = std::mem::take(&mut self.main_lens);
+                self.read_lengths(&mut lens[..NUM_CHARS])?;
+                self.read_lengths(&mut lens[NUM_CHARS..])?;
+                self.main = Huffman::new(&lens)?;
+                self.main_lens = lens;
+
+                let mut lens = std::mem::take(&mut self.length_lens);
+                self.read_lengths(&mut lens)?;
+                self.length = Huffman::new(&lens)?;
+                self.length_lens = lens;
+            }
+            BLOCK_UNCOMPRESSED => {
+                self.bits.align_for_bytes()?;
+                for r in self.r.iter_mut() {
+                    let bytes = self.bits.read_bytes(4)?;
+                    *r =
+                        u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+                }
+                self.block_odd = self.block_remaining % 2 == 1;
+            }
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    /// Reads code lengths, which are encoded as deltas with respect to the
+    /// previous ones using a pretree.
+    fn read_lengths(&mut self, lens: &mut [u8]) -> Option<()> {
+        let mut pretree_lens = [0; PRETREE_SIZE];
+        for len in pretree_lens.iter_mut() {
+            *len = self.bits.read(4)? as u8;
+        }
+        let pretree = Huffman::new(&pretree_lens)?;
+
+        let delta = |prev: u8, z: usize| ((prev as usize + 17 - z) % 17) as u8;
+
+        let mut i = 0;
+        while i < lens.len() {
+            match pretree.decode(&mut self.bits)? {
+                17 => {
+                    let n = self.bits.read(4)? as usize + 4;
+                    fill(lens, &mut i, n, 0);
+                }
+                18 => {
+                    let n = self.bits.read(5)? as usize + 20;
+                    fill(lens, &mut i, n, 0);
+                }
+                19 => {
+                    let n = self.bits.read(1)? as usize + 4;
+                    let z = pretree.decode(&mut self.bits)?;
+                    if z > 16 {
+                        return None;
+                    }
+                    let len = delta(lens[i], z);
+                    fill(lens, &mut i, n, len);
+                }
+                z => {
+                    lens[i] = delta(lens[i], z);
+                    i += 1;
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    /// Decodes at least `run` bytes from a verbatim or aligned block,
+    /// returning the number of decoded bytes.
+    fn decode_run(
+        &mut self,
+        run: usize,
+        position_base: &[usize],
+        extra_bits: &[u32],
+    ) -> Option<usize> {
+        let start = self.output.len();
+
+        while self.output.len() - start < run {
+            let element = self.main.decode(&mut self.bits)?;
+
+            if element < NUM_CHARS {
+                self.output.push(element as u8);
+                continue;
+            }
+
+            let element = element - NUM_CHARS;
+            let mut match_len = element & NUM_PRIMARY_LENGTHS;
+            if match_len == NUM_PRIMARY_LENGTHS {
+                match_len += self.length.decode(&mut self.bits)?;
+            }
+            match_len += MIN_MATCH;
+
+            let slot = element >> 3;
+            let offset = match slot {
+                0 => self.r[0],
+                1 => {
+                    self.r.swap(0, 1);
+                    self.r[0]
+                }
+                2 => {
+                    self.r.swap(0, 2);
+                    self.r[0]
+                }
+                _ => {
+                    let extra = extra_bits[slot];
+                    let mut offset = position_base[slot] - 2;
+                    if self.block_type == BLOCK_ALIGNED && extra >= 3 {
+                        offset += (self.bits.read(extra - 3)? as usize) << 3;
+                        offset += self.aligned.decode(&mut self.bits)?;
+                    } else {
+                        offset += self.bits.read(extra)? as usize;
+                    }
+                    self.r = [offset, self.r[0], self.r[1]];
+                    offset
+                }
+            };
+
+            if offset == 0
+                || offset > self.output.len()
+                || offset > self.window_size
+            {
+                return None;
+            }
+
+            // The match can overlap with the bytes being copied.
+            let from = self.output.len() - offset;
+            for i in 0..match_len {
+                let byte = self.output[from + i];
+                self.output.push(byte);
+            }
+        }
+
+        Some(self.output.len() - start)
+    }
+
+    /// Copies `run` bytes from an uncompressed block.
+    fn copy_uncompressed(&mut self, run: usize) -> Option<usize> {
+        let bytes = self.bits.read_bytes(run)?;
+        self.output.extend_from_slice(bytes);
+        Some(run)
+    }
+}
+
+/// Sets `n` code lengths to `len`, starting at `lens[*i]`.
+fn fill(lens: &mut [u8], i: &mut usize, n: usize, len: u8) {
+    let end = (*i + n).min(lens.len());
+    lens[*i..end].fill(len);
+    *i = end;
+}
+
+/// A canonical Huffman code.
+#[derive(Default)]
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; MAX_CODE_LEN + 1],
+    /// Symbols sorted by code length, and then by their value.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the code from the length of the code for each symbol, where
+    /// zero means that the symbol is not used. Returns `None` if the
+    /// lengths don't form a valid code.
+    fn new(lens: &[u8]) -> Option<Self> {
+        let mut huffman = Self::default();
+
+        for len in lens {
+            huffman.counts[*len as usize] += 1;
+        }
+        huffman.counts[0] = 0;
+
+        // Check that the code is not over-subscribed.
+        let mut left: i32 = 1;
+        for count in &huffman.counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return None;
+            }
+        }
+
+        for len in 1..=MAX_CODE_LEN {
+            for (symbol, l) in lens.iter().enumerate() {
+                if *l as usize == len {
+                    huffman.symbols.push(symbol as u16);
+                }
+            }
+        }
+
+        Some(huffman)
+    }
+
+    /// Decodes a symbol, reading its code bit by bit.
+    fn decode(&self, bits: &mut BitReader) -> Option<usize> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Some(
+                    self.symbols[(index + code - first) as usize] as usize,
+                );
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+/// Reads bits from a sequence of 16-bit little-endian words, starting at
+/// the most significant bit of each word.
+struct BitReader<'a> {
+    input: &'a [u8],
+    /// Position of the next word to be loaded in the buffer.
+    pos: usize,
+    /// Bits in the buffer, aligned to the most significant bit.
+    buffer: u64,
+    bits_left: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, pos: 0, buffer: 0, bits_left: 0 }
+    }
+
+    /// Reads `n` bits, with `n` up to 32.
+    fn read(&mut self, n: u32) -> Option<u32> {
+        if n == 0 {
+            return Some(0);
+        }
+        while self.bits_left < n {
+            let word = self.input.get(self.pos..self.pos + 2)?;
+            let word = u16::from_le_bytes([word[0], word[1]]) as u64;
+            self.buffer |= word << (48 - self.bits_left);
+            self.bits_left += 16;
+            self.pos += 2;
+        }
+        let value = (self.buffer >> (64 - n)) as u32;
+        self.buffer <<= n;
+        self.bits_left -= n;
+        Some(value)
+    }
+
+    /// Discards the remaining bits in the current word.
+    fn align_to_word(&mut self) {
+        let n = self.bits_left % 16;
+        self.buffer <<= n;
+        self.bits_left -= n;
+    }
+
+    /// Aligns the input for reading bytes, as done at the start of
+    /// uncompressed blocks. The remaining bits of the current word are
+    /// discarded, or the whole next word if the current one was completely
+    /// read.
+    fn align_for_bytes(&mut self) -> Option<()> {
+        match self.bits_left % 16 {
+            0 => self.read(16)?,
+            n => self.read(n)?,
+        };
+        // Words that were loaded in the buffer but not read yet are read
+        // again as bytes.
+        self.pos -= (self.bits_left / 8) as usize;
+        self.buffer = 0;
+        self.bits_left = 0;
+        Some(())
+    }
+
+    /// Reads `n` bytes. The buffer must be empty.
+    fn read_bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.input.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn skip_byte(&mut self) -> Option<()> {
+        self.read_bytes(1).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::decompress;
+
+    /// Writes bits in the order used by LZX.
+    #[derive(Default)]
+    struct BitWriter {
+        words: Vec<u16>,
+        current: u16,
+        len: u32,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, bits: u32) {
+            for i in (0..bits).rev() {
+                self.current = self.current << 1 | ((value >> i) & 1) as u16;
+                self.len += 1;
+                if self.len == 16 {
+                    self.words.push(self.current);
+                    self.current = 0;
+                    self.len = 0;
+                }
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            if self.len > 0 {
+                self.write(0, 16 - self.len);
+            }
+            self.words.iter().flat_map(|word| word.to_le_bytes()).collect()
+        }
+    }
+
+    /// Writes the header of a verbatim block for a window of 32KB, where
+    /// all the symbols in the main tree have 9-bit codes and the length
+    /// tree is empty. The codes in the main tree are equal to the symbols.
+    fn verbatim_block(w: &mut BitWriter, len: u32) {
+        w.write(1, 3);
+        w.write(len >> 8, 16);
+        w.write(len & 0xff, 8);
+
+        // The pretree has 5-bit codes for all the symbols, and symbol 8
+        // changes the length from 0 to 9.
+        for num_lens in [256, 240] {
+            (0..20).for_each(|_| w.write(5, 4));
+            (0..num_lens).for_each(|_| w.write(8, 5));
+        }
+
+        (0..20).for_each(|_| w.write(5, 4));
+        (0..249).for_each(|_| w.write(0, 5));
+    }
+
+    #[test]
+    fn verbatim() {
+        let mut w = BitWriter::default();
+        w.write(0, 1);
+        verbatim_block(&mut w, 12);
+        for c in b"abc" {
+            w.write(*c as u32, 9);
+        }
+        // A match of 6 bytes at offset 3, which is position slot 4 with a
+        // single extra bit.
+        w.write(256 + (4 << 3) + 4, 9);
+        w.write(1, 1);
+        // A match of 3 bytes that repeats the last offset.
+        w.write(256 + 1, 9);
+        let data = w.finish();
+
+        assert_eq!(
+            decompress(&data, 15, 1, 12),
+            Some(b"abcabcabcabc".to_vec())
+        );
+        assert_eq!(decompress(&data, 15, 1, 5), Some(b"abcab".to_vec()));
+        assert_eq!(decompress(&data, 15, 1, 13), None);
+        assert_eq!(decompress(&data, 14, 1, 12), None);
+    }
+
+    #[test]
+    fn uncompressed() {
+        let mut w = BitWriter::default();
+        w.write(0, 1);
+        w.write(3, 3);
+        w.write(0, 16);
+        w.write(5, 8);
+        let mut data = w.finish();
+        data.extend([1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        data.extend(b"hello\x00");
+
+        assert_eq!(decompress(&data, 15, 1, 5), Some(b"hello".to_vec()));
+
+        // Block type 7 is not valid.
+        data[1] = 0x70;
+        assert_eq!(decompress(&data, 15, 1, 5), None);
+    }
+}
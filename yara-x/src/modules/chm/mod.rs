@@ -0,0 +1,493 @@
+/*! YARA module that parses compiled HTML help files (.chm).
+
+CHM files are containers with an internal directory of files, which are
+stored either uncompressed in section 0, or compressed with LZX in
+section 1. The module lists the files, decompresses section 1, and finds
+the scripts in the HTML files. The content of any file, decompressed if
+necessary, can be obtained with the `object` function.
+
+See: http://www.nongnu.org/chmspec/latest/
+*/
+
+use std::cell::RefCell;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::chm::*;
+use crate::modules::utils::{html, u32_at, u64_at};
+
+mod lzx;
+
+/// Maximum number of entries in each of the repeated fields.
+const MAX_ENTRIES: usize = 16384;
+
+/// Maximum size of the decompressed section 1.
+const MAX_CONTENT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Files in section 0 that describe section 1.
+const CONTENT: &str = "::DataSpace/Storage/MSCompressed/Content";
+const CONTROL_DATA: &str = "::DataSpace/Storage/MSCompressed/ControlData";
+
+/// An entry in the internal directory.
+struct Entry {
+    name: String,
+    section: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// The internal directory.
+struct Directory {
+    language: u32,
+    /// Offset of section 0 within the file.
+    section0: usize,
+    entries: Vec<Entry>,
+    /// True if the directory has more than `MAX_ENTRIES` entries.
+    truncated: bool,
+}
+
+impl Directory {
+    fn find(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+}
+
+/// Location of the sections of the file being scanned.
+struct Sections {
+    /// Offset of section 0 within the file.
+    section0: usize,
+    /// Decompressed content of section 1, if available.
+    section1: Option<Vec<u8>>,
+}
+
+impl Sections {
+    /// Returns the content of a file.
+    fn get<'a>(
+        &'a self,
+        data: &'a [u8],
+        section: u64,
+        offset: u64,
+        size: u64,
+    ) -> Option<&'a [u8]> {
+        let offset = usize::try_from(offset).ok()?;
+        let end = offset.checked_add(usize::try_from(size).ok()?)?;
+        match section {
+            0 => data.get(
+                self.section0.checked_add(offset)?
+                    ..self.section0.checked_add(end)?,
+            ),
+            1 => self.section1.as_ref()?.get(offset..end),
+            _ => None,
+        }
+    }
+}
+
+thread_local!(
+    /// Sections of the file being scanned, used by `object` for obtaining
+    /// the content of files. It's set before every scan.
+    static SECTIONS: RefCell<Option<Sections>> = const { RefCell::new(None) };
+);
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Chm {
+    SECTIONS.with(|sections| *sections.borrow_mut() = None);
+
+    let data = ctx.scanned_data();
+    let mut chm = Chm::new();
+
+    let directory = match parse(data) {
+        Some(directory) => directory,
+        None => {
+            chm.set_is_chm(false);
+            return chm;
+        }
+    };
+
+    chm.set_is_chm(true);
+    chm.set_language(directory.language.into());
+    chm.set_files_truncated(directory.truncated);
+
+    let mut sections =
+        Sections { section0: directory.section0, section1: None };
+
+    sections.section1 = decompress(data, &directory, &sections);
+    chm.set_is_decompressed(sections.section1.is_some());
+
+    for entry in directory.entries.iter() {
+        let mut file = ChmFile::new();
+        file.set_name(entry.name.clone());
+        file.set_section(entry.section as i64);
+        file.set_offset(entry.offset as i64);
+        file.set_size(entry.size as i64);
+        chm.files.push(file);
+
+        let name = entry.name.to_ascii_lowercase();
+        if !name.ends_with(".htm")
+            && !name.ends_with(".html")
+            && !name.ends_with(".hta")
+        {
+            continue;
+        }
+
+        let content = match sections.get(
+            data,
+            entry.section,
+            entry.offset,
+            entry.size,
+        ) {
+            Some(content) => content,
+            None => continue,
+        };
+
+        for script in html::scripts(content) {
+            if chm.scripts.len() == MAX_ENTRIES {
+                chm.set_scripts_truncated(true);
+                break;
+            }
+            let mut s = ChmScript::new();
+            s.set_file(entry.name.clone());
+            s.language = script.language;
+            s.src = script.src;
+            s.set_offset(script.offset as i64);
+            s.set_size(script.size as i64);
+            chm.scripts.push(s);
+        }
+    }
+
+    SECTIONS.with(|s| *s.borrow_mut() = Some(sections));
+
+    chm
+}
+
+/// Returns the content of the file with the given name (e.g: `/index.htm`).
+/// Compressed files are returned decompressed. The result is undefined if
+/// the file doesn't exist or its content is not available.
+#[module_export]
+fn object(
+    ctx: &mut ScanContext,
+    name: RuntimeString,
+) -> Option<RuntimeString> {
+    let chm = ctx.module_output::<Chm>()?;
+    let name = name.as_bstr(ctx);
+
+    let file = chm.files.iter().find(|file| file.name().as_bytes() == name)?;
+    let (section, offset, size) =
+        (file.section() as u64, file.offset() as u64, file.size() as u64);
+
+    let content = SECTIONS.with(|sections| {
+        sections
+            .borrow()
+            .as_ref()?
+            .get(ctx.scanned_data(), section, offset, size)
+            .map(|content| content.to_vec())
+    })?;
+
+    Some(RuntimeString::from_bytes(ctx, content))
+}
+
+/// Parses the file's header and its internal directory.
+fn parse(data: &[u8]) -> Option<Directory> {
+    if !data.starts_with(b"ITSF") {
+        return None;
+    }
+
+    let version = u32_at(data, 0x04)?;
+    let language = u32_at(data, 0x14)?;
+    let directory_offset = u64_at(data, 0x48)?;
+    let directory_len = u64_at(data, 0x50)?;
+
+    // Version 3 headers have the offset of section 0, in previous versions
+    // it starts right after the directory.
+    let section0 = if version >= 3 {
+        u64_at(data, 0x58)?
+    } else {
+        directory_offset.checked_add(directory_len)?
+    };
+
+    let itsp = data.get(usize::try_from(directory_offset).ok()?..)?;
+
+    if !itsp.starts_with(b"ITSP") {
+        return None;
+    }
+
+    let header_len = u32_at(itsp, 0x08)? as usize;
+    let chunk_size = u32_at(itsp, 0x10)? as usize;
+    let num_chunks = u32_at(itsp, 0x2c)? as usize;
+
+    if chunk_size < 0x14 {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut truncated = false;
+
+    // Listing chunks (PMGL) have the entries sorted by name, index chunks
+    // (PMGI) are only used for speeding up lookups, and are ignored.
+    'chunks: for chunk in
+        itsp.get(header_len..)?.chunks_exact(chunk_size).take(num_chunks)
+    {
+        if !chunk.starts_with(b"PMGL") {
+            continue;
+        }
+
+        // The end of the chunk has free space followed by an index of the
+        // entries, whose combined size is in the chunk's header.
+        let free_space = u32_at(chunk, 0x04)? as usize;
+        let end = chunk_size.saturating_sub(free_space).max(0x14);
+        let mut input = &chunk[0x14..end];
+
+        while let Some(entry) = read_entry(&mut input) {
+            if entries.len() == MAX_ENTRIES {
+                truncated = true;
+                break 'chunks;
+            }
+            entries.push(entry);
+        }
+    }
+
+    Some(Directory {
+        language,
+        section0: usize::try_from(section0).ok()?,
+        entries,
+        truncated,
+    })
+}
+
+/// Reads an entry of the directory, which has the length of the name, the
+/// name, the section, the offset and the size.
+fn read_entry(input: &mut &[u8]) -> Option<Entry> {
+    let len = usize::try_from(encint(input)?).ok()?;
+    let name = input.get(..len)?;
+    *input = &input[len..];
+
+    Some(Entry {
+        name: String::from_utf8_lossy(name).into_owned(),
+        section: encint(input)?,
+        offset: encint(input)?,
+        size: encint(input)?,
+    })
+}
+
+/// Reads a variable-length integer, where each byte contributes 7 bits,
+/// the most significant ones first, and the highest bit indicates that more
+/// bytes follow.
+fn encint(input: &mut &[u8]) -> Option<u64> {
+    let mut value: u64 = 0;
+
+    for (i, byte) in input.iter().enumerate().take(10) {
+        value = value << 7 | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            *input = &input[i + 1..];
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Decompresses section 1, up to the end of the last file stored in it.
+fn decompress(
+    data: &[u8],
+    directory: &Directory,
+    sections: &Sections,
+) -> Option<Vec<u8>> {
+    let output_len = directory
+        .entries
+        .iter()
+        .filter(|entry| entry.section == 1)
+        .map(|entry| entry.offset.saturating_add(entry.size))
+        .max()?;
+
+    let output_len = usize::try_from(output_len)
+        .unwrap_or(MAX_CONTENT_SIZE)
+        .min(MAX_CONTENT_SIZE);
+
+    let get = |name| {
+        let entry = directory.find(name)?;
+        sections.get(data, entry.section, entry.offset, entry.size)
+    };
+
+    // The control data has its size in 32-bit words, the `LZXC` signature,
+    // the version, the reset interval and the window size.
+    let control_data = get(CONTROL_DATA)?;
+
+    if control_data.get(4..8)? != b"LZXC" {
+        return None;
+    }
+
+    let version = u32_at(control_data, 8)?;
+    let mut reset_interval = u32_at(control_data, 12)? as usize;
+    let mut window_size = u32_at(control_data, 16)? as usize;
+
+    // In version 2 the reset interval and the window size are expressed in
+    // units of 32KB.
+    if version == 2 {
+        reset_interval = reset_interval.checked_mul(0x8000)?;
+        window_size = window_size.checked_mul(0x8000)?;
+    }
+
+    if !window_size.is_power_of_two() {
+        return None;
+    }
+
+    lzx::decompress(
+        get(CONTENT)?,
+        window_size.trailing_zeros(),
+        reset_interval / lzx::FRAME_SIZE,
+        output_len,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    const CHUNK_SIZE: usize = 0x1000;
+
+    fn encint(mut value: u64) -> Vec<u8> {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        value >>= 7;
+        while value > 0 {
+            bytes.insert(0, (value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes
+    }
+
+    /// Compresses data with LZX, using a single uncompressed block. The
+    /// data must be shorter than 4KB.
+    fn lzx_uncompressed(data: &[u8]) -> Vec<u8> {
+        // The E8 preprocessing bit, the block type (3) and the block's
+        // size, followed by the padding up to the next 16-bit word.
+        let mut lzx = vec![0x00, 0x30];
+        lzx.extend(((data.len() as u16) << 4).to_le_bytes());
+        // The three most recent match offsets.
+        lzx.extend([1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        lzx.extend(data);
+        lzx
+    }
+
+    /// Builds a CHM file with the given files, each one with its name, the
+    /// section where it's stored, and its content.
+    fn chm(files: &[(&str, u64, &[u8])]) -> Vec<u8> {
+        let mut sections = [Vec::new(), Vec::new()];
+        let mut pmgl = b"PMGL".to_vec();
+        pmgl.extend([0; 16]);
+
+        for (name, section, content) in files {
+            let section_data = &mut sections[*section as usize];
+            pmgl.extend(encint(name.len() as u64));
+            pmgl.extend(name.as_bytes());
+            pmgl.extend(encint(*section));
+            pmgl.extend(encint(section_data.len() as u64));
+            pmgl.extend(encint(content.len() as u64));
+            section_data.extend(*content);
+        }
+
+        let free_space = (CHUNK_SIZE - pmgl.len()) as u32;
+        pmgl[4..8].copy_from_slice(&free_space.to_le_bytes());
+        pmgl.resize(CHUNK_SIZE, 0);
+
+        let mut itsp = b"ITSP".to_vec();
+        itsp.extend(1_u32.to_le_bytes());
+        itsp.extend(0x54_u32.to_le_bytes());
+        itsp.extend(0x0a_u32.to_le_bytes());
+        itsp.extend((CHUNK_SIZE as u32).to_le_bytes());
+        itsp.resize(0x2c, 0);
+        itsp.extend(1_u32.to_le_bytes());
+        itsp.resize(0x54, 0);
+        itsp.extend(pmgl);
+
+        let section0 = 0x60 + itsp.len();
+
+        let mut itsf = b"ITSF".to_vec();
+        itsf.extend(3_u32.to_le_bytes());
+        itsf.extend(0x60_u32.to_le_bytes());
+        itsf.extend(1_u32.to_le_bytes());
+        itsf.extend(0_u32.to_le_bytes());
+        itsf.extend(0x409_u32.to_le_bytes());
+        itsf.resize(0x48, 0);
+        itsf.extend(0x60_u64.to_le_bytes());
+        itsf.extend((itsp.len() as u64).to_le_bytes());
+        itsf.extend((section0 as u64).to_le_bytes());
+        itsf.extend(itsp);
+        itsf.extend(&sections[0]);
+        itsf
+    }
+
+    #[test]
+    fn end2end() {
+        let page = br#"<html><SCRIPT src="http://evil.com/a.js"></SCRIPT>"#;
+
+        let mut control_data = 6_u32.to_le_bytes().to_vec();
+        control_data.extend(b"LZXC");
+        for value in [2_u32, 1, 1, 1, 0] {
+            control_data.extend(value.to_le_bytes());
+        }
+
+        let content = lzx_uncompressed(page);
+
+        let data = chm(&[
+            ("/", 0, b""),
+            (
+                "/index.htm",
+                0,
+                b"<html><script language=\"VBScript\">MsgBox 1</script>",
+            ),
+            ("/page.htm", 1, page),
+            (super::CONTENT, 0, &content),
+            (super::CONTROL_DATA, 0, &control_data),
+        ]);
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "chm"
+                rule files {
+                  condition:
+                    chm.is_chm and
+                    chm.language == 0x409 and
+                    chm.is_decompressed and
+                    not chm.files_truncated and
+                    chm.files[1].name == "/index.htm" and
+                    chm.files[1].section == 0 and
+                    chm.files[2].name == "/page.htm" and
+                    chm.files[2].section == 1 and
+                    chm.files[2].size == 50
+                }
+                rule scripts {
+                  condition:
+                    chm.scripts[0].file == "/index.htm" and
+                    chm.scripts[0].language == "VBScript" and
+                    chm.scripts[0].offset == 34 and
+                    chm.scripts[0].size == 8 and
+                    chm.scripts[1].file == "/page.htm" and
+                    chm.scripts[1].src == "http://evil.com/a.js" and
+                    not defined chm.scripts[1].language
+                }
+                rule object {
+                  condition:
+                    chm.object("/index.htm") contains "MsgBox" and
+                    chm.object("/page.htm") contains "evil.com" and
+                    not defined chm.object("/missing.htm")
+                }
+                rule not_chm {
+                  condition:
+                    not chm.is_chm
+                }"#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            let results = scanner.scan(data).unwrap();
+            results
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&data), ["files", "scripts", "object"]);
+        assert_eq!(matching_rules(b"MZ"), ["not_chm"]);
+    }
+}
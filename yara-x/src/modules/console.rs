@@ -0,0 +1,314 @@
+use std::fmt::Write;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::console::*;
+use crate::scanner::ConsoleLevel;
+
+#[module_main]
+fn main(_ctx: &ScanContext) -> Console {
+    // Nothing to do, but we have to return our protobuf
+    Console::new()
+}
+
+// All the functions in this module return `true`, so that they can be
+// used in conditions like `console.log("foo") and $a`, without altering
+// the result of the condition.
+
+/// Logs a string.
+#[module_export(name = "log")]
+fn log_str(ctx: &mut ScanContext, s: RuntimeString) -> bool {
+    log(ctx, ConsoleLevel::Info, None, s)
+}
+
+/// Logs a message followed by a string.
+#[module_export(name = "log")]
+fn log_msg_str(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    s: RuntimeString,
+) -> bool {
+    log(ctx, ConsoleLevel::Info, Some(message), s)
+}
+
+/// Logs a message followed by an integer.
+#[module_export(name = "log")]
+fn log_msg_int(ctx: &mut ScanContext, message: RuntimeString, i: i64) -> bool {
+    log_value(ctx, ConsoleLevel::Info, message, i)
+}
+
+/// Logs a message followed by a float.
+#[module_export(name = "log")]
+fn log_msg_float(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    f: f64,
+) -> bool {
+    log_value(ctx, ConsoleLevel::Info, message, f)
+}
+
+/// Logs a string as a warning.
+#[module_export(name = "warn")]
+fn warn_str(ctx: &mut ScanContext, s: RuntimeString) -> bool {
+    log(ctx, ConsoleLevel::Warning, None, s)
+}
+
+/// Logs a message followed by a string as a warning.
+#[module_export(name = "warn")]
+fn warn_msg_str(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    s: RuntimeString,
+) -> bool {
+    log(ctx, ConsoleLevel::Warning, Some(message), s)
+}
+
+/// Logs a message followed by an integer as a warning.
+#[module_export(name = "warn")]
+fn warn_msg_int(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    i: i64,
+) -> bool {
+    log_value(ctx, ConsoleLevel::Warning, message, i)
+}
+
+/// Logs a message followed by a float as a warning.
+#[module_export(name = "warn")]
+fn warn_msg_float(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    f: f64,
+) -> bool {
+    log_value(ctx, ConsoleLevel::Warning, message, f)
+}
+
+/// Logs a string as an error.
+#[module_export(name = "error")]
+fn error_str(ctx: &mut ScanContext, s: RuntimeString) -> bool {
+    log(ctx, ConsoleLevel::Error, None, s)
+}
+
+/// Logs a message followed by a string as an error.
+#[module_export(name = "error")]
+fn error_msg_str(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    s: RuntimeString,
+) -> bool {
+    log(ctx, ConsoleLevel::Error, Some(message), s)
+}
+
+/// Logs a message followed by an integer as an error.
+#[module_export(name = "error")]
+fn error_msg_int(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    i: i64,
+) -> bool {
+    log_value(ctx, ConsoleLevel::Error, message, i)
+}
+
+/// Logs a message followed by a float as an error.
+#[module_export(name = "error")]
+fn error_msg_float(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    f: f64,
+) -> bool {
+    log_value(ctx, ConsoleLevel::Error, message, f)
+}
+
+/// Logs a hex dump of the `length` bytes of scanned data starting at
+/// `offset`. The dump is truncated if it exceeds the end of the data.
+#[module_export(name = "hex")]
+fn hex(ctx: &mut ScanContext, offset: i64, length: i64) -> bool {
+    if let Some(dump) = hex_dump(ctx.scanned_data(), offset, length) {
+        ctx.console_log(ConsoleLevel::Info, &dump);
+    }
+    true
+}
+
+/// Logs a message followed by a hex dump of the `length` bytes of scanned
+/// data starting at `offset`.
+#[module_export(name = "hex")]
+fn hex_msg(
+    ctx: &mut ScanContext,
+    message: RuntimeString,
+    offset: i64,
+    length: i64,
+) -> bool {
+    if let Some(dump) = hex_dump(ctx.scanned_data(), offset, length) {
+        let text = format!("{}\n{}", message.as_bstr(ctx), dump);
+        ctx.console_log(ConsoleLevel::Info, &text);
+    }
+    true
+}
+
+fn log(
+    ctx: &mut ScanContext,
+    level: ConsoleLevel,
+    message: Option<RuntimeString>,
+    s: RuntimeString,
+) -> bool {
+    let text = match message {
+        Some(message) => {
+            format!("{}{}", message.as_bstr(ctx), s.as_bstr(ctx))
+        }
+        None => s.as_bstr(ctx).to_string(),
+    };
+    ctx.console_log(level, &text);
+    true
+}
+
+fn log_value<T: std::fmt::Display>(
+    ctx: &mut ScanContext,
+    level: ConsoleLevel,
+    message: RuntimeString,
+    value: T,
+) -> bool {
+    let text = format!("{}{}", message.as_bstr(ctx), value);
+    ctx.console_log(level, &text);
+    true
+}
+
+/// Returns a hex dump of the `length` bytes of `data` starting at `offset`,
+/// with 16 bytes per line. Each line contains the offset of its first byte,
+/// the bytes in hex, and the bytes as ASCII characters.
+///
+/// Returns `None` if `offset` or `length` are negative, or `offset` is
+/// beyond the end of the data.
+fn hex_dump(data: &[u8], offset: i64, length: i64) -> Option<String> {
+    let start: usize = offset.try_into().ok()?;
+    let length: usize = length.try_into().ok()?;
+
+    if start >= data.len() {
+        return None;
+    }
+
+    let end = start.saturating_add(length).min(data.len());
+    let mut dump = String::new();
+
+    for (i, chunk) in data[start..end].chunks(16).enumerate() {
+        if i > 0 {
+            dump.push('\n');
+        }
+
+        write!(dump, "{:08x}  ", start + i * 16).unwrap();
+
+        for b in chunk {
+            write!(dump, "{:02x} ", b).unwrap();
+        }
+
+        // Align the ASCII column in the last line.
+        dump.push_str(&"   ".repeat(16 - chunk.len()));
+        dump.push(' ');
+
+        for b in chunk {
+            dump.push(if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            });
+        }
+    }
+
+    Some(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use pretty_assertions::assert_eq;
+
+    use super::hex_dump;
+    use crate::scanner::ConsoleLevel;
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "console"
+                rule rule_1 {
+                  condition:
+                    console.log("foo") and
+                    console.log("bar: ", "baz") and
+                    console.warn("int: ", 1) and
+                    console.error("float: ", 0.5)
+                }
+                rule rule_2 {
+                  condition:
+                    console.hex(0, 4) and
+                    console.hex("dump:", 2, 100) and
+                    console.hex(100, 1)
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let messages = RefCell::new(Vec::new());
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        scanner.console_log(|msg| {
+            messages.borrow_mut().push((
+                msg.level(),
+                msg.rule_name().unwrap().to_string(),
+                msg.text().to_string(),
+            ))
+        });
+
+        assert_eq!(scanner.scan(b"ABCD").unwrap().matching_rules().len(), 2);
+
+        drop(scanner);
+
+        assert_eq!(
+            messages.into_inner(),
+            vec![
+                (ConsoleLevel::Info, "rule_1".to_string(), "foo".to_string()),
+                (
+                    ConsoleLevel::Info,
+                    "rule_1".to_string(),
+                    "bar: baz".to_string()
+                ),
+                (
+                    ConsoleLevel::Warning,
+                    "rule_1".to_string(),
+                    "int: 1".to_string()
+                ),
+                (
+                    ConsoleLevel::Error,
+                    "rule_1".to_string(),
+                    "float: 0.5".to_string()
+                ),
+                (
+                    ConsoleLevel::Info,
+                    "rule_2".to_string(),
+                    format!("00000000  41 42 43 44 {} ABCD", "   ".repeat(12))
+                ),
+                (
+                    ConsoleLevel::Info,
+                    "rule_2".to_string(),
+                    format!("dump:\n00000002  43 44 {} CD", "   ".repeat(14))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hex_dumps() {
+        let data: Vec<u8> = (0..20).collect();
+
+        assert_eq!(
+            hex_dump(&data, 0, 20).unwrap(),
+            "00000000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f  ................\n\
+             00000010  10 11 12 13                                      ...."
+        );
+
+        assert_eq!(hex_dump(&data, 20, 1), None);
+        assert_eq!(hex_dump(&data, -1, 1), None);
+    }
+}
@@ -0,0 +1,321 @@
+use protobuf::MessageField;
+use serde_json::Value;
+
+use crate::compiler::RegexpId;
+use crate::modules::prelude::*;
+use crate::modules::protos::cuckoo::*;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Cuckoo {
+    // The report is provided by the user with `Scanner::set_module_data`.
+    // If no report was provided, or it is not valid JSON, all the fields
+    // in the module remain undefined.
+    match ctx
+        .module_data("cuckoo")
+        .and_then(|data| serde_json::from_slice::<Value>(data).ok())
+    {
+        Some(report) => parse_report(&report),
+        None => Cuckoo::new(),
+    }
+}
+
+/// Returns true if the sample resolved a domain name that matches the
+/// regular expression.
+#[module_export(name = "network.dns_lookup")]
+fn network_dns_lookup(ctx: &ScanContext, regexp_id: RegexpId) -> Option<bool> {
+    let network = ctx.module_output::<Cuckoo>()?.network.as_ref()?;
+    Some(any_match(ctx, regexp_id, network.domains.iter()))
+}
+
+/// Returns true if the sample contacted a host that matches the regular
+/// expression.
+#[module_export(name = "network.host")]
+fn network_host(ctx: &ScanContext, regexp_id: RegexpId) -> Option<bool> {
+    let network = ctx.module_output::<Cuckoo>()?.network.as_ref()?;
+    Some(any_match(ctx, regexp_id, network.hosts.iter()))
+}
+
+/// Returns true if the sample made an HTTP request, with any method, to
+/// a URI that matches the regular expression.
+#[module_export(name = "network.http_request")]
+fn network_http_request(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+) -> Option<bool> {
+    http_request(ctx, regexp_id, None)
+}
+
+/// Returns true if the sample made an HTTP GET request to a URI that
+/// matches the regular expression.
+#[module_export(name = "network.http_get")]
+fn network_http_get(ctx: &ScanContext, regexp_id: RegexpId) -> Option<bool> {
+    http_request(ctx, regexp_id, Some("GET"))
+}
+
+/// Returns true if the sample made an HTTP POST request to a URI that
+/// matches the regular expression.
+#[module_export(name = "network.http_post")]
+fn network_http_post(ctx: &ScanContext, regexp_id: RegexpId) -> Option<bool> {
+    http_request(ctx, regexp_id, Some("POST"))
+}
+
+/// Returns true if the sample made an HTTP request with a user agent that
+/// matches the regular expression.
+#[module_export(name = "network.http_user_agent")]
+fn network_http_user_agent(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+) -> Option<bool> {
+    let network = ctx.module_output::<Cuckoo>()?.network.as_ref()?;
+    Some(any_match(
+        ctx,
+        regexp_id,
+        network.http_requests.iter().map(|req| req.user_agent()),
+    ))
+}
+
+/// Returns true if the sample established a TCP connection with a host
+/// that matches the regular expression, at the given port.
+#[module_export(name = "network.tcp")]
+fn network_tcp(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+    port: i64,
+) -> Option<bool> {
+    let network = ctx.module_output::<Cuckoo>()?.network.as_ref()?;
+    Some(connection(ctx, regexp_id, port, &network.tcp_connections))
+}
+
+/// Returns true if the sample sent UDP traffic to a host that matches the
+/// regular expression, at the given port.
+#[module_export(name = "network.udp")]
+fn network_udp(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+    port: i64,
+) -> Option<bool> {
+    let network = ctx.module_output::<Cuckoo>()?.network.as_ref()?;
+    Some(connection(ctx, regexp_id, port, &network.udp_connections))
+}
+
+/// Returns true if the sample accessed a file whose path matches the
+/// regular expression.
+#[module_export(name = "filesystem.file_access")]
+fn filesystem_file_access(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+) -> Option<bool> {
+    let filesystem = ctx.module_output::<Cuckoo>()?.filesystem.as_ref()?;
+    Some(any_match(ctx, regexp_id, filesystem.files.iter()))
+}
+
+/// Returns true if the sample accessed a registry key that matches the
+/// regular expression.
+#[module_export(name = "registry.key_access")]
+fn registry_key_access(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+) -> Option<bool> {
+    let registry = ctx.module_output::<Cuckoo>()?.registry.as_ref()?;
+    Some(any_match(ctx, regexp_id, registry.keys.iter()))
+}
+
+/// Returns true if the sample created or opened a mutex whose name matches
+/// the regular expression.
+#[module_export(name = "sync.mutex")]
+fn sync_mutex(ctx: &ScanContext, regexp_id: RegexpId) -> Option<bool> {
+    let sync = ctx.module_output::<Cuckoo>()?.sync.as_ref()?;
+    Some(any_match(ctx, regexp_id, sync.mutexes.iter()))
+}
+
+/// Returns true if any of the strings matches the regular expression.
+fn any_match<'a, I, S>(ctx: &ScanContext, regexp_id: RegexpId, iter: I) -> bool
+where
+    I: IntoIterator<Item = &'a S>,
+    S: AsRef<str> + ?Sized + 'a,
+{
+    iter.into_iter()
+        .any(|s| ctx.regexp_matches(regexp_id, s.as_ref().as_bytes()))
+}
+
+/// Returns true if the sample made an HTTP request to a URI that matches
+/// the regular expression. If `method` is not `None`, only requests with
+/// that method are taken into account.
+fn http_request(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+    method: Option<&str>,
+) -> Option<bool> {
+    let network = ctx.module_output::<Cuckoo>()?.network.as_ref()?;
+    Some(any_match(
+        ctx,
+        regexp_id,
+        network
+            .http_requests
+            .iter()
+            .filter(|req| {
+                method.map_or(true, |m| req.method().eq_ignore_ascii_case(m))
+            })
+            .map(|req| req.uri()),
+    ))
+}
+
+/// Returns true if any of the connections has a destination address that
+/// matches the regular expression, and the given destination port.
+fn connection(
+    ctx: &ScanContext,
+    regexp_id: RegexpId,
+    port: i64,
+    connections: &[Connection],
+) -> bool {
+    connections.iter().any(|conn| {
+        conn.dport() == port
+            && ctx.regexp_matches(regexp_id, conn.dst().as_bytes())
+    })
+}
+
+/// Builds the module's output from a Cuckoo report.
+///
+/// Network activity is read from the `network` section of the report, and
+/// the accessed files, registry keys and mutexes are read from the
+/// `behavior.summary` section. Any missing or malformed entry is ignored.
+fn parse_report(report: &Value) -> Cuckoo {
+    let network = &report["network"];
+    let summary = &report["behavior"]["summary"];
+
+    let mut cuckoo = Cuckoo::new();
+
+    cuckoo.network = MessageField::some(Network {
+        // Older versions of Cuckoo report the hosts as strings, newer ones
+        // report them as objects where the address is in the `ip` field.
+        hosts: items(network, "hosts")
+            .filter_map(|host| host.as_str().or_else(|| host["ip"].as_str()))
+            .map(String::from)
+            .collect(),
+        domains: items(network, "domains")
+            .filter_map(|domain| domain["domain"].as_str())
+            .map(String::from)
+            .collect(),
+        http_requests: items(network, "http")
+            .map(|req| HttpRequest {
+                method: req["method"].as_str().map(String::from),
+                uri: req["uri"].as_str().map(String::from),
+                user_agent: req["user-agent"].as_str().map(String::from),
+                ..Default::default()
+            })
+            .collect(),
+        tcp_connections: connections(network, "tcp"),
+        udp_connections: connections(network, "udp"),
+        ..Default::default()
+    });
+
+    cuckoo.filesystem = MessageField::some(Filesystem {
+        files: strings(summary, "files"),
+        ..Default::default()
+    });
+
+    cuckoo.registry = MessageField::some(Registry {
+        keys: strings(summary, "keys"),
+        ..Default::default()
+    });
+
+    cuckoo.sync = MessageField::some(Sync {
+        mutexes: strings(summary, "mutexes"),
+        ..Default::default()
+    });
+
+    cuckoo
+}
+
+/// Returns an iterator over the items in the array `value[key]`. If the
+/// array doesn't exist, the iterator is empty.
+fn items<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value[key].as_array().into_iter().flatten()
+}
+
+/// Returns the strings in the array `value[key]`.
+fn strings(value: &Value, key: &str) -> Vec<String> {
+    items(value, key).filter_map(Value::as_str).map(String::from).collect()
+}
+
+/// Returns the connections in the array `value[key]`.
+fn connections(value: &Value, key: &str) -> Vec<Connection> {
+    items(value, key)
+        .map(|conn| Connection {
+            dst: conn["dst"].as_str().map(String::from),
+            dport: conn["dport"].as_i64(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    const REPORT: &[u8] = br#"{
+      "network": {
+        "hosts": ["192.168.1.1", {"ip": "10.0.0.1"}],
+        "domains": [{"domain": "evil.example.com", "ip": "10.0.0.1"}],
+        "http": [
+          {
+            "method": "GET",
+            "uri": "http://evil.example.com/payload.bin",
+            "user-agent": "Mozilla/5.0 (evil)"
+          },
+          {
+            "method": "POST",
+            "uri": "http://evil.example.com/gate.php",
+            "user-agent": "curl/7.0"
+          }
+        ],
+        "tcp": [{"dst": "10.0.0.1", "dport": 443}],
+        "udp": [{"dst": "8.8.8.8", "dport": 53}]
+      },
+      "behavior": {
+        "summary": {
+          "files": ["C:\\Windows\\Temp\\dropped.exe"],
+          "keys": ["HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows\\CurrentVersion\\Run"],
+          "mutexes": ["Global\\EvilMutex"]
+        }
+      }
+    }"#;
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "cuckoo"
+                rule rule_1 { condition: cuckoo.network.dns_lookup(/evil\.example\.com/) }
+                rule rule_2 { condition: cuckoo.network.host(/^10\.0\.0\.1$/) }
+                rule rule_3 { condition: cuckoo.network.host(/^192\.168\./) }
+                rule rule_4 { condition: cuckoo.network.http_request(/payload\.bin/) }
+                rule rule_5 { condition: cuckoo.network.http_get(/payload\.bin/) }
+                rule rule_6 { condition: cuckoo.network.http_post(/gate\.php/) }
+                rule rule_7 { condition: not cuckoo.network.http_post(/payload\.bin/) }
+                rule rule_8 { condition: cuckoo.network.http_user_agent(/evil/) }
+                rule rule_9 { condition: cuckoo.network.tcp(/10\.0\.0\.1/, 443) }
+                rule rule_10 { condition: not cuckoo.network.tcp(/10\.0\.0\.1/, 80) }
+                rule rule_11 { condition: cuckoo.network.udp(/8\.8\.8\.8/, 53) }
+                rule rule_12 { condition: cuckoo.filesystem.file_access(/dropped\.exe$/) }
+                rule rule_13 { condition: cuckoo.registry.key_access(/\\Run$/) }
+                rule rule_14 { condition: cuckoo.sync.mutex(/EvilMutex/) }
+                rule rule_15 { condition: cuckoo.network.domains[0] == "evil.example.com" }
+                rule rule_16 { condition: not cuckoo.sync.mutex(/GoodMutex/) }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        // Without a report all the functions return undefined.
+        assert_eq!(scanner.scan(&[]).unwrap().matching_rules().len(), 0);
+
+        scanner.set_module_data("cuckoo", REPORT);
+
+        assert_eq!(scanner.scan(&[]).unwrap().matching_rules().len(), 16);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::modules::prelude::*;
+use crate::modules::protos::dex::*;
+
+mod parser;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Dex {
+    parser::parse(ctx.scanned_data()).unwrap_or_else(|| {
+        let mut dex = Dex::new();
+        dex.set_is_dex(false);
+        dex
+    })
+}
+
+/// Returns true if the DEX file defines a class with the given name, which
+/// must be a type descriptor like `Lcom/example/Main;`.
+#[module_export]
+fn has_class(ctx: &ScanContext, class_name: RuntimeString) -> Option<bool> {
+    let dex = ctx.module_output::<Dex>()?;
+    let class_name = class_name.as_bstr(ctx);
+
+    Some(
+        dex.classes
+            .iter()
+            .any(|class| class.class_name().as_bytes() == class_name),
+    )
+}
+
+/// Returns true if the DEX file references a method with the given name,
+/// in any class.
+#[module_export(name = "has_method")]
+fn has_method(ctx: &ScanContext, method_name: RuntimeString) -> Option<bool> {
+    let dex = ctx.module_output::<Dex>()?;
+    let method_name = method_name.as_bstr(ctx);
+
+    Some(
+        dex.methods
+            .iter()
+            .any(|method| method.name().as_bytes() == method_name),
+    )
+}
+
+/// Returns true if the DEX file references a method with the given name in
+/// the given class, which must be a type descriptor like
+/// `Lcom/example/Main;`.
+#[module_export(name = "has_method")]
+fn has_class_method(
+    ctx: &ScanContext,
+    class_name: RuntimeString,
+    method_name: RuntimeString,
+) -> Option<bool> {
+    let dex = ctx.module_output::<Dex>()?;
+    let class_name = class_name.as_bstr(ctx);
+    let method_name = method_name.as_bstr(ctx);
+
+    Some(dex.methods.iter().any(|method| {
+        method.class_name().as_bytes() == class_name
+            && method.name().as_bytes() == method_name
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    /// Builds a minimal DEX file that defines a class `Foo`, with a single
+    /// method `public static void main()`.
+    fn build_dex() -> Vec<u8> {
+        let strings =
+            ["<init>", "Foo.java", "LFoo;", "Ljava/lang/Object;", "V", "main"];
+
+        let mut dex = vec![0_u8; 0x70];
+
+        let string_ids_offset = dex.len();
+        dex.resize(dex.len() + strings.len() * 4, 0);
+
+        // Types: LFoo;, Ljava/lang/Object; and V.
+        let type_ids_offset = dex.len();
+        for string_idx in [2_u32, 3, 4] {
+            dex.extend(string_idx.to_le_bytes());
+        }
+
+        // Prototype `()V`: shorty, return type, no parameters.
+        let proto_ids_offset = dex.len();
+        dex.extend(4_u32.to_le_bytes());
+        dex.extend(2_u32.to_le_bytes());
+        dex.extend(0_u32.to_le_bytes());
+
+        // Method `LFoo;->main()V`: class, prototype, name.
+        let method_ids_offset = dex.len();
+        dex.extend(0_u16.to_le_bytes());
+        dex.extend(0_u16.to_le_bytes());
+        dex.extend(5_u32.to_le_bytes());
+
+        // Class `LFoo;`: class, access flags, superclass, interfaces,
+        // source file, annotations, class data and static values.
+        let class_defs_offset = dex.len();
+        for value in [0_u32, 1, 1, 0, 1, 0, 0, 0] {
+            dex.extend(value.to_le_bytes());
+        }
+
+        for (i, s) in strings.iter().enumerate() {
+            let offset = dex.len() as u32;
+            dex[string_ids_offset + i * 4..string_ids_offset + i * 4 + 4]
+                .copy_from_slice(&offset.to_le_bytes());
+            dex.push(s.len() as u8);
+            dex.extend(s.as_bytes());
+            dex.push(0);
+        }
+
+        // Code item with 2 instructions of 16 bits.
+        let code_offset = dex.len();
+        dex.extend([0_u8; 12]);
+        dex.extend(2_u32.to_le_bytes());
+        dex.extend([0_u8; 4]);
+
+        // Class data with one direct method, its code offset is encoded
+        // as a 2-bytes LEB128.
+        let class_data_offset = dex.len() as u32;
+        assert!((128..16384).contains(&code_offset));
+        dex.extend([0, 0, 1, 0]);
+        dex.extend([0, 9]);
+        dex.extend([
+            (code_offset & 0x7f) as u8 | 0x80,
+            (code_offset >> 7) as u8,
+        ]);
+        dex[class_defs_offset + 24..class_defs_offset + 28]
+            .copy_from_slice(&class_data_offset.to_le_bytes());
+
+        // Map list with a single item for the header.
+        let map_offset = dex.len();
+        dex.extend(1_u32.to_le_bytes());
+        dex.extend(0_u16.to_le_bytes());
+        dex.extend(0_u16.to_le_bytes());
+        dex.extend(1_u32.to_le_bytes());
+        dex.extend(0_u32.to_le_bytes());
+
+        let file_size = dex.len();
+
+        dex[..8].copy_from_slice(b"dex\n035\x00");
+
+        for (offset, value) in [
+            (32, file_size),
+            (36, 0x70),
+            (40, 0x12345678),
+            (52, map_offset),
+            (56, strings.len()),
+            (60, string_ids_offset),
+            (64, 3),
+            (68, type_ids_offset),
+            (72, 1),
+            (76, proto_ids_offset),
+            (88, 1),
+            (92, method_ids_offset),
+            (96, 1),
+            (100, class_defs_offset),
+        ] {
+            dex[offset..offset + 4]
+                .copy_from_slice(&(value as u32).to_le_bytes());
+        }
+
+        dex
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "dex"
+                rule rule_1 { condition: dex.is_dex and dex.header.version == 35 }
+                rule rule_2 { condition: dex.header.endian_tag == 0x12345678 }
+                rule rule_3 { condition: dex.string_table[5] == "main" and dex.types[1] == "Ljava/lang/Object;" }
+                rule rule_4 {
+                  condition:
+                    dex.classes[0].class_name == "LFoo;" and
+                    dex.classes[0].superclass == "Ljava/lang/Object;" and
+                    dex.classes[0].source_file == "Foo.java" and
+                    dex.classes[0].access_flags == 1
+                }
+                rule rule_5 {
+                  condition:
+                    dex.methods[0].name == "main" and
+                    dex.methods[0].class_name == "LFoo;" and
+                    dex.methods[0].prototype.descriptor == "()V" and
+                    dex.methods[0].prototype.shorty == "V" and
+                    dex.methods[0].access_flags == 9 and
+                    not dex.methods[0].is_virtual and
+                    dex.methods[0].code_size == 4
+                }
+                rule rule_6 {
+                  condition:
+                    dex.map_items[0].type == dex.MapType.TYPE_HEADER_ITEM and
+                    dex.map_items[0].size == 1
+                }
+                rule rule_7 { condition: dex.has_class("LFoo;") and not dex.has_class("LBar;") }
+                rule rule_8 { condition: dex.has_method("main") and not dex.has_method("foo") }
+                rule rule_9 { condition: dex.has_method("LFoo;", "main") and not dex.has_method("LBar;", "main") }
+                rule rule_10 { condition: not dex.is_dex }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_dex()),
+            [
+                "rule_1", "rule_2", "rule_3", "rule_4", "rule_5", "rule_6",
+                "rule_7", "rule_8", "rule_9"
+            ]
+        );
+
+        assert_eq!(matching_rules(b"dex\n035\x00 truncated"), ["rule_10"]);
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt::Write;
+
+use protobuf::{EnumOrUnknown, MessageField};
+
+use crate::modules::protos::dex::*;
+
+/// Size of the DEX header.
+const HEADER_SIZE: usize = 0x70;
+
+/// Value used in indexes that don't point to any item.
+const NO_INDEX: u32 = 0xffffffff;
+
+/// Parses a DEX file. Returns `None` if the data is not a DEX file.
+///
+/// The parser is lenient, items that can't be parsed because they are out
+/// of the file's bounds are skipped, and references to strings or types
+/// that don't exist are left undefined.
+pub fn parse(data: &[u8]) -> Option<Dex> {
+    if !data.starts_with(b"dex\n") || data.len() < HEADER_SIZE {
+        return None;
+    }
+
+    let header = parse_header(data)?;
+
+    let strings: Vec<String> =
+        items(data, header.string_ids_offset(), header.string_ids_size(), 4)
+            .map(|item| string_data(data, u32_at(item, 0)).unwrap_or_default())
+            .collect();
+
+    let types: Vec<String> =
+        items(data, header.type_ids_offset(), header.type_ids_size(), 4)
+            .map(|item| lookup(&strings, u32_at(item, 0)).unwrap_or_default())
+            .collect();
+
+    let prototypes: Vec<Prototype> =
+        items(data, header.proto_ids_offset(), header.proto_ids_size(), 12)
+            .map(|item| {
+                let parameters: Vec<String> = type_list(data, u32_at(item, 8))
+                    .filter_map(|idx| lookup(&types, idx))
+                    .collect();
+
+                let return_type = lookup(&types, u32_at(item, 4));
+
+                Prototype {
+                    shorty: lookup(&strings, u32_at(item, 0)),
+                    descriptor: Some(format!(
+                        "({}){}",
+                        parameters.concat(),
+                        return_type.as_deref().unwrap_or_default()
+                    )),
+                    return_type,
+                    parameters,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+    let mut fields: Vec<Field> =
+        items(data, header.field_ids_offset(), header.field_ids_size(), 8)
+            .map(|item| Field {
+                class_name: lookup(&types, u16_at(item, 0)),
+                type_: lookup(&types, u16_at(item, 2)),
+                name: lookup(&strings, u32_at(item, 4)),
+                ..Default::default()
+            })
+            .collect();
+
+    let mut methods: Vec<Method> =
+        items(data, header.method_ids_offset(), header.method_ids_size(), 8)
+            .map(|item| Method {
+                class_name: lookup(&types, u16_at(item, 0)),
+                prototype: MessageField::from_option(
+                    prototypes.get(u16_at(item, 2) as usize).cloned(),
+                ),
+                name: lookup(&strings, u32_at(item, 4)),
+                ..Default::default()
+            })
+            .collect();
+
+    let classes: Vec<Class> =
+        items(data, header.class_defs_offset(), header.class_defs_size(), 32)
+            .map(|item| {
+                let class_data_offset = u32_at(item, 24);
+
+                parse_class_data(
+                    data,
+                    class_data_offset,
+                    &mut fields,
+                    &mut methods,
+                );
+
+                Class {
+                    class_name: lookup(&types, u32_at(item, 0)),
+                    access_flags: Some(u32_at(item, 4)),
+                    superclass: lookup(&types, u32_at(item, 8)),
+                    interfaces: type_list(data, u32_at(item, 12))
+                        .filter_map(|idx| lookup(&types, idx))
+                        .collect(),
+                    source_file: lookup(&strings, u32_at(item, 16)),
+                    class_data_offset: Some(class_data_offset),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+    let map_items = read_u32(data, header.map_offset())
+        .map(|size| {
+            items(data, header.map_offset().saturating_add(4), size, 12)
+                .map(|item| MapItem {
+                    type_: Some(EnumOrUnknown::from_i32(
+                        u16_at(item, 0) as i32
+                    )),
+                    size: Some(u32_at(item, 4)),
+                    offset: Some(u32_at(item, 8)),
+                    ..Default::default()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut dex = Dex::new();
+
+    dex.set_is_dex(true);
+    dex.header = MessageField::some(header);
+    dex.string_table = strings;
+    dex.types = types;
+    dex.prototypes = prototypes;
+    dex.fields = fields;
+    dex.methods = methods;
+    dex.classes = classes;
+    dex.map_items = map_items;
+
+    Some(dex)
+}
+
+fn parse_header(data: &[u8]) -> Option<Header> {
+    let header = data.get(..HEADER_SIZE)?;
+    let magic = &header[..8];
+
+    let mut signature = String::with_capacity(40);
+    for b in &header[12..32] {
+        write!(signature, "{:02x}", b).unwrap();
+    }
+
+    Some(Header {
+        magic: Some(magic.to_vec()),
+        // The version is a 3-digits decimal number (e.g: "035") that
+        // follows the "dex\n" prefix.
+        version: std::str::from_utf8(&magic[4..7])
+            .ok()
+            .and_then(|version| version.parse().ok()),
+        checksum: Some(u32_at(header, 8)),
+        signature: Some(signature),
+        file_size: Some(u32_at(header, 32)),
+        header_size: Some(u32_at(header, 36)),
+        endian_tag: Some(u32_at(header, 40)),
+        link_size: Some(u32_at(header, 44)),
+        link_offset: Some(u32_at(header, 48)),
+        map_offset: Some(u32_at(header, 52)),
+        string_ids_size: Some(u32_at(header, 56)),
+        string_ids_offset: Some(u32_at(header, 60)),
+        type_ids_size: Some(u32_at(header, 64)),
+        type_ids_offset: Some(u32_at(header, 68)),
+        proto_ids_size: Some(u32_at(header, 72)),
+        proto_ids_offset: Some(u32_at(header, 76)),
+        field_ids_size: Some(u32_at(header, 80)),
+        field_ids_offset: Some(u32_at(header, 84)),
+        method_ids_size: Some(u32_at(header, 88)),
+        method_ids_offset: Some(u32_at(header, 92)),
+        class_defs_size: Some(u32_at(header, 96)),
+        class_defs_offset: Some(u32_at(header, 100)),
+        data_size: Some(u32_at(header, 104)),
+        data_offset: Some(u32_at(header, 108)),
+        ..Default::default()
+    })
+}
+
+/// Parses a `class_data_item`, which describes the fields and methods
+/// declared by a class, and updates the corresponding items in `fields`
+/// and `methods`.
+fn parse_class_data(
+    data: &[u8],
+    offset: u32,
+    fields: &mut [Field],
+    methods: &mut [Method],
+) -> Option<()> {
+    if offset == 0 {
+        return None;
+    }
+
+    let mut pos = offset as usize;
+
+    let static_fields_size = read_uleb128(data, &mut pos)?;
+    let instance_fields_size = read_uleb128(data, &mut pos)?;
+    let direct_methods_size = read_uleb128(data, &mut pos)?;
+    let virtual_methods_size = read_uleb128(data, &mut pos)?;
+
+    for (size, is_static) in
+        [(static_fields_size, true), (instance_fields_size, false)]
+    {
+        // Field indexes are encoded as the difference with the previous
+        // index in the same list.
+        let mut idx = 0_u32;
+        for _ in 0..size {
+            idx = idx.wrapping_add(read_uleb128(data, &mut pos)?);
+            let access_flags = read_uleb128(data, &mut pos)?;
+            if let Some(field) = fields.get_mut(idx as usize) {
+                field.set_access_flags(access_flags);
+                field.set_is_static(is_static);
+            }
+        }
+    }
+
+    for (size, is_virtual) in
+        [(direct_methods_size, false), (virtual_methods_size, true)]
+    {
+        let mut idx = 0_u32;
+        for _ in 0..size {
+            idx = idx.wrapping_add(read_uleb128(data, &mut pos)?);
+            let access_flags = read_uleb128(data, &mut pos)?;
+            let code_offset = read_uleb128(data, &mut pos)?;
+            if let Some(method) = methods.get_mut(idx as usize) {
+                method.set_access_flags(access_flags);
+                method.set_is_virtual(is_virtual);
+                method.set_code_offset(code_offset);
+                // The size of the bytecode is stored in the code item as
+                // a number of 16-bits units.
+                if let Some(insns_size) =
+                    read_u32(data, code_offset.saturating_add(12))
+                        .filter(|_| code_offset != 0)
+                {
+                    method.set_code_size(insns_size.saturating_mul(2));
+                }
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// Returns an iterator over the `count` items of `item_size` bytes that
+/// start at `offset`. The iterator stops at the first item that exceeds
+/// the end of the data.
+fn items(
+    data: &[u8],
+    offset: u32,
+    count: u32,
+    item_size: usize,
+) -> impl Iterator<Item = &[u8]> {
+    let offset = offset as usize;
+    (0..count as usize).map_while(move |i| {
+        let start = offset.checked_add(i.checked_mul(item_size)?)?;
+        data.get(start..start.checked_add(item_size)?)
+    })
+}
+
+/// Returns an iterator over the type indexes in the `type_list` at the
+/// given offset. If the offset is 0 the list is empty.
+fn type_list(data: &[u8], offset: u32) -> impl Iterator<Item = u32> + '_ {
+    let size =
+        if offset == 0 { 0 } else { read_u32(data, offset).unwrap_or(0) };
+    items(data, offset.saturating_add(4), size, 2).map(|item| u16_at(item, 0))
+}
+
+/// Returns the string in the `string_data_item` at the given offset.
+///
+/// Strings are encoded in MUTF-8, which for most practical purposes is
+/// equivalent to UTF-8. Any invalid sequence is replaced with the Unicode
+/// replacement character.
+fn string_data(data: &[u8], offset: u32) -> Option<String> {
+    let mut pos = offset as usize;
+    // The string starts with its length in UTF-16 code units, which is not
+    // needed because the string is null-terminated.
+    read_uleb128(data, &mut pos)?;
+    let s = data.get(pos..)?;
+    let s = &s[..s.iter().position(|b| *b == 0).unwrap_or(s.len())];
+    Some(String::from_utf8_lossy(s).into_owned())
+}
+
+/// Returns the item at position `idx` in `table`, or `None` if `idx` is
+/// out of bounds or is [`NO_INDEX`].
+fn lookup(table: &[String], idx: u32) -> Option<String> {
+    if idx == NO_INDEX {
+        return None;
+    }
+    table.get(idx as usize).cloned()
+}
+
+/// Reads an unsigned LEB128 value at `pos`, and advances `pos` to the byte
+/// that follows it.
+fn read_uleb128(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut result = 0_u32;
+    // A 32-bits value is encoded in 5 bytes at most.
+    for i in 0..5 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u32) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+fn read_u32(data: &[u8], offset: u32) -> Option<u32> {
+    let offset = offset as usize;
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Reads a little-endian `u16` from an item. The offset must be within
+/// the item's bounds.
+fn u16_at(item: &[u8], offset: usize) -> u32 {
+    u16::from_le_bytes([item[offset], item[offset + 1]]) as u32
+}
+
+/// Reads a little-endian `u32` from an item. The offset must be within
+/// the item's bounds.
+fn u32_at(item: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(item[offset..offset + 4].try_into().unwrap())
+}
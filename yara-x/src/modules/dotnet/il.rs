@@ -0,0 +1,230 @@
+/*! Parser for method bodies in CIL (Common Intermediate Language).
+
+See: ECMA-335, Partition II, section 25.4, and Partition III.
+*/
+
+/// Kinds of operands of CIL instructions.
+#[derive(Clone, Copy)]
+enum Operand {
+    /// The instruction has no operand.
+    None,
+    /// An immediate value or a branch target, with the given size.
+    Immediate(usize),
+    /// A metadata token, which is 4 bytes long.
+    Token,
+    /// The jump table of the `switch` instruction.
+    Switch,
+    /// The opcode is not valid.
+    Invalid,
+}
+
+/// Returns the code of a method, given the data starting at the method's
+/// body. The body starts with a tiny header (1 byte) or a fat header (12
+/// bytes, or more), followed by the code.
+pub(crate) fn method_code(body: &[u8]) -> Option<&[u8]> {
+    let first = *body.first()?;
+    match first & 0x3 {
+        // Tiny header. The 6 upper bits are the code size.
+        0x2 => body.get(1..1 + (first >> 2) as usize),
+        // Fat header. The 4 upper bits of the flags are the size of the
+        // header in 4-byte units, and the code size follows the maximum
+        // stack size.
+        0x3 => {
+            let flags = u16::from_le_bytes(body.get(0..2)?.try_into().ok()?);
+            let header_size = ((flags >> 12) as usize) * 4;
+            let code_size =
+                u32::from_le_bytes(body.get(4..8)?.try_into().ok()?) as usize;
+            if header_size < 12 {
+                return None;
+            }
+            body.get(header_size..header_size.checked_add(code_size)?)
+        }
+        _ => None,
+    }
+}
+
+/// Returns a copy of the given code where the metadata tokens used as
+/// operands are replaced with zeroes.
+///
+/// Tokens are indexes in the metadata tables and heaps, which change
+/// every time an assembly is compiled, even if the method's code doesn't
+/// change. Opcodes, immediate values and branch targets are kept as they
+/// are. If an invalid opcode is found, the rest of the code is copied
+/// without changes.
+pub(crate) fn normalize(code: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(code.len());
+    let mut pos = 0;
+
+    while pos < code.len() {
+        let (opcode_size, operand) = match code[pos] {
+            0xfe => match code.get(pos + 1) {
+                Some(opcode) => (2, prefixed_operand(*opcode)),
+                None => (1, Operand::Invalid),
+            },
+            opcode => (1, operand(opcode)),
+        };
+
+        let operand_size = match operand {
+            Operand::None => 0,
+            Operand::Immediate(size) => size,
+            Operand::Token => 4,
+            Operand::Switch => code
+                .get(pos + 1..pos + 5)
+                .and_then(|n| n.try_into().ok())
+                .map(u32::from_le_bytes)
+                .and_then(|n| (n as usize).checked_mul(4))
+                .and_then(|n| n.checked_add(4))
+                .unwrap_or(usize::MAX),
+            Operand::Invalid => usize::MAX,
+        };
+
+        let end = pos
+            .saturating_add(opcode_size)
+            .saturating_add(operand_size)
+            .min(code.len());
+
+        match operand {
+            Operand::Token => {
+                normalized.extend(&code[pos..pos + opcode_size]);
+                normalized
+                    .resize(normalized.len() + end - pos - opcode_size, 0);
+            }
+            _ => normalized.extend(&code[pos..end]),
+        }
+
+        pos = end;
+    }
+
+    normalized
+}
+
+/// Returns the kind of operand of one-byte opcodes.
+fn operand(opcode: u8) -> Operand {
+    match opcode {
+        // ldarg.s, ldarga.s, starg.s, ldloc.s, ldloca.s, stloc.s
+        0x0e..=0x13 => Operand::Immediate(1),
+        // ldc.i4.s
+        0x1f => Operand::Immediate(1),
+        // ldc.i4, ldc.r4
+        0x20 | 0x22 => Operand::Immediate(4),
+        // ldc.i8, ldc.r8
+        0x21 | 0x23 => Operand::Immediate(8),
+        // jmp, call, calli
+        0x27..=0x29 => Operand::Token,
+        // Short branches (br.s ... blt.un.s)
+        0x2b..=0x37 => Operand::Immediate(1),
+        // Long branches (br ... blt.un)
+        0x38..=0x44 => Operand::Immediate(4),
+        0x45 => Operand::Switch,
+        // callvirt, cpobj, ldobj, ldstr, newobj, castclass, isinst
+        0x6f..=0x75 => Operand::Token,
+        // unbox
+        0x79 => Operand::Token,
+        // ldfld, ldflda, stfld, ldsfld, ldsflda, stsfld, stobj
+        0x7b..=0x81 => Operand::Token,
+        // box, newarr
+        0x8c | 0x8d => Operand::Token,
+        // ldelema
+        0x8f => Operand::Token,
+        // ldelem, stelem, unbox.any
+        0xa3..=0xa5 => Operand::Token,
+        // refanyval, mkrefany, ldtoken
+        0xc2 | 0xc6 | 0xd0 => Operand::Token,
+        // leave
+        0xdd => Operand::Immediate(4),
+        // leave.s
+        0xde => Operand::Immediate(1),
+        0x00..=0x0d
+        | 0x14..=0x1e
+        | 0x25
+        | 0x26
+        | 0x2a
+        | 0x46..=0x6e
+        | 0x76
+        | 0x7a
+        | 0x82..=0x8b
+        | 0x8e
+        | 0x90..=0xa2
+        | 0xb3..=0xba
+        | 0xc3
+        | 0xd1..=0xdc
+        | 0xdf
+        | 0xe0 => Operand::None,
+        _ => Operand::Invalid,
+    }
+}
+
+/// Returns the kind of operand of two-byte opcodes, which start with 0xfe.
+fn prefixed_operand(opcode: u8) -> Operand {
+    match opcode {
+        // ldftn, ldvirtftn
+        0x06 | 0x07 => Operand::Token,
+        // ldarg, ldarga, starg, ldloc, ldloca, stloc
+        0x09..=0x0e => Operand::Immediate(2),
+        // unaligned., no.
+        0x12 | 0x19 => Operand::Immediate(1),
+        // initobj, constrained., sizeof
+        0x15 | 0x16 | 0x1c => Operand::Token,
+        0x00..=0x05
+        | 0x0f
+        | 0x11
+        | 0x13
+        | 0x14
+        | 0x17
+        | 0x18
+        | 0x1a
+        | 0x1d
+        | 0x1e => Operand::None,
+        _ => Operand::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{method_code, normalize};
+
+    #[test]
+    fn method_code_headers() {
+        // Tiny header with 3 bytes of code.
+        assert_eq!(
+            method_code(b"\x0e\x02\x2a\x00\xff"),
+            Some(b"\x02\x2a\x00".as_slice())
+        );
+
+        // Fat header with 2 bytes of code.
+        let mut fat = vec![0x13, 0x30, 0x08, 0x00];
+        fat.extend(2_u32.to_le_bytes());
+        fat.extend(0_u32.to_le_bytes());
+        fat.extend(b"\x00\x2a\xff");
+        assert_eq!(method_code(&fat), Some(b"\x00\x2a".as_slice()));
+
+        // Code that exceeds the data.
+        fat[4] = 0x10;
+        assert_eq!(method_code(&fat), None);
+        assert_eq!(method_code(b"\x00"), None);
+        assert_eq!(method_code(b""), None);
+    }
+
+    #[test]
+    fn normalize_tokens() {
+        // ldstr <token>; call <token>; ldc.i4.s 5; br.s -2; ret
+        let code =
+            b"\x72\x01\x00\x00\x70\x28\x0a\x00\x00\x0a\x1f\x05\x2b\xfe\x2a";
+        assert_eq!(
+            normalize(code),
+            b"\x72\x00\x00\x00\x00\x28\x00\x00\x00\x00\x1f\x05\x2b\xfe\x2a"
+        );
+
+        // switch with two targets; ldftn <token>
+        let code = b"\x45\x02\x00\x00\x00\x01\x00\x00\x00\x02\x00\x00\x00\xfe\x06\x01\x00\x00\x06";
+        assert_eq!(
+            normalize(code),
+            b"\x45\x02\x00\x00\x00\x01\x00\x00\x00\x02\x00\x00\x00\xfe\x06\x00\x00\x00\x00"
+        );
+
+        // Tokens truncated by the end of the code, and invalid opcodes,
+        // which stop the normalization.
+        assert_eq!(normalize(b"\x28\x01\x02"), b"\x28\x00\x00");
+        assert_eq!(normalize(b"\x00\x24\x28\x01"), b"\x00\x24\x28\x01");
+    }
+}
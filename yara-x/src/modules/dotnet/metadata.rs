@@ -0,0 +1,529 @@
+/*! Reader for the metadata tables and heaps of .NET assemblies.
+
+The tables are stored in the `#~` stream (or `#-`, for unoptimized
+metadata) as consecutive arrays of fixed-size rows. The size of each
+column depends on the size of the heaps and on the number of rows in the
+tables it refers to, so every table must be known for locating the ones
+that come after it, even if they are not used by the module.
+
+See: ECMA-335, Partition II, sections 22 and 24.
+*/
+
+use crate::modules::utils::pe::MetadataRoot;
+
+/// Identifiers of the tables.
+pub(crate) const MODULE: usize = 0x00;
+pub(crate) const TYPE_REF: usize = 0x01;
+pub(crate) const TYPE_DEF: usize = 0x02;
+pub(crate) const FIELD: usize = 0x04;
+pub(crate) const METHOD_DEF: usize = 0x06;
+pub(crate) const PARAM: usize = 0x08;
+pub(crate) const INTERFACE_IMPL: usize = 0x09;
+pub(crate) const MEMBER_REF: usize = 0x0a;
+pub(crate) const CONSTANT: usize = 0x0b;
+pub(crate) const CUSTOM_ATTRIBUTE: usize = 0x0c;
+pub(crate) const DECL_SECURITY: usize = 0x0e;
+pub(crate) const FIELD_LAYOUT: usize = 0x10;
+pub(crate) const STAND_ALONE_SIG: usize = 0x11;
+pub(crate) const EVENT: usize = 0x14;
+pub(crate) const PROPERTY: usize = 0x17;
+pub(crate) const MODULE_REF: usize = 0x1a;
+pub(crate) const TYPE_SPEC: usize = 0x1b;
+pub(crate) const IMPL_MAP: usize = 0x1c;
+pub(crate) const ASSEMBLY: usize = 0x20;
+pub(crate) const ASSEMBLY_REF: usize = 0x23;
+pub(crate) const FILE: usize = 0x26;
+pub(crate) const EXPORTED_TYPE: usize = 0x27;
+pub(crate) const MANIFEST_RESOURCE: usize = 0x28;
+pub(crate) const NESTED_CLASS: usize = 0x29;
+pub(crate) const GENERIC_PARAM: usize = 0x2a;
+pub(crate) const METHOD_SPEC: usize = 0x2b;
+pub(crate) const GENERIC_PARAM_CONSTRAINT: usize = 0x2c;
+
+/// Number of tables defined by the format.
+const NUM_TABLES: usize = 0x2d;
+
+/// Kinds of coded indexes, which refer to a row in one of multiple
+/// tables. The lower bits of the index identify the table, and the
+/// remaining bits the row.
+#[derive(Clone, Copy)]
+pub(crate) enum Coded {
+    TypeDefOrRef,
+    HasConstant,
+    HasCustomAttribute,
+    HasFieldMarshal,
+    HasDeclSecurity,
+    MemberRefParent,
+    HasSemantics,
+    MethodDefOrRef,
+    MemberForwarded,
+    Implementation,
+    CustomAttributeType,
+    ResolutionScope,
+    TypeOrMethodDef,
+}
+
+/// Value used in coded indexes for tags that don't correspond to any
+/// table.
+const UNUSED: usize = usize::MAX;
+
+impl Coded {
+    /// Returns the tables that the coded index can refer to, in the order
+    /// given by their tags.
+    fn tables(self) -> &'static [usize] {
+        match self {
+            Coded::TypeDefOrRef => &[TYPE_DEF, TYPE_REF, TYPE_SPEC],
+            Coded::HasConstant => &[FIELD, PARAM, PROPERTY],
+            Coded::HasCustomAttribute => &[
+                METHOD_DEF,
+                FIELD,
+                TYPE_REF,
+                TYPE_DEF,
+                PARAM,
+                INTERFACE_IMPL,
+                MEMBER_REF,
+                MODULE,
+                DECL_SECURITY,
+                PROPERTY,
+                EVENT,
+                STAND_ALONE_SIG,
+                MODULE_REF,
+                TYPE_SPEC,
+                ASSEMBLY,
+                ASSEMBLY_REF,
+                FILE,
+                EXPORTED_TYPE,
+                MANIFEST_RESOURCE,
+                GENERIC_PARAM,
+                GENERIC_PARAM_CONSTRAINT,
+                METHOD_SPEC,
+            ],
+            Coded::HasFieldMarshal => &[FIELD, PARAM],
+            Coded::HasDeclSecurity => &[TYPE_DEF, METHOD_DEF, ASSEMBLY],
+            Coded::MemberRefParent => {
+                &[TYPE_DEF, TYPE_REF, MODULE_REF, METHOD_DEF, TYPE_SPEC]
+            }
+            Coded::HasSemantics => &[EVENT, PROPERTY],
+            Coded::MethodDefOrRef => &[METHOD_DEF, MEMBER_REF],
+            Coded::MemberForwarded => &[FIELD, METHOD_DEF],
+            Coded::Implementation => &[FILE, ASSEMBLY_REF, EXPORTED_TYPE],
+            Coded::CustomAttributeType => {
+                &[UNUSED, UNUSED, METHOD_DEF, MEMBER_REF, UNUSED]
+            }
+            Coded::ResolutionScope => {
+                &[MODULE, MODULE_REF, ASSEMBLY_REF, TYPE_REF]
+            }
+            Coded::TypeOrMethodDef => &[TYPE_DEF, METHOD_DEF],
+        }
+    }
+
+    /// Number of bits used for the tag.
+    fn tag_bits(self) -> u32 {
+        usize::BITS - (self.tables().len() - 1).leading_zeros()
+    }
+
+    /// Splits a coded index into the table and the row it refers to. Rows
+    /// are numbered starting at 1, and 0 means that there's no row.
+    pub fn decode(self, value: u32) -> Option<(usize, u32)> {
+        let bits = self.tag_bits();
+        let tag = (value & ((1 << bits) - 1)) as usize;
+        let table = *self.tables().get(tag)?;
+        (table != UNUSED).then_some((table, value >> bits))
+    }
+}
+
+/// Types of the columns in the tables.
+#[derive(Clone, Copy)]
+enum Col {
+    U8,
+    U16,
+    U32,
+    /// Offset within the `#Strings` heap.
+    Str,
+    /// Index in the `#GUID` heap.
+    Guid,
+    /// Offset within the `#Blob` heap.
+    Blob,
+    /// Index of a row in the given table.
+    Index(usize),
+    Coded(Coded),
+}
+
+use crate::modules::utils::{u32_at, u64_at};
+use Col::*;
+
+/// Columns of each table, indexed by the table's identifier.
+const SCHEMA: [&[Col]; NUM_TABLES] = [
+    // Module
+    &[U16, Str, Guid, Guid, Guid],
+    // TypeRef
+    &[Coded(Coded::ResolutionScope), Str, Str],
+    // TypeDef
+    &[
+        U32,
+        Str,
+        Str,
+        Coded(Coded::TypeDefOrRef),
+        Index(FIELD),
+        Index(METHOD_DEF),
+    ],
+    // FieldPtr
+    &[Index(FIELD)],
+    // Field
+    &[U16, Str, Blob],
+    // MethodPtr
+    &[Index(METHOD_DEF)],
+    // MethodDef
+    &[U32, U16, U16, Str, Blob, Index(PARAM)],
+    // ParamPtr
+    &[Index(PARAM)],
+    // Param
+    &[U16, U16, Str],
+    // InterfaceImpl
+    &[Index(TYPE_DEF), Coded(Coded::TypeDefOrRef)],
+    // MemberRef
+    &[Coded(Coded::MemberRefParent), Str, Blob],
+    // Constant
+    &[U8, U8, Coded(Coded::HasConstant), Blob],
+    // CustomAttribute
+    &[
+        Coded(Coded::HasCustomAttribute),
+        Coded(Coded::CustomAttributeType),
+        Blob,
+    ],
+    // FieldMarshal
+    &[Coded(Coded::HasFieldMarshal), Blob],
+    // DeclSecurity
+    &[U16, Coded(Coded::HasDeclSecurity), Blob],
+    // ClassLayout
+    &[U16, U32, Index(TYPE_DEF)],
+    // FieldLayout
+    &[U32, Index(FIELD)],
+    // StandAloneSig
+    &[Blob],
+    // EventMap
+    &[Index(TYPE_DEF), Index(EVENT)],
+    // EventPtr
+    &[Index(EVENT)],
+    // Event
+    &[U16, Str, Coded(Coded::TypeDefOrRef)],
+    // PropertyMap
+    &[Index(TYPE_DEF), Index(PROPERTY)],
+    // PropertyPtr
+    &[Index(PROPERTY)],
+    // Property
+    &[U16, Str, Blob],
+    // MethodSemantics
+    &[U16, Index(METHOD_DEF), Coded(Coded::HasSemantics)],
+    // MethodImpl
+    &[
+        Index(TYPE_DEF),
+        Coded(Coded::MethodDefOrRef),
+        Coded(Coded::MethodDefOrRef),
+    ],
+    // ModuleRef
+    &[Str],
+    // TypeSpec
+    &[Blob],
+    // ImplMap
+    &[U16, Coded(Coded::MemberForwarded), Str, Index(MODULE_REF)],
+    // FieldRVA
+    &[U32, Index(FIELD)],
+    // EncLog
+    &[U32, U32],
+    // EncMap
+    &[U32],
+    // Assembly
+    &[U32, U16, U16, U16, U16, U32, Blob, Str, Str],
+    // AssemblyProcessor
+    &[U32],
+    // AssemblyOS
+    &[U32, U32, U32],
+    // AssemblyRef
+    &[U16, U16, U16, U16, U32, Blob, Str, Str, Blob],
+    // AssemblyRefProcessor
+    &[U32, Index(ASSEMBLY_REF)],
+    // AssemblyRefOS
+    &[U32, U32, U32, Index(ASSEMBLY_REF)],
+    // File
+    &[U32, Str, Blob],
+    // ExportedType
+    &[U32, U32, Str, Str, Coded(Coded::Implementation)],
+    // ManifestResource
+    &[U32, U32, Str, Coded(Coded::Implementation)],
+    // NestedClass
+    &[Index(TYPE_DEF), Index(TYPE_DEF)],
+    // GenericParam
+    &[U16, U16, Coded(Coded::TypeOrMethodDef), Str],
+    // MethodSpec
+    &[Coded(Coded::MethodDefOrRef), Blob],
+    // GenericParamConstraint
+    &[Index(GENERIC_PARAM), Coded(Coded::TypeDefOrRef)],
+];
+
+/// Flags in the `HeapSizes` field of the tables stream.
+const HEAP_STRING_4: u8 = 0x01;
+const HEAP_GUID_4: u8 = 0x02;
+const HEAP_BLOB_4: u8 = 0x04;
+/// Indicates that the row counts are followed by 4 extra bytes.
+const HEAP_EXTRA_DATA: u8 = 0x40;
+
+/// Maximum length of strings read from the `#Strings` heap.
+const MAX_STRING_LEN: usize = 1024;
+
+/// A table in the tables stream.
+struct Table<'a> {
+    row_size: usize,
+    data: &'a [u8],
+    /// Offset and size of each column within a row.
+    columns: Vec<(usize, usize)>,
+}
+
+/// The metadata of a .NET assembly.
+pub(crate) struct Metadata<'a> {
+    tables: Vec<Table<'a>>,
+    strings: &'a [u8],
+    guids: &'a [u8],
+    blobs: &'a [u8],
+    pub user_strings: &'a [u8],
+}
+
+impl<'a> Metadata<'a> {
+    /// Parses the tables stream. Returns `None` if the stream doesn't
+    /// exist, or its header is truncated. Tables that are truncated have
+    /// the rows that fit in the stream.
+    pub fn parse(root: &MetadataRoot<'a>) -> Option<Self> {
+        let stream = root.stream(b"#~").or_else(|| root.stream(b"#-"))?;
+
+        let heap_sizes = *stream.get(6)?;
+        let valid = u64_at(stream, 8)?;
+
+        let mut offset = 24;
+        let mut rows = [0_u32; NUM_TABLES];
+
+        for (table, rows) in rows.iter_mut().enumerate() {
+            if valid & (1 << table) != 0 {
+                *rows = u32_at(stream, offset)?;
+                offset += 4;
+            }
+        }
+
+        // Tables beyond the known ones have row counts too, but their row
+        // size is not known, so they must be the last ones.
+        offset += 4 * (valid >> NUM_TABLES).count_ones() as usize;
+
+        if heap_sizes & HEAP_EXTRA_DATA != 0 {
+            offset += 4;
+        }
+
+        let index_size = |rows: u32| if rows < 0x10000 { 2 } else { 4 };
+        let heap_index_size =
+            |flag: u8| if heap_sizes & flag != 0 { 4 } else { 2 };
+
+        let col_size = |col: &Col| match col {
+            U8 => 1,
+            U16 => 2,
+            U32 => 4,
+            Str => heap_index_size(HEAP_STRING_4),
+            Guid => heap_index_size(HEAP_GUID_4),
+            Blob => heap_index_size(HEAP_BLOB_4),
+            Index(table) => index_size(rows[*table]),
+            Coded(coded) => {
+                let max_rows = coded
+                    .tables()
+                    .iter()
+                    .filter(|table| **table != UNUSED)
+                    .map(|table| rows[*table])
+                    .max()
+                    .unwrap_or(0);
+                if max_rows < 1 << (16 - coded.tag_bits()) {
+                    2
+                } else {
+                    4
+                }
+            }
+        };
+
+        let mut tables = Vec::with_capacity(NUM_TABLES);
+
+        for (table, columns) in SCHEMA.iter().enumerate() {
+            let mut row_size = 0;
+            let columns = columns
+                .iter()
+                .map(|col| {
+                    let size = col_size(col);
+                    row_size += size;
+                    (row_size - size, size)
+                })
+                .collect();
+
+            let size = (rows[table] as usize).saturating_mul(row_size);
+            let data =
+                stream.get(offset.min(stream.len())..).unwrap_or_default();
+            let data = &data[..size.min(data.len())];
+
+            offset = offset.saturating_add(size);
+
+            tables.push(Table { row_size, data, columns });
+        }
+
+        Some(Self {
+            tables,
+            strings: root.stream(b"#Strings").unwrap_or_default(),
+            guids: root.stream(b"#GUID").unwrap_or_default(),
+            blobs: root.stream(b"#Blob").unwrap_or_default(),
+            user_strings: root.stream(b"#US").unwrap_or_default(),
+        })
+    }
+
+    /// Returns the number of rows in a table that are present in the
+    /// tables stream.
+    pub fn num_rows(&self, table: usize) -> u32 {
+        let t = &self.tables[table];
+        (t.data.len() / t.row_size) as u32
+    }
+
+    /// Returns an iterator over the rows in a table.
+    pub fn rows(&self, table: usize) -> impl Iterator<Item = Row<'_>> {
+        let t = &self.tables[table];
+        t.data
+            .chunks_exact(t.row_size)
+            .map(move |data| Row { data, columns: &t.columns })
+    }
+
+    /// Returns a row in a table. Rows are numbered starting at 1, like in
+    /// the indexes that refer to them.
+    pub fn row(&self, table: usize, index: u32) -> Option<Row<'_>> {
+        let t = &self.tables[table];
+        let start =
+            (index.checked_sub(1)? as usize).checked_mul(t.row_size)?;
+        let data = t.data.get(start..start.checked_add(t.row_size)?)?;
+        Some(Row { data, columns: &t.columns })
+    }
+
+    /// Returns the string at the given offset in the `#Strings` heap.
+    pub fn string(&self, offset: u32) -> Option<String> {
+        let data = self.strings.get(offset as usize..)?;
+        let len = data
+            .iter()
+            .take(MAX_STRING_LEN)
+            .position(|b| *b == 0)
+            .unwrap_or(data.len().min(MAX_STRING_LEN));
+        Some(String::from_utf8_lossy(&data[..len]).into_owned())
+    }
+
+    /// Returns the GUID with the given index, starting at 1, in the
+    /// `#GUID` heap.
+    pub fn guid(&self, index: u32) -> Option<&'a [u8]> {
+        let start = (index.checked_sub(1)? as usize).checked_mul(16)?;
+        self.guids.get(start..start.checked_add(16)?)
+    }
+
+    /// Returns the number of GUIDs in the `#GUID` heap.
+    pub fn num_guids(&self) -> usize {
+        self.guids.len() / 16
+    }
+
+    /// Returns the blob at the given offset in the `#Blob` heap.
+    pub fn blob(&self, offset: u32) -> Option<&'a [u8]> {
+        let (blob, _) = blob_at(self.blobs, offset as usize)?;
+        Some(blob)
+    }
+
+    /// Returns an iterator over the strings in the `#US` heap, as UTF-16LE
+    /// bytes. The byte that follows each string, which indicates whether
+    /// the string contains non-ASCII characters, is not included. Empty
+    /// strings are skipped.
+    pub fn user_strings(&self) -> impl Iterator<Item = &'a [u8]> {
+        let heap = self.user_strings;
+        // The first entry in the heap is always an empty blob.
+        let mut offset = 1;
+        std::iter::from_fn(move || loop {
+            let (blob, next) = blob_at(heap, offset)?;
+            offset = next;
+            if blob.len() > 1 {
+                return Some(&blob[..blob.len() - 1]);
+            }
+        })
+    }
+}
+
+/// A row in a table.
+pub(crate) struct Row<'a> {
+    data: &'a [u8],
+    columns: &'a [(usize, usize)],
+}
+
+impl Row<'_> {
+    /// Returns the value of the column with the given index.
+    pub fn get(&self, column: usize) -> u32 {
+        let (offset, size) = self.columns[column];
+        let bytes = &self.data[offset..offset + size];
+        bytes.iter().rev().fold(0_u32, |value, b| value << 8 | *b as u32)
+    }
+}
+
+/// Reads a blob, which is prefixed by its length encoded as a compressed
+/// unsigned integer. Returns the blob and the offset that follows it.
+pub(crate) fn blob_at(heap: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let (len, len_size) = compressed_u32(heap.get(offset..)?)?;
+    let start = offset + len_size;
+    let end = start.checked_add(len as usize)?;
+    Some((heap.get(start..end)?, end))
+}
+
+/// Decodes a compressed unsigned integer, as used in blobs and
+/// signatures. Returns the integer and the number of bytes it occupies.
+pub(crate) fn compressed_u32(data: &[u8]) -> Option<(u32, usize)> {
+    let first = *data.first()? as u32;
+    if first & 0x80 == 0 {
+        Some((first, 1))
+    } else if first & 0xc0 == 0x80 {
+        Some(((first & 0x3f) << 8 | *data.get(1)? as u32, 2))
+    } else if first & 0xe0 == 0xc0 {
+        let bytes = data.get(1..4)?;
+        let value = (first & 0x1f) << 24
+            | (bytes[0] as u32) << 16
+            | (bytes[1] as u32) << 8
+            | bytes[2] as u32;
+        Some((value, 4))
+    } else {
+        None
+    }
+}
+
+/// Builds a tables stream with the given rows, for testing the module.
+///
+/// All columns that refer to heaps or tables are written with 2 bytes, so
+/// the heaps and the tables must be small enough for their indexes to fit
+/// in 2 bytes.
+#[cfg(test)]
+pub(crate) fn build_tables(tables: &[(usize, Vec<Vec<u32>>)]) -> Vec<u8> {
+    let mut stream = vec![0, 0, 0, 0, 2, 0, 0, 1];
+    let valid = tables.iter().fold(0_u64, |valid, (t, _)| valid | 1 << t);
+
+    stream.extend(valid.to_le_bytes());
+    stream.extend(0_u64.to_le_bytes());
+
+    let mut tables = tables.to_vec();
+    tables.sort_by_key(|(table, _)| *table);
+
+    for (_, rows) in &tables {
+        stream.extend((rows.len() as u32).to_le_bytes());
+    }
+
+    for (table, rows) in &tables {
+        for row in rows {
+            assert_eq!(row.len(), SCHEMA[*table].len());
+            for (value, col) in row.iter().zip(SCHEMA[*table].iter()) {
+                match col {
+                    U8 => stream.push(*value as u8),
+                    U32 => stream.extend(value.to_le_bytes()),
+                    _ => stream.extend((*value as u16).to_le_bytes()),
+                }
+            }
+        }
+    }
+
+    stream
+}
@@ -0,0 +1,993 @@
+/*! YARA module that parses .NET assemblies.
+
+.NET assemblies are PE files with a CLR header, which points to the
+metadata. The metadata consists of a series of streams: the tables stream
+(`#~`), which describes the types, methods, assembly references, etc. and
+a few heaps that store the strings (`#Strings` and `#US`), GUIDs (`#GUID`)
+and binary data (`#Blob`) referenced from the tables.
+
+See: ECMA-335, Partition II.
+*/
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use protobuf::MessageField;
+use sha2::{Digest, Sha256};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::dotnet::*;
+use crate::modules::utils::format_guid;
+use crate::modules::utils::pe::Pe;
+use crate::modules::utils::u32_at;
+
+use metadata::{Coded, Metadata};
+
+mod il;
+mod metadata;
+
+/// Maximum number of entries in each of the repeated fields.
+const MAX_ENTRIES: usize = 16384;
+
+/// Maximum size of the data returned by `resource_data`. Larger resources
+/// are truncated.
+const MAX_RESOURCE_DATA: usize = 16 * 1024 * 1024;
+
+/// Maximum nesting level of nested types. Deeper types are considered
+/// to be top-level ones.
+const MAX_NESTING: usize = 32;
+
+/// Flags in TypeAttributes.
+const TYPE_VISIBILITY_MASK: u32 = 0x7;
+const TYPE_INTERFACE: u32 = 0x20;
+const TYPE_ABSTRACT: u32 = 0x80;
+const TYPE_SEALED: u32 = 0x100;
+
+/// Flags in MethodAttributes.
+const METHOD_ACCESS_MASK: u32 = 0x7;
+const METHOD_STATIC: u32 = 0x10;
+const METHOD_FINAL: u32 = 0x20;
+const METHOD_VIRTUAL: u32 = 0x40;
+const METHOD_ABSTRACT: u32 = 0x400;
+const METHOD_PINVOKE_IMPL: u32 = 0x2000;
+
+/// Flags in MethodImplAttributes.
+const METHOD_IMPL_CODE_TYPE_MASK: u32 = 0x3;
+const METHOD_IMPL_NATIVE: u32 = 0x1;
+const METHOD_IMPL_INTERNAL_CALL: u32 = 0x1000;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Dotnet {
+    let mut dotnet = Dotnet::new();
+
+    if parse(ctx.scanned_data(), &mut dotnet).is_none() {
+        dotnet.set_is_dotnet(false);
+    }
+
+    dotnet
+}
+
+/// Returns the data of the embedded resource with the given name, or
+/// undefined if there's no such resource or its data is not in the file.
+/// Only the first 16MB are returned for larger resources.
+#[module_export]
+fn resource_data(
+    ctx: &ScanContext,
+    name: RuntimeString,
+) -> Option<RuntimeString> {
+    let dotnet = ctx.module_output::<Dotnet>()?;
+    let name = name.as_bstr(ctx);
+
+    let resource = dotnet
+        .resources
+        .iter()
+        .find(|resource| resource.name().as_bytes() == name.as_bytes())?;
+
+    let offset: usize = resource.offset?.try_into().ok()?;
+    let length: usize = resource.length?.try_into().ok()?;
+
+    Some(RuntimeString::ScannedDataSlice {
+        offset,
+        length: length.min(MAX_RESOURCE_DATA),
+    })
+}
+
+fn parse(data: &[u8], dotnet: &mut Dotnet) -> Option<()> {
+    let pe = Pe::parse(data)?;
+    let clr_header = pe.clr_header()?;
+    let root = pe.metadata_root(&clr_header)?;
+    let root_offset = pe.rva_to_offset(clr_header.metadata.0)?;
+
+    dotnet.set_is_dotnet(true);
+    dotnet.set_version(root.version.clone());
+
+    for stream in root.streams.iter().take(MAX_ENTRIES) {
+        let mut s = MetadataStream::new();
+        s.set_name(String::from_utf8_lossy(stream.name).into_owned());
+        s.set_offset(root_offset as i64 + stream.offset as i64);
+        s.set_size(stream.size.into());
+        dotnet.streams.push(s);
+    }
+
+    dotnet.set_number_of_streams(dotnet.streams.len() as i64);
+
+    let metadata = match Metadata::parse(&root) {
+        Some(metadata) => metadata,
+        None => return Some(()),
+    };
+
+    dotnet.guids = (1..=metadata.num_guids().min(MAX_ENTRIES) as u32)
+        .filter_map(|index| metadata.guid(index))
+        .map(format_guid)
+        .collect();
+
+    dotnet.set_number_of_guids(dotnet.guids.len() as i64);
+
+    if let Some(module) = metadata.row(metadata::MODULE, 1) {
+        dotnet.module_name = metadata.string(module.get(1));
+        dotnet.mvid = metadata.guid(module.get(2)).map(format_guid);
+    }
+
+    if let Some(row) = metadata.row(metadata::ASSEMBLY, 1) {
+        let mut assembly = Assembly::new();
+        assembly.version =
+            version(&[row.get(1), row.get(2), row.get(3), row.get(4)]);
+        assembly.name = metadata.string(row.get(7));
+        assembly.culture = metadata.string(row.get(8));
+        dotnet.assembly = MessageField::some(assembly);
+    }
+
+    for row in metadata.rows(metadata::ASSEMBLY_REF).take(MAX_ENTRIES) {
+        let mut assembly_ref = AssemblyRef::new();
+        assembly_ref.version =
+            version(&[row.get(0), row.get(1), row.get(2), row.get(3)]);
+        assembly_ref.public_key_or_token =
+            metadata.blob(row.get(5)).map(|blob| blob.to_vec());
+        assembly_ref.name = metadata.string(row.get(6));
+        assembly_ref.culture = metadata.string(row.get(7));
+        dotnet.assembly_refs.push(assembly_ref);
+    }
+
+    dotnet.set_number_of_assembly_refs(dotnet.assembly_refs.len() as i64);
+
+    resources(&pe, clr_header.resources.0, &metadata, dotnet);
+
+    dotnet.modulerefs = metadata
+        .rows(metadata::MODULE_REF)
+        .take(MAX_ENTRIES)
+        .filter_map(|row| metadata.string(row.get(0)))
+        .collect();
+
+    dotnet.set_number_of_modulerefs(dotnet.modulerefs.len() as i64);
+
+    dotnet.user_strings = metadata
+        .user_strings()
+        .take(MAX_ENTRIES)
+        .map(|s| s.to_vec())
+        .collect();
+
+    dotnet.set_number_of_user_strings(dotnet.user_strings.len() as i64);
+
+    dotnet.constants = metadata
+        .rows(metadata::CONSTANT)
+        .take(MAX_ENTRIES)
+        .filter_map(|row| metadata.blob(row.get(3)))
+        .map(|blob| blob.to_vec())
+        .collect();
+
+    dotnet.set_number_of_constants(dotnet.constants.len() as i64);
+
+    dotnet.field_offsets = metadata
+        .rows(metadata::FIELD_LAYOUT)
+        .take(MAX_ENTRIES)
+        .map(|row| row.get(0).into())
+        .collect();
+
+    dotnet.set_number_of_field_offsets(dotnet.field_offsets.len() as i64);
+
+    let types = Types::new(&metadata);
+
+    classes(&pe, &metadata, &types, dotnet);
+    custom_attributes(&metadata, &types, dotnet);
+
+    dotnet.typelib = dotnet
+        .custom_attributes
+        .iter()
+        .filter(|attribute| {
+            attribute.target() == "assembly"
+                && attribute.type_()
+                    == "System.Runtime.InteropServices.GuidAttribute"
+        })
+        .find_map(|attribute| string_argument(attribute.value()));
+
+    Some(())
+}
+
+/// Full names of the types in the TypeDef and TypeRef tables.
+struct Types {
+    type_defs: Vec<String>,
+    type_refs: Vec<String>,
+    /// Index of the first method of each type in the TypeDef table.
+    method_lists: Vec<u32>,
+}
+
+impl Types {
+    fn new(metadata: &Metadata) -> Self {
+        // Maps each nested type to its enclosing type.
+        let enclosing: HashMap<u32, u32> = metadata
+            .rows(metadata::NESTED_CLASS)
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+
+        let type_defs = (1..=metadata.num_rows(metadata::TYPE_DEF))
+            .map(|mut index| {
+                let mut names = Vec::new();
+                while let Some(row) = metadata.row(metadata::TYPE_DEF, index) {
+                    names.push(full_name(metadata, row.get(2), row.get(1)));
+                    match enclosing.get(&index) {
+                        Some(outer) if names.len() < MAX_NESTING => {
+                            index = *outer
+                        }
+                        _ => break,
+                    }
+                }
+                names.reverse();
+                names.join(".")
+            })
+            .collect();
+
+        // Types in the TypeRef table are nested when their resolution scope
+        // is another type in the same table.
+        let type_refs = (1..=metadata.num_rows(metadata::TYPE_REF))
+            .map(|mut index| {
+                let mut names = Vec::new();
+                while let Some(row) = metadata.row(metadata::TYPE_REF, index) {
+                    names.push(full_name(metadata, row.get(2), row.get(1)));
+                    match Coded::ResolutionScope.decode(row.get(0)) {
+                        Some((metadata::TYPE_REF, outer))
+                            if names.len() < MAX_NESTING =>
+                        {
+                            index = outer
+                        }
+                        _ => break,
+                    }
+                }
+                names.reverse();
+                names.join(".")
+            })
+            .collect();
+
+        let method_lists =
+            metadata.rows(metadata::TYPE_DEF).map(|row| row.get(5)).collect();
+
+        Self { type_defs, type_refs, method_lists }
+    }
+
+    /// Returns the full name of a type in the TypeDef or TypeRef tables.
+    /// Rows are numbered starting at 1.
+    fn name(&self, table: usize, index: u32) -> Option<&str> {
+        let names = match table {
+            metadata::TYPE_DEF => &self.type_defs,
+            metadata::TYPE_REF => &self.type_refs,
+            _ => return None,
+        };
+        names.get(index.checked_sub(1)? as usize).map(|name| name.as_str())
+    }
+
+    /// Returns the range of rows in the MethodDef table with the methods of
+    /// the type at the given index in the TypeDef table, starting at 0.
+    fn methods(&self, index: usize, num_methods: u32) -> std::ops::Range<u32> {
+        let start = self.method_lists[index];
+        let end = self
+            .method_lists
+            .get(index + 1)
+            .copied()
+            .unwrap_or(num_methods + 1);
+        start..end.max(start).min(num_methods + 1)
+    }
+
+    /// Returns the index in the TypeDef table, starting at 0, of the type
+    /// that has the method at the given row of the MethodDef table.
+    fn method_owner(&self, method: u32) -> Option<usize> {
+        // The lists of methods are sorted, so the owner is the last type
+        // whose list starts at or before the method.
+        self.method_lists
+            .partition_point(|start| *start <= method)
+            .checked_sub(1)
+    }
+}
+
+/// Returns a type's name prefixed by its namespace, if any.
+fn full_name(metadata: &Metadata, namespace: u32, name: u32) -> String {
+    let name = metadata.string(name).unwrap_or_default();
+    match metadata.string(namespace) {
+        Some(namespace) if !namespace.is_empty() => {
+            format!("{}.{}", namespace, name)
+        }
+        _ => name,
+    }
+}
+
+/// Adds the types defined in the assembly to the module's output.
+fn classes(pe: &Pe, metadata: &Metadata, types: &Types, dotnet: &mut Dotnet) {
+    let num_methods = metadata.num_rows(metadata::METHOD_DEF);
+
+    // Maps each method implemented with P/Invoke to the name of the
+    // function and the module it's imported from.
+    let imports: HashMap<u32, (Option<String>, Option<String>)> = metadata
+        .rows(metadata::IMPL_MAP)
+        .filter_map(|row| {
+            let (table, method) = Coded::MemberForwarded.decode(row.get(1))?;
+            if table != metadata::METHOD_DEF {
+                return None;
+            }
+            let module = metadata
+                .row(metadata::MODULE_REF, row.get(3))
+                .and_then(|module| metadata.string(module.get(0)));
+            Some((method, (metadata.string(row.get(2)), module)))
+        })
+        .collect();
+
+    let mut num_entries = 0;
+
+    for (i, row) in metadata.rows(metadata::TYPE_DEF).enumerate() {
+        if num_entries >= MAX_ENTRIES {
+            break;
+        }
+
+        let flags = row.get(0);
+        let mut class = DotnetClass::new();
+
+        class.set_fullname(types.type_defs[i].clone());
+        class.name = metadata.string(row.get(1));
+        class.namespace = metadata.string(row.get(2));
+        class.set_visibility(type_visibility(flags).to_string());
+        class.set_type(
+            if flags & TYPE_INTERFACE != 0 { "interface" } else { "class" }
+                .to_string(),
+        );
+        class.set_abstract(flags & TYPE_ABSTRACT != 0);
+        class.set_sealed(flags & TYPE_SEALED != 0);
+        class.set_flags(flags.into());
+
+        for index in types.methods(i, num_methods) {
+            if num_entries >= MAX_ENTRIES {
+                break;
+            }
+            let row = match metadata.row(metadata::METHOD_DEF, index) {
+                Some(row) => row,
+                None => break,
+            };
+
+            let impl_flags = row.get(1);
+            let flags = row.get(2);
+            let mut method = DotnetMethod::new();
+
+            method.name = metadata.string(row.get(3));
+            method.set_visibility(method_visibility(flags).to_string());
+            method.set_static(flags & METHOD_STATIC != 0);
+            method.set_virtual(flags & METHOD_VIRTUAL != 0);
+            method.set_final(flags & METHOD_FINAL != 0);
+            method.set_abstract(flags & METHOD_ABSTRACT != 0);
+            method.set_pinvoke(flags & METHOD_PINVOKE_IMPL != 0);
+            method.set_native(
+                impl_flags & METHOD_IMPL_CODE_TYPE_MASK == METHOD_IMPL_NATIVE,
+            );
+            method.set_internal_call(
+                impl_flags & METHOD_IMPL_INTERNAL_CALL != 0,
+            );
+            method.set_flags(flags.into());
+            method.set_impl_flags(impl_flags.into());
+            method.set_rva(row.get(0).into());
+
+            if let Some(code) = method_code(pe, row.get(0)) {
+                method.set_code_offset(
+                    (code.as_ptr() as usize - pe.data.as_ptr() as usize)
+                        as i64,
+                );
+                method.set_code_size(code.len() as i64);
+                method.set_code_hash(code_hash(code));
+            }
+
+            if let Some((name, module)) = imports.get(&index) {
+                method.pinvoke_name = name.clone();
+                method.pinvoke_module = module.clone();
+            }
+
+            class.methods.push(method);
+            num_entries += 1;
+        }
+
+        class.set_number_of_methods(class.methods.len() as i64);
+
+        if let Some(namespace) = class.namespace.as_ref() {
+            if !namespace.is_empty() && !dotnet.namespaces.contains(namespace)
+            {
+                dotnet.namespaces.push(namespace.clone());
+            }
+        }
+
+        dotnet.classes.push(class);
+        num_entries += 1;
+    }
+
+    dotnet.set_number_of_classes(dotnet.classes.len() as i64);
+}
+
+/// Returns the IL code of the method whose body is at the given RVA.
+/// Methods without a body have a zero RVA.
+fn method_code<'a>(pe: &Pe<'a>, rva: u32) -> Option<&'a [u8]> {
+    if rva == 0 {
+        return None;
+    }
+    il::method_code(pe.data_at_rva(rva)?)
+}
+
+/// Returns the SHA-256 of the normalized IL code, as a hex string.
+fn code_hash(code: &[u8]) -> String {
+    let mut digest = String::with_capacity(64);
+    for b in Sha256::digest(il::normalize(code)) {
+        write!(digest, "{:02x}", b).unwrap();
+    }
+    digest
+}
+
+/// Adds the custom attributes to the module's output.
+fn custom_attributes(metadata: &Metadata, types: &Types, dotnet: &mut Dotnet) {
+    for row in metadata.rows(metadata::CUSTOM_ATTRIBUTE).take(MAX_ENTRIES) {
+        let mut attribute = CustomAttribute::new();
+
+        attribute.type_ =
+            attribute_type(metadata, types, row.get(1)).map(String::from);
+
+        if let Some((table, index)) =
+            Coded::HasCustomAttribute.decode(row.get(0))
+        {
+            let (target, target_name) = match table {
+                metadata::ASSEMBLY => ("assembly", None),
+                metadata::MODULE => ("module", None),
+                metadata::TYPE_DEF => {
+                    ("class", types.name(table, index).map(String::from))
+                }
+                metadata::METHOD_DEF => (
+                    "method",
+                    metadata
+                        .row(table, index)
+                        .and_then(|row| metadata.string(row.get(3))),
+                ),
+                metadata::FIELD => (
+                    "field",
+                    metadata
+                        .row(table, index)
+                        .and_then(|row| metadata.string(row.get(1))),
+                ),
+                metadata::PARAM => ("parameter", None),
+                metadata::PROPERTY => ("property", None),
+                metadata::EVENT => ("event", None),
+                _ => ("other", None),
+            };
+            attribute.set_target(target.to_string());
+            attribute.target_name = target_name;
+        }
+
+        attribute.value = metadata.blob(row.get(2)).map(|blob| blob.to_vec());
+        dotnet.custom_attributes.push(attribute);
+    }
+
+    dotnet
+        .set_number_of_custom_attributes(dotnet.custom_attributes.len() as i64);
+}
+
+/// Returns the full name of the type of a custom attribute, given the
+/// coded index of its constructor.
+///
+/// Constructors of attributes defined in other assemblies are in the
+/// MemberRef table, and their parent is a type in the TypeRef table.
+/// Constructors of attributes defined in the assembly itself are in the
+/// MethodDef table, and their type is the one whose list of methods
+/// contains the constructor.
+fn attribute_type<'a>(
+    metadata: &Metadata,
+    types: &'a Types,
+    constructor: u32,
+) -> Option<&'a str> {
+    match Coded::CustomAttributeType.decode(constructor)? {
+        (metadata::MEMBER_REF, index) => {
+            let member_ref = metadata.row(metadata::MEMBER_REF, index)?;
+            let (table, index) =
+                Coded::MemberRefParent.decode(member_ref.get(0))?;
+            types.name(table, index)
+        }
+        (_, index) => {
+            let owner = types.method_owner(index)?;
+            Some(types.type_defs.get(owner)?.as_str())
+        }
+    }
+}
+
+/// Returns the first argument of a custom attribute, which must be a
+/// string. The attribute's value starts with the prolog 0x0001, followed
+/// by the arguments. Strings are prefixed by their length.
+fn string_argument(value: &[u8]) -> Option<String> {
+    let value = value.strip_prefix(b"\x01\x00")?;
+    let (s, _) = metadata::blob_at(value, 0)?;
+    Some(String::from_utf8_lossy(s).into_owned())
+}
+
+/// Returns the visibility of a type, given its TypeAttributes.
+fn type_visibility(flags: u32) -> &'static str {
+    match flags & TYPE_VISIBILITY_MASK {
+        1 | 2 => "public",
+        3 => "private",
+        4 => "protected",
+        6 => "private protected",
+        7 => "protected internal",
+        _ => "internal",
+    }
+}
+
+/// Returns the visibility of a method, given its MethodAttributes.
+fn method_visibility(flags: u32) -> &'static str {
+    match flags & METHOD_ACCESS_MASK {
+        2 => "private protected",
+        3 => "internal",
+        4 => "protected",
+        5 => "protected internal",
+        6 => "public",
+        _ => "private",
+    }
+}
+
+/// Adds the resources embedded in the assembly to the module's output.
+///
+/// The data of the embedded resources is stored one after the other at
+/// the location indicated by the CLR header, and each resource in the
+/// ManifestResource table has the offset of its data relative to that
+/// location. The data is prefixed by its length.
+fn resources(
+    pe: &Pe,
+    resources_rva: u32,
+    metadata: &Metadata,
+    dotnet: &mut Dotnet,
+) {
+    let base = pe.rva_to_offset(resources_rva);
+
+    for row in metadata.rows(metadata::MANIFEST_RESOURCE) {
+        if dotnet.resources.len() == MAX_ENTRIES {
+            break;
+        }
+
+        // Resources with an implementation are stored in another file or
+        // assembly.
+        if row.get(3) != 0 {
+            continue;
+        }
+
+        let mut resource = ManifestResource::new();
+        resource.name = metadata.string(row.get(2));
+
+        // The offset and length are left undefined for resources that
+        // exceed the end of the file.
+        if let Some((offset, length)) = base
+            .and_then(|base| base.checked_add(row.get(0) as usize))
+            .and_then(|offset| {
+                Some((offset + 4, u32_at(pe.data, offset)? as usize))
+            })
+            .filter(|(offset, length)| offset + length <= pe.data.len())
+        {
+            resource.set_offset(offset as i64);
+            resource.set_length(length as i64);
+        }
+
+        dotnet.resources.push(resource);
+    }
+
+    dotnet.set_number_of_resources(dotnet.resources.len() as i64);
+}
+
+fn version(parts: &[u32; 4]) -> MessageField<AssemblyVersion> {
+    let mut version = AssemblyVersion::new();
+    version.set_major(parts[0].into());
+    version.set_minor(parts[1].into());
+    version.set_build_number(parts[2].into());
+    version.set_revision_number(parts[3].into());
+    MessageField::some(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::metadata::*;
+    use crate::modules::utils::pe::{TestPe, TestSection};
+
+    /// RVA of the section that contains the CLR header, the metadata and
+    /// the resources.
+    const TEXT_RVA: u32 = 0x2000;
+
+    /// RVA of the section that contains the methods' code.
+    const CODE_RVA: u32 = 0x4000;
+
+    /// Heaps of the metadata, which are filled while building the tables.
+    struct Heaps {
+        strings: Vec<u8>,
+        blobs: Vec<u8>,
+        guids: Vec<u8>,
+        user_strings: Vec<u8>,
+    }
+
+    impl Heaps {
+        fn new() -> Self {
+            Self {
+                strings: vec![0],
+                blobs: vec![0],
+                guids: Vec::new(),
+                user_strings: vec![0],
+            }
+        }
+
+        fn string(&mut self, s: &str) -> u32 {
+            let offset = self.strings.len() as u32;
+            self.strings.extend(s.as_bytes());
+            self.strings.push(0);
+            offset
+        }
+
+        fn blob(&mut self, blob: &[u8]) -> u32 {
+            let offset = self.blobs.len() as u32;
+            self.blobs.push(blob.len() as u8);
+            self.blobs.extend(blob);
+            offset
+        }
+
+        fn guid(&mut self, guid: &[u8; 16]) -> u32 {
+            self.guids.extend(guid);
+            (self.guids.len() / 16) as u32
+        }
+
+        fn user_string(&mut self, s: &str) {
+            let units: Vec<u8> =
+                s.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+            self.user_strings.push(units.len() as u8 + 1);
+            self.user_strings.extend(units);
+            self.user_strings.push(0);
+        }
+    }
+
+    /// Builds a .NET assembly with the given heaps and tables. `resources`
+    /// is the data of the embedded resources, and `code` the methods'
+    /// bodies, which are at `CODE_RVA`.
+    fn build_assembly(
+        heaps: Heaps,
+        tables: &[(usize, Vec<Vec<u32>>)],
+        resources: &[u8],
+        code: Vec<u8>,
+    ) -> Vec<u8> {
+        let streams: [(&[u8], Vec<u8>); 5] = [
+            (b"#~\0\0", build_tables(tables)),
+            (b"#Strings\0\0\0\0", heaps.strings),
+            (b"#US\0", heaps.user_strings),
+            (b"#GUID\0\0\0", heaps.guids),
+            (b"#Blob\0\0\0", heaps.blobs),
+        ];
+
+        let mut root = b"BSJB\x01\x00\x01\x00\x00\x00\x00\x00".to_vec();
+        root.extend(12_u32.to_le_bytes());
+        root.extend(b"v4.0.30319\0\0");
+        root.extend(0_u16.to_le_bytes());
+        root.extend((streams.len() as u16).to_le_bytes());
+
+        let headers_size: usize =
+            streams.iter().map(|(name, _)| 8 + name.len()).sum();
+        let mut offset = (root.len() + headers_size) as u32;
+
+        for (name, content) in &streams {
+            root.extend(offset.to_le_bytes());
+            root.extend((content.len() as u32).to_le_bytes());
+            root.extend(*name);
+            offset += content.len() as u32;
+        }
+
+        for (_, content) in streams {
+            root.extend(content);
+        }
+
+        let metadata_rva = TEXT_RVA + 72;
+        let resources_rva = metadata_rva + root.len() as u32;
+
+        let mut text = Vec::new();
+        text.extend(72_u32.to_le_bytes());
+        text.extend(2_u16.to_le_bytes());
+        text.extend(5_u16.to_le_bytes());
+        text.extend(metadata_rva.to_le_bytes());
+        text.extend((root.len() as u32).to_le_bytes());
+        text.extend(1_u32.to_le_bytes());
+        text.extend(0x06000001_u32.to_le_bytes());
+        text.extend(resources_rva.to_le_bytes());
+        text.extend((resources.len() as u32).to_le_bytes());
+        text.resize(72, 0);
+        text.extend(root);
+        text.extend(resources);
+
+        TestPe {
+            directories: vec![(14, TEXT_RVA, 72)],
+            sections: vec![
+                TestSection::new(b".text", TEXT_RVA, text),
+                TestSection::new(b".code", CODE_RVA, code),
+            ],
+            ..Default::default()
+        }
+        .build()
+    }
+
+    #[test]
+    fn end2end() {
+        let mut heaps = Heaps::new();
+
+        let mvid = heaps.guid(b"\x33\x22\x11\x00\x55\x44\x77\x66\x88\x99\xaa\xbb\xcc\xdd\xee\xff");
+        let module = vec![vec![0, heaps.string("evil.exe"), mvid, 0, 0]];
+
+        let assembly =
+            vec![vec![0x8004, 1, 2, 3, 4, 0, 0, heaps.string("evil"), 0]];
+
+        let token = heaps.blob(b"\xb7\x7a\x5c\x56\x19\x34\xe0\x89");
+        let assembly_refs = vec![
+            vec![4, 0, 0, 0, 0, token, heaps.string("mscorlib"), 0, 0],
+            vec![1, 2, 0, 0, 0, 0, heaps.string("Newtonsoft.Json"), 0, 0],
+        ];
+
+        let type_refs = vec![vec![
+            (1 << 2) | 2,
+            heaps.string("GuidAttribute"),
+            heaps.string("System.Runtime.InteropServices"),
+        ]];
+
+        // Constructor of GuidAttribute, whose parent is the TypeRef above.
+        let member_refs = vec![vec![(1 << 3) | 1, heaps.string(".ctor"), 0]];
+
+        let evil = heaps.string("Evil");
+        let type_defs = vec![
+            vec![0, heaps.string("<Module>"), 0, 0, 1, 1],
+            vec![0x100101, heaps.string("Payload"), evil, 0, 1, 1],
+            vec![0x2, heaps.string("Config"), 0, 0, 1, 3],
+            vec![0xa1, heaps.string("IFoo"), evil, 0, 1, 4],
+        ];
+
+        let method_defs = vec![
+            vec![CODE_RVA, 0, 0xd6, heaps.string("Run"), 0, 1],
+            vec![0, 0x80, 0x2013, heaps.string("MessageBoxA"), 0, 1],
+            vec![CODE_RVA + 0x10, 0, 0x1886, heaps.string(".ctor"), 0, 1],
+        ];
+
+        // The body of Run has a tiny header, and the body of .ctor a fat
+        // one. Both have the same code, except for the tokens.
+        let mut code = vec![0x2e];
+        code.extend(b"\x72\x01\x00\x00\x70\x28\x01\x00\x00\x0a\x2a");
+        code.resize(0x10, 0);
+        code.extend(b"\x03\x30\x08\x00");
+        code.extend(11_u32.to_le_bytes());
+        code.extend(0_u32.to_le_bytes());
+        code.extend(b"\x72\x05\x00\x00\x70\x28\x03\x00\x00\x0a\x2a");
+
+        // MessageBoxA is imported from user32 (second row in ModuleRef).
+        let impl_maps =
+            vec![vec![0x100, (2 << 1) | 1, heaps.string("MessageBoxA"), 2]];
+
+        // Config is nested in Payload.
+        let nested_classes = vec![vec![3, 2]];
+
+        let guid_value =
+            b"\x01\x00\x24e4f0c2a8-1b3d-4f5e-8a9b-0c1d2e3f4a5b\x00\x00";
+        let custom_attributes = vec![
+            // GuidAttribute applied to the assembly.
+            vec![(1 << 5) | 14, (1 << 3) | 3, heaps.blob(guid_value)],
+            // Attribute defined in the assembly itself, whose constructor
+            // is the third method, applied to Payload.
+            vec![(2 << 5) | 3, (3 << 3) | 2, heaps.blob(b"\x01\x00\x00\x00")],
+        ];
+
+        let module_refs = vec![
+            vec![heaps.string("kernel32.dll")],
+            vec![heaps.string("user32")],
+        ];
+
+        let constants =
+            vec![vec![0x08, 0, 0, heaps.blob(b"\x2a\x00\x00\x00")]];
+        let field_layouts = vec![vec![0x10, 1]];
+
+        let mut resources = Vec::new();
+        resources.extend(5_u32.to_le_bytes());
+        resources.extend(b"hello\0\0\0");
+        resources.extend(2_u32.to_le_bytes());
+        resources.extend(b"MZ");
+
+        let manifest_resources = vec![
+            vec![0, 1, heaps.string("Payload.resources"), 0],
+            vec![12, 1, heaps.string("stub.bin"), 0],
+            // A resource stored in another assembly.
+            vec![0, 1, heaps.string("Other.resources"), (1 << 2) | 1],
+        ];
+
+        heaps.user_string("Hello, world!");
+        heaps.user_string("");
+        heaps.user_string("cmd.exe");
+
+        let data = build_assembly(
+            heaps,
+            &[
+                (MODULE, module),
+                (TYPE_REF, type_refs),
+                (TYPE_DEF, type_defs),
+                (METHOD_DEF, method_defs),
+                (MEMBER_REF, member_refs),
+                (CONSTANT, constants),
+                (CUSTOM_ATTRIBUTE, custom_attributes),
+                (FIELD_LAYOUT, field_layouts),
+                (MODULE_REF, module_refs),
+                (IMPL_MAP, impl_maps),
+                (ASSEMBLY, assembly),
+                (ASSEMBLY_REF, assembly_refs),
+                (MANIFEST_RESOURCE, manifest_resources),
+                (NESTED_CLASS, nested_classes),
+            ],
+            &resources,
+            code,
+        );
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r##"import "dotnet"
+                rule metadata {
+                  condition:
+                    dotnet.is_dotnet and
+                    dotnet.version == "v4.0.30319" and
+                    dotnet.module_name == "evil.exe" and
+                    dotnet.mvid == "00112233-4455-6677-8899-aabbccddeeff" and
+                    dotnet.guids[0] == dotnet.mvid and
+                    dotnet.number_of_guids == 1 and
+                    dotnet.typelib == "e4f0c2a8-1b3d-4f5e-8a9b-0c1d2e3f4a5b" and
+                    dotnet.number_of_streams == 5 and
+                    dotnet.streams[0].name == "#~" and
+                    dotnet.streams[4].name == "#Blob" and
+                    uint8(dotnet.streams[1].offset + 1) == 0x65
+                }
+                rule assembly {
+                  condition:
+                    dotnet.assembly.name == "evil" and
+                    dotnet.assembly.version.major == 1 and
+                    dotnet.assembly.version.minor == 2 and
+                    dotnet.assembly.version.build_number == 3 and
+                    dotnet.assembly.version.revision_number == 4 and
+                    dotnet.number_of_assembly_refs == 2 and
+                    dotnet.assembly_refs[0].name == "mscorlib" and
+                    dotnet.assembly_refs[0].version.major == 4 and
+                    dotnet.assembly_refs[0].public_key_or_token == "\xb7\x7a\x5c\x56\x19\x34\xe0\x89" and
+                    dotnet.assembly_refs[1].name == "Newtonsoft.Json" and
+                    dotnet.assembly_refs[1].version.minor == 2
+                }
+                rule resources {
+                  condition:
+                    dotnet.number_of_resources == 2 and
+                    dotnet.resources[0].name == "Payload.resources" and
+                    dotnet.resources[0].length == 5 and
+                    uint8(dotnet.resources[0].offset) == 0x68 and
+                    dotnet.resources[1].name == "stub.bin" and
+                    uint16(dotnet.resources[1].offset) == 0x5a4d and
+                    dotnet.resource_data("Payload.resources") == "hello" and
+                    dotnet.resource_data("stub.bin") == "MZ" and
+                    not defined dotnet.resource_data("Other.resources") and
+                    not defined dotnet.resource_data("missing")
+                }
+                rule heaps {
+                  condition:
+                    dotnet.number_of_user_strings == 2 and
+                    dotnet.user_strings[0] == "H\x00e\x00l\x00l\x00o\x00,\x00 \x00w\x00o\x00r\x00l\x00d\x00!\x00" and
+                    for any s in dotnet.user_strings : (s == "c\x00m\x00d\x00.\x00e\x00x\x00e\x00") and
+                    dotnet.number_of_modulerefs == 2 and
+                    dotnet.modulerefs[0] == "kernel32.dll" and
+                    dotnet.number_of_constants == 1 and
+                    dotnet.constants[0] == "\x2a\x00\x00\x00" and
+                    dotnet.number_of_field_offsets == 1 and
+                    dotnet.field_offsets[0] == 0x10
+                }
+                rule classes {
+                  condition:
+                    dotnet.number_of_classes == 4 and
+                    dotnet.classes[0].fullname == "<Module>" and
+                    dotnet.classes[0].number_of_methods == 0 and
+                    dotnet.classes[1].fullname == "Evil.Payload" and
+                    dotnet.classes[1].namespace == "Evil" and
+                    dotnet.classes[1].visibility == "public" and
+                    dotnet.classes[1].type == "class" and
+                    dotnet.classes[1].sealed and
+                    not dotnet.classes[1].abstract and
+                    dotnet.classes[2].fullname == "Evil.Payload.Config" and
+                    dotnet.classes[2].visibility == "public" and
+                    dotnet.classes[3].fullname == "Evil.IFoo" and
+                    dotnet.classes[3].type == "interface" and
+                    dotnet.classes[3].abstract and
+                    dotnet.classes[3].number_of_methods == 0 and
+                    dotnet.namespaces[0] == "Evil" and
+                    not defined dotnet.namespaces[1]
+                }
+                rule methods {
+                  condition:
+                    dotnet.classes[1].number_of_methods == 2 and
+                    dotnet.classes[1].methods[0].name == "Run" and
+                    dotnet.classes[1].methods[0].visibility == "public" and
+                    dotnet.classes[1].methods[0].static and
+                    dotnet.classes[1].methods[0].virtual and
+                    not dotnet.classes[1].methods[0].pinvoke and
+                    dotnet.classes[1].methods[1].name == "MessageBoxA" and
+                    dotnet.classes[1].methods[1].visibility == "internal" and
+                    dotnet.classes[1].methods[1].pinvoke and
+                    dotnet.classes[1].methods[1].pinvoke_module == "user32" and
+                    dotnet.classes[1].methods[1].pinvoke_name == "MessageBoxA" and
+                    not dotnet.classes[1].methods[1].native and
+                    not defined dotnet.classes[1].methods[0].pinvoke_name and
+                    dotnet.classes[2].number_of_methods == 1 and
+                    dotnet.classes[2].methods[0].name == ".ctor"
+                }
+                rule code {
+                  condition:
+                    dotnet.classes[1].methods[0].rva == 0x4000 and
+                    dotnet.classes[1].methods[0].code_size == 11 and
+                    uint8(dotnet.classes[1].methods[0].code_offset) == 0x72 and
+                    dotnet.classes[1].methods[0].code_hash == "3d36d3773443c84f2801b414ba3306dd4a640d798ab045e183ec6231b0ded348" and
+                    dotnet.classes[2].methods[0].code_size == 11 and
+                    dotnet.classes[2].methods[0].code_hash == dotnet.classes[1].methods[0].code_hash and
+                    dotnet.classes[1].methods[1].rva == 0 and
+                    not defined dotnet.classes[1].methods[1].code_size
+                }
+                rule custom_attributes {
+                  condition:
+                    dotnet.number_of_custom_attributes == 2 and
+                    dotnet.custom_attributes[0].type == "System.Runtime.InteropServices.GuidAttribute" and
+                    dotnet.custom_attributes[0].target == "assembly" and
+                    not defined dotnet.custom_attributes[0].target_name and
+                    dotnet.custom_attributes[1].type == "Evil.Payload.Config" and
+                    dotnet.custom_attributes[1].target == "class" and
+                    dotnet.custom_attributes[1].target_name == "Evil.Payload" and
+                    dotnet.custom_attributes[1].value == "\x01\x00\x00\x00"
+                }
+                rule not_dotnet { condition: not dotnet.is_dotnet }
+                "##,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&data),
+            [
+                "metadata",
+                "assembly",
+                "resources",
+                "heaps",
+                "classes",
+                "methods",
+                "code",
+                "custom_attributes"
+            ]
+        );
+
+        // A native PE file is not a .NET assembly.
+        let native = TestPe {
+            sections: vec![TestSection::new(b".text", 0x1000, vec![0xc3])],
+            ..Default::default()
+        }
+        .build();
+
+        assert_eq!(matching_rules(&native), ["not_dotnet"]);
+    }
+}
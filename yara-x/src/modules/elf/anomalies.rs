@@ -0,0 +1,83 @@
+/*! Detection of anomalies that are common in packed ELF files.
+
+Packers like UPX replace the original segments with a couple of loadable
+segments, one of them writable and executable, where the original code is
+decompressed at run time, and drop the section header table, which is not
+needed for running the file. Each anomaly is a flag in the value returned
+by [`anomalies`], which are the same as in the `Anomaly` enum in the
+module's protobuf.
+*/
+
+use super::parser::{Elf, ET_DYN, ET_EXEC, PT_LOAD};
+
+/// PF_X and PF_W flags in the segments' flags.
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+
+/// The file doesn't have a section header table.
+const NO_SECTION_HEADERS: u32 = 0x1;
+/// An executable or shared object with fewer than three segments, which is
+/// less than what linkers produce.
+const FEW_SEGMENTS: u32 = 0x2;
+/// The memory ranges of two loadable segments overlap.
+const OVERLAPPING_SEGMENTS: u32 = 0x4;
+/// A loadable segment is both writable and executable.
+const WRITABLE_EXECUTABLE_SEGMENT: u32 = 0x8;
+/// An executable loadable segment is larger in memory than in the file,
+/// which leaves room for decompressing code.
+const EXPANDING_EXECUTABLE_SEGMENT: u32 = 0x10;
+/// The `UPX!` magic of the structure that UPX puts right after the program
+/// header table.
+const UPX_INFO: u32 = 0x20;
+
+/// Returns the anomalies found in the file, as a combination of the flags
+/// defined in this module.
+pub(super) fn anomalies(elf: &Elf) -> u32 {
+    let mut anomalies = 0;
+
+    if elf.sections.is_empty() {
+        anomalies |= NO_SECTION_HEADERS;
+    }
+
+    if matches!(elf.type_, ET_EXEC | ET_DYN) && elf.segments.len() < 3 {
+        anomalies |= FEW_SEGMENTS;
+    }
+
+    let loadable = elf.segments.iter().filter(|s| s.type_ == PT_LOAD);
+
+    let mut ranges: Vec<(u64, u64)> = loadable
+        .clone()
+        .filter(|s| s.memory_size > 0)
+        .map(|s| {
+            let start = s.virtual_address;
+            (start, start.saturating_add(s.memory_size))
+        })
+        .collect();
+
+    ranges.sort_unstable();
+
+    if ranges.windows(2).any(|pair| pair[1].0 < pair[0].1) {
+        anomalies |= OVERLAPPING_SEGMENTS;
+    }
+
+    for segment in loadable.filter(|s| s.flags & PF_X != 0) {
+        if segment.flags & PF_W != 0 {
+            anomalies |= WRITABLE_EXECUTABLE_SEGMENT;
+        }
+        if segment.memory_size > segment.file_size {
+            anomalies |= EXPANDING_EXECUTABLE_SEGMENT;
+        }
+    }
+
+    // The structure starts with a 4-byte checksum followed by the magic.
+    let upx_info = (elf.segments.len() as u64)
+        .checked_mul(elf.ph_entry_size.into())
+        .and_then(|size| size.checked_add(elf.ph_offset))
+        .and_then(|offset| elf.data_at(offset.checked_add(4)?, 4));
+
+    if upx_info == Some(b"UPX!") {
+        anomalies |= UPX_INFO;
+    }
+
+    anomalies
+}
@@ -0,0 +1,170 @@
+/*! Parsing of the ARM build attributes and the AArch64 features.
+
+The `.ARM.attributes` section describes the target of 32-bit ARM files,
+like the architecture version and the floating point unit. It starts
+with the `A` format version, followed by subsections for each vendor,
+which contain sub-subsections with a list of attributes. Each attribute
+is a tag followed by either an integer or a null-terminated string. Only
+the attributes in the `aeabi` subsection that apply to the whole file are
+read.
+
+AArch64 files describe the security features they were built with, like
+BTI and PAC, in the `NT_GNU_PROPERTY_TYPE_0` note.
+
+See: https://github.com/ARM-software/abi-aa/blob/main/addenda32/addenda32.rst
+*/
+
+use super::parser::Elf;
+
+/// Tag of the sub-subsections with attributes for the whole file.
+const TAG_FILE: u64 = 1;
+
+/// Tag of `Tag_compatibility`, which is an integer followed by a string.
+const TAG_COMPATIBILITY: u64 = 32;
+
+/// Tags of attributes whose value is a string.
+const TAG_CPU_RAW_NAME: u64 = 4;
+pub(super) const TAG_CPU_NAME: u64 = 5;
+
+/// Tags of attributes whose value is an integer.
+pub(super) const TAG_CPU_ARCH: u64 = 6;
+pub(super) const TAG_CPU_ARCH_PROFILE: u64 = 7;
+
+/// Type of the property with the AArch64 features.
+const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc0000000;
+
+/// Maximum number of attributes read from `.ARM.attributes`.
+const MAX_ATTRIBUTES: usize = 1024;
+
+/// Maximum length of the strings in `.ARM.attributes`.
+const MAX_STRING_LEN: usize = 1024;
+
+/// An attribute value.
+pub(super) enum Value<'a> {
+    Integer(u64),
+    String(&'a [u8]),
+}
+
+/// Returns the attributes in the `.ARM.attributes` section, as tag and
+/// value pairs.
+pub(super) fn attributes<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+) -> Vec<(u64, Value<'a>)> {
+    let mut attributes = Vec::new();
+
+    if let Some(data) = data.strip_prefix(b"A") {
+        parse(elf, data, &mut attributes);
+    }
+
+    attributes
+}
+
+fn parse<'a>(
+    elf: &Elf<'a>,
+    mut data: &'a [u8],
+    attributes: &mut Vec<(u64, Value<'a>)>,
+) -> Option<()> {
+    while !data.is_empty() {
+        // The length of the subsection includes the length itself.
+        let len = elf.u32_at(data, 0)? as usize;
+        let subsection = data.get(4..len)?;
+        data = &data[len..];
+
+        let vendor = string(subsection, &mut 0)?;
+        if vendor != b"aeabi" {
+            continue;
+        }
+
+        let mut subsection = &subsection[vendor.len() + 1..];
+
+        while !subsection.is_empty() {
+            let mut pos = 0;
+            let tag = uleb128(subsection, &mut pos)?;
+            // The size includes the tag and the size itself.
+            let size = elf.u32_at(subsection, pos)? as usize;
+            let content = subsection.get(pos + 4..size)?;
+            subsection = &subsection[size..];
+
+            if tag == TAG_FILE {
+                parse_attributes(content, attributes);
+            }
+        }
+    }
+
+    Some(())
+}
+
+fn parse_attributes<'a>(
+    data: &'a [u8],
+    attributes: &mut Vec<(u64, Value<'a>)>,
+) -> Option<()> {
+    let mut pos = 0;
+
+    while pos < data.len() && attributes.len() < MAX_ATTRIBUTES {
+        let tag = uleb128(data, &mut pos)?;
+        // Tags higher than 32 have an integer value if they are even, and
+        // a string value if they are odd.
+        let value = match tag {
+            TAG_CPU_RAW_NAME | TAG_CPU_NAME => {
+                Value::String(string(data, &mut pos)?)
+            }
+            TAG_COMPATIBILITY => {
+                let value = uleb128(data, &mut pos)?;
+                string(data, &mut pos)?;
+                Value::Integer(value)
+            }
+            tag if tag > TAG_COMPATIBILITY && tag % 2 == 1 => {
+                Value::String(string(data, &mut pos)?)
+            }
+            _ => Value::Integer(uleb128(data, &mut pos)?),
+        };
+        attributes.push((tag, value));
+    }
+
+    Some(())
+}
+
+/// Returns the AArch64 features in the descriptor of a
+/// `NT_GNU_PROPERTY_TYPE_0` note, which is an array of properties with
+/// a type, a size and the data, padded to 8 bytes.
+pub(super) fn aarch64_features(elf: &Elf, desc: &[u8]) -> Option<u32> {
+    let mut pos = 0;
+
+    while pos < desc.len() {
+        let type_ = elf.u32_at(desc, pos)?;
+        let size = elf.u32_at(desc, pos + 4)? as usize;
+
+        if type_ == GNU_PROPERTY_AARCH64_FEATURE_1_AND {
+            return elf.u32_at(desc, pos + 8);
+        }
+
+        pos = pos.checked_add(8 + size)?.checked_add(7)? & !7;
+    }
+
+    None
+}
+
+/// Reads a null-terminated string at `pos`, and moves `pos` past the null
+/// character.
+fn string<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let data = data.get(*pos..)?;
+    let len = data.iter().take(MAX_STRING_LEN).position(|b| *b == 0)?;
+    *pos += len + 1;
+    Some(&data[..len])
+}
+
+/// Reads an unsigned LEB128 integer at `pos`, and moves `pos` past it.
+fn uleb128(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut result = 0_u64;
+    // A 64-bits value is encoded in 10 bytes at most.
+    for i in 0..10 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
@@ -0,0 +1,150 @@
+/*! Parsing of the notes in core files.
+
+Core files produced by Linux have notes owned by `CORE` that describe the
+process at the time of the dump. `NT_PRPSINFO` has the name, arguments and
+IDs of the process, there's a `NT_PRSTATUS` note for each thread, with the
+signal that caused the dump and the registers, and `NT_FILE` has the files
+mapped in the address space.
+
+The layout of these notes depends on the size of the C `long` type, which
+is 4 bytes in 32-bit files and 8 in 64-bit ones.
+*/
+
+use super::notes::Note;
+use super::parser::Elf;
+
+/// Types of the notes owned by `CORE`.
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_FILE: u32 = 0x46494c45;
+
+/// Maximum number of mapped files read from the `NT_FILE` note.
+const MAX_MAPPED_FILES: usize = 65536;
+
+/// Size of the `pr_fname` and `pr_psargs` fields at the end of
+/// `NT_PRPSINFO`.
+const FNAME_LEN: usize = 16;
+const PSARGS_LEN: usize = 80;
+
+/// Information about the process in a core file.
+#[derive(Default)]
+pub(super) struct Core<'a> {
+    /// Name of the executable, truncated to 15 characters.
+    pub process_name: Option<&'a [u8]>,
+    /// Command line, truncated to 79 characters.
+    pub arguments: Option<&'a [u8]>,
+    pub pid: Option<u32>,
+    pub ppid: Option<u32>,
+    pub threads: Vec<Thread>,
+    pub files: Vec<MappedFile<'a>>,
+}
+
+/// A thread, as described in `NT_PRSTATUS`.
+pub(super) struct Thread {
+    pub pid: u32,
+    /// The signal that was being handled by the thread, or zero.
+    pub signal: u16,
+}
+
+/// A file mapped in the address space of the process.
+pub(super) struct MappedFile<'a> {
+    pub start: u64,
+    pub end: u64,
+    /// Offset within the file of the mapped data.
+    pub offset: u64,
+    pub name: &'a [u8],
+}
+
+/// Returns the information in the notes of a core file.
+pub(super) fn parse<'a>(elf: &Elf<'a>, notes: &[Note<'a>]) -> Core<'a> {
+    let mut core = Core::default();
+
+    for note in notes.iter().filter(|note| note.name == b"CORE") {
+        match note.type_ {
+            NT_PRPSINFO if core.process_name.is_none() => {
+                prpsinfo(elf, note.desc, &mut core);
+            }
+            NT_PRSTATUS => {
+                // pr_cursig is right after the 12-byte `elf_siginfo`, and
+                // pr_pid after pr_sigpend and pr_sighold, which are longs.
+                let pid_offset = if elf.is_64bit { 32 } else { 24 };
+                if let (Some(signal), Some(pid)) = (
+                    elf.u16_at(note.desc, 12),
+                    elf.u32_at(note.desc, pid_offset),
+                ) {
+                    core.threads.push(Thread { pid, signal });
+                }
+            }
+            NT_FILE if core.files.is_empty() => {
+                mapped_files(elf, note.desc, &mut core.files);
+            }
+            _ => {}
+        }
+    }
+
+    core
+}
+
+/// Parses `NT_PRPSINFO`. The size of the fields before the process IDs
+/// varies among architectures, so the fields are read from the end of the
+/// note, where there are the IDs, `pr_fname` and `pr_psargs`.
+fn prpsinfo<'a>(
+    elf: &Elf<'a>,
+    desc: &'a [u8],
+    core: &mut Core<'a>,
+) -> Option<()> {
+    // Offset of pr_pid, which is followed by pr_ppid, pr_pgrp and pr_sid.
+    let ids = desc.len().checked_sub(PSARGS_LEN + FNAME_LEN + 16)?;
+
+    let trim = |s: &'a [u8]| {
+        let s = s.split(|b| *b == 0).next().unwrap_or_default();
+        s.strip_suffix(b" ").unwrap_or(s)
+    };
+
+    let fname = ids + 16;
+    let psargs = fname + FNAME_LEN;
+
+    core.pid = elf.u32_at(desc, ids);
+    core.ppid = elf.u32_at(desc, ids + 4);
+    core.process_name = Some(trim(&desc[fname..psargs]));
+    core.arguments = Some(trim(&desc[psargs..]));
+
+    Some(())
+}
+
+/// Parses `NT_FILE`, which has the number of mapped files and the page
+/// size, followed by the start and end addresses and the offset in pages
+/// of each file, and then by their names.
+fn mapped_files<'a>(
+    elf: &Elf<'a>,
+    desc: &'a [u8],
+    files: &mut Vec<MappedFile<'a>>,
+) -> Option<()> {
+    let long = if elf.is_64bit { 8 } else { 4 };
+
+    let count = elf.long_at(desc, 0)?;
+    let page_size = elf.long_at(desc, long)?;
+    let count = usize::try_from(count).ok()?.min(MAX_MAPPED_FILES);
+
+    let mut names = desc
+        .get(
+            long.checked_mul(count)?
+                .checked_mul(3)?
+                .checked_add(2 * long)?..,
+        )?
+        .split(|b| *b == 0);
+
+    for i in 0..count {
+        let entry = (2 + i * 3) * long;
+        files.push(MappedFile {
+            start: elf.long_at(desc, entry)?,
+            end: elf.long_at(desc, entry + long)?,
+            offset: elf
+                .long_at(desc, entry + 2 * long)?
+                .saturating_mul(page_size),
+            name: names.next()?,
+        });
+    }
+
+    Some(())
+}
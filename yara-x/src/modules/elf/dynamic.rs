@@ -0,0 +1,94 @@
+/*! Parsing of the dynamic section.
+
+The dynamic section is an array of tag and value pairs, terminated by a
+`DT_NULL` entry, with the information needed by the dynamic linker, like
+the libraries the file depends on. Values that are strings, like library
+names, are offsets within the string table pointed to by `DT_STRTAB`.
+*/
+
+use super::parser::{string_at, Elf, PT_DYNAMIC, SHT_DYNAMIC};
+
+/// Maximum number of entries read from the dynamic section.
+const MAX_ENTRIES: usize = 16384;
+
+/// Tags of the dynamic entries.
+pub(super) const DT_NULL: u64 = 0;
+pub(super) const DT_NEEDED: u64 = 1;
+pub(super) const DT_STRTAB: u64 = 5;
+pub(super) const DT_STRSZ: u64 = 10;
+pub(super) const DT_SONAME: u64 = 14;
+pub(super) const DT_RPATH: u64 = 15;
+pub(super) const DT_RUNPATH: u64 = 29;
+
+/// An entry in the dynamic section.
+pub(super) struct Entry {
+    pub tag: u64,
+    pub value: u64,
+}
+
+/// Returns the entries in the dynamic section, up to the `DT_NULL` entry.
+/// The dynamic section is located with the `PT_DYNAMIC` segment, or with
+/// the section table if there's no such segment.
+pub(super) fn entries(elf: &Elf) -> Vec<Entry> {
+    let table = elf
+        .segments
+        .iter()
+        .find(|s| s.type_ == PT_DYNAMIC)
+        .and_then(|s| elf.data_at(s.offset, s.file_size))
+        .or_else(|| {
+            elf.sections
+                .iter()
+                .find(|s| s.type_ == SHT_DYNAMIC)
+                .and_then(|s| elf.section_data(s))
+        })
+        .unwrap_or_default();
+
+    let entry_size = if elf.is_64bit { 16 } else { 8 };
+
+    table
+        .chunks_exact(entry_size)
+        .take(MAX_ENTRIES)
+        .map_while(|entry| {
+            Some(if elf.is_64bit {
+                Entry {
+                    tag: elf.u64_at(entry, 0)?,
+                    value: elf.u64_at(entry, 8)?,
+                }
+            } else {
+                Entry {
+                    tag: elf.u32_at(entry, 0)?.into(),
+                    value: elf.u32_at(entry, 4)?.into(),
+                }
+            })
+        })
+        .take_while(|entry| entry.tag != DT_NULL)
+        .collect()
+}
+
+/// Returns the string table used by the dynamic entries. This is the one
+/// pointed to by `DT_STRTAB`, or the one linked to the dynamic section if
+/// the address in `DT_STRTAB` is not backed by the file.
+pub(super) fn strings<'a>(elf: &Elf<'a>, entries: &[Entry]) -> &'a [u8] {
+    let value = |tag| entries.iter().find(|e| e.tag == tag).map(|e| e.value);
+
+    value(DT_STRTAB)
+        .and_then(|address| elf.va_to_offset(address))
+        .and_then(|offset| {
+            elf.data_at(offset, value(DT_STRSZ).unwrap_or(u64::MAX))
+        })
+        .or_else(|| {
+            elf.sections
+                .iter()
+                .find(|s| s.type_ == SHT_DYNAMIC)
+                .and_then(|s| elf.sections.get(s.link as usize))
+                .and_then(|s| elf.section_data(s))
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the string at the given offset within the string table returned
+/// by [`strings`].
+pub(super) fn string(strings: &[u8], offset: u64) -> Option<String> {
+    let string = string_at(strings, u32::try_from(offset).ok()?)?;
+    Some(String::from_utf8_lossy(string).into_owned())
+}
@@ -0,0 +1,1310 @@
+/*! YARA module that parses ELF (Executable and Linkable Format) files.
+
+The module exposes the headers, the sections, the segments and the symbol
+tables of 32-bit and 64-bit files, in either byte order. It also computes
+hashes over the symbols, which are useful for clustering files that were
+built from the same source code, like `telfhash`, `import_md5` and
+`export_md5`.
+
+See: https://refspecs.linuxfoundation.org/elf/gabi4+/contents.html
+*/
+
+use protobuf::{EnumOrUnknown, MessageField};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::elf::*;
+use crate::modules::utils::digest::md5_hex;
+use crate::modules::utils::{entropy, hex, tlsh};
+
+use dynamic::{DT_NEEDED, DT_RPATH, DT_RUNPATH, DT_SONAME};
+use notes::{NT_GNU_ABI_TAG, NT_GNU_BUILD_ID, NT_GNU_PROPERTY_TYPE_0};
+use parser::{
+    Elf, Symbol, EM_AARCH64, EM_ARM, ET_CORE, SHN_UNDEF, SHT_DYNSYM,
+    SHT_SYMTAB, STB_GLOBAL, STB_WEAK, STT_FUNC, STV_DEFAULT,
+};
+use versions::VERSYM_HIDDEN;
+
+mod anomalies;
+mod arm;
+mod core_dump;
+mod dynamic;
+mod notes;
+mod parser;
+mod relocations;
+mod toolchain;
+mod versions;
+
+/// Names of functions that are ignored by `telfhash`, besides the ones
+/// that start with `_` or `.`, end with `64`, or start with `str` or
+/// `mem`. These are functions that are present in most files, regardless
+/// of their origin.
+const TELFHASH_EXCLUDED: &[&[u8]] = &[
+    b"__libc_start_main",
+    b"main",
+    b"abort",
+    b"cachectl",
+    b"cacheflush",
+    b"puts",
+    b"atol",
+    b"malloc_trim",
+];
+
+#[module_main]
+fn main(ctx: &ScanContext) -> ELF {
+    let mut elf = ELF::new();
+
+    match Elf::parse(ctx.scanned_data()) {
+        Some(parsed) => parse(&parsed, &mut elf),
+        None => elf.set_is_elf(false),
+    }
+
+    elf
+}
+
+/// Returns the Trend Micro `telfhash` of the file, which is the TLSH of a
+/// comma-separated list with the names of the global functions in the
+/// dynamic symbol table, in lowercase and sorted alphabetically. The
+/// static symbol table is used if there's no dynamic one. Undefined if
+/// the file doesn't have enough symbols for computing the TLSH.
+#[module_export]
+fn telfhash(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let elf = ctx.module_output::<ELF>()?;
+
+    let symbols =
+        if elf.dynsym.is_empty() { &elf.symtab } else { &elf.dynsym };
+
+    let mut names: Vec<String> = symbols
+        .iter()
+        .filter(|s| {
+            s.type_() == i64::from(STT_FUNC)
+                && s.bind() == i64::from(STB_GLOBAL)
+                && s.visibility() == i64::from(STV_DEFAULT)
+                && !telfhash_excluded(s.name())
+        })
+        .map(|s| String::from_utf8_lossy(s.name()).to_lowercase())
+        .collect();
+
+    names.sort_unstable();
+
+    let digest = tlsh::digest(names.join(",").as_bytes())?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the MD5 of a comma-separated list with the names of the
+/// symbols imported by the file, which are the undefined symbols in the
+/// dynamic symbol table, in lowercase and sorted alphabetically, as a hex
+/// string. Undefined if the file doesn't import any symbol.
+#[module_export]
+fn import_md5(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let elf = ctx.module_output::<ELF>()?;
+    let digest =
+        symbols_md5(&elf.dynsym, |s| s.shndx() == i64::from(SHN_UNDEF))?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the MD5 of a comma-separated list with the names of the global
+/// and weak symbols defined in the dynamic symbol table, in lowercase and
+/// sorted alphabetically, as a hex string. Undefined if the file doesn't
+/// export any symbol.
+#[module_export]
+fn export_md5(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let elf = ctx.module_output::<ELF>()?;
+    let digest = symbols_md5(&elf.dynsym, |s| {
+        s.shndx() != i64::from(SHN_UNDEF)
+            && (s.bind() == i64::from(STB_GLOBAL)
+                || s.bind() == i64::from(STB_WEAK))
+    })?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns true if a function must be ignored when computing `telfhash`.
+fn telfhash_excluded(name: &[u8]) -> bool {
+    name.is_empty()
+        || name.starts_with(b"_")
+        || name.starts_with(b".")
+        || name.ends_with(b"64")
+        || name.starts_with(b"str")
+        || name.starts_with(b"mem")
+        || TELFHASH_EXCLUDED.contains(&name)
+}
+
+/// Returns the MD5 of the names of the symbols accepted by `predicate`, as
+/// explained in `import_md5` and `export_md5`. Symbols without a name are
+/// ignored.
+fn symbols_md5<P>(symbols: &[ElfSymbol], predicate: P) -> Option<String>
+where
+    P: Fn(&ElfSymbol) -> bool,
+{
+    let mut names: Vec<String> = symbols
+        .iter()
+        .filter(|s| !s.name().is_empty() && predicate(s))
+        .map(|s| String::from_utf8_lossy(s.name()).to_lowercase())
+        .collect();
+
+    if names.is_empty() {
+        return None;
+    }
+
+    names.sort_unstable();
+
+    Some(md5_hex(names.join(",").as_bytes()))
+}
+
+fn parse(parsed: &Elf, elf: &mut ELF) {
+    elf.set_is_elf(true);
+    elf.type_ = Some(EnumOrUnknown::from_i32(parsed.type_.into()));
+    elf.machine = Some(EnumOrUnknown::from_i32(parsed.machine.into()));
+    elf.set_is_64bit(parsed.is_64bit);
+    elf.set_is_big_endian(parsed.is_big_endian);
+    elf.set_os_abi(parsed.os_abi.into());
+    elf.set_flags(parsed.flags.into());
+    elf.set_entry_point_raw(parsed.entry_point as i64);
+    elf.entry_point = parsed.entry_point_offset().map(|offset| offset as i64);
+    elf.set_sh_offset(parsed.sh_offset as i64);
+    elf.set_sh_entry_size(parsed.sh_entry_size.into());
+    elf.set_ph_offset(parsed.ph_offset as i64);
+    elf.set_ph_entry_size(parsed.ph_entry_size.into());
+
+    for s in &parsed.sections {
+        let mut section = ElfSection::new();
+        section.set_name(s.name.to_vec());
+        section.set_type(s.type_.into());
+        section.set_flags(s.flags as i64);
+        section.set_address(s.address as i64);
+        section.set_offset(s.offset as i64);
+        section.set_size(s.size as i64);
+        section.set_link(s.link.into());
+        section.set_info(s.info.into());
+        section.set_alignment(s.alignment as i64);
+        section.set_entry_size(s.entry_size as i64);
+        section.entropy = parsed
+            .section_data(s)
+            .filter(|data| !data.is_empty())
+            .map(entropy);
+        elf.sections.push(section);
+    }
+
+    for s in &parsed.segments {
+        let mut segment = ElfSegment::new();
+        segment.set_type(s.type_.into());
+        segment.set_flags(s.flags.into());
+        segment.set_offset(s.offset as i64);
+        segment.set_virtual_address(s.virtual_address as i64);
+        segment.set_physical_address(s.physical_address as i64);
+        segment.set_file_size(s.file_size as i64);
+        segment.set_memory_size(s.memory_size as i64);
+        segment.set_alignment(s.alignment as i64);
+        segment.entropy = parsed
+            .data_at(s.offset, s.file_size)
+            .filter(|data| !data.is_empty())
+            .map(entropy);
+        elf.segments.push(segment);
+    }
+
+    elf.set_number_of_sections(elf.sections.len() as i64);
+    elf.set_number_of_segments(elf.segments.len() as i64);
+    elf.set_anomalies(anomalies::anomalies(parsed).into());
+
+    if let Some(symbols) = parsed.symbols(SHT_SYMTAB) {
+        elf.symtab = symbols.iter().map(symbol).collect();
+        elf.set_symtab_entries(elf.symtab.len() as i64);
+    }
+
+    elf.set_is_stripped(
+        !parsed.sections.iter().any(|s| s.type_ == SHT_SYMTAB),
+    );
+
+    let verneed = versions::verneed(parsed);
+    let version_names = versions::names(parsed, &verneed);
+    let versym = versions::versym(parsed);
+
+    if let Some(symbols) = parsed.symbols(SHT_DYNSYM) {
+        elf.dynsym = symbols.iter().map(symbol).collect();
+        elf.set_dynsym_entries(elf.dynsym.len() as i64);
+
+        // The version indexes include the null symbol, which is not in
+        // `dynsym`.
+        for (symbol, index) in elf.dynsym.iter_mut().zip(versym.iter().skip(1))
+        {
+            let hidden = index & VERSYM_HIDDEN != 0;
+            let index = index & !VERSYM_HIDDEN;
+            symbol.set_version_index(index.into());
+            symbol.set_version_hidden(hidden);
+            symbol.version = version_names
+                .get(&index)
+                .map(|name| String::from_utf8_lossy(name).into_owned());
+        }
+    }
+
+    for v in &verneed {
+        let mut entry = ElfVerneed::new();
+        entry.set_file(String::from_utf8_lossy(v.file).into_owned());
+        entry.set_version(v.version.into());
+        for aux in &v.entries {
+            let mut vernaux = ElfVernaux::new();
+            vernaux.set_name(String::from_utf8_lossy(aux.name).into_owned());
+            vernaux.set_hash(aux.hash.into());
+            vernaux.set_flags(aux.flags.into());
+            vernaux.set_index(aux.index.into());
+            entry.entries.push(vernaux);
+        }
+        elf.verneed.push(entry);
+    }
+
+    for (type_, count) in relocations::counts(parsed) {
+        elf.relocation_counts.insert(type_.into(), count);
+    }
+
+    elf.set_number_of_relocations(elf.relocation_counts.values().sum());
+
+    let entries = dynamic::entries(parsed);
+    let strings = dynamic::strings(parsed, &entries);
+
+    for entry in &entries {
+        let mut dynamic = ElfDynamic::new();
+        dynamic.set_type(entry.tag as i64);
+        dynamic.set_value(entry.value as i64);
+        elf.dynamic.push(dynamic);
+
+        let string = || dynamic::string(strings, entry.value);
+
+        match entry.tag {
+            DT_NEEDED => elf.needed.extend(string()),
+            DT_SONAME => elf.soname = string(),
+            DT_RPATH => elf.rpath = string(),
+            DT_RUNPATH => elf.runpath = string(),
+            _ => {}
+        }
+    }
+
+    elf.set_dynamic_section_entries(elf.dynamic.len() as i64);
+
+    let notes = notes::notes(parsed);
+
+    for n in &notes {
+        let mut note = ElfNote::new();
+        note.set_name(String::from_utf8_lossy(n.name).into_owned());
+        note.set_type(n.type_.into());
+        note.set_offset(n.offset as i64);
+        note.set_size(n.desc.len() as i64);
+        elf.notes.push(note);
+
+        if n.name != b"GNU" {
+            continue;
+        }
+
+        match n.type_ {
+            NT_GNU_BUILD_ID if !n.desc.is_empty() => {
+                elf.build_id = Some(hex(n.desc));
+            }
+            NT_GNU_ABI_TAG => {
+                if let (Some(os), Some(major), Some(minor), Some(patch)) = (
+                    parsed.u32_at(n.desc, 0),
+                    parsed.u32_at(n.desc, 4),
+                    parsed.u32_at(n.desc, 8),
+                    parsed.u32_at(n.desc, 12),
+                ) {
+                    let mut abi_tag = ElfAbiTag::new();
+                    abi_tag.set_os(os.into());
+                    abi_tag.set_major(major.into());
+                    abi_tag.set_minor(minor.into());
+                    abi_tag.set_patch(patch.into());
+                    elf.abi_tag = MessageField::some(abi_tag);
+                }
+            }
+            NT_GNU_PROPERTY_TYPE_0 if parsed.machine == EM_AARCH64 => {
+                elf.aarch64_features =
+                    arm::aarch64_features(parsed, n.desc).map(i64::from);
+            }
+            _ => {}
+        }
+    }
+
+    let comments = toolchain::comments(parsed);
+
+    elf.go_build_id = toolchain::go_build_id(&notes)
+        .map(|id| String::from_utf8_lossy(id).into_owned());
+
+    elf.toolchain = toolchain::toolchain(
+        parsed,
+        &notes,
+        &comments,
+        elf.symtab.iter().chain(&elf.dynsym).map(|s| s.name()),
+    )
+    .map(String::from);
+
+    elf.comments = comments
+        .iter()
+        .map(|c| String::from_utf8_lossy(c).into_owned())
+        .collect();
+
+    let arm_attributes = parsed
+        .sections
+        .iter()
+        .find(|s| s.name == b".ARM.attributes")
+        .and_then(|s| parsed.section_data(s))
+        .filter(|_| parsed.machine == EM_ARM);
+
+    if let Some(data) = arm_attributes {
+        elf.arm_attributes =
+            MessageField::some(arm_info(&arm::attributes(parsed, data)));
+    }
+
+    if parsed.type_ == ET_CORE {
+        elf.core =
+            MessageField::some(core_info(&core_dump::parse(parsed, &notes)));
+    }
+}
+
+fn arm_info(attributes: &[(u64, arm::Value)]) -> ElfArmAttributes {
+    let mut info = ElfArmAttributes::new();
+
+    for (tag, value) in attributes {
+        match value {
+            arm::Value::Integer(value) => {
+                info.integer_values.insert(*tag as i64, *value as i64);
+            }
+            arm::Value::String(value) => {
+                info.string_values.insert(
+                    *tag as i64,
+                    String::from_utf8_lossy(value).into_owned(),
+                );
+            }
+        }
+    }
+
+    info.cpu_name =
+        info.string_values.get(&(arm::TAG_CPU_NAME as i64)).cloned();
+    info.cpu_arch =
+        info.integer_values.get(&(arm::TAG_CPU_ARCH as i64)).copied();
+    info.cpu_arch_profile = info
+        .integer_values
+        .get(&(arm::TAG_CPU_ARCH_PROFILE as i64))
+        .and_then(|profile| char::from_u32(*profile as u32))
+        .filter(|profile| *profile != '\0')
+        .map(String::from);
+
+    info
+}
+
+fn core_info(core: &core_dump::Core) -> ElfCore {
+    let string = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+
+    let mut info = ElfCore::new();
+    info.process_name = core.process_name.map(string);
+    info.arguments = core.arguments.map(string);
+    info.pid = core.pid.map(i64::from);
+    info.ppid = core.ppid.map(i64::from);
+    info.signal = core.threads.first().map(|t| t.signal.into());
+
+    for t in &core.threads {
+        let mut thread = ElfCoreThread::new();
+        thread.set_pid(t.pid.into());
+        thread.set_signal(t.signal.into());
+        info.threads.push(thread);
+    }
+
+    for f in &core.files {
+        let mut file = ElfMappedFile::new();
+        file.set_start(f.start as i64);
+        file.set_end(f.end as i64);
+        file.set_offset(f.offset as i64);
+        file.set_name(string(f.name));
+        info.mapped_files.push(file);
+    }
+
+    info.set_number_of_threads(info.threads.len() as i64);
+    info.set_number_of_mapped_files(info.mapped_files.len() as i64);
+    info
+}
+
+fn symbol(s: &Symbol) -> ElfSymbol {
+    let mut symbol = ElfSymbol::new();
+    symbol.set_name(s.name.to_vec());
+    symbol.set_value(s.value as i64);
+    symbol.set_size(s.size as i64);
+    symbol.set_type(s.type_.into());
+    symbol.set_bind(s.bind.into());
+    symbol.set_visibility(s.visibility.into());
+    symbol.set_shndx(s.shndx.into());
+    symbol
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::utils::digest::md5_hex;
+    use crate::modules::utils::tlsh;
+
+    /// Virtual address where the test files are loaded.
+    const BASE: u64 = 0x400000;
+
+    /// A section in the files built by [`build`].
+    struct TestSection {
+        name: &'static str,
+        type_: u32,
+        flags: u64,
+        data: Vec<u8>,
+        link: u32,
+        entry_size: u64,
+    }
+
+    impl TestSection {
+        fn new(
+            name: &'static str,
+            type_: u32,
+            flags: u64,
+            data: Vec<u8>,
+        ) -> Self {
+            Self { name, type_, flags, data, link: 0, entry_size: 0 }
+        }
+    }
+
+    /// Builds a little-endian 64-bit executable with the given sections,
+    /// which are preceded by the null section and followed by the section
+    /// header string table. Sections with the SHF_ALLOC flag are mapped at
+    /// `BASE` plus their offset, and the entry point is the start of the
+    /// .text section, if any.
+    ///
+    /// The program header table has a PT_LOAD segment that covers the
+    /// whole file, followed by a segment for each `(type, flags, index)`
+    /// tuple in `segments`, which covers the section with the given index.
+    /// The data of the first section starts right after the program header
+    /// table.
+    fn build(
+        sections: &[TestSection],
+        segments: &[(u32, u32, usize)],
+    ) -> Vec<u8> {
+        let address = |s: &TestSection, offset: usize| {
+            if s.flags & 2 != 0 {
+                BASE + offset as u64
+            } else {
+                0
+            }
+        };
+
+        let mut data = vec![0; 64 + 56 * (segments.len() + 1)];
+        let mut shstrtab = vec![0];
+        let mut layout = Vec::new();
+
+        for s in sections {
+            data.resize((data.len() + 7) & !7, 0);
+            layout.push((shstrtab.len() as u32, data.len()));
+            shstrtab.extend(s.name.as_bytes());
+            shstrtab.push(0);
+            data.extend(&s.data);
+        }
+
+        let shstrtab_name = shstrtab.len() as u32;
+        let shstrtab_offset = data.len();
+        shstrtab.extend(b".shstrtab\0");
+        data.extend(&shstrtab);
+        data.resize((data.len() + 7) & !7, 0);
+
+        let sh_offset = data.len();
+        let size = sh_offset + 64 * (sections.len() + 2);
+
+        let entry_point = sections
+            .iter()
+            .zip(&layout)
+            .find(|(s, _)| s.name == ".text")
+            .map_or(0, |(s, (_, offset))| address(s, *offset));
+
+        let mut section_header =
+            |name: u32, s: &TestSection, address: u64, offset: usize| {
+                data.extend(name.to_le_bytes());
+                data.extend(s.type_.to_le_bytes());
+                data.extend(s.flags.to_le_bytes());
+                data.extend(address.to_le_bytes());
+                data.extend((offset as u64).to_le_bytes());
+                data.extend((s.data.len() as u64).to_le_bytes());
+                data.extend(s.link.to_le_bytes());
+                data.extend(0_u32.to_le_bytes());
+                data.extend(8_u64.to_le_bytes());
+                data.extend(s.entry_size.to_le_bytes());
+            };
+
+        section_header(0, &TestSection::new("", 0, 0, vec![]), 0, 0);
+
+        for (s, (name, offset)) in sections.iter().zip(&layout) {
+            section_header(*name, s, address(s, *offset), *offset);
+        }
+
+        section_header(
+            shstrtab_name,
+            &TestSection::new(".shstrtab", 3, 0, shstrtab),
+            0,
+            shstrtab_offset,
+        );
+
+        let mut header = Vec::new();
+        header.extend(b"\x7fELF\x02\x01\x01\x00");
+        header.extend([0; 8]);
+        header.extend(2_u16.to_le_bytes());
+        header.extend(62_u16.to_le_bytes());
+        header.extend(1_u32.to_le_bytes());
+        header.extend(entry_point.to_le_bytes());
+        header.extend(64_u64.to_le_bytes());
+        header.extend((sh_offset as u64).to_le_bytes());
+        header.extend(0_u32.to_le_bytes());
+        header.extend(64_u16.to_le_bytes());
+        header.extend(56_u16.to_le_bytes());
+        header.extend((segments.len() as u16 + 1).to_le_bytes());
+        header.extend(64_u16.to_le_bytes());
+        header.extend((sections.len() as u16 + 2).to_le_bytes());
+        header.extend((sections.len() as u16 + 1).to_le_bytes());
+
+        let segment = |type_: u32, flags: u32, offset: usize, size: usize| {
+            let alignment: u64 = match type_ {
+                1 => 0x1000,
+                4 => 4,
+                _ => 8,
+            };
+            let mut segment = Vec::new();
+            segment.extend(type_.to_le_bytes());
+            segment.extend(flags.to_le_bytes());
+            segment.extend((offset as u64).to_le_bytes());
+            segment.extend((BASE + offset as u64).to_le_bytes());
+            segment.extend((BASE + offset as u64).to_le_bytes());
+            segment.extend((size as u64).to_le_bytes());
+            segment.extend((size as u64).to_le_bytes());
+            segment.extend(alignment.to_le_bytes());
+            segment
+        };
+
+        header.extend(segment(1, 5, 0, size));
+
+        for (type_, flags, index) in segments {
+            let (_, offset) = layout[index - 1];
+            header.extend(segment(
+                *type_,
+                *flags,
+                offset,
+                sections[index - 1].data.len(),
+            ));
+        }
+
+        data[..header.len()].copy_from_slice(&header);
+        data
+    }
+
+    /// Returns the content of a dynamic symbol table with the given
+    /// symbols, and of the string table with their names. Each symbol is a
+    /// tuple with the name, the `st_info` field and the section index.
+    fn dynsym(symbols: &[(&str, u8, u16)]) -> (Vec<u8>, Vec<u8>) {
+        let mut dynstr = vec![0];
+        let mut dynsym = vec![0; 24];
+        for (name, info, shndx) in symbols {
+            dynsym.extend((dynstr.len() as u32).to_le_bytes());
+            dynsym.push(*info);
+            dynsym.push(0);
+            dynsym.extend(shndx.to_le_bytes());
+            dynsym.extend([0; 16]);
+            dynstr.extend(name.as_bytes());
+            dynstr.push(0);
+        }
+        (dynsym, dynstr)
+    }
+
+    /// Builds an executable with a .text section and a dynamic symbol
+    /// table with the given symbols, as accepted by [`dynsym`].
+    fn elf(symbols: &[(&str, u8, u16)]) -> Vec<u8> {
+        let (dynsym, dynstr) = self::dynsym(symbols);
+
+        let mut dynsym = TestSection::new(".dynsym", 11, 2, dynsym);
+        dynsym.link = 3;
+        dynsym.entry_size = 24;
+
+        build(
+            &[
+                TestSection::new(".text", 1, 6, vec![0xc3, 0x90, 0x90, 0x90]),
+                dynsym,
+                TestSection::new(".dynstr", 3, 2, dynstr),
+            ],
+            &[],
+        )
+    }
+
+    /// Returns the symbols used in the tests, as accepted by [`elf`].
+    fn symbols() -> Vec<(&'static str, u8, u16)> {
+        // st_info values: global functions, weak functions and global
+        // objects.
+        const FUNC: u8 = 0x12;
+        const WEAK: u8 = 0x22;
+        const OBJECT: u8 = 0x11;
+
+        vec![
+            ("connect", FUNC, 0),
+            ("Socket", FUNC, 0),
+            ("getaddrinfo", FUNC, 0),
+            ("inet_pton", FUNC, 0),
+            ("sendto", FUNC, 0),
+            ("recvfrom", FUNC, 0),
+            ("setsockopt", FUNC, 0),
+            // Excluded from telfhash.
+            ("__libc_start_main", FUNC, 0),
+            ("memcpy", FUNC, 0),
+            ("strlen", FUNC, 0),
+            ("lseek64", FUNC, 0),
+            ("puts", FUNC, 0),
+            // Not global functions.
+            ("__gmon_start__", WEAK, 0),
+            ("stdout", OBJECT, 0),
+            // Exported.
+            ("run_payload", FUNC, 1),
+            ("config", OBJECT, 1),
+        ]
+    }
+
+    #[test]
+    fn headers() {
+        let data = elf(&symbols());
+
+        let rules = crate::compile(
+            r#"import "elf"
+            rule headers {
+              condition:
+                elf.is_elf and
+                elf.is_64bit and
+                not elf.is_big_endian and
+                elf.type == elf.Type.ET_EXEC and
+                elf.machine == elf.Machine.EM_X86_64 and
+                elf.entry_point == 0x78 and
+                elf.entry_point_raw == 0x400078 and
+                elf.number_of_segments == 1 and
+                elf.segments[0].type == elf.SegmentType.PT_LOAD and
+                elf.segments[0].flags ==
+                  elf.SegmentFlags.PF_R | elf.SegmentFlags.PF_X and
+                elf.number_of_sections == 5 and
+                elf.sections[1].name == ".text" and
+                elf.sections[2].name == ".dynsym" and
+                elf.sections[2].type == elf.SectionType.SHT_DYNSYM and
+                elf.sections[4].name == ".shstrtab" and
+                elf.dynsym_entries == 16 and
+                elf.dynsym[0].name == "connect" and
+                elf.dynsym[0].type == elf.SymbolType.STT_FUNC and
+                elf.dynsym[0].bind == elf.SymbolBind.STB_GLOBAL and
+                elf.dynsym[14].shndx == 1 and
+                not defined elf.symtab_entries
+            }
+            rule not_elf {
+              condition:
+                not elf.is_elf and not defined elf.telfhash()
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["headers"]);
+
+        let results = scanner.scan(b"MZ").unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["not_elf"]);
+    }
+
+    #[test]
+    fn symbol_hashes() {
+        let data = elf(&symbols());
+
+        let telfhash = tlsh::digest(
+            b"connect,getaddrinfo,inet_pton,recvfrom,run_payload,sendto,\
+              setsockopt,socket",
+        )
+        .unwrap();
+
+        let import_md5 = md5_hex(
+            b"__gmon_start__,__libc_start_main,connect,getaddrinfo,\
+              inet_pton,lseek64,memcpy,puts,recvfrom,sendto,setsockopt,\
+              socket,stdout,strlen",
+        );
+
+        let export_md5 = md5_hex(b"config,run_payload");
+
+        let source = format!(
+            r#"import "elf"
+            rule hashes {{
+              condition:
+                elf.telfhash() == "{telfhash}" and
+                elf.import_md5() == "{import_md5}" and
+                elf.export_md5() == "{export_md5}"
+            }}
+            "#,
+        );
+
+        let rules = crate::compile(source.as_str()).unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+        let results = scanner.scan(&data).unwrap();
+        assert_eq!(results.matching_rules().len(), 1);
+
+        // Without symbols there's nothing to compute the hashes from.
+        let rules = crate::compile(
+            r#"import "elf"
+            rule no_hashes {
+              condition:
+                elf.is_elf and
+                not defined elf.telfhash() and
+                not defined elf.import_md5() and
+                not defined elf.export_md5()
+            }
+            "#,
+        )
+        .unwrap();
+
+        let data = elf(&[]);
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+        let results = scanner.scan(&data).unwrap();
+        assert_eq!(results.matching_rules().len(), 1);
+    }
+
+    #[test]
+    fn dynamic_and_notes() {
+        let mut dynstr = vec![0];
+        let mut string = |s: &str| {
+            let offset = dynstr.len() as u64;
+            dynstr.extend(s.as_bytes());
+            dynstr.push(0);
+            offset
+        };
+
+        let libc = string("libc.so.6");
+        let libcrypto = string("libcrypto.so.3");
+        let soname = string("libevil.so");
+        let runpath = string("$ORIGIN/../lib");
+
+        let mut build_id = b"\x04\0\0\0\x14\0\0\0\x03\0\0\0GNU\0".to_vec();
+        build_id.extend(0..20);
+
+        let mut abi_tag = b"\x04\0\0\0\x10\0\0\0\x01\0\0\0GNU\0".to_vec();
+        for field in [0_u32, 3, 2, 0] {
+            abi_tag.extend(field.to_le_bytes());
+        }
+
+        let rules = crate::compile(
+            r#"import "elf"
+            rule dynamic {
+              condition:
+                elf.dynamic_section_entries == 6 and
+                elf.dynamic[0].type == elf.DynamicType.DT_NEEDED and
+                elf.dynamic[5].type == elf.DynamicType.DT_STRSZ and
+                elf.needed[0] == "libc.so.6" and
+                elf.needed[1] == "libcrypto.so.3" and
+                not defined elf.needed[2] and
+                elf.soname == "libevil.so" and
+                elf.runpath == "$ORIGIN/../lib" and
+                not defined elf.rpath
+            }
+            rule notes {
+              condition:
+                elf.build_id == "000102030405060708090a0b0c0d0e0f10111213" and
+                elf.abi_tag.os == elf.AbiTagOs.ELF_NOTE_OS_LINUX and
+                elf.abi_tag.major == 3 and
+                elf.abi_tag.minor == 2 and
+                elf.abi_tag.patch == 0 and
+                elf.notes[0].name == "GNU" and
+                elf.notes[0].type == elf.GnuNoteType.NT_GNU_BUILD_ID and
+                elf.notes[0].size == 20 and
+                uint8(elf.notes[0].offset + 19) == 0x13 and
+                elf.notes[1].type == elf.GnuNoteType.NT_GNU_ABI_TAG
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        // The string table is found with DT_STRTAB, which is the address of
+        // the first section, or with the section linked to the dynamic
+        // section when DT_STRTAB is not a valid address.
+        for strtab in [BASE + 0x120, 0x9000000] {
+            let mut dynamic = Vec::new();
+            for (tag, value) in [
+                (1_u64, libc),
+                (1, libcrypto),
+                (14, soname),
+                (29, runpath),
+                (5, strtab),
+                (10, dynstr.len() as u64),
+                (0, 0),
+                // Entries after DT_NULL are ignored.
+                (1, libc),
+            ] {
+                dynamic.extend(tag.to_le_bytes());
+                dynamic.extend(value.to_le_bytes());
+            }
+
+            let mut dynamic = TestSection::new(".dynamic", 6, 3, dynamic);
+            dynamic.link = 1;
+            dynamic.entry_size = 16;
+
+            let data = build(
+                &[
+                    TestSection::new(".dynstr", 3, 2, dynstr.clone()),
+                    dynamic,
+                    TestSection::new(
+                        ".note.gnu.build-id",
+                        7,
+                        2,
+                        build_id.clone(),
+                    ),
+                    TestSection::new(".note.ABI-tag", 7, 2, abi_tag.clone()),
+                ],
+                &[(2, 6, 2), (4, 4, 3), (4, 4, 4)],
+            );
+
+            let results = scanner.scan(&data).unwrap();
+            assert_eq!(results.matching_rules().len(), 2);
+        }
+    }
+
+    #[test]
+    fn versions_and_relocations() {
+        let (dynsym, mut dynstr) =
+            dynsym(&[("printf", 0x12, 0), ("my_export", 0x12, 1)]);
+
+        let mut string = |s: &str| {
+            let offset = dynstr.len() as u32;
+            dynstr.extend(s.as_bytes());
+            dynstr.push(0);
+            offset
+        };
+
+        let libc = string("libc.so.6");
+        let glibc = string("GLIBC_2.2.5");
+        let mylib = string("MYLIB_1.0");
+
+        // printf has the version required from libc, and my_export a
+        // hidden version defined by the file.
+        let mut versym = Vec::new();
+        for index in [0_u16, 2, 0x8003] {
+            versym.extend(index.to_le_bytes());
+        }
+
+        let mut verneed = Vec::new();
+        verneed.extend(1_u16.to_le_bytes());
+        verneed.extend(1_u16.to_le_bytes());
+        verneed.extend(libc.to_le_bytes());
+        verneed.extend(16_u32.to_le_bytes());
+        verneed.extend(0_u32.to_le_bytes());
+        verneed.extend(0x09691a75_u32.to_le_bytes());
+        verneed.extend(0_u16.to_le_bytes());
+        verneed.extend(2_u16.to_le_bytes());
+        verneed.extend(glibc.to_le_bytes());
+        verneed.extend(0_u32.to_le_bytes());
+
+        let mut verdef = Vec::new();
+        for field in [1_u16, 0, 3, 1] {
+            verdef.extend(field.to_le_bytes());
+        }
+        for field in [0_u32, 20, 0, mylib, 0] {
+            verdef.extend(field.to_le_bytes());
+        }
+
+        let relocations = |types: &[u64]| {
+            let mut data = Vec::new();
+            for type_ in types {
+                data.extend(0_u64.to_le_bytes());
+                data.extend(type_.to_le_bytes());
+                data.extend(0_u64.to_le_bytes());
+            }
+            data
+        };
+
+        let mut sections = vec![
+            TestSection::new(".dynsym", 11, 2, dynsym),
+            TestSection::new(".dynstr", 3, 2, dynstr),
+            TestSection::new(".gnu.version", 0x6fffffff, 2, versym),
+            TestSection::new(".gnu.version_r", 0x6ffffffe, 2, verneed),
+            TestSection::new(".gnu.version_d", 0x6ffffffd, 2, verdef),
+            TestSection::new(".rela.dyn", 4, 2, relocations(&[8, 8, 37])),
+            TestSection::new(".rela.plt", 4, 2, relocations(&[1 << 32 | 7])),
+        ];
+
+        sections[0].link = 2;
+        sections[2].link = 1;
+        sections[3].link = 2;
+        sections[4].link = 2;
+
+        let rules = crate::compile(
+            r#"import "elf"
+            rule versions {
+              condition:
+                elf.dynsym[0].name == "printf" and
+                elf.dynsym[0].version == "GLIBC_2.2.5" and
+                elf.dynsym[0].version_index == 2 and
+                not elf.dynsym[0].version_hidden and
+                elf.dynsym[1].version == "MYLIB_1.0" and
+                elf.dynsym[1].version_index == 3 and
+                elf.dynsym[1].version_hidden and
+                elf.verneed[0].file == "libc.so.6" and
+                elf.verneed[0].version == 1 and
+                elf.verneed[0].entries[0].name == "GLIBC_2.2.5" and
+                elf.verneed[0].entries[0].hash == 0x09691a75 and
+                elf.verneed[0].entries[0].index == 2 and
+                not defined elf.verneed[0].entries[1].name
+            }
+            rule relocations {
+              condition:
+                elf.number_of_relocations == 4 and
+                elf.relocation_counts[
+                  elf.X86_64RelocationType.R_X86_64_RELATIVE] == 2 and
+                elf.relocation_counts[
+                  elf.X86_64RelocationType.R_X86_64_IRELATIVE] == 1 and
+                elf.relocation_counts[
+                  elf.X86_64RelocationType.R_X86_64_JUMP_SLOT] == 1 and
+                not defined elf.relocation_counts[
+                  elf.X86_64RelocationType.R_X86_64_COPY]
+            }
+            "#,
+        )
+        .unwrap();
+
+        let data = build(&sections, &[]);
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+        let results = scanner.scan(&data).unwrap();
+        assert_eq!(results.matching_rules().len(), 2);
+    }
+
+    #[test]
+    fn entropy_and_anomalies() {
+        let rules = crate::compile(
+            r#"import "elf"
+            rule entropy {
+              condition:
+                elf.sections[1].entropy == 8.0 and
+                elf.sections[2].entropy == 0.0 and
+                not defined elf.sections[3].entropy and
+                elf.segments[0].entropy > 0.0 and
+                not elf.is_stripped and
+                elf.anomalies == elf.Anomaly.FEW_SEGMENTS
+            }
+            rule packed {
+              condition:
+                elf.is_stripped and
+                elf.number_of_sections == 0 and
+                elf.anomalies ==
+                  elf.Anomaly.NO_SECTION_HEADERS |
+                  elf.Anomaly.FEW_SEGMENTS |
+                  elf.Anomaly.OVERLAPPING_SEGMENTS |
+                  elf.Anomaly.WRITABLE_EXECUTABLE_SEGMENT |
+                  elf.Anomaly.EXPANDING_EXECUTABLE_SEGMENT |
+                  elf.Anomaly.UPX_INFO
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let data = build(
+            &[
+                TestSection::new(".data", 1, 3, (0..=255).collect()),
+                TestSection::new(".symtab", 2, 0, vec![0; 24]),
+                TestSection::new(".bss", 8, 3, vec![]),
+            ],
+            &[],
+        );
+
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["entropy"]);
+
+        // A file like the ones produced by UPX, without section headers
+        // and with a writable and executable segment that is larger in
+        // memory than in the file, followed by the UPX information.
+        let mut data = build(
+            &[TestSection::new(".upx", 1, 2, b"\0\0\0\0UPX!".to_vec())],
+            &[(1, 4, 1)],
+        );
+
+        data[40..48].copy_from_slice(&0_u64.to_le_bytes());
+        data[60..64].copy_from_slice(&[0; 4]);
+        data[68..72].copy_from_slice(&7_u32.to_le_bytes());
+        data[104..112].copy_from_slice(&0x10000_u64.to_le_bytes());
+
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["packed"]);
+    }
+
+    /// Returns a note with the given name, type and descriptor.
+    fn note(name: &str, type_: u32, desc: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        note.extend((name.len() as u32 + 1).to_le_bytes());
+        note.extend((desc.len() as u32).to_le_bytes());
+        note.extend(type_.to_le_bytes());
+        note.extend(name.as_bytes());
+        note.resize((note.len() + 4) & !3, 0);
+        note.extend(desc);
+        note.resize((note.len() + 3) & !3, 0);
+        note
+    }
+
+    #[test]
+    fn core_dump() {
+        let mut prpsinfo = vec![0; 136];
+        prpsinfo[24..28].copy_from_slice(&1234_u32.to_le_bytes());
+        prpsinfo[28..32].copy_from_slice(&1_u32.to_le_bytes());
+        prpsinfo[40..44].copy_from_slice(b"evil");
+        prpsinfo[56..75].copy_from_slice(b"./evil -c 10.0.0.1 ");
+
+        let prstatus = |pid: u32, signal: u16| {
+            let mut prstatus = vec![0; 112];
+            prstatus[12..14].copy_from_slice(&signal.to_le_bytes());
+            prstatus[32..36].copy_from_slice(&pid.to_le_bytes());
+            prstatus
+        };
+
+        let mut files = Vec::new();
+        // Number of files, page size, and the address range and offset in
+        // pages of each file.
+        for value in [
+            2_u64,
+            0x1000,
+            0x400000,
+            0x401000,
+            0,
+            0x7f0000000000,
+            0x7f0000021000,
+            2,
+        ] {
+            files.extend(value.to_le_bytes());
+        }
+        files.extend(b"/usr/bin/evil\0/lib/libc.so.6\0");
+
+        let mut notes = note("CORE", 1, &prstatus(1234, 11));
+        notes.extend(note("CORE", 3, &prpsinfo));
+        notes.extend(note("CORE", 1, &prstatus(1235, 0)));
+        notes.extend(note("CORE", 0x46494c45, &files));
+
+        let mut data =
+            build(&[TestSection::new(".note", 7, 0, notes)], &[(4, 4, 1)]);
+
+        let rules = crate::compile(
+            r#"import "elf"
+            rule core {
+              condition:
+                elf.type == elf.Type.ET_CORE and
+                elf.notes[1].type == elf.CoreNoteType.NT_PRPSINFO and
+                elf.core.process_name == "evil" and
+                elf.core.arguments == "./evil -c 10.0.0.1" and
+                elf.core.pid == 1234 and
+                elf.core.ppid == 1 and
+                elf.core.signal == 11 and
+                elf.core.number_of_threads == 2 and
+                elf.core.threads[1].pid == 1235 and
+                elf.core.threads[1].signal == 0 and
+                elf.core.number_of_mapped_files == 2 and
+                elf.core.mapped_files[0].name == "/usr/bin/evil" and
+                elf.core.mapped_files[0].start == 0x400000 and
+                elf.core.mapped_files[1].name == "/lib/libc.so.6" and
+                elf.core.mapped_files[1].end == 0x7f0000021000 and
+                elf.core.mapped_files[1].offset == 0x2000
+            }
+            rule not_core {
+              condition:
+                not defined elf.core.pid
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        // The same notes are ignored in files that are not core files.
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["not_core"]);
+
+        data[16..18].copy_from_slice(&4_u16.to_le_bytes());
+
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["core"]);
+    }
+
+    #[test]
+    fn toolchain() {
+        let rules = crate::compile(
+            r#"import "elf"
+            rule go {
+              condition:
+                elf.toolchain == "go" and
+                elf.go_build_id == "Yk3l/2vHh/aB-c/xyz"
+            }
+            rule rust {
+              condition:
+                elf.toolchain == "rust" and not defined elf.go_build_id
+            }
+            rule clang {
+              condition:
+                elf.toolchain == "clang" and
+                elf.comments[0] == "GCC: (GNU) 13.2.1" and
+                elf.comments[1] == "clang version 17.0.6"
+            }
+            rule gcc {
+              condition:
+                elf.toolchain == "gcc"
+            }
+            rule unknown {
+              condition:
+                not defined elf.toolchain
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |sections: &[TestSection]| {
+            let data = build(sections, &[]);
+            scanner
+                .scan(&data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let go = note("Go", 4, b"Yk3l/2vHh/aB-c/xyz");
+        let comment = |comment: &[u8]| {
+            TestSection::new(".comment", 1, 0, comment.to_vec())
+        };
+
+        assert_eq!(
+            matching_rules(&[
+                TestSection::new(".note.go.buildid", 7, 2, go),
+                comment(b"GCC: (GNU) 13.2.1\0"),
+            ]),
+            ["go"]
+        );
+
+        assert_eq!(
+            matching_rules(&[
+                TestSection::new(
+                    ".rodata",
+                    1,
+                    2,
+                    b"/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library\
+                      /core/src/panicking.rs"
+                        .to_vec(),
+                ),
+                comment(b"GCC: (GNU) 13.2.1\0"),
+            ]),
+            ["rust"]
+        );
+
+        assert_eq!(
+            matching_rules(&[comment(
+                b"GCC: (GNU) 13.2.1\0clang version 17.0.6\0"
+            )]),
+            ["clang"]
+        );
+
+        assert_eq!(
+            matching_rules(&[comment(b"GCC: (Ubuntu 11.4.0) 11.4.0\0")]),
+            ["gcc"]
+        );
+
+        assert_eq!(matching_rules(&[]), ["unknown"]);
+    }
+
+    #[test]
+    fn arm() {
+        let mut attributes = vec![5];
+        attributes.extend(b"cortex-a9\0");
+        attributes.extend([6, 10, 7, b'A', 34, 1, 67]);
+        attributes.extend(b"2.09\0");
+
+        let mut subsection = b"aeabi\0\x01".to_vec();
+        subsection.extend((attributes.len() as u32 + 5).to_le_bytes());
+        subsection.extend(attributes);
+
+        let mut section = b"A".to_vec();
+        section.extend((subsection.len() as u32 + 4).to_le_bytes());
+        section.extend(subsection);
+
+        let rules = crate::compile(
+            r#"import "elf"
+            rule arm {
+              condition:
+                elf.machine == elf.Machine.EM_ARM and
+                elf.arm_attributes.cpu_name == "cortex-a9" and
+                elf.arm_attributes.cpu_arch == 10 and
+                elf.arm_attributes.cpu_arch_profile == "A" and
+                elf.arm_attributes.integer_values[
+                  elf.ArmAttributeTag.TAG_CPU_UNALIGNED_ACCESS] == 1 and
+                elf.arm_attributes.string_values[67] == "2.09"
+            }
+            rule aarch64 {
+              condition:
+                elf.machine == elf.Machine.EM_AARCH64 and
+                elf.aarch64_features ==
+                  elf.Aarch64Feature.AARCH64_FEATURE_BTI |
+                  elf.Aarch64Feature.AARCH64_FEATURE_PAC
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut data = build(
+            &[TestSection::new(".ARM.attributes", 0x70000003, 0, section)],
+            &[],
+        );
+        data[18..20].copy_from_slice(&40_u16.to_le_bytes());
+
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["arm"]);
+
+        // A GNU property with the BTI and PAC features, padded to 8 bytes.
+        let mut property = 0xc0000000_u32.to_le_bytes().to_vec();
+        property.extend(4_u32.to_le_bytes());
+        property.extend(3_u32.to_le_bytes());
+        property.extend([0; 4]);
+
+        let mut data = build(
+            &[TestSection::new(
+                ".note.gnu.property",
+                7,
+                2,
+                note("GNU", 5, &property),
+            )],
+            &[(4, 4, 1)],
+        );
+        data[18..20].copy_from_slice(&183_u16.to_le_bytes());
+
+        let results = scanner.scan(&data).unwrap();
+        let matching: Vec<_> = results
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(matching, ["aarch64"]);
+    }
+}
@@ -0,0 +1,102 @@
+/*! Parsing of ELF notes.
+
+Notes are records with a name that identifies their owner, like `GNU`, a
+type whose meaning depends on the owner, and a descriptor with arbitrary
+data. They are stored in `PT_NOTE` segments or, in relocatable files,
+which don't have segments, in `SHT_NOTE` sections.
+*/
+
+use super::parser::{Elf, PT_NOTE, SHT_NOTE};
+
+/// Maximum number of notes read from a file.
+const MAX_NOTES: usize = 4096;
+
+/// Types of the notes owned by `GNU`.
+pub(super) const NT_GNU_ABI_TAG: u32 = 1;
+pub(super) const NT_GNU_BUILD_ID: u32 = 3;
+pub(super) const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+
+/// A note.
+pub(super) struct Note<'a> {
+    /// The owner of the note, without the null terminator.
+    pub name: &'a [u8],
+    pub type_: u32,
+    /// Offset of the descriptor within the file.
+    pub offset: usize,
+    pub desc: &'a [u8],
+}
+
+/// Returns the notes in the file, as found in the `PT_NOTE` segments, or
+/// in the `SHT_NOTE` sections if there are no such segments.
+pub(super) fn notes<'a>(elf: &Elf<'a>) -> Vec<Note<'a>> {
+    let mut areas: Vec<(u64, u64, u64)> = elf
+        .segments
+        .iter()
+        .filter(|s| s.type_ == PT_NOTE)
+        .map(|s| (s.offset, s.file_size, s.alignment))
+        .collect();
+
+    if areas.is_empty() {
+        areas = elf
+            .sections
+            .iter()
+            .filter(|s| s.type_ == SHT_NOTE)
+            .map(|s| (s.offset, s.size, s.alignment))
+            .collect();
+    }
+
+    let mut notes = Vec::new();
+
+    for (offset, size, alignment) in areas {
+        if let Some(data) = elf.data_at(offset, size) {
+            parse(elf, data, offset as usize, alignment, &mut notes);
+        }
+    }
+
+    notes.truncate(MAX_NOTES);
+    notes
+}
+
+/// Parses the notes in `data`, which starts at the given offset within the
+/// file. Both the name and the descriptor are padded to 4 bytes, except in
+/// areas aligned to 8 bytes, where they are padded to 8.
+fn parse<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    offset: usize,
+    alignment: u64,
+    notes: &mut Vec<Note<'a>>,
+) -> Option<()> {
+    let align = |n: usize| {
+        if alignment == 8 {
+            n.checked_add(7).map(|n| n & !7)
+        } else {
+            n.checked_add(3).map(|n| n & !3)
+        }
+    };
+
+    let mut pos = 0;
+
+    while notes.len() < MAX_NOTES {
+        let name_size = elf.u32_at(data, pos)? as usize;
+        let desc_size = elf.u32_at(data, pos + 4)? as usize;
+        let type_ = elf.u32_at(data, pos + 8)?;
+
+        let name_start = pos + 12;
+        let name = data.get(name_start..name_start.checked_add(name_size)?)?;
+        let desc_start = align(name_start + name_size)?;
+        let desc_end = desc_start.checked_add(desc_size)?;
+        let desc = data.get(desc_start..desc_end)?;
+
+        notes.push(Note {
+            name: name.strip_suffix(b"\0").unwrap_or(name),
+            type_,
+            offset: offset + desc_start,
+            desc,
+        });
+
+        pos = align(desc_end)?;
+    }
+
+    Some(())
+}
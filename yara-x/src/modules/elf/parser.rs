@@ -0,0 +1,478 @@
+/*! Parser for ELF files.
+
+This reads the ELF header, the section header table and the program
+header table, of both 32-bit and 64-bit files, in either byte order.
+Sections and segments are exposed as they are, leaving the interpretation
+of their content to the rest of the module.
+
+See: https://refspecs.linuxfoundation.org/elf/gabi4+/contents.html
+*/
+
+/// Maximum number of sections and segments.
+const MAX_SECTIONS: usize = 32768;
+const MAX_SEGMENTS: usize = 32768;
+
+/// Maximum number of symbols read from a symbol table.
+const MAX_SYMBOLS: usize = 131072;
+
+/// Maximum length of the null-terminated strings in string tables.
+const MAX_STRING_LEN: usize = 1024;
+
+/// File types.
+pub(super) const ET_EXEC: u16 = 2;
+pub(super) const ET_DYN: u16 = 3;
+pub(super) const ET_CORE: u16 = 4;
+
+/// Machine types.
+pub(super) const EM_ARM: u16 = 40;
+pub(super) const EM_AARCH64: u16 = 183;
+
+/// Section types.
+pub(super) const SHT_SYMTAB: u32 = 2;
+pub(super) const SHT_STRTAB: u32 = 3;
+pub(super) const SHT_RELA: u32 = 4;
+pub(super) const SHT_DYNAMIC: u32 = 6;
+pub(super) const SHT_NOTE: u32 = 7;
+pub(super) const SHT_NOBITS: u32 = 8;
+pub(super) const SHT_REL: u32 = 9;
+pub(super) const SHT_DYNSYM: u32 = 11;
+pub(super) const SHT_GNU_VERDEF: u32 = 0x6ffffffd;
+pub(super) const SHT_GNU_VERNEED: u32 = 0x6ffffffe;
+pub(super) const SHT_GNU_VERSYM: u32 = 0x6fffffff;
+
+/// Segment types.
+pub(super) const PT_LOAD: u32 = 1;
+pub(super) const PT_DYNAMIC: u32 = 2;
+pub(super) const PT_NOTE: u32 = 4;
+
+/// Section index of undefined symbols.
+pub(super) const SHN_UNDEF: u16 = 0;
+
+/// Symbol types, bindings and visibilities.
+pub(super) const STT_FUNC: u8 = 2;
+pub(super) const STB_GLOBAL: u8 = 1;
+pub(super) const STB_WEAK: u8 = 2;
+pub(super) const STV_DEFAULT: u8 = 0;
+
+/// Section index that indicates that the real index is somewhere else.
+const SHN_XINDEX: u16 = 0xffff;
+
+/// A parsed ELF file.
+pub(super) struct Elf<'a> {
+    pub data: &'a [u8],
+    pub is_64bit: bool,
+    pub is_big_endian: bool,
+    pub os_abi: u8,
+    pub type_: u16,
+    pub machine: u16,
+    pub entry_point: u64,
+    pub ph_offset: u64,
+    pub sh_offset: u64,
+    pub flags: u32,
+    pub ph_entry_size: u16,
+    pub sh_entry_size: u16,
+    pub sections: Vec<Section<'a>>,
+    pub segments: Vec<Segment>,
+}
+
+/// An entry in the section header table.
+pub(super) struct Section<'a> {
+    /// The name, from the section header string table. Empty if the name
+    /// can't be read.
+    pub name: &'a [u8],
+    /// Offset of the name within the section header string table.
+    pub name_offset: u32,
+    pub type_: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub alignment: u64,
+    pub entry_size: u64,
+}
+
+/// An entry in the program header table.
+pub(super) struct Segment {
+    pub type_: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub virtual_address: u64,
+    pub physical_address: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub alignment: u64,
+}
+
+/// An entry in a symbol table.
+pub(super) struct Symbol<'a> {
+    pub name: &'a [u8],
+    pub value: u64,
+    pub size: u64,
+    /// The lower 4 bits of `st_info`.
+    pub type_: u8,
+    /// The upper 4 bits of `st_info`.
+    pub bind: u8,
+    /// The lower 2 bits of `st_other`.
+    pub visibility: u8,
+    pub shndx: u16,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses an ELF file. Returns `None` if the data is not an ELF file,
+    /// or its header is truncated.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if !data.starts_with(b"\x7fELF") {
+            return None;
+        }
+
+        let is_64bit = match data.get(4)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+
+        let is_big_endian = match data.get(5)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+
+        let mut elf = Self {
+            data,
+            is_64bit,
+            is_big_endian,
+            os_abi: *data.get(7)?,
+            type_: 0,
+            machine: 0,
+            entry_point: 0,
+            ph_offset: 0,
+            sh_offset: 0,
+            flags: 0,
+            ph_entry_size: 0,
+            sh_entry_size: 0,
+            sections: Vec::new(),
+            segments: Vec::new(),
+        };
+
+        elf.type_ = elf.u16_at(data, 16)?;
+        elf.machine = elf.u16_at(data, 18)?;
+
+        // Offsets of the fields that follow the addresses, which are 4 bytes
+        // larger in 64-bit files.
+        let (flags, ph_num, sh_num, sh_str_index) = if is_64bit {
+            elf.entry_point = elf.u64_at(data, 24)?;
+            elf.ph_offset = elf.u64_at(data, 32)?;
+            elf.sh_offset = elf.u64_at(data, 40)?;
+            (48, 56, 60, 62)
+        } else {
+            elf.entry_point = elf.u32_at(data, 24)?.into();
+            elf.ph_offset = elf.u32_at(data, 28)?.into();
+            elf.sh_offset = elf.u32_at(data, 32)?.into();
+            (36, 44, 48, 50)
+        };
+
+        elf.flags = elf.u32_at(data, flags)?;
+        elf.ph_entry_size = elf.u16_at(data, ph_num - 2)?;
+        elf.sh_entry_size = elf.u16_at(data, sh_num - 2)?;
+
+        let ph_num = elf.u16_at(data, ph_num)?;
+        let sh_num = elf.u16_at(data, sh_num)?;
+        let sh_str_index = elf.u16_at(data, sh_str_index)?;
+
+        elf.segments = elf.parse_segments(ph_num);
+        elf.sections = elf.parse_sections(sh_num, sh_str_index);
+
+        Some(elf)
+    }
+
+    fn parse_segments(&self, count: u16) -> Vec<Segment> {
+        let entry_size = if self.is_64bit { 56 } else { 32 };
+
+        if self.ph_offset == 0 || (self.ph_entry_size as usize) < entry_size {
+            return Vec::new();
+        }
+
+        self.table(self.ph_offset, self.ph_entry_size, count as usize)
+            .take(MAX_SEGMENTS)
+            .filter_map(|entry| {
+                Some(if self.is_64bit {
+                    Segment {
+                        type_: self.u32_at(entry, 0)?,
+                        flags: self.u32_at(entry, 4)?,
+                        offset: self.u64_at(entry, 8)?,
+                        virtual_address: self.u64_at(entry, 16)?,
+                        physical_address: self.u64_at(entry, 24)?,
+                        file_size: self.u64_at(entry, 32)?,
+                        memory_size: self.u64_at(entry, 40)?,
+                        alignment: self.u64_at(entry, 48)?,
+                    }
+                } else {
+                    Segment {
+                        type_: self.u32_at(entry, 0)?,
+                        offset: self.u32_at(entry, 4)?.into(),
+                        virtual_address: self.u32_at(entry, 8)?.into(),
+                        physical_address: self.u32_at(entry, 12)?.into(),
+                        file_size: self.u32_at(entry, 16)?.into(),
+                        memory_size: self.u32_at(entry, 20)?.into(),
+                        flags: self.u32_at(entry, 24)?,
+                        alignment: self.u32_at(entry, 28)?.into(),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn parse_sections(&self, count: u16, str_index: u16) -> Vec<Section<'a>> {
+        let entry_size = if self.is_64bit { 64 } else { 40 };
+
+        if self.sh_offset == 0 || (self.sh_entry_size as usize) < entry_size {
+            return Vec::new();
+        }
+
+        let first = self
+            .table(self.sh_offset, self.sh_entry_size, 1)
+            .next()
+            .and_then(|entry| self.section(entry));
+
+        // When the number of sections doesn't fit in the header, the header
+        // has zero and the real number is the size of the first section.
+        // Similarly, the index of the string table is in the first section's
+        // link field.
+        let count = match (count, &first) {
+            (0, Some(first)) => first.size.min(MAX_SECTIONS as u64) as usize,
+            (count, _) => count as usize,
+        };
+
+        let str_index = match (str_index, &first) {
+            (SHN_XINDEX, Some(first)) => first.link as usize,
+            (index, _) => index as usize,
+        };
+
+        let mut sections: Vec<Section> = self
+            .table(self.sh_offset, self.sh_entry_size, count)
+            .map_while(|entry| self.section(entry))
+            .collect();
+
+        let names = sections
+            .get(str_index)
+            .filter(|section| section.type_ == SHT_STRTAB)
+            .and_then(|section| self.section_data(section));
+
+        if let Some(names) = names {
+            for section in sections.iter_mut() {
+                section.name =
+                    string_at(names, section.name_offset).unwrap_or_default();
+            }
+        }
+
+        sections
+    }
+
+    /// Parses an entry in the section header table. The name is filled in
+    /// later, when the section header string table is found.
+    fn section(&self, entry: &[u8]) -> Option<Section<'a>> {
+        Some(if self.is_64bit {
+            Section {
+                name: &[],
+                name_offset: self.u32_at(entry, 0)?,
+                type_: self.u32_at(entry, 4)?,
+                flags: self.u64_at(entry, 8)?,
+                address: self.u64_at(entry, 16)?,
+                offset: self.u64_at(entry, 24)?,
+                size: self.u64_at(entry, 32)?,
+                link: self.u32_at(entry, 40)?,
+                info: self.u32_at(entry, 44)?,
+                alignment: self.u64_at(entry, 48)?,
+                entry_size: self.u64_at(entry, 56)?,
+            }
+        } else {
+            Section {
+                name: &[],
+                name_offset: self.u32_at(entry, 0)?,
+                type_: self.u32_at(entry, 4)?,
+                flags: self.u32_at(entry, 8)?.into(),
+                address: self.u32_at(entry, 12)?.into(),
+                offset: self.u32_at(entry, 16)?.into(),
+                size: self.u32_at(entry, 20)?.into(),
+                link: self.u32_at(entry, 24)?,
+                info: self.u32_at(entry, 28)?,
+                alignment: self.u32_at(entry, 32)?.into(),
+                entry_size: self.u32_at(entry, 36)?.into(),
+            }
+        })
+    }
+
+    /// Returns the entries of a table at the given offset.
+    fn table(
+        &self,
+        offset: u64,
+        entry_size: u16,
+        count: usize,
+    ) -> impl Iterator<Item = &'a [u8]> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.data.get(offset..))
+            .unwrap_or_default()
+            .chunks_exact(entry_size.max(1) as usize)
+            .take(count)
+    }
+
+    /// Returns the content of a section, or `None` if the section doesn't
+    /// have content in the file. The content is truncated if the section
+    /// exceeds the end of the file.
+    pub fn section_data(&self, section: &Section) -> Option<&'a [u8]> {
+        if section.type_ == SHT_NOBITS {
+            return None;
+        }
+        self.data_at(section.offset, section.size)
+    }
+
+    /// Returns `size` bytes of data starting at the given file offset. The
+    /// data is truncated if it exceeds the end of the file.
+    pub fn data_at(&self, offset: u64, size: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(offset).ok()?;
+        let end = start
+            .saturating_add(usize::try_from(size).unwrap_or(usize::MAX))
+            .min(self.data.len());
+        self.data.get(start..end)
+    }
+
+    /// Returns the symbols in the first section of the given type, which
+    /// is either `SHT_SYMTAB` or `SHT_DYNSYM`. Returns `None` if there's no
+    /// such section. The first symbol, which is always null, is skipped.
+    pub fn symbols(&self, type_: u32) -> Option<Vec<Symbol<'a>>> {
+        let section = self.sections.iter().find(|s| s.type_ == type_)?;
+        let table = self.section_data(section)?;
+
+        let strings = self
+            .sections
+            .get(section.link as usize)
+            .and_then(|strings| self.section_data(strings))
+            .unwrap_or_default();
+
+        let entry_size = if self.is_64bit { 24 } else { 16 };
+
+        Some(
+            table
+                .chunks_exact(entry_size)
+                .skip(1)
+                .take(MAX_SYMBOLS)
+                .filter_map(|entry| {
+                    let (name, value, size, info, other, shndx) =
+                        if self.is_64bit {
+                            (
+                                self.u32_at(entry, 0)?,
+                                self.u64_at(entry, 8)?,
+                                self.u64_at(entry, 16)?,
+                                entry[4],
+                                entry[5],
+                                self.u16_at(entry, 6)?,
+                            )
+                        } else {
+                            (
+                                self.u32_at(entry, 0)?,
+                                self.u32_at(entry, 4)?.into(),
+                                self.u32_at(entry, 8)?.into(),
+                                entry[12],
+                                entry[13],
+                                self.u16_at(entry, 14)?,
+                            )
+                        };
+                    Some(Symbol {
+                        name: string_at(strings, name).unwrap_or_default(),
+                        value,
+                        size,
+                        type_: info & 0xf,
+                        bind: info >> 4,
+                        visibility: other & 0x3,
+                        shndx,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Translates a virtual address into a file offset, using the loadable
+    /// segments. Returns `None` if the address is not backed by the file.
+    pub fn va_to_offset(&self, va: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .filter(|s| s.type_ == PT_LOAD)
+            .find(|s| {
+                s.virtual_address <= va && va - s.virtual_address < s.file_size
+            })
+            .map(|s| s.offset + (va - s.virtual_address))
+            .filter(|offset| *offset < self.data.len() as u64)
+    }
+
+    /// Returns the file offset of the entry point. For files without
+    /// segments, like relocatable objects, the entry point is translated
+    /// using the sections.
+    pub fn entry_point_offset(&self) -> Option<u64> {
+        if self.entry_point == 0 {
+            return None;
+        }
+        if !self.segments.is_empty() {
+            return self.va_to_offset(self.entry_point);
+        }
+        self.sections
+            .iter()
+            .filter(|s| s.type_ != SHT_NOBITS && s.address != 0)
+            .find(|s| {
+                s.address <= self.entry_point
+                    && self.entry_point - s.address < s.size
+            })
+            .map(|s| s.offset + (self.entry_point - s.address))
+            .filter(|offset| *offset < self.data.len() as u64)
+    }
+
+    pub fn u16_at(&self, data: &[u8], offset: usize) -> Option<u16> {
+        let bytes = data.get(offset..offset.checked_add(2)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.is_big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    pub fn u32_at(&self, data: &[u8], offset: usize) -> Option<u32> {
+        let bytes = data.get(offset..offset.checked_add(4)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.is_big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Reads an integer of the native size, which is 64-bit in 64-bit
+    /// files and 32-bit in 32-bit files, like the C `long` type in Linux.
+    pub fn long_at(&self, data: &[u8], offset: usize) -> Option<u64> {
+        if self.is_64bit {
+            self.u64_at(data, offset)
+        } else {
+            self.u32_at(data, offset).map(u64::from)
+        }
+    }
+
+    pub fn u64_at(&self, data: &[u8], offset: usize) -> Option<u64> {
+        let bytes = data.get(offset..offset.checked_add(8)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.is_big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+}
+
+/// Returns the null-terminated string at the given offset within a string
+/// table, without the null character.
+pub(super) fn string_at(strings: &[u8], offset: u32) -> Option<&[u8]> {
+    let data = strings.get(offset as usize..)?;
+    let len = data.iter().take(MAX_STRING_LEN).position(|b| *b == 0)?;
+    Some(&data[..len])
+}
@@ -0,0 +1,50 @@
+/*! Counting of relocations.
+
+The relocations are read from the `SHT_REL` and `SHT_RELA` sections, and
+counted by type. The meaning of each type depends on the architecture,
+for instance, `R_X86_64_IRELATIVE` relocations, which are used for
+resolving indirect functions (IFUNCs), have type 37 in x86-64 files.
+*/
+
+use std::collections::HashMap;
+
+use super::parser::{Elf, SHT_REL, SHT_RELA};
+
+/// Maximum number of relocations read from a file.
+const MAX_RELOCATIONS: usize = 1 << 20;
+
+/// Returns the number of relocations of each type.
+pub(super) fn counts(elf: &Elf) -> HashMap<u32, i64> {
+    let mut counts = HashMap::new();
+    let mut remaining = MAX_RELOCATIONS;
+
+    for section in &elf.sections {
+        // Size of the entries, and offset of the `r_info` field, which is
+        // the same in both kinds of relocations.
+        let entry_size = match (section.type_, elf.is_64bit) {
+            (SHT_REL, false) => 8,
+            (SHT_REL, true) => 16,
+            (SHT_RELA, false) => 12,
+            (SHT_RELA, true) => 24,
+            _ => continue,
+        };
+
+        let data = elf.section_data(section).unwrap_or_default();
+
+        for entry in data.chunks_exact(entry_size).take(remaining) {
+            // The type is in the lower 8 bits of `r_info` in 32-bit files,
+            // and in the lower 32 bits in 64-bit files.
+            let type_ = if elf.is_64bit {
+                elf.u64_at(entry, 8).map(|info| info as u32)
+            } else {
+                elf.u32_at(entry, 4).map(|info| info & 0xff)
+            };
+            if let Some(type_) = type_ {
+                *counts.entry(type_).or_default() += 1;
+            }
+            remaining -= 1;
+        }
+    }
+
+    counts
+}
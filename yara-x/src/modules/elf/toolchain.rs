@@ -0,0 +1,106 @@
+/*! Identification of the toolchain that produced a file.
+
+Go and Rust binaries are statically linked with the language's runtime,
+which leaves markers that survive stripping, like the `.note.go.buildid`
+note in Go binaries, and the paths to the standard library sources in
+the panic messages of Rust binaries. Files produced by C compilers are
+identified by the compiler versions that they put in `.comment`.
+*/
+
+use super::notes::Note;
+use super::parser::Elf;
+
+/// Type of the note with the Go build ID, which is owned by `Go`.
+const NT_GO_BUILD_ID: u32 = 4;
+
+/// Maximum number of strings read from the `.comment` section.
+const MAX_COMMENTS: usize = 64;
+
+/// Sections created by the Go linker.
+const GO_SECTIONS: &[&[u8]] = &[b".gopclntab", b".go.buildinfo", b".gosymtab"];
+
+/// Sections created by the Rust compiler.
+const RUST_SECTIONS: &[&[u8]] = &[b".rustc"];
+
+/// Prefixes of the names of symbols defined by the Rust runtime.
+const RUST_SYMBOLS: &[&[u8]] =
+    &[b"__rust_", b"rust_begin_unwind", b"rust_panic"];
+
+/// Returns the Go build ID, from the `.note.go.buildid` note.
+pub(super) fn go_build_id<'a>(notes: &[Note<'a>]) -> Option<&'a [u8]> {
+    notes
+        .iter()
+        .find(|n| n.name == b"Go" && n.type_ == NT_GO_BUILD_ID)
+        .map(|n| n.desc)
+}
+
+/// Returns the strings in the `.comment` section, which are usually the
+/// versions of the compilers and linkers that produced the file.
+pub(super) fn comments<'a>(elf: &Elf<'a>) -> Vec<&'a [u8]> {
+    elf.sections
+        .iter()
+        .find(|s| s.name == b".comment")
+        .and_then(|s| elf.section_data(s))
+        .unwrap_or_default()
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .take(MAX_COMMENTS)
+        .collect()
+}
+
+/// Returns the toolchain that produced the file, which is one of `go`,
+/// `rust`, `clang` or `gcc`. Go and Rust take precedence, as their
+/// binaries are usually linked with C libraries that were built with GCC
+/// or Clang.
+pub(super) fn toolchain<'a, I>(
+    elf: &Elf,
+    notes: &[Note],
+    comments: &[&[u8]],
+    symbols: I,
+) -> Option<&'static str>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let has_section =
+        |names: &[&[u8]]| elf.sections.iter().any(|s| names.contains(&s.name));
+
+    if go_build_id(notes).is_some() || has_section(GO_SECTIONS) {
+        return Some("go");
+    }
+
+    if has_section(RUST_SECTIONS)
+        || symbols.into_iter().any(|name| {
+            RUST_SYMBOLS.iter().any(|prefix| name.starts_with(prefix))
+        })
+        || has_rust_paths(elf)
+    {
+        return Some("rust");
+    }
+
+    let contains = |s: &[u8], needle: &[u8]| memx::memmem(s, needle).is_some();
+
+    if comments.iter().any(|c| contains(c, b"clang version")) {
+        return Some("clang");
+    }
+
+    if comments.iter().any(|c| c.starts_with(b"GCC:")) {
+        return Some("gcc");
+    }
+
+    None
+}
+
+/// Returns true if the read-only data contains the paths of the standard
+/// library sources, like `/rustc/<commit>/library/core/src/panicking.rs`,
+/// which Rust embeds in panic messages. The whole file is searched if it
+/// doesn't have a `.rodata` section.
+fn has_rust_paths(elf: &Elf) -> bool {
+    let data = elf
+        .sections
+        .iter()
+        .find(|s| s.name == b".rodata")
+        .and_then(|s| elf.section_data(s))
+        .unwrap_or(elf.data);
+
+    memx::memmem(data, b"/rustc/").is_some()
+}
@@ -0,0 +1,196 @@
+/*! Parsing of the symbol versioning sections.
+
+GNU extends the dynamic symbol table with three sections: `.gnu.version`,
+with the version index of each dynamic symbol, `.gnu.version_r`, with the
+versions that the file requires from each library, and `.gnu.version_d`,
+with the versions defined by the file itself. Version indexes 0 and 1 are
+reserved for local and global symbols without a version. Any other index
+refers to an entry in `.gnu.version_r` or `.gnu.version_d`.
+*/
+
+use std::collections::HashMap;
+
+use super::parser::{
+    string_at, Elf, SHT_GNU_VERDEF, SHT_GNU_VERNEED, SHT_GNU_VERSYM,
+};
+
+/// Maximum number of entries read from `.gnu.version_r` and
+/// `.gnu.version_d`, including the auxiliary ones.
+const MAX_ENTRIES: usize = 4096;
+
+/// Bit in the version indexes of symbols that are not the default version
+/// of the symbol.
+pub(super) const VERSYM_HIDDEN: u16 = 0x8000;
+
+/// The versions required from a library.
+pub(super) struct Verneed<'a> {
+    pub file: &'a [u8],
+    pub version: u16,
+    pub entries: Vec<Vernaux<'a>>,
+}
+
+/// A version required from a library.
+pub(super) struct Vernaux<'a> {
+    pub name: &'a [u8],
+    pub hash: u32,
+    pub flags: u16,
+    /// The version index used in `.gnu.version` for this version.
+    pub index: u16,
+}
+
+/// Returns the version index of each dynamic symbol, including the null
+/// symbol at the start of the table.
+pub(super) fn versym(elf: &Elf) -> Vec<u16> {
+    section(elf, SHT_GNU_VERSYM)
+        .map(|(data, _)| {
+            data.chunks_exact(2).filter_map(|c| elf.u16_at(c, 0)).collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the entries in `.gnu.version_r`.
+pub(super) fn verneed<'a>(elf: &Elf<'a>) -> Vec<Verneed<'a>> {
+    let mut result = Vec::new();
+    if let Some((data, strings)) = section(elf, SHT_GNU_VERNEED) {
+        parse_verneed(elf, data, strings, &mut result);
+    }
+    result
+}
+
+/// Returns the names of the versions defined in `.gnu.version_d` and
+/// required in `.gnu.version_r`, by version index.
+pub(super) fn names<'a>(
+    elf: &Elf<'a>,
+    verneed: &[Verneed<'a>],
+) -> HashMap<u16, &'a [u8]> {
+    let mut names = HashMap::new();
+
+    if let Some((data, strings)) = section(elf, SHT_GNU_VERDEF) {
+        parse_verdef(elf, data, strings, &mut names);
+    }
+
+    for entry in verneed.iter().flat_map(|v| &v.entries) {
+        names.insert(entry.index, entry.name);
+    }
+
+    names
+}
+
+/// Returns the content of the first section of the given type, and of the
+/// string table linked to it.
+fn section<'a>(elf: &Elf<'a>, type_: u32) -> Option<(&'a [u8], &'a [u8])> {
+    let section = elf.sections.iter().find(|s| s.type_ == type_)?;
+    let data = elf.section_data(section)?;
+    let strings = elf
+        .sections
+        .get(section.link as usize)
+        .and_then(|strings| elf.section_data(strings))
+        .unwrap_or_default();
+    Some((data, strings))
+}
+
+fn parse_verneed<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    strings: &'a [u8],
+    result: &mut Vec<Verneed<'a>>,
+) -> Option<()> {
+    let mut offset = 0_usize;
+    let mut remaining = MAX_ENTRIES;
+
+    while remaining > 0 {
+        let entry = data.get(offset..)?;
+        let version = elf.u16_at(entry, 0)?;
+        let count = elf.u16_at(entry, 2)?;
+        let file = elf.u32_at(entry, 4)?;
+        let aux = elf.u32_at(entry, 8)?;
+        let next = elf.u32_at(entry, 12)?;
+
+        remaining -= 1;
+
+        let mut verneed = Verneed {
+            file: string_at(strings, file).unwrap_or_default(),
+            version,
+            entries: Vec::new(),
+        };
+
+        let mut aux_offset = offset.checked_add(aux as usize);
+
+        while let Some((vernaux, next)) = aux_offset
+            .filter(|_| {
+                verneed.entries.len() < count as usize && remaining > 0
+            })
+            .and_then(|aux_offset| vernaux(elf, data, aux_offset, strings))
+        {
+            verneed.entries.push(vernaux);
+            remaining -= 1;
+            aux_offset = match next {
+                0 => None,
+                next => aux_offset.and_then(|o| o.checked_add(next as usize)),
+            };
+        }
+
+        result.push(verneed);
+
+        if next == 0 {
+            break;
+        }
+
+        offset = offset.checked_add(next as usize)?;
+    }
+
+    Some(())
+}
+
+/// Parses the auxiliary entry of `.gnu.version_r` at the given offset.
+/// Returns the entry and the offset of the next one, relative to this one.
+fn vernaux<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    offset: usize,
+    strings: &'a [u8],
+) -> Option<(Vernaux<'a>, u32)> {
+    let entry = data.get(offset..)?;
+    let vernaux = Vernaux {
+        hash: elf.u32_at(entry, 0)?,
+        flags: elf.u16_at(entry, 4)?,
+        index: elf.u16_at(entry, 6)?,
+        name: string_at(strings, elf.u32_at(entry, 8)?).unwrap_or_default(),
+    };
+    Some((vernaux, elf.u32_at(entry, 12)?))
+}
+
+/// Parses `.gnu.version_d`, adding the name of each version, which is the
+/// first auxiliary entry, to `names`.
+fn parse_verdef<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    strings: &'a [u8],
+    names: &mut HashMap<u16, &'a [u8]>,
+) -> Option<()> {
+    let mut offset = 0_usize;
+
+    for _ in 0..MAX_ENTRIES {
+        let entry = data.get(offset..)?;
+        let index = elf.u16_at(entry, 4)?;
+        let aux = elf.u32_at(entry, 12)?;
+        let next = elf.u32_at(entry, 16)?;
+
+        let name = offset
+            .checked_add(aux as usize)
+            .and_then(|aux| elf.u32_at(data, aux))
+            .and_then(|name| string_at(strings, name));
+
+        if let Some(name) = name {
+            names.insert(index, name);
+        }
+
+        if next == 0 {
+            break;
+        }
+
+        offset = offset.checked_add(next as usize)?;
+    }
+
+    Some(())
+}
@@ -0,0 +1,605 @@
+/*! Parser for RFC 822 messages and MIME entities.
+
+The parser is lenient: lines can end with CRLF or LF, headers that can't be
+parsed end the header section, and multipart bodies without a closing
+boundary extend up to the end of the data.
+*/
+
+use bstr::ByteSlice;
+
+/// Maximum nesting level for multipart entities and attached messages.
+const MAX_DEPTH: usize = 16;
+
+/// Maximum number of parts extracted from a message.
+const MAX_PARTS: usize = 1024;
+
+/// A header, with its name as it appears in the message and its value
+/// unfolded.
+pub(crate) struct Header {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+/// Finds the header with the given name, ignoring case.
+pub(crate) fn find_header<'a>(
+    headers: &'a [Header],
+    name: &str,
+) -> Option<&'a [u8]> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .map(|header| header.value.as_slice())
+}
+
+/// Parses the header section at the start of `data`. Returns the headers
+/// and the offset where the body starts.
+pub(crate) fn parse_headers(data: &[u8]) -> (Vec<Header>, usize) {
+    let mut headers: Vec<Header> = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let (line, next) = line_at(data, pos);
+
+        if line.is_empty() {
+            return (headers, next);
+        }
+
+        // Lines starting with whitespace continue the previous header.
+        if matches!(line[0], b' ' | b'\t') {
+            match headers.last_mut() {
+                Some(header) => {
+                    header.value.push(b' ');
+                    header.value.extend(line.trim());
+                    pos = next;
+                    continue;
+                }
+                None => break,
+            }
+        }
+
+        let colon = match line.find_byte(b':') {
+            Some(colon) if colon > 0 => colon,
+            _ => break,
+        };
+
+        let name = line[..colon].trim_end();
+
+        if !name.iter().all(|b| (33..=126).contains(b)) {
+            break;
+        }
+
+        headers.push(Header {
+            name: String::from_utf8_lossy(name).into_owned(),
+            value: line[colon + 1..].trim().to_vec(),
+        });
+
+        pos = next;
+    }
+
+    (headers, pos)
+}
+
+/// A value of headers like `Content-Type` or `Content-Disposition`, with
+/// the form `value; param1=value1; param2="value2"`.
+pub(crate) struct ParameterizedValue {
+    /// The value, in lowercase.
+    pub value: String,
+    /// Parameters with their names in lowercase. Parameters split in
+    /// multiple sections as described in RFC 2231 (`name*0`, `name*1`...)
+    /// are joined, and their values decoded.
+    pub params: Vec<(String, Vec<u8>)>,
+}
+
+impl ParameterizedValue {
+    pub fn parse(data: &[u8]) -> Self {
+        let mut items = split_unquoted(data, b';').into_iter();
+
+        let value = items
+            .next()
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let mut params: Vec<(String, Vec<u8>)> = Vec::new();
+
+        for item in items {
+            let (name, value) = match item.find_byte(b'=') {
+                Some(eq) => (&item[..eq], &item[eq + 1..]),
+                None => continue,
+            };
+
+            let name = name.trim().to_ascii_lowercase();
+            let mut value = unquote(value.trim());
+
+            // Extended parameters (`name*=charset'lang'value`) and
+            // continuations (`name*0=`, `name*1*=`).
+            let (name, extended) = match name.strip_suffix(b"*") {
+                Some(name) => (name.to_vec(), true),
+                None => (name, false),
+            };
+
+            let (name, is_continuation) = match name.rfind_byte(b'*') {
+                Some(star)
+                    if name[star + 1..].iter().all(u8::is_ascii_digit) =>
+                {
+                    (name[..star].to_vec(), name[star + 1..] != b"0"[..])
+                }
+                _ => (name, false),
+            };
+
+            if extended {
+                if !is_continuation {
+                    // Remove the charset and language.
+                    if let Some(start) =
+                        value.find_byte(b'\'').and_then(|q1| {
+                            value[q1 + 1..]
+                                .find_byte(b'\'')
+                                .map(|q2| q1 + q2 + 2)
+                        })
+                    {
+                        value.drain(..start);
+                    }
+                }
+                value = percent_decode(&value);
+            }
+
+            let name = String::from_utf8_lossy(&name).into_owned();
+
+            match params.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, existing)) if is_continuation => {
+                    existing.extend(value)
+                }
+                Some(_) => {}
+                None => params.push((name, value)),
+            }
+        }
+
+        Self { value: String::from_utf8_lossy(&value).into_owned(), params }
+    }
+
+    /// Returns the value of a parameter, given its name in lowercase.
+    pub fn param(&self, name: &str) -> Option<&[u8]> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_slice())
+    }
+}
+
+/// A part of a message that is not a multipart entity.
+pub(crate) struct Part {
+    pub headers: Vec<Header>,
+    /// Offset and length of the part's body within the message, before
+    /// decoding it.
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Parses a message and returns its headers and its parts. A message
+/// that is not a multipart entity has a single part, its body.
+pub(crate) fn parse_message(data: &[u8]) -> (Vec<Header>, Vec<Part>) {
+    let (headers, _) = parse_headers(data);
+    let mut parts = Vec::new();
+
+    entity_parts(data, 0, 0, &mut parts);
+
+    (headers, parts)
+}
+
+/// Adds to `parts` the parts in the entity that starts at `offset` and
+/// ends at the end of `data`.
+fn entity_parts(
+    data: &[u8],
+    offset: usize,
+    depth: usize,
+    parts: &mut Vec<Part>,
+) {
+    if parts.len() >= MAX_PARTS {
+        return;
+    }
+
+    let (headers, body_start) = parse_headers(&data[offset..]);
+    let body_start = offset + body_start;
+
+    let content_type =
+        find_header(&headers, "Content-Type").map(ParameterizedValue::parse);
+
+    let (media_type, boundary) = match &content_type {
+        Some(content_type) => {
+            (content_type.value.as_str(), content_type.param("boundary"))
+        }
+        None => ("text/plain", None),
+    };
+
+    if depth < MAX_DEPTH {
+        if let Some(boundary) =
+            boundary.filter(|_| media_type.starts_with("multipart/"))
+        {
+            for (start, end) in multipart_bodies(data, body_start, boundary) {
+                entity_parts(&data[..end], start, depth + 1, parts);
+            }
+            return;
+        }
+        // Attached messages are parsed as well, their headers are not
+        // part of the result.
+        if media_type == "message/rfc822" {
+            entity_parts(data, body_start, depth + 1, parts);
+            return;
+        }
+    }
+
+    parts.push(Part {
+        headers,
+        offset: body_start,
+        length: data.len() - body_start,
+    });
+}
+
+/// Returns the start and end offsets of the bodies in a multipart entity,
+/// whose content starts at `offset`.
+fn multipart_bodies(
+    data: &[u8],
+    offset: usize,
+    boundary: &[u8],
+) -> Vec<(usize, usize)> {
+    let delimiter = [b"--", boundary].concat();
+    let mut bodies = Vec::new();
+    let mut body_start: Option<usize> = None;
+    let mut pos = offset;
+
+    while pos < data.len() {
+        let (line, next) = line_at(data, pos);
+        if line.starts_with(&delimiter) {
+            // The line break before the delimiter is part of it.
+            if let Some(start) = body_start {
+                let mut end = pos;
+                if data[..end].ends_with(b"\r\n") {
+                    end -= 2;
+                } else if data[..end].ends_with(b"\n") {
+                    end -= 1;
+                }
+                bodies.push((start, end.max(start)));
+            }
+            if line[delimiter.len()..].starts_with(b"--") {
+                return bodies;
+            }
+            body_start = Some(next);
+        }
+        pos = next;
+    }
+
+    if let Some(start) = body_start {
+        bodies.push((start, data.len()));
+    }
+
+    bodies
+}
+
+/// Returns the line that starts at `pos`, without the line break, and the
+/// offset where the next line starts.
+fn line_at(data: &[u8], pos: usize) -> (&[u8], usize) {
+    match data[pos..].find_byte(b'\n') {
+        Some(len) => {
+            let line = &data[pos..pos + len];
+            (line.strip_suffix(b"\r").unwrap_or(line), pos + len + 1)
+        }
+        None => (&data[pos..], data.len()),
+    }
+}
+
+/// Splits data on the given separator, ignoring separators inside quoted
+/// strings.
+pub(crate) fn split_unquoted(data: &[u8], separator: u8) -> Vec<&[u8]> {
+    let mut items = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, b) in data.iter().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if in_quotes => escaped = true,
+            b'"' => in_quotes = !in_quotes,
+            b if *b == separator && !in_quotes => {
+                items.push(&data[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    items.push(&data[start..]);
+    items
+}
+
+/// Removes the quotes around a quoted string, and unescapes its content.
+pub(crate) fn unquote(s: &[u8]) -> Vec<u8> {
+    match s.strip_prefix(b"\"").map(|s| s.strip_suffix(b"\"").unwrap_or(s)) {
+        Some(s) => {
+            let mut unquoted = Vec::with_capacity(s.len());
+            let mut escaped = false;
+            for b in s {
+                if *b == b'\\' && !escaped {
+                    escaped = true;
+                } else {
+                    unquoted.push(*b);
+                    escaped = false;
+                }
+            }
+            unquoted
+        }
+        None => s.to_vec(),
+    }
+}
+
+fn percent_decode(s: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .filter(|_| s[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(s[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Decodes the body of a part according to its `Content-Transfer-Encoding`,
+/// truncating the result to `max_size` bytes. Unknown encodings are
+/// treated as binary data.
+pub(crate) fn decode_body(
+    body: &[u8],
+    transfer_encoding: &str,
+    max_size: usize,
+) -> Vec<u8> {
+    let mut decoded = match transfer_encoding {
+        "base64" => base64_decode(body, max_size),
+        "quoted-printable" => quoted_printable_decode(body, max_size),
+        _ => body[..body.len().min(max_size)].to_vec(),
+    };
+    decoded.truncate(max_size);
+    decoded
+}
+
+/// Decodes base64 data, ignoring characters outside the base64 alphabet
+/// like line breaks. Decoding stops at the first padding character.
+pub(crate) fn base64_decode(data: &[u8], max_size: usize) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity((data.len() * 3 / 4).min(max_size));
+    let mut acc = 0_u32;
+    let mut bits = 0;
+
+    for b in data {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => continue,
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((acc >> bits) as u8);
+            if decoded.len() >= max_size {
+                break;
+            }
+        }
+    }
+
+    decoded
+}
+
+fn quoted_printable_decode(data: &[u8], max_size: usize) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len().min(max_size));
+    let mut i = 0;
+
+    while i < data.len() && decoded.len() < max_size {
+        if data[i] != b'=' {
+            decoded.push(data[i]);
+            i += 1;
+        } else if data[i + 1..].starts_with(b"\r\n") {
+            // Soft line break.
+            i += 3;
+        } else if data[i + 1..].starts_with(b"\n") {
+            i += 2;
+        } else {
+            match data
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(b) => {
+                    decoded.push(b);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    decoded
+}
+
+/// Decodes the encoded words described in RFC 2047 (e.g:
+/// `=?utf-8?B?SGVsbG8=?=`) that appear in a header's value. The whitespace
+/// between adjacent encoded words is removed.
+pub(crate) fn decode_header_value(value: &[u8]) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    let mut prev_was_encoded = false;
+
+    while !rest.is_empty() {
+        let start = match rest.find(b"=?") {
+            Some(start) => start,
+            None => {
+                result.push_str(&decode_charset(rest, b"utf-8"));
+                break;
+            }
+        };
+
+        let word = encoded_word(&rest[start..]);
+
+        let (decoded, len) = match word {
+            Some(word) => word,
+            None => {
+                result.push_str(&decode_charset(&rest[..start + 2], b"utf-8"));
+                rest = &rest[start + 2..];
+                prev_was_encoded = false;
+                continue;
+            }
+        };
+
+        let between = &rest[..start];
+        if !(prev_was_encoded && between.trim().is_empty()) {
+            result.push_str(&decode_charset(between, b"utf-8"));
+        }
+
+        result.push_str(&decoded);
+        rest = &rest[start + len..];
+        prev_was_encoded = true;
+    }
+
+    result
+}
+
+/// Decodes the encoded word at the start of `s`, returning the decoded text
+/// and the length of the encoded word.
+fn encoded_word(s: &[u8]) -> Option<(String, usize)> {
+    let mut fields = s[2..].splitn(3, |b| *b == b'?');
+    let charset = fields.next()?;
+    let encoding = fields.next()?;
+    let text_and_rest = fields.next()?;
+    let text_len = text_and_rest.find(b"?=")?;
+    let text = &text_and_rest[..text_len];
+
+    if text.contains(&b' ') {
+        return None;
+    }
+
+    let decoded = match encoding {
+        b"B" | b"b" => base64_decode(text, usize::MAX),
+        b"Q" | b"q" => {
+            let text = text.replace(b"_", b" ");
+            quoted_printable_decode(&text, usize::MAX)
+        }
+        _ => return None,
+    };
+
+    let len = 2 + charset.len() + 1 + encoding.len() + 1 + text_len + 2;
+
+    Some((decode_charset(&decoded, charset), len))
+}
+
+/// Decodes text in the given charset. Only UTF-8 and Latin-1 (including
+/// its superset Windows-1252, approximated with Latin-1) are supported,
+/// other charsets are decoded as UTF-8.
+fn decode_charset(text: &[u8], charset: &[u8]) -> String {
+    let charset = charset.to_ascii_lowercase();
+    match charset.as_slice() {
+        b"iso-8859-1" | b"latin1" | b"windows-1252" | b"cp1252" => {
+            text.iter().map(|b| *b as char).collect()
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    }
+}
+
+/// An address in headers like `From` or `To`.
+pub(crate) struct Address {
+    pub display_name: Option<String>,
+    pub address: String,
+}
+
+/// Parses a list of addresses, like `"John" <john@example.com>, jane@example.com`.
+/// Comments in parentheses and group names are removed.
+pub(crate) fn parse_addresses(value: &[u8]) -> Vec<Address> {
+    let value = remove_comments(value);
+    let mut addresses = Vec::new();
+
+    for item in split_unquoted(&value, b',') {
+        // Groups (`undisclosed-recipients: a@b, c@d;`) have a name
+        // followed by a colon before the first address.
+        let item = match item.find_byte(b':') {
+            Some(colon)
+                if !item[..colon].contains(&b'"')
+                    && !item[..colon].contains(&b'<') =>
+            {
+                &item[colon + 1..]
+            }
+            _ => item,
+        };
+        let item = item.trim().trim_end_with(|c| c == ';');
+
+        if item.is_empty() {
+            continue;
+        }
+
+        let (display_name, address) =
+            match (item.rfind_byte(b'<'), item.rfind_byte(b'>')) {
+                (Some(open), Some(close)) if open < close => {
+                    let display_name = unquote(item[..open].trim());
+                    (
+                        Some(decode_header_value(&display_name))
+                            .filter(|name| !name.is_empty()),
+                        &item[open + 1..close],
+                    )
+                }
+                _ => (None, item),
+            };
+
+        addresses.push(Address {
+            display_name,
+            address: String::from_utf8_lossy(address.trim()).into_owned(),
+        });
+    }
+
+    addresses
+}
+
+/// Removes comments, which are text in parentheses outside quoted strings.
+fn remove_comments(value: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(value.len());
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for b in value {
+        match b {
+            _ if escaped => {
+                escaped = false;
+                if depth == 0 {
+                    result.push(*b);
+                }
+                continue;
+            }
+            b'\\' => escaped = true,
+            b'"' if depth == 0 => in_quotes = !in_quotes,
+            b'(' if !in_quotes => {
+                depth += 1;
+                continue;
+            }
+            b')' if !in_quotes && depth > 0 => {
+                depth -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            result.push(*b);
+        }
+    }
+
+    result
+}
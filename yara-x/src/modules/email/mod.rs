@@ -0,0 +1,268 @@
+use std::fmt::Write;
+
+use bstr::ByteSlice;
+use sha2::{Digest, Sha256};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::email::*;
+
+mod mime;
+
+/// Maximum size of decoded parts.
+const MAX_PART_SIZE: usize = 32 * 1024 * 1024;
+
+/// Headers that identify a message. At least one of them must be present.
+const MESSAGE_HEADERS: &[&str] =
+    &["From", "To", "Subject", "Date", "Message-ID", "Received"];
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Email {
+    let data = ctx.scanned_data();
+    let mut email = Email::new();
+
+    let (headers, parts) = mime::parse_message(data);
+
+    if !MESSAGE_HEADERS
+        .iter()
+        .any(|name| mime::find_header(&headers, name).is_some())
+    {
+        email.set_is_email(false);
+        return email;
+    }
+
+    email.set_is_email(true);
+
+    let header = |name: &str| mime::find_header(&headers, name);
+
+    email.subject = header("Subject").map(mime::decode_header_value);
+    email.message_id = header("Message-ID")
+        .map(|id| id.trim_with(|c| c == '<' || c == '>' || c == ' '))
+        .map(|id| id.to_str_lossy().into_owned());
+    email.date = header("Date")
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| {
+            chrono::DateTime::parse_from_rfc2822(date.trim()).ok()
+        })
+        .map(|date| date.timestamp());
+
+    for (name, addresses) in [
+        ("From", &mut email.from),
+        ("To", &mut email.to),
+        ("Cc", &mut email.cc),
+        ("Reply-To", &mut email.reply_to),
+    ] {
+        // The same header can appear multiple times.
+        for h in headers.iter().filter(|h| h.name.eq_ignore_ascii_case(name)) {
+            for addr in mime::parse_addresses(&h.value) {
+                let mut address = Address::new();
+                address.domain = addr
+                    .address
+                    .rsplit_once('@')
+                    .map(|(_, domain)| domain.to_ascii_lowercase());
+                address.display_name = addr.display_name;
+                address.set_address(addr.address);
+                addresses.push(address);
+            }
+        }
+    }
+
+    for h in headers.iter() {
+        let mut header = EmailHeader::new();
+        header.set_name(h.name.clone());
+        header.set_value(mime::decode_header_value(&h.value));
+        email.headers.push(header);
+    }
+
+    for p in parts {
+        email.parts.push(part(data, &p));
+    }
+
+    email
+}
+
+/// Returns the value of the first header with the given name, ignoring
+/// case. Encoded words in the value are decoded.
+#[module_export]
+fn header(
+    ctx: &mut ScanContext,
+    name: RuntimeString,
+) -> Option<RuntimeString> {
+    let email = ctx.module_output::<Email>()?;
+    let name = name.as_bstr(ctx);
+
+    let value = email
+        .headers
+        .iter()
+        .find(|h| h.name().as_bytes().eq_ignore_ascii_case(name))?
+        .value()
+        .to_string();
+
+    Some(RuntimeString::from_bytes(ctx, value))
+}
+
+/// Returns the body of the part at the given index in `parts`, decoded
+/// according to its `Content-Transfer-Encoding`.
+#[module_export]
+fn decoded_part(ctx: &mut ScanContext, index: i64) -> Option<RuntimeString> {
+    let email = ctx.module_output::<Email>()?;
+    let part = email.parts.get(usize::try_from(index).ok()?)?;
+
+    let offset = usize::try_from(part.offset()).ok()?;
+    let length = usize::try_from(part.length()).ok()?;
+    let body = ctx.scanned_data().get(offset..offset.checked_add(length)?)?;
+
+    let decoded =
+        mime::decode_body(body, part.transfer_encoding(), MAX_PART_SIZE);
+
+    Some(RuntimeString::from_bytes(ctx, decoded))
+}
+
+fn part(data: &[u8], p: &mime::Part) -> Part {
+    let mut part = Part::new();
+
+    let content_type = mime::find_header(&p.headers, "Content-Type")
+        .map(mime::ParameterizedValue::parse);
+
+    let disposition = mime::find_header(&p.headers, "Content-Disposition")
+        .map(mime::ParameterizedValue::parse);
+
+    let transfer_encoding =
+        mime::find_header(&p.headers, "Content-Transfer-Encoding")
+            .map(|encoding| encoding.trim().to_str_lossy().to_lowercase())
+            .unwrap_or_else(|| "7bit".to_string());
+
+    let filename = disposition
+        .as_ref()
+        .and_then(|disposition| disposition.param("filename"))
+        .or_else(|| content_type.as_ref().and_then(|ct| ct.param("name")))
+        .map(mime::decode_header_value);
+
+    part.set_content_type(
+        content_type
+            .as_ref()
+            .map(|ct| ct.value.clone())
+            .unwrap_or_else(|| "text/plain".to_string()),
+    );
+    part.charset = content_type
+        .as_ref()
+        .and_then(|ct| ct.param("charset"))
+        .map(|charset| charset.to_str_lossy().into_owned());
+    part.set_is_attachment(
+        filename.is_some()
+            || disposition.map_or(false, |d| d.value == "attachment"),
+    );
+    part.filename = filename;
+
+    let body = &data[p.offset..p.offset + p.length];
+    let decoded = mime::decode_body(body, &transfer_encoding, MAX_PART_SIZE);
+
+    let mut sha256 = String::with_capacity(64);
+    for b in Sha256::digest(&decoded) {
+        write!(sha256, "{:02x}", b).unwrap();
+    }
+
+    part.set_transfer_encoding(transfer_encoding);
+    part.set_offset(p.offset as i64);
+    part.set_length(p.length as i64);
+    part.set_size(decoded.len() as i64);
+    part.set_sha256(sha256);
+    part
+}
+
+#[cfg(test)]
+mod tests {
+    const MESSAGE: &[u8] = b"\
+Received: from mail.example.com by mx.example.org; Tue, 1 Jul 2003 10:52:37 +0200
+From: \"support@bank.com\" <Attacker@EVIL.example>
+To: alice@example.org, \"Bob (work)\" <bob@example.org> (comment)
+Subject: =?utf-8?B?SW52b2ljZQ==?= =?iso-8859-1?Q?_n=BA_1?=
+Date: Tue, 1 Jul 2003 10:52:37 +0200
+Message-ID: <1234@mail.example.com>
+MIME-Version: 1.0
+Content-Type: multipart/mixed;
+ boundary=\"XXXX\"
+
+This is a multipart message.
+--XXXX
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: quoted-printable
+
+Please open the attached invoice=2E=
+ Thanks.
+--XXXX
+Content-Type: application/octet-stream; name=\"ignored.bin\"
+Content-Disposition: attachment;
+ filename*=utf-8''invoice%20%231.exe
+Content-Transfer-Encoding: base64
+
+TVqQAAMAAAAE
+AAAA//8AAA==
+--XXXX--
+";
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "email"
+                rule rule_1 {
+                  condition:
+                    email.is_email and
+                    email.subject == "Invoice nº 1" and
+                    email.message_id == "1234@mail.example.com" and
+                    email.date == 1057049557 and
+                    email.header("mime-version") == "1.0" and
+                    not defined email.header("X-Mailer")
+                }
+                rule rule_2 {
+                  condition:
+                    email.from[0].display_name == "support@bank.com" and
+                    email.from[0].address == "Attacker@EVIL.example" and
+                    email.from[0].domain == "evil.example" and
+                    email.to[0].address == "alice@example.org" and
+                    not defined email.to[0].display_name and
+                    email.to[1].display_name == "Bob (work)" and
+                    email.to[1].address == "bob@example.org"
+                }
+                rule rule_3 {
+                  condition:
+                    email.parts[0].content_type == "text/plain" and
+                    email.parts[0].charset == "utf-8" and
+                    not email.parts[0].is_attachment and
+                    email.decoded_part(0) == "Please open the attached invoice. Thanks."
+                }
+                rule rule_4 {
+                  condition:
+                    email.parts[1].is_attachment and
+                    email.parts[1].filename == "invoice #1.exe" and
+                    email.parts[1].transfer_encoding == "base64" and
+                    email.parts[1].size == 16 and
+                    email.decoded_part(1) startswith "MZ"
+                }
+                rule rule_5 { condition: not email.is_email }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(MESSAGE),
+            ["rule_1", "rule_2", "rule_3", "rule_4"]
+        );
+
+        assert_eq!(matching_rules(b"Foo: bar\n\nbody"), ["rule_5"]);
+    }
+}
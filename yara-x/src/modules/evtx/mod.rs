@@ -0,0 +1,384 @@
+use crate::modules::prelude::*;
+use crate::modules::protos::evtx::*;
+use crate::modules::utils::filetime_to_epoch;
+
+mod parser;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Evtx {
+    let mut evtx = Evtx::new();
+
+    let file = match parser::Evtx::parse(ctx.scanned_data()) {
+        Some(file) => file,
+        None => {
+            evtx.set_is_evtx(false);
+            return evtx;
+        }
+    };
+
+    evtx.set_is_evtx(true);
+    evtx.set_major_version(file.major_version.into());
+    evtx.set_minor_version(file.minor_version.into());
+    evtx.set_num_chunks(file.num_chunks.into());
+    evtx.set_is_dirty(file.flags & 0x1 != 0);
+
+    for r in file.records() {
+        let mut record = Record::new();
+        record.set_record_id(r.record_id as i64);
+        record.timestamp = filetime_to_epoch(r.timestamp);
+
+        let event = r.xml.iter().find_map(|node| match node {
+            parser::Node::Element(e) if e.name == "Event" => Some(e),
+            _ => None,
+        });
+
+        if let Some(event) = event {
+            if let Some(system) = event.child("System") {
+                let text = |name| system.child(name).map(|e| e.text());
+                record.event_id =
+                    text("EventID").and_then(|id| id.trim().parse().ok());
+                record.level =
+                    text("Level").and_then(|level| level.trim().parse().ok());
+                record.channel = text("Channel");
+                record.computer = text("Computer");
+                record.provider = system
+                    .child("Provider")
+                    .and_then(|provider| provider.attribute("Name"))
+                    .map(|name| name.to_string());
+            }
+
+            if let Some(event_data) = event.child("EventData") {
+                for data in event_data.elements() {
+                    let mut field = EventField::new();
+                    field.set_name(
+                        data.attribute("Name")
+                            .unwrap_or(&data.name)
+                            .to_string(),
+                    );
+                    field.set_value(data.text());
+                    record.fields.push(field);
+                }
+            }
+
+            if let Some(user_data) = event.child("UserData") {
+                user_data_fields(user_data, &mut record.fields);
+            }
+        }
+
+        evtx.records.push(record);
+    }
+
+    evtx.set_num_records(evtx.records.len() as i64);
+    evtx
+}
+
+/// Returns the value of the field with the given name in the record at
+/// the given index in `records`.
+#[module_export]
+fn field(
+    ctx: &mut ScanContext,
+    index: i64,
+    name: RuntimeString,
+) -> Option<RuntimeString> {
+    let evtx = ctx.module_output::<Evtx>()?;
+    let name = name.to_str(ctx).ok()?;
+
+    let value = evtx
+        .records
+        .get(usize::try_from(index).ok()?)?
+        .fields
+        .iter()
+        .find(|field| field.name() == name)?
+        .value()
+        .to_string();
+
+    Some(RuntimeString::from_bytes(ctx, value))
+}
+
+/// Appends to `fields` the elements under `element` that don't contain
+/// other elements, which are the ones that contain values.
+fn user_data_fields(element: &parser::Element, fields: &mut Vec<EventField>) {
+    for child in element.elements() {
+        if child.elements().next().is_some() {
+            user_data_fields(child, fields);
+        } else {
+            let mut field = EventField::new();
+            field.set_name(child.name.clone());
+            field.set_value(child.text());
+            fields.push(field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// FILETIME corresponding to 2023-08-01 10:30:00 UTC.
+    const FILETIME: u64 = (1690885800 + 11644473600) * 10_000_000;
+
+    /// Builds the Binary XML of records within a chunk. All names are
+    /// stored inline, right after the reference to them.
+    struct BinXml {
+        chunk: Vec<u8>,
+    }
+
+    impl BinXml {
+        fn name(&mut self, name: &str) {
+            let offset = self.chunk.len() as u32 + 4;
+            self.chunk.extend(offset.to_le_bytes());
+            self.chunk.extend(0_u32.to_le_bytes());
+            self.chunk.extend(0_u16.to_le_bytes());
+            self.chunk.extend((name.len() as u16).to_le_bytes());
+            self.chunk.extend(utf16(name));
+            self.chunk.extend(0_u16.to_le_bytes());
+        }
+
+        fn open(&mut self, name: &str, attributes: &[(&str, Content)]) {
+            self.chunk.push(if attributes.is_empty() { 0x01 } else { 0x41 });
+            self.chunk.extend(0_u16.to_le_bytes());
+            self.chunk.extend(0_u32.to_le_bytes());
+            self.name(name);
+            if !attributes.is_empty() {
+                self.chunk.extend(0_u32.to_le_bytes());
+                for (name, value) in attributes {
+                    self.chunk.push(0x06);
+                    self.name(name);
+                    self.content(value);
+                }
+            }
+        }
+
+        /// Appends an element that contains only a value.
+        fn element(
+            &mut self,
+            name: &str,
+            attributes: &[(&str, Content)],
+            content: Content,
+        ) {
+            self.open(name, attributes);
+            self.chunk.push(0x02);
+            self.content(&content);
+            self.chunk.push(0x04);
+        }
+
+        fn content(&mut self, content: &Content) {
+            match content {
+                Content::Text(s) => {
+                    self.chunk.extend([0x05, 0x01]);
+                    self.chunk.extend((s.len() as u16).to_le_bytes());
+                    self.chunk.extend(utf16(s));
+                }
+                Content::Substitution(index, value_type) => {
+                    self.chunk.push(0x0d);
+                    self.chunk.extend(index.to_le_bytes());
+                    self.chunk.push(*value_type);
+                }
+            }
+        }
+
+        /// Appends a template instance. If `definition` is `None` the
+        /// template definition is included in the instance. Returns the
+        /// offset of the template definition.
+        fn template_instance(
+            &mut self,
+            definition: Option<u32>,
+            values: &[(u8, Vec<u8>)],
+        ) -> u32 {
+            self.chunk.extend([0x0c, 0x01]);
+            self.chunk.extend(1_u32.to_le_bytes());
+
+            let definition = match definition {
+                Some(definition) => {
+                    self.chunk.extend(definition.to_le_bytes());
+                    definition
+                }
+                None => {
+                    let definition = self.chunk.len() as u32 + 4;
+                    self.chunk.extend(definition.to_le_bytes());
+                    self.template_definition();
+                    definition
+                }
+            };
+
+            self.chunk.extend((values.len() as u32).to_le_bytes());
+            for (value_type, data) in values {
+                self.chunk.extend((data.len() as u16).to_le_bytes());
+                self.chunk.extend([*value_type, 0]);
+            }
+            for (_, data) in values {
+                self.chunk.extend(data);
+            }
+
+            definition
+        }
+
+        fn template_definition(&mut self) {
+            let start = self.chunk.len();
+            self.chunk.extend([0; 24]);
+            self.chunk.extend([0x0f, 0x01, 0x01, 0x00]);
+            self.open("Event", &[("xmlns", Content::Text("http://x"))]);
+            self.chunk.push(0x02);
+            self.open("System", &[]);
+            self.chunk.push(0x02);
+            self.open("Provider", &[("Name", Content::Substitution(0, 0x01))]);
+            self.chunk.push(0x03);
+            self.element("EventID", &[], Content::Substitution(1, 0x06));
+            self.element("Level", &[], Content::Substitution(2, 0x04));
+            self.element("Channel", &[], Content::Text("Security"));
+            self.element("Computer", &[], Content::Substitution(3, 0x01));
+            self.chunk.push(0x04);
+            self.open("EventData", &[]);
+            self.chunk.push(0x02);
+            self.element(
+                "Data",
+                &[("Name", Content::Text("TargetUserName"))],
+                Content::Substitution(4, 0x01),
+            );
+            self.element(
+                "Data",
+                &[("Name", Content::Text("TargetSid"))],
+                Content::Substitution(5, 0x13),
+            );
+            self.chunk.push(0x04);
+            self.chunk.push(0x04);
+            self.chunk.push(0x00);
+            let size = (self.chunk.len() - start - 24) as u32;
+            self.chunk[start + 20..start + 24]
+                .copy_from_slice(&size.to_le_bytes());
+        }
+    }
+
+    enum Content {
+        Text(&'static str),
+        Substitution(u16, u8),
+    }
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn build_evtx() -> Vec<u8> {
+        let mut xml = BinXml { chunk: vec![0; 512] };
+        let mut definition = None;
+
+        let sid = [
+            &[1_u8, 2, 0, 0, 0, 0, 0, 5][..],
+            &32_u32.to_le_bytes(),
+            &544_u32.to_le_bytes(),
+        ]
+        .concat();
+
+        for (record_id, event_id, user) in
+            [(1_u64, 4624_u16, "alice"), (2, 4625, "bob")]
+        {
+            let start = xml.chunk.len();
+            xml.chunk.extend(b"\x2a\x2a\x00\x00");
+            xml.chunk.extend(0_u32.to_le_bytes());
+            xml.chunk.extend(record_id.to_le_bytes());
+            xml.chunk.extend(FILETIME.to_le_bytes());
+            xml.chunk.extend([0x0f, 0x01, 0x01, 0x00]);
+
+            // The first record includes the template definition, the
+            // second one references it.
+            definition = Some(xml.template_instance(
+                definition,
+                &[
+                    (0x01, utf16("Microsoft-Windows-Security-Auditing")),
+                    (0x06, event_id.to_le_bytes().to_vec()),
+                    (0x04, vec![0]),
+                    (0x01, utf16("DC01\0")),
+                    (0x01, utf16(user)),
+                    (0x13, sid.clone()),
+                ],
+            ));
+
+            xml.chunk.push(0x00);
+
+            let size = (xml.chunk.len() - start + 4) as u32;
+            xml.chunk.extend(size.to_le_bytes());
+            xml.chunk[start + 4..start + 8]
+                .copy_from_slice(&size.to_le_bytes());
+        }
+
+        let mut chunk = xml.chunk;
+        let free_space_offset = chunk.len() as u32;
+
+        chunk[..8].copy_from_slice(b"ElfChnk\0");
+        chunk[48..52].copy_from_slice(&free_space_offset.to_le_bytes());
+        chunk.resize(65536, 0);
+
+        let mut evtx = b"ElfFile\0".to_vec();
+        evtx.resize(36, 0);
+        evtx.extend(1_u16.to_le_bytes());
+        evtx.extend(3_u16.to_le_bytes());
+        evtx.extend(4096_u16.to_le_bytes());
+        evtx.extend(1_u16.to_le_bytes());
+        evtx.resize(120, 0);
+        evtx.extend(1_u32.to_le_bytes());
+        evtx.resize(4096, 0);
+        evtx.extend(chunk);
+        evtx
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "evtx"
+                rule rule_1 {
+                  condition:
+                    evtx.is_evtx and
+                    evtx.major_version == 3 and
+                    evtx.minor_version == 1 and
+                    evtx.num_chunks == 1 and
+                    evtx.is_dirty and
+                    evtx.num_records == 2
+                }
+                rule rule_2 {
+                  condition:
+                    evtx.records[0].record_id == 1 and
+                    evtx.records[0].timestamp == 1690885800 and
+                    evtx.records[0].event_id == 4624 and
+                    evtx.records[0].provider == "Microsoft-Windows-Security-Auditing" and
+                    evtx.records[0].channel == "Security" and
+                    evtx.records[0].computer == "DC01" and
+                    evtx.records[0].level == 0 and
+                    evtx.records[0].fields[0].name == "TargetUserName" and
+                    evtx.records[0].fields[0].value == "alice" and
+                    evtx.records[0].fields[1].value == "S-1-5-32-544"
+                }
+                rule rule_3 {
+                  condition:
+                    for any r in evtx.records : (
+                      r.event_id == 4625 and r.record_id == 2
+                    ) and
+                    evtx.field(1, "TargetUserName") == "bob" and
+                    not defined evtx.field(1, "Foo") and
+                    not defined evtx.field(2, "TargetUserName")
+                }
+                rule rule_4 { condition: not evtx.is_evtx }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_evtx()),
+            ["rule_1", "rule_2", "rule_3"]
+        );
+
+        assert_eq!(matching_rules(b"ElfFile\0"), ["rule_4"]);
+    }
+}
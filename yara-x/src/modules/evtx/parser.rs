@@ -0,0 +1,561 @@
+/*! Parser for Windows XML Event Log (EVTX) files.
+
+An EVTX file starts with a 4KB header, followed by chunks of 64KB. Each
+chunk has a 512 bytes header, followed by event records. The content of
+each record is encoded in Binary XML, a format where element and attribute
+names are stored once per chunk and referenced by their offset within the
+chunk. Records usually consist of a template instance, which references a
+template definition and provides the values that must be substituted in the
+template.
+*/
+
+use std::fmt::Write;
+
+use crate::modules::utils::{
+    filetime_to_epoch, u16_at, u32_at, u64_at, utf16_string,
+};
+
+const FILE_HEADER_SIZE: usize = 4096;
+const CHUNK_SIZE: usize = 65536;
+const CHUNK_HEADER_SIZE: usize = 512;
+
+/// Size of the header that precedes the Binary XML in each record.
+const RECORD_HEADER_SIZE: usize = 24;
+
+/// Maximum nesting level of elements and template instances.
+const MAX_DEPTH: usize = 64;
+
+/// Maximum number of elements and template instances in a record. As
+/// templates and substitutions can be referenced multiple times, a small
+/// record could otherwise produce a huge number of elements.
+const MAX_NODES: usize = 10_000;
+
+/// Size of the header that precedes the content of a template definition.
+const TEMPLATE_HEADER_SIZE: usize = 24;
+
+/// An EVTX file.
+pub(crate) struct Evtx<'a> {
+    data: &'a [u8],
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// Number of chunks according to the file header.
+    pub num_chunks: u16,
+    pub flags: u32,
+}
+
+/// An event record.
+pub(crate) struct Record {
+    pub record_id: u64,
+    /// Time when the record was written, as a Windows FILETIME.
+    pub timestamp: u64,
+    /// Nodes at the top level of the record's XML.
+    pub xml: Vec<Node>,
+}
+
+/// A node in the XML of a record.
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+/// An XML element.
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// Returns an iterator over the child elements.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// Returns the first child element with the given name.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// Returns the value of the attribute with the given name.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the text in the element, excluding the one in child
+    /// elements.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in self.children.iter() {
+            if let Node::Text(s) = node {
+                text.push_str(s);
+            }
+        }
+        text
+    }
+}
+
+impl<'a> Evtx<'a> {
+    /// Parses the file header. Returns `None` if the data is not an EVTX
+    /// file.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let header = data.get(..128)?;
+
+        if !header.starts_with(b"ElfFile\0") {
+            return None;
+        }
+
+        Some(Self {
+            data,
+            minor_version: u16_at(header, 36)?,
+            major_version: u16_at(header, 38)?,
+            num_chunks: u16_at(header, 42)?,
+            flags: u32_at(header, 120)?,
+        })
+    }
+
+    /// Returns the records in all the chunks, in the order they appear
+    /// in the file. The number of chunks in the file header is ignored,
+    /// as it may be outdated if the file was not closed properly.
+    pub fn records(&self) -> Vec<Record> {
+        let mut records = Vec::new();
+        let chunks = self.data.get(FILE_HEADER_SIZE..).unwrap_or_default();
+
+        for chunk in chunks.chunks(CHUNK_SIZE) {
+            if !chunk.starts_with(b"ElfChnk\0") {
+                continue;
+            }
+
+            // Records end where the chunk's free space starts.
+            let end = u32_at(chunk, 48)
+                .map(|offset| (offset as usize).min(chunk.len()))
+                .unwrap_or(0);
+
+            let mut offset = CHUNK_HEADER_SIZE;
+
+            while offset + RECORD_HEADER_SIZE < end {
+                let header = &chunk[offset..offset + RECORD_HEADER_SIZE];
+
+                if !header.starts_with(b"\x2a\x2a\x00\x00") {
+                    break;
+                }
+
+                let size = u32_at(header, 4).unwrap() as usize;
+
+                if size < RECORD_HEADER_SIZE || offset + size > end {
+                    break;
+                }
+
+                let mut parser = BinXml {
+                    chunk,
+                    pos: offset + RECORD_HEADER_SIZE,
+                    num_nodes: 0,
+                };
+
+                records.push(Record {
+                    record_id: u64_at(header, 8).unwrap(),
+                    timestamp: u64_at(header, 16).unwrap(),
+                    xml: parser.fragment(&[], 0).unwrap_or_default(),
+                });
+
+                offset += size;
+            }
+        }
+
+        records
+    }
+}
+
+/// A value that must be substituted in a template.
+struct Substitution {
+    value_type: u8,
+    /// Offset of the value's data within the chunk.
+    offset: usize,
+    size: usize,
+}
+
+/// Parser for the Binary XML in a chunk.
+struct BinXml<'a> {
+    chunk: &'a [u8],
+    /// Current position within the chunk.
+    pos: usize,
+    /// Number of elements and template instances parsed so far.
+    num_nodes: usize,
+}
+
+impl BinXml<'_> {
+    /// Parses nodes until the end of the fragment.
+    fn fragment(
+        &mut self,
+        substitutions: &[Substitution],
+        depth: usize,
+    ) -> Option<Vec<Node>> {
+        let mut nodes = Vec::new();
+
+        loop {
+            match self.peek()? {
+                // End of fragment.
+                0x00 => {
+                    self.pos += 1;
+                    return Some(nodes);
+                }
+                // Fragment header.
+                0x0f => self.pos += 4,
+                0x0c => nodes.extend(self.template_instance(depth)?),
+                0x01 | 0x41 => nodes
+                    .push(Node::Element(self.element(substitutions, depth)?)),
+                _ => return Some(nodes),
+            }
+        }
+    }
+
+    /// Parses a template instance, and returns the nodes resulting from
+    /// applying the substitutions to the template.
+    fn template_instance(&mut self, depth: usize) -> Option<Vec<Node>> {
+        self.num_nodes += 1;
+
+        if depth > MAX_DEPTH || self.num_nodes > MAX_NODES {
+            return None;
+        }
+
+        // Skip the token and an unknown byte.
+        self.pos += 2;
+
+        let _template_id = self.u32()?;
+        let definition = self.u32()? as usize;
+
+        // The template definition is included in the instance the first
+        // time it's used in the chunk, it must be skipped.
+        if definition == self.pos {
+            let size = u32_at(self.chunk, self.pos + 20)? as usize;
+            self.pos += TEMPLATE_HEADER_SIZE + size;
+        }
+
+        // The number of substitutions is followed by a descriptor for
+        // each of them, and then by the substitutions' data.
+        let count = self.u32()? as usize;
+        let descriptors = self
+            .chunk
+            .get(self.pos..self.pos.checked_add(count.checked_mul(4)?)?)?;
+
+        let mut offset = self.pos + descriptors.len();
+        let mut substitutions = Vec::with_capacity(count);
+
+        for descriptor in descriptors.chunks_exact(4) {
+            let size = u16_at(descriptor, 0)? as usize;
+            substitutions.push(Substitution {
+                value_type: descriptor[2],
+                offset,
+                size,
+            });
+            offset += size;
+        }
+
+        if offset > self.chunk.len() {
+            return None;
+        }
+
+        self.pos = definition + TEMPLATE_HEADER_SIZE;
+        let nodes = self.fragment(&substitutions, depth + 1);
+        self.pos = offset;
+
+        nodes
+    }
+
+    /// Parses an element, including its attributes and content.
+    fn element(
+        &mut self,
+        substitutions: &[Substitution],
+        depth: usize,
+    ) -> Option<Element> {
+        self.num_nodes += 1;
+
+        if depth > MAX_DEPTH || self.num_nodes > MAX_NODES {
+            return None;
+        }
+
+        let token = self.u8()?;
+
+        // Skip the dependency identifier and the size of the element.
+        self.pos += 6;
+
+        let mut element = Element {
+            name: self.name()?,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        };
+
+        if token & 0x40 != 0 {
+            // Size of the attribute list.
+            self.u32()?;
+
+            while let Some(0x06 | 0x46) = self.peek() {
+                self.pos += 1;
+                let name = self.name()?;
+                let mut value = String::new();
+                for node in self.content(substitutions, depth)? {
+                    if let Node::Text(s) = node {
+                        value.push_str(&s);
+                    }
+                }
+                element.attributes.push((name, value));
+            }
+        }
+
+        match self.u8()? {
+            // Close start element.
+            0x02 => {
+                element.children = self.content(substitutions, depth)?;
+                // End element.
+                if self.u8()? != 0x04 {
+                    return None;
+                }
+            }
+            // Close empty element.
+            0x03 => {}
+            _ => return None,
+        }
+
+        Some(element)
+    }
+
+    /// Parses the content of an element or attribute.
+    fn content(
+        &mut self,
+        substitutions: &[Substitution],
+        depth: usize,
+    ) -> Option<Vec<Node>> {
+        let mut nodes = Vec::new();
+
+        loop {
+            match self.peek()? {
+                0x01 | 0x41 => nodes.push(Node::Element(
+                    self.element(substitutions, depth + 1)?,
+                )),
+                // Value, only strings are used in this context.
+                0x05 | 0x45 => {
+                    self.pos += 1;
+                    if self.u8()? != 0x01 {
+                        return None;
+                    }
+                    nodes.push(Node::Text(self.string()?));
+                }
+                // CDATA section.
+                0x07 | 0x47 => {
+                    self.pos += 1;
+                    nodes.push(Node::Text(self.string()?));
+                }
+                // Character reference.
+                0x08 | 0x48 => {
+                    self.pos += 1;
+                    let c = char::from_u32(self.u16()?.into())?;
+                    nodes.push(Node::Text(c.to_string()));
+                }
+                // Entity reference.
+                0x09 | 0x49 => {
+                    self.pos += 1;
+                    let entity = match self.name()?.as_str() {
+                        "amp" => "&",
+                        "lt" => "<",
+                        "gt" => ">",
+                        "quot" => "\"",
+                        "apos" => "'",
+                        _ => "",
+                    };
+                    nodes.push(Node::Text(entity.to_string()));
+                }
+                // Processing instruction target and data, ignored.
+                0x0a => {
+                    self.pos += 1;
+                    self.name()?;
+                }
+                0x0b => {
+                    self.pos += 1;
+                    self.string()?;
+                }
+                0x0c => nodes.extend(self.template_instance(depth + 1)?),
+                // Normal and optional substitutions.
+                0x0d | 0x0e => {
+                    self.pos += 1;
+                    let index = self.u16()? as usize;
+                    self.u8()?;
+                    if let Some(s) = substitutions.get(index) {
+                        nodes.extend(self.substitution(s, depth)?);
+                    }
+                }
+                _ => return Some(nodes),
+            }
+        }
+    }
+
+    /// Returns the nodes resulting from a substitution.
+    fn substitution(
+        &mut self,
+        substitution: &Substitution,
+        depth: usize,
+    ) -> Option<Vec<Node>> {
+        // Values of type BinXml contain a fragment, which is parsed like
+        // the ones in the records.
+        if substitution.value_type == 0x21 {
+            let pos = self.pos;
+            self.pos = substitution.offset;
+            let nodes = self.fragment(&[], depth + 1);
+            self.pos = pos;
+            return nodes;
+        }
+
+        let data = self.chunk.get(
+            substitution.offset..substitution.offset + substitution.size,
+        )?;
+
+        let text =
+            render_value(substitution.value_type, data).unwrap_or_default();
+
+        if text.is_empty() {
+            Some(vec![])
+        } else {
+            Some(vec![Node::Text(text)])
+        }
+    }
+
+    /// Reads a name. Names are stored once per chunk, and referenced by
+    /// their offset. The first time a name is used, it's stored right after
+    /// the reference and must be skipped.
+    fn name(&mut self) -> Option<String> {
+        let offset = self.u32()? as usize;
+        let len = u16_at(self.chunk, offset + 6)? as usize;
+
+        if offset == self.pos {
+            self.pos += 8 + 2 * len + 2;
+        }
+
+        Some(utf16_string(self.chunk.get(offset + 8..offset + 8 + 2 * len)?))
+    }
+
+    /// Reads a UTF-16 string preceded by its length in characters.
+    fn string(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        let s = self.chunk.get(self.pos..self.pos + 2 * len)?;
+        self.pos += 2 * len;
+        Some(utf16_string(s))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.chunk.get(self.pos).copied()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let value = self.peek()?;
+        self.pos += 1;
+        Some(value)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let value = u16_at(self.chunk, self.pos)?;
+        self.pos += 2;
+        Some(value)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let value = u32_at(self.chunk, self.pos)?;
+        self.pos += 4;
+        Some(value)
+    }
+}
+
+/// Renders a substitution value as text, like in the XML produced by the
+/// Windows Event Viewer.
+fn render_value(value_type: u8, data: &[u8]) -> Option<String> {
+    let text = match value_type {
+        0x00 => String::new(),
+        0x01 => utf16_string(data),
+        0x02 => {
+            data.iter().take_while(|b| **b != 0).map(|b| *b as char).collect()
+        }
+        0x03 => (*data.first()? as i8).to_string(),
+        0x04 => data.first()?.to_string(),
+        0x05 => (u16_at(data, 0)? as i16).to_string(),
+        0x06 => u16_at(data, 0)?.to_string(),
+        0x07 => (u32_at(data, 0)? as i32).to_string(),
+        0x08 => u32_at(data, 0)?.to_string(),
+        0x09 => (u64_at(data, 0)? as i64).to_string(),
+        0x0a => u64_at(data, 0)?.to_string(),
+        0x0b => f32::from_bits(u32_at(data, 0)?).to_string(),
+        0x0c => f64::from_bits(u64_at(data, 0)?).to_string(),
+        0x0d => (u32_at(data, 0)? != 0).to_string(),
+        0x0e => {
+            let mut s = String::with_capacity(2 * data.len());
+            for b in data {
+                write!(s, "{:02X}", b).unwrap();
+            }
+            s
+        }
+        0x0f => {
+            let d4 = data.get(8..16)?;
+            format!(
+                "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+                u32_at(data, 0)?,
+                u16_at(data, 4)?,
+                u16_at(data, 6)?,
+                d4[0], d4[1], d4[2], d4[3], d4[4], d4[5], d4[6], d4[7],
+            )
+        }
+        0x10 if data.len() == 4 => format!("0x{:08x}", u32_at(data, 0)?),
+        0x10 | 0x15 => format!("0x{:016x}", u64_at(data, 0)?),
+        0x11 => {
+            let filetime = u64_at(data, 0)?;
+            let date = chrono::DateTime::from_timestamp(
+                filetime_to_epoch(filetime)?,
+                0,
+            )?;
+            format!(
+                "{}.{:07}Z",
+                date.format("%Y-%m-%dT%H:%M:%S"),
+                filetime % 10_000_000
+            )
+        }
+        0x12 => format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            u16_at(data, 0)?,
+            u16_at(data, 2)?,
+            u16_at(data, 6)?,
+            u16_at(data, 8)?,
+            u16_at(data, 10)?,
+            u16_at(data, 12)?,
+            u16_at(data, 14)?,
+        ),
+        0x13 => {
+            let count = *data.get(1)? as usize;
+            let authority = data
+                .get(2..8)?
+                .iter()
+                .fold(0_u64, |acc, b| (acc << 8) | *b as u64);
+            let mut sid = format!("S-{}-{}", data[0], authority);
+            for i in 0..count {
+                write!(sid, "-{}", u32_at(data, 8 + 4 * i)?).unwrap();
+            }
+            sid
+        }
+        0x14 => format!("0x{:08x}", u32_at(data, 0)?),
+        // Array of strings, separated by null characters.
+        0x81 => {
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+                .split('\0')
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        _ => return None,
+    };
+
+    Some(text)
+}
@@ -0,0 +1,141 @@
+/*! Reader for the build information embedded in Go binaries.
+
+Since Go 1.13 binaries contain a `.go.buildinfo` section (or an equivalent
+region in formats without sections) that starts with the magic
+`\xff Go buildinf:`. Since Go 1.18 the Go version and the module
+information follow the 32-bytes header, as strings prefixed with their
+length. Older versions store pointers to the strings instead, which are not
+supported.
+
+The module information has one item per line, with tab-separated fields:
+
+```text
+path    example.com/cmd/tool
+mod     example.com/cmd     v1.2.3  h1:...
+dep     golang.org/x/sys    v0.15.0 h1:...
+=>      ../sys              (devel)
+build   GOOS=linux
+```
+*/
+
+use bstr::ByteSlice;
+
+const MAGIC: &[u8] = b"\xff Go buildinf:";
+
+/// Flag that indicates that the strings are stored inline.
+const FLAG_INLINE: u8 = 0x2;
+
+/// A Go module.
+pub(crate) struct Module {
+    pub path: String,
+    pub version: String,
+    pub sum: String,
+    /// The module that replaces this one, with a `replace` directive.
+    pub replace: Option<Box<Module>>,
+}
+
+/// Information about how a Go binary was built.
+#[derive(Default)]
+pub(crate) struct BuildInfo {
+    /// Go version used for building the binary (e.g: "go1.21.5").
+    pub go_version: String,
+    /// Package path of the main package.
+    pub path: String,
+    pub main: Option<Module>,
+    pub deps: Vec<Module>,
+    /// Build settings, like the target OS or the flags passed to the
+    /// compiler.
+    pub settings: Vec<(String, String)>,
+}
+
+/// Finds the build information in the data and parses it. The header is
+/// aligned to 16 bytes within its section, but sections are not always
+/// aligned within the file, so all occurrences of the magic are checked.
+pub(crate) fn parse(data: &[u8]) -> Option<BuildInfo> {
+    data.find_iter(MAGIC).find_map(|offset| parse_at(&data[offset..]))
+}
+
+fn parse_at(data: &[u8]) -> Option<BuildInfo> {
+    let header = data.get(..32)?;
+
+    if header[15] & FLAG_INLINE == 0 {
+        return None;
+    }
+
+    let mut rest = &data[32..];
+    let go_version = string(&mut rest)?;
+    let mod_info = string(&mut rest)?;
+
+    if !go_version.starts_with(b"go") {
+        return None;
+    }
+
+    let mut info = BuildInfo {
+        go_version: go_version.to_str_lossy().into_owned(),
+        ..Default::default()
+    };
+
+    // The module information is surrounded by 16 bytes long sentinels.
+    let mod_info = match mod_info.len() {
+        len if len >= 33 => &mod_info[16..len - 16],
+        _ => return Some(info),
+    };
+
+    for line in mod_info.lines() {
+        let mut fields = line.split_str("\t");
+        let key = fields.next().unwrap_or_default();
+        let mut fields = fields.map(|field| field.to_str_lossy().into_owned());
+        let mut module = || Module {
+            path: fields.next().unwrap_or_default(),
+            version: fields.next().unwrap_or_default(),
+            sum: fields.next().unwrap_or_default(),
+            replace: None,
+        };
+
+        match key {
+            b"path" => info.path = module().path,
+            b"mod" => info.main = Some(module()),
+            b"dep" => info.deps.push(module()),
+            // A replacement for the module in the previous line.
+            b"=>" => {
+                let replace = Some(Box::new(module()));
+                if let Some(dep) = info.deps.last_mut() {
+                    dep.replace = replace;
+                } else if let Some(main) = info.main.as_mut() {
+                    main.replace = replace;
+                }
+            }
+            b"build" => {
+                let setting = line.get(6..).unwrap_or_default();
+                if let Some((key, value)) =
+                    setting.to_str_lossy().split_once('=')
+                {
+                    info.settings.push((key.to_string(), value.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(info)
+}
+
+/// Reads a string prefixed with its length, encoded as a varint.
+fn string<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let mut len = 0_usize;
+    let mut shift = 0_u32;
+
+    loop {
+        let (b, rest) = data.split_first()?;
+        *data = rest;
+        len |= ((b & 0x7f) as usize).checked_shl(shift)?;
+        if b & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+
+    let s = data.get(..len)?;
+    *data = &data[len..];
+    Some(s)
+}
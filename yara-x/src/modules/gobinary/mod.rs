@@ -0,0 +1,240 @@
+use crate::modules::prelude::*;
+use crate::modules::protos::gobinary::*;
+
+mod buildinfo;
+mod pclntab;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> GoBinary {
+    let data = ctx.scanned_data();
+    let mut go = GoBinary::new();
+
+    let build_info = buildinfo::parse(data);
+    let pclntab = pclntab::parse(data);
+
+    go.set_is_go(build_info.is_some() || pclntab.is_some());
+
+    if let Some(info) = build_info {
+        go.set_version(info.go_version);
+        go.set_path(info.path);
+        go.main_module = info.main.map(convert_module).into();
+        go.dependencies = info.deps.into_iter().map(convert_module).collect();
+        for (key, value) in info.settings {
+            let mut setting = BuildSetting::new();
+            setting.set_key(key);
+            setting.set_value(value);
+            go.build_settings.push(setting);
+        }
+    }
+
+    if let Some(pclntab) = pclntab {
+        go.set_pclntab_version(pclntab.version.to_string());
+        go.set_pclntab_offset(pclntab.offset as i64);
+        go.set_pointer_size(pclntab.pointer_size.into());
+        go.set_num_functions(pclntab.functions.len() as i64);
+        go.functions = pclntab.functions;
+    }
+
+    go
+}
+
+/// Returns true if the function table contains a function with the given
+/// name (e.g: `gobinary.has_function("main.main")`).
+#[module_export]
+fn has_function(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let go = ctx.module_output::<GoBinary>()?;
+    let name = name.to_str(ctx).ok()?;
+
+    Some(go.functions.iter().any(|function| function == name))
+}
+
+/// Returns true if the binary depends on the module with the given path.
+#[module_export]
+fn has_dependency(ctx: &ScanContext, path: RuntimeString) -> Option<bool> {
+    let go = ctx.module_output::<GoBinary>()?;
+    let path = path.to_str(ctx).ok()?;
+
+    Some(go.dependencies.iter().any(|dep| dep.path() == path))
+}
+
+/// Returns the value of the build setting with the given key (e.g:
+/// `gobinary.build_setting("GOOS") == "windows"`).
+#[module_export]
+fn build_setting(
+    ctx: &mut ScanContext,
+    key: RuntimeString,
+) -> Option<RuntimeString> {
+    let go = ctx.module_output::<GoBinary>()?;
+    let key = key.to_str(ctx).ok()?;
+
+    let value = go
+        .build_settings
+        .iter()
+        .find(|setting| setting.key() == key)?
+        .value()
+        .to_string();
+
+    Some(RuntimeString::from_bytes(ctx, value))
+}
+
+fn convert_module(m: buildinfo::Module) -> GoModule {
+    let mut module = GoModule::new();
+    module.set_path(m.path);
+    module.set_version(m.version);
+    module.set_sum(m.sum);
+    module.replace = m
+        .replace
+        .map(|replace| {
+            let mut replacement = GoModuleReplacement::new();
+            replacement.set_path(replace.path);
+            replacement.set_version(replace.version);
+            replacement.set_sum(replace.sum);
+            replacement
+        })
+        .into();
+    module
+}
+
+#[cfg(test)]
+mod tests {
+    /// Builds the build information as stored by Go 1.18 and later.
+    fn build_info() -> Vec<u8> {
+        let mod_info = [
+            &b"0w\xaf\x0c\x92t\x08\x02A\xe1\xc1\x07\xe6\xd6\x18\xe6"[..],
+            b"path\texample.com/stealer\n",
+            b"mod\texample.com/stealer\t(devel)\t\n",
+            b"dep\tgithub.com/kbinani/screenshot\tv0.0.0-20230812\th1:abc=\n",
+            b"dep\tgolang.org/x/sys\tv0.15.0\th1:def=\n",
+            b"=>\t../sys\t(devel)\t\n",
+            b"build\t-ldflags=\"-s -w\"\n",
+            b"build\tGOOS=windows\n",
+            b"\xf92C1\x86\x18 r\x00\x82B\x10A\x16\xd8\xf2",
+        ]
+        .concat();
+
+        let mut info = b"\xff Go buildinf:\x08\x02".to_vec();
+        info.resize(32, 0);
+        info.push(8);
+        info.extend(b"go1.21.5");
+        // The length of the module information needs two bytes.
+        info.extend([0x80 | (mod_info.len() & 0x7f) as u8]);
+        info.push((mod_info.len() >> 7) as u8);
+        info.extend(mod_info);
+        info
+    }
+
+    /// Builds a function table in the format used by Go 1.20, for a 64-bit
+    /// architecture.
+    fn pclntab(functions: &[&str]) -> Vec<u8> {
+        let header_size = 8 + 8 * 8;
+
+        let mut names = Vec::new();
+        let mut name_offsets = Vec::new();
+        for function in functions {
+            name_offsets.push(names.len() as u32);
+            names.extend(function.as_bytes());
+            names.push(0);
+        }
+
+        let names_offset = header_size;
+        let functab_offset = names_offset + names.len();
+
+        // The functab has an entry per function plus a final one, followed
+        // by the structures that describe the functions.
+        let mut functab = Vec::new();
+        let funcs_offset = (functions.len() + 1) * 8;
+        for i in 0..=functions.len() {
+            functab.extend((i as u32 * 16).to_le_bytes());
+            functab.extend(((funcs_offset + i * 8) as u32).to_le_bytes());
+        }
+        for (i, name_offset) in name_offsets.iter().enumerate() {
+            functab.extend((i as u32 * 16).to_le_bytes());
+            functab.extend(name_offset.to_le_bytes());
+        }
+
+        let mut table = b"\xf1\xff\xff\xff\x00\x00\x01\x08".to_vec();
+        for value in [
+            functions.len(),
+            0,
+            0x401000,
+            names_offset,
+            0,
+            0,
+            0,
+            functab_offset,
+        ] {
+            table.extend((value as u64).to_le_bytes());
+        }
+        table.extend(names);
+        table.extend(functab);
+        table
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "gobinary"
+                rule buildinfo {
+                  condition:
+                    gobinary.is_go and
+                    gobinary.version == "go1.21.5" and
+                    gobinary.path == "example.com/stealer" and
+                    gobinary.main_module.path == "example.com/stealer" and
+                    gobinary.main_module.version == "(devel)" and
+                    gobinary.dependencies[0].path == "github.com/kbinani/screenshot" and
+                    gobinary.dependencies[0].sum == "h1:abc=" and
+                    not defined gobinary.dependencies[0].replace.path and
+                    gobinary.dependencies[1].replace.path == "../sys" and
+                    gobinary.has_dependency("golang.org/x/sys") and
+                    gobinary.build_setting("GOOS") == "windows" and
+                    gobinary.build_setting("-ldflags") == "\"-s -w\"" and
+                    not defined gobinary.build_setting("GOARCH")
+                }
+                rule pclntab {
+                  condition:
+                    gobinary.is_go and
+                    gobinary.pclntab_version == "1.20" and
+                    gobinary.pointer_size == 8 and
+                    gobinary.num_functions == 3 and
+                    gobinary.functions[0] == "runtime.main" and
+                    gobinary.has_function("main.main") and
+                    gobinary.has_function("main.(*Stealer).Upload") and
+                    not gobinary.has_function("main")
+                }
+                rule not_go { condition: not gobinary.is_go }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let functions =
+            pclntab(&["runtime.main", "main.main", "main.(*Stealer).Upload"]);
+
+        let binary =
+            [&b"MZ"[..], &[0; 14], &build_info(), &[0; 16], &functions]
+                .concat();
+
+        assert_eq!(matching_rules(&binary), ["buildinfo", "pclntab"]);
+        assert_eq!(matching_rules(&build_info()), ["buildinfo"]);
+        assert_eq!(matching_rules(&functions), ["pclntab"]);
+
+        // A function table whose names are out of bounds.
+        let mut corrupt = functions.clone();
+        corrupt[8 + 3 * 8] = 0xff;
+        assert_eq!(matching_rules(&corrupt), ["not_go"]);
+    }
+}
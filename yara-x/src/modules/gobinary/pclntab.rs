@@ -0,0 +1,138 @@
+/*! Reader for the function table in Go binaries (pclntab).
+
+Go binaries keep a table that maps program counters to functions, which
+the runtime uses for stack traces, so it's present even in stripped
+binaries. The table starts with a header that identifies its version, and
+contains the names of all the functions in the binary.
+
+The table is located by its header instead of the section that contains
+it (`.gopclntab` in ELF, `__gopclntab` in Mach-O, part of `.rdata` in PE),
+which works with any executable format. Only little-endian binaries are
+supported.
+
+See: https://github.com/golang/go/blob/master/src/debug/gosym/pclntab.go
+*/
+
+use bstr::ByteSlice;
+
+/// Magic numbers of the table's versions, from Go 1.2 to Go 1.20 and
+/// later.
+const MAGIC_12: u32 = 0xfffffffb;
+const MAGIC_116: u32 = 0xfffffffa;
+const MAGIC_118: u32 = 0xfffffff0;
+const MAGIC_120: u32 = 0xfffffff1;
+
+/// Maximum length of function names.
+const MAX_NAME_LEN: usize = 4096;
+
+/// The function table.
+pub(crate) struct PcLnTab {
+    /// Oldest Go version that uses this table format (e.g: "1.18").
+    pub version: &'static str,
+    /// Offset of the table within the scanned data.
+    pub offset: usize,
+    pub pointer_size: u8,
+    pub functions: Vec<String>,
+}
+
+/// Finds the function table in the data and parses it.
+pub(crate) fn parse(data: &[u8]) -> Option<PcLnTab> {
+    for (magic, version) in [
+        (MAGIC_120, "1.20"),
+        (MAGIC_118, "1.18"),
+        (MAGIC_116, "1.16"),
+        (MAGIC_12, "1.2"),
+    ] {
+        let header = [&magic.to_le_bytes()[..], b"\x00\x00"].concat();
+        for offset in data.find_iter(&header) {
+            if let Some(functions) = functions(&data[offset..], magic) {
+                return Some(PcLnTab {
+                    version,
+                    offset,
+                    pointer_size: data[offset + 7],
+                    functions,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Returns the names of the functions in the table at the start of
+/// `table`, or `None` if it's not a valid table.
+fn functions(table: &[u8], magic: u32) -> Option<Vec<String>> {
+    // The minimum instruction size ("quantum") is 1 for x86, 2 for s390x
+    // and 4 for ARM.
+    let quantum = *table.get(6)?;
+    let ptr_size = *table.get(7)? as usize;
+
+    if !matches!(quantum, 1 | 2 | 4) || !matches!(ptr_size, 4 | 8) {
+        return None;
+    }
+
+    let word = |offset: usize| -> Option<usize> {
+        let bytes = table.get(offset..offset + ptr_size)?;
+        let mut value = [0; 8];
+        value[..ptr_size].copy_from_slice(bytes);
+        usize::try_from(u64::from_le_bytes(value)).ok()
+    };
+
+    let u32_at = |offset: usize| -> Option<usize> {
+        let bytes = table.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+
+    let num_functions = word(8)?;
+
+    // Each function uses at least 8 bytes in the table, which puts a limit
+    // on the number of functions in corrupt tables.
+    if num_functions == 0 || num_functions > table.len() / 8 {
+        return None;
+    }
+
+    // Offsets of the table with the functions (functab) and the table
+    // with their names. Function names are relative to the start of the
+    // names table, and the entries in the functab point to structures
+    // relative to the functab, except before Go 1.16 when everything is
+    // relative to the start of the table.
+    //
+    // Each entry in the functab has the function's entry point and the
+    // offset of the structure that describes the function. From Go 1.18
+    // both are 32-bit, before they are pointer-sized.
+    let (functab, names, entry_size, func_offset) = match magic {
+        MAGIC_118 | MAGIC_120 => {
+            (word(8 + 7 * ptr_size)?, word(8 + 3 * ptr_size)?, 8, 4)
+        }
+        MAGIC_116 => (
+            word(8 + 6 * ptr_size)?,
+            word(8 + 2 * ptr_size)?,
+            2 * ptr_size,
+            ptr_size,
+        ),
+        _ => (8 + ptr_size, 0, 2 * ptr_size, ptr_size),
+    };
+
+    let mut functions = Vec::with_capacity(num_functions.min(65536));
+
+    for i in 0..num_functions {
+        let entry = functab.checked_add(i * entry_size)?;
+        let offset = match entry_size {
+            8 => u32_at(entry + 4)?,
+            _ => word(entry + ptr_size)?,
+        };
+        // The name's offset comes right after the function's entry point
+        // in the structure that describes the function.
+        let func = if magic == MAGIC_12 {
+            offset
+        } else {
+            functab.checked_add(offset)?
+        };
+        let name_offset = u32_at(func.checked_add(func_offset)?)?;
+        let name = table.get(names.checked_add(name_offset)?..)?;
+        let name = &name[..name.len().min(MAX_NAME_LEN)];
+        let len = name.find_byte(0)?;
+        functions.push(name[..len].to_str_lossy().into_owned());
+    }
+
+    Some(functions)
+}
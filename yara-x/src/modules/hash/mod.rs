@@ -0,0 +1,456 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::thread::LocalKey;
+
+use rustc_hash::FxHashMap;
+use sha2::{Digest, Sha256};
+use xxhash_rust::xxh64::xxh64;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::hash::*;
+use crate::modules::utils::tlsh;
+
+mod ssdeep;
+
+/// Cache that maps an `(offset, size)` range of the scanned data to the hash
+/// computed for that range. Rules often compute the same hash multiple times,
+/// so this avoids hashing the same data again and again.
+type Cache<T> = RefCell<FxHashMap<(i64, i64), T>>;
+
+thread_local!(
+    static CRC32_CACHE: Cache<u32> = RefCell::new(FxHashMap::default());
+    static XXHASH64_CACHE: Cache<String> = RefCell::new(FxHashMap::default());
+    static SSDEEP_CACHE: Cache<String> = RefCell::new(FxHashMap::default());
+    static TLSH_CACHE: Cache<Option<String>> =
+        RefCell::new(FxHashMap::default());
+);
+
+#[module_main]
+fn main(_ctx: &ScanContext) -> Hash {
+    // The caches are valid only while scanning the same data, they must be
+    // cleared before every scan.
+    CRC32_CACHE.with(|cache| cache.borrow_mut().clear());
+    XXHASH64_CACHE.with(|cache| cache.borrow_mut().clear());
+    SSDEEP_CACHE.with(|cache| cache.borrow_mut().clear());
+    TLSH_CACHE.with(|cache| cache.borrow_mut().clear());
+
+    Hash::new()
+}
+
+/// Returns the CRC32 checksum of the `size` bytes starting at `offset`.
+#[module_export(name = "crc32")]
+fn crc32_data(ctx: &ScanContext, offset: i64, size: i64) -> Option<i64> {
+    let data = data_range(ctx, offset, size)?;
+    let crc = cached(&CRC32_CACHE, (offset, size), || crc32fast::hash(data));
+    Some(crc as i64)
+}
+
+/// Returns the CRC32 checksum of a string.
+#[module_export(name = "crc32")]
+fn crc32_string(ctx: &ScanContext, s: RuntimeString) -> i64 {
+    crc32fast::hash(s.as_bstr(ctx)) as i64
+}
+
+/// Returns the CRC32 checksum of the first match of a pattern.
+#[module_export(name = "crc32")]
+fn crc32_match(ctx: &ScanContext, pattern_id: PatternId) -> Option<i64> {
+    crc32_occurrence(ctx, pattern_id, 1)
+}
+
+/// Returns the CRC32 checksum of the `occurrence`-th match of a pattern.
+#[module_export(name = "crc32")]
+fn crc32_occurrence(
+    ctx: &ScanContext,
+    pattern_id: PatternId,
+    occurrence: i64,
+) -> Option<i64> {
+    let (offset, size) = match_range(ctx, pattern_id, occurrence)?;
+    crc32_data(ctx, offset, size)
+}
+
+/// Returns the xxHash64 of the `size` bytes starting at `offset`, as a
+/// hex string.
+#[module_export(name = "xxhash64")]
+fn xxhash64_data(
+    ctx: &mut ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<RuntimeString> {
+    let data = data_range(ctx, offset, size)?;
+    let digest = cached(&XXHASH64_CACHE, (offset, size), || {
+        format!("{:016x}", xxh64(data, 0))
+    });
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the xxHash64 of a string, as a hex string.
+#[module_export(name = "xxhash64")]
+fn xxhash64_string(ctx: &mut ScanContext, s: RuntimeString) -> RuntimeString {
+    let digest = format!("{:016x}", xxh64(s.as_bstr(ctx), 0));
+    RuntimeString::from_bytes(ctx, digest)
+}
+
+/// Returns the xxHash64 of the first match of a pattern, as a hex string.
+#[module_export(name = "xxhash64")]
+fn xxhash64_match(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+) -> Option<RuntimeString> {
+    xxhash64_occurrence(ctx, pattern_id, 1)
+}
+
+/// Returns the xxHash64 of the `occurrence`-th match of a pattern, as a hex
+/// string.
+#[module_export(name = "xxhash64")]
+fn xxhash64_occurrence(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+    occurrence: i64,
+) -> Option<RuntimeString> {
+    let (offset, size) = match_range(ctx, pattern_id, occurrence)?;
+    xxhash64_data(ctx, offset, size)
+}
+
+/// Returns the ssdeep digest of the `size` bytes starting at `offset`.
+#[module_export(name = "ssdeep")]
+fn ssdeep_data(
+    ctx: &mut ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<RuntimeString> {
+    let data = data_range(ctx, offset, size)?;
+    let digest =
+        cached(&SSDEEP_CACHE, (offset, size), || ssdeep::digest(data));
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the ssdeep digest of a string.
+#[module_export(name = "ssdeep")]
+fn ssdeep_string(ctx: &mut ScanContext, s: RuntimeString) -> RuntimeString {
+    let digest = ssdeep::digest(s.as_bstr(ctx));
+    RuntimeString::from_bytes(ctx, digest)
+}
+
+/// Returns the ssdeep digest of the first match of a pattern.
+#[module_export(name = "ssdeep")]
+fn ssdeep_match(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+) -> Option<RuntimeString> {
+    ssdeep_occurrence(ctx, pattern_id, 1)
+}
+
+/// Returns the ssdeep digest of the `occurrence`-th match of a pattern.
+#[module_export(name = "ssdeep")]
+fn ssdeep_occurrence(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+    occurrence: i64,
+) -> Option<RuntimeString> {
+    let (offset, size) = match_range(ctx, pattern_id, occurrence)?;
+    ssdeep_data(ctx, offset, size)
+}
+
+/// Compares two ssdeep digests and returns a similarity score between 0
+/// (completely different) and 100 (identical).
+///
+/// Returns undefined if any of the digests is not a valid ssdeep digest.
+#[module_export]
+fn ssdeep_compare(
+    ctx: &ScanContext,
+    digest1: RuntimeString,
+    digest2: RuntimeString,
+) -> Option<i64> {
+    ssdeep::compare(digest1.to_str(ctx).ok()?, digest2.to_str(ctx).ok()?)
+}
+
+/// Returns the TLSH digest of the `size` bytes starting at `offset`.
+///
+/// Returns undefined if the data is too short or doesn't have enough
+/// variability for computing the digest.
+#[module_export(name = "tlsh")]
+fn tlsh_data(
+    ctx: &mut ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<RuntimeString> {
+    let data = data_range(ctx, offset, size)?;
+    let digest = cached(&TLSH_CACHE, (offset, size), || tlsh::digest(data))?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the TLSH digest of a string.
+///
+/// Returns undefined if the string is too short or doesn't have enough
+/// variability for computing the digest.
+#[module_export(name = "tlsh")]
+fn tlsh_string(
+    ctx: &mut ScanContext,
+    s: RuntimeString,
+) -> Option<RuntimeString> {
+    let digest = tlsh::digest(s.as_bstr(ctx))?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the TLSH digest of the first match of a pattern.
+#[module_export(name = "tlsh")]
+fn tlsh_match(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+) -> Option<RuntimeString> {
+    tlsh_occurrence(ctx, pattern_id, 1)
+}
+
+/// Returns the TLSH digest of the `occurrence`-th match of a pattern.
+#[module_export(name = "tlsh")]
+fn tlsh_occurrence(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+    occurrence: i64,
+) -> Option<RuntimeString> {
+    let (offset, size) = match_range(ctx, pattern_id, occurrence)?;
+    tlsh_data(ctx, offset, size)
+}
+
+/// Returns the distance between two TLSH digests. The distance is 0 for
+/// identical digests, and grows as the digests are more different.
+///
+/// Returns undefined if any of the digests is not a valid TLSH digest.
+#[module_export]
+fn tlsh_diff(
+    ctx: &ScanContext,
+    digest1: RuntimeString,
+    digest2: RuntimeString,
+) -> Option<i64> {
+    tlsh::diff(digest1.to_str(ctx).ok()?, digest2.to_str(ctx).ok()?)
+}
+
+/// Returns the SHA-256 of the concatenation of all the matches of a pattern,
+/// in the order they appear in the scanned data, as a hex string.
+///
+/// Returns undefined if the pattern didn't match.
+#[module_export]
+fn sha256_of_matches(
+    ctx: &mut ScanContext,
+    pattern_id: PatternId,
+) -> Option<RuntimeString> {
+    let matches = ctx.pattern_matches.get(&pattern_id)?;
+
+    if matches.is_empty() {
+        return None;
+    }
+
+    let data = ctx.scanned_data();
+    let mut hasher = Sha256::new();
+
+    for m in matches.iter() {
+        hasher.update(data.get(m.range.clone())?);
+    }
+
+    let mut digest = String::with_capacity(64);
+    for b in hasher.finalize() {
+        write!(digest, "{:02x}", b).unwrap();
+    }
+
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the `size` bytes of scanned data starting at `offset`. If the
+/// range exceeds the end of the data, it's truncated. Returns `None` if
+/// `offset` or `size` are negative, or `offset` is beyond the end of the
+/// data.
+fn data_range<'a>(
+    ctx: &'a ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<&'a [u8]> {
+    let data = ctx.scanned_data();
+    let start: usize = offset.try_into().ok()?;
+    let size: usize = size.try_into().ok()?;
+
+    if start >= data.len() {
+        return None;
+    }
+
+    let end = start.saturating_add(size).min(data.len());
+
+    Some(&data[start..end])
+}
+
+/// Returns the offset and length of the `occurrence`-th match of a pattern.
+/// Occurrences are numbered starting at 1, like in `@a[1]`. Returns `None`
+/// if the pattern doesn't have so many matches.
+fn match_range(
+    ctx: &ScanContext,
+    pattern_id: PatternId,
+    occurrence: i64,
+) -> Option<(i64, i64)> {
+    let index: usize = occurrence.checked_sub(1)?.try_into().ok()?;
+    let m = ctx.pattern_matches.get(&pattern_id)?.get(index)?;
+    Some((m.range.start as i64, m.range.len() as i64))
+}
+
+/// Returns the value stored in `cache` for `key`, computing it with `f` if
+/// it's not in the cache yet.
+fn cached<T: Clone>(
+    cache: &'static LocalKey<Cache<T>>,
+    key: (i64, i64),
+    f: impl FnOnce() -> T,
+) -> T {
+    cache.with(|cache| cache.borrow_mut().entry(key).or_insert_with(f).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn end2end() {
+        let data = b"123456789";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "hash"
+                rule rule_1 { condition: hash.crc32(0, filesize) == 0xcbf43926 }
+                rule rule_2 { condition: hash.crc32(0, filesize) == hash.crc32("123456789") }
+                rule rule_3 { condition: hash.crc32("") == 0 }
+                rule rule_4 { condition: hash.xxhash64("") == "ef46db3751d8e999" }
+                rule rule_5 { condition: hash.xxhash64(0, filesize) == hash.xxhash64("123456789") }
+                rule rule_6 { condition: hash.ssdeep("") == "3::" }
+                rule rule_7 { condition: hash.ssdeep(0, filesize) == hash.ssdeep("123456789") }
+                rule rule_8 { condition: not defined hash.tlsh(0, filesize) }
+                rule rule_9 { condition: not defined hash.crc32(100, 1) }
+                rule rule_10 { condition: not defined hash.ssdeep_compare("foo", "3::") }
+                rule rule_11 { condition: not defined hash.tlsh_diff("foo", "bar") }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(data).unwrap().matching_rules().len(), 11);
+    }
+
+    #[test]
+    fn match_hashes() {
+        let data = b"foo 123456789 bar 987654321";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "hash"
+                rule rule_1 {
+                  strings:
+                    $a = /[0-9]{9}/
+                    $b = "baz"
+                  condition:
+                    hash.crc32($a) == 0xcbf43926 and
+                    hash.crc32($a, 1) == hash.crc32("123456789") and
+                    hash.crc32($a, 2) == hash.crc32(@a[2], !a[2]) and
+                    hash.xxhash64($a, 2) == hash.xxhash64("987654321") and
+                    hash.ssdeep($a) == hash.ssdeep("123456789") and
+                    not defined hash.crc32($a, 3) and
+                    not defined hash.crc32($a, 0) and
+                    not defined hash.crc32($b) and
+                    not defined hash.tlsh($a)
+                }
+                rule rule_2 {
+                  strings:
+                    $a = "foo"
+                    $b = "bar"
+                  condition:
+                    for all of them : (hash.crc32($) == hash.crc32(@[1], 3))
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(data).unwrap().matching_rules().len(), 2);
+    }
+
+    #[test]
+    fn hash_of_matches() {
+        let data = b"foo 123456789 bar 987654321";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "hash"
+                rule rule_1 {
+                  strings:
+                    $a = /[0-9]{9}/
+                  condition:
+                    hash.sha256_of_matches($a) ==
+                      "5ffed2e1bd56b37e3a974a269a1c26a9af87f6854ca60448473a729256ee040f"
+                }
+                rule rule_2 {
+                  strings:
+                    $a = "foo"
+                    $b = "baz"
+                  condition:
+                    $a and not defined hash.sha256_of_matches($b)
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(data).unwrap().matching_rules().len(), 2);
+    }
+
+    #[test]
+    fn fuzzy_hashes() {
+        let data: Vec<u8> = (0..4096_u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "hash"
+                rule rule_1 {
+                  condition:
+                    hash.ssdeep_compare(
+                        hash.ssdeep(0, filesize),
+                        hash.ssdeep(0, filesize)) == 100
+                }
+                rule rule_2 {
+                  condition:
+                    hash.ssdeep_compare(
+                        hash.ssdeep(0, filesize),
+                        hash.ssdeep(0, 2048)) < 100
+                }
+                rule rule_3 {
+                  condition:
+                    hash.tlsh_diff(
+                        hash.tlsh(0, filesize),
+                        hash.tlsh(0, filesize)) == 0
+                }
+                rule rule_4 {
+                  condition:
+                    hash.tlsh_diff(
+                        hash.tlsh(0, filesize),
+                        hash.tlsh(0, 2048)) > 0
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(
+            scanner.scan(data.as_slice()).unwrap().matching_rules().len(),
+            4
+        );
+    }
+}
@@ -0,0 +1,323 @@
+/*! Implementation of ssdeep, a context triggered piecewise hash.
+
+The digests produced here are the same produced by the `ssdeep` tool, and
+the comparison function follows the one implemented by `libfuzzy`. The
+digest has the form `<block size>:<hash1>:<hash2>`, where `hash1` is
+computed with the given block size and `hash2` with twice that block size.
+*/
+
+use bstr::{BString, ByteSlice};
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCK_SIZE: u64 = 3;
+const HASH_PRIME: u32 = 0x01000193;
+const HASH_INIT: u32 = 0x28021967;
+const SPAMSUM_LENGTH: usize = 64;
+const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Rolling hash computed over the last [`ROLLING_WINDOW`] bytes. It
+/// determines the points in which the data is split into pieces.
+#[derive(Default)]
+struct RollingHash {
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+    window: [u8; ROLLING_WINDOW],
+}
+
+impl RollingHash {
+    /// Adds a byte to the rolling hash and returns the updated hash.
+    fn update(&mut self, byte: u8) -> u32 {
+        let c = byte as u32;
+
+        self.h2 = self
+            .h2
+            .wrapping_sub(self.h1)
+            .wrapping_add(ROLLING_WINDOW as u32 * c);
+
+        self.h1 =
+            self.h1.wrapping_add(c).wrapping_sub(self.window[self.n] as u32);
+
+        self.window[self.n] = byte;
+        self.n = (self.n + 1) % ROLLING_WINDOW;
+        self.h3 = (self.h3 << 5) ^ c;
+
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// Hash function used for each of the pieces in which the data is split.
+#[inline]
+fn sum_hash(byte: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ byte as u32
+}
+
+/// Computes the ssdeep digest for `data`.
+pub(crate) fn digest(data: &[u8]) -> String {
+    let mut block_size = MIN_BLOCK_SIZE;
+
+    while block_size * (SPAMSUM_LENGTH as u64) < data.len() as u64 {
+        block_size *= 2;
+    }
+
+    loop {
+        let mut rolling_hash = RollingHash::default();
+        let mut roll = 0;
+
+        // `hash1` uses `block_size`, while `hash2` uses twice that size and
+        // is limited to half the length.
+        let mut hash1 = Piecewise::new(block_size, SPAMSUM_LENGTH);
+        let mut hash2 = Piecewise::new(block_size * 2, SPAMSUM_LENGTH / 2);
+
+        for byte in data {
+            roll = rolling_hash.update(*byte) as u64;
+            hash1.update(*byte, roll);
+            hash2.update(*byte, roll);
+        }
+
+        // If the block size produced too few pieces, try again with a smaller
+        // block size.
+        if block_size > MIN_BLOCK_SIZE
+            && hash1.digest.len() < SPAMSUM_LENGTH / 2
+        {
+            block_size /= 2;
+            continue;
+        }
+
+        return format!(
+            "{}:{}:{}",
+            block_size,
+            hash1.finish(roll != 0),
+            hash2.finish(roll != 0)
+        );
+    }
+}
+
+/// One of the two hashes in a ssdeep digest.
+struct Piecewise {
+    block_size: u64,
+    max_len: usize,
+    h: u32,
+    digest: Vec<u8>,
+    /// Last character of the digest. Once the digest reaches its maximum
+    /// length, this character keeps changing until the end of the data.
+    last: Option<u8>,
+}
+
+impl Piecewise {
+    fn new(block_size: u64, max_len: usize) -> Self {
+        Self {
+            block_size,
+            max_len,
+            h: HASH_INIT,
+            digest: Vec::with_capacity(max_len),
+            last: None,
+        }
+    }
+
+    fn update(&mut self, byte: u8, roll: u64) {
+        self.h = sum_hash(byte, self.h);
+        if roll % self.block_size == self.block_size - 1 {
+            if self.digest.len() < self.max_len - 1 {
+                self.digest.push(BASE64[(self.h % 64) as usize]);
+                self.h = HASH_INIT;
+            } else {
+                self.last = Some(BASE64[(self.h % 64) as usize]);
+            }
+        }
+    }
+
+    /// Returns the digest. `trailing_piece` indicates whether the data ends
+    /// with a piece that is not followed by a trigger point.
+    fn finish(mut self, trailing_piece: bool) -> String {
+        if trailing_piece {
+            self.last = Some(BASE64[(self.h % 64) as usize]);
+        }
+        self.digest.extend(self.last);
+        // The digest contains only characters from BASE64.
+        String::from_utf8(self.digest).unwrap()
+    }
+}
+
+/// Compares two ssdeep digests and returns a score between 0 and 100.
+///
+/// Returns `None` if any of the digests is not valid.
+pub(crate) fn compare(digest1: &str, digest2: &str) -> Option<i64> {
+    let (block_size1, a1, a2) = parse(digest1)?;
+    let (block_size2, b1, b2) = parse(digest2)?;
+
+    // Digests can be compared only if their block sizes are equal, or one is
+    // twice the other.
+    if block_size1 != block_size2
+        && block_size1.checked_mul(2) != Some(block_size2)
+        && block_size2.checked_mul(2) != Some(block_size1)
+    {
+        return Some(0);
+    }
+
+    if block_size1 == block_size2 && a1 == b1 && a2 == b2 {
+        return Some(100);
+    }
+
+    let score = if block_size1 == block_size2 {
+        score_strings(&a1, &b1, block_size1).max(score_strings(
+            &a2,
+            &b2,
+            block_size1 * 2,
+        ))
+    } else if block_size1 * 2 == block_size2 {
+        score_strings(&b1, &a2, block_size2)
+    } else {
+        score_strings(&a1, &b2, block_size1)
+    };
+
+    Some(score as i64)
+}
+
+/// Splits a digest into its block size and its two hashes, removing
+/// sequences of more than three identical characters from the hashes.
+fn parse(digest: &str) -> Option<(u64, BString, BString)> {
+    let mut parts = digest.splitn(3, ':');
+
+    let block_size: u64 = parts.next()?.parse().ok()?;
+    let hash1 = parts.next()?;
+    // Digests generated by the `ssdeep` tool may be followed by a comma and
+    // the file name.
+    let hash2 = parts.next()?.split(',').next()?;
+
+    if block_size == 0
+        || hash1.len() > SPAMSUM_LENGTH
+        || hash2.len() > SPAMSUM_LENGTH
+    {
+        return None;
+    }
+
+    Some((
+        block_size,
+        eliminate_sequences(hash1.as_bytes()),
+        eliminate_sequences(hash2.as_bytes()),
+    ))
+}
+
+fn eliminate_sequences(s: &[u8]) -> BString {
+    let mut result = Vec::with_capacity(s.len());
+    for (i, c) in s.iter().enumerate() {
+        if i < 3 || s[i - 1] != *c || s[i - 2] != *c || s[i - 3] != *c {
+            result.push(*c);
+        }
+    }
+    result.into()
+}
+
+fn score_strings(s1: &[u8], s2: &[u8], block_size: u64) -> u64 {
+    // Strings that don't have at least one substring of ROLLING_WINDOW bytes
+    // in common are considered completely different.
+    if s1.len() < ROLLING_WINDOW
+        || s2.len() < ROLLING_WINDOW
+        || !s1.windows(ROLLING_WINDOW).any(|w| s2.find(w).is_some())
+    {
+        return 0;
+    }
+
+    let len = (s1.len() + s2.len()) as u64;
+    let mut score = edit_distance(s1, s2) * SPAMSUM_LENGTH as u64 / len;
+    score = 100 * score / SPAMSUM_LENGTH as u64;
+
+    if score >= 100 {
+        return 0;
+    }
+
+    score = 100 - score;
+
+    // With small block sizes the score is limited, as the hashes of small
+    // inputs are more likely to be similar.
+    let uncapped_block_size =
+        (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64 * MIN_BLOCK_SIZE;
+
+    if block_size < uncapped_block_size {
+        let max_score =
+            block_size / MIN_BLOCK_SIZE * s1.len().min(s2.len()) as u64;
+        score = score.min(max_score);
+    }
+
+    score
+}
+
+/// Computes the edit distance between two strings, where insertions and
+/// deletions cost 1, and substitutions cost 2.
+fn edit_distance(s1: &[u8], s2: &[u8]) -> u64 {
+    let mut prev: Vec<u64> = (0..=s2.len() as u64).collect();
+    let mut curr = vec![0; s2.len() + 1];
+
+    for (i, c1) in s1.iter().enumerate() {
+        curr[0] = i as u64 + 1;
+        for (j, c2) in s2.iter().enumerate() {
+            let substitution = prev[j] + if c1 == c2 { 0 } else { 2 };
+            curr[j + 1] = (prev[j + 1] + 1).min(curr[j] + 1).min(substitution);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[s2.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{compare, digest, edit_distance, eliminate_sequences};
+
+    #[test]
+    fn ssdeep_digest() {
+        assert_eq!(digest(b""), "3::");
+
+        let data: Vec<u8> = (0..100_000_u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 7) as u8)
+            .collect();
+
+        let d = digest(&data);
+        let mut parts = d.split(':');
+        let block_size: u64 = parts.next().unwrap().parse().unwrap();
+
+        assert!(block_size * 64 >= data.len() as u64 / 2);
+        assert!(parts.next().unwrap().len() <= 64);
+        assert!(parts.next().unwrap().len() <= 32);
+    }
+
+    #[test]
+    fn ssdeep_compare() {
+        assert_eq!(compare("3::", "3::"), Some(100));
+        assert_eq!(
+            compare("3:AAAAAAAA:BBBB", "3:AAAAAAAAAAA:BBBB"),
+            Some(100)
+        );
+        assert_eq!(compare("3:abcdefgh:", "12:abcdefgh:"), Some(0));
+        assert_eq!(compare("foo", "3::"), None);
+        assert_eq!(compare("3:abc", "3::"), None);
+
+        assert_eq!(
+            compare(
+                "96:abcdefghijklmnopqrstuvwxyz:abcdefghijklm",
+                "96:abcdefghijklmnopqrstuvwxyZ:abcdefghijklm"
+            ),
+            Some(100)
+        );
+
+        assert_eq!(
+            compare(
+                "96:abcdefghijklmnopqrstuvwxyz:abcdefghijklm",
+                "96:abcdefghijklmnopqrstuvwxAB:zyxwvutsrqpon"
+            ),
+            Some(94)
+        );
+    }
+
+    #[test]
+    fn ssdeep_helpers() {
+        assert_eq!(eliminate_sequences(b"aaaaabbbc"), "aaabbbc");
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 5);
+        assert_eq!(edit_distance(b"", b"abc"), 3);
+    }
+}
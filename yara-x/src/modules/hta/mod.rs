@@ -0,0 +1,148 @@
+/*! YARA module that analyzes HTML applications (.hta).
+
+HTML applications are HTML documents executed by `mshta.exe`, outside the
+browser's security model, which makes them a common vehicle for scripts
+written in VBScript or JScript. The module reports the attributes of the
+`<HTA:APPLICATION>` tag and the scripts found in the document.
+*/
+
+use crate::modules::prelude::*;
+use crate::modules::protos::hta::*;
+use crate::modules::utils::html;
+
+/// Maximum number of scripts.
+const MAX_SCRIPTS: usize = 16384;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Hta {
+    let data = ctx.scanned_data();
+    let mut hta = Hta::new();
+
+    // Skip the UTF-8 byte order mark and whitespaces.
+    let start = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let start = start.iter().find(|c| !c.is_ascii_whitespace());
+
+    if start != Some(&b'<') {
+        hta.set_is_html(false);
+        return hta;
+    }
+
+    hta.set_is_html(true);
+
+    let mut pos = 0;
+    while let Some(tag) = html::next_tag(data, pos) {
+        pos = tag.end;
+        if tag.name != "hta:application" {
+            continue;
+        }
+
+        let attribute = |name| tag.attribute(name).map(String::from);
+        let mut application = Application::new();
+
+        application.set_offset(tag.start as i64);
+        application.id = attribute("id");
+        application.application_name = attribute("applicationname");
+        application.border = attribute("border");
+        application.caption = attribute("caption");
+        application.icon = attribute("icon");
+        application.show_in_taskbar = attribute("showintaskbar");
+        application.single_instance = attribute("singleinstance");
+        application.sys_menu = attribute("sysmenu");
+        application.version = attribute("version");
+        application.window_state = attribute("windowstate");
+
+        hta.application = Some(application).into();
+        break;
+    }
+
+    hta.set_is_hta(hta.application.is_some());
+
+    let scripts = html::scripts(data);
+
+    hta.set_scripts_truncated(scripts.len() > MAX_SCRIPTS);
+
+    for script in scripts.into_iter().take(MAX_SCRIPTS) {
+        let mut s = HtaScript::new();
+        s.language = script.language;
+        s.src = script.src;
+        s.set_offset(script.offset as i64);
+        s.set_size(script.size as i64);
+        hta.scripts.push(s);
+    }
+
+    hta
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "hta"
+                rule application {
+                  condition:
+                    hta.is_html and
+                    hta.is_hta and
+                    hta.application.offset == 23 and
+                    hta.application.application_name == "Update" and
+                    hta.application.show_in_taskbar == "no" and
+                    hta.application.window_state == "minimize" and
+                    not defined hta.application.icon
+                }
+                rule scripts {
+                  condition:
+                    not hta.scripts_truncated and
+                    hta.scripts[0].language == "VBScript" and
+                    hta.scripts[0].offset == 140 and
+                    hta.scripts[0].size == 18 and
+                    hta.scripts[1].src == "a.js" and
+                    hta.scripts[1].size == 0
+                }
+                rule html_only {
+                  condition:
+                    hta.is_html and not hta.is_hta and
+                    hta.scripts[0].language == "text/javascript"
+                }
+                rule not_html {
+                  condition:
+                    not hta.is_html
+                }"#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            let results = scanner.scan(data).unwrap();
+            results
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(
+                b"<html><!-- <script> --><HTA:APPLICATION ID=app \
+                  applicationName='Update' SHOWINTASKBAR=\"no\" \
+                  windowState=minimize /><script language=VBScript>\
+                  CreateObject(\"x\")\n</script><script src=\"a.js\">\
+                  </SCRIPT>"
+            ),
+            ["application", "scripts"]
+        );
+
+        assert_eq!(
+            matching_rules(
+                b"\xef\xbb\xbf\r\n<html><script type=\"text/javascript\">\
+                  alert(1)</script>"
+            ),
+            ["html_only"]
+        );
+
+        assert_eq!(matching_rules(b"MZ<html>"), ["not_html"]);
+    }
+}
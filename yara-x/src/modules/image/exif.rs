@@ -0,0 +1,87 @@
+use crate::modules::protos::image::Exif;
+
+/// Tag of the IFD with GPS information.
+const GPS_IFD: u16 = 0x8825;
+
+/// Parses an EXIF block, which has the structure of a TIFF file. Only the
+/// tags in the first IFD (Image File Directory) are used.
+pub fn parse(tiff: &[u8]) -> Option<Exif> {
+    let big_endian = match tiff.get(..4)? {
+        b"II*\0" => false,
+        b"MM\0*" => true,
+        _ => return None,
+    };
+
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().unwrap();
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().unwrap();
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let num_entries = u16_at(ifd)? as usize;
+    let mut exif = Exif::new();
+
+    exif.set_has_gps(false);
+
+    for i in 0..num_entries {
+        let entry = ifd + 2 + 12 * i;
+        let tag = match u16_at(entry) {
+            Some(tag) => tag,
+            None => break,
+        };
+
+        if tag == GPS_IFD {
+            exif.set_has_gps(true);
+            continue;
+        }
+
+        // Only ASCII values are used, they are stored in the entry itself
+        // when they fit in 4 bytes.
+        if u16_at(entry + 2) != Some(2) {
+            continue;
+        }
+
+        let count = u32_at(entry + 4).unwrap_or(0) as usize;
+        let value = if count <= 4 {
+            tiff.get(entry + 8..entry + 8 + count)
+        } else {
+            u32_at(entry + 8)
+                .and_then(|offset| tiff.get(offset as usize..)?.get(..count))
+        };
+
+        let value = match value {
+            Some(value) => String::from_utf8_lossy(
+                value.split(|b| *b == 0).next().unwrap_or_default(),
+            )
+            .trim()
+            .to_string(),
+            None => continue,
+        };
+
+        match tag {
+            0x010e => exif.image_description = Some(value),
+            0x010f => exif.make = Some(value),
+            0x0110 => exif.model = Some(value),
+            0x0131 => exif.software = Some(value),
+            0x0132 => exif.date_time = Some(value),
+            0x013b => exif.artist = Some(value),
+            0x8298 => exif.copyright = Some(value),
+            _ => {}
+        }
+    }
+
+    Some(exif)
+}
@@ -0,0 +1,79 @@
+use crate::modules::image::chunk;
+use crate::modules::protos::image::*;
+
+pub const SIGNATURES: &[&[u8]] = &[b"GIF87a", b"GIF89a"];
+
+/// Parses a GIF image. Returns the offset where the image ends, right after
+/// the trailer byte.
+///
+/// After the header and the optional global color table, the image is a
+/// sequence of blocks. Image descriptors and extensions are followed by
+/// sub-blocks, each one starting with its size, and terminated by an empty
+/// sub-block.
+pub fn parse(data: &[u8], image: &mut Image) -> usize {
+    let header = match data.get(..13) {
+        Some(header) => header,
+        None => return data.len(),
+    };
+
+    image.set_width(u16::from_le_bytes([header[6], header[7]]).into());
+    image.set_height(u16::from_le_bytes([header[8], header[9]]).into());
+
+    let mut offset = 13 + color_table_size(header[10]);
+
+    while let Some(block) = data.get(offset) {
+        let (block_type, sub_blocks) = match block {
+            // Image descriptor, followed by the local color table, if any,
+            // and by the minimum code size of the LZW-compressed data.
+            0x2c => match data.get(offset + 9) {
+                Some(flags) => {
+                    ("IMAGE", offset + 10 + color_table_size(*flags) + 1)
+                }
+                None => break,
+            },
+            0x21 => match data.get(offset + 1) {
+                Some(0xf9) => ("GRAPHIC_CONTROL", offset + 2),
+                Some(0xfe) => ("COMMENT", offset + 2),
+                Some(0xff) => ("APPLICATION", offset + 2),
+                Some(0x01) => ("PLAIN_TEXT", offset + 2),
+                Some(_) => ("EXTENSION", offset + 2),
+                None => break,
+            },
+            // Trailer.
+            0x3b => return offset + 1,
+            _ => break,
+        };
+
+        let end = match skip_sub_blocks(data, sub_blocks) {
+            Some(end) => end,
+            None => break,
+        };
+
+        image.chunks.push(chunk(block_type.to_string(), offset, end - offset));
+
+        offset = end;
+    }
+
+    data.len()
+}
+
+/// Returns the size of the color table described by the flags in the
+/// header or in an image descriptor.
+fn color_table_size(flags: u8) -> usize {
+    if flags & 0x80 != 0 {
+        3 * (1 << ((flags & 0x07) + 1))
+    } else {
+        0
+    }
+}
+
+/// Returns the offset that follows the sub-blocks starting at `offset`.
+fn skip_sub_blocks(data: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let size = *data.get(offset)? as usize;
+        offset += 1 + size;
+        if size == 0 {
+            return Some(offset);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use protobuf::MessageField;
+
+use crate::modules::image::{chunk, exif};
+use crate::modules::protos::image::*;
+
+pub const SIGNATURE: &[u8] = b"\xff\xd8\xff";
+
+/// Parses a JPEG image. Returns the offset where the image ends, right
+/// after the EOI (End Of Image) marker.
+///
+/// The image is a sequence of segments, each one starting with a marker
+/// (0xFF followed by the marker type). Most segments are followed by their
+/// length, except for a few standalone markers. The SOS (Start Of Scan)
+/// segment is followed by entropy-coded data, which ends at the next
+/// marker that is not a restart marker (RSTn) or an escaped 0xFF byte.
+pub fn parse(data: &[u8], image: &mut Image) -> usize {
+    let mut offset = 0;
+
+    while offset + 1 < data.len() {
+        if data[offset] != 0xff {
+            break;
+        }
+
+        let marker = data[offset + 1];
+
+        // Fill bytes can precede a marker.
+        if marker == 0xff {
+            offset += 1;
+            continue;
+        }
+
+        // Standalone markers, which are not followed by a length.
+        if matches!(marker, 0x01 | 0xd0..=0xd9) {
+            image.chunks.push(chunk(marker_name(marker), offset, 2));
+            offset += 2;
+            // End Of Image.
+            if marker == 0xd9 {
+                return offset;
+            }
+            continue;
+        }
+
+        let length = match data.get(offset + 2..offset + 4) {
+            Some(length) => {
+                u16::from_be_bytes([length[0], length[1]]) as usize
+            }
+            None => break,
+        };
+
+        let segment = match data.get(offset + 4..offset + 2 + length) {
+            Some(segment) if length >= 2 => segment,
+            _ => break,
+        };
+
+        let mut end = offset + 2 + length;
+
+        match marker {
+            // Start Of Frame markers, except DHT, JPG and DAC, which share
+            // the same range.
+            0xc0..=0xcf
+                if !matches!(marker, 0xc4 | 0xc8 | 0xcc)
+                    && segment.len() >= 5 =>
+            {
+                image.set_height(
+                    u16::from_be_bytes([segment[1], segment[2]]).into(),
+                );
+                image.set_width(
+                    u16::from_be_bytes([segment[3], segment[4]]).into(),
+                );
+            }
+            // APP1, which contains the EXIF block.
+            0xe1 if segment.starts_with(b"Exif\0\0")
+                && image.exif.is_none() =>
+            {
+                image.exif =
+                    MessageField::from_option(exif::parse(&segment[6..]));
+            }
+            // Start Of Scan, followed by the entropy-coded data.
+            0xda => {
+                while end + 1 < data.len() {
+                    if data[end] == 0xff
+                        && !matches!(data[end + 1], 0x00 | 0xd0..=0xd7)
+                    {
+                        break;
+                    }
+                    end += 1;
+                }
+                if end + 1 >= data.len() {
+                    end = data.len();
+                }
+            }
+            _ => {}
+        }
+
+        image.chunks.push(chunk(marker_name(marker), offset, end - offset));
+        offset = end;
+    }
+
+    data.len()
+}
+
+/// Returns the name of a marker.
+fn marker_name(marker: u8) -> String {
+    match marker {
+        0xc4 => "DHT".to_string(),
+        0xc8 => "JPG".to_string(),
+        0xcc => "DAC".to_string(),
+        0xc0..=0xcf => format!("SOF{}", marker - 0xc0),
+        0xd0..=0xd7 => format!("RST{}", marker - 0xd0),
+        0xd8 => "SOI".to_string(),
+        0xd9 => "EOI".to_string(),
+        0xda => "SOS".to_string(),
+        0xdb => "DQT".to_string(),
+        0xdd => "DRI".to_string(),
+        0xe0..=0xef => format!("APP{}", marker - 0xe0),
+        0xfe => "COM".to_string(),
+        _ => format!("0x{:02X}", marker),
+    }
+}
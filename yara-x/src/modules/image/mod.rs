@@ -0,0 +1,222 @@
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::image::*;
+
+mod exif;
+mod gif;
+mod jpeg;
+mod png;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Image {
+    let data = ctx.scanned_data();
+    let mut image = Image::new();
+
+    // Each parser returns the offset where the image ends, which is the
+    // size of the scanned data if the image is truncated.
+    let parsed = if data.starts_with(jpeg::SIGNATURE) {
+        Some((ImageFormat::JPEG, jpeg::parse(data, &mut image)))
+    } else if data.starts_with(png::SIGNATURE) {
+        Some((ImageFormat::PNG, png::parse(data, &mut image)))
+    } else if gif::SIGNATURES.iter().any(|s| data.starts_with(s)) {
+        Some((ImageFormat::GIF, gif::parse(data, &mut image)))
+    } else {
+        None
+    };
+
+    match parsed {
+        Some((format, end)) => {
+            image.set_is_image(true);
+            image.format = Some(EnumOrUnknown::new(format));
+            image.set_trailing_data_offset(end as i64);
+            image.set_trailing_data_size((data.len() - end) as i64);
+        }
+        None => image.set_is_image(false),
+    }
+
+    image
+}
+
+/// Returns a chunk with the given type, offset and length.
+fn chunk(chunk_type: String, offset: usize, length: usize) -> Chunk {
+    let mut chunk = Chunk::new();
+    chunk.set_type(chunk_type);
+    chunk.set_offset(offset as i64);
+    chunk.set_length(length as i64);
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    fn png_chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let type_and_data = [chunk_type, data].concat();
+        [
+            &(data.len() as u32).to_be_bytes()[..],
+            &type_and_data,
+            &crc32fast::hash(&type_and_data).to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    /// Returns an EXIF block in big-endian byte order, with the `Software`
+    /// tag and a pointer to the GPS IFD.
+    fn exif() -> Vec<u8> {
+        let software = b"Photoshop 7.0\0";
+        let mut tiff = b"MM\0*".to_vec();
+        tiff.extend(8_u32.to_be_bytes());
+        tiff.extend(2_u16.to_be_bytes());
+        // Software, ASCII, stored after the IFD.
+        tiff.extend(0x0131_u16.to_be_bytes());
+        tiff.extend(2_u16.to_be_bytes());
+        tiff.extend((software.len() as u32).to_be_bytes());
+        tiff.extend(38_u32.to_be_bytes());
+        // GPS IFD pointer.
+        tiff.extend(0x8825_u16.to_be_bytes());
+        tiff.extend(4_u16.to_be_bytes());
+        tiff.extend(1_u32.to_be_bytes());
+        tiff.extend(0_u32.to_be_bytes());
+        // Offset of the next IFD.
+        tiff.extend(0_u32.to_be_bytes());
+        tiff.extend(software);
+        tiff
+    }
+
+    fn build_png() -> Vec<u8> {
+        let mut ihdr = Vec::new();
+        ihdr.extend(640_u32.to_be_bytes());
+        ihdr.extend(480_u32.to_be_bytes());
+        ihdr.extend([8, 2, 0, 0, 0]);
+
+        let mut idat = png_chunk(b"IDAT", b"\x78\x9c\x03\x00");
+        // Corrupt the CRC of the IDAT chunk.
+        let len = idat.len();
+        idat[len - 1] ^= 0xff;
+
+        [
+            super::png::SIGNATURE.to_vec(),
+            png_chunk(b"IHDR", &ihdr),
+            png_chunk(b"eXIf", &exif()),
+            idat,
+            png_chunk(b"IEND", b""),
+            b"PK\x03\x04".to_vec(),
+        ]
+        .concat()
+    }
+
+    fn build_jpeg() -> Vec<u8> {
+        let app1 = [&b"Exif\0\0"[..], &exif()].concat();
+        let sof0 = [8, 0, 100, 0, 200, 1, 1, 0x11, 0];
+        let sos = [1, 1, 0, 0, 0x3f, 0];
+
+        [
+            &b"\xff\xd8"[..],
+            b"\xff\xe1",
+            &(app1.len() as u16 + 2).to_be_bytes(),
+            &app1,
+            b"\xff\xc0",
+            &(sof0.len() as u16 + 2).to_be_bytes(),
+            &sof0,
+            b"\xff\xda",
+            &(sos.len() as u16 + 2).to_be_bytes(),
+            &sos,
+            // Entropy-coded data, with an escaped 0xFF and a restart marker.
+            b"\x12\xff\x00\x34\xff\xd0\x56",
+            b"\xff\xd9",
+            b"MZ\x90\x00",
+        ]
+        .concat()
+    }
+
+    fn build_gif() -> Vec<u8> {
+        [
+            &b"GIF89a"[..],
+            &3_u16.to_le_bytes(),
+            &2_u16.to_le_bytes(),
+            // Global color table with 2 entries.
+            &[0x80, 0, 0],
+            &[0; 6],
+            // Graphic control extension.
+            &[0x21, 0xf9, 4, 0, 0, 0, 0, 0],
+            // Image descriptor, LZW minimum code size, and image data.
+            &[0x2c, 0, 0, 0, 0, 3, 0, 2, 0, 0],
+            &[2, 2, 0x44, 0x01, 0],
+            // Comment extension.
+            &[0x21, 0xfe, 3],
+            b"foo",
+            &[0],
+            &[0x3b],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "image"
+                rule png {
+                  condition:
+                    image.format == image.ImageFormat.PNG and
+                    image.width == 640 and
+                    image.height == 480 and
+                    image.exif.software == "Photoshop 7.0" and
+                    image.exif.has_gps and
+                    image.trailing_data_size == 4 and
+                    uint32be(image.trailing_data_offset) == 0x504B0304 and
+                    image.chunks[0].type == "IHDR" and
+                    image.chunks[0].crc_valid and
+                    image.chunks[2].type == "IDAT" and
+                    not image.chunks[2].crc_valid
+                }
+                rule jpeg {
+                  condition:
+                    image.format == image.ImageFormat.JPEG and
+                    image.width == 200 and
+                    image.height == 100 and
+                    image.exif.software == "Photoshop 7.0" and
+                    image.trailing_data_size == 4 and
+                    image.chunks[0].type == "SOI" and
+                    image.chunks[1].type == "APP1" and
+                    image.chunks[2].type == "SOF0" and
+                    image.chunks[3].type == "SOS" and
+                    image.chunks[3].length == 17 and
+                    image.chunks[4].type == "EOI"
+                }
+                rule gif {
+                  condition:
+                    image.format == image.ImageFormat.GIF and
+                    image.width == 3 and
+                    image.height == 2 and
+                    not defined image.exif.software and
+                    image.trailing_data_size == 0 and
+                    image.chunks[0].type == "GRAPHIC_CONTROL" and
+                    image.chunks[1].type == "IMAGE" and
+                    image.chunks[1].length == 15 and
+                    image.chunks[2].type == "COMMENT"
+                }
+                rule not_image { condition: not image.is_image }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&build_png()), ["png"]);
+        assert_eq!(matching_rules(&build_jpeg()), ["jpeg"]);
+        assert_eq!(matching_rules(&build_gif()), ["gif"]);
+        assert_eq!(matching_rules(b"GIF8"), ["not_image"]);
+    }
+}
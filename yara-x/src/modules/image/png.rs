@@ -0,0 +1,63 @@
+use protobuf::MessageField;
+
+use crate::modules::image::{chunk, exif};
+use crate::modules::protos::image::*;
+
+pub const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Parses a PNG image. Returns the offset where the image ends, right
+/// after the `IEND` chunk.
+///
+/// Each chunk consists of the length of its data, a 4 bytes type, the data,
+/// and a CRC computed over the type and data.
+pub fn parse(data: &[u8], image: &mut Image) -> usize {
+    let mut offset = SIGNATURE.len();
+
+    while let Some(header) = data.get(offset..offset + 8) {
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let chunk_type = &header[4..8];
+
+        let end = match offset
+            .checked_add(12)
+            .and_then(|end| end.checked_add(length as usize))
+        {
+            Some(end) if end <= data.len() => end,
+            _ => break,
+        };
+
+        let type_and_data = &data[offset + 4..end - 4];
+        let chunk_data = &type_and_data[4..];
+        let crc = u32::from_be_bytes(data[end - 4..end].try_into().unwrap());
+
+        let mut c = chunk(
+            String::from_utf8_lossy(chunk_type).into_owned(),
+            offset,
+            end - offset,
+        );
+        c.set_crc_valid(crc32fast::hash(type_and_data) == crc);
+        image.chunks.push(c);
+
+        match chunk_type {
+            b"IHDR" if chunk_data.len() >= 8 => {
+                image.set_width(
+                    u32::from_be_bytes(chunk_data[..4].try_into().unwrap())
+                        .into(),
+                );
+                image.set_height(
+                    u32::from_be_bytes(chunk_data[4..8].try_into().unwrap())
+                        .into(),
+                );
+            }
+            b"eXIf" => {
+                image.exif =
+                    MessageField::from_option(exif::parse(chunk_data));
+            }
+            b"IEND" => return end,
+            _ => {}
+        }
+
+        offset = end;
+    }
+
+    data.len()
+}
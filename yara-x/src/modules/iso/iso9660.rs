@@ -0,0 +1,225 @@
+/*! Parser for the ISO9660 file system, including Joliet extensions.
+
+ISO9660 volumes start with a sequence of volume descriptors at sector 16.
+The primary volume descriptor has the volume's metadata and the root
+directory, Joliet adds a supplementary descriptor whose directories have
+UTF-16 names, and El Torito adds a boot record that points to the boot
+catalog. UDF bridge images continue the sequence with the UDF volume
+recognition descriptors.
+
+See: https://wiki.osdev.org/ISO_9660
+*/
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::modules::iso::{timestamp, utf16be, Entry, Listing, MAX_DEPTH};
+
+/// Size of the sectors where volume descriptors are stored.
+pub(crate) const SECTOR_SIZE: usize = 2048;
+
+/// Maximum number of volume descriptors.
+const MAX_DESCRIPTORS: usize = 64;
+
+/// Escape sequences that identify Joliet supplementary volume descriptors,
+/// one for each UCS-2 level.
+const JOLIET_ESCAPES: &[&[u8]] = &[b"%/@", b"%/C", b"%/E"];
+
+/// The volume descriptors found at the start of the image.
+pub(crate) struct Descriptors<'a> {
+    pub primary: Option<&'a [u8]>,
+    pub joliet: Option<&'a [u8]>,
+    pub has_boot_catalog: bool,
+    /// True if the volume recognition sequence has a UDF descriptor.
+    pub is_udf: bool,
+}
+
+impl<'a> Descriptors<'a> {
+    pub fn parse(data: &'a [u8]) -> Self {
+        let mut descriptors = Self {
+            primary: None,
+            joliet: None,
+            has_boot_catalog: false,
+            is_udf: false,
+        };
+
+        for i in 16..16 + MAX_DESCRIPTORS {
+            let sector = match data.get(i * SECTOR_SIZE..(i + 1) * SECTOR_SIZE)
+            {
+                Some(sector) => sector,
+                None => break,
+            };
+            match (&sector[1..6], sector[0]) {
+                (b"CD001", 0) => {
+                    descriptors.has_boot_catalog |=
+                        sector[7..].starts_with(b"EL TORITO SPECIFICATION");
+                }
+                (b"CD001", 1) => {
+                    descriptors.primary.get_or_insert(sector);
+                }
+                (b"CD001", 2) => {
+                    if JOLIET_ESCAPES.contains(&&sector[88..91]) {
+                        descriptors.joliet.get_or_insert(sector);
+                    }
+                }
+                // The terminator of the ISO9660 descriptors, which can be
+                // followed by the UDF descriptors.
+                (b"CD001", 255) | (b"BEA01", _) | (b"TEA01", _) => {}
+                (b"NSR02", _) | (b"NSR03", _) => descriptors.is_udf = true,
+                _ => break,
+            }
+        }
+
+        descriptors
+    }
+}
+
+/// Returns a string field from a volume descriptor, without the trailing
+/// padding.
+pub(crate) fn string(bytes: &[u8], joliet: bool) -> String {
+    let s = if joliet {
+        utf16be(bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    };
+    s.trim_end_matches([' ', '\0']).to_string()
+}
+
+/// Parses a date from a volume descriptor, which is stored as ASCII digits
+/// ("YYYYMMDDHHMMSScc") followed by the offset from GMT in 15-minute
+/// intervals.
+pub(crate) fn volume_date(bytes: &[u8]) -> Option<i64> {
+    let digits = std::str::from_utf8(bytes.get(..14)?).ok()?;
+    let field = |range: std::ops::Range<usize>| -> Option<u32> {
+        digits.get(range)?.parse().ok()
+    };
+    let offset = *bytes.get(16)? as i8;
+
+    timestamp(
+        field(0..4)? as i32,
+        field(4..6)?,
+        field(6..8)?,
+        field(8..10)?,
+        field(10..12)?,
+        field(12..14)?,
+        i32::from(offset) * 15,
+    )
+}
+
+/// Lists the files in the directory hierarchy that starts at the root
+/// directory of the given volume descriptor.
+pub(crate) fn files(
+    data: &[u8],
+    descriptor: &[u8],
+    joliet: bool,
+    max_entries: usize,
+) -> Listing {
+    let mut listing = Listing::default();
+
+    let block_size =
+        u16::from_le_bytes([descriptor[128], descriptor[129]]) as usize;
+
+    if !matches!(block_size, 512 | 1024 | 2048) {
+        return listing;
+    }
+
+    let root = &descriptor[156..190];
+    let mut pending =
+        VecDeque::from([(extent(root, block_size), String::new(), 0)]);
+    let mut visited = HashSet::new();
+
+    while let Some(((start, size), path, depth)) = pending.pop_front() {
+        if !visited.insert(start) {
+            continue;
+        }
+
+        let end = start.saturating_add(size).min(data.len());
+        let dir = data.get(start..end).unwrap_or_default();
+        let mut pos = 0;
+
+        while pos < dir.len() {
+            let len = dir[pos] as usize;
+            // Records don't cross sector boundaries, the rest of the sector
+            // is filled with zeroes.
+            if len == 0 {
+                pos = (pos / SECTOR_SIZE + 1) * SECTOR_SIZE;
+                continue;
+            }
+            let record = match dir.get(pos..pos + len) {
+                Some(record) if len >= 34 => record,
+                _ => break,
+            };
+            pos += len;
+
+            let name_len = record[32] as usize;
+            let name = match record.get(33..33 + name_len) {
+                Some(name) => name,
+                None => continue,
+            };
+            // The first two records are "." and "..".
+            if name == [0] || name == [1] {
+                continue;
+            }
+
+            if listing.entries.len() == max_entries {
+                listing.truncated = true;
+                return listing;
+            }
+
+            let name = if joliet {
+                utf16be(name)
+            } else {
+                String::from_utf8_lossy(name).into_owned()
+            };
+            // Remove the version number (";1") and the dot that separates
+            // the empty extension in names without one.
+            let name = name.split(';').next().unwrap_or_default();
+            let name = name.strip_suffix('.').unwrap_or(name);
+
+            let flags = record[25];
+            let entry = Entry {
+                path: format!("{}/{}", path, name),
+                offset: extent(record, block_size).0 as u64,
+                size: u32::from_le_bytes(record[10..14].try_into().unwrap())
+                    .into(),
+                is_directory: flags & 0x02 != 0,
+                is_hidden: flags & 0x01 != 0,
+                modification_time: record_date(&record[18..25]),
+            };
+
+            if entry.is_directory && depth < MAX_DEPTH {
+                pending.push_back((
+                    extent(record, block_size),
+                    entry.path.clone(),
+                    depth + 1,
+                ));
+            }
+
+            listing.entries.push(entry);
+        }
+    }
+
+    listing
+}
+
+/// Returns the offset and size of the extent described by a directory
+/// record.
+fn extent(record: &[u8], block_size: usize) -> (usize, usize) {
+    let block = u32::from_le_bytes(record[2..6].try_into().unwrap());
+    let size = u32::from_le_bytes(record[10..14].try_into().unwrap());
+    ((block as usize).saturating_mul(block_size), size as usize)
+}
+
+/// Parses the date in a directory record, which has the number of years
+/// since 1900, the month, day, hour, minute and second, and the offset from
+/// GMT in 15-minute intervals.
+fn record_date(bytes: &[u8]) -> Option<i64> {
+    timestamp(
+        1900 + i32::from(bytes[0]),
+        bytes[1].into(),
+        bytes[2].into(),
+        bytes[3].into(),
+        bytes[4].into(),
+        bytes[5].into(),
+        i32::from(bytes[6] as i8) * 15,
+    )
+}
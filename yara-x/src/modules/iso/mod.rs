@@ -0,0 +1,424 @@
+/*! YARA module that parses optical disc images (.iso, .img, .udf).
+
+The module exposes the volume's metadata and lists the files in the image,
+with their offsets within the scanned data, but doesn't extract them. Both
+ISO9660, with or without Joliet extensions, and UDF file systems are
+supported. Many images have both (i.e: bridge images), in which case the
+files are listed from the same file system Windows would use when mounting
+the image.
+*/
+
+use chrono::NaiveDate;
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::iso::*;
+
+mod iso9660;
+mod udf;
+
+/// Maximum number of entries in `files`.
+const MAX_FILES: usize = 16384;
+
+/// Maximum depth of the directory hierarchy.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+/// A file or directory in the image.
+pub(crate) struct Entry {
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+    pub is_directory: bool,
+    pub is_hidden: bool,
+    pub modification_time: Option<i64>,
+}
+
+/// The files and directories in a file system.
+#[derive(Default)]
+pub(crate) struct Listing {
+    pub entries: Vec<Entry>,
+    pub truncated: bool,
+}
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Iso {
+    let data = ctx.scanned_data();
+    let mut iso = Iso::new();
+
+    let descriptors = iso9660::Descriptors::parse(data);
+
+    let udf =
+        if descriptors.is_udf { udf::parse(data, MAX_FILES) } else { None };
+
+    let (file_system, label, listing) =
+        match (udf, descriptors.joliet, descriptors.primary) {
+            (Some(volume), _, _) => {
+                (FileSystem::UDF, volume.label, volume.listing)
+            }
+            (None, Some(svd), _) => (
+                FileSystem::JOLIET,
+                iso9660::string(&svd[40..72], true),
+                iso9660::files(data, svd, true, MAX_FILES),
+            ),
+            (None, None, Some(pvd)) => (
+                FileSystem::ISO9660,
+                iso9660::string(&pvd[40..72], false),
+                iso9660::files(data, pvd, false, MAX_FILES),
+            ),
+            (None, None, None) => {
+                iso.set_is_iso(false);
+                return iso;
+            }
+        };
+
+    iso.set_is_iso(true);
+    iso.set_is_udf(descriptors.is_udf);
+    iso.set_has_joliet(descriptors.joliet.is_some());
+    iso.set_has_boot_catalog(descriptors.has_boot_catalog);
+    iso.file_system = Some(EnumOrUnknown::new(file_system));
+    iso.set_volume_label(label);
+
+    if let Some(pvd) = descriptors.primary {
+        let num_blocks = u32::from_le_bytes(pvd[80..84].try_into().unwrap());
+        let block_size = u16::from_le_bytes([pvd[128], pvd[129]]);
+
+        iso.set_system_id(iso9660::string(&pvd[8..40], false));
+        iso.set_publisher(iso9660::string(&pvd[318..446], false));
+        iso.set_preparer(iso9660::string(&pvd[446..574], false));
+        iso.set_application_id(iso9660::string(&pvd[574..702], false));
+        iso.set_volume_size(i64::from(num_blocks) * i64::from(block_size));
+        iso.creation_time = iso9660::volume_date(&pvd[813..830]);
+        iso.modification_time = iso9660::volume_date(&pvd[830..847]);
+    }
+
+    for entry in listing.entries {
+        let mut file = IsoFile::new();
+        file.set_name(entry.path);
+        file.set_offset(entry.offset as i64);
+        file.set_size(entry.size as i64);
+        file.set_is_directory(entry.is_directory);
+        file.set_is_hidden(entry.is_hidden);
+        file.modification_time = entry.modification_time;
+        iso.files.push(file);
+    }
+
+    iso.set_files_truncated(listing.truncated);
+
+    iso
+}
+
+/// Converts a date and time, with its offset from UTC in minutes, to a
+/// UNIX timestamp. Returns `None` if the date is not valid, which is also
+/// the case for unset dates, where all the fields are zero.
+pub(crate) fn timestamp(
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    offset: i32,
+) -> Option<i64> {
+    let timestamp = NaiveDate::from_ymd_opt(year, month, day)?
+        .and_hms_opt(hour, minute, second)?
+        .and_utc()
+        .timestamp();
+
+    Some(timestamp - i64::from(offset) * 60)
+}
+
+pub(crate) fn utf16be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    const SECTOR: usize = 2048;
+
+    fn utf16be(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect()
+    }
+
+    /// Builds an ISO9660 directory record.
+    fn dir_record(name: &[u8], sector: u32, size: u32, flags: u8) -> Vec<u8> {
+        let mut record = vec![0; 33];
+        record[0] = (33 + name.len() + (name.len() + 1) % 2) as u8;
+        record[2..6].copy_from_slice(&sector.to_le_bytes());
+        record[6..10].copy_from_slice(&sector.to_be_bytes());
+        record[10..14].copy_from_slice(&size.to_le_bytes());
+        record[14..18].copy_from_slice(&size.to_be_bytes());
+        // 2023-10-17 12:00:00 UTC
+        record[18..25].copy_from_slice(&[123, 10, 17, 12, 0, 0, 0]);
+        record[25] = flags;
+        record[28] = 1;
+        record[32] = name.len() as u8;
+        record.extend(name);
+        record.resize(record[0] as usize, 0);
+        record
+    }
+
+    /// Builds a directory with the given records, plus the "." and ".."
+    /// records at the start.
+    fn directory(sector: u32, records: &[Vec<u8>]) -> Vec<u8> {
+        let mut dir = dir_record(&[0], sector, SECTOR as u32, 2);
+        dir.extend(dir_record(&[1], sector, SECTOR as u32, 2));
+        dir.extend(records.concat());
+        dir
+    }
+
+    /// Builds a UDF descriptor with the given tag identifier and content.
+    fn udf_descriptor(tag: u16, content: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut d = vec![0; SECTOR];
+        d[0..2].copy_from_slice(&tag.to_le_bytes());
+        d[2] = 2;
+        for (offset, bytes) in content {
+            d[*offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        d[4] = d[..16]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 4)
+            .fold(0_u8, |sum, (_, b)| sum.wrapping_add(*b));
+        d
+    }
+
+    /// Builds a UDF file entry, with allocation descriptors of the given
+    /// type.
+    fn udf_file_entry(size: usize, ad_type: u8, ads: &[u8]) -> Vec<u8> {
+        // 2023-10-17 12:00:00 UTC
+        let mtime = [0x00, 0x10, 0xe7, 0x07, 10, 17, 12, 0, 0, 0, 0, 0];
+        udf_descriptor(
+            261,
+            &[
+                (34, &[ad_type]),
+                (56, &(size as u64).to_le_bytes()),
+                (84, &mtime),
+                (172, &(ads.len() as u32).to_le_bytes()),
+                (176, ads),
+            ],
+        )
+    }
+
+    /// Builds a UDF file identifier descriptor.
+    fn udf_fid(characteristics: u8, name: &str, block: u32) -> Vec<u8> {
+        let name = if name.is_empty() {
+            vec![]
+        } else {
+            [&[8], name.as_bytes()].concat()
+        };
+        let mut fid = udf_descriptor(
+            257,
+            &[
+                (18, &[characteristics, name.len() as u8]),
+                (20, &(SECTOR as u32).to_le_bytes()),
+                (24, &block.to_le_bytes()),
+            ],
+        );
+        fid.truncate(38);
+        fid.extend(name);
+        fid.resize((fid.len() + 3) & !3, 0);
+        fid
+    }
+
+    fn short_ad(size: u32, sector: u32) -> Vec<u8> {
+        [size.to_le_bytes(), sector.to_le_bytes()].concat()
+    }
+
+    /// Builds a bootable UDF bridge image with Joliet extensions, with the
+    /// files "invoice.lnk", "readme.txt" (hidden) and "docs/notes.txt".
+    fn image() -> Vec<u8> {
+        let mut image = vec![0; 257 * SECTOR];
+        let mut put = |sector: usize, bytes: &[u8]| {
+            let start = sector * SECTOR;
+            image[start..start + bytes.len()].copy_from_slice(bytes);
+        };
+
+        let mut pvd = vec![0; SECTOR];
+        pvd[0..7].copy_from_slice(b"\x01CD001\x01");
+        pvd[8..40].copy_from_slice(&[b' '; 32]);
+        pvd[8..13].copy_from_slice(b"WIN32");
+        pvd[40..72].copy_from_slice(&[b' '; 32]);
+        pvd[40..51].copy_from_slice(b"INVOICE_ISO");
+        pvd[80..84].copy_from_slice(&257_u32.to_le_bytes());
+        pvd[128..130].copy_from_slice(&2048_u16.to_le_bytes());
+        pvd[156..190].copy_from_slice(&dir_record(&[0], 23, 2048, 2));
+        pvd[318..322].copy_from_slice(b"ACME");
+        pvd[813..830].copy_from_slice(b"2023101712000000\0");
+        put(16, &pvd);
+
+        put(17, b"\x00CD001\x01EL TORITO SPECIFICATION");
+
+        let mut svd = pvd.clone();
+        svd[0] = 2;
+        svd[40..72].copy_from_slice(&[0; 32]);
+        svd[40..54].copy_from_slice(&utf16be("Invoice"));
+        svd[88..91].copy_from_slice(b"%/E");
+        svd[156..190].copy_from_slice(&dir_record(&[0], 25, 2048, 2));
+        put(18, &svd);
+
+        put(19, b"\xffCD001\x01");
+        put(20, b"\x00BEA01\x01");
+        put(21, b"\x00NSR02\x01");
+        put(22, b"\x00TEA01\x01");
+
+        put(
+            23,
+            &directory(
+                23,
+                &[
+                    dir_record(b"DOCS", 24, 2048, 2),
+                    dir_record(b"INVOICE.LNK;1", 27, 10, 0),
+                    dir_record(b"README.TXT;1", 28, 5, 1),
+                ],
+            ),
+        );
+        put(24, &directory(24, &[dir_record(b"NOTES.TXT;1", 28, 5, 0)]));
+        put(
+            25,
+            &directory(
+                25,
+                &[
+                    dir_record(&utf16be("docs"), 26, 2048, 2),
+                    dir_record(&utf16be("invoice.lnk;1"), 27, 10, 0),
+                    dir_record(&utf16be("readme.txt;1"), 28, 5, 1),
+                ],
+            ),
+        );
+        put(
+            26,
+            &directory(26, &[dir_record(&utf16be("notes.txt;1"), 28, 5, 0)]),
+        );
+        put(27, b"LNK-DATA!!");
+        put(28, b"hello");
+
+        // The UDF partition starts at sector 0, so blocks and sectors are
+        // the same.
+        put(
+            256,
+            &udf_descriptor(
+                2,
+                &[(16, &(3 * 2048_u32).to_le_bytes()), (20, &[32])],
+            ),
+        );
+        put(32, &udf_descriptor(5, &[]));
+        put(
+            33,
+            &udf_descriptor(
+                6,
+                &[
+                    (84, b"\x08Invoice UDF"),
+                    (211, &[12]),
+                    (212, &2048_u32.to_le_bytes()),
+                    (252, &[40]),
+                ],
+            ),
+        );
+        put(34, &udf_descriptor(8, &[]));
+        put(40, &udf_descriptor(256, &[(404, &[41])]));
+
+        let root = [
+            udf_fid(0x0a, "", 41),
+            udf_fid(0x02, "Docs", 42),
+            udf_fid(0x00, "invoice.lnk", 43),
+            udf_fid(0x01, "readme.txt", 44),
+        ]
+        .concat();
+        put(41, &udf_file_entry(root.len(), 3, &root));
+
+        let docs =
+            [udf_fid(0x0a, "", 41), udf_fid(0x00, "notes.txt", 44)].concat();
+        put(42, &udf_file_entry(docs.len(), 3, &docs));
+
+        put(43, &udf_file_entry(10, 0, &short_ad(10, 27)));
+        put(44, &udf_file_entry(5, 0, &short_ad(5, 28)));
+
+        image
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "iso"
+                rule udf {
+                  condition:
+                    iso.is_iso and
+                    iso.is_udf and
+                    iso.has_joliet and
+                    iso.has_boot_catalog and
+                    iso.file_system == iso.FileSystem.UDF and
+                    iso.volume_label == "Invoice UDF" and
+                    iso.system_id == "WIN32" and
+                    iso.publisher == "ACME" and
+                    iso.volume_size == 257 * 2048 and
+                    iso.creation_time == 1697544000 and
+                    iso.files[0].name == "/Docs" and
+                    iso.files[0].is_directory and
+                    iso.files[1].name == "/invoice.lnk" and
+                    iso.files[1].size == 10 and
+                    uint32(iso.files[1].offset) == 0x2d4b4e4c and
+                    iso.files[1].modification_time == 1697544000 and
+                    iso.files[2].is_hidden and
+                    iso.files[3].name == "/Docs/notes.txt" and
+                    uint8(iso.files[3].offset) == 0x68 and
+                    not iso.files_truncated
+                }
+                rule joliet {
+                  condition:
+                    iso.file_system == iso.FileSystem.JOLIET and
+                    iso.volume_label == "Invoice" and
+                    iso.files[0].name == "/docs" and
+                    iso.files[1].name == "/invoice.lnk" and
+                    uint32(iso.files[1].offset) == 0x2d4b4e4c and
+                    iso.files[1].modification_time == 1697544000 and
+                    iso.files[2].is_hidden and
+                    iso.files[3].name == "/docs/notes.txt"
+                }
+                rule iso9660 {
+                  condition:
+                    iso.file_system == iso.FileSystem.ISO9660 and
+                    not iso.is_udf and
+                    not iso.has_joliet and
+                    iso.volume_label == "INVOICE_ISO" and
+                    iso.files[1].name == "/INVOICE.LNK" and
+                    iso.files[3].name == "/DOCS/NOTES.TXT"
+                }
+                rule not_iso { condition: not iso.is_iso }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut image = image();
+        assert_eq!(matching_rules(&image), ["udf"]);
+
+        // Without the UDF anchor the files are listed from the Joliet
+        // directories.
+        image.truncate(256 * SECTOR);
+        assert_eq!(matching_rules(&image), ["joliet"]);
+
+        // Without the Joliet descriptor the sequence ends before the UDF
+        // descriptors, leaving only the ISO9660 file system.
+        image[18 * SECTOR] = 0xaa;
+        assert_eq!(matching_rules(&image), ["iso9660"]);
+
+        assert_eq!(matching_rules(b"MZ\x90\x00"), ["not_iso"]);
+    }
+}
@@ -0,0 +1,316 @@
+/*! Parser for the UDF file system.
+
+UDF volumes are located through the anchor volume descriptor at sector 256,
+which points to the sequence of volume descriptors. The partition
+descriptor tells where the partition starts, and the logical volume
+descriptor has the volume's label and the location of the file set
+descriptor, which in turn has the location of the root directory. Every
+file and directory is described by a file entry, and directories contain
+file identifier descriptors that point to the file entries of their
+children.
+
+Only images with a single partition and 2048-byte sectors are supported,
+which covers the images produced by the usual authoring tools.
+
+See: http://www.osta.org/specs/pdf/udf260.pdf
+*/
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::modules::iso::{timestamp, utf16be, Entry, Listing, MAX_DEPTH};
+use crate::modules::utils::u32_at;
+
+const SECTOR_SIZE: usize = 2048;
+
+/// Maximum size of the directories.
+const MAX_DIRECTORY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Identifiers of the descriptors used by the parser.
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATOR: u16 = 8;
+const TAG_FILE_SET: u16 = 256;
+const TAG_FILE_IDENTIFIER: u16 = 257;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXTENDED_FILE_ENTRY: u16 = 266;
+
+/// Characteristics of the file identifier descriptors.
+const FILE_HIDDEN: u8 = 0x01;
+const FILE_DIRECTORY: u8 = 0x02;
+const FILE_DELETED: u8 = 0x04;
+const FILE_PARENT: u8 = 0x08;
+
+/// The volume's label and files.
+pub(crate) struct Volume {
+    pub label: String,
+    pub listing: Listing,
+}
+
+/// A file entry, which describes the content of a file or directory.
+struct FileEntry {
+    size: u64,
+    modification_time: Option<i64>,
+    /// Offsets and sizes of the extents with the content.
+    extents: Vec<(usize, usize)>,
+}
+
+pub(crate) fn parse(data: &[u8], max_entries: usize) -> Option<Volume> {
+    let anchor = descriptor(data, 256, 2)?;
+    let sequence_len = u32_at(anchor, 16)? as usize;
+    let sequence_start = u32_at(anchor, 20)? as usize;
+
+    let mut partition_start = None;
+    let mut logical_volume = None;
+
+    for sector in sequence_start..sequence_start + sequence_len / SECTOR_SIZE {
+        let d = match sector_at(data, sector) {
+            Some(d) => d,
+            None => break,
+        };
+        match tag(d) {
+            Some(TAG_PARTITION) => {
+                partition_start = u32_at(d, 188).map(|s| s as usize)
+            }
+            Some(TAG_LOGICAL_VOLUME) => logical_volume = Some(d),
+            Some(TAG_TERMINATOR) | None => break,
+            _ => {}
+        }
+    }
+
+    let parser = Parser { data, partition_start: partition_start? };
+    let logical_volume = logical_volume?;
+
+    if u32_at(logical_volume, 212)? as usize != SECTOR_SIZE {
+        return None;
+    }
+
+    // The location of the file set descriptor is a long allocation
+    // descriptor with the extent's length and its block.
+    let file_set = parser
+        .block(u32_at(logical_volume, 252)?)
+        .filter(|d| tag(d) == Some(TAG_FILE_SET))?;
+
+    let mut volume = Volume {
+        label: dstring(&logical_volume[84..212]),
+        listing: Listing::default(),
+    };
+
+    let mut pending =
+        VecDeque::from([(u32_at(file_set, 404)?, String::new(), 0)]);
+    let mut visited = HashSet::new();
+
+    while let Some((block, path, depth)) = pending.pop_front() {
+        if !visited.insert(block) {
+            continue;
+        }
+
+        let dir = match parser.file_entry(block) {
+            Some(entry) => parser.content(&entry, MAX_DIRECTORY_SIZE),
+            None => continue,
+        };
+
+        let mut pos = 0;
+
+        while let Some(fid) = dir.get(pos..pos + 38) {
+            if tag(fid) != Some(TAG_FILE_IDENTIFIER) {
+                break;
+            }
+
+            let characteristics = fid[18];
+            let name_len = fid[19] as usize;
+            let impl_use_len = u16::from_le_bytes([fid[36], fid[37]]) as usize;
+            let name_start = pos + 38 + impl_use_len;
+            let name = dir.get(name_start..name_start + name_len);
+
+            // Descriptors are padded to a multiple of 4 bytes.
+            pos += (38 + impl_use_len + name_len + 3) & !3;
+
+            if characteristics & (FILE_PARENT | FILE_DELETED) != 0 {
+                continue;
+            }
+
+            if volume.listing.entries.len() == max_entries {
+                volume.listing.truncated = true;
+                return Some(volume);
+            }
+
+            let child = u32_at(fid, 24).unwrap();
+            let file_entry = parser.file_entry(child);
+            let entry = Entry {
+                path: format!(
+                    "{}/{}",
+                    path,
+                    name.map(dchars).unwrap_or_default()
+                ),
+                offset: file_entry
+                    .as_ref()
+                    .and_then(|entry| entry.extents.first())
+                    .map_or(0, |(offset, _)| *offset as u64),
+                size: file_entry.as_ref().map_or(0, |entry| entry.size),
+                is_directory: characteristics & FILE_DIRECTORY != 0,
+                is_hidden: characteristics & FILE_HIDDEN != 0,
+                modification_time: file_entry
+                    .and_then(|entry| entry.modification_time),
+            };
+
+            if entry.is_directory && depth < MAX_DEPTH {
+                pending.push_back((child, entry.path.clone(), depth + 1));
+            }
+
+            volume.listing.entries.push(entry);
+        }
+    }
+
+    Some(volume)
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    /// Sector where the partition starts. Blocks in the file system are
+    /// relative to the partition.
+    partition_start: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn block(&self, block: u32) -> Option<&'a [u8]> {
+        sector_at(self.data, self.partition_start.checked_add(block as usize)?)
+    }
+
+    fn file_entry(&self, block: u32) -> Option<FileEntry> {
+        let d = self.block(block)?;
+        let offset = (self.partition_start + block as usize) * SECTOR_SIZE;
+
+        // Offsets of the modification time and the lengths of the extended
+        // attributes and allocation descriptors.
+        let (mtime, ea_len) = match tag(d)? {
+            TAG_FILE_ENTRY => (84, 168),
+            TAG_EXTENDED_FILE_ENTRY => (92, 208),
+            _ => return None,
+        };
+
+        let ea_size = u32_at(d, ea_len)? as usize;
+        let ad_size = u32_at(d, ea_len + 4)? as usize;
+        let ad_start = (ea_len + 8).checked_add(ea_size)?;
+        let ads = d.get(ad_start..ad_start.checked_add(ad_size)?)?;
+        let size = u64::from_le_bytes(d[56..64].try_into().unwrap());
+
+        // The lowest bits of the flags in the ICB tag indicate the type of
+        // the allocation descriptors.
+        let mut extents = Vec::new();
+
+        match d[34] & 0x07 {
+            // Short and long allocation descriptors, both start with the
+            // extent's length and block. The two highest bits of the length
+            // are the extent's type.
+            ad_type @ (0 | 1) => {
+                let ad_len = if ad_type == 0 { 8 } else { 16 };
+                for ad in ads.chunks_exact(ad_len) {
+                    let len = u32_at(ad, 0)?;
+                    if len == 0 || len >> 30 == 3 {
+                        break;
+                    }
+                    let start = self
+                        .partition_start
+                        .checked_add(u32_at(ad, 4)? as usize)?
+                        .checked_mul(SECTOR_SIZE)?;
+                    extents.push((start, (len & 0x3fffffff) as usize));
+                }
+            }
+            // The content is embedded in the file entry.
+            3 => {
+                extents.push((offset + ad_start, ad_size));
+            }
+            _ => {}
+        }
+
+        Some(FileEntry {
+            size,
+            modification_time: udf_timestamp(&d[mtime..mtime + 12]),
+            extents,
+        })
+    }
+
+    /// Returns the content of a file, up to `max_size` bytes.
+    fn content(&self, entry: &FileEntry, max_size: usize) -> Vec<u8> {
+        let size = (entry.size as usize).min(max_size);
+        let mut content = Vec::new();
+
+        for (start, len) in entry.extents.iter() {
+            let len = (*len).min(size - content.len());
+            match self.data.get(*start..start.saturating_add(len)) {
+                Some(extent) => content.extend_from_slice(extent),
+                None => break,
+            }
+        }
+
+        content
+    }
+}
+
+/// Returns the sector with the given number if it starts with a valid
+/// descriptor with the given tag.
+fn descriptor(data: &[u8], sector: usize, tag_id: u16) -> Option<&[u8]> {
+    sector_at(data, sector).filter(|d| tag(d) == Some(tag_id))
+}
+
+fn sector_at(data: &[u8], sector: usize) -> Option<&[u8]> {
+    let start = sector.checked_mul(SECTOR_SIZE)?;
+    data.get(start..start.checked_add(SECTOR_SIZE)?)
+}
+
+/// Returns the identifier in the descriptor tag at the start of `d`, or
+/// `None` if the tag's checksum is not valid.
+fn tag(d: &[u8]) -> Option<u16> {
+    let tag = d.get(..16)?;
+    let checksum = tag
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 4)
+        .fold(0_u8, |sum, (_, b)| sum.wrapping_add(*b));
+
+    if checksum != tag[4] {
+        return None;
+    }
+
+    Some(u16::from_le_bytes([tag[0], tag[1]]))
+}
+
+/// Decodes a string stored as a "dstring", a field of fixed size whose last
+/// byte is the length of the string.
+fn dstring(field: &[u8]) -> String {
+    let len = *field.last().unwrap_or(&0) as usize;
+    dchars(&field[..len.min(field.len() - 1)])
+}
+
+/// Decodes a string whose first byte indicates the size of the characters,
+/// which can be 8 or 16 bits.
+fn dchars(bytes: &[u8]) -> String {
+    match bytes.split_first() {
+        Some((8, chars)) => chars.iter().map(|c| *c as char).collect(),
+        Some((16, chars)) => utf16be(chars),
+        _ => String::new(),
+    }
+}
+
+/// Parses a UDF timestamp, whose first two bytes have the timestamp's type
+/// in the highest 4 bits and the offset from UTC in minutes in the lowest
+/// 12 bits, as a signed integer.
+fn udf_timestamp(bytes: &[u8]) -> Option<i64> {
+    let type_and_zone = u16::from_le_bytes([bytes[0], bytes[1]]);
+    let mut offset = (((type_and_zone << 4) as i16) >> 4) as i32;
+
+    // An offset of -2047 means that it's not specified.
+    if type_and_zone >> 12 != 1 || offset == -2047 {
+        offset = 0;
+    }
+
+    timestamp(
+        i16::from_le_bytes([bytes[2], bytes[3]]).into(),
+        bytes[4].into(),
+        bytes[5].into(),
+        bytes[6].into(),
+        bytes[7].into(),
+        bytes[8].into(),
+        offset,
+    )
+}
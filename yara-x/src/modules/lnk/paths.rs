@@ -0,0 +1,75 @@
+/*! Paths computed from the different locations of the target.
+
+A shortcut may have the target's location in the link info, as a local
+base path or a network share followed by a common suffix, and as a path
+relative to the shortcut in the string data. Rules usually don't care
+about where the path comes from, so the module resolves it into a single
+path.
+*/
+
+use super::parser::Lnk;
+
+/// Returns the path of the target. The one in the link info is preferred,
+/// and the relative path is resolved against the working directory, if
+/// any, because the location of the shortcut itself is unknown.
+pub(super) fn target_path(lnk: &Lnk) -> Option<String> {
+    if let Some(info) = &lnk.link_info {
+        let suffix = info.common_path_suffix.as_deref().unwrap_or_default();
+
+        if let Some(base) = &info.local_base_path {
+            return Some(format!("{}{}", base, suffix));
+        }
+
+        if let Some(network_link) = &info.network_link {
+            if suffix.is_empty() {
+                return Some(network_link.net_name.clone());
+            }
+            return Some(join(&network_link.net_name, suffix));
+        }
+    }
+
+    let relative_path = lnk.relative_path.as_deref()?;
+
+    Some(match lnk.working_dir.as_deref() {
+        Some(dir) if !dir.is_empty() => join(dir, relative_path),
+        _ => relative_path.to_string(),
+    })
+}
+
+/// Returns the name of the file in a path, without the directories and
+/// the icon index that follows a comma in icon locations.
+pub(super) fn file_name(path: &str) -> &str {
+    let path = path.rsplit(['\\', '/']).next().unwrap_or(path);
+    path.split(',').next().unwrap_or(path).trim_matches('"')
+}
+
+/// Joins a directory and a relative path, removing the `.` and `..`
+/// components of the relative path.
+fn join(dir: &str, relative_path: &str) -> String {
+    let mut components: Vec<&str> =
+        dir.split('\\').filter(|c| !c.is_empty()).collect();
+
+    // The first component is kept, so that `..` components don't remove
+    // the drive or the server of UNC paths.
+    let root = usize::from(!components.is_empty());
+
+    for component in relative_path.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.len() > root {
+                    components.pop();
+                }
+            }
+            _ => components.push(component),
+        }
+    }
+
+    let path = components.join("\\");
+
+    if dir.starts_with("\\\\") {
+        format!("\\\\{}", path)
+    } else {
+        path
+    }
+}
@@ -0,0 +1,189 @@
+/*! Property stores in the property store data block.
+
+Each property store is identified by the GUID of its format, and has a
+list of typed values, which are identified by an integer, or by a string
+in the stores with the FMTID_UserDefinedProperties format. Shortcuts
+created by the shell often have properties of the target, like its SID or
+its parsing path, that are not in the rest of the file.
+
+See: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-propstore
+*/
+
+use crate::modules::utils::{filetime_to_epoch, format_guid};
+
+use super::parser::utf16_at;
+use crate::modules::utils::{u16_at, u32_at, u64_at};
+
+/// Version of serialized property stores, which is "1SPS".
+const PROPERTY_STORE_VERSION: u32 = 0x53505331;
+
+/// Format of the property stores whose values are identified by strings.
+const FMTID_USER_DEFINED_PROPERTIES: &str =
+    "d5cdd505-2e9c-101b-9397-08002b2cf9ae";
+
+/// Types of property values.
+const VT_I2: u16 = 0x2;
+const VT_I4: u16 = 0x3;
+const VT_BSTR: u16 = 0x8;
+const VT_BOOL: u16 = 0xb;
+const VT_I1: u16 = 0x10;
+const VT_UI1: u16 = 0x11;
+const VT_UI2: u16 = 0x12;
+const VT_UI4: u16 = 0x13;
+const VT_I8: u16 = 0x14;
+const VT_UI8: u16 = 0x15;
+const VT_INT: u16 = 0x16;
+const VT_UINT: u16 = 0x17;
+const VT_LPSTR: u16 = 0x1e;
+const VT_LPWSTR: u16 = 0x1f;
+const VT_FILETIME: u16 = 0x40;
+const VT_CLSID: u16 = 0x48;
+
+/// Maximum number of property stores, and of values in each of them.
+const MAX_STORES: usize = 64;
+const MAX_VALUES: usize = 256;
+
+/// A property store.
+pub(super) struct PropertyStore {
+    pub format_id: String,
+    pub values: Vec<Property>,
+}
+
+/// A value in a property store.
+pub(super) struct Property {
+    /// Identifier of the value, for stores whose values are identified by
+    /// integers.
+    pub id: Option<u32>,
+    /// Name of the value, for stores whose values are identified by
+    /// strings.
+    pub name: Option<String>,
+    pub type_: u16,
+    pub value: Value,
+}
+
+/// A typed value. Values of unsupported types are `None`.
+pub(super) enum Value {
+    Integer(i64),
+    String(String),
+    None,
+}
+
+/// Returns the property stores in a property store data block.
+pub(super) fn property_stores(block: &[u8]) -> Vec<PropertyStore> {
+    let mut stores = Vec::new();
+    let mut pos = 8;
+
+    while stores.len() < MAX_STORES {
+        let size = match u32_at(block, pos) {
+            Some(size) if size >= 24 => size as usize,
+            _ => break,
+        };
+
+        match block.get(pos..pos.saturating_add(size)).and_then(property_store)
+        {
+            Some(store) => stores.push(store),
+            None => break,
+        }
+
+        pos += size;
+    }
+
+    stores
+}
+
+fn property_store(data: &[u8]) -> Option<PropertyStore> {
+    if u32_at(data, 4)? != PROPERTY_STORE_VERSION {
+        return None;
+    }
+
+    let format_id = format_guid(data.get(8..24)?);
+    let named = format_id == FMTID_USER_DEFINED_PROPERTIES;
+
+    let mut values = Vec::new();
+    let mut pos = 24;
+
+    while values.len() < MAX_VALUES {
+        let size = match u32_at(data, pos) {
+            Some(size) if size >= 9 => size as usize,
+            _ => break,
+        };
+
+        let entry = match data.get(pos..pos.saturating_add(size)) {
+            Some(entry) => entry,
+            None => break,
+        };
+
+        // Values identified by strings have the size of the name, in bytes,
+        // where the others have the identifier, and both are followed by a
+        // reserved byte.
+        let (id, name, value_offset) = if named {
+            let name_size = u32_at(entry, 4)? as usize;
+            (None, utf16_at(entry, 9), 9 + name_size)
+        } else {
+            (Some(u32_at(entry, 4)?), None, 9)
+        };
+
+        if let Some(type_) = u16_at(entry, value_offset) {
+            values.push(Property {
+                id,
+                name,
+                type_,
+                value: value(entry.get(value_offset + 4..)?, type_),
+            });
+        }
+
+        pos += size;
+    }
+
+    Some(PropertyStore { format_id, values })
+}
+
+/// Returns a value of the given type.
+fn value(data: &[u8], type_: u16) -> Value {
+    let integer = |size: usize, signed: bool| {
+        let bytes = data.get(..size)?;
+        let mut buf = [0_u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        let value = u64::from_le_bytes(buf);
+        // Sign-extend the value.
+        let shift = 64 - size * 8;
+        Some(if signed {
+            ((value << shift) as i64) >> shift
+        } else {
+            value as i64
+        })
+    };
+
+    let value = match type_ {
+        VT_I1 => integer(1, true).map(Value::Integer),
+        VT_UI1 => integer(1, false).map(Value::Integer),
+        VT_I2 | VT_BOOL => integer(2, true).map(Value::Integer),
+        VT_UI2 => integer(2, false).map(Value::Integer),
+        VT_I4 | VT_INT => integer(4, true).map(Value::Integer),
+        VT_UI4 | VT_UINT => integer(4, false).map(Value::Integer),
+        VT_I8 | VT_UI8 => integer(8, true).map(Value::Integer),
+        VT_FILETIME => {
+            u64_at(data, 0).and_then(filetime_to_epoch).map(Value::Integer)
+        }
+        VT_CLSID => {
+            data.get(..16).map(|guid| Value::String(format_guid(guid)))
+        }
+        // Strings with the number of characters, including the null one.
+        VT_LPWSTR => utf16_at(data, 4).map(Value::String),
+        // Strings with their size in bytes.
+        VT_LPSTR | VT_BSTR => u32_at(data, 0)
+            .and_then(|size| data.get(4..4 + size as usize))
+            .map(|string| {
+                let len = string
+                    .iter()
+                    .position(|b| *b == 0)
+                    .unwrap_or(string.len());
+                Value::String(
+                    String::from_utf8_lossy(&string[..len]).into_owned(),
+                )
+            }),
+        _ => None,
+    };
+
+    value.unwrap_or(Value::None)
+}
@@ -0,0 +1,77 @@
+/*! Libraries and search paths used by dyld.
+
+Each library that the file links to has a dylib command, whose position
+among the other dylib commands is the ordinal used by the binding info.
+Libraries whose names start with `@rpath` are searched in the paths of the
+LC_RPATH commands, in the order they appear in the file.
+*/
+
+use super::parser::{string_at, Command, MachO};
+
+/// Load commands for linked libraries.
+pub(super) const LC_LOAD_DYLIB: u32 = 0xc;
+pub(super) const LC_ID_DYLIB: u32 = 0xd;
+pub(super) const LC_LAZY_LOAD_DYLIB: u32 = 0x20;
+pub(super) const LC_LOAD_WEAK_DYLIB: u32 = 0x80000018;
+pub(super) const LC_REEXPORT_DYLIB: u32 = 0x8000001f;
+pub(super) const LC_LOAD_UPWARD_DYLIB: u32 = 0x80000023;
+
+/// Load command with a search path.
+const LC_RPATH: u32 = 0x8000001c;
+
+/// A library from a dylib command.
+pub(super) struct Dylib<'a> {
+    /// Type of the load command.
+    pub type_: u32,
+    pub name: &'a [u8],
+    pub timestamp: u32,
+    pub current_version: u32,
+    pub compatibility_version: u32,
+}
+
+/// Returns the libraries linked by the file, in the order of the load
+/// commands.
+pub(super) fn dylibs<'a>(macho: &MachO<'a>) -> Vec<Dylib<'a>> {
+    macho
+        .commands
+        .iter()
+        .filter(|c| {
+            matches!(
+                c.type_,
+                LC_LOAD_DYLIB
+                    | LC_LAZY_LOAD_DYLIB
+                    | LC_LOAD_WEAK_DYLIB
+                    | LC_REEXPORT_DYLIB
+                    | LC_LOAD_UPWARD_DYLIB
+            )
+        })
+        .filter_map(|c| dylib(macho, c))
+        .collect()
+}
+
+/// Returns the library identity of a shared library, from the LC_ID_DYLIB
+/// command.
+pub(super) fn id_dylib<'a>(macho: &MachO<'a>) -> Option<Dylib<'a>> {
+    dylib(macho, macho.command(LC_ID_DYLIB)?)
+}
+
+/// Returns the paths in the LC_RPATH commands.
+pub(super) fn rpaths<'a>(macho: &MachO<'a>) -> Vec<&'a [u8]> {
+    macho
+        .commands
+        .iter()
+        .filter(|c| c.type_ == LC_RPATH)
+        .filter_map(|c| string_at(c.data, macho.u32_at(c.data, 8)?))
+        .collect()
+}
+
+fn dylib<'a>(macho: &MachO<'a>, command: &Command<'a>) -> Option<Dylib<'a>> {
+    let data = command.data;
+    Some(Dylib {
+        type_: command.type_,
+        name: string_at(data, macho.u32_at(data, 8)?)?,
+        timestamp: macho.u32_at(data, 12)?,
+        current_version: macho.u32_at(data, 16)?,
+        compatibility_version: macho.u32_at(data, 20)?,
+    })
+}
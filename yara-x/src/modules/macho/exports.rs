@@ -0,0 +1,101 @@
+/*! Symbols exported through the dyld export trie.
+
+The trie is referenced by the LC_DYLD_EXPORTS_TRIE command in recent
+files, and by the LC_DYLD_INFO(_ONLY) command in older ones. Each node
+has an optional terminal part, which is present when the path from the
+root to the node is the name of an exported symbol, followed by the edges
+to its children, which are labeled with the next part of the name.
+
+See: https://github.com/apple-oss-distributions/dyld/blob/main/mach-o/ExportsTrie.cpp
+*/
+
+use std::collections::HashSet;
+
+use super::parser::{
+    string, uleb128, MachO, LC_DYLD_EXPORTS_TRIE, LC_DYLD_INFO,
+    LC_DYLD_INFO_ONLY,
+};
+
+/// Maximum number of exported symbols.
+const MAX_EXPORTS: usize = 65536;
+
+/// Maximum length of the names of exported symbols.
+const MAX_NAME_LEN: usize = 4096;
+
+/// Returns the names of the symbols in the export trie, in depth-first
+/// order.
+pub(super) fn exports(macho: &MachO) -> Vec<Vec<u8>> {
+    let trie = match trie(macho) {
+        Some(trie) => trie,
+        None => return Vec::new(),
+    };
+
+    let mut exports = Vec::new();
+    // Nodes are visited only once, as a malformed trie could have cycles.
+    let mut visited = HashSet::new();
+    let mut pending = vec![(0_usize, Vec::new())];
+
+    while let Some((offset, name)) = pending.pop() {
+        if exports.len() >= MAX_EXPORTS || !visited.insert(offset) {
+            continue;
+        }
+
+        let mut pos = offset;
+
+        let terminal_size = match uleb128(trie, &mut pos) {
+            Some(size) => size as usize,
+            None => continue,
+        };
+
+        if terminal_size > 0 {
+            exports.push(name.clone());
+        }
+
+        pos = pos.saturating_add(terminal_size);
+
+        let children = match trie.get(pos) {
+            Some(children) => *children,
+            None => continue,
+        };
+
+        pos += 1;
+
+        let mut edges = Vec::new();
+
+        for _ in 0..children {
+            match (string(trie, &mut pos), uleb128(trie, &mut pos)) {
+                (Some(label), Some(child)) => {
+                    edges.push((label, child as usize));
+                }
+                _ => break,
+            }
+        }
+
+        // Children are pushed in reverse order, so that they are visited in
+        // the order they appear in the trie.
+        for (label, child) in edges.into_iter().rev() {
+            if name.len() + label.len() <= MAX_NAME_LEN {
+                pending.push((child, [name.as_slice(), label].concat()));
+            }
+        }
+    }
+
+    exports
+}
+
+/// Returns the export trie, from the LC_DYLD_EXPORTS_TRIE command or from
+/// the LC_DYLD_INFO(_ONLY) command.
+fn trie<'a>(macho: &MachO<'a>) -> Option<&'a [u8]> {
+    if let Some(trie) = macho.linkedit_data(LC_DYLD_EXPORTS_TRIE) {
+        return Some(trie);
+    }
+
+    let info = macho
+        .command(LC_DYLD_INFO_ONLY)
+        .or_else(|| macho.command(LC_DYLD_INFO))?;
+
+    let offset = macho.u32_at(info.data, 40)?;
+    let size = macho.u32_at(info.data, 44)?;
+
+    macho.data_at(offset.into(), size.into()).filter(|trie| !trie.is_empty())
+}
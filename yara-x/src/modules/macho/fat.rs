@@ -0,0 +1,95 @@
+/*! Parser for universal (fat) binaries.
+
+A universal binary is a table of architectures followed by a Mach-O file
+for each of them. The table is always big-endian, and uses 64-bit offsets
+and sizes when the magic is FAT_MAGIC_64.
+
+See: https://github.com/apple-oss-distributions/xnu/blob/main/EXTERNAL_HEADERS/mach-o/fat.h
+*/
+
+use crate::modules::utils::{u32_be_at, u64_be_at};
+
+/// Magic numbers, as read in big-endian.
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+/// Maximum number of architectures. Java class files have the same magic
+/// as universal binaries, followed by their version, which is 45 or more,
+/// where universal binaries have the number of architectures.
+const MAX_ARCHS: u32 = 32;
+
+/// A parsed universal binary.
+pub(super) struct FatBinary {
+    pub magic: u32,
+    pub archs: Vec<FatArch>,
+}
+
+/// An entry in the table of architectures.
+pub(super) struct FatArch {
+    pub cpu_type: u32,
+    pub cpu_subtype: u32,
+    pub offset: u64,
+    pub size: u64,
+    pub align: u32,
+    pub reserved: u32,
+}
+
+impl FatBinary {
+    /// Parses the table of architectures of a universal binary. Returns
+    /// `None` if the data is not a universal binary.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let magic = u32_be_at(data, 0)?;
+        let count = u32_be_at(data, 4)?;
+
+        if magic != FAT_MAGIC && magic != FAT_MAGIC_64
+            || count == 0
+            || count > MAX_ARCHS
+        {
+            return None;
+        }
+
+        let entry_size = if magic == FAT_MAGIC_64 { 32 } else { 20 };
+
+        let archs = data
+            .get(8..)?
+            .chunks_exact(entry_size)
+            .take(count as usize)
+            .filter_map(|entry| {
+                Some(if magic == FAT_MAGIC_64 {
+                    FatArch {
+                        cpu_type: u32_be_at(entry, 0)?,
+                        cpu_subtype: u32_be_at(entry, 4)?,
+                        offset: u64_be_at(entry, 8)?,
+                        size: u64_be_at(entry, 16)?,
+                        align: u32_be_at(entry, 24)?,
+                        reserved: u32_be_at(entry, 28)?,
+                    }
+                } else {
+                    FatArch {
+                        cpu_type: u32_be_at(entry, 0)?,
+                        cpu_subtype: u32_be_at(entry, 4)?,
+                        offset: u32_be_at(entry, 8)?.into(),
+                        size: u32_be_at(entry, 12)?.into(),
+                        align: u32_be_at(entry, 16)?,
+                        reserved: 0,
+                    }
+                })
+            })
+            .collect();
+
+        Some(Self { magic, archs })
+    }
+}
+
+impl FatArch {
+    /// Returns the Mach-O file for this architecture, or `None` if it is
+    /// not within `data`. The file is truncated if it exceeds the end of
+    /// `data`.
+    pub fn data<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let start = usize::try_from(self.offset).ok()?;
+        let end = start
+            .saturating_add(usize::try_from(self.size).unwrap_or(usize::MAX))
+            .min(data.len());
+        data.get(start..end)
+    }
+}
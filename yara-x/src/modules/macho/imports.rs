@@ -0,0 +1,176 @@
+/*! Symbols imported from other images.
+
+Older files describe the symbols that dyld must bind with the opcodes
+referenced by the LC_DYLD_INFO(_ONLY) command, while recent ones list them
+in the imports table of the LC_DYLD_CHAINED_FIXUPS command.
+
+See: https://github.com/apple-oss-distributions/dyld/blob/main/common/MachOLayout.cpp
+*/
+
+use std::collections::HashSet;
+
+use super::parser::{
+    string, string_at, uleb128, MachO, LC_DYLD_CHAINED_FIXUPS, LC_DYLD_INFO,
+    LC_DYLD_INFO_ONLY,
+};
+
+/// Maximum number of imported symbols.
+const MAX_IMPORTS: usize = 65536;
+
+/// Bind opcodes, in the upper 4 bits of each opcode byte.
+const BIND_OPCODE_DONE: u8 = 0x00;
+const BIND_OPCODE_SET_DYLIB_ORDINAL_IMM: u8 = 0x10;
+const BIND_OPCODE_SET_DYLIB_ORDINAL_ULEB: u8 = 0x20;
+const BIND_OPCODE_SET_DYLIB_SPECIAL_IMM: u8 = 0x30;
+const BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM: u8 = 0x40;
+const BIND_OPCODE_SET_TYPE_IMM: u8 = 0x50;
+const BIND_OPCODE_SET_ADDEND_SLEB: u8 = 0x60;
+const BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB: u8 = 0x70;
+const BIND_OPCODE_ADD_ADDR_ULEB: u8 = 0x80;
+const BIND_OPCODE_DO_BIND: u8 = 0x90;
+const BIND_OPCODE_DO_BIND_ADD_ADDR_ULEB: u8 = 0xa0;
+const BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED: u8 = 0xb0;
+const BIND_OPCODE_DO_BIND_ULEB_TIMES_SKIPPING_ULEB: u8 = 0xc0;
+const BIND_OPCODE_THREADED: u8 = 0xd0;
+
+/// Sub-opcode of BIND_OPCODE_THREADED that is followed by an operand.
+const BIND_SUBOPCODE_THREADED_SET_BIND_ORDINAL_TABLE_SIZE_ULEB: u8 = 0x00;
+
+/// Formats of the imports table of chained fixups.
+const DYLD_CHAINED_IMPORT: u32 = 1;
+const DYLD_CHAINED_IMPORT_ADDEND: u32 = 2;
+const DYLD_CHAINED_IMPORT_ADDEND64: u32 = 3;
+
+/// Returns the names of the imported symbols, without duplicates, in the
+/// order they are bound.
+pub(super) fn imports<'a>(macho: &MachO<'a>) -> Vec<&'a [u8]> {
+    let mut names = chained_fixups(macho).unwrap_or_default();
+
+    if let Some(info) = macho
+        .command(LC_DYLD_INFO_ONLY)
+        .or_else(|| macho.command(LC_DYLD_INFO))
+    {
+        // Offsets of the regular and lazy binding info within the command.
+        for (offset, lazy) in [(16, false), (32, true)] {
+            let opcodes = macho.u32_at(info.data, offset).and_then(|start| {
+                let size = macho.u32_at(info.data, offset + 4)?;
+                macho.data_at(start.into(), size.into())
+            });
+            if let Some(opcodes) = opcodes {
+                bound_symbols(opcodes, lazy, &mut names);
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(*name));
+    names.truncate(MAX_IMPORTS);
+    names
+}
+
+/// Runs the bind opcodes, and appends the names of the bound symbols to
+/// `names`. The lazy binding info has an entry for each symbol, terminated
+/// by BIND_OPCODE_DONE, so the opcode doesn't stop the lazy binding.
+/// Returns `None` if an opcode is unknown or truncated.
+fn bound_symbols<'a>(
+    opcodes: &'a [u8],
+    lazy: bool,
+    names: &mut Vec<&'a [u8]>,
+) -> Option<()> {
+    let mut pos = 0;
+    let mut symbol = None;
+
+    while let Some(byte) = opcodes.get(pos) {
+        pos += 1;
+
+        if names.len() >= MAX_IMPORTS {
+            break;
+        }
+
+        match byte & 0xf0 {
+            BIND_OPCODE_DONE if lazy => {}
+            BIND_OPCODE_DONE => break,
+            BIND_OPCODE_SET_DYLIB_ORDINAL_IMM
+            | BIND_OPCODE_SET_DYLIB_SPECIAL_IMM
+            | BIND_OPCODE_SET_TYPE_IMM => {}
+            // Signed LEB128 integers are skipped as unsigned ones, as their
+            // values are not used.
+            BIND_OPCODE_SET_DYLIB_ORDINAL_ULEB
+            | BIND_OPCODE_SET_ADDEND_SLEB
+            | BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB
+            | BIND_OPCODE_ADD_ADDR_ULEB => {
+                uleb128(opcodes, &mut pos)?;
+            }
+            BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM => {
+                symbol = Some(string(opcodes, &mut pos)?);
+            }
+            BIND_OPCODE_DO_BIND | BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED => {
+                names.extend(symbol);
+            }
+            BIND_OPCODE_DO_BIND_ADD_ADDR_ULEB => {
+                names.extend(symbol);
+                uleb128(opcodes, &mut pos)?;
+            }
+            BIND_OPCODE_DO_BIND_ULEB_TIMES_SKIPPING_ULEB => {
+                names.extend(symbol);
+                uleb128(opcodes, &mut pos)?;
+                uleb128(opcodes, &mut pos)?;
+            }
+            BIND_OPCODE_THREADED => {
+                if byte & 0x0f
+                    == BIND_SUBOPCODE_THREADED_SET_BIND_ORDINAL_TABLE_SIZE_ULEB
+                {
+                    uleb128(opcodes, &mut pos)?;
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    Some(())
+}
+
+/// Returns the names in the imports table of the LC_DYLD_CHAINED_FIXUPS
+/// command. Returns `None` if there's no such command, or the table uses
+/// an unknown format.
+fn chained_fixups<'a>(macho: &MachO<'a>) -> Option<Vec<&'a [u8]>> {
+    let fixups = macho.linkedit_data(LC_DYLD_CHAINED_FIXUPS)?;
+    let imports_offset = macho.u32_at(fixups, 8)? as usize;
+    let symbols_offset = macho.u32_at(fixups, 12)? as usize;
+    let count = macho.u32_at(fixups, 16)? as usize;
+    let format = macho.u32_at(fixups, 20)?;
+    let symbols_format = macho.u32_at(fixups, 24)?;
+
+    // Compressed symbol names are not supported.
+    if symbols_format != 0 {
+        return None;
+    }
+
+    let entry_size = match format {
+        DYLD_CHAINED_IMPORT => 4,
+        DYLD_CHAINED_IMPORT_ADDEND => 8,
+        DYLD_CHAINED_IMPORT_ADDEND64 => 16,
+        _ => return None,
+    };
+
+    let symbols = fixups.get(symbols_offset..)?;
+
+    Some(
+        fixups
+            .get(imports_offset..)?
+            .chunks_exact(entry_size)
+            .take(count.min(MAX_IMPORTS))
+            .filter_map(|entry| {
+                // The offset of the name is in the upper 23 bits of the
+                // 32-bit formats, and in the upper 32 bits of the 64-bit
+                // one.
+                let name = if format == DYLD_CHAINED_IMPORT_ADDEND64 {
+                    (macho.u64_at(entry, 0)? >> 32) as u32
+                } else {
+                    macho.u32_at(entry, 0)? >> 9
+                };
+                string_at(symbols, name)
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,887 @@
+/*! YARA module that parses Mach-O files.
+
+The module exposes the header, the segments and the symbol table of
+32-bit and 64-bit files, in either byte order, and the symbols imported
+and exported through dyld. The imported symbols are the base of `symhash`,
+which like `imphash` for PE files is useful for clustering files that use
+the same APIs.
+
+Universal binaries have a Mach-O file for each architecture, which are
+parsed into the `file` array. The first one is also copied to the root
+structure, so that rules written for single-architecture files work with
+universal binaries too.
+
+See: https://github.com/apple-oss-distributions/xnu/blob/main/EXTERNAL_HEADERS/mach-o/loader.h
+*/
+
+use protobuf::reflect::RuntimeFieldType;
+use protobuf::{EnumOrUnknown, MessageField, MessageFull};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::macho::*;
+use crate::modules::utils::digest::md5_hex;
+
+use fat::FatBinary;
+use parser::{MachO, N_EXT, N_STAB, N_TYPE, N_UNDF};
+
+mod dylibs;
+mod exports;
+mod fat;
+mod imports;
+mod objc;
+mod parser;
+mod versions;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Macho {
+    let data = ctx.scanned_data();
+    let mut macho = Macho::new();
+
+    if let Some(fat) = FatBinary::parse(data) {
+        macho.set_is_macho(true);
+        macho.set_fat_magic(fat.magic.into());
+        macho.set_nfat_arch(fat.archs.len() as i64);
+
+        for arch in &fat.archs {
+            let mut fat_arch = MachoFatArch::new();
+            fat_arch.cputype =
+                Some(EnumOrUnknown::from_i32(arch.cpu_type as i32));
+            fat_arch.set_cpusubtype(arch.cpu_subtype.into());
+            fat_arch.set_offset(arch.offset as i64);
+            fat_arch.set_size(arch.size as i64);
+            fat_arch.set_align(arch.align.into());
+            fat_arch.set_reserved(arch.reserved.into());
+            macho.fat_arch.push(fat_arch);
+
+            let mut file = MachoFile::new();
+            if let Some(parsed) = arch.data(data).and_then(MachO::parse) {
+                parse(&parsed, arch.offset, &mut file);
+            }
+            macho.file.push(file);
+        }
+
+        if let Some(file) = macho.file.first().cloned() {
+            select(&file, &mut macho);
+        }
+    } else if let Some(parsed) = MachO::parse(data) {
+        let mut file = MachoFile::new();
+        parse(&parsed, 0, &mut file);
+        select(&file, &mut macho);
+        macho.set_is_macho(true);
+    } else {
+        macho.set_is_macho(false);
+    }
+
+    macho
+}
+
+/// Returns the `symhash` of the file, which is the MD5 of a comma-separated
+/// list with the names of the undefined external symbols in the symbol
+/// table, sorted alphabetically, as a hex string. Undefined if the file
+/// doesn't have such symbols.
+#[module_export]
+fn symhash(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let macho = ctx.module_output::<Macho>()?;
+
+    let mut names: Vec<&str> = macho
+        .symbols
+        .iter()
+        .filter(|s| {
+            let type_ = s.type_();
+            type_ & i64::from(N_STAB) == 0
+                && type_ & i64::from(N_TYPE) == i64::from(N_UNDF)
+                && type_ & i64::from(N_EXT) != 0
+        })
+        .map(|s| s.name())
+        .collect();
+
+    if names.is_empty() {
+        return None;
+    }
+
+    names.sort_unstable();
+
+    let digest = md5_hex(names.join(",").as_bytes());
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns true if the file imports a symbol with the given name.
+#[module_export]
+fn has_import(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let macho = ctx.module_output::<Macho>()?;
+    let name = name.as_bstr(ctx);
+    Some(macho.imports.iter().any(|import| import.as_bytes() == name))
+}
+
+/// Returns true if the file exports a symbol with the given name.
+#[module_export]
+fn has_export(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let macho = ctx.module_output::<Macho>()?;
+    let name = name.as_bstr(ctx);
+    Some(macho.exports.iter().any(|export| export.as_bytes() == name))
+}
+
+/// Returns true if the file links to a library with the given name, like
+/// "/usr/lib/libSystem.B.dylib".
+#[module_export]
+fn has_dylib(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let macho = ctx.module_output::<Macho>()?;
+    let name = name.as_bstr(ctx);
+    Some(macho.dylibs.iter().any(|dylib| dylib.name().as_bytes() == name))
+}
+
+/// Returns true if the file has a LC_RPATH command with the given path.
+#[module_export]
+fn has_rpath(ctx: &ScanContext, path: RuntimeString) -> Option<bool> {
+    let macho = ctx.module_output::<Macho>()?;
+    let path = path.as_bstr(ctx);
+    Some(macho.rpaths.iter().any(|rpath| rpath.as_bytes() == path))
+}
+
+/// Returns the index in `file` of the first architecture with the given
+/// CPU type, or undefined if the file doesn't have such architecture. The
+/// index is 0 for files that are not universal binaries, provided that the
+/// CPU type matches.
+#[module_export(name = "file_index_for_arch")]
+fn file_index_for_arch_type(ctx: &ScanContext, cputype: i64) -> Option<i64> {
+    file_index_for_arch(ctx, cputype, None)
+}
+
+/// Like `file_index_for_arch(cputype)`, but the CPU subtype must match
+/// too.
+#[module_export(name = "file_index_for_arch")]
+fn file_index_for_arch_subtype(
+    ctx: &ScanContext,
+    cputype: i64,
+    cpusubtype: i64,
+) -> Option<i64> {
+    file_index_for_arch(ctx, cputype, Some(cpusubtype))
+}
+
+/// Returns the entry point of the first architecture with the given CPU
+/// type, as an offset within the scanned data. Undefined if the file
+/// doesn't have such architecture, or the architecture doesn't have an
+/// entry point.
+#[module_export(name = "entry_point_for_arch")]
+fn entry_point_for_arch_type(ctx: &ScanContext, cputype: i64) -> Option<i64> {
+    entry_point_for_arch(ctx, cputype, None)
+}
+
+/// Like `entry_point_for_arch(cputype)`, but the CPU subtype must match
+/// too.
+#[module_export(name = "entry_point_for_arch")]
+fn entry_point_for_arch_subtype(
+    ctx: &ScanContext,
+    cputype: i64,
+    cpusubtype: i64,
+) -> Option<i64> {
+    entry_point_for_arch(ctx, cputype, Some(cpusubtype))
+}
+
+fn file_index_for_arch(
+    ctx: &ScanContext,
+    cputype: i64,
+    cpusubtype: Option<i64>,
+) -> Option<i64> {
+    let macho = ctx.module_output::<Macho>()?;
+    let is_match = |t: i32, s: i64| {
+        i64::from(t) == cputype && cpusubtype.map_or(true, |c| c == s)
+    };
+
+    if macho.fat_magic.is_none() {
+        return macho
+            .cputype
+            .filter(|t| is_match(t.value(), macho.cpusubtype()))
+            .map(|_| 0);
+    }
+
+    macho
+        .fat_arch
+        .iter()
+        .position(|arch| {
+            arch.cputype
+                .map_or(false, |t| is_match(t.value(), arch.cpusubtype()))
+        })
+        .map(|index| index as i64)
+}
+
+fn entry_point_for_arch(
+    ctx: &ScanContext,
+    cputype: i64,
+    cpusubtype: Option<i64>,
+) -> Option<i64> {
+    let index = file_index_for_arch(ctx, cputype, cpusubtype)?;
+    let macho = ctx.module_output::<Macho>()?;
+
+    if macho.fat_magic.is_none() {
+        macho.entry_point
+    } else {
+        macho.file.get(index as usize)?.entry_point
+    }
+}
+
+/// Copies the fields of an architecture's file to the fields with the same
+/// names in the root structure.
+fn select(file: &MachoFile, macho: &mut Macho) {
+    let root = Macho::descriptor();
+
+    for field in MachoFile::descriptor().fields() {
+        let target = match root.field_by_name(field.name()) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        match field.runtime_field_type() {
+            RuntimeFieldType::Singular(_) => {
+                if let Some(value) = field.get_singular(file) {
+                    target.set_singular_field(macho, value.to_box());
+                }
+            }
+            RuntimeFieldType::Repeated(_) => {
+                let items = field.get_repeated(file);
+                let mut array = target.mut_repeated(macho);
+                for i in 0..items.len() {
+                    array.push(items.get(i).to_box());
+                }
+            }
+            RuntimeFieldType::Map(..) => {
+                let mut map = target.mut_map(macho);
+                for (key, value) in &field.get_map(file) {
+                    map.insert(key.to_box(), value.to_box());
+                }
+            }
+        }
+    }
+}
+
+/// Parses a Mach-O file that starts at the given offset within the scanned
+/// data.
+fn parse(parsed: &MachO, offset: u64, macho: &mut MachoFile) {
+    let string = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+
+    macho.set_magic(parsed.magic.into());
+    macho.cputype = Some(EnumOrUnknown::from_i32(parsed.cpu_type as i32));
+    macho.set_cpusubtype(parsed.cpu_subtype.into());
+    macho.filetype = Some(EnumOrUnknown::from_i32(parsed.file_type as i32));
+    macho.set_ncmds(parsed.number_of_commands.into());
+    macho.set_sizeofcmds(parsed.size_of_commands.into());
+    macho.set_flags(parsed.flags.into());
+    macho.entry_point =
+        parsed.entry_point().map(|entry_point| (offset + entry_point) as i64);
+    macho.stack_size = parsed.stack_size().map(|size| size as i64);
+
+    for s in &parsed.segments {
+        let mut segment = MachoSegment::new();
+        segment.set_segname(string(s.name));
+        segment.set_vmaddr(s.address as i64);
+        segment.set_vmsize(s.size as i64);
+        segment.set_fileoff(s.offset as i64);
+        segment.set_filesize(s.file_size as i64);
+        segment.set_maxprot(s.max_protection.into());
+        segment.set_initprot(s.initial_protection.into());
+        segment.set_nsects(s.number_of_sections.into());
+        segment.set_flags(s.flags.into());
+
+        for s in &s.sections {
+            let mut section = MachoSection::new();
+            section.set_segname(string(s.segment_name));
+            section.set_sectname(string(s.name));
+            section.set_addr(s.address as i64);
+            section.set_size(s.size as i64);
+            section.set_offset(s.offset.into());
+            section.set_align(s.alignment.into());
+            section.set_reloff(s.relocations_offset.into());
+            section.set_nreloc(s.number_of_relocations.into());
+            section.set_flags(s.flags.into());
+            segment.sections.push(section);
+        }
+
+        macho.segments.push(segment);
+    }
+
+    macho.set_number_of_segments(macho.segments.len() as i64);
+
+    if let Some(symbols) = parsed.symbols() {
+        for s in &symbols {
+            let mut symbol = MachoSymbol::new();
+            symbol.set_name(string(s.name));
+            symbol.set_type(s.type_.into());
+            symbol.set_sect(s.section.into());
+            symbol.set_desc(s.description.into());
+            symbol.set_value(s.value as i64);
+            macho.symbols.push(symbol);
+        }
+        macho.set_number_of_symbols(macho.symbols.len() as i64);
+    }
+
+    macho.dylibs = dylibs::dylibs(parsed).iter().map(dylib).collect();
+    macho.id_dylib = dylibs::id_dylib(parsed).as_ref().map(dylib).into();
+    macho.rpaths = dylibs::rpaths(parsed).into_iter().map(string).collect();
+
+    if let Some(build_version) = versions::build_version(parsed) {
+        macho.platform =
+            Some(EnumOrUnknown::from_i32(build_version.platform as i32));
+        macho.min_os_version = Some(versions::version(build_version.min_os));
+        macho.sdk_version = Some(versions::version(build_version.sdk));
+
+        for (tool, version) in build_version.tools {
+            let mut build_tool = MachoBuildTool::new();
+            build_tool.tool = Some(EnumOrUnknown::from_i32(tool as i32));
+            build_tool.set_version(versions::version(version));
+            macho.build_tools.push(build_tool);
+        }
+    }
+
+    if let Some(e) = parsed.encryption_info() {
+        let mut encryption_info = MachoEncryptionInfo::new();
+        encryption_info.set_cryptoff(e.offset.into());
+        encryption_info.set_cryptsize(e.size.into());
+        encryption_info.set_cryptid(e.id.into());
+        macho.encryption_info = MessageField::some(encryption_info);
+    }
+
+    macho.objc_classes = objc::names(parsed, b"__objc_classname")
+        .into_iter()
+        .map(string)
+        .collect();
+    macho.objc_selectors = objc::names(parsed, b"__objc_methname")
+        .into_iter()
+        .map(string)
+        .collect();
+
+    macho.exports =
+        exports::exports(parsed).iter().map(|e| string(e)).collect();
+    macho.imports = imports::imports(parsed).into_iter().map(string).collect();
+}
+
+fn dylib(d: &dylibs::Dylib) -> MachoDylib {
+    let mut dylib = MachoDylib::new();
+    dylib.set_name(String::from_utf8_lossy(d.name).into_owned());
+    dylib.set_cmd(d.type_.into());
+    dylib.set_weak(d.type_ == dylibs::LC_LOAD_WEAK_DYLIB);
+    dylib.set_timestamp(d.timestamp.into());
+    dylib.set_current_version(versions::version(d.current_version));
+    dylib
+        .set_compatibility_version(versions::version(d.compatibility_version));
+    dylib
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::utils::digest::md5_hex;
+
+    /// Virtual address where the test files are loaded.
+    const BASE: u64 = 0x100000000;
+
+    /// Size of the test files.
+    const SIZE: usize = 0x2000;
+
+    /// Builds a little-endian 64-bit x86-64 executable with the given load
+    /// commands, which are tuples with the type and the content that
+    /// follows the command size. The file has `SIZE` bytes, and each tuple
+    /// in `data` is copied at the given offset.
+    fn build(commands: &[(u32, Vec<u8>)], data: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend(0xfeedfacf_u32.to_le_bytes());
+        file.extend(0x01000007_u32.to_le_bytes());
+        file.extend(3_u32.to_le_bytes());
+        file.extend(2_u32.to_le_bytes());
+        file.extend((commands.len() as u32).to_le_bytes());
+        file.extend(0_u32.to_le_bytes());
+        file.extend(0x200085_u32.to_le_bytes());
+        file.extend(0_u32.to_le_bytes());
+
+        for (type_, content) in commands {
+            file.extend(type_.to_le_bytes());
+            file.extend((content.len() as u32 + 8).to_le_bytes());
+            file.extend(content);
+        }
+
+        let size_of_commands = (file.len() - 32) as u32;
+        file[20..24].copy_from_slice(&size_of_commands.to_le_bytes());
+
+        file.resize(SIZE, 0);
+
+        for (offset, bytes) in data {
+            file[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        file
+    }
+
+    /// Returns a LC_SEGMENT_64 command for a __TEXT segment that covers the
+    /// whole file, with a __text section at offset 0x400.
+    fn text_segment() -> (u32, Vec<u8>) {
+        text_segment_with(&[("__text", 0x400, 0x10)])
+    }
+
+    /// Returns a LC_SEGMENT_64 command for a __TEXT segment that covers the
+    /// whole file, with sections that have the given names, offsets and
+    /// sizes.
+    fn text_segment_with(sections: &[(&str, u32, u64)]) -> (u32, Vec<u8>) {
+        let name = |name: &str| {
+            let mut field = [0_u8; 16];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+            field
+        };
+
+        let mut segment = Vec::new();
+        segment.extend(name("__TEXT"));
+        segment.extend(BASE.to_le_bytes());
+        segment.extend((SIZE as u64).to_le_bytes());
+        segment.extend(0_u64.to_le_bytes());
+        segment.extend((SIZE as u64).to_le_bytes());
+        segment.extend(5_u32.to_le_bytes());
+        segment.extend(5_u32.to_le_bytes());
+        segment.extend((sections.len() as u32).to_le_bytes());
+        segment.extend(0_u32.to_le_bytes());
+
+        for (section, offset, size) in sections {
+            segment.extend(name(section));
+            segment.extend(name("__TEXT"));
+            segment.extend((BASE + *offset as u64).to_le_bytes());
+            segment.extend(size.to_le_bytes());
+            segment.extend(offset.to_le_bytes());
+            segment.extend(4_u32.to_le_bytes());
+            segment.extend([0; 8]);
+            segment.extend(0x80000400_u32.to_le_bytes());
+            segment.extend([0; 12]);
+        }
+
+        (0x19, segment)
+    }
+
+    /// Returns the content of a `linkedit_data_command`, or of any other
+    /// command with pairs of 32-bit offsets and sizes.
+    fn offsets(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    /// Returns an export trie with the symbols `_main` and `_helper`.
+    fn export_trie() -> Vec<u8> {
+        let mut trie = vec![0, 1, b'_', 0, 5];
+        trie.extend([0, 2]);
+        trie.extend(b"main\0");
+        trie.push(21);
+        trie.extend(b"helper\0");
+        trie.push(25);
+        trie.extend([2, 0, 0x10, 0]);
+        trie.extend([2, 0, 0x20, 0]);
+        trie
+    }
+
+    fn scan(rules: &str, data: &[u8]) -> Vec<String> {
+        let rules = crate::compile(rules).unwrap();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+        let results = scanner.scan(data).unwrap();
+        results.matching_rules().map(|rule| rule.name().to_string()).collect()
+    }
+
+    #[test]
+    fn dyld_info() {
+        let mut bind = vec![0x11, 0x40];
+        bind.extend(b"_malloc\0");
+        bind.extend([0x51, 0x72, 0x10, 0x90, 0x40]);
+        bind.extend(b"_free\0");
+        bind.extend([0x90, 0x00]);
+
+        let mut lazy_bind = vec![0x72, 0x18, 0x11, 0x40];
+        lazy_bind.extend(b"_printf\0");
+        lazy_bind.extend([0x90, 0x00, 0x72, 0x20, 0x11, 0x40]);
+        lazy_bind.extend(b"_malloc\0");
+        lazy_bind.extend([0x90, 0x00]);
+
+        let strings = b"\0_main\0_printf\0_free\0_malloc\0foo.c\0";
+
+        let mut symbols = Vec::new();
+        for (name, type_, section) in [
+            (1_u32, 0x0f_u8, 1_u8),
+            (7, 0x01, 0),
+            (15, 0x01, 0),
+            (21, 0x01, 0),
+            (29, 0x64, 0),
+        ] {
+            symbols.extend(name.to_le_bytes());
+            symbols.extend([type_, section, 0, 0]);
+            symbols.extend(0_u64.to_le_bytes());
+        }
+
+        let trie = export_trie();
+
+        let mut main = 0x400_u64.to_le_bytes().to_vec();
+        main.extend(0x10000_u64.to_le_bytes());
+
+        let data = build(
+            &[
+                text_segment(),
+                (
+                    0x80000022,
+                    offsets(&[
+                        0,
+                        0,
+                        0x1000,
+                        bind.len() as u32,
+                        0,
+                        0,
+                        0x1100,
+                        lazy_bind.len() as u32,
+                        0x1200,
+                        trie.len() as u32,
+                    ]),
+                ),
+                (0x2, offsets(&[0x1300, 5, 0x1400, strings.len() as u32])),
+                (0x80000028, main),
+            ],
+            &[
+                (0x1000, &bind),
+                (0x1100, &lazy_bind),
+                (0x1200, &trie),
+                (0x1300, &symbols),
+                (0x1400, strings),
+            ],
+        );
+
+        let symhash = md5_hex(b"_free,_malloc,_printf");
+
+        assert_eq!(
+            scan(
+                format!(
+                    r#"import "macho"
+                    rule headers {{
+                      condition:
+                        macho.is_macho and
+                        macho.magic == 0xfeedfacf and
+                        macho.cputype == macho.CpuType.CPU_TYPE_X86_64 and
+                        macho.filetype == macho.FileType.MH_EXECUTE and
+                        macho.ncmds == 4 and
+                        macho.flags & macho.HeaderFlag.MH_PIE != 0 and
+                        macho.entry_point == 0x400 and
+                        macho.stack_size == 0x10000 and
+                        macho.number_of_segments == 1 and
+                        macho.segments[0].segname == "__TEXT" and
+                        macho.segments[0].initprot ==
+                          macho.Protection.VM_PROT_READ |
+                          macho.Protection.VM_PROT_EXECUTE and
+                        macho.segments[0].sections[0].sectname == "__text" and
+                        macho.segments[0].sections[0].addr == 0x100000400
+                    }}
+                    rule symbols {{
+                      condition:
+                        macho.number_of_symbols == 5 and
+                        macho.symbols[0].name == "_main" and
+                        macho.symbols[0].type & macho.SymbolMask.N_TYPE ==
+                          macho.SymbolType.N_SECT and
+                        macho.symhash() == "{symhash}"
+                    }}
+                    rule imports_and_exports {{
+                      condition:
+                        macho.imports[0] == "_malloc" and
+                        macho.imports[1] == "_free" and
+                        macho.imports[2] == "_printf" and
+                        not defined macho.imports[3] and
+                        macho.exports[0] == "_main" and
+                        macho.exports[1] == "_helper" and
+                        macho.has_import("_printf") and
+                        not macho.has_import("_main") and
+                        macho.has_export("_helper")
+                    }}
+                    "#
+                )
+                .as_str(),
+                &data
+            ),
+            ["headers", "symbols", "imports_and_exports"]
+        );
+    }
+
+    #[test]
+    fn chained_fixups() {
+        let mut fixups = offsets(&[0, 0, 32, 40, 2, 1, 0, 0]);
+        // Imports from the first library, with the offset of the name in
+        // the upper 23 bits.
+        fixups.extend(1_u32.to_le_bytes());
+        fixups.extend((1_u32 | (14 << 9)).to_le_bytes());
+        fixups.extend(b"_objc_msgSend\0_NSLog\0");
+
+        let trie = export_trie();
+
+        // x86_THREAD_STATE64, with rip pointing to the __text section.
+        let mut thread = offsets(&[4, 42]);
+        thread.extend([0; 128]);
+        thread.extend((BASE + 0x400).to_le_bytes());
+        thread.extend([0; 32]);
+
+        let data = build(
+            &[
+                text_segment(),
+                (0x5, thread),
+                (0x80000034, offsets(&[0x1000, fixups.len() as u32])),
+                (0x80000033, offsets(&[0x1100, trie.len() as u32])),
+            ],
+            &[(0x1000, &fixups), (0x1100, &trie)],
+        );
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule chained_fixups {
+                  condition:
+                    macho.entry_point == 0x400 and
+                    not defined macho.stack_size and
+                    not defined macho.number_of_symbols and
+                    not defined macho.symhash() and
+                    macho.imports[0] == "_objc_msgSend" and
+                    macho.imports[1] == "_NSLog" and
+                    macho.has_import("_NSLog") and
+                    macho.has_export("_main")
+                }
+                rule not_macho {
+                  condition:
+                    not macho.is_macho
+                }
+                "#,
+                &data
+            ),
+            ["chained_fixups"]
+        );
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule not_macho {
+                  condition:
+                    not macho.is_macho and not defined macho.symhash()
+                }
+                "#,
+                b"MZ"
+            ),
+            ["not_macho"]
+        );
+    }
+
+    #[test]
+    fn universal_binary() {
+        let main = |entry_point: u64| {
+            let mut main = entry_point.to_le_bytes().to_vec();
+            main.extend(0_u64.to_le_bytes());
+            (0x80000028, main)
+        };
+
+        let x86_64 = build(&[text_segment(), main(0x400)], &[]);
+
+        let mut arm64 = build(&[text_segment(), main(0x800)], &[]);
+        arm64[4..8].copy_from_slice(&0x0100000c_u32.to_le_bytes());
+
+        let mut data = Vec::new();
+        data.extend(0xcafebabe_u32.to_be_bytes());
+        data.extend(2_u32.to_be_bytes());
+        for (cputype, offset) in
+            [(0x01000007_u32, 0x1000), (0x0100000c, 0x3000)]
+        {
+            data.extend(cputype.to_be_bytes());
+            data.extend(3_u32.to_be_bytes());
+            data.extend((offset as u32).to_be_bytes());
+            data.extend((SIZE as u32).to_be_bytes());
+            data.extend(12_u32.to_be_bytes());
+        }
+        data.resize(0x1000, 0);
+        data.extend(&x86_64);
+        data.resize(0x3000, 0);
+        data.extend(&arm64);
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule universal_binary {
+                  condition:
+                    macho.is_macho and
+                    macho.fat_magic == 0xcafebabe and
+                    macho.nfat_arch == 2 and
+                    macho.fat_arch[0].cputype ==
+                      macho.CpuType.CPU_TYPE_X86_64 and
+                    macho.fat_arch[1].cputype ==
+                      macho.CpuType.CPU_TYPE_ARM64 and
+                    macho.fat_arch[1].offset == 0x3000 and
+                    macho.fat_arch[1].align == 12 and
+                    macho.file[0].cputype == macho.CpuType.CPU_TYPE_X86_64 and
+                    macho.file[1].cputype == macho.CpuType.CPU_TYPE_ARM64 and
+                    macho.file[0].entry_point == 0x1400 and
+                    macho.file[1].entry_point == 0x3800 and
+                    macho.file[1].segments[0].segname == "__TEXT" and
+                    macho.cputype == macho.CpuType.CPU_TYPE_X86_64 and
+                    macho.entry_point == 0x1400 and
+                    macho.number_of_segments == 1 and
+                    macho.file_index_for_arch(
+                      macho.CpuType.CPU_TYPE_ARM64) == 1 and
+                    macho.file_index_for_arch(
+                      macho.CpuType.CPU_TYPE_ARM64, 3) == 1 and
+                    not defined macho.file_index_for_arch(
+                      macho.CpuType.CPU_TYPE_ARM64, 2) and
+                    not defined macho.file_index_for_arch(
+                      macho.CpuType.CPU_TYPE_X86) and
+                    macho.entry_point_for_arch(
+                      macho.CpuType.CPU_TYPE_ARM64) == 0x3800
+                }
+                "#,
+                &data
+            ),
+            ["universal_binary"]
+        );
+
+        // Java class files have the same magic number.
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule not_macho {
+                  condition:
+                    not macho.is_macho
+                }
+                "#,
+                b"\xca\xfe\xba\xbe\x00\x00\x00\x34"
+            ),
+            ["not_macho"]
+        );
+    }
+
+    #[test]
+    fn dylibs_and_versions() {
+        let dylib = |type_: u32, name: &str, current: u32, compat: u32| {
+            let mut dylib = offsets(&[24, 2, current, compat]);
+            dylib.extend(name.as_bytes());
+            dylib.resize((dylib.len() + 8) & !7, 0);
+            (type_, dylib)
+        };
+
+        let rpath = |path: &str| {
+            let mut rpath = offsets(&[12]);
+            rpath.extend(path.as_bytes());
+            rpath.resize((rpath.len() + 8) & !7, 0);
+            (0x8000001c, rpath)
+        };
+
+        let data = build(
+            &[
+                text_segment(),
+                dylib(0xd, "@rpath/libme.dylib", 0x20100, 0x10000),
+                dylib(0xc, "/usr/lib/libSystem.B.dylib", 0x5270000, 0x10000),
+                dylib(0x80000018, "@rpath/libfoo.dylib", 0x10000, 0x10000),
+                rpath("@executable_path/../Frameworks"),
+                rpath("/tmp"),
+                (0x32, offsets(&[1, 0xb0000, 0xe0200, 1, 3, 0x3ba0100])),
+            ],
+            &[],
+        );
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule dylibs {
+                  condition:
+                    macho.id_dylib.name == "@rpath/libme.dylib" and
+                    macho.id_dylib.current_version == "2.1.0" and
+                    macho.dylibs[0].name == "/usr/lib/libSystem.B.dylib" and
+                    macho.dylibs[0].cmd == 0xc and
+                    not macho.dylibs[0].weak and
+                    macho.dylibs[0].current_version == "1319.0.0" and
+                    macho.dylibs[0].compatibility_version == "1.0.0" and
+                    macho.dylibs[0].timestamp == 2 and
+                    macho.dylibs[1].name == "@rpath/libfoo.dylib" and
+                    macho.dylibs[1].weak and
+                    not defined macho.dylibs[2].name and
+                    macho.rpaths[0] == "@executable_path/../Frameworks" and
+                    macho.rpaths[1] == "/tmp" and
+                    macho.has_dylib("@rpath/libfoo.dylib") and
+                    not macho.has_dylib("@rpath/libme.dylib") and
+                    macho.has_rpath("/tmp")
+                }
+                rule versions {
+                  condition:
+                    macho.platform == macho.Platform.PLATFORM_MACOS and
+                    macho.min_os_version == "11.0.0" and
+                    macho.sdk_version == "14.2.0" and
+                    macho.build_tools[0].tool == macho.Tool.TOOL_LD and
+                    macho.build_tools[0].version == "954.1.0"
+                }
+                "#,
+                &data
+            ),
+            ["dylibs", "versions"]
+        );
+    }
+
+    #[test]
+    fn objc() {
+        let classes = b"AppDelegate\0Keylogger\0";
+        let selectors =
+            b"init\0\0addGlobalMonitorForEventsMatchingMask:handler:\0";
+
+        let data = build(
+            &[text_segment_with(&[
+                ("__objc_classname", 0x1000, classes.len() as u64),
+                ("__objc_methname", 0x1100, selectors.len() as u64),
+            ])],
+            &[(0x1000, classes), (0x1100, selectors)],
+        );
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule objc {
+                  condition:
+                    macho.objc_classes[0] == "AppDelegate" and
+                    macho.objc_classes[1] == "Keylogger" and
+                    not defined macho.objc_classes[2] and
+                    macho.objc_selectors[0] == "init" and
+                    macho.objc_selectors[1] ==
+                      "addGlobalMonitorForEventsMatchingMask:handler:" and
+                    for any selector in macho.objc_selectors : (
+                      selector == "init"
+                    )
+                }
+                "#,
+                &data
+            ),
+            ["objc"]
+        );
+    }
+
+    #[test]
+    fn encryption_info() {
+        let data = build(
+            &[text_segment(), (0x2c, offsets(&[0x1000, 0x1000, 1, 0]))],
+            &[],
+        );
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule encrypted {
+                  condition:
+                    macho.encryption_info.cryptoff == 0x1000 and
+                    macho.encryption_info.cryptsize == 0x1000 and
+                    macho.encryption_info.cryptid == 1
+                }
+                rule not_encrypted {
+                  condition:
+                    not defined macho.encryption_info.cryptid
+                }
+                "#,
+                &data
+            ),
+            ["encrypted"]
+        );
+
+        assert_eq!(
+            scan(
+                r#"import "macho"
+                rule not_encrypted {
+                  condition:
+                    not defined macho.encryption_info.cryptid
+                }
+                "#,
+                &build(&[text_segment()], &[])
+            ),
+            ["not_encrypted"]
+        );
+    }
+}
@@ -0,0 +1,28 @@
+/*! Names of Objective-C classes and selectors.
+
+The Objective-C compiler puts the names of the classes defined by the file
+in the `__objc_classname` section, and the names of the methods defined or
+called by the file in the `__objc_methname` section, both in the __TEXT
+segment. The names are null-terminated strings, one after the other.
+*/
+
+use super::parser::MachO;
+
+/// Maximum number of names read from each section.
+const MAX_NAMES: usize = 65536;
+
+/// Returns the names in the first section with the given name, in the
+/// order they appear in the section, without empty names.
+pub(super) fn names<'a>(macho: &MachO<'a>, section: &[u8]) -> Vec<&'a [u8]> {
+    macho
+        .segments
+        .iter()
+        .flat_map(|s| s.sections.iter())
+        .find(|s| s.name == section)
+        .and_then(|s| macho.section_data(s))
+        .unwrap_or_default()
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .take(MAX_NAMES)
+        .collect()
+}
@@ -0,0 +1,466 @@
+/*! Parser for Mach-O files.
+
+This reads the header and the load commands of 32-bit and 64-bit files,
+in either byte order. The segments and their sections, the entry point and
+the symbol table are parsed here, the rest of the load commands are kept
+as they are, leaving their interpretation to the rest of the module.
+
+See: https://github.com/apple-oss-distributions/xnu/blob/main/EXTERNAL_HEADERS/mach-o/loader.h
+*/
+
+/// Maximum number of load commands, sections and symbols.
+const MAX_COMMANDS: usize = 8192;
+const MAX_SECTIONS: usize = 8192;
+const MAX_SYMBOLS: usize = 131072;
+
+/// Maximum length of the null-terminated strings.
+const MAX_STRING_LEN: usize = 1024;
+
+/// Magic numbers, as read in little-endian.
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_CIGAM: u32 = 0xcefaedfe;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const MH_CIGAM_64: u32 = 0xcffaedfe;
+
+/// CPU types.
+const CPU_TYPE_X86: u32 = 7;
+const CPU_TYPE_X86_64: u32 = 0x01000007;
+const CPU_TYPE_ARM: u32 = 12;
+const CPU_TYPE_ARM64: u32 = 0x0100000c;
+
+/// Load command types.
+pub(super) const LC_SEGMENT: u32 = 0x1;
+pub(super) const LC_SYMTAB: u32 = 0x2;
+pub(super) const LC_UNIXTHREAD: u32 = 0x5;
+pub(super) const LC_SEGMENT_64: u32 = 0x19;
+const LC_ENCRYPTION_INFO: u32 = 0x21;
+pub(super) const LC_DYLD_INFO: u32 = 0x22;
+const LC_ENCRYPTION_INFO_64: u32 = 0x2c;
+pub(super) const LC_DYLD_INFO_ONLY: u32 = 0x80000022;
+pub(super) const LC_MAIN: u32 = 0x80000028;
+pub(super) const LC_DYLD_EXPORTS_TRIE: u32 = 0x80000033;
+pub(super) const LC_DYLD_CHAINED_FIXUPS: u32 = 0x80000034;
+
+/// Mask of the section type in the flags of a section, and the type of
+/// sections that don't have content in the file.
+const SECTION_TYPE: u32 = 0xff;
+const S_ZEROFILL: u32 = 0x1;
+
+/// Masks and values of the `n_type` field of symbols.
+pub(super) const N_STAB: u8 = 0xe0;
+pub(super) const N_TYPE: u8 = 0x0e;
+pub(super) const N_EXT: u8 = 0x01;
+pub(super) const N_UNDF: u8 = 0x0;
+
+/// A parsed Mach-O file.
+pub(super) struct MachO<'a> {
+    pub data: &'a [u8],
+    pub is_64bit: bool,
+    pub is_big_endian: bool,
+    pub magic: u32,
+    pub cpu_type: u32,
+    pub cpu_subtype: u32,
+    pub file_type: u32,
+    pub number_of_commands: u32,
+    pub size_of_commands: u32,
+    pub flags: u32,
+    pub commands: Vec<Command<'a>>,
+    pub segments: Vec<Segment<'a>>,
+}
+
+/// A load command.
+pub(super) struct Command<'a> {
+    pub type_: u32,
+    /// The whole command, including the type and the size.
+    pub data: &'a [u8],
+}
+
+/// A segment, from a LC_SEGMENT or LC_SEGMENT_64 command.
+pub(super) struct Segment<'a> {
+    pub name: &'a [u8],
+    pub address: u64,
+    pub size: u64,
+    pub offset: u64,
+    pub file_size: u64,
+    pub max_protection: u32,
+    pub initial_protection: u32,
+    pub number_of_sections: u32,
+    pub flags: u32,
+    pub sections: Vec<Section<'a>>,
+}
+
+/// A section within a segment.
+pub(super) struct Section<'a> {
+    pub name: &'a [u8],
+    pub segment_name: &'a [u8],
+    pub address: u64,
+    pub size: u64,
+    pub offset: u32,
+    pub alignment: u32,
+    pub relocations_offset: u32,
+    pub number_of_relocations: u32,
+    pub flags: u32,
+}
+
+/// The content of a LC_ENCRYPTION_INFO or LC_ENCRYPTION_INFO_64 command.
+pub(super) struct EncryptionInfo {
+    /// File offset and size of the encrypted range.
+    pub offset: u32,
+    pub size: u32,
+    /// The encryption system, or 0 if the range is not encrypted.
+    pub id: u32,
+}
+
+/// An entry in the symbol table.
+pub(super) struct Symbol<'a> {
+    pub name: &'a [u8],
+    pub type_: u8,
+    pub section: u8,
+    pub description: u16,
+    pub value: u64,
+}
+
+impl<'a> MachO<'a> {
+    /// Parses a Mach-O file. Returns `None` if the data is not a Mach-O
+    /// file, or its header is truncated.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let magic = u32::from_le_bytes(data.get(0..4)?.try_into().unwrap());
+
+        let (is_64bit, is_big_endian) = match magic {
+            MH_MAGIC => (false, false),
+            MH_CIGAM => (false, true),
+            MH_MAGIC_64 => (true, false),
+            MH_CIGAM_64 => (true, true),
+            _ => return None,
+        };
+
+        let mut macho = Self {
+            data,
+            is_64bit,
+            is_big_endian,
+            magic: 0,
+            cpu_type: 0,
+            cpu_subtype: 0,
+            file_type: 0,
+            number_of_commands: 0,
+            size_of_commands: 0,
+            flags: 0,
+            commands: Vec::new(),
+            segments: Vec::new(),
+        };
+
+        macho.magic = macho.u32_at(data, 0)?;
+        macho.cpu_type = macho.u32_at(data, 4)?;
+        macho.cpu_subtype = macho.u32_at(data, 8)?;
+        macho.file_type = macho.u32_at(data, 12)?;
+        macho.number_of_commands = macho.u32_at(data, 16)?;
+        macho.size_of_commands = macho.u32_at(data, 20)?;
+        macho.flags = macho.u32_at(data, 24)?;
+
+        macho.commands = macho.parse_commands();
+        macho.segments = macho
+            .commands
+            .iter()
+            .filter(|c| c.type_ == LC_SEGMENT || c.type_ == LC_SEGMENT_64)
+            .filter_map(|c| macho.segment(c))
+            .collect();
+
+        Some(macho)
+    }
+
+    fn parse_commands(&self) -> Vec<Command<'a>> {
+        let header_size = if self.is_64bit { 32 } else { 28 };
+        let mut commands = Vec::new();
+        let mut offset = header_size;
+
+        for _ in 0..(self.number_of_commands as usize).min(MAX_COMMANDS) {
+            let (type_, size) = match (
+                self.u32_at(self.data, offset),
+                self.u32_at(self.data, offset + 4),
+            ) {
+                (Some(type_), Some(size)) if size >= 8 => {
+                    (type_, size as usize)
+                }
+                _ => break,
+            };
+
+            let data = match self.data.get(offset..offset.saturating_add(size))
+            {
+                Some(data) => data,
+                None => break,
+            };
+
+            commands.push(Command { type_, data });
+            offset += size;
+        }
+
+        commands
+    }
+
+    fn segment(&self, command: &Command<'a>) -> Option<Segment<'a>> {
+        let data = command.data;
+
+        let (mut segment, sections_offset, section_size) = if self.is_64bit {
+            let segment = Segment {
+                name: fixed_string(data.get(8..24)?),
+                address: self.u64_at(data, 24)?,
+                size: self.u64_at(data, 32)?,
+                offset: self.u64_at(data, 40)?,
+                file_size: self.u64_at(data, 48)?,
+                max_protection: self.u32_at(data, 56)?,
+                initial_protection: self.u32_at(data, 60)?,
+                number_of_sections: self.u32_at(data, 64)?,
+                flags: self.u32_at(data, 68)?,
+                sections: Vec::new(),
+            };
+            (segment, 72, 80)
+        } else {
+            let segment = Segment {
+                name: fixed_string(data.get(8..24)?),
+                address: self.u32_at(data, 24)?.into(),
+                size: self.u32_at(data, 28)?.into(),
+                offset: self.u32_at(data, 32)?.into(),
+                file_size: self.u32_at(data, 36)?.into(),
+                max_protection: self.u32_at(data, 40)?,
+                initial_protection: self.u32_at(data, 44)?,
+                number_of_sections: self.u32_at(data, 48)?,
+                flags: self.u32_at(data, 52)?,
+                sections: Vec::new(),
+            };
+            (segment, 56, 68)
+        };
+
+        segment.sections = data
+            .get(sections_offset..)
+            .unwrap_or_default()
+            .chunks_exact(section_size)
+            .take((segment.number_of_sections as usize).min(MAX_SECTIONS))
+            .filter_map(|entry| self.section(entry))
+            .collect();
+
+        Some(segment)
+    }
+
+    fn section(&self, entry: &'a [u8]) -> Option<Section<'a>> {
+        // The fields that follow the address and the size are 8 bytes
+        // further in 64-bit files.
+        let (address, size, rest) = if self.is_64bit {
+            (self.u64_at(entry, 32)?, self.u64_at(entry, 40)?, 48)
+        } else {
+            (
+                self.u32_at(entry, 32)?.into(),
+                self.u32_at(entry, 36)?.into(),
+                40,
+            )
+        };
+
+        Some(Section {
+            name: fixed_string(entry.get(0..16)?),
+            segment_name: fixed_string(entry.get(16..32)?),
+            address,
+            size,
+            offset: self.u32_at(entry, rest)?,
+            alignment: self.u32_at(entry, rest + 4)?,
+            relocations_offset: self.u32_at(entry, rest + 8)?,
+            number_of_relocations: self.u32_at(entry, rest + 12)?,
+            flags: self.u32_at(entry, rest + 16)?,
+        })
+    }
+
+    /// Returns the first load command of the given type.
+    pub fn command(&self, type_: u32) -> Option<&Command<'a>> {
+        self.commands.iter().find(|c| c.type_ == type_)
+    }
+
+    /// Returns the data pointed to by a `linkedit_data_command`, like
+    /// LC_DYLD_EXPORTS_TRIE or LC_DYLD_CHAINED_FIXUPS.
+    pub fn linkedit_data(&self, type_: u32) -> Option<&'a [u8]> {
+        let command = self.command(type_)?;
+        let offset = self.u32_at(command.data, 8)?;
+        let size = self.u32_at(command.data, 12)?;
+        self.data_at(offset.into(), size.into())
+    }
+
+    /// Returns the file offset of the entry point, from the LC_MAIN
+    /// command, or from the program counter in the LC_UNIXTHREAD command
+    /// used by older files.
+    pub fn entry_point(&self) -> Option<u64> {
+        if let Some(main) = self.command(LC_MAIN) {
+            return self.u64_at(main.data, 8);
+        }
+
+        let thread = self.command(LC_UNIXTHREAD)?;
+        let flavor = self.u32_at(thread.data, 8)?;
+
+        // Offset of the program counter within the command, which depends
+        // on the architecture and the flavor of the thread state. The state
+        // starts after the command type, its size, the flavor and the
+        // number of words in the state.
+        let pc = match (self.cpu_type, flavor) {
+            // x86_THREAD_STATE32, eip is the 11th register.
+            (CPU_TYPE_X86, 1) => self.u32_at(thread.data, 16 + 40)?.into(),
+            // x86_THREAD_STATE64, rip is the 17th register.
+            (CPU_TYPE_X86_64, 4) => self.u64_at(thread.data, 16 + 128)?,
+            // ARM_THREAD_STATE, pc is r15.
+            (CPU_TYPE_ARM, 1) => self.u32_at(thread.data, 16 + 60)?.into(),
+            // ARM_THREAD_STATE64, pc follows x0-x28, fp, lr and sp.
+            (CPU_TYPE_ARM64, 6) => self.u64_at(thread.data, 16 + 256)?,
+            _ => return None,
+        };
+
+        self.va_to_offset(pc)
+    }
+
+    /// Returns the content of a section, or `None` if the section doesn't
+    /// have content in the file. The content is truncated if the section
+    /// exceeds the end of the file.
+    pub fn section_data(&self, section: &Section) -> Option<&'a [u8]> {
+        if section.flags & SECTION_TYPE == S_ZEROFILL {
+            return None;
+        }
+        self.data_at(section.offset.into(), section.size)
+    }
+
+    /// Returns the content of the encryption info command. Both commands
+    /// have the same fields, followed by padding in the 64-bit one.
+    pub fn encryption_info(&self) -> Option<EncryptionInfo> {
+        let command = self.commands.iter().find(|c| {
+            c.type_ == LC_ENCRYPTION_INFO || c.type_ == LC_ENCRYPTION_INFO_64
+        })?;
+        Some(EncryptionInfo {
+            offset: self.u32_at(command.data, 8)?,
+            size: self.u32_at(command.data, 12)?,
+            id: self.u32_at(command.data, 16)?,
+        })
+    }
+
+    /// Returns the stack size, from the LC_MAIN command.
+    pub fn stack_size(&self) -> Option<u64> {
+        self.command(LC_MAIN).and_then(|main| self.u64_at(main.data, 16))
+    }
+
+    /// Returns the symbols in the symbol table described by the LC_SYMTAB
+    /// command. Returns `None` if there's no such command.
+    pub fn symbols(&self) -> Option<Vec<Symbol<'a>>> {
+        let symtab = self.command(LC_SYMTAB)?;
+        let offset = self.u32_at(symtab.data, 8)?;
+        let count = self.u32_at(symtab.data, 12)?;
+        let strings_offset = self.u32_at(symtab.data, 16)?;
+        let strings_size = self.u32_at(symtab.data, 20)?;
+
+        let strings = self
+            .data_at(strings_offset.into(), strings_size.into())
+            .unwrap_or_default();
+
+        let entry_size = if self.is_64bit { 16 } else { 12 };
+
+        Some(
+            self.data_at(offset.into(), count as u64 * entry_size as u64)?
+                .chunks_exact(entry_size)
+                .take(MAX_SYMBOLS)
+                .filter_map(|entry| {
+                    Some(Symbol {
+                        name: string_at(strings, self.u32_at(entry, 0)?)
+                            .unwrap_or_default(),
+                        type_: entry[4],
+                        section: entry[5],
+                        description: self.u16_at(entry, 6)?,
+                        value: if self.is_64bit {
+                            self.u64_at(entry, 8)?
+                        } else {
+                            self.u32_at(entry, 8)?.into()
+                        },
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Translates a virtual address into a file offset, using the
+    /// segments. Returns `None` if the address is not backed by the file.
+    pub fn va_to_offset(&self, va: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|s| s.address <= va && va - s.address < s.file_size)
+            .map(|s| s.offset + (va - s.address))
+            .filter(|offset| *offset < self.data.len() as u64)
+    }
+
+    /// Returns `size` bytes of data starting at the given file offset. The
+    /// data is truncated if it exceeds the end of the file.
+    pub fn data_at(&self, offset: u64, size: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(offset).ok()?;
+        let end = start
+            .saturating_add(usize::try_from(size).unwrap_or(usize::MAX))
+            .min(self.data.len());
+        self.data.get(start..end)
+    }
+
+    pub fn u16_at(&self, data: &[u8], offset: usize) -> Option<u16> {
+        let bytes = data.get(offset..offset.checked_add(2)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.is_big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    pub fn u32_at(&self, data: &[u8], offset: usize) -> Option<u32> {
+        let bytes = data.get(offset..offset.checked_add(4)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.is_big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    pub fn u64_at(&self, data: &[u8], offset: usize) -> Option<u64> {
+        let bytes = data.get(offset..offset.checked_add(8)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.is_big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+}
+
+/// Returns the null-terminated string at the given offset, without the
+/// null character.
+pub(super) fn string_at(data: &[u8], offset: u32) -> Option<&[u8]> {
+    let data = data.get(offset as usize..)?;
+    let len = data.iter().take(MAX_STRING_LEN).position(|b| *b == 0)?;
+    Some(&data[..len])
+}
+
+/// Returns the content of a fixed-size field with a string that is padded
+/// with null characters, without the padding.
+fn fixed_string(field: &[u8]) -> &[u8] {
+    let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    &field[..len]
+}
+
+/// Reads an unsigned LEB128 integer at `pos`, and moves `pos` past it.
+pub(super) fn uleb128(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut result = 0_u64;
+    // A 64-bits value is encoded in 10 bytes at most.
+    for i in 0..10 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Reads a null-terminated string at `pos`, and moves `pos` past it.
+pub(super) fn string<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let data = data.get(*pos..)?;
+    let len = data.iter().take(MAX_STRING_LEN).position(|b| *b == 0)?;
+    *pos += len + 1;
+    Some(&data[..len])
+}
@@ -0,0 +1,83 @@
+/*! Target platform and versions of the tools that built the file.
+
+Recent files have a LC_BUILD_VERSION command with the platform, the
+minimum OS version, the SDK version and the versions of the tools used
+for building the file. Older ones have a LC_VERSION_MIN_* command, which
+only has the minimum OS version and the SDK version, and whose type
+implies the platform.
+*/
+
+use super::parser::MachO;
+
+/// Load commands with versions.
+const LC_VERSION_MIN_MACOSX: u32 = 0x24;
+const LC_VERSION_MIN_IPHONEOS: u32 = 0x25;
+const LC_VERSION_MIN_TVOS: u32 = 0x2f;
+const LC_VERSION_MIN_WATCHOS: u32 = 0x30;
+const LC_BUILD_VERSION: u32 = 0x32;
+
+/// Platforms, as in the LC_BUILD_VERSION command.
+const PLATFORM_MACOS: u32 = 1;
+const PLATFORM_IOS: u32 = 2;
+const PLATFORM_TVOS: u32 = 3;
+const PLATFORM_WATCHOS: u32 = 4;
+
+/// Maximum number of tools in the LC_BUILD_VERSION command.
+const MAX_TOOLS: usize = 64;
+
+/// The content of a LC_BUILD_VERSION or LC_VERSION_MIN_* command. The
+/// versions are encoded as `xxxx.yy.zz` in nibbles, see [`version`].
+pub(super) struct BuildVersion {
+    pub platform: u32,
+    pub min_os: u32,
+    pub sdk: u32,
+    /// Tools as `(tool, version)` tuples.
+    pub tools: Vec<(u32, u32)>,
+}
+
+/// Returns the build version, from the first LC_BUILD_VERSION or
+/// LC_VERSION_MIN_* command.
+pub(super) fn build_version(macho: &MachO) -> Option<BuildVersion> {
+    macho.commands.iter().find_map(|c| {
+        let platform = match c.type_ {
+            LC_BUILD_VERSION => macho.u32_at(c.data, 8)?,
+            LC_VERSION_MIN_MACOSX => PLATFORM_MACOS,
+            LC_VERSION_MIN_IPHONEOS => PLATFORM_IOS,
+            LC_VERSION_MIN_TVOS => PLATFORM_TVOS,
+            LC_VERSION_MIN_WATCHOS => PLATFORM_WATCHOS,
+            _ => return None,
+        };
+
+        if c.type_ != LC_BUILD_VERSION {
+            return Some(BuildVersion {
+                platform,
+                min_os: macho.u32_at(c.data, 8)?,
+                sdk: macho.u32_at(c.data, 12)?,
+                tools: Vec::new(),
+            });
+        }
+
+        let count = macho.u32_at(c.data, 20)? as usize;
+
+        Some(BuildVersion {
+            platform,
+            min_os: macho.u32_at(c.data, 12)?,
+            sdk: macho.u32_at(c.data, 16)?,
+            tools: c
+                .data
+                .get(24..)?
+                .chunks_exact(8)
+                .take(count.min(MAX_TOOLS))
+                .filter_map(|tool| {
+                    Some((macho.u32_at(tool, 0)?, macho.u32_at(tool, 4)?))
+                })
+                .collect(),
+        })
+    })
+}
+
+/// Returns a version encoded as `xxxx.yy.zz` in nibbles as a string, like
+/// "10.15.7".
+pub(super) fn version(version: u32) -> String {
+    format!("{}.{}.{}", version >> 16, (version >> 8) & 0xff, version & 0xff)
+}
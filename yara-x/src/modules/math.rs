@@ -0,0 +1,331 @@
+use crate::modules::prelude::*;
+use crate::modules::protos::math::*;
+use crate::modules::utils::{entropy, histogram};
+
+#[module_main]
+fn main(_ctx: &ScanContext) -> Math {
+    // Nothing to do, but we have to return our protobuf
+    Math::new()
+}
+
+/// Returns the Shannon entropy of the `size` bytes starting at `offset`.
+#[module_export(name = "entropy")]
+fn entropy_data(ctx: &ScanContext, offset: i64, size: i64) -> Option<f64> {
+    let data = data_range(ctx, offset, size)?;
+    Some(entropy(data))
+}
+
+/// Returns the Shannon entropy of a string.
+#[module_export(name = "entropy")]
+fn entropy_string(ctx: &ScanContext, s: RuntimeString) -> f64 {
+    entropy(s.as_bstr(ctx))
+}
+
+/// Computes the entropy of each window of `window` bytes in the range
+/// that starts at `offset` and has `size` bytes, moving the window `step`
+/// bytes each time, and returns the maximum entropy found.
+///
+/// Returns undefined if the range is shorter than the window.
+#[module_export]
+fn max_entropy_window(
+    ctx: &ScanContext,
+    offset: i64,
+    size: i64,
+    window: i64,
+    step: i64,
+) -> Option<f64> {
+    windows(data_range(ctx, offset, size)?, window, step)?
+        .map(entropy)
+        .reduce(f64::max)
+}
+
+/// Like [`max_entropy_window`], but returns the minimum entropy found.
+#[module_export]
+fn min_entropy_window(
+    ctx: &ScanContext,
+    offset: i64,
+    size: i64,
+    window: i64,
+    step: i64,
+) -> Option<f64> {
+    windows(data_range(ctx, offset, size)?, window, step)?
+        .map(entropy)
+        .reduce(f64::min)
+}
+
+/// Returns the chi-square statistic of the `size` bytes starting at
+/// `offset`, assuming a uniform distribution of byte values.
+#[module_export(name = "chi_square")]
+fn chi_square_data(ctx: &ScanContext, offset: i64, size: i64) -> Option<f64> {
+    let data = data_range(ctx, offset, size)?;
+    Some(chi_square(data))
+}
+
+/// Returns the chi-square statistic of a string, assuming a uniform
+/// distribution of byte values.
+#[module_export(name = "chi_square")]
+fn chi_square_string(ctx: &ScanContext, s: RuntimeString) -> f64 {
+    chi_square(s.as_bstr(ctx))
+}
+
+/// Returns the serial correlation coefficient of the `size` bytes starting
+/// at `offset`.
+#[module_export(name = "serial_correlation")]
+fn serial_correlation_data(
+    ctx: &ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<f64> {
+    let data = data_range(ctx, offset, size)?;
+    Some(serial_correlation(data))
+}
+
+/// Returns the serial correlation coefficient of a string.
+#[module_export(name = "serial_correlation")]
+fn serial_correlation_string(ctx: &ScanContext, s: RuntimeString) -> f64 {
+    serial_correlation(s.as_bstr(ctx))
+}
+
+/// Returns the mean entropy of the matches found for a pattern.
+///
+/// Returns undefined if the pattern didn't match.
+#[module_export]
+fn mean_string_entropy(
+    ctx: &ScanContext,
+    pattern_id: PatternId,
+) -> Option<f64> {
+    let entropies = match_entropies(ctx, pattern_id)?;
+    Some(entropies.iter().sum::<f64>() / entropies.len() as f64)
+}
+
+/// Returns the minimum entropy of the matches found for a pattern.
+///
+/// Returns undefined if the pattern didn't match.
+#[module_export]
+fn min_string_entropy(
+    ctx: &ScanContext,
+    pattern_id: PatternId,
+) -> Option<f64> {
+    match_entropies(ctx, pattern_id)?.into_iter().reduce(f64::min)
+}
+
+/// Returns the maximum entropy of the matches found for a pattern.
+///
+/// Returns undefined if the pattern didn't match.
+#[module_export]
+fn max_string_entropy(
+    ctx: &ScanContext,
+    pattern_id: PatternId,
+) -> Option<f64> {
+    match_entropies(ctx, pattern_id)?.into_iter().reduce(f64::max)
+}
+
+/// Returns the number of times that `byte` appears in the scanned data.
+#[module_export(name = "byte_histogram")]
+fn byte_histogram_all(ctx: &ScanContext, byte: i64) -> Option<i64> {
+    byte_histogram(ctx.scanned_data(), byte)
+}
+
+/// Returns the number of times that `byte` appears in the `size` bytes
+/// starting at `offset`.
+#[module_export(name = "byte_histogram")]
+fn byte_histogram_data(
+    ctx: &ScanContext,
+    byte: i64,
+    offset: i64,
+    size: i64,
+) -> Option<i64> {
+    byte_histogram(data_range(ctx, offset, size)?, byte)
+}
+
+/// Returns the `size` bytes of scanned data starting at `offset`. If the
+/// range exceeds the end of the data, it's truncated. Returns `None` if
+/// `offset` or `size` are negative, or `offset` is beyond the end of the
+/// data.
+fn data_range<'a>(
+    ctx: &'a ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<&'a [u8]> {
+    let data = ctx.scanned_data();
+    let start: usize = offset.try_into().ok()?;
+    let size: usize = size.try_into().ok()?;
+
+    if start >= data.len() {
+        return None;
+    }
+
+    let end = start.saturating_add(size).min(data.len());
+
+    Some(&data[start..end])
+}
+
+/// Returns the entropy of each match found for a pattern, or `None` if the
+/// pattern didn't match.
+fn match_entropies(
+    ctx: &ScanContext,
+    pattern_id: PatternId,
+) -> Option<Vec<f64>> {
+    let data = ctx.scanned_data();
+    let matches = ctx.pattern_matches.get(&pattern_id)?;
+
+    if matches.is_empty() {
+        return None;
+    }
+
+    Some(matches.iter().map(|m| entropy(&data[m.range.clone()])).collect())
+}
+
+/// Returns an iterator over the windows of `window` bytes in `data`, each
+/// window starting `step` bytes after the previous one. Returns `None` if
+/// `window` or `step` are not positive, or `data` is shorter than `window`.
+fn windows(
+    data: &[u8],
+    window: i64,
+    step: i64,
+) -> Option<impl Iterator<Item = &[u8]>> {
+    let window: usize = window.try_into().ok().filter(|w| *w > 0)?;
+    let step: usize = step.try_into().ok().filter(|s| *s > 0)?;
+
+    if data.len() < window {
+        return None;
+    }
+
+    Some(
+        (0..=data.len() - window)
+            .step_by(step)
+            .map(move |start| &data[start..start + window]),
+    )
+}
+
+fn byte_histogram(data: &[u8], byte: i64) -> Option<i64> {
+    let byte: u8 = byte.try_into().ok()?;
+    Some(data.iter().filter(|b| **b == byte).count() as i64)
+}
+
+fn chi_square(data: &[u8]) -> f64 {
+    let expected = data.len() as f64 / 256.0;
+
+    if expected == 0.0 {
+        return 0.0;
+    }
+
+    histogram(data)
+        .iter()
+        .map(|count| {
+            let diff = *count as f64 - expected;
+            diff * diff / expected
+        })
+        .sum()
+}
+
+/// Computes the serial correlation coefficient in the same way as YARA's
+/// `math.serial_correlation`, which in turn is based on the `ent` tool.
+fn serial_correlation(data: &[u8]) -> f64 {
+    let (first, last) = match (data.first(), data.last()) {
+        (Some(first), Some(last)) => (*first as f64, *last as f64),
+        _ => return -100000.0,
+    };
+
+    let mut scct1 = 0.0;
+    let mut scct2 = 0.0;
+    let mut scct3 = 0.0;
+    let mut prev = 0.0;
+
+    for byte in data {
+        let byte = *byte as f64;
+        scct1 += prev * byte;
+        scct2 += byte;
+        scct3 += byte * byte;
+        prev = byte;
+    }
+
+    scct1 += last * first;
+    scct2 *= scct2;
+
+    let n = data.len() as f64;
+    let scc = n * scct3 - scct2;
+
+    if scc == 0.0 {
+        -100000.0
+    } else {
+        (n * scct1 - scct2) / scc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn end2end() {
+        let data = b"AAAAAAAAAAAAAAAA0123456789abcdef";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "math"
+                rule rule_1 { condition: math.entropy(0, 16) == 0.0 }
+                rule rule_2 { condition: math.entropy(16, 16) == 4.0 }
+                rule rule_3 { condition: math.entropy("AAAA") == 0.0 }
+                rule rule_4 { condition: math.max_entropy_window(0, filesize, 16, 4) == 4.0 }
+                rule rule_5 { condition: math.min_entropy_window(0, filesize, 16, 4) == 0.0 }
+                rule rule_6 { condition: math.chi_square(0, 16) == 4080.0 }
+                rule rule_7 { condition: math.serial_correlation("AAAA") == -100000.0 }
+                rule rule_8 { condition: math.byte_histogram(0x41) == 16 }
+                rule rule_9 { condition: math.byte_histogram(0x41, 8, 16) == 8 }
+                rule rule_10 { condition: not defined math.entropy(100, 1) }
+                rule rule_11 { condition: not defined math.max_entropy_window(0, 8, 16, 1) }
+                rule rule_12 { condition: not defined math.byte_histogram(256) }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(data).unwrap().matching_rules().len(), 12);
+    }
+
+    #[test]
+    fn string_entropy() {
+        let data = b"AAAA 0123456789abcdef AAAA 01234567";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "math"
+                rule rule_1 {
+                  strings:
+                    $a = /0123[0-9a-f]+/
+                  condition:
+                    math.max_string_entropy($a) == 4.0 and
+                    math.min_string_entropy($a) == 3.0 and
+                    math.mean_string_entropy($a) == 3.5 and
+                    math.entropy(@a[2], !a[2]) == 3.0
+                }
+                rule rule_2 {
+                  strings:
+                    $a = "AAAA"
+                    $b = "foo"
+                  condition:
+                    math.mean_string_entropy($a) == 0.0 and
+                    not defined math.mean_string_entropy($b)
+                }
+                rule rule_3 {
+                  strings:
+                    $a = "AAAA"
+                    $b = "0123"
+                  condition:
+                    for all of them : (math.max_string_entropy($) <= 2.0)
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(data).unwrap().matching_rules().len(), 3);
+    }
+}
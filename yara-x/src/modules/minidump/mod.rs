@@ -0,0 +1,560 @@
+/*! YARA module that parses Windows minidump (.dmp) files.
+
+Minidumps start with a header that points to a directory of streams, each
+stream containing a different kind of information about the dumped
+process: the loaded modules, the threads and their contexts, the memory
+ranges included in the dump, etc. Streams that the module doesn't know are
+listed in `streams` but otherwise ignored.
+
+See: https://learn.microsoft.com/en-us/windows/win32/api/minidumpapiset/
+*/
+
+use protobuf::{Enum, EnumOrUnknown};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::minidump::*;
+use crate::modules::utils::{u16_at, u32_at, u64_at};
+
+/// Maximum number of entries in each of the repeated fields.
+const MAX_ENTRIES: usize = 16384;
+
+/// Maximum length of strings, in UTF-16 code units.
+const MAX_STRING_LEN: usize = 1024;
+
+/// Size of each structure in the module, thread and memory lists.
+const MODULE_SIZE: usize = 108;
+const THREAD_SIZE: usize = 48;
+const MEMORY_DESCRIPTOR_SIZE: usize = 16;
+
+/// Flags in the MiscInfoStream that indicate which fields are valid.
+const MISC1_PROCESS_ID: u32 = 0x1;
+const MISC1_PROCESS_TIMES: u32 = 0x2;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Minidump {
+    let mut dump = Minidump::new();
+
+    if parse(ctx.scanned_data(), &mut dump).is_none() {
+        dump.set_is_minidump(false);
+    }
+
+    dump
+}
+
+/// Returns true if the dump contains a module whose file name or full path
+/// is `name`, ignoring case (e.g: `minidump.has_module("ntdll.dll")`).
+#[module_export]
+fn has_module(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let dump = ctx.module_output::<Minidump>()?;
+    let name = name.to_str(ctx).ok()?;
+
+    Some(dump.modules.iter().any(|module| {
+        let path = module.path();
+        let file_name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+        path.eq_ignore_ascii_case(name) || file_name.eq_ignore_ascii_case(name)
+    }))
+}
+
+/// Returns the offset within the scanned data of the given virtual address
+/// in the dumped process, or undefined if the address is not included in
+/// any of the memory ranges in the dump.
+#[module_export]
+fn address_to_offset(ctx: &ScanContext, address: i64) -> Option<i64> {
+    let dump = ctx.module_output::<Minidump>()?;
+
+    dump.memory_ranges.iter().find_map(|range| {
+        let delta = address.checked_sub(range.start())?;
+        if (0..range.size()).contains(&delta) {
+            Some(range.offset() + delta)
+        } else {
+            None
+        }
+    })
+}
+
+fn parse(data: &[u8], dump: &mut Minidump) -> Option<()> {
+    if !data.starts_with(b"MDMP") {
+        return None;
+    }
+
+    let num_streams = u32_at(data, 8)? as usize;
+    let directory = u32_at(data, 12)? as usize;
+
+    dump.set_is_minidump(true);
+    dump.set_version((u32_at(data, 4)? & 0xffff).into());
+    dump.set_timestamp(u32_at(data, 20)?.into());
+    dump.set_flags(u64_at(data, 24)? as i64);
+
+    // Each entry in the directory has the stream's type, size and offset.
+    let mut streams = Vec::new();
+
+    for i in 0..num_streams {
+        let entry = match directory.checked_add(i * 12) {
+            Some(entry) => entry,
+            None => break,
+        };
+        let (stream_type, size, offset) = match (
+            u32_at(data, entry),
+            u32_at(data, entry + 4),
+            u32_at(data, entry + 8),
+        ) {
+            (Some(t), Some(size), Some(offset)) => {
+                (t, size as usize, offset as usize)
+            }
+            _ => break,
+        };
+
+        if dump.streams.len() < MAX_ENTRIES {
+            let mut stream = DumpStream::new();
+            stream.type_ = Some(EnumOrUnknown::from_i32(stream_type as i32));
+            stream.set_offset(offset as i64);
+            stream.set_size(size as i64);
+            dump.streams.push(stream);
+        } else {
+            dump.set_streams_truncated(true);
+        }
+
+        if let Some(body) = data.get(offset..offset.saturating_add(size)) {
+            streams.push((stream_type, body));
+        }
+    }
+
+    // The system information is needed for interpreting the thread
+    // contexts, which depend on the processor architecture.
+    let system_info = streams
+        .iter()
+        .find(|(t, _)| *t == StreamType::SYSTEM_INFO as u32)
+        .and_then(|(_, body)| system_info(data, body));
+
+    let architecture = system_info
+        .as_ref()
+        .and_then(|info| info.processor_architecture)
+        .map(|architecture| architecture.value());
+
+    for (stream_type, body) in streams {
+        match StreamType::from_i32(stream_type as i32) {
+            Some(StreamType::MODULE_LIST) => modules(data, body, dump),
+            Some(StreamType::THREAD_LIST) => {
+                threads(data, body, architecture, dump)
+            }
+            Some(StreamType::MEMORY_LIST) => memory_list(body, dump),
+            Some(StreamType::MEMORY64_LIST) => memory64_list(body, dump),
+            Some(StreamType::MISC_INFO) => misc_info(body, dump),
+            Some(StreamType::EXCEPTION) => {
+                dump.exception = exception(body).into();
+            }
+            _ => {}
+        };
+    }
+
+    dump.system_info = system_info.into();
+
+    Some(())
+}
+
+fn system_info(data: &[u8], body: &[u8]) -> Option<SystemInfo> {
+    let mut info = SystemInfo::new();
+
+    info.processor_architecture =
+        Some(EnumOrUnknown::from_i32(u16_at(body, 0)?.into()));
+    info.set_processor_level(u16_at(body, 2)?.into());
+    info.set_processor_revision(u16_at(body, 4)?.into());
+    info.set_number_of_processors((*body.get(6)?).into());
+    info.set_product_type((*body.get(7)?).into());
+    info.set_major_version(u32_at(body, 8)?.into());
+    info.set_minor_version(u32_at(body, 12)?.into());
+    info.set_build_number(u32_at(body, 16)?.into());
+    info.set_platform_id(u32_at(body, 20)?.into());
+    info.csd_version = string_at(data, u32_at(body, 24)? as usize);
+
+    Some(info)
+}
+
+fn modules(data: &[u8], body: &[u8], dump: &mut Minidump) {
+    for entry in list(body, 4, MODULE_SIZE) {
+        if dump.modules.len() == MAX_ENTRIES {
+            dump.set_modules_truncated(true);
+            break;
+        }
+
+        let mut module = DumpModule::new();
+        module.base_address = u64_at(entry, 0).map(|a| a as i64);
+        module.size = u32_at(entry, 8).map(i64::from);
+        module.checksum = u32_at(entry, 12).map(i64::from);
+        module.timestamp = u32_at(entry, 16).map(i64::from);
+        module.path = u32_at(entry, 20)
+            .and_then(|offset| string_at(data, offset as usize));
+
+        dump.modules.push(module);
+    }
+}
+
+fn threads(
+    data: &[u8],
+    body: &[u8],
+    architecture: Option<i32>,
+    dump: &mut Minidump,
+) {
+    for entry in list(body, 4, THREAD_SIZE) {
+        if dump.threads.len() == MAX_ENTRIES {
+            dump.set_threads_truncated(true);
+            break;
+        }
+
+        let mut thread = Thread::new();
+        thread.id = u32_at(entry, 0).map(i64::from);
+        thread.suspend_count = u32_at(entry, 4).map(i64::from);
+        thread.priority_class = u32_at(entry, 8).map(i64::from);
+        thread.priority = u32_at(entry, 12).map(i64::from);
+        thread.teb = u64_at(entry, 16).map(|a| a as i64);
+        thread.stack_start = u64_at(entry, 24).map(|a| a as i64);
+        thread.stack_size = u32_at(entry, 32).map(i64::from);
+
+        let context = match (u32_at(entry, 40), u32_at(entry, 44)) {
+            (Some(size), Some(offset)) => {
+                let offset = offset as usize;
+                data.get(offset..offset.saturating_add(size as usize))
+            }
+            _ => None,
+        };
+
+        // Offsets of the instruction and stack pointers within the CONTEXT
+        // structure of each architecture.
+        if let Some(context) = context {
+            match architecture.and_then(ProcessorArchitecture::from_i32) {
+                Some(ProcessorArchitecture::X86) => {
+                    thread.instruction_pointer =
+                        u32_at(context, 0xb8).map(i64::from);
+                    thread.stack_pointer =
+                        u32_at(context, 0xc4).map(i64::from);
+                }
+                Some(ProcessorArchitecture::AMD64) => {
+                    thread.instruction_pointer =
+                        u64_at(context, 0xf8).map(|a| a as i64);
+                    thread.stack_pointer =
+                        u64_at(context, 0x98).map(|a| a as i64);
+                }
+                _ => {}
+            }
+        }
+
+        dump.threads.push(thread);
+    }
+}
+
+fn memory_list(body: &[u8], dump: &mut Minidump) {
+    for entry in list(body, 4, MEMORY_DESCRIPTOR_SIZE) {
+        let (start, size, offset) =
+            match (u64_at(entry, 0), u32_at(entry, 8), u32_at(entry, 12)) {
+                (Some(start), Some(size), Some(offset)) => {
+                    (start, u64::from(size), u64::from(offset))
+                }
+                _ => break,
+            };
+        if !push_memory_range(dump, start, size, offset) {
+            break;
+        }
+    }
+}
+
+/// Parses the list of memory ranges used by full memory dumps. The content
+/// of the ranges is stored sequentially, starting at a base offset.
+fn memory64_list(body: &[u8], dump: &mut Minidump) {
+    let mut offset = match u64_at(body, 8) {
+        Some(offset) => offset,
+        None => return,
+    };
+
+    for entry in list(body, 8, MEMORY_DESCRIPTOR_SIZE) {
+        let (start, size) = match (u64_at(entry, 0), u64_at(entry, 8)) {
+            (Some(start), Some(size)) => (start, size),
+            _ => break,
+        };
+        if !push_memory_range(dump, start, size, offset) {
+            break;
+        }
+        offset = match offset.checked_add(size) {
+            Some(offset) => offset,
+            None => break,
+        };
+    }
+}
+
+/// Adds a memory range to the dump, returning false if there's no room for
+/// more ranges.
+fn push_memory_range(
+    dump: &mut Minidump,
+    start: u64,
+    size: u64,
+    offset: u64,
+) -> bool {
+    if dump.memory_ranges.len() == MAX_ENTRIES {
+        dump.set_memory_ranges_truncated(true);
+        return false;
+    }
+
+    let mut range = MemoryRange::new();
+    range.set_start(start as i64);
+    range.set_size(size as i64);
+    range.set_offset(offset as i64);
+    dump.memory_ranges.push(range);
+
+    true
+}
+
+fn misc_info(body: &[u8], dump: &mut Minidump) {
+    let flags = u32_at(body, 4).unwrap_or_default();
+
+    if flags & MISC1_PROCESS_ID != 0 {
+        dump.process_id = u32_at(body, 8).map(i64::from);
+    }
+    if flags & MISC1_PROCESS_TIMES != 0 {
+        dump.process_create_time = u32_at(body, 12).map(i64::from);
+    }
+}
+
+fn exception(body: &[u8]) -> Option<Exception> {
+    let mut exception = Exception::new();
+
+    exception.set_thread_id(u32_at(body, 0)?.into());
+    exception.set_code(u32_at(body, 8)?.into());
+    exception.set_flags(u32_at(body, 12)?.into());
+    exception.set_address(u64_at(body, 24)? as i64);
+
+    Some(exception)
+}
+
+/// Returns an iterator over the entries in a list stream. Lists start with
+/// a header of `header_size` bytes that begins with the number of entries,
+/// followed by the entries themselves.
+fn list(
+    body: &[u8],
+    header_size: usize,
+    entry_size: usize,
+) -> impl Iterator<Item = &[u8]> {
+    let count = u32_at(body, 0).unwrap_or_default() as usize;
+
+    body.get(header_size..)
+        .unwrap_or_default()
+        .chunks_exact(entry_size)
+        .take(count)
+}
+
+/// Reads a MINIDUMP_STRING, which is a UTF-16 string prefixed by its
+/// length in bytes.
+fn string_at(data: &[u8], offset: usize) -> Option<String> {
+    let len = (u32_at(data, offset)? as usize / 2).min(MAX_STRING_LEN);
+    let start = offset.checked_add(4)?;
+    let bytes = data.get(start..start + len * 2)?;
+
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+
+    Some(String::from_utf16_lossy(&units))
+}
+
+#[cfg(test)]
+mod tests {
+    /// Appends a MINIDUMP_STRING to `data`, returning its offset.
+    fn push_string(data: &mut Vec<u8>, s: &str) -> u32 {
+        let offset = data.len() as u32;
+        let units: Vec<u16> = s.encode_utf16().collect();
+        data.extend((units.len() as u32 * 2).to_le_bytes());
+        for unit in units {
+            data.extend(unit.to_le_bytes());
+        }
+        offset
+    }
+
+    /// Builds a minidump of an AMD64 process with a module, a thread, a
+    /// memory range and an exception.
+    fn minidump() -> Vec<u8> {
+        let mut data = b"MDMP\x93\xa7\x00\x00".to_vec();
+        data.resize(32, 0);
+        data[20..24].copy_from_slice(&1700000000_u32.to_le_bytes());
+        data[24] = 0x2;
+
+        let module_path =
+            push_string(&mut data, r"C:\Windows\System32\ntdll.dll");
+        let csd_version = push_string(&mut data, "Service Pack 1");
+
+        let context = data.len() as u32;
+        let mut context_data = vec![0; 0x4d0];
+        context_data[0x98..0xa0].copy_from_slice(&0x5fe000_u64.to_le_bytes());
+        context_data[0xf8..0x100]
+            .copy_from_slice(&0x7ff812340000_u64.to_le_bytes());
+        data.extend(context_data);
+
+        let memory = data.len() as u32;
+        data.extend(b"MZ\x90\x00shellcode");
+
+        let mut system_info = Vec::new();
+        system_info.extend(9_u16.to_le_bytes());
+        system_info.extend(6_u16.to_le_bytes());
+        system_info.extend(0x9e0a_u16.to_le_bytes());
+        system_info.extend([8, 1]);
+        for value in [10_u32, 0, 19045, 2, csd_version] {
+            system_info.extend(value.to_le_bytes());
+        }
+        system_info.resize(56, 0);
+
+        let mut module_list = 1_u32.to_le_bytes().to_vec();
+        module_list.extend(0x7ff812340000_u64.to_le_bytes());
+        for value in [0x1f8000_u32, 0x1fa34c, 1600000000, module_path] {
+            module_list.extend(value.to_le_bytes());
+        }
+        module_list.resize(4 + 108, 0);
+
+        let mut thread_list = 1_u32.to_le_bytes().to_vec();
+        for value in [0x1a2c_u32, 0, 0x20, 0] {
+            thread_list.extend(value.to_le_bytes());
+        }
+        thread_list.extend(0x3b1000_u64.to_le_bytes());
+        thread_list.extend(0x5fd000_u64.to_le_bytes());
+        for value in [0x3000_u32, 0, 0x4d0, context] {
+            thread_list.extend(value.to_le_bytes());
+        }
+
+        let mut memory_list = 1_u32.to_le_bytes().to_vec();
+        memory_list.extend(0x10000000_u64.to_le_bytes());
+        memory_list.extend(13_u32.to_le_bytes());
+        memory_list.extend(memory.to_le_bytes());
+
+        let mut misc_info = Vec::new();
+        for value in [24_u32, 0x3, 4242, 1690000000, 0, 0] {
+            misc_info.extend(value.to_le_bytes());
+        }
+
+        let mut exception = 0x1a2c_u32.to_le_bytes().to_vec();
+        exception.extend([0; 4]);
+        exception.extend(0xc0000005_u32.to_le_bytes());
+        exception.extend(0_u32.to_le_bytes());
+        exception.extend(0_u64.to_le_bytes());
+        exception.extend(0x7ff812340000_u64.to_le_bytes());
+        exception.resize(168, 0);
+
+        let streams = [
+            (7_u32, system_info),
+            (4, module_list),
+            (3, thread_list),
+            (5, memory_list),
+            (15, misc_info),
+            (6, exception),
+            (0x47670001, vec![0; 4]),
+        ];
+
+        let mut directory = Vec::new();
+        for (stream_type, body) in &streams {
+            directory.extend(stream_type.to_le_bytes());
+            directory.extend((body.len() as u32).to_le_bytes());
+            directory.extend((data.len() as u32).to_le_bytes());
+            data.extend(body);
+        }
+
+        data[8..12].copy_from_slice(&(streams.len() as u32).to_le_bytes());
+        let directory_offset = data.len() as u32;
+        data[12..16].copy_from_slice(&directory_offset.to_le_bytes());
+        data.extend(directory);
+        data
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "minidump"
+                rule header {
+                  condition:
+                    minidump.is_minidump and
+                    minidump.version == 0xa793 and
+                    minidump.timestamp == 1700000000 and
+                    minidump.flags == 0x2 and
+                    minidump.streams[0].type == minidump.StreamType.SYSTEM_INFO and
+                    minidump.streams[6].type == 0x47670001 and
+                    minidump.process_id == 4242 and
+                    minidump.process_create_time == 1690000000
+                }
+                rule system_info {
+                  condition:
+                    minidump.system_info.processor_architecture ==
+                        minidump.ProcessorArchitecture.AMD64 and
+                    minidump.system_info.number_of_processors == 8 and
+                    minidump.system_info.build_number == 19045 and
+                    minidump.system_info.csd_version == "Service Pack 1"
+                }
+                rule modules {
+                  condition:
+                    minidump.modules[0].base_address == 0x7ff812340000 and
+                    minidump.modules[0].size == 0x1f8000 and
+                    minidump.modules[0].timestamp == 1600000000 and
+                    minidump.modules[0].path == "C:\\Windows\\System32\\ntdll.dll" and
+                    minidump.has_module("NTDLL.dll") and
+                    minidump.has_module("C:\\Windows\\System32\\ntdll.dll") and
+                    not minidump.has_module("kernel32.dll")
+                }
+                rule threads {
+                  condition:
+                    minidump.threads[0].id == 0x1a2c and
+                    minidump.threads[0].priority_class == 0x20 and
+                    minidump.threads[0].teb == 0x3b1000 and
+                    minidump.threads[0].stack_start == 0x5fd000 and
+                    minidump.threads[0].stack_size == 0x3000 and
+                    minidump.threads[0].instruction_pointer == 0x7ff812340000 and
+                    minidump.threads[0].stack_pointer == 0x5fe000
+                }
+                rule memory {
+                  condition:
+                    minidump.memory_ranges[0].start == 0x10000000 and
+                    minidump.memory_ranges[0].size == 13 and
+                    uint16(minidump.address_to_offset(0x10000000)) == 0x5a4d and
+                    uint8(minidump.address_to_offset(0x10000004)) == 0x73 and
+                    not defined minidump.address_to_offset(0x1000000d)
+                }
+                rule exception {
+                  condition:
+                    minidump.exception.thread_id == 0x1a2c and
+                    minidump.exception.code == 0xc0000005 and
+                    minidump.exception.address == 0x7ff812340000
+                }
+                rule not_minidump { condition: not minidump.is_minidump }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let dump = minidump();
+
+        assert_eq!(
+            matching_rules(&dump),
+            [
+                "header",
+                "system_info",
+                "modules",
+                "threads",
+                "memory",
+                "exception"
+            ]
+        );
+
+        // A dump truncated right after the header still has the header's
+        // fields, but none of the streams.
+        assert_eq!(matching_rules(&dump[..32]), Vec::<String>::new());
+
+        assert_eq!(matching_rules(b"MZ\x90\x00"), ["not_minidump"]);
+    }
+}
@@ -1,5 +1,7 @@
 use lazy_static::lazy_static;
-use protobuf::reflect::MessageDescriptor;
+use protobuf::reflect::{
+    MessageDescriptor, ReflectValueBox, RuntimeFieldType, RuntimeType,
+};
 use protobuf::MessageDyn;
 use rustc_hash::FxHashMap;
 
@@ -11,6 +13,7 @@ pub mod protos {
 
 #[allow(unused_imports)]
 pub(crate) mod prelude {
+    pub(crate) use crate::compiler::PatternId;
     pub(crate) use crate::scanner::ScanContext;
     pub(crate) use crate::wasm::string::*;
     pub(crate) use crate::wasm::*;
@@ -22,6 +25,8 @@ pub(crate) mod prelude {
 
 include!("modules.rs");
 
+pub(crate) mod utils;
+
 /// Type of module's main function.
 type MainFn = fn(&ScanContext) -> Box<dyn MessageDyn>;
 
@@ -39,6 +44,75 @@ pub(crate) struct Module {
     pub root_struct_descriptor: MessageDescriptor,
 }
 
+/// Truncates the repeated fields in a module's output, including the ones
+/// in nested messages, so that they have at most `max_len` items.
+///
+/// For each repeated field `foo` that is truncated, the boolean field
+/// `foo_truncated` in the same message is set to true, provided that the
+/// message has such a field. Maps are not truncated.
+pub(crate) fn truncate_arrays(msg: &mut dyn MessageDyn, max_len: usize) {
+    let descriptor = msg.descriptor_dyn();
+
+    for field in descriptor.fields() {
+        match field.runtime_field_type() {
+            RuntimeFieldType::Singular(RuntimeType::Message(_))
+                if field.has_field(msg) =>
+            {
+                truncate_arrays(field.mut_message(msg), max_len);
+            }
+            RuntimeFieldType::Repeated(element_type) => {
+                let items = field.get_repeated(msg);
+                let truncated = items.len() > max_len;
+                let has_messages =
+                    matches!(element_type, RuntimeType::Message(_));
+
+                // Items in the array are not accessible by mutable
+                // reference, so the array is rebuilt when it must be
+                // truncated, or when it contains messages that may need to
+                // be truncated too.
+                if !truncated && !has_messages {
+                    continue;
+                }
+
+                let items: Vec<ReflectValueBox> =
+                    (0..items.len().min(max_len))
+                        .map(|i| items.get(i).to_box())
+                        .collect();
+
+                let mut array = field.mut_repeated(msg);
+
+                array.clear();
+
+                for mut item in items {
+                    if let ReflectValueBox::Message(m) = &mut item {
+                        truncate_arrays(m.as_mut(), max_len);
+                    }
+                    array.push(item);
+                }
+
+                if !truncated {
+                    continue;
+                }
+
+                if let Some(flag) = descriptor.field_by_name(
+                    format!("{}_truncated", field.name()).as_str(),
+                ) {
+                    if matches!(
+                        flag.runtime_field_type(),
+                        RuntimeFieldType::Singular(RuntimeType::Bool)
+                    ) {
+                        flag.set_singular_field(
+                            msg,
+                            ReflectValueBox::Bool(true),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 /// Macro that adds a module to the `BUILTIN_MODULES` map.
 ///
 /// This macro is used by `add_modules.rs`, a file that is automatically
@@ -117,3 +191,16 @@ lazy_static! {
         modules
     };
 }
+
+/// Returns the names of the modules available in this build, sorted
+/// alphabetically.
+///
+/// These are the names that can be used in `import` statements. Modules
+/// that were disabled at build time by means of their corresponding
+/// features are not included.
+pub fn module_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> =
+        BUILTIN_MODULES.keys().copied().collect();
+    names.sort();
+    names
+}
@@ -6,4 +6,76 @@ pub mod test_proto2;
 #[cfg(feature = "time-module")]
 pub mod time;
 #[cfg(feature = "test_proto3-module")]
-pub mod test_proto3;
\ No newline at end of file
+pub mod test_proto3;
+#[cfg(feature = "math-module")]
+pub mod math;
+#[cfg(feature = "hash-module")]
+pub mod hash;
+#[cfg(feature = "string-module")]
+pub mod string;
+#[cfg(feature = "console-module")]
+pub mod console;
+#[cfg(feature = "cuckoo-module")]
+pub mod cuckoo;
+#[cfg(feature = "magic-module")]
+pub mod magic;
+#[cfg(feature = "dex-module")]
+pub mod dex;
+#[cfg(feature = "apk-module")]
+pub mod apk;
+#[cfg(feature = "pdf-module")]
+pub mod pdf;
+#[cfg(feature = "olevba-module")]
+pub mod olevba;
+#[cfg(feature = "rtf-module")]
+pub mod rtf;
+#[cfg(feature = "email-module")]
+pub mod email;
+#[cfg(feature = "zip-module")]
+pub mod zip;
+#[cfg(feature = "registry-module")]
+pub mod registry;
+#[cfg(feature = "evtx-module")]
+pub mod evtx;
+#[cfg(feature = "sqlite-module")]
+pub mod sqlite;
+#[cfg(feature = "image-module")]
+pub mod image;
+#[cfg(feature = "x509-module")]
+pub mod x509;
+#[cfg(feature = "wasm-module")]
+pub mod wasm;
+#[cfg(feature = "pyc-module")]
+pub mod pyc;
+#[cfg(feature = "javaclass-module")]
+pub mod javaclass;
+#[cfg(feature = "gobinary-module")]
+pub mod gobinary;
+#[cfg(feature = "minidump-module")]
+pub mod minidump;
+#[cfg(feature = "iso-module")]
+pub mod iso;
+#[cfg(feature = "onenote-module")]
+pub mod onenote;
+#[cfg(feature = "msi-module")]
+pub mod msi;
+#[cfg(feature = "chm-module")]
+pub mod chm;
+#[cfg(feature = "hta-module")]
+pub mod hta;
+#[cfg(feature = "tar-module")]
+pub mod tar;
+#[cfg(feature = "jobs-module")]
+pub mod jobs;
+#[cfg(feature = "wallet-module")]
+pub mod wallet;
+#[cfg(feature = "pe-module")]
+pub mod pe;
+#[cfg(feature = "dotnet-module")]
+pub mod dotnet;
+#[cfg(feature = "elf-module")]
+pub mod elf;
+#[cfg(feature = "macho-module")]
+pub mod macho;
+#[cfg(feature = "lnk-module")]
+pub mod lnk;
\ No newline at end of file
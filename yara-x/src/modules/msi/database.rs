@@ -0,0 +1,256 @@
+/*! Reader for the tables in Windows Installer databases.
+
+MSI files are compound files where each table is stored in its own stream,
+column by column. String values are stored as references to a string pool
+shared by all tables, formed by the `_StringPool` stream with the length
+of each string, and the `_StringData` stream with the strings themselves.
+The columns of each table are described in the `_Columns` table, and the
+names of the tables are in the `_Tables` table.
+
+Stream names are compressed, most characters are packed two by two in a
+single UTF-16 code unit, and names of tables are prefixed by a special
+code unit.
+
+See: https://learn.microsoft.com/en-us/windows/win32/msi/about-the-installer-database
+*/
+
+use std::collections::HashMap;
+
+use crate::modules::utils::ole::{DirEntry, EntryType, Ole};
+
+/// Maximum size of the table streams and the string pool.
+const MAX_STREAM_SIZE: usize = 16 * 1024 * 1024;
+
+/// Code unit at the start of the names of streams that contain tables.
+const TABLE_PREFIX: u32 = 0x4840;
+
+/// Bit in the column types that indicates a string column.
+const COLUMN_STRING: u16 = 0x0800;
+
+/// A value in a table.
+pub(crate) enum Value {
+    Null,
+    Integer(i64),
+    String(String),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes the name of a stream, returning the name and whether the stream
+/// contains a table.
+pub(crate) fn decode_name(name: &str) -> (String, bool) {
+    let mut decoded = String::new();
+    let mut is_table = false;
+
+    for (i, c) in name.chars().enumerate() {
+        let c = c as u32;
+        match c {
+            TABLE_PREFIX if i == 0 => is_table = true,
+            0x3800..=0x47ff => {
+                decoded.push(decode_char(c - 0x3800));
+                decoded.push(decode_char((c - 0x3800) >> 6));
+            }
+            0x4800..=0x483f => decoded.push(decode_char(c - 0x4800)),
+            _ => decoded.push(char::from_u32(c).unwrap()),
+        }
+    }
+
+    (decoded, is_table)
+}
+
+/// Decodes a 6-bit character in a stream name.
+fn decode_char(c: u32) -> char {
+    match c & 0x3f {
+        c @ 0..=9 => (b'0' + c as u8) as char,
+        c @ 10..=35 => (b'A' + (c - 10) as u8) as char,
+        c @ 36..=61 => (b'a' + (c - 36) as u8) as char,
+        62 => '.',
+        _ => '_',
+    }
+}
+
+pub(crate) struct Database<'a> {
+    ole: &'a Ole<'a>,
+    /// Streams with tables, indexed by the table's name.
+    tables: HashMap<String, &'a DirEntry>,
+    /// Strings in the string pool, indexed by their IDs. The string with
+    /// ID 0 is the null value.
+    strings: Vec<String>,
+    /// Size of the string references, which is 3 bytes in databases with
+    /// many strings, and 2 bytes otherwise.
+    string_ref_size: usize,
+}
+
+impl<'a> Database<'a> {
+    /// Opens the database in a compound file. Returns `None` if the file
+    /// doesn't have a string pool.
+    pub fn open(ole: &'a Ole<'a>) -> Option<Self> {
+        let mut tables = HashMap::new();
+
+        for entry in ole.entries.iter() {
+            if entry.entry_type != EntryType::Stream {
+                continue;
+            }
+            if let (name, true) = decode_name(&entry.name) {
+                tables.insert(name, entry);
+            }
+        }
+
+        let pool = ole.read(tables.get("_StringPool")?, MAX_STREAM_SIZE);
+        let data = ole.read(tables.get("_StringData")?, MAX_STREAM_SIZE);
+
+        let pool: Vec<u16> = pool
+            .chunks_exact(2)
+            .map(|word| u16::from_le_bytes([word[0], word[1]]))
+            .collect();
+
+        // The first entry in the pool has the codepage, and the highest bit
+        // indicates that string references are 3 bytes long.
+        let string_ref_size = match pool.get(1) {
+            Some(word) if word & 0x8000 != 0 => 3,
+            Some(_) => 2,
+            None => return None,
+        };
+
+        let mut strings = vec![String::new()];
+        let mut offset = 0;
+        let mut i = 1;
+
+        // Each entry has the string's length and reference count. Strings
+        // longer than 64KB use two entries, the first one has a zero length
+        // and the second one has the length in both words.
+        while let (Some(len), Some(refs)) =
+            (pool.get(i * 2), pool.get(i * 2 + 1))
+        {
+            let len = match (*len, *refs) {
+                (0, 0) => 0,
+                (0, _) => {
+                    i += 1;
+                    match (pool.get(i * 2), pool.get(i * 2 + 1)) {
+                        (Some(low), Some(high)) => {
+                            (*high as usize) << 16 | *low as usize
+                        }
+                        _ => break,
+                    }
+                }
+                (len, _) => len as usize,
+            };
+            i += 1;
+
+            let s = match data.get(offset..offset + len) {
+                Some(s) => s,
+                None => break,
+            };
+            strings.push(String::from_utf8_lossy(s).into_owned());
+            offset += len;
+        }
+
+        Some(Self { ole, tables, strings, string_ref_size })
+    }
+
+    /// Returns the names of the tables listed in the `_Tables` table.
+    pub fn table_names(&self) -> Vec<String> {
+        self.read_table("_Tables", &[COLUMN_STRING])
+            .into_iter()
+            .filter_map(|mut row| match row.pop() {
+                Some(Value::String(name)) => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the rows in the given table, with the columns described in
+    /// the `_Columns` table.
+    pub fn rows(&self, table: &str) -> Vec<Vec<Value>> {
+        // The `_Columns` table has the table's name, the column's number,
+        // its name and its type.
+        let mut columns: Vec<(i64, u16)> = self
+            .read_table("_Columns", &[COLUMN_STRING, 2, COLUMN_STRING, 2])
+            .into_iter()
+            .filter(|row| row[0].as_str() == Some(table))
+            .filter_map(|row| {
+                Some((row[1].as_integer()?, row[3].as_integer()? as u16))
+            })
+            .collect();
+
+        columns.sort_by_key(|(number, _)| *number);
+
+        let types: Vec<u16> = columns.iter().map(|(_, t)| *t).collect();
+
+        self.read_table(table, &types)
+    }
+
+    /// Reads a table with columns of the given types. The lowest byte of
+    /// the type is the size of integer columns.
+    fn read_table(&self, table: &str, types: &[u16]) -> Vec<Vec<Value>> {
+        let entry = match self.tables.get(table) {
+            Some(entry) if !types.is_empty() => entry,
+            _ => return Vec::new(),
+        };
+
+        let widths: Vec<usize> = types
+            .iter()
+            .map(|t| {
+                if t & COLUMN_STRING != 0 {
+                    self.string_ref_size
+                } else if t & 0xff == 4 {
+                    4
+                } else {
+                    2
+                }
+            })
+            .collect();
+
+        let data = self.ole.read(entry, MAX_STREAM_SIZE);
+        let num_rows = data.len() / widths.iter().sum::<usize>();
+
+        let mut rows: Vec<Vec<Value>> =
+            (0..num_rows).map(|_| Vec::with_capacity(types.len())).collect();
+
+        // Tables are stored column by column.
+        let mut offset = 0;
+
+        for (t, width) in types.iter().zip(widths) {
+            for row in rows.iter_mut() {
+                let mut bytes = [0; 4];
+                bytes[..width].copy_from_slice(&data[offset..offset + width]);
+                let raw = u32::from_le_bytes(bytes);
+                offset += width;
+
+                // Integers are stored with their highest bit flipped, and
+                // zero means null, both for strings and integers.
+                row.push(match (raw, width) {
+                    (0, _) => Value::Null,
+                    _ if t & COLUMN_STRING != 0 => Value::String(
+                        self.strings
+                            .get(raw as usize)
+                            .cloned()
+                            .unwrap_or_default(),
+                    ),
+                    (raw, 2) => {
+                        Value::Integer((raw as u16 ^ 0x8000) as i16 as i64)
+                    }
+                    (raw, _) => {
+                        Value::Integer((raw ^ 0x80000000) as i32 as i64)
+                    }
+                });
+            }
+        }
+
+        rows
+    }
+}
@@ -0,0 +1,459 @@
+/*! YARA module that parses Windows Installer databases (.msi).
+
+The module exposes the summary information, the names of the tables, the
+custom actions, and the streams that are not tables, like the binaries
+used by custom actions and the embedded cabinets, together with their
+hashes.
+*/
+
+use sha2::{Digest, Sha256};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::msi::*;
+use crate::modules::utils::ole::{EntryType, Ole};
+
+mod database;
+mod summary;
+
+use database::Database;
+use summary::Property;
+
+/// Class identifier of the root storage in MSI files,
+/// {000C1084-0000-0000-C000-000000000046}.
+const MSI_CLSID: [u8; 16] =
+    *b"\x84\x10\x0c\x00\x00\x00\x00\x00\xc0\x00\x00\x00\x00\x00\x00\x46";
+
+/// Maximum size of the summary information stream.
+const MAX_SUMMARY_SIZE: usize = 1024 * 1024;
+
+/// Maximum size of the streams whose hash is computed.
+const MAX_HASHED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Maximum number of entries in each of the repeated fields.
+const MAX_ENTRIES: usize = 16384;
+
+/// Identifiers of the properties in the summary information.
+const PID_CODEPAGE: u32 = 1;
+const PID_TITLE: u32 = 2;
+const PID_SUBJECT: u32 = 3;
+const PID_AUTHOR: u32 = 4;
+const PID_KEYWORDS: u32 = 5;
+const PID_COMMENTS: u32 = 6;
+const PID_TEMPLATE: u32 = 7;
+const PID_LASTAUTHOR: u32 = 8;
+const PID_REVNUMBER: u32 = 9;
+const PID_CREATE_DTM: u32 = 12;
+const PID_LASTSAVE_DTM: u32 = 13;
+const PID_APPNAME: u32 = 18;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Msi {
+    let mut msi = Msi::new();
+
+    let ole = match Ole::parse(ctx.scanned_data()) {
+        Some(ole) => ole,
+        None => {
+            msi.set_is_msi(false);
+            return msi;
+        }
+    };
+
+    let db = Database::open(&ole);
+
+    let is_msi = db.is_some()
+        || ole.entries.first().map_or(false, |root| root.clsid == MSI_CLSID);
+
+    msi.set_is_msi(is_msi);
+
+    if !is_msi {
+        return msi;
+    }
+
+    if let Some(entry) = ole.stream("\u{5}SummaryInformation") {
+        let properties = summary::parse(&ole.read(entry, MAX_SUMMARY_SIZE));
+
+        let string = |id| match properties.get(&id) {
+            Some(Property::String(s)) => Some(s.clone()),
+            _ => None,
+        };
+        let time = |id| match properties.get(&id) {
+            Some(Property::Time(t)) => Some(*t),
+            _ => None,
+        };
+
+        if let Some(Property::Integer(codepage)) =
+            properties.get(&PID_CODEPAGE)
+        {
+            // The codepage is a 16-bit unsigned integer stored as a signed
+            // one, 65001 (UTF-8) would be negative otherwise.
+            msi.set_codepage(*codepage as u16 as i64);
+        }
+
+        msi.title = string(PID_TITLE);
+        msi.subject = string(PID_SUBJECT);
+        msi.author = string(PID_AUTHOR);
+        msi.keywords = string(PID_KEYWORDS);
+        msi.comments = string(PID_COMMENTS);
+        msi.template = string(PID_TEMPLATE);
+        msi.last_saved_by = string(PID_LASTAUTHOR);
+        msi.revision_number = string(PID_REVNUMBER);
+        msi.creating_application = string(PID_APPNAME);
+        msi.creation_time = time(PID_CREATE_DTM);
+        msi.last_save_time = time(PID_LASTSAVE_DTM);
+    }
+
+    if let Some(db) = db {
+        let tables = db.table_names();
+        msi.set_tables_truncated(tables.len() > MAX_ENTRIES);
+        msi.tables = tables.into_iter().take(MAX_ENTRIES).collect();
+
+        let rows = db.rows("CustomAction");
+        msi.set_custom_actions_truncated(rows.len() > MAX_ENTRIES);
+
+        // The columns are Action, Type, Source and Target, and newer
+        // schemas add ExtendedType.
+        for row in rows.into_iter().take(MAX_ENTRIES) {
+            let mut action = CustomAction::new();
+            let column = |i: usize| row.get(i);
+            action.action = column(0).and_then(|v| v.as_str()).map(Into::into);
+            action.type_ = column(1).and_then(|v| v.as_integer());
+            action.source = column(2).and_then(|v| v.as_str()).map(Into::into);
+            action.target = column(3).and_then(|v| v.as_str()).map(Into::into);
+            msi.custom_actions.push(action);
+        }
+    }
+
+    for entry in ole.entries.iter() {
+        if entry.entry_type != EntryType::Stream {
+            continue;
+        }
+
+        // Property sets, like the summary information, have names that
+        // start with "\x05".
+        let (name, is_table) = database::decode_name(&entry.name);
+        if is_table || name.starts_with('\u{5}') {
+            continue;
+        }
+
+        if msi.streams.len() == MAX_ENTRIES {
+            msi.set_streams_truncated(true);
+            break;
+        }
+
+        let mut stream = MsiStream::new();
+        stream.set_name(name);
+        stream.set_size(entry.size as i64);
+
+        if entry.size <= MAX_HASHED_SIZE as u64 {
+            let content = ole.read(entry, MAX_HASHED_SIZE);
+            stream.set_sha256(format!("{:x}", Sha256::digest(content)));
+        }
+
+        msi.streams.push(stream);
+    }
+
+    msi
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::utils::ole::SIGNATURE;
+
+    /// Characters that can be packed in stream names.
+    const NAME_CHARS: &[u8] =
+        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._";
+
+    /// Encodes the name of a stream, as done by Windows Installer.
+    fn encode_name(name: &str, is_table: bool) -> String {
+        let index =
+            |c: u8| NAME_CHARS.iter().position(|n| *n == c).unwrap() as u32;
+
+        let mut encoded = Vec::new();
+        if is_table {
+            encoded.push(0x4840);
+        }
+        for pair in name.as_bytes().chunks(2) {
+            match pair {
+                [a, b] => encoded.push(0x3800 + index(*a) + (index(*b) << 6)),
+                [a] => encoded.push(0x4800 + index(*a)),
+                _ => unreachable!(),
+            }
+        }
+
+        encoded.into_iter().map(|c| char::from_u32(c).unwrap()).collect()
+    }
+
+    /// Returns a 128-byte directory entry for a compound file.
+    fn dir_entry(
+        name: &str,
+        entry_type: u8,
+        right: u32,
+        child: u32,
+        start_sector: u32,
+        size: usize,
+    ) -> Vec<u8> {
+        let mut entry = vec![0; 128];
+        let name = name.encode_utf16().flat_map(u16::to_le_bytes);
+        let name = name.collect::<Vec<_>>();
+        entry[..name.len()].copy_from_slice(&name);
+        entry[0x40..0x42]
+            .copy_from_slice(&(name.len() as u16 + 2).to_le_bytes());
+        entry[0x42] = entry_type;
+        entry[0x44..0x48].fill(0xff);
+        entry[0x48..0x4c].copy_from_slice(&right.to_le_bytes());
+        entry[0x4c..0x50].copy_from_slice(&child.to_le_bytes());
+        entry[0x74..0x78].copy_from_slice(&start_sector.to_le_bytes());
+        entry[0x78..0x80].copy_from_slice(&(size as u64).to_le_bytes());
+        entry
+    }
+
+    /// Builds a compound file with the given streams, all of them in the
+    /// root storage and stored in regular sectors. The root storage has
+    /// the class identifier of MSI files.
+    fn compound_file(streams: &[(String, Vec<u8>)]) -> Vec<u8> {
+        const NONE: u32 = 0xffffffff;
+        const END_OF_CHAIN: u32 = 0xfffffffe;
+
+        let dir_sectors = (streams.len() + 1 + 3) / 4;
+
+        // Sector 0 contains the FAT, followed by the directory and the
+        // streams.
+        let mut fat = vec![0xfffffffd_u32];
+        let mut directory = Vec::new();
+        let mut sectors = Vec::new();
+
+        for i in 1..dir_sectors {
+            fat.push(i as u32 + 1);
+        }
+        fat.push(END_OF_CHAIN);
+
+        let mut root = dir_entry("Root Entry", 5, NONE, 1, END_OF_CHAIN, 0);
+        root[0x50..0x60].copy_from_slice(&super::MSI_CLSID);
+        directory.extend(root);
+
+        for (i, (name, content)) in streams.iter().enumerate() {
+            let start = fat.len() as u32;
+            let num_sectors = (content.len() + 511) / 512;
+            for j in 1..num_sectors {
+                fat.push(start + j as u32);
+            }
+            fat.push(END_OF_CHAIN);
+
+            let right =
+                if i + 1 < streams.len() { i as u32 + 2 } else { NONE };
+            directory.extend(dir_entry(
+                name,
+                2,
+                right,
+                NONE,
+                start,
+                content.len(),
+            ));
+
+            let mut content = content.clone();
+            content.resize(num_sectors * 512, 0);
+            sectors.extend(content);
+        }
+
+        fat.resize(128, NONE);
+        directory.resize(dir_sectors * 512, 0);
+
+        let mut header = vec![0; 512];
+        header[..8].copy_from_slice(SIGNATURE);
+        header[0x18..0x1a].copy_from_slice(&0x3e_u16.to_le_bytes());
+        header[0x1a..0x1c].copy_from_slice(&3_u16.to_le_bytes());
+        header[0x1c..0x1e].copy_from_slice(&0xfffe_u16.to_le_bytes());
+        header[0x1e..0x20].copy_from_slice(&9_u16.to_le_bytes());
+        header[0x20..0x22].copy_from_slice(&6_u16.to_le_bytes());
+        header[0x2c..0x30].copy_from_slice(&1_u32.to_le_bytes());
+        header[0x30..0x34].copy_from_slice(&1_u32.to_le_bytes());
+        header[0x3c..0x40].copy_from_slice(&NONE.to_le_bytes());
+        header[0x44..0x48].copy_from_slice(&NONE.to_le_bytes());
+        header[0x4c..0x50].copy_from_slice(&0_u32.to_le_bytes());
+        header[0x50..].fill(0xff);
+
+        [
+            header,
+            fat.iter().flat_map(|entry| entry.to_le_bytes()).collect(),
+            directory,
+            sectors,
+        ]
+        .concat()
+    }
+
+    /// Builds a summary information stream.
+    fn summary_information() -> Vec<u8> {
+        let string = |s: &str| {
+            let mut value = 0x1e_u32.to_le_bytes().to_vec();
+            value.extend((s.len() as u32 + 1).to_le_bytes());
+            value.extend(s.as_bytes());
+            value.push(0);
+            value.resize((value.len() + 3) & !3, 0);
+            value
+        };
+
+        let properties = [
+            (1_u32, [2, 0, 0, 0, 0xe4, 0x04, 0, 0].to_vec()),
+            (4, string("Evil Corp")),
+            (7, string("x64;1033")),
+            (9, string("{8F5D3A2B-1C4E-4F6A-9B7D-2E1F0A3C5B6D}")),
+            (
+                12,
+                [&[0x40, 0, 0, 0][..], &133420176000000000_u64.to_le_bytes()]
+                    .concat(),
+            ),
+            (18, string("Windows Installer XML")),
+        ];
+
+        let mut header = b"\xfe\xff\x00\x00".to_vec();
+        header.resize(24, 0);
+        header.extend(1_u32.to_le_bytes());
+        header.extend([0; 16]);
+        header.extend(48_u32.to_le_bytes());
+
+        let mut offsets = Vec::new();
+        let mut values = Vec::new();
+        let mut offset = 8 + properties.len() * 8;
+        for (id, value) in properties.iter() {
+            offsets.extend(id.to_le_bytes());
+            offsets.extend((offset as u32).to_le_bytes());
+            offset += value.len();
+            values.extend(value);
+        }
+
+        [
+            header,
+            (offset as u32).to_le_bytes().to_vec(),
+            (properties.len() as u32).to_le_bytes().to_vec(),
+            offsets,
+            values,
+        ]
+        .concat()
+    }
+
+    /// Builds an MSI file with a custom action that runs a DLL from the
+    /// Binary table, and another one that runs PowerShell.
+    fn msi() -> Vec<u8> {
+        let strings = [
+            "CustomAction",
+            "Binary",
+            "Action",
+            "Type",
+            "Source",
+            "Target",
+            "RunDll",
+            "payload",
+            "DllMain",
+            "RunPowerShell",
+            "SystemFolder",
+            "powershell.exe -enc SQBFAFgA",
+        ];
+        let id =
+            |s: &str| strings.iter().position(|x| *x == s).unwrap() as u16 + 1;
+
+        // The codepage is 1252, and string references are 2 bytes long.
+        let mut pool = vec![0xe4, 0x04, 0, 0];
+        for s in strings {
+            pool.extend((s.len() as u16).to_le_bytes());
+            pool.extend(1_u16.to_le_bytes());
+        }
+
+        // Tables are stored column by column. Integers have their highest
+        // bit flipped.
+        let column = |values: &[u16]| -> Vec<u8> {
+            values.iter().flat_map(|v| v.to_le_bytes()).collect()
+        };
+
+        let tables = column(&[id("CustomAction"), id("Binary")]);
+
+        let columns = [
+            column(&[id("CustomAction"); 4]),
+            column(&[0x8001, 0x8002, 0x8003, 0x8004]),
+            column(&[id("Action"), id("Type"), id("Source"), id("Target")]),
+            column(&[0x2d48, 0x0502, 0x1d48, 0x1fff]),
+        ]
+        .concat();
+
+        let custom_actions = [
+            column(&[id("RunDll"), id("RunPowerShell")]),
+            column(&[0x8001, 0x8000 + 34]),
+            column(&[id("payload"), id("SystemFolder")]),
+            column(&[id("DllMain"), id("powershell.exe -enc SQBFAFgA")]),
+        ]
+        .concat();
+
+        compound_file(&[
+            ("\u{5}SummaryInformation".to_string(), summary_information()),
+            (encode_name("_StringPool", true), pool),
+            (encode_name("_StringData", true), strings.concat().into()),
+            (encode_name("_Tables", true), tables),
+            (encode_name("_Columns", true), columns),
+            (encode_name("CustomAction", true), custom_actions),
+            (encode_name("Binary.payload", false), b"MZ\x90\x00".to_vec()),
+        ])
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "msi"
+                rule summary {
+                  condition:
+                    msi.is_msi and
+                    msi.codepage == 1252 and
+                    msi.author == "Evil Corp" and
+                    msi.template == "x64;1033" and
+                    msi.revision_number == "{8F5D3A2B-1C4E-4F6A-9B7D-2E1F0A3C5B6D}" and
+                    msi.creating_application == "Windows Installer XML" and
+                    msi.creation_time == 1697544000 and
+                    not defined msi.title
+                }
+                rule tables {
+                  condition:
+                    msi.tables[0] == "CustomAction" and
+                    msi.tables[1] == "Binary" and
+                    not defined msi.tables[2]
+                }
+                rule custom_actions {
+                  condition:
+                    msi.custom_actions[0].action == "RunDll" and
+                    msi.custom_actions[0].type == 1 and
+                    msi.custom_actions[0].source == "payload" and
+                    msi.custom_actions[0].target == "DllMain" and
+                    (msi.custom_actions[1].type & 0x3f) == 34 and
+                    msi.custom_actions[1].target contains "powershell"
+                }
+                rule streams {
+                  condition:
+                    msi.streams[0].name == "Binary.payload" and
+                    msi.streams[0].size == 4 and
+                    msi.streams[0].sha256 == "9f2981a7cc4d40a2a409dc895de64253acd819d7c0011c8e80b86fe899464e31" and
+                    not defined msi.streams[1].name
+                }
+                rule not_msi { condition: not msi.is_msi }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&msi()),
+            ["summary", "tables", "custom_actions", "streams"]
+        );
+        assert_eq!(matching_rules(b"MZ\x90\x00"), ["not_msi"]);
+    }
+}
@@ -0,0 +1,83 @@
+/*! Reader for the summary information stream.
+
+The `\x05SummaryInformation` stream is a property set, a structure used by
+compound files for storing metadata. It starts with a header that points
+to a section, which has a list of property identifiers and the offsets
+where their values are stored. Each value starts with its type.
+
+See: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-oleps/
+*/
+
+use std::collections::HashMap;
+
+use crate::modules::utils::{filetime_to_epoch, u16_at, u32_at};
+
+/// Types of the property values used by the summary information.
+const VT_I2: u32 = 0x02;
+const VT_I4: u32 = 0x03;
+const VT_LPSTR: u32 = 0x1e;
+const VT_FILETIME: u32 = 0x40;
+
+/// Maximum number of properties.
+const MAX_PROPERTIES: usize = 256;
+
+/// A property value.
+pub(crate) enum Property {
+    Integer(i64),
+    String(String),
+    Time(i64),
+}
+
+/// Parses a property set, returning its properties indexed by their
+/// identifiers. Only the first section is parsed, and properties with
+/// unsupported types are ignored.
+pub(crate) fn parse(data: &[u8]) -> HashMap<u32, Property> {
+    let mut properties = HashMap::new();
+
+    // The header has the byte order mark, the version, the system
+    // identifier, the class identifier and the number of sections, followed
+    // by the format identifier and offset of each section.
+    let section = match u32_at(data, 44) {
+        Some(offset) if data.starts_with(b"\xfe\xff") => offset as usize,
+        _ => return properties,
+    };
+
+    let count = u32_at(data, section + 4).unwrap_or_default() as usize;
+
+    for i in 0..count.min(MAX_PROPERTIES) {
+        let entry = section + 8 + i * 8;
+        let (id, offset) = match (u32_at(data, entry), u32_at(data, entry + 4))
+        {
+            (Some(id), Some(offset)) => (id, section + offset as usize),
+            _ => break,
+        };
+
+        let value = match u32_at(data, offset) {
+            Some(VT_I2) => u16_at(data, offset + 4)
+                .map(|v| Property::Integer(v as i16 as i64)),
+            Some(VT_I4) => u32_at(data, offset + 4)
+                .map(|v| Property::Integer(v as i32 as i64)),
+            Some(VT_LPSTR) => u32_at(data, offset + 4)
+                .and_then(|len| {
+                    data.get(offset + 8..offset + 8 + len as usize)
+                })
+                .map(|s| {
+                    let s = s.split(|b| *b == 0).next().unwrap_or_default();
+                    Property::String(String::from_utf8_lossy(s).into_owned())
+                }),
+            Some(VT_FILETIME) => u32_at(data, offset + 4)
+                .zip(u32_at(data, offset + 8))
+                .and_then(|(low, high)| {
+                    filetime_to_epoch(u64::from(high) << 32 | u64::from(low))
+                })
+                .map(Property::Time),
+            _ => None,
+        };
+
+        if let Some(value) = value {
+            properties.insert(id, value);
+        }
+    }
+
+    properties
+}
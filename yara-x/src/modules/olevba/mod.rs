@@ -0,0 +1,410 @@
+use crate::modules::prelude::*;
+use crate::modules::protos::olevba::*;
+use crate::modules::utils::ole::{self, EntryType, Ole};
+use crate::modules::utils::zip::Archive;
+
+mod vba;
+
+/// Maximum size of `vbaProject.bin` files extracted from OOXML documents.
+const MAX_PROJECT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Maximum size of the streams in a VBA project, and of the decompressed
+/// source code of each module.
+const MAX_STREAM_SIZE: usize = 16 * 1024 * 1024;
+
+/// Keywords that are counted in `suspicious_keywords`. They include macros
+/// that run automatically, functions used for executing commands or
+/// downloading files, and functions commonly used for obfuscation.
+const SUSPICIOUS_KEYWORDS: &[&str] = &[
+    "AutoExec",
+    "AutoOpen",
+    "Auto_Open",
+    "AutoClose",
+    "Auto_Close",
+    "Document_Open",
+    "Document_Close",
+    "Workbook_Open",
+    "Workbook_Activate",
+    "Shell",
+    "WScript.Shell",
+    "ShellExecute",
+    "CreateObject",
+    "GetObject",
+    "CallByName",
+    "Environ",
+    "PowerShell",
+    "URLDownloadToFile",
+    "MSXML2.XMLHTTP",
+    "ADODB.Stream",
+    "SaveToFile",
+    "Kill",
+    "Declare",
+    "Lib",
+    "VirtualAlloc",
+    "RtlMoveMemory",
+    "CreateThread",
+    "ExecuteExcel4Macro",
+    "Chr",
+    "ChrW",
+    "StrReverse",
+    "Base64",
+];
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Olevba {
+    let data = ctx.scanned_data();
+    let mut olevba = Olevba::new();
+
+    if data.starts_with(ole::SIGNATURE) {
+        olevba.set_container(Container::OLE);
+        if let Some(ole) = Ole::parse(data) {
+            extract_modules(&ole, &mut olevba);
+        }
+    } else if let Some(archive) = Archive::parse(data) {
+        if archive.entry(b"[Content_Types].xml").is_some() {
+            olevba.set_container(Container::OOXML);
+        }
+        // Word, Excel and PowerPoint documents store the project in
+        // `word/vbaProject.bin`, `xl/vbaProject.bin` and
+        // `ppt/vbaProject.bin`, respectively.
+        for entry in archive.entries.iter() {
+            if !entry.name.ends_with(b"vbaProject.bin") {
+                continue;
+            }
+            if let Some(project) = archive.read(entry, MAX_PROJECT_SIZE) {
+                if let Some(ole) = Ole::parse(&project) {
+                    extract_modules(&ole, &mut olevba);
+                }
+            }
+        }
+    }
+
+    olevba.set_has_macros(!olevba.modules.is_empty());
+
+    for keyword in SUSPICIOUS_KEYWORDS {
+        let count = olevba
+            .modules
+            .iter()
+            .map(|module| count_keyword(module.source(), keyword.as_bytes()))
+            .sum::<usize>();
+        if count > 0 {
+            let mut k = Keyword::new();
+            k.set_keyword(keyword.to_string());
+            k.set_count(count as i64);
+            olevba.suspicious_keywords.push(k);
+        }
+    }
+
+    olevba
+}
+
+/// Returns the number of times a keyword appears in the source code of all
+/// modules. The comparison is case-insensitive, and only occurrences that
+/// are not part of a longer identifier are counted.
+#[module_export]
+fn keyword_count(ctx: &ScanContext, keyword: RuntimeString) -> Option<i64> {
+    let olevba = ctx.module_output::<Olevba>()?;
+    let keyword = keyword.as_bstr(ctx);
+
+    Some(
+        olevba
+            .modules
+            .iter()
+            .map(|module| count_keyword(module.source(), keyword))
+            .sum::<usize>() as i64,
+    )
+}
+
+/// Adds the modules of every VBA project in a compound file. Documents
+/// usually have a single project, but the location of its `VBA` storage
+/// depends on the application (e.g: `Macros/VBA` in Word documents,
+/// `_VBA_PROJECT_CUR/VBA` in Excel workbooks).
+fn extract_modules(ole: &Ole, olevba: &mut Olevba) {
+    for entry in ole.entries.iter() {
+        if entry.entry_type != EntryType::Stream
+            || !(entry.path.eq_ignore_ascii_case("VBA/dir")
+                || ends_with_ignore_case(&entry.path, "/VBA/dir"))
+        {
+            continue;
+        }
+
+        // Path of the `VBA` storage, including the trailing slash.
+        let storage = &entry.path[..entry.path.len() - "dir".len()];
+
+        let project = match vba::decompress(
+            &ole.read(entry, MAX_STREAM_SIZE),
+            MAX_STREAM_SIZE,
+        ) {
+            Some(dir) => vba::parse_dir(&dir),
+            None => continue,
+        };
+
+        for module in project.modules {
+            let stream_path = format!("{}{}", storage, module.stream_name);
+            let stream = ole
+                .stream(&stream_path)
+                .map(|stream| ole.read(stream, MAX_STREAM_SIZE))
+                .unwrap_or_default();
+
+            let mut m = Module::new();
+
+            m.set_name(module.name);
+            m.set_stream_path(stream_path);
+            m.set_type(if module.is_document {
+                ModuleType::DOCUMENT
+            } else {
+                ModuleType::PROCEDURAL
+            });
+            m.set_has_pcode(module.text_offset > 0);
+            m.source = stream
+                .get(module.text_offset..)
+                .and_then(|source| vba::decompress(source, MAX_STREAM_SIZE))
+                .map(|source| vba::decode(&source));
+
+            olevba.modules.push(m);
+        }
+    }
+}
+
+fn ends_with_ignore_case(s: &str, suffix: &str) -> bool {
+    s.len() >= suffix.len()
+        && s.as_bytes()[s.len() - suffix.len()..]
+            .eq_ignore_ascii_case(suffix.as_bytes())
+}
+
+/// Counts the occurrences of a keyword in the source code, ignoring case.
+/// Occurrences preceded or followed by characters that can be part of an
+/// identifier are not counted.
+fn count_keyword(source: &str, keyword: &[u8]) -> usize {
+    let source = source.as_bytes();
+
+    if keyword.is_empty() || keyword.len() > source.len() {
+        return 0;
+    }
+
+    let is_ident = |b: Option<&u8>| {
+        b.map_or(false, |b| b.is_ascii_alphanumeric() || *b == b'_')
+    };
+
+    (0..=source.len() - keyword.len())
+        .filter(|i| {
+            source[*i..*i + keyword.len()].eq_ignore_ascii_case(keyword)
+                && !is_ident(i.checked_sub(1).and_then(|j| source.get(j)))
+                && !is_ident(source.get(i + keyword.len()))
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::utils::ole::SIGNATURE;
+
+    /// Compresses data with the algorithm used by VBA projects, but using
+    /// literal bytes only.
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut compressed = vec![0x01];
+        for chunk in data.chunks(4096) {
+            let mut tokens = Vec::new();
+            for group in chunk.chunks(8) {
+                tokens.push(0x00);
+                tokens.extend(group);
+            }
+            let header = 0xb000 | (tokens.len() as u16 + 2 - 3);
+            compressed.extend(header.to_le_bytes());
+            compressed.extend(tokens);
+        }
+        compressed
+    }
+
+    /// Returns a record for a `dir` stream.
+    fn record(id: u16, data: &[u8]) -> Vec<u8> {
+        [&id.to_le_bytes()[..], &(data.len() as u32).to_le_bytes(), data]
+            .concat()
+    }
+
+    /// Returns a 128-byte directory entry.
+    fn dir_entry(
+        name: &str,
+        entry_type: u8,
+        siblings: (u32, u32),
+        child: u32,
+        start_sector: u32,
+        size: u64,
+    ) -> Vec<u8> {
+        let mut entry = vec![0; 128];
+        let name = name.encode_utf16().flat_map(u16::to_le_bytes);
+        let name = name.collect::<Vec<_>>();
+        entry[..name.len()].copy_from_slice(&name);
+        entry[0x40..0x42]
+            .copy_from_slice(&(name.len() as u16 + 2).to_le_bytes());
+        entry[0x42] = entry_type;
+        entry[0x44..0x48].copy_from_slice(&siblings.0.to_le_bytes());
+        entry[0x48..0x4c].copy_from_slice(&siblings.1.to_le_bytes());
+        entry[0x4c..0x50].copy_from_slice(&child.to_le_bytes());
+        entry[0x74..0x78].copy_from_slice(&start_sector.to_le_bytes());
+        entry[0x78..0x80].copy_from_slice(&size.to_le_bytes());
+        entry
+    }
+
+    /// Builds a compound file with a VBA project in `Macros/VBA`. The mini
+    /// stream cutoff is set to zero, so all the streams are stored in
+    /// regular sectors.
+    fn build_document() -> Vec<u8> {
+        const NONE: u32 = 0xffffffff;
+
+        let dir = compress(
+            &[
+                record(0x0003, &1252_u16.to_le_bytes()),
+                record(0x0004, b"Project"),
+                // PROJECTVERSION, whose size is 4 but has 6 bytes of data.
+                [
+                    &0x0009_u16.to_le_bytes()[..],
+                    &[4, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                ]
+                .concat(),
+                record(0x0019, b"Module1"),
+                record(0x001a, b"Module1"),
+                record(0x0031, &0_u32.to_le_bytes()),
+                record(0x0021, &[]),
+                record(0x002b, &[]),
+                record(0x0019, b"ThisDocument"),
+                record(0x001a, b"ThisDocument"),
+                record(0x0031, &4_u32.to_le_bytes()),
+                record(0x0022, &[]),
+                record(0x002b, &[]),
+                record(0x0010, &[]),
+            ]
+            .concat(),
+        );
+
+        let module1 = compress(
+            b"Attribute VB_Name = \"Module1\"\r\n\
+              Sub AutoOpen()\r\n  Shell \"calc.exe\"\r\nEnd Sub\r\n",
+        );
+
+        let this_document = [
+            &b"\x01\x02\x03\x04"[..],
+            &compress(
+                b"Private Sub Document_Open()\r\n  \
+                  CreateObject(\"WScript.Shell\")\r\nEnd Sub\r\n",
+            ),
+        ]
+        .concat();
+
+        let streams = [dir, module1, this_document];
+
+        // Sector 0 contains the FAT, sectors 1 and 2 the directory, and the
+        // streams are in sectors 3, 4 and 5.
+        let mut fat = vec![0xfffffffd_u32, 2, 0xfffffffe];
+        fat.extend(streams.iter().map(|_| 0xfffffffe));
+        fat.resize(128, NONE);
+
+        let directory = [
+            dir_entry("Root Entry", 5, (NONE, NONE), 1, 0xfffffffe, 0),
+            dir_entry("Macros", 1, (NONE, NONE), 2, 0, 0),
+            dir_entry("VBA", 1, (NONE, NONE), 3, 0, 0),
+            dir_entry("dir", 2, (NONE, 4), NONE, 3, streams[0].len() as u64),
+            dir_entry(
+                "Module1",
+                2,
+                (NONE, 5),
+                NONE,
+                4,
+                streams[1].len() as u64,
+            ),
+            dir_entry(
+                "ThisDocument",
+                2,
+                (NONE, NONE),
+                NONE,
+                5,
+                streams[2].len() as u64,
+            ),
+        ]
+        .concat();
+
+        let mut header = vec![0; 512];
+        header[..8].copy_from_slice(SIGNATURE);
+        header[0x18..0x1a].copy_from_slice(&0x3e_u16.to_le_bytes());
+        header[0x1a..0x1c].copy_from_slice(&3_u16.to_le_bytes());
+        header[0x1c..0x1e].copy_from_slice(&0xfffe_u16.to_le_bytes());
+        header[0x1e..0x20].copy_from_slice(&9_u16.to_le_bytes());
+        header[0x20..0x22].copy_from_slice(&6_u16.to_le_bytes());
+        header[0x2c..0x30].copy_from_slice(&1_u32.to_le_bytes());
+        header[0x30..0x34].copy_from_slice(&1_u32.to_le_bytes());
+        header[0x3c..0x40].copy_from_slice(&NONE.to_le_bytes());
+        header[0x44..0x48].copy_from_slice(&NONE.to_le_bytes());
+        header[0x4c..0x50].copy_from_slice(&0_u32.to_le_bytes());
+        header[0x50..].fill(0xff);
+
+        let mut document = header;
+        document.extend(fat.iter().flat_map(|entry| entry.to_le_bytes()));
+        document.extend(directory);
+        document.resize(512 * 4, 0);
+        for stream in streams {
+            let mut sector = stream.clone();
+            sector.resize(512, 0);
+            document.extend(sector);
+        }
+        document
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "olevba"
+                rule rule_1 {
+                  condition:
+                    olevba.container == olevba.Container.OLE and
+                    olevba.has_macros and
+                    olevba.modules[0].name == "Module1" and
+                    olevba.modules[0].stream_path == "Macros/VBA/Module1" and
+                    olevba.modules[0].type == olevba.ModuleType.PROCEDURAL and
+                    olevba.modules[0].source contains "Shell \"calc.exe\"" and
+                    not olevba.modules[0].has_pcode
+                }
+                rule rule_2 {
+                  condition:
+                    olevba.modules[1].name == "ThisDocument" and
+                    olevba.modules[1].type == olevba.ModuleType.DOCUMENT and
+                    olevba.modules[1].source startswith "Private Sub" and
+                    olevba.modules[1].has_pcode
+                }
+                rule rule_3 {
+                  condition:
+                    olevba.suspicious_keywords[0].keyword == "AutoOpen" and
+                    olevba.keyword_count("shell") == 2 and
+                    olevba.keyword_count("Document_Open") == 1 and
+                    olevba.keyword_count("Open") == 0
+                }
+                rule rule_4 {
+                  condition:
+                    not defined olevba.container and not olevba.has_macros
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_document()),
+            ["rule_1", "rule_2", "rule_3"]
+        );
+
+        assert_eq!(matching_rules(b"not a document"), ["rule_4"]);
+    }
+}
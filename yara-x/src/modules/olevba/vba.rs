@@ -0,0 +1,214 @@
+/*! Extraction of VBA projects, as described in [MS-OVBA].
+
+A VBA project is a storage named `VBA` inside a compound file. Its `dir`
+stream describes the project and its modules, and each module has a stream
+containing the compiled code (p-code) followed by the source code. Both the
+`dir` stream and the source code are compressed with the algorithm
+described in section 2.4.1 of the specification.
+
+[MS-OVBA]: https://learn.microsoft.com/en-us/openspecs/office_file_formats/ms-ovba
+*/
+
+use crate::modules::utils::{u16_at, u32_at};
+
+/// Record IDs in the `dir` stream.
+const PROJECTCODEPAGE: u16 = 0x0003;
+const PROJECTNAME: u16 = 0x0004;
+const PROJECTVERSION: u16 = 0x0009;
+const MODULENAME: u16 = 0x0019;
+const MODULESTREAMNAME: u16 = 0x001a;
+const MODULETYPE_PROCEDURAL: u16 = 0x0021;
+const MODULETYPE_DOCUMENT: u16 = 0x0022;
+const MODULETERMINATOR: u16 = 0x002b;
+const MODULEOFFSET: u16 = 0x0031;
+
+/// Size of decompressed chunks.
+const CHUNK_SIZE: usize = 4096;
+
+/// A VBA project, as described by its `dir` stream.
+#[derive(Default)]
+pub(crate) struct Project {
+    pub name: Option<String>,
+    pub code_page: Option<u16>,
+    pub modules: Vec<Module>,
+}
+
+/// A module in a VBA project.
+#[derive(Default)]
+pub(crate) struct Module {
+    pub name: String,
+    /// Name of the stream that contains the module, relative to the `VBA`
+    /// storage.
+    pub stream_name: String,
+    /// True for document modules (e.g: `ThisDocument`) and class modules,
+    /// false for procedural modules.
+    pub is_document: bool,
+    /// Offset of the compressed source code in the module stream. The data
+    /// before this offset is the p-code.
+    pub text_offset: usize,
+}
+
+/// Parses a decompressed `dir` stream.
+///
+/// The stream is a sequence of records, each one with a 2-byte ID, a 4-byte
+/// size and the record's data. The only exception is `PROJECTVERSION`,
+/// whose size field is always 4 while its data has 6 bytes.
+pub(crate) fn parse_dir(dir: &[u8]) -> Project {
+    let mut project = Project::default();
+    let mut module: Option<Module> = None;
+    let mut pos = 0;
+
+    while let (Some(id), Some(size)) = (u16_at(dir, pos), u32_at(dir, pos + 2))
+    {
+        let size = if id == PROJECTVERSION { 6 } else { size as usize };
+        let data = match dir.get(pos + 6..(pos + 6).saturating_add(size)) {
+            Some(data) => data,
+            None => break,
+        };
+
+        pos += 6 + size;
+
+        match id {
+            PROJECTCODEPAGE => project.code_page = u16_at(data, 0),
+            PROJECTNAME => project.name = Some(decode(data)),
+            MODULENAME => {
+                project.modules.extend(module.take());
+                module = Some(Module {
+                    name: decode(data),
+                    stream_name: decode(data),
+                    ..Default::default()
+                });
+            }
+            MODULESTREAMNAME => {
+                if let Some(module) = module.as_mut() {
+                    module.stream_name = decode(data);
+                }
+            }
+            MODULETYPE_PROCEDURAL | MODULETYPE_DOCUMENT => {
+                if let Some(module) = module.as_mut() {
+                    module.is_document = id == MODULETYPE_DOCUMENT;
+                }
+            }
+            MODULEOFFSET => {
+                if let Some(module) = module.as_mut() {
+                    module.text_offset = u32_at(data, 0).unwrap_or(0) as usize;
+                }
+            }
+            MODULETERMINATOR => project.modules.extend(module.take()),
+            _ => {}
+        }
+    }
+
+    project.modules.extend(module);
+    project
+}
+
+/// Decompresses data compressed with the algorithm described in section
+/// 2.4.1 of [MS-OVBA]. The output is truncated to `max_size` bytes.
+///
+/// Returns `None` if the data doesn't start with the signature byte of
+/// compressed containers. Corrupt chunks stop the decompression, but the
+/// data decompressed so far is returned.
+///
+/// [MS-OVBA]: https://learn.microsoft.com/en-us/openspecs/office_file_formats/ms-ovba
+pub(crate) fn decompress(data: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    if data.first() != Some(&0x01) {
+        return None;
+    }
+
+    let mut output = Vec::new();
+    let mut pos = 1;
+
+    while let Some(header) = u16_at(data, pos) {
+        if output.len() >= max_size {
+            break;
+        }
+
+        // The lower 12 bits are the chunk size minus 3, and the highest bit
+        // indicates whether the chunk is compressed.
+        let chunk_end = (pos + (header & 0x0fff) as usize + 3).min(data.len());
+        let chunk_start = output.len();
+
+        pos += 2;
+
+        if header & 0x8000 == 0 {
+            let end = (pos + CHUNK_SIZE).min(data.len());
+            output.extend(&data[pos..end]);
+            pos = end;
+            continue;
+        }
+
+        while pos < chunk_end {
+            let flags = data[pos];
+            pos += 1;
+            for bit in 0..8 {
+                if pos >= chunk_end {
+                    break;
+                }
+                // A zero bit indicates a literal byte, while a one indicates
+                // a copy token, which references data previously
+                // decompressed in the same chunk.
+                if flags & (1 << bit) == 0 {
+                    output.push(data[pos]);
+                    pos += 1;
+                    continue;
+                }
+                let token = match u16_at(data, pos) {
+                    Some(token) => token as usize,
+                    None => return Some(output),
+                };
+                pos += 2;
+                // The number of bits used for the offset depends on the
+                // amount of data decompressed so far, with a minimum of 4.
+                let decompressed = output.len() - chunk_start;
+                let mut offset_bits = 4;
+                while (1 << offset_bits) < decompressed {
+                    offset_bits += 1;
+                }
+                let length_mask = 0xffff >> offset_bits;
+                let length = (token & length_mask) + 3;
+                let offset = (token >> (16 - offset_bits)) + 1;
+                if offset > decompressed {
+                    return Some(output);
+                }
+                for _ in 0..length {
+                    output.push(output[output.len() - offset]);
+                }
+            }
+        }
+
+        pos = chunk_end;
+    }
+
+    output.truncate(max_size);
+
+    Some(output)
+}
+
+/// Decodes text from a VBA project. Strings in the `dir` stream and source
+/// code use the project's code page, which is approximated with Latin-1.
+pub(crate) fn decode(data: &[u8]) -> String {
+    data.iter().map(|b| *b as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decompress;
+
+    #[test]
+    fn decompression() {
+        // Example from section 3.2.3 of [MS-OVBA], with copy tokens.
+        let compressed = b"\x01\x2f\xb0\x00\x23\x61\x61\x61\x62\x63\x64\x65\
+            \x82\x66\x00\x70\x61\x67\x68\x69\x6a\x01\x38\x08\x61\x6b\x6c\
+            \x00\x30\x6d\x6e\x6f\x70\x06\x71\x02\x70\x04\x10\x72\x73\x74\
+            \x75\x76\x10\x77\x78\x79\x7a\x00\x3c";
+
+        assert_eq!(
+            decompress(compressed, usize::MAX).unwrap(),
+            b"#aaabcdefaaaaghijaaaaaklaaamnopqaaaaaaaaaaaarstuvwxyzaaa"
+        );
+
+        assert_eq!(decompress(compressed, 5).unwrap(), b"#aaab");
+        assert_eq!(decompress(b"\x02\x00", usize::MAX), None);
+    }
+}
@@ -0,0 +1,285 @@
+/*! YARA module that finds the files embedded in OneNote documents.
+
+OneNote sections (.one) store embedded files, including both attachments
+and inserted pictures, in FileDataStoreObject structures. Each structure
+starts with a well-known GUID and the length of the file, so the module
+locates them by that GUID instead of walking the whole revision store.
+
+Structures are referenced from FileDataStoreObjectReference file nodes,
+which have the structure's location and the GUID that identifies the file
+within the document. The module uses these references for obtaining the
+GUID of each embedded file.
+
+See: https://learn.microsoft.com/en-us/openspecs/office_file_formats/ms-onestore/
+*/
+
+use std::collections::HashMap;
+
+use bstr::ByteSlice;
+use protobuf::EnumOrUnknown;
+use sha2::{Digest, Sha256};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::onenote::*;
+use crate::modules::utils::{format_guid, u16_at, u32_at, u64_at};
+
+/// GUID at the start of OneNote sections,
+/// {7B5C52E4-D88C-4DA7-AEB1-5378D02996D3}.
+const SECTION_GUID: &[u8] =
+    b"\xe4\x52\x5c\x7b\x8c\xd8\xa7\x4d\xae\xb1\x53\x78\xd0\x29\x96\xd3";
+
+/// GUID at the start of FileDataStoreObject structures,
+/// {BDE316E7-2665-4511-A4C4-8D4D0B7A9EAC}.
+const FILE_DATA_GUID: &[u8] =
+    b"\xe7\x16\xe3\xbd\x65\x26\x11\x45\xa4\xc4\x8d\x4d\x0b\x7a\x9e\xac";
+
+/// Identifier of FileDataStoreObjectReference file nodes.
+const FILE_DATA_STORE_OBJECT_REFERENCE: u32 = 0x094;
+
+/// Maximum number of embedded files.
+const MAX_FILES: usize = 4096;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> OneNote {
+    let data = ctx.scanned_data();
+    let mut onenote = OneNote::new();
+
+    if !data.starts_with(SECTION_GUID) {
+        onenote.set_is_onenote(false);
+        return onenote;
+    }
+
+    onenote.set_is_onenote(true);
+
+    for offset in data.find_iter(FILE_DATA_GUID) {
+        // The GUID is followed by the file's length as a 64-bit integer,
+        // and 12 unused bytes.
+        let content = match u64_at(data, offset + 16)
+            .and_then(|len| usize::try_from(len).ok())
+            .and_then(|len| data.get(offset + 36..)?.get(..len))
+        {
+            Some(content) => content,
+            None => continue,
+        };
+
+        if onenote.files.len() == MAX_FILES {
+            onenote.set_files_truncated(true);
+            break;
+        }
+
+        let mut file = OneNoteFile::new();
+        file.set_offset((offset + 36) as i64);
+        file.set_size(content.len() as i64);
+        file.type_ = Some(EnumOrUnknown::new(file_type(content)));
+        file.set_sha256(format!("{:x}", Sha256::digest(content)));
+        onenote.files.push(file);
+    }
+
+    if !onenote.files.is_empty() {
+        let guids = references(data);
+        for file in onenote.files.iter_mut() {
+            file.guid = guids.get(&(file.offset() as u64 - 36)).cloned();
+        }
+    }
+
+    onenote
+}
+
+/// Finds the FileDataStoreObjectReference file nodes in the data, and
+/// returns a map from the location of the referenced structure to the
+/// GUID of the file.
+///
+/// File nodes start with a 32-bit header that contains the node's type,
+/// size, base type and the format of its reference, followed by the
+/// reference itself, and then the GUID. As the nodes are not located by
+/// walking the revision store, candidates are accepted only if their
+/// reference points to a FileDataStoreObject structure.
+fn references(data: &[u8]) -> HashMap<u64, String> {
+    let mut guids = HashMap::new();
+
+    for (offset, header) in data.windows(4).enumerate() {
+        let header = u32::from_le_bytes(header.try_into().unwrap());
+
+        // The base type must be 2, which means that the node references
+        // a structure that is not a file node list.
+        if header & 0x3ff != FILE_DATA_STORE_OBJECT_REFERENCE
+            || (header >> 27) & 0xf != 2
+        {
+            continue;
+        }
+
+        // The location of the structure, which can be a 64-bit or 32-bit
+        // integer, or a compressed 16-bit or 32-bit integer that must be
+        // multiplied by 8.
+        let stp_format = (header >> 23) & 0x3;
+        let location = match stp_format {
+            0 => u64_at(data, offset + 4),
+            1 => u32_at(data, offset + 4).map(u64::from),
+            2 => u16_at(data, offset + 4).map(|stp| u64::from(stp) * 8),
+            _ => u32_at(data, offset + 4).map(|stp| u64::from(stp) * 8),
+        };
+
+        // The reference ends with the structure's size, whose length also
+        // depends on the format.
+        let stp_len = [8, 4, 2, 4][stp_format as usize];
+        let cb_len = [4, 8, 1, 2][((header >> 25) & 0x3) as usize];
+        let guid_offset = offset + 4 + stp_len + cb_len;
+
+        let (location, guid) =
+            match (location, data.get(guid_offset..guid_offset + 16)) {
+                (Some(location), Some(guid)) => (location, guid),
+                _ => continue,
+            };
+
+        let is_file_data = usize::try_from(location)
+            .ok()
+            .and_then(|location| data.get(location..))
+            .map_or(false, |object| object.starts_with(FILE_DATA_GUID));
+
+        if is_file_data {
+            guids.entry(location).or_insert_with(|| format_guid(guid));
+        }
+    }
+
+    guids
+}
+
+/// Identifies the type of an embedded file by its content.
+fn file_type(content: &[u8]) -> FileType {
+    const IMAGE_MAGICS: &[&[u8]] = &[
+        b"\x89PNG",
+        b"\xff\xd8\xff",
+        b"GIF8",
+        b"BM",
+        b"\x01\x00\x00\x00", // EMF
+    ];
+
+    if content.starts_with(b"MZ") {
+        FileType::EXECUTABLE
+    } else if content.starts_with(b"PK\x03\x04") {
+        FileType::ZIP
+    } else if content.starts_with(b"%PDF") {
+        FileType::PDF
+    } else if content.starts_with(b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1") {
+        FileType::OLE
+    } else if content.starts_with(b"\x4c\x00\x00\x00\x01\x14\x02\x00") {
+        FileType::LNK
+    } else if IMAGE_MAGICS.iter().any(|magic| content.starts_with(magic)) {
+        FileType::IMAGE
+    } else if is_html(content) {
+        FileType::HTML
+    } else if is_text(content) {
+        FileType::TEXT
+    } else {
+        FileType::UNKNOWN
+    }
+}
+
+/// Returns true if the content looks like an HTML document, which includes
+/// HTML applications (.hta).
+fn is_html(content: &[u8]) -> bool {
+    let head = content[..content.len().min(1024)].to_ascii_lowercase();
+
+    head.trim_start().starts_with(b"<")
+        && [&b"<html"[..], b"<!doctype html", b"<hta:", b"<script"]
+            .iter()
+            .any(|tag| head.contains_str(tag))
+}
+
+/// Returns true if the first bytes of the content are printable ASCII
+/// or whitespace.
+fn is_text(content: &[u8]) -> bool {
+    !content.is_empty()
+        && content[..content.len().min(512)].iter().all(|b| {
+            b.is_ascii_graphic() || b.is_ascii_whitespace() || *b == 0x1a
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FILE_DATA_GUID, SECTION_GUID};
+
+    /// Appends a FileDataStoreObject with the given content to `data`,
+    /// returning its offset.
+    fn push_file(data: &mut Vec<u8>, content: &[u8]) -> usize {
+        let offset = data.len();
+        data.extend(FILE_DATA_GUID);
+        data.extend((content.len() as u64).to_le_bytes());
+        data.extend([0; 12]);
+        data.extend(content);
+        // Structures are padded to a multiple of 8 bytes, and followed by
+        // a footer GUID.
+        data.resize((data.len() + 7) & !7, 0);
+        data.extend([0x22; 16]);
+        offset
+    }
+
+    /// Appends a FileDataStoreObjectReference file node with a compressed
+    /// 32-bit location and a 16-bit size.
+    fn push_reference(data: &mut Vec<u8>, location: usize, guid: &[u8]) {
+        let header: u32 =
+            0x094 | (28 << 10) | (3 << 23) | (3 << 25) | (2 << 27);
+        data.extend(header.to_le_bytes());
+        data.extend(((location / 8) as u32).to_le_bytes());
+        data.extend(8_u16.to_le_bytes());
+        data.extend(guid);
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "onenote"
+                rule payload {
+                  condition:
+                    onenote.is_onenote and
+                    onenote.files[0].type == onenote.FileType.IMAGE and
+                    not defined onenote.files[0].guid and
+                    onenote.files[1].type == onenote.FileType.HTML and
+                    onenote.files[1].guid == "e40d6d4f-a5a4-4a69-9a4d-1f0a2b3c4d5e" and
+                    onenote.files[1].size == 54 and
+                    uint32be(onenote.files[1].offset) == 0x3c68746d and
+                    onenote.files[2].type == onenote.FileType.EXECUTABLE and
+                    onenote.files[2].sha256 == "9f2981a7cc4d40a2a409dc895de64253acd819d7c0011c8e80b86fe899464e31" and
+                    onenote.files[3].type == onenote.FileType.TEXT
+                }
+                rule not_onenote { condition: not onenote.is_onenote }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut data = SECTION_GUID.to_vec();
+        data.resize(64, 0);
+
+        push_file(&mut data, b"\x89PNG\r\n\x1a\n");
+        let hta = push_file(
+            &mut data,
+            b"<html><hta:application /><script>run()</script></html>",
+        );
+        push_file(&mut data, b"MZ\x90\x00");
+        push_file(&mut data, b"@echo off\r\npowershell -enc AAAA\r\n");
+
+        push_reference(
+            &mut data,
+            hta,
+            b"\x4f\x6d\x0d\xe4\xa4\xa5\x69\x4a\x9a\x4d\x1f\x0a\x2b\x3c\x4d\x5e",
+        );
+
+        assert_eq!(matching_rules(&data), ["payload"]);
+        assert_eq!(matching_rules(b"MZ\x90\x00"), ["not_onenote"]);
+    }
+}
@@ -0,0 +1,129 @@
+/*! Decoding of PDF stream filters.
+
+Only the filters that are commonly used for hiding content in malicious
+documents are supported: `FlateDecode`, `ASCIIHexDecode` and
+`ASCII85Decode`. Parameters in `/DecodeParms`, like predictors, are not
+applied.
+*/
+
+use crate::modules::utils::inflate::{inflate, zlib_decompress};
+
+/// Returns the full name of a filter, given its abbreviated name. Names that
+/// are not abbreviations are returned as is.
+pub(crate) fn full_name(name: &[u8]) -> &[u8] {
+    match name {
+        b"AHx" => b"ASCIIHexDecode",
+        b"A85" => b"ASCII85Decode",
+        b"LZW" => b"LZWDecode",
+        b"Fl" => b"FlateDecode",
+        b"RL" => b"RunLengthDecode",
+        b"CCF" => b"CCITTFaxDecode",
+        b"DCT" => b"DCTDecode",
+        name => name,
+    }
+}
+
+/// Decodes the data of a stream by applying the given filters in order.
+/// Returns `None` if some filter is not supported, or the data is not
+/// valid for some of them. The output is truncated to `max_size` bytes.
+pub(crate) fn decode(
+    data: &[u8],
+    filters: &[String],
+    max_size: usize,
+) -> Option<Vec<u8>> {
+    let mut decoded = data.to_vec();
+
+    for filter in filters {
+        decoded = match filter.as_str() {
+            // Some writers produce raw DEFLATE streams without the zlib
+            // header, PDF readers accept them too.
+            "FlateDecode" => zlib_decompress(&decoded, max_size)
+                .or_else(|_| inflate(&decoded, max_size))
+                .ok()?,
+            "ASCIIHexDecode" => ascii_hex_decode(&decoded),
+            "ASCII85Decode" => ascii85_decode(&decoded)?,
+            _ => return None,
+        };
+    }
+
+    decoded.truncate(max_size);
+
+    Some(decoded)
+}
+
+/// Decodes data encoded with `ASCIIHexDecode`. Whitespaces are ignored and
+/// decoding stops at the `>` end marker. If the number of digits is odd,
+/// the last one is assumed to be followed by a zero.
+pub(crate) fn ascii_hex_decode(data: &[u8]) -> Vec<u8> {
+    let digits = data
+        .iter()
+        .take_while(|b| **b != b'>')
+        .filter_map(|b| (*b as char).to_digit(16))
+        .map(|d| d as u8)
+        .collect::<Vec<_>>();
+
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Decodes data encoded with `ASCII85Decode`. Returns `None` if the data
+/// contains invalid characters.
+fn ascii85_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(data.len() * 4 / 5);
+    let mut group = [0_u8; 5];
+    let mut n = 0;
+
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+
+    for b in data.iter() {
+        match b {
+            b'~' => break,
+            b'z' if n == 0 => decoded.extend([0; 4]),
+            b'!'..=b'u' => {
+                group[n] = b - b'!';
+                n += 1;
+                if n == 5 {
+                    decoded.extend(ascii85_group(&group)?);
+                    n = 0;
+                }
+            }
+            b if b.is_ascii_whitespace() || *b == b'\0' => {}
+            _ => return None,
+        }
+    }
+
+    // A final partial group of N characters is padded with `u` and produces
+    // N - 1 bytes.
+    if n > 1 {
+        group[n..].fill(b'u' - b'!');
+        decoded.extend(&ascii85_group(&group)?[..n - 1]);
+    }
+
+    Some(decoded)
+}
+
+fn ascii85_group(group: &[u8; 5]) -> Option<[u8; 4]> {
+    let value = group.iter().try_fold(0_u32, |acc, d| {
+        acc.checked_mul(85)?.checked_add(*d as u32)
+    })?;
+    Some(value.to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ascii85_decode, ascii_hex_decode};
+
+    #[test]
+    fn ascii_decoders() {
+        assert_eq!(ascii_hex_decode(b"48 65 6c6C 6f>"), b"Hello");
+        assert_eq!(ascii_hex_decode(b"414"), b"A@");
+        assert_eq!(
+            ascii85_decode(b"<~87cURD]i,\"Ebo80~>").unwrap(),
+            b"Hello World!"
+        );
+        assert_eq!(ascii85_decode(b"z!!~>").unwrap(), b"\0\0\0\0\0");
+        assert_eq!(ascii85_decode(b"87c\xff"), None);
+    }
+}
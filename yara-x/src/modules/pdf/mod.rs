@@ -0,0 +1,237 @@
+use bstr::ByteSlice;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::pdf::*;
+
+mod filters;
+mod parser;
+
+/// Maximum size of the data returned by `decoded_stream`.
+const MAX_DECODED_STREAM_SIZE: usize = 16 * 1024 * 1024;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Pdf {
+    let mut pdf = Pdf::new();
+
+    let document = match parser::parse(ctx.scanned_data()) {
+        Some(document) => document,
+        None => {
+            pdf.set_is_pdf(false);
+            return pdf;
+        }
+    };
+
+    pdf.set_is_pdf(true);
+    pdf.set_version(document.version.clone());
+    pdf.set_num_objects(document.objects.len() as i64);
+    pdf.set_num_streams(document.streams.len() as i64);
+    pdf.set_num_eof_markers(document.num_eof_markers as i64);
+
+    for (name, count) in document.names.iter() {
+        let mut n = Name::new();
+        n.set_name(name.to_str_lossy().into_owned());
+        n.set_count(*count);
+        pdf.names.push(n);
+    }
+
+    for stream in document.streams.iter() {
+        let mut s = Stream::new();
+        s.set_object_number(stream.object_number.into());
+        s.set_generation(stream.generation.into());
+        s.set_offset(stream.offset as i64);
+        s.set_length(stream.length as i64);
+        s.filters = stream.filters();
+        s.type_ = stream
+            .dict
+            .get_name(b"Type")
+            .map(|name| name.to_str_lossy().into_owned());
+        s.subtype = stream
+            .dict
+            .get_name(b"Subtype")
+            .map(|name| name.to_str_lossy().into_owned());
+        pdf.streams.push(s);
+    }
+
+    // File specifications with an `/EF` entry describe embedded files. They
+    // can appear as indirect objects or as values nested in other objects,
+    // like the `/EmbeddedFiles` name tree.
+    for object in document.objects.iter() {
+        for dict in parser::dicts(&object.value) {
+            let ef = match dict.get(b"EF") {
+                Some(parser::Object::Dict(ef)) => ef,
+                _ => continue,
+            };
+            let mut file = EmbeddedFile::new();
+            file.name = [&b"UF"[..], b"F"].iter().find_map(|key| {
+                match dict.get(key) {
+                    Some(parser::Object::String(s)) => {
+                        Some(parser::decode_text_string(s))
+                    }
+                    _ => None,
+                }
+            });
+            file.stream_index = [&b"UF"[..], b"F"]
+                .iter()
+                .find_map(|key| match ef.get(key) {
+                    Some(parser::Object::Ref(number, _)) => {
+                        document.stream_index(*number)
+                    }
+                    _ => None,
+                })
+                .map(|index| index as i64);
+            pdf.embedded_files.push(file);
+        }
+    }
+
+    pdf
+}
+
+/// Returns the number of occurrences of a name in the document's objects.
+/// The leading slash is optional, both `/JavaScript` and `JavaScript` are
+/// accepted.
+#[module_export]
+fn name_count(ctx: &ScanContext, name: RuntimeString) -> Option<i64> {
+    let pdf = ctx.module_output::<Pdf>()?;
+    let name = name.as_bstr(ctx);
+    let name = name.strip_prefix(b"/").unwrap_or(name);
+
+    Some(
+        pdf.names
+            .iter()
+            .find(|n| n.name().as_bytes() == name)
+            .map(|n| n.count())
+            .unwrap_or(0),
+    )
+}
+
+/// Returns the content of the stream at the given index in `streams`, after
+/// applying its filters. The result is undefined if the index is out of
+/// bounds, or the stream uses a filter that is not supported.
+#[module_export]
+fn decoded_stream(ctx: &mut ScanContext, index: i64) -> Option<RuntimeString> {
+    let pdf = ctx.module_output::<Pdf>()?;
+    let stream = pdf.streams.get(usize::try_from(index).ok()?)?;
+
+    let offset = usize::try_from(stream.offset()).ok()?;
+    let length = usize::try_from(stream.length()).ok()?;
+    let data = ctx.scanned_data().get(offset..offset.checked_add(length)?)?;
+
+    let decoded =
+        filters::decode(data, &stream.filters, MAX_DECODED_STREAM_SIZE)?;
+
+    Some(RuntimeString::from_bytes(ctx, decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    /// Compresses data in zlib format, using stored (uncompressed) blocks.
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let mut zlib = vec![0x78, 0x01, 0x01];
+        zlib.extend((data.len() as u16).to_le_bytes());
+        zlib.extend((!(data.len() as u16)).to_le_bytes());
+        zlib.extend(data);
+        // The Adler-32 checksum is not verified.
+        zlib.extend([0; 4]);
+        zlib
+    }
+
+    /// Returns an indirect object with a stream.
+    fn stream_object(number: u32, dict: &str, data: &[u8]) -> Vec<u8> {
+        [
+            format!(
+                "{} 0 obj\n<< {} /Length {} >>\nstream\r\n",
+                number,
+                dict,
+                data.len()
+            )
+            .as_bytes(),
+            data,
+            b"\r\nendstream\nendobj\n",
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn end2end() {
+        let object_stream = b"5 0 \
+            << /Type /Filespec /F (evil.exe) /EF << /F 6 0 R >> \
+               /AA << /O << /S /Launch >> >> >>";
+
+        let pdf = [
+            &b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n"[..],
+            b"1 0 obj\n<< /Type /Catalog /OpenAction 2 0 R >>\nendobj\n",
+            b"2 0 obj\n<< /S /J#61vaScript /JS 3 0 R >>\nendobj\n",
+            // The length is an indirect reference, the end of the stream
+            // is found by looking for `endstream`.
+            b"3 0 obj\n<< /Filter /AHx /Length 7 0 R >>\nstream\n",
+            b"6170702e616c657274283129>\nendstream\nendobj\n",
+            &stream_object(
+                4,
+                "/Type /ObjStm /N 1 /First 4 /Filter [/FlateDecode]",
+                &zlib_stored(object_stream),
+            ),
+            &stream_object(6, "/Type /EmbeddedFile", b"hello"),
+            b"trailer\n<< /Root 1 0 R >>\n%%EOF\n",
+        ]
+        .concat();
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "pdf"
+                rule rule_1 {
+                  condition:
+                    pdf.is_pdf and
+                    pdf.version == "1.7" and
+                    pdf.num_objects == 6 and
+                    pdf.num_streams == 3 and
+                    pdf.num_eof_markers == 1
+                }
+                rule rule_2 {
+                  condition:
+                    pdf.name_count("JavaScript") == 1 and
+                    pdf.name_count("/OpenAction") == 1 and
+                    pdf.name_count("Launch") == 1 and
+                    pdf.name_count("AcroForm") == 0
+                }
+                rule rule_3 {
+                  condition:
+                    pdf.streams[0].filters[0] == "ASCIIHexDecode" and
+                    pdf.decoded_stream(0) == "app.alert(1)" and
+                    pdf.streams[1].type == "ObjStm" and
+                    pdf.decoded_stream(1) contains "/Filespec" and
+                    not defined pdf.decoded_stream(3)
+                }
+                rule rule_4 {
+                  condition:
+                    pdf.embedded_files[0].name == "evil.exe" and
+                    pdf.embedded_files[0].stream_index == 2 and
+                    pdf.decoded_stream(pdf.embedded_files[0].stream_index)
+                      == "hello"
+                }
+                rule rule_5 { condition: not pdf.is_pdf }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&pdf),
+            ["rule_1", "rule_2", "rule_3", "rule_4"]
+        );
+
+        assert_eq!(matching_rules(b"not a pdf"), ["rule_5"]);
+    }
+}
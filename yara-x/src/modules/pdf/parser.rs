@@ -0,0 +1,658 @@
+/*! Lenient parser for PDF documents.
+
+Malicious documents are often malformed on purpose, with broken cross
+reference tables, wrong stream lengths or objects that overlap. For this
+reason the parser doesn't rely on the cross reference table, instead it
+scans the whole file looking for indirect objects (`N G obj ... endobj`),
+the same way PDF readers do when repairing a document. Objects stored in
+object streams (`/Type /ObjStm`) are parsed as well.
+*/
+
+use std::collections::BTreeMap;
+
+use bstr::ByteSlice;
+
+use crate::modules::pdf::filters;
+
+/// Maximum nesting level for arrays and dictionaries. Deeper values are not
+/// parsed.
+const MAX_DEPTH: usize = 64;
+
+/// Maximum size of decoded object streams.
+const MAX_OBJECT_STREAM_SIZE: usize = 16 * 1024 * 1024;
+
+/// A PDF object.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Object {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Real(f64),
+    Name(Vec<u8>),
+    String(Vec<u8>),
+    Array(Vec<Object>),
+    Dict(Dict),
+    Ref(u32, u16),
+}
+
+/// A PDF dictionary, as a list of key-value pairs in the order they appear
+/// in the document.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Dict(pub Vec<(Vec<u8>, Object)>);
+
+impl Dict {
+    /// Returns the value associated to the given key. Keys are names
+    /// without the leading slash.
+    pub fn get(&self, key: &[u8]) -> Option<&Object> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Returns the value of a key whose value is a name.
+    pub fn get_name(&self, key: &[u8]) -> Option<&[u8]> {
+        match self.get(key)? {
+            Object::Name(name) => Some(name.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a key whose value is an integer.
+    pub fn get_int(&self, key: &[u8]) -> Option<i64> {
+        match self.get(key)? {
+            Object::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+/// An indirect object.
+pub(crate) struct IndirectObject {
+    pub value: Object,
+}
+
+/// A stream, which is an indirect object whose dictionary is followed by
+/// arbitrary data.
+pub(crate) struct Stream {
+    pub object_number: u32,
+    pub generation: u16,
+    pub dict: Dict,
+    /// Offset of the stream's data within the file.
+    pub offset: usize,
+    /// Length of the stream's data, before applying any filter.
+    pub length: usize,
+}
+
+impl Stream {
+    /// Returns the names of the filters applied to the stream, in the order
+    /// they must be applied for decoding it. Abbreviated names are
+    /// replaced with the full ones.
+    pub fn filters(&self) -> Vec<String> {
+        let names = match self.dict.get(b"Filter") {
+            Some(Object::Name(name)) => vec![name.as_slice()],
+            Some(Object::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Object::Name(name) => Some(name.as_slice()),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        names
+            .into_iter()
+            .map(|name| filters::full_name(name).to_str_lossy().into_owned())
+            .collect()
+    }
+}
+
+/// A parsed PDF document.
+pub(crate) struct Document {
+    /// Version declared in the header (e.g: `1.7`).
+    pub version: String,
+    /// Indirect objects, in the order they appear in the file. Objects
+    /// from object streams appear after the remaining ones.
+    pub objects: Vec<IndirectObject>,
+    pub streams: Vec<Stream>,
+    /// Number of occurrences of each name in the document's objects.
+    pub names: BTreeMap<Vec<u8>, i64>,
+    /// Number of `%%EOF` markers, which is usually the number of revisions
+    /// of the document.
+    pub num_eof_markers: usize,
+}
+
+impl Document {
+    /// Returns the index of the stream with the given object number. If the
+    /// document has multiple streams with the same number, the last one
+    /// takes precedence, as it happens with incremental updates.
+    pub fn stream_index(&self, object_number: u32) -> Option<usize> {
+        self.streams
+            .iter()
+            .rposition(|stream| stream.object_number == object_number)
+    }
+}
+
+/// Parses a PDF document. Returns `None` if the data doesn't have a PDF
+/// header in its first 1024 bytes.
+pub(crate) fn parse(data: &[u8]) -> Option<Document> {
+    let header = data[..data.len().min(1024)].find(b"%PDF-")?;
+
+    let version = data[header + 5..]
+        .iter()
+        .take_while(|b| b.is_ascii_digit() || **b == b'.')
+        .map(|b| *b as char)
+        .collect();
+
+    let mut parser =
+        Parser { lexer: Lexer { data, pos: header }, names: BTreeMap::new() };
+
+    let mut objects = Vec::new();
+    let mut streams = Vec::new();
+
+    // The last two tokens, if they were integers. When the `obj` keyword is
+    // found, they are the object and generation numbers.
+    let mut prev: (Option<i64>, Option<i64>) = (None, None);
+
+    while let Some(token) = parser.lexer.next() {
+        match token {
+            Token::Keyword(b"obj") => {
+                if let (Some(number), Some(generation)) = prev {
+                    if let (Ok(number), Ok(generation)) =
+                        (u32::try_from(number), u16::try_from(generation))
+                    {
+                        let (object, stream) =
+                            parser.indirect_object(number, generation);
+                        objects.push(object);
+                        streams.extend(stream);
+                    }
+                }
+                prev = (None, None);
+            }
+            Token::Int(i) => prev = (prev.1, Some(i)),
+            // Dictionaries outside of objects, like the trailer.
+            Token::DictStart => {
+                parser.value(Token::DictStart, 0);
+                prev = (None, None);
+            }
+            _ => prev = (None, None),
+        }
+    }
+
+    // Parse the objects contained in object streams.
+    for stream in streams.iter() {
+        if stream.dict.get_name(b"Type") != Some(b"ObjStm") {
+            continue;
+        }
+        let decoded = match filters::decode(
+            &data[stream.offset..stream.offset + stream.length],
+            &stream.filters(),
+            MAX_OBJECT_STREAM_SIZE,
+        ) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        objects.extend(parser.object_stream(&stream.dict, &decoded));
+    }
+
+    Some(Document {
+        version,
+        objects,
+        streams,
+        names: parser.names,
+        num_eof_markers: data.find_iter(b"%%EOF").count(),
+    })
+}
+
+/// Tokens produced by [`Lexer`].
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Int(i64),
+    Real(f64),
+    Name(Vec<u8>),
+    String(Vec<u8>),
+    DictStart,
+    DictEnd,
+    ArrayStart,
+    ArrayEnd,
+    /// Any other sequence of regular characters, like `obj`, `R` or
+    /// `true`.
+    Keyword(&'a [u8]),
+}
+
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(b: u8) -> bool {
+    !is_whitespace(b) && !is_delimiter(b)
+}
+
+impl<'a> Lexer<'a> {
+    fn peek_byte(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        loop {
+            let b = self.peek_byte()?;
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                // Comments extend up to the end of the line.
+                while !matches!(self.peek_byte()?, b'\r' | b'\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+
+        let b = self.peek_byte()?;
+        self.pos += 1;
+
+        let token = match b {
+            b'/' => Token::Name(self.name()),
+            b'(' => Token::String(self.literal_string()),
+            b'<' if self.peek_byte() == Some(b'<') => {
+                self.pos += 1;
+                Token::DictStart
+            }
+            b'<' => Token::String(self.hex_string()),
+            b'>' if self.peek_byte() == Some(b'>') => {
+                self.pos += 1;
+                Token::DictEnd
+            }
+            b'[' => Token::ArrayStart,
+            b']' => Token::ArrayEnd,
+            b')' | b'>' | b'{' | b'}' => {
+                Token::Keyword(&self.data[self.pos - 1..self.pos])
+            }
+            _ => {
+                let start = self.pos - 1;
+                while self.peek_byte().map_or(false, is_regular) {
+                    self.pos += 1;
+                }
+                let word = &self.data[start..self.pos];
+                number(word).unwrap_or(Token::Keyword(word))
+            }
+        };
+
+        Some(token)
+    }
+
+    /// Reads a name, decoding `#xx` escape sequences.
+    fn name(&mut self) -> Vec<u8> {
+        let mut name = Vec::new();
+        while let Some(b) = self.peek_byte().filter(|b| is_regular(*b)) {
+            self.pos += 1;
+            let escaped = self
+                .data
+                .get(self.pos..self.pos + 2)
+                .filter(|_| b == b'#')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(escaped) => {
+                    name.push(escaped);
+                    self.pos += 2;
+                }
+                None => name.push(b),
+            }
+        }
+        name
+    }
+
+    /// Reads a literal string, the opening parenthesis was already read.
+    fn literal_string(&mut self) -> Vec<u8> {
+        let mut s = Vec::new();
+        let mut depth = 0;
+        while let Some(b) = self.peek_byte() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    s.push(b);
+                }
+                b')' if depth == 0 => break,
+                b')' => {
+                    depth -= 1;
+                    s.push(b);
+                }
+                b'\\' => {
+                    let escaped = match self.peek_byte() {
+                        Some(escaped) => escaped,
+                        None => break,
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => s.push(b'\n'),
+                        b'r' => s.push(b'\r'),
+                        b't' => s.push(b'\t'),
+                        b'b' => s.push(b'\x08'),
+                        b'f' => s.push(b'\x0c'),
+                        // A backslash at the end of a line continues the
+                        // string in the next line.
+                        b'\r' => {
+                            if self.peek_byte() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek_byte() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        value = value * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            s.push(value as u8);
+                        }
+                        _ => s.push(escaped),
+                    }
+                }
+                _ => s.push(b),
+            }
+        }
+        s
+    }
+
+    /// Reads a hex string, the opening angle bracket was already read.
+    fn hex_string(&mut self) -> Vec<u8> {
+        let start = self.pos;
+        let end = self.data[start..]
+            .find_byte(b'>')
+            .map(|len| start + len)
+            .unwrap_or(self.data.len());
+        self.pos = (end + 1).min(self.data.len());
+        filters::ascii_hex_decode(&self.data[start..end])
+    }
+}
+
+/// Returns the numeric token represented by `word`, if any.
+fn number(word: &[u8]) -> Option<Token<'static>> {
+    let first = *word.first()?;
+    if !(first.is_ascii_digit() || matches!(first, b'+' | b'-' | b'.')) {
+        return None;
+    }
+    let s = std::str::from_utf8(word).ok()?;
+    if let Ok(i) = s.parse::<i64>() {
+        Some(Token::Int(i))
+    } else {
+        s.parse::<f64>().ok().map(Token::Real)
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    names: BTreeMap<Vec<u8>, i64>,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses an indirect object, after its `obj` keyword. If the object is
+    /// a stream, the stream is returned too.
+    fn indirect_object(
+        &mut self,
+        number: u32,
+        generation: u16,
+    ) -> (IndirectObject, Option<Stream>) {
+        let value = match self.lexer.next() {
+            Some(token) => self.value(token, 0).unwrap_or(Object::Null),
+            None => Object::Null,
+        };
+
+        let pos = self.lexer.pos;
+
+        match (value, self.lexer.next()) {
+            (Object::Dict(dict), Some(Token::Keyword(b"stream"))) => {
+                let stream = self.stream(number, generation, dict);
+                (IndirectObject { value: Object::Null }, Some(stream))
+            }
+            (value, _) => {
+                self.lexer.pos = pos;
+                (IndirectObject { value }, None)
+            }
+        }
+    }
+
+    /// Reads the data of a stream, after its `stream` keyword.
+    fn stream(&mut self, number: u32, generation: u16, dict: Dict) -> Stream {
+        let data = self.lexer.data;
+
+        // The keyword is followed by CRLF or LF, but some writers use a
+        // single CR.
+        let mut start = self.lexer.pos;
+        if data[start..].starts_with(b"\r\n") {
+            start += 2;
+        } else if matches!(data.get(start), Some(b'\n' | b'\r')) {
+            start += 1;
+        }
+
+        let ends_at = |end: usize| {
+            data.get(end..).map_or(false, |rest| {
+                rest.trim_start_with(|c| c.is_ascii_whitespace())
+                    .starts_with(b"endstream")
+            })
+        };
+
+        // Use the length declared in the dictionary if it's correct. When
+        // it's not, or is an indirect reference, look for the `endstream`
+        // keyword instead.
+        let end = match dict
+            .get_int(b"Length")
+            .and_then(|len| usize::try_from(len).ok())
+            .and_then(|len| start.checked_add(len))
+        {
+            Some(end) if ends_at(end) => end,
+            _ => match data[start..].find(b"endstream") {
+                Some(len) => {
+                    let mut end = start + len;
+                    if data[..end].ends_with(b"\r\n") {
+                        end -= 2;
+                    } else if data[..end].ends_with(b"\n")
+                        || data[..end].ends_with(b"\r")
+                    {
+                        end -= 1;
+                    }
+                    end.max(start)
+                }
+                None => data.len(),
+            },
+        };
+
+        // Skip the `endstream` keyword, if present.
+        self.lexer.pos = end;
+        if self.lexer.next() != Some(Token::Keyword(b"endstream")) {
+            self.lexer.pos = end;
+        }
+
+        Stream {
+            object_number: number,
+            generation,
+            dict,
+            offset: start,
+            length: end - start,
+        }
+    }
+
+    /// Parses the objects in a decoded object stream.
+    ///
+    /// The stream starts with `N` pairs of integers, the object number and
+    /// the offset of each object relative to the `/First` offset.
+    fn object_stream(
+        &mut self,
+        dict: &Dict,
+        decoded: &[u8],
+    ) -> Vec<IndirectObject> {
+        let n = dict.get_int(b"N").unwrap_or(0);
+        let first = dict
+            .get_int(b"First")
+            .and_then(|first| usize::try_from(first).ok())
+            .unwrap_or(0);
+
+        let mut lexer = Lexer { data: decoded, pos: 0 };
+        let mut offsets = Vec::new();
+
+        for _ in 0..n {
+            match (lexer.next(), lexer.next()) {
+                (Some(Token::Int(_)), Some(Token::Int(offset))) => {
+                    if let Ok(offset) = usize::try_from(offset) {
+                        offsets.push(offset);
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        // The objects are parsed with another parser that reads the decoded
+        // stream, but shares the name counts with this one.
+        let mut parser =
+            Parser { lexer, names: std::mem::take(&mut self.names) };
+
+        let mut objects = Vec::new();
+
+        for offset in offsets {
+            parser.lexer.pos = first.saturating_add(offset);
+            let value = match parser.lexer.next() {
+                Some(token) => parser.value(token, 0).unwrap_or(Object::Null),
+                None => Object::Null,
+            };
+            objects.push(IndirectObject { value });
+        }
+
+        self.names = parser.names;
+
+        objects
+    }
+
+    /// Parses a value starting with the given token. Returns `None` if the
+    /// token can't start a value.
+    fn value(&mut self, token: Token<'_>, depth: usize) -> Option<Object> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match token {
+            Token::Int(i) => {
+                // Integers followed by another integer and the `R` keyword
+                // are references to indirect objects.
+                let pos = self.lexer.pos;
+                if let (
+                    Some(Token::Int(generation)),
+                    Some(Token::Keyword(b"R")),
+                ) = (self.lexer.next(), self.lexer.next())
+                {
+                    if let (Ok(number), Ok(generation)) =
+                        (u32::try_from(i), u16::try_from(generation))
+                    {
+                        return Some(Object::Ref(number, generation));
+                    }
+                }
+                self.lexer.pos = pos;
+                Some(Object::Int(i))
+            }
+            Token::Real(r) => Some(Object::Real(r)),
+            Token::Name(name) => {
+                *self.names.entry(name.clone()).or_default() += 1;
+                Some(Object::Name(name))
+            }
+            Token::String(s) => Some(Object::String(s)),
+            Token::Keyword(b"true") => Some(Object::Bool(true)),
+            Token::Keyword(b"false") => Some(Object::Bool(false)),
+            Token::Keyword(b"null") => Some(Object::Null),
+            Token::ArrayStart => {
+                let mut items = Vec::new();
+                while let Some(token) = self.next_in_container() {
+                    match token {
+                        Token::ArrayEnd => break,
+                        token => items.extend(self.value(token, depth + 1)),
+                    }
+                }
+                Some(Object::Array(items))
+            }
+            Token::DictStart => {
+                let mut entries = Vec::new();
+                while let Some(token) = self.next_in_container() {
+                    let key = match token {
+                        Token::DictEnd => break,
+                        Token::Name(key) => key,
+                        // Ignore anything that is not a key.
+                        _ => continue,
+                    };
+                    *self.names.entry(key.clone()).or_default() += 1;
+                    let value = match self.next_in_container() {
+                        Some(Token::DictEnd) | None => {
+                            entries.push((key, Object::Null));
+                            break;
+                        }
+                        Some(token) => self
+                            .value(token, depth + 1)
+                            .unwrap_or(Object::Null),
+                    };
+                    entries.push((key, value));
+                }
+                Some(Object::Dict(Dict(entries)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the next token inside an array or dictionary. Returns `None`
+    /// when the end of the object is reached, which means that the array
+    /// or dictionary is not properly closed.
+    fn next_in_container(&mut self) -> Option<Token<'a>> {
+        let pos = self.lexer.pos;
+        match self.lexer.next()? {
+            Token::Keyword(b"endobj" | b"stream" | b"obj") => {
+                self.lexer.pos = pos;
+                None
+            }
+            token => Some(token),
+        }
+    }
+}
+
+/// Decodes a text string, which is encoded in UTF-16BE when it starts with
+/// a byte order mark, or in PDFDocEncoding otherwise. PDFDocEncoding is
+/// approximated with Latin-1.
+pub(crate) fn decode_text_string(s: &[u8]) -> String {
+    match s.strip_prefix(b"\xfe\xff") {
+        Some(utf16) => String::from_utf16_lossy(
+            &utf16
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        None => s.iter().map(|b| *b as char).collect(),
+    }
+}
+
+/// Returns the dictionaries in an object, including the nested ones.
+pub(crate) fn dicts(object: &Object) -> Vec<&Dict> {
+    let mut result = Vec::new();
+    let mut pending = vec![object];
+    while let Some(object) = pending.pop() {
+        match object {
+            Object::Dict(dict) => {
+                result.push(dict);
+                pending.extend(dict.0.iter().map(|(_, v)| v));
+            }
+            Object::Array(items) => pending.extend(items.iter()),
+            _ => {}
+        }
+    }
+    result
+}
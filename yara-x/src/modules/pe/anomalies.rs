@@ -0,0 +1,105 @@
+/*! Detection of anomalies in the PE headers.
+
+The Windows loader accepts files with headers that no linker produces,
+like headers that overlap each other, and packers and hand-crafted
+malware often rely on that. Each anomaly is a flag in the value returned
+by [`header_anomalies`], which are the same as in the `HeaderAnomaly`
+enum in the module's protobuf.
+*/
+
+use super::DOS_HEADER_SIZE;
+use crate::modules::utils::pe::Pe;
+
+/// The PE header starts inside the DOS header.
+const PE_HEADER_IN_DOS_HEADER: u32 = 0x1;
+/// The section table extends beyond the size of the headers, or into the
+/// raw data of a section.
+const SECTION_TABLE_OVERLAPS_DATA: u32 = 0x2;
+/// The raw data of two sections overlap.
+const OVERLAPPING_SECTIONS: u32 = 0x4;
+const ZERO_SIZE_OF_IMAGE: u32 = 0x8;
+const ZERO_SIZE_OF_HEADERS: u32 = 0x10;
+const ZERO_SECTIONS: u32 = 0x20;
+/// The file alignment is not a power of two between 512 and 64K, and it's
+/// not equal to the section alignment.
+const NONSTANDARD_FILE_ALIGNMENT: u32 = 0x40;
+/// The section alignment is not a power of two, or it's lower than the
+/// file alignment.
+const NONSTANDARD_SECTION_ALIGNMENT: u32 = 0x80;
+/// The size of the optional header is not the one used by linkers, which
+/// is 224 bytes for PE32 files and 240 for PE32+ files.
+const NONSTANDARD_OPTIONAL_HEADER_SIZE: u32 = 0x100;
+
+/// Returns the anomalies found in the headers, as a combination of the
+/// flags defined in this module.
+pub(super) fn header_anomalies(pe: &Pe) -> u32 {
+    let mut anomalies = 0;
+
+    if pe.pe_offset < DOS_HEADER_SIZE {
+        anomalies |= PE_HEADER_IN_DOS_HEADER;
+    }
+
+    let section_table_end = pe.optional_header_offset
+        + pe.optional_header_size as usize
+        + pe.number_of_sections as usize * 40;
+
+    let first_raw_data = pe
+        .sections
+        .iter()
+        .filter(|s| s.raw_data_size > 0)
+        .map(|s| s.raw_data_offset as usize)
+        .min();
+
+    if section_table_end > pe.size_of_headers as usize
+        || first_raw_data.map_or(false, |offset| section_table_end > offset)
+    {
+        anomalies |= SECTION_TABLE_OVERLAPS_DATA;
+    }
+
+    let mut ranges: Vec<(u64, u64)> = pe
+        .sections
+        .iter()
+        .filter(|s| s.raw_data_size > 0)
+        .map(|s| {
+            let start = s.raw_data_offset as u64;
+            (start, start + s.raw_data_size as u64)
+        })
+        .collect();
+
+    ranges.sort_unstable();
+
+    if ranges.windows(2).any(|pair| pair[1].0 < pair[0].1) {
+        anomalies |= OVERLAPPING_SECTIONS;
+    }
+
+    if pe.size_of_image == 0 {
+        anomalies |= ZERO_SIZE_OF_IMAGE;
+    }
+
+    if pe.size_of_headers == 0 {
+        anomalies |= ZERO_SIZE_OF_HEADERS;
+    }
+
+    if pe.number_of_sections == 0 {
+        anomalies |= ZERO_SECTIONS;
+    }
+
+    if !(pe.file_alignment.is_power_of_two()
+        && (512..=65536).contains(&pe.file_alignment)
+        || pe.file_alignment == pe.section_alignment)
+    {
+        anomalies |= NONSTANDARD_FILE_ALIGNMENT;
+    }
+
+    if !pe.section_alignment.is_power_of_two()
+        || pe.section_alignment < pe.file_alignment
+    {
+        anomalies |= NONSTANDARD_SECTION_ALIGNMENT;
+    }
+
+    if pe.optional_header_size != if pe.is_64bit { 240 } else { 224 } {
+        anomalies |= NONSTANDARD_OPTIONAL_HEADER_SIZE;
+    }
+
+    anomalies
+}
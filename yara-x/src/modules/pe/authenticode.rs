@@ -0,0 +1,939 @@
+/*! Parser and verifier for Authenticode signatures.
+
+The signatures are in the certificate table, pointed to by the security
+directory. Each entry in the table is a PKCS#7 `SignedData` structure,
+whose content is a `SpcIndirectDataContent` with the digest of the file.
+The digest is signed, together with other attributes, by the signer's
+certificate, and the signature can be countersigned by a timestamping
+authority, which certifies the time at which the file was signed.
+
+A signature is verified when:
+
+* The digest in the signature matches the digest of the file.
+* The signer's signature of the digest is valid.
+* There's a chain of certificates that goes from the signer's certificate
+  to one of the trusted root certificates, where each certificate is
+  signed by the next one.
+* If the signature has a valid countersignature, all the certificates in
+  the chain were valid at the time of the countersignature. Without a
+  countersignature there's no reference time, and the validity periods
+  are not checked.
+
+The trusted root certificates are provided by the user with
+`Scanner::set_module_data`, as a sequence of DER-encoded certificates.
+Without them, no signature is verified, but the rest of the information
+is still available.
+
+Only RSA signatures are verified.
+
+See: https://learn.microsoft.com/en-us/windows-hardware/drivers/install/authenticode
+*/
+
+use num::BigUint;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::modules::utils::der::{
+    algorithm_name, distinguished_name, oid_string, strip_leading_zero, time,
+    Tlv, BIT_STRING, INTEGER, OCTET_STRING, OID, SEQUENCE, SET,
+};
+use crate::modules::utils::digest::{Md5, Sha1};
+use crate::modules::utils::pe::{Pe, DIRECTORY_SECURITY};
+use crate::modules::utils::{u16_at, u32_at};
+
+/// Maximum number of signatures, including nested ones.
+const MAX_SIGNATURES: usize = 16;
+
+/// Maximum number of certificates in a chain.
+const MAX_CHAIN_LEN: usize = 16;
+
+/// Type of the entries in the certificate table that have a PKCS#7
+/// `SignedData` structure.
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 2;
+
+const SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const SPC_INDIRECT_DATA: &str = "1.3.6.1.4.1.311.2.1.4";
+const SPC_NESTED_SIGNATURE: &str = "1.3.6.1.4.1.311.2.4.1";
+const MESSAGE_DIGEST: &str = "1.2.840.113549.1.9.4";
+const SIGNING_TIME: &str = "1.2.840.113549.1.9.5";
+const COUNTERSIGNATURE: &str = "1.2.840.113549.1.9.6";
+/// RFC 3161 timestamps, which Microsoft stores as an unauthenticated
+/// attribute with its own OID.
+const MS_COUNTERSIGNATURE: &str = "1.3.6.1.4.1.311.3.3.1";
+
+/// A hash algorithm used in signatures.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Returns the algorithm with the given OID, which can be the OID of
+    /// the hash algorithm itself, or the OID of RSA with the algorithm.
+    fn from_oid(oid: &str) -> Option<Self> {
+        match oid {
+            "1.2.840.113549.2.5" | "1.2.840.113549.1.1.4" => Some(Self::Md5),
+            "1.3.14.3.2.26" | "1.2.840.113549.1.1.5" | "1.3.14.3.2.29" => {
+                Some(Self::Sha1)
+            }
+            "2.16.840.1.101.3.4.2.1" | "1.2.840.113549.1.1.11" => {
+                Some(Self::Sha256)
+            }
+            "2.16.840.1.101.3.4.2.2" | "1.2.840.113549.1.1.12" => {
+                Some(Self::Sha384)
+            }
+            "2.16.840.1.101.3.4.2.3" | "1.2.840.113549.1.1.13" => {
+                Some(Self::Sha512)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    /// Returns the hash of the concatenation of `parts`.
+    fn hash(&self, parts: &[&[u8]]) -> Vec<u8> {
+        fn sha2<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            Self::Md5 => {
+                let mut hasher = Md5::new();
+                parts.iter().for_each(|part| hasher.update(part));
+                hasher.finalize().to_vec()
+            }
+            Self::Sha1 => {
+                let mut hasher = Sha1::new();
+                parts.iter().for_each(|part| hasher.update(part));
+                hasher.finalize().to_vec()
+            }
+            Self::Sha256 => sha2::<Sha256>(parts),
+            Self::Sha384 => sha2::<Sha384>(parts),
+            Self::Sha512 => sha2::<Sha512>(parts),
+        }
+    }
+}
+
+/// A certificate.
+#[derive(Clone)]
+pub(super) struct Certificate<'a> {
+    /// The whole certificate, in DER format.
+    pub raw: &'a [u8],
+    pub version: u32,
+    /// The serial number, as a big-endian integer.
+    pub serial: &'a [u8],
+    pub issuer: String,
+    pub subject: String,
+    pub not_before: Option<i64>,
+    pub not_after: Option<i64>,
+    /// Name and OID of the algorithm used by the issuer for signing the
+    /// certificate.
+    pub algorithm: String,
+    pub algorithm_oid: String,
+    /// The signed part of the certificate.
+    tbs: &'a [u8],
+    signature: &'a [u8],
+    issuer_raw: &'a [u8],
+    subject_raw: &'a [u8],
+    key: Option<RsaKey<'a>>,
+}
+
+/// An RSA public key.
+#[derive(Clone)]
+struct RsaKey<'a> {
+    modulus: &'a [u8],
+    exponent: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    /// Parses a certificate.
+    fn parse(cert: &Tlv<'a>) -> Option<Self> {
+        if cert.tag != SEQUENCE {
+            return None;
+        }
+
+        let mut parts = cert.children();
+        let tbs = parts.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let algorithm = parts.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let signature = parts.next().filter(|tlv| tlv.tag == BIT_STRING)?;
+
+        let mut fields = tbs.children().peekable();
+
+        // The version is optional, and explicitly tagged with [0]. The
+        // default version is v1, encoded as 0.
+        let version = match fields.next_if(|tlv| tlv.tag == 0xa0) {
+            Some(version) => {
+                let (version, _) = Tlv::parse(version.content)?;
+                *version.content.last()? as u32 + 1
+            }
+            None => 1,
+        };
+
+        let serial = fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+        fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let issuer = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let validity = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let subject = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let key = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+
+        let mut times = validity.children();
+
+        Some(Self {
+            raw: cert.raw,
+            version,
+            serial: strip_leading_zero(serial.content),
+            issuer: distinguished_name(&issuer),
+            subject: distinguished_name(&subject),
+            not_before: times.next().and_then(|t| time(&t)),
+            not_after: times.next().and_then(|t| time(&t)),
+            algorithm: algorithm_name(&algorithm),
+            algorithm_oid: algorithm_oid(&algorithm).unwrap_or_default(),
+            tbs: tbs.raw,
+            // The first byte of the bit string is the number of unused
+            // bits, which is always zero for signatures.
+            signature: signature.content.get(1..)?,
+            issuer_raw: issuer.raw,
+            subject_raw: subject.raw,
+            key: rsa_key(&key),
+        })
+    }
+
+    /// Returns true if the certificate is signed by `issuer`.
+    fn is_signed_by(&self, issuer: &Certificate) -> bool {
+        if self.issuer_raw != issuer.subject_raw {
+            return false;
+        }
+        let (algorithm, key) = match (
+            HashAlgorithm::from_oid(&self.algorithm_oid),
+            &issuer.key,
+        ) {
+            (Some(algorithm), Some(key)) => (algorithm, key),
+            _ => return false,
+        };
+        rsa_verify(
+            key,
+            algorithm,
+            &algorithm.hash(&[self.tbs]),
+            self.signature,
+        )
+    }
+
+    /// Returns true if the certificate was valid at the given time.
+    fn is_valid_at(&self, time: i64) -> bool {
+        self.not_before.map_or(false, |not_before| not_before <= time)
+            && self.not_after.map_or(false, |not_after| time <= not_after)
+    }
+}
+
+/// An Authenticode signature.
+pub(super) struct Signature<'a> {
+    /// Certificates included in the signature.
+    pub certificates: Vec<Certificate<'a>>,
+    /// Chain of certificates that starts with the signer's certificate.
+    /// Empty if the signer's certificate is not in the signature.
+    pub chain: Vec<Certificate<'a>>,
+    /// Algorithm used for computing the digest of the file.
+    pub digest_algorithm: Option<HashAlgorithm>,
+    /// Digest of the file, as it appears in the signature.
+    pub digest: &'a [u8],
+    /// Digest of the file, computed with the same algorithm.
+    pub file_digest: Vec<u8>,
+    /// True if `digest` and `file_digest` are equal.
+    pub digest_matches: bool,
+    /// True if the signer's signature is valid.
+    pub signature_valid: bool,
+    /// True if the chain ends with a trusted root certificate.
+    pub trusted: bool,
+    pub verified: bool,
+    pub countersignatures: Vec<Countersignature<'a>>,
+}
+
+/// A countersignature, which can be a PKCS#9 countersignature or an
+/// RFC 3161 timestamp.
+pub(super) struct Countersignature<'a> {
+    pub sign_time: Option<i64>,
+    pub digest_algorithm: Option<HashAlgorithm>,
+    /// Digest of the signer's signature, as it appears in the
+    /// countersignature.
+    pub digest: &'a [u8],
+    /// Chain of certificates that starts with the countersigner's
+    /// certificate.
+    pub chain: Vec<Certificate<'a>>,
+    pub verified: bool,
+}
+
+/// Parses the trusted root certificates provided by the user, which are
+/// DER-encoded certificates, one after the other.
+pub(super) fn trust_store(mut data: &[u8]) -> Vec<Certificate<'_>> {
+    let mut certificates = Vec::new();
+    while let Some((tlv, rest)) = Tlv::parse(data) {
+        certificates.extend(Certificate::parse(&tlv));
+        data = rest;
+    }
+    certificates
+}
+
+/// Parses and verifies the signatures in the certificate table. Nested
+/// signatures come right after the signature that contains them.
+pub(super) fn parse<'a>(
+    pe: &Pe<'a>,
+    trust_store: &[Certificate<'a>],
+) -> Vec<Signature<'a>> {
+    let mut signatures = Vec::new();
+
+    // The security directory has the file offset of the certificate table,
+    // instead of an RVA.
+    let table =
+        match pe.directory(DIRECTORY_SECURITY).and_then(|(offset, size)| {
+            let start = offset as usize;
+            let end = start.saturating_add(size as usize).min(pe.data.len());
+            pe.data.get(start..end)
+        }) {
+            Some(table) => table,
+            None => return signatures,
+        };
+
+    let mut verifier = Verifier { pe, trust_store, file_digests: Vec::new() };
+
+    // Each entry starts with its length, including the 8-byte header, a
+    // revision number and a type. Entries are aligned to 8 bytes.
+    let mut offset = 0;
+
+    while let (Some(len), Some(type_)) =
+        (u32_at(table, offset), u16_at(table, offset + 6))
+    {
+        let len = len as usize;
+        if len < 8 {
+            break;
+        }
+        if type_ == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            if let Some(content) =
+                table.get(offset + 8..offset.saturating_add(len))
+            {
+                verifier.signatures(content, &mut signatures);
+            }
+        }
+        offset = offset.saturating_add((len + 7) & !7);
+    }
+
+    signatures
+}
+
+/// Verifies the signatures in a file, caching the digests of the file.
+struct Verifier<'a, 'b> {
+    pe: &'b Pe<'a>,
+    trust_store: &'b [Certificate<'a>],
+    file_digests: Vec<(HashAlgorithm, Vec<u8>)>,
+}
+
+impl<'a> Verifier<'a, '_> {
+    /// Parses the PKCS#7 `ContentInfo` at the start of `data`, adding the
+    /// signature it contains, and its nested signatures, to `signatures`.
+    fn signatures(
+        &mut self,
+        data: &'a [u8],
+        signatures: &mut Vec<Signature<'a>>,
+    ) {
+        if signatures.len() >= MAX_SIGNATURES {
+            return;
+        }
+
+        let signed_data = match SignedData::parse(data) {
+            Some(signed_data) => signed_data,
+            None => return,
+        };
+
+        let nested: Vec<&'a [u8]> = signed_data
+            .signer
+            .as_ref()
+            .map(|signer| {
+                signer
+                    .unauthenticated_attributes()
+                    .filter(|(oid, _)| oid == SPC_NESTED_SIGNATURE)
+                    .map(|(_, value)| value.raw)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if let Some(signature) = self.signature(signed_data) {
+            signatures.push(signature);
+        }
+
+        for data in nested {
+            self.signatures(data, signatures);
+        }
+    }
+
+    fn signature(
+        &mut self,
+        signed_data: SignedData<'a>,
+    ) -> Option<Signature<'a>> {
+        if signed_data.content_type != SPC_INDIRECT_DATA {
+            return None;
+        }
+
+        // SpcIndirectDataContent ::= SEQUENCE {
+        //   data SpcAttributeTypeAndOptionalValue,
+        //   messageDigest DigestInfo }
+        let content = signed_data.content?;
+        let (digest_algorithm, digest) =
+            digest_info(&content.children().nth(1)?)?;
+
+        let file_digest = match digest_algorithm {
+            Some(algorithm) => self.file_digest(algorithm),
+            None => Vec::new(),
+        };
+
+        let digest_matches =
+            digest_algorithm.is_some() && file_digest == digest;
+
+        let signer = signed_data.signer.as_ref();
+
+        let chain = signer
+            .and_then(|signer| signer.certificate(&signed_data.certificates))
+            .map(|cert| self.chain(cert, &signed_data.certificates))
+            .unwrap_or_default();
+
+        let signature_valid = match (signer, chain.first()) {
+            (Some(signer), Some(cert)) => signer.verify(content.content, cert),
+            _ => false,
+        };
+
+        let countersignatures: Vec<Countersignature> = signer
+            .map(|signer| self.countersignatures(signer, &signed_data))
+            .unwrap_or_default();
+
+        let trusted = self.is_trusted(&chain);
+
+        // The validity periods of the certificates are checked against the
+        // time of the first valid countersignature, if any.
+        let time_valid = match countersignatures
+            .iter()
+            .filter(|countersignature| countersignature.verified)
+            .find_map(|countersignature| countersignature.sign_time)
+        {
+            Some(time) => chain.iter().all(|cert| cert.is_valid_at(time)),
+            None => true,
+        };
+
+        Some(Signature {
+            verified: digest_matches
+                && signature_valid
+                && trusted
+                && time_valid,
+            certificates: signed_data.certificates,
+            chain,
+            digest_algorithm,
+            digest,
+            file_digest,
+            digest_matches,
+            signature_valid,
+            trusted,
+            countersignatures,
+        })
+    }
+
+    /// Returns the countersignatures of a signature.
+    fn countersignatures(
+        &self,
+        signer: &SignerInfo<'a>,
+        signed_data: &SignedData<'a>,
+    ) -> Vec<Countersignature<'a>> {
+        signer
+            .unauthenticated_attributes()
+            .filter_map(|(oid, value)| match oid.as_str() {
+                COUNTERSIGNATURE => self.pkcs9_countersignature(
+                    signer,
+                    &value,
+                    &signed_data.certificates,
+                ),
+                MS_COUNTERSIGNATURE => {
+                    self.rfc3161_countersignature(signer, &value)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Parses and verifies a PKCS#9 countersignature, which is a
+    /// `SignerInfo` that signs the signer's signature. The certificates
+    /// are in the countersigned `SignedData`.
+    fn pkcs9_countersignature(
+        &self,
+        signer: &SignerInfo<'a>,
+        value: &Tlv<'a>,
+        certificates: &[Certificate<'a>],
+    ) -> Option<Countersignature<'a>> {
+        let countersigner = SignerInfo::parse(value)?;
+
+        let sign_time = countersigner
+            .authenticated_attributes()
+            .find(|(oid, _)| oid == SIGNING_TIME)
+            .and_then(|(_, value)| time(&value));
+
+        let chain = countersigner
+            .certificate(certificates)
+            .map(|cert| self.chain(cert, certificates))
+            .unwrap_or_default();
+
+        let verified = chain.first().map_or(false, |cert| {
+            countersigner.verify(signer.encrypted_digest, cert)
+        }) && self.is_trusted(&chain)
+            && sign_time.map_or(false, |time| {
+                chain.iter().all(|cert| cert.is_valid_at(time))
+            });
+
+        Some(Countersignature {
+            sign_time,
+            digest_algorithm: countersigner.digest_algorithm,
+            digest: countersigner.message_digest().unwrap_or_default(),
+            chain,
+            verified,
+        })
+    }
+
+    /// Parses and verifies an RFC 3161 timestamp, which is a `SignedData`
+    /// whose content is a `TSTInfo` with the digest of the signer's
+    /// signature.
+    fn rfc3161_countersignature(
+        &self,
+        signer: &SignerInfo<'a>,
+        value: &Tlv<'a>,
+    ) -> Option<Countersignature<'a>> {
+        let signed_data = SignedData::parse(value.raw)?;
+
+        // TSTInfo ::= SEQUENCE {
+        //   version INTEGER,
+        //   policy OBJECT IDENTIFIER,
+        //   messageImprint MessageImprint,
+        //   serialNumber INTEGER,
+        //   genTime GeneralizedTime,
+        //   ... }
+        let tst_info = signed_data.content.as_ref()?;
+        let (tst_info, _) = Tlv::parse(tst_info.content)?;
+        let mut fields = tst_info.children();
+        let (digest_algorithm, digest) = digest_info(&fields.nth(2)?)?;
+        let sign_time = fields.nth(1).and_then(|t| time(&t));
+
+        let countersigner = signed_data.signer.as_ref()?;
+
+        let chain = countersigner
+            .certificate(&signed_data.certificates)
+            .map(|cert| self.chain(cert, &signed_data.certificates))
+            .unwrap_or_default();
+
+        let imprint_matches = digest_algorithm.map_or(false, |algorithm| {
+            algorithm.hash(&[signer.encrypted_digest]) == digest
+        });
+
+        let verified = imprint_matches
+            && chain.first().map_or(false, |cert| {
+                countersigner.verify(tst_info.raw, cert)
+            })
+            && self.is_trusted(&chain)
+            && sign_time.map_or(false, |time| {
+                chain.iter().all(|cert| cert.is_valid_at(time))
+            });
+
+        Some(Countersignature {
+            sign_time,
+            digest_algorithm,
+            digest,
+            chain,
+            verified,
+        })
+    }
+
+    /// Builds the chain of certificates that starts with `cert`, looking
+    /// for the issuers in `certificates` and the trust store. The chain
+    /// ends with a trusted certificate, a self-signed certificate, or a
+    /// certificate whose issuer is not found.
+    fn chain(
+        &self,
+        cert: &Certificate<'a>,
+        certificates: &[Certificate<'a>],
+    ) -> Vec<Certificate<'a>> {
+        let mut chain = vec![cert.clone()];
+
+        while chain.len() < MAX_CHAIN_LEN {
+            let last = chain.last().unwrap();
+            if self.is_trusted(&chain) || last.issuer_raw == last.subject_raw {
+                break;
+            }
+            match certificates
+                .iter()
+                .chain(self.trust_store)
+                .find(|issuer| last.is_signed_by(issuer))
+            {
+                Some(issuer) => chain.push(issuer.clone()),
+                None => break,
+            }
+        }
+
+        chain
+    }
+
+    /// Returns true if the last certificate in the chain is trusted.
+    fn is_trusted(&self, chain: &[Certificate]) -> bool {
+        chain.last().map_or(false, |last| {
+            self.trust_store.iter().any(|trusted| trusted.raw == last.raw)
+        })
+    }
+
+    /// Returns the Authenticode digest of the file, which excludes the
+    /// checksum, the security directory entry and the certificate table.
+    fn file_digest(&mut self, algorithm: HashAlgorithm) -> Vec<u8> {
+        if let Some((_, digest)) =
+            self.file_digests.iter().find(|(a, _)| *a == algorithm)
+        {
+            return digest.clone();
+        }
+
+        let pe = self.pe;
+        let data = pe.data;
+
+        let checksum = pe.optional_header_offset + 64;
+        let security_directory = pe.optional_header_offset
+            + if pe.is_64bit { 112 } else { 96 }
+            + DIRECTORY_SECURITY * 8;
+        let end = pe
+            .directory(DIRECTORY_SECURITY)
+            .map_or(data.len(), |(offset, _)| offset as usize)
+            .min(data.len());
+
+        let range = |start: usize, end: usize| {
+            data.get(start.min(end)..end.min(data.len())).unwrap_or_default()
+        };
+
+        let digest = algorithm.hash(&[
+            range(0, checksum),
+            range(checksum + 4, security_directory),
+            range(security_directory + 8, end),
+        ]);
+
+        self.file_digests.push((algorithm, digest.clone()));
+        digest
+    }
+}
+
+/// A PKCS#7 `SignedData` structure.
+///
+/// ```text
+/// ContentInfo ::= SEQUENCE {
+///   contentType OBJECT IDENTIFIER,
+///   content [0] EXPLICIT SignedData }
+///
+/// SignedData ::= SEQUENCE {
+///   version INTEGER,
+///   digestAlgorithms SET OF AlgorithmIdentifier,
+///   contentInfo SEQUENCE {
+///     contentType OBJECT IDENTIFIER,
+///     content [0] EXPLICIT ANY OPTIONAL },
+///   certificates [0] IMPLICIT SET OF Certificate OPTIONAL,
+///   crls [1] IMPLICIT SET OF CertificateList OPTIONAL,
+///   signerInfos SET OF SignerInfo }
+/// ```
+struct SignedData<'a> {
+    content_type: String,
+    content: Option<Tlv<'a>>,
+    certificates: Vec<Certificate<'a>>,
+    /// The first signer. Authenticode signatures have a single one.
+    signer: Option<SignerInfo<'a>>,
+}
+
+impl<'a> SignedData<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let (content_info, _) = Tlv::parse(data)?;
+        let mut parts = content_info.children();
+
+        let content_type = parts.next().filter(|tlv| tlv.tag == OID)?;
+        if oid_string(content_type.content) != SIGNED_DATA {
+            return None;
+        }
+
+        let explicit = parts.next().filter(|tlv| tlv.tag == 0xa0)?;
+        let (signed_data, _) = Tlv::parse(explicit.content)?;
+        let mut fields = signed_data.children().peekable();
+
+        fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+        fields.next().filter(|tlv| tlv.tag == SET)?;
+
+        let encapsulated = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let mut encapsulated = encapsulated.children();
+        let content_type = encapsulated.next().filter(|tlv| tlv.tag == OID)?;
+        let content = encapsulated
+            .next()
+            .filter(|tlv| tlv.tag == 0xa0)
+            .and_then(|explicit| Tlv::parse(explicit.content))
+            .map(|(content, _)| content);
+
+        let certificates = fields
+            .next_if(|tlv| tlv.tag == 0xa0)
+            .map(|certificates| {
+                certificates
+                    .children()
+                    .filter_map(|cert| Certificate::parse(&cert))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        fields.next_if(|tlv| tlv.tag == 0xa1);
+
+        let signer = fields
+            .next()
+            .filter(|tlv| tlv.tag == SET)
+            .and_then(|signers| signers.children().next())
+            .and_then(|signer| SignerInfo::parse(&signer));
+
+        Some(Self {
+            content_type: oid_string(content_type.content),
+            content,
+            certificates,
+            signer,
+        })
+    }
+}
+
+/// A PKCS#7 `SignerInfo` structure.
+///
+/// ```text
+/// SignerInfo ::= SEQUENCE {
+///   version INTEGER,
+///   issuerAndSerialNumber SEQUENCE {
+///     issuer Name,
+///     serialNumber INTEGER },
+///   digestAlgorithm AlgorithmIdentifier,
+///   authenticatedAttributes [0] IMPLICIT SET OF Attribute OPTIONAL,
+///   digestEncryptionAlgorithm AlgorithmIdentifier,
+///   encryptedDigest OCTET STRING,
+///   unauthenticatedAttributes [1] IMPLICIT SET OF Attribute OPTIONAL }
+/// ```
+struct SignerInfo<'a> {
+    issuer: &'a [u8],
+    serial: &'a [u8],
+    digest_algorithm: Option<HashAlgorithm>,
+    authenticated_attributes: Option<Tlv<'a>>,
+    encrypted_digest: &'a [u8],
+    unauthenticated_attributes: Option<Tlv<'a>>,
+}
+
+impl<'a> SignerInfo<'a> {
+    fn parse(signer: &Tlv<'a>) -> Option<Self> {
+        let mut fields = signer.children().peekable();
+
+        fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+
+        let issuer_and_serial =
+            fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let mut issuer_and_serial = issuer_and_serial.children();
+        let issuer = issuer_and_serial.next()?;
+        let serial =
+            issuer_and_serial.next().filter(|tlv| tlv.tag == INTEGER)?;
+
+        let digest_algorithm =
+            fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let authenticated_attributes = fields.next_if(|tlv| tlv.tag == 0xa0);
+        fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+        let encrypted_digest =
+            fields.next().filter(|tlv| tlv.tag == OCTET_STRING)?;
+        let unauthenticated_attributes = fields.next_if(|tlv| tlv.tag == 0xa1);
+
+        Some(Self {
+            issuer: issuer.raw,
+            serial: strip_leading_zero(serial.content),
+            digest_algorithm: algorithm_oid(&digest_algorithm)
+                .and_then(|oid| HashAlgorithm::from_oid(&oid)),
+            authenticated_attributes,
+            encrypted_digest: encrypted_digest.content,
+            unauthenticated_attributes,
+        })
+    }
+
+    /// Returns the signer's certificate, which is identified by its issuer
+    /// and serial number.
+    fn certificate<'c>(
+        &self,
+        certificates: &'c [Certificate<'a>],
+    ) -> Option<&'c Certificate<'a>> {
+        certificates.iter().find(|cert| {
+            cert.issuer_raw == self.issuer && cert.serial == self.serial
+        })
+    }
+
+    fn authenticated_attributes(
+        &self,
+    ) -> impl Iterator<Item = (String, Tlv<'a>)> {
+        attributes(self.authenticated_attributes)
+    }
+
+    fn unauthenticated_attributes(
+        &self,
+    ) -> impl Iterator<Item = (String, Tlv<'a>)> {
+        attributes(self.unauthenticated_attributes)
+    }
+
+    /// Returns the `messageDigest` attribute, which is the digest of the
+    /// signed content.
+    fn message_digest(&self) -> Option<&'a [u8]> {
+        self.authenticated_attributes()
+            .find(|(oid, _)| oid == MESSAGE_DIGEST)
+            .filter(|(_, value)| value.tag == OCTET_STRING)
+            .map(|(_, value)| value.content)
+    }
+
+    /// Returns true if the signer signed `content` with the key in `cert`.
+    ///
+    /// The signature is not computed over the content, but over the
+    /// authenticated attributes, which include the content's digest.
+    fn verify(&self, content: &[u8], cert: &Certificate) -> bool {
+        let (algorithm, key, attributes) = match (
+            self.digest_algorithm,
+            &cert.key,
+            &self.authenticated_attributes,
+        ) {
+            (Some(algorithm), Some(key), Some(attributes)) => {
+                (algorithm, key, attributes)
+            }
+            _ => return false,
+        };
+
+        if self.message_digest() != Some(algorithm.hash(&[content]).as_slice())
+        {
+            return false;
+        }
+
+        // The attributes are signed as a SET OF, instead of with the
+        // implicit [0] tag they have in the structure.
+        let hash = algorithm.hash(&[&[SET], &attributes.raw[1..]]);
+
+        rsa_verify(key, algorithm, &hash, self.encrypted_digest)
+    }
+}
+
+/// Returns the type and the values of the attributes in a set.
+///
+/// ```text
+/// Attribute ::= SEQUENCE {
+///   type OBJECT IDENTIFIER,
+///   values SET OF ANY }
+/// ```
+fn attributes<'a>(
+    attributes: Option<Tlv<'a>>,
+) -> impl Iterator<Item = (String, Tlv<'a>)> {
+    attributes
+        .into_iter()
+        .flat_map(|attributes| attributes.children())
+        .filter_map(|attribute| {
+            let mut parts = attribute.children();
+            let oid = parts.next().filter(|tlv| tlv.tag == OID)?;
+            let values = parts.next().filter(|tlv| tlv.tag == SET)?;
+            Some((oid_string(oid.content), values))
+        })
+        .flat_map(|(oid, values)| {
+            values.children().map(move |value| (oid.clone(), value))
+        })
+}
+
+/// Parses a `DigestInfo` or a `MessageImprint`, which have the same
+/// structure, returning the hash algorithm and the digest. The algorithm
+/// is `None` if it's not supported.
+///
+/// ```text
+/// DigestInfo ::= SEQUENCE {
+///   digestAlgorithm AlgorithmIdentifier,
+///   digest OCTET STRING }
+/// ```
+fn digest_info<'a>(
+    digest_info: &Tlv<'a>,
+) -> Option<(Option<HashAlgorithm>, &'a [u8])> {
+    let mut parts = digest_info.children();
+    let algorithm = parts.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let digest = parts.next().filter(|tlv| tlv.tag == OCTET_STRING)?;
+    Some((
+        algorithm_oid(&algorithm)
+            .and_then(|oid| HashAlgorithm::from_oid(&oid)),
+        digest.content,
+    ))
+}
+
+/// Returns the OID in an `AlgorithmIdentifier`.
+fn algorithm_oid(algorithm: &Tlv) -> Option<String> {
+    let oid = algorithm.children().next().filter(|tlv| tlv.tag == OID)?;
+    Some(oid_string(oid.content))
+}
+
+/// Returns the RSA key in a `SubjectPublicKeyInfo`, or `None` if it's not
+/// an RSA key.
+fn rsa_key<'a>(spki: &Tlv<'a>) -> Option<RsaKey<'a>> {
+    let mut fields = spki.children();
+    let algorithm = fields.next()?;
+    if algorithm_name(&algorithm) != "rsa" {
+        return None;
+    }
+    // The bit string contains the sequence with the modulus and the
+    // exponent. The first byte of the bit string is the number of unused
+    // bits.
+    let bits = fields.next().filter(|tlv| tlv.tag == BIT_STRING)?;
+    let (key, _) = Tlv::parse(bits.content.get(1..)?)?;
+    let mut parts = key.children();
+    let modulus = parts.next().filter(|tlv| tlv.tag == INTEGER)?;
+    let exponent = parts.next().filter(|tlv| tlv.tag == INTEGER)?;
+    Some(RsaKey {
+        modulus: strip_leading_zero(modulus.content),
+        exponent: strip_leading_zero(exponent.content),
+    })
+}
+
+/// Verifies an RSASSA-PKCS1-v1_5 signature of `hash`.
+///
+/// See: RFC 8017, section 8.2.2.
+fn rsa_verify(
+    key: &RsaKey,
+    algorithm: HashAlgorithm,
+    hash: &[u8],
+    signature: &[u8],
+) -> bool {
+    let modulus = BigUint::from_bytes_be(key.modulus);
+    let signature = BigUint::from_bytes_be(signature);
+
+    if signature >= modulus {
+        return false;
+    }
+
+    let message = signature
+        .modpow(&BigUint::from_bytes_be(key.exponent), &modulus)
+        .to_bytes_be();
+
+    // The message is 0x00 0x01 PS 0x00 T, where PS is a padding of at least
+    // 8 bytes with the value 0xff, and T is the DER-encoded DigestInfo. The
+    // leading zero is lost in the conversion from an integer.
+    if message.len() + 1 != key.modulus.len() || message[0] != 0x01 {
+        return false;
+    }
+
+    let padding = message[1..].iter().take_while(|b| **b == 0xff).count();
+
+    if padding < 8 || message.get(1 + padding) != Some(&0) {
+        return false;
+    }
+
+    match Tlv::parse(&message[2 + padding..]) {
+        Some((tlv, [])) => digest_info(&tlv) == Some((Some(algorithm), hash)),
+        _ => false,
+    }
+}
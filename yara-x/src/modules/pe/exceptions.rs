@@ -0,0 +1,64 @@
+/*! Parser for the exception directory (.pdata).
+
+The directory is a table with the address range and the unwind data of
+each function that allocates stack space or calls other functions. The
+format of the table depends on the architecture, and it only exists for
+64-bit and ARM files.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#the-pdata-section
+*/
+
+use crate::modules::utils::pe::{Pe, DIRECTORY_EXCEPTION};
+use crate::modules::utils::u32_at;
+
+/// Maximum number of functions in the exception directory.
+const MAX_FUNCTIONS: usize = 65536;
+
+/// An entry in the exception directory.
+pub(super) struct RuntimeFunction {
+    pub begin: u32,
+    /// Undefined for ARM files, where the function's length is in the
+    /// unwind data.
+    pub end: Option<u32>,
+    /// RVA of the unwind information. In ARM files, this is the unwind data
+    /// itself when the lowest two bits are not zero.
+    pub unwind_info: u32,
+}
+
+/// Parses the exception directory. Returns an empty vector for
+/// architectures that don't have one, or whose format is not supported.
+pub(super) fn parse(pe: &Pe) -> Vec<RuntimeFunction> {
+    let directory = match pe.directory_data(DIRECTORY_EXCEPTION) {
+        Some(directory) => directory,
+        None => return Vec::new(),
+    };
+
+    let entry_size = match pe.machine {
+        // AMD64 and IA64.
+        0x8664 | 0x0200 => 12,
+        // ARM64 and ARM Thumb-2.
+        0xaa64 | 0x01c4 => 8,
+        _ => return Vec::new(),
+    };
+
+    directory
+        .chunks_exact(entry_size)
+        .take(MAX_FUNCTIONS)
+        .map(|entry| {
+            let begin = u32_at(entry, 0).unwrap();
+            if entry_size == 12 {
+                RuntimeFunction {
+                    begin,
+                    end: u32_at(entry, 4),
+                    unwind_info: u32_at(entry, 8).unwrap(),
+                }
+            } else {
+                RuntimeFunction {
+                    begin,
+                    end: None,
+                    unwind_info: u32_at(entry, 4).unwrap(),
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,104 @@
+/*! Parser for the export directory.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#the-edata-section-image-only
+*/
+
+use crate::modules::protos::pe::PeExport;
+use crate::modules::utils::digest::md5_hex;
+use crate::modules::utils::pe::{Pe, DIRECTORY_EXPORT};
+use crate::modules::utils::{u16_at, u32_at};
+
+/// Maximum number of exported functions.
+const MAX_EXPORTS: usize = 16384;
+
+/// The export directory.
+pub(super) struct Exports {
+    /// Name of the DLL, as it appears in the export directory.
+    pub dll_name: Option<String>,
+    pub timestamp: u32,
+    pub functions: Vec<ExportedFunction>,
+}
+
+/// An exported function, which is exported by name or only by ordinal.
+pub(super) struct ExportedFunction {
+    pub name: Option<String>,
+    pub ordinal: u32,
+    pub rva: u32,
+    /// The function exported by another DLL that this export is forwarded
+    /// to (e.g: `NTDLL.RtlAllocateHeap`), if any.
+    pub forward_name: Option<String>,
+}
+
+/// Parses the export directory. Returns `None` if the file doesn't have
+/// one.
+pub(super) fn parse(pe: &Pe) -> Option<Exports> {
+    let (rva, size) = pe.directory(DIRECTORY_EXPORT)?;
+    let directory = pe.data_at_rva(rva)?;
+
+    let timestamp = u32_at(directory, 4)?;
+    let dll_name = pe.string_at_rva(u32_at(directory, 12)?);
+    let base = u32_at(directory, 16)?;
+    let num_functions = u32_at(directory, 20)? as usize;
+    let num_names = u32_at(directory, 24)? as usize;
+
+    // The address table has the RVAs of all the exported functions, indexed
+    // by ordinal minus the base. The name table and the ordinal table are
+    // parallel arrays, with the names of the functions exported by name,
+    // sorted alphabetically, and their indexes in the address table.
+    let addresses = pe.data_at_rva(u32_at(directory, 28)?).unwrap_or_default();
+    let names = pe.data_at_rva(u32_at(directory, 32)?).unwrap_or_default();
+    let ordinals = pe.data_at_rva(u32_at(directory, 36)?).unwrap_or_default();
+
+    let names: Vec<(usize, u32)> = (0..num_names.min(MAX_EXPORTS))
+        .map_while(|i| {
+            let name = u32_at(names, i * 4)?;
+            let index = u16_at(ordinals, i * 2)? as usize;
+            Some((index, name))
+        })
+        .collect();
+
+    // Exports are returned in the order of the address table, which is the
+    // order of their ordinals. Entries with a zero RVA are unused.
+    let functions = (0..num_functions.min(MAX_EXPORTS))
+        .map_while(|index| Some((index, u32_at(addresses, index * 4)?)))
+        .filter(|(_, rva)| *rva != 0)
+        .map(|(index, function_rva)| {
+            let name = names
+                .iter()
+                .find(|(i, _)| *i == index)
+                .and_then(|(_, name)| pe.string_at_rva(*name));
+            // The export is forwarded when its RVA points to a string
+            // inside the export directory, instead of code.
+            let forward_name = (function_rva >= rva
+                && function_rva - rva < size)
+                .then(|| pe.string_at_rva(function_rva))
+                .flatten();
+            ExportedFunction {
+                name,
+                ordinal: base.wrapping_add(index as u32),
+                rva: function_rva,
+                forward_name,
+            }
+        })
+        .collect();
+
+    Some(Exports { dll_name, timestamp, functions })
+}
+
+/// Computes the export hash, which is the MD5 of a comma-separated list
+/// with the names of the exported functions, in lowercase, in the order
+/// of their ordinals. Functions exported only by ordinal are ignored. Returns `None` if the file doesn't
+/// export any function by name.
+pub(super) fn exphash(exports: &[PeExport]) -> Option<String> {
+    let names: Vec<String> = exports
+        .iter()
+        .filter_map(|export| export.name.as_ref())
+        .map(|name| name.to_lowercase())
+        .collect();
+
+    if names.is_empty() {
+        return None;
+    }
+
+    Some(md5_hex(names.join(",").as_bytes()))
+}
@@ -0,0 +1,899 @@
+/*! Parsers for the import directories, and the import hash.
+
+Besides the regular import directory, this parses the delay-load import
+directory, which has the DLLs that are loaded the first time one of their
+functions is called, and the bound import directory, which has the
+timestamps of the DLLs the import address table was bound to.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#the-idata-section
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#delay-load-import-tables-image-only
+*/
+
+use crate::modules::protos::pe::PeImport;
+use crate::modules::utils::digest::md5_hex;
+use crate::modules::utils::pe::{
+    Pe, DIRECTORY_BOUND_IMPORT, DIRECTORY_DELAY_IMPORT, DIRECTORY_IMPORT,
+};
+use crate::modules::utils::{u16_at, u32_at};
+
+/// Maximum number of imported DLLs and functions.
+const MAX_IMPORTS: usize = 16384;
+
+/// A DLL in the import directory.
+pub(super) struct ImportedDll {
+    pub name: String,
+    pub functions: Vec<ImportedFunction>,
+}
+
+/// A function imported from a DLL, either by name or by ordinal.
+pub(super) struct ImportedFunction {
+    pub name: Option<String>,
+    pub ordinal: Option<u16>,
+    /// RVA of the function's entry in the import address table.
+    pub rva: u32,
+}
+
+/// Parses the import directory, returning the imported DLLs in the order
+/// they appear in the directory.
+pub(super) fn parse(pe: &Pe) -> Vec<ImportedDll> {
+    let mut dlls = Vec::new();
+
+    let directory = match pe
+        .directory(DIRECTORY_IMPORT)
+        .and_then(|(rva, _)| pe.data_at_rva(rva))
+    {
+        Some(directory) => directory,
+        None => return dlls,
+    };
+
+    let mut num_functions = 0;
+
+    // The directory is an array of 20-byte descriptors, terminated by one
+    // that is all zeroes. The directory's size is often wrong, so it's not
+    // used for finding the end of the array.
+    for descriptor in directory.chunks_exact(20) {
+        if num_functions >= MAX_IMPORTS {
+            break;
+        }
+
+        let original_first_thunk = u32_at(descriptor, 0).unwrap();
+        let name = u32_at(descriptor, 12).unwrap();
+        let first_thunk = u32_at(descriptor, 16).unwrap();
+
+        if name == 0 {
+            break;
+        }
+
+        let name = match pe.string_at_rva(name) {
+            Some(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+
+        // The lookup table has the names or ordinals of the functions. If
+        // it's missing, the import address table is used instead, which has
+        // the same content until the DLL is bound.
+        let lookup_table = if original_first_thunk != 0 {
+            original_first_thunk
+        } else {
+            first_thunk
+        };
+
+        let functions = functions(
+            pe,
+            lookup_table,
+            first_thunk,
+            MAX_IMPORTS - num_functions,
+        );
+
+        num_functions += functions.len();
+        dlls.push(ImportedDll { name, functions });
+    }
+
+    dlls
+}
+
+/// A DLL in the bound import directory.
+pub(super) struct BoundImport {
+    pub name: String,
+    /// Timestamp of the DLL the imports were bound to.
+    pub timestamp: u32,
+    /// DLLs that the bound DLL forwards some of its functions to.
+    pub forwarders: Vec<String>,
+}
+
+/// Parses the delay-load import directory, returning the DLLs in the
+/// order they appear in the directory.
+pub(super) fn parse_delayed(pe: &Pe) -> Vec<ImportedDll> {
+    let mut dlls = Vec::new();
+
+    let directory = match pe
+        .directory(DIRECTORY_DELAY_IMPORT)
+        .and_then(|(rva, _)| pe.data_at_rva(rva))
+    {
+        Some(directory) => directory,
+        None => return dlls,
+    };
+
+    let mut num_functions = 0;
+
+    // Like the import directory, this is an array of descriptors terminated
+    // by one that is all zeroes, but the descriptors are 32 bytes long.
+    for descriptor in directory.chunks_exact(32) {
+        if num_functions >= MAX_IMPORTS {
+            break;
+        }
+
+        let attributes = u32_at(descriptor, 0).unwrap();
+        let name = u32_at(descriptor, 4).unwrap();
+        let iat = u32_at(descriptor, 12).unwrap();
+        let name_table = u32_at(descriptor, 16).unwrap();
+
+        if name == 0 {
+            break;
+        }
+
+        // When the lowest bit of the attributes is not set, which is the
+        // case for files produced by old linkers, the descriptor has
+        // virtual addresses instead of RVAs.
+        let rva = |address: u32| {
+            if attributes & 1 != 0 {
+                Some(address)
+            } else {
+                u64::from(address)
+                    .checked_sub(pe.image_base)
+                    .and_then(|rva| u32::try_from(rva).ok())
+            }
+        };
+
+        let name = match rva(name).and_then(|name| pe.string_at_rva(name)) {
+            Some(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+
+        let functions = match (rva(name_table), rva(iat)) {
+            (Some(name_table), Some(iat)) => {
+                functions(pe, name_table, iat, MAX_IMPORTS - num_functions)
+            }
+            _ => Vec::new(),
+        };
+
+        num_functions += functions.len();
+        dlls.push(ImportedDll { name, functions });
+    }
+
+    dlls
+}
+
+/// Parses the bound import directory.
+pub(super) fn parse_bound(pe: &Pe) -> Vec<BoundImport> {
+    let mut bound_imports = Vec::new();
+
+    // The names are at offsets relative to the start of the directory,
+    // usually after the descriptors.
+    let directory = match pe
+        .directory(DIRECTORY_BOUND_IMPORT)
+        .and_then(|(rva, _)| pe.data_at_rva(rva))
+    {
+        Some(directory) => directory,
+        None => return bound_imports,
+    };
+
+    // Each descriptor is followed by the descriptors of its forwarders,
+    // and all of them are 8 bytes long: a timestamp, the offset of the
+    // name, and the number of forwarders (reserved in forwarders). The
+    // last descriptor is all zeroes.
+    let mut descriptors = directory.chunks_exact(8);
+
+    while bound_imports.len() < MAX_IMPORTS {
+        let descriptor = match descriptors.next() {
+            Some(descriptor) if descriptor != [0; 8] => descriptor,
+            _ => break,
+        };
+
+        let timestamp = u32_at(descriptor, 0).unwrap();
+        let name = u16_at(descriptor, 4).unwrap();
+        let num_forwarders = u16_at(descriptor, 6).unwrap();
+
+        let forwarders = descriptors
+            .by_ref()
+            .take(num_forwarders.into())
+            .filter_map(|forwarder| {
+                string_at(directory, u16_at(forwarder, 4).unwrap())
+            })
+            .collect();
+
+        if let Some(name) = string_at(directory, name) {
+            bound_imports.push(BoundImport { name, timestamp, forwarders });
+        }
+    }
+
+    bound_imports
+}
+
+/// Returns the null-terminated string at the given offset within the bound
+/// import directory.
+fn string_at(directory: &[u8], offset: u16) -> Option<String> {
+    let data = directory.get(offset as usize..)?;
+    let len = data.iter().take(256).position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&data[..len]).into_owned())
+}
+
+/// Parses the lookup table at the given RVA, which has one entry for each
+/// function imported from a DLL. `iat` is the RVA of the import address
+/// table, which has the same number of entries.
+fn functions(
+    pe: &Pe,
+    lookup_table: u32,
+    iat: u32,
+    max: usize,
+) -> Vec<ImportedFunction> {
+    let table = match pe.data_at_rva(lookup_table) {
+        Some(table) => table,
+        None => return Vec::new(),
+    };
+
+    let entry_size = if pe.is_64bit { 8 } else { 4 };
+    let ordinal_flag = if pe.is_64bit { 1 << 63 } else { 1 << 31 };
+
+    table
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            if pe.is_64bit {
+                u64::from_le_bytes(entry.try_into().unwrap())
+            } else {
+                u32::from_le_bytes(entry.try_into().unwrap()).into()
+            }
+        })
+        .take_while(|entry| *entry != 0)
+        .take(max)
+        .enumerate()
+        .map(|(i, entry)| {
+            let rva = iat.wrapping_add((i * entry_size) as u32);
+            if entry & ordinal_flag != 0 {
+                ImportedFunction {
+                    name: None,
+                    ordinal: Some(entry as u16),
+                    rva,
+                }
+            } else {
+                // The entry is the RVA of a 2-byte hint followed by the
+                // function's name.
+                let name = pe.string_at_rva((entry as u32).wrapping_add(2));
+                ImportedFunction { name, ordinal: None, rva }
+            }
+        })
+        .collect()
+}
+
+/// Computes the import hash (imphash) in the same way as `pefile` and YARA
+/// 4.x.
+///
+/// The hash is the MD5 of a comma-separated list with the imported
+/// functions, in the order they appear in the import directory. Each
+/// function is represented as `dll.function`, in lowercase, where `dll`
+/// is the DLL's name without the `.dll`, `.ocx` or `.sys` extension.
+/// Functions imported by ordinal are replaced with their names for a few
+/// well known DLLs, and with `ordN` for the rest.
+pub(super) fn imphash(dlls: &[PeImport]) -> Option<String> {
+    let mut imports = Vec::new();
+
+    for dll in dlls {
+        let dll_name = dll.library_name().to_lowercase();
+        let library = match dll_name.rsplit_once('.') {
+            Some((stem, "dll" | "ocx" | "sys")) => stem,
+            _ => dll_name.as_str(),
+        };
+
+        for function in &dll.functions {
+            let function = match (&function.name, function.ordinal) {
+                (Some(name), _) => name.to_lowercase(),
+                (None, Some(ordinal)) => {
+                    match ordinal_name(&dll_name, ordinal) {
+                        Some(name) => name.to_lowercase(),
+                        None => format!("ord{}", ordinal),
+                    }
+                }
+                (None, None) => continue,
+            };
+            imports.push(format!("{}.{}", library, function));
+        }
+    }
+
+    if imports.is_empty() {
+        return None;
+    }
+
+    Some(md5_hex(imports.join(",").as_bytes()))
+}
+
+/// Returns the DLL that implements an API set (e.g: `kernelbase.dll` for
+/// `api-ms-win-core-file-l1-2-0.dll`), or `None` if `dll` is not an API
+/// set, or it's not a known one.
+///
+/// API sets are virtual DLLs that the loader redirects to the DLL that
+/// hosts the functions, according to a schema that changes between Windows
+/// versions. The built-in table has the hosts for the most common API sets
+/// in Windows 10 and later, matched by the longest prefix.
+pub(super) fn api_set_host(dll: &str) -> Option<String> {
+    let dll = dll.to_ascii_lowercase();
+
+    if !dll.starts_with("api-ms-") && !dll.starts_with("ext-ms-") {
+        return None;
+    }
+
+    // The down-level API sets are named after their hosts, e.g:
+    // api-ms-win-downlevel-advapi32-l1-1-0.dll is hosted by advapi32.dll.
+    if let Some(rest) = dll.strip_prefix("api-ms-win-downlevel-") {
+        let (host, _) = rest.split_once("-l")?;
+        return Some(format!("{}.dll", host));
+    }
+
+    API_SET_HOSTS
+        .iter()
+        .filter(|(prefix, _)| dll.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, host)| host.to_string())
+}
+
+/// Prefixes of API set names, and the DLLs that host them.
+const API_SET_HOSTS: &[(&str, &str)] = &[
+    ("api-ms-win-core-", "kernelbase.dll"),
+    ("api-ms-win-core-com-", "combase.dll"),
+    ("api-ms-win-core-com-midlproxystub-", "combase.dll"),
+    ("api-ms-win-core-comm-", "kernelbase.dll"),
+    ("api-ms-win-core-rtlsupport-", "ntdll.dll"),
+    ("api-ms-win-core-winrt-", "combase.dll"),
+    ("api-ms-win-crt-", "ucrtbase.dll"),
+    ("api-ms-win-eventing-classicprovider-", "kernelbase.dll"),
+    ("api-ms-win-eventing-consumer-", "sechost.dll"),
+    ("api-ms-win-eventing-controller-", "sechost.dll"),
+    ("api-ms-win-eventing-provider-", "kernelbase.dll"),
+    ("api-ms-win-security-base-", "kernelbase.dll"),
+    ("api-ms-win-security-capability-", "sechost.dll"),
+    ("api-ms-win-security-lsalookup-", "sechost.dll"),
+    ("api-ms-win-security-sddl-", "sechost.dll"),
+    ("api-ms-win-service-", "sechost.dll"),
+    ("api-ms-win-shcore-", "shcore.dll"),
+    ("ext-ms-win-gdi-", "gdi32full.dll"),
+    ("ext-ms-win-ntuser-", "user32.dll"),
+    ("ext-ms-win-shell32-", "shell32.dll"),
+];
+
+/// Returns the name of a function exported by ordinal from one of the DLLs
+/// known by `pefile`.
+fn ordinal_name(dll: &str, ordinal: i64) -> Option<&'static str> {
+    let table = match dll {
+        "ws2_32.dll" | "wsock32.dll" => WS2_32_ORDINALS,
+        "oleaut32.dll" => OLEAUT32_ORDINALS,
+        _ => return None,
+    };
+    table
+        .binary_search_by_key(&ordinal, |(ordinal, _)| (*ordinal).into())
+        .ok()
+        .map(|index| table[index].1)
+}
+
+/// Functions exported by ordinal from `ws2_32.dll` and `wsock32.dll`, as
+/// in `pefile`'s `ordlookup` module.
+const WS2_32_ORDINALS: &[(u16, &str)] = &[
+    (1, "accept"),
+    (2, "bind"),
+    (3, "closesocket"),
+    (4, "connect"),
+    (5, "getpeername"),
+    (6, "getsockname"),
+    (7, "getsockopt"),
+    (8, "htonl"),
+    (9, "htons"),
+    (10, "ioctlsocket"),
+    (11, "inet_addr"),
+    (12, "inet_ntoa"),
+    (13, "listen"),
+    (14, "ntohl"),
+    (15, "ntohs"),
+    (16, "recv"),
+    (17, "recvfrom"),
+    (18, "select"),
+    (19, "send"),
+    (20, "sendto"),
+    (21, "setsockopt"),
+    (22, "shutdown"),
+    (23, "socket"),
+    (24, "GetAddrInfoW"),
+    (25, "GetNameInfoW"),
+    (26, "WSApSetPostRoutine"),
+    (27, "FreeAddrInfoW"),
+    (28, "WPUCompleteOverlappedRequest"),
+    (29, "WSAAccept"),
+    (30, "WSAAddressToStringA"),
+    (31, "WSAAddressToStringW"),
+    (32, "WSACloseEvent"),
+    (33, "WSAConnect"),
+    (34, "WSACreateEvent"),
+    (35, "WSADuplicateSocketA"),
+    (36, "WSADuplicateSocketW"),
+    (37, "WSAEnumNameSpaceProvidersA"),
+    (38, "WSAEnumNameSpaceProvidersW"),
+    (39, "WSAEnumNetworkEvents"),
+    (40, "WSAEnumProtocolsA"),
+    (41, "WSAEnumProtocolsW"),
+    (42, "WSAEventSelect"),
+    (43, "WSAGetOverlappedResult"),
+    (44, "WSAGetQOSByName"),
+    (45, "WSAGetServiceClassInfoA"),
+    (46, "WSAGetServiceClassInfoW"),
+    (47, "WSAGetServiceClassNameByClassIdA"),
+    (48, "WSAGetServiceClassNameByClassIdW"),
+    (49, "WSAHtonl"),
+    (50, "WSAHtons"),
+    (51, "gethostbyaddr"),
+    (52, "gethostbyname"),
+    (53, "getprotobyname"),
+    (54, "getprotobynumber"),
+    (55, "getservbyname"),
+    (56, "getservbyport"),
+    (57, "gethostname"),
+    (58, "WSAInstallServiceClassA"),
+    (59, "WSAInstallServiceClassW"),
+    (60, "WSAIoctl"),
+    (61, "WSAJoinLeaf"),
+    (62, "WSALookupServiceBeginA"),
+    (63, "WSALookupServiceBeginW"),
+    (64, "WSALookupServiceEnd"),
+    (65, "WSALookupServiceNextA"),
+    (66, "WSALookupServiceNextW"),
+    (67, "WSANSPIoctl"),
+    (68, "WSANtohl"),
+    (69, "WSANtohs"),
+    (70, "WSAProviderConfigChange"),
+    (71, "WSARecv"),
+    (72, "WSARecvDisconnect"),
+    (73, "WSARecvFrom"),
+    (74, "WSARemoveServiceClass"),
+    (75, "WSAResetEvent"),
+    (76, "WSASend"),
+    (77, "WSASendDisconnect"),
+    (78, "WSASendTo"),
+    (79, "WSASetEvent"),
+    (80, "WSASetServiceA"),
+    (81, "WSASetServiceW"),
+    (82, "WSASocketA"),
+    (83, "WSASocketW"),
+    (84, "WSAStringToAddressA"),
+    (85, "WSAStringToAddressW"),
+    (86, "WSAWaitForMultipleEvents"),
+    (87, "WSCDeinstallProvider"),
+    (88, "WSCEnableNSProvider"),
+    (89, "WSCEnumProtocols"),
+    (90, "WSCGetProviderPath"),
+    (91, "WSCInstallNameSpace"),
+    (92, "WSCInstallProvider"),
+    (93, "WSCUnInstallNameSpace"),
+    (94, "WSCUpdateProvider"),
+    (95, "WSCWriteNameSpaceOrder"),
+    (96, "WSCWriteProviderOrder"),
+    (97, "freeaddrinfo"),
+    (98, "getaddrinfo"),
+    (99, "getnameinfo"),
+    (101, "WSAAsyncSelect"),
+    (102, "WSAAsyncGetHostByAddr"),
+    (103, "WSAAsyncGetHostByName"),
+    (104, "WSAAsyncGetProtoByNumber"),
+    (105, "WSAAsyncGetProtoByName"),
+    (106, "WSAAsyncGetServByPort"),
+    (107, "WSAAsyncGetServByName"),
+    (108, "WSACancelAsyncRequest"),
+    (109, "WSASetBlockingHook"),
+    (110, "WSAUnhookBlockingHook"),
+    (111, "WSAGetLastError"),
+    (112, "WSASetLastError"),
+    (113, "WSACancelBlockingCall"),
+    (114, "WSAIsBlocking"),
+    (115, "WSAStartup"),
+    (116, "WSACleanup"),
+    (151, "__WSAFDIsSet"),
+    (500, "WEP"),
+];
+
+/// Functions exported by ordinal from `oleaut32.dll`, as in `pefile`'s
+/// `ordlookup` module.
+const OLEAUT32_ORDINALS: &[(u16, &str)] = &[
+    (2, "SysAllocString"),
+    (3, "SysReAllocString"),
+    (4, "SysAllocStringLen"),
+    (5, "SysReAllocStringLen"),
+    (6, "SysFreeString"),
+    (7, "SysStringLen"),
+    (8, "VariantInit"),
+    (9, "VariantClear"),
+    (10, "VariantCopy"),
+    (11, "VariantCopyInd"),
+    (12, "VariantChangeType"),
+    (13, "VariantTimeToDosDateTime"),
+    (14, "DosDateTimeToVariantTime"),
+    (15, "SafeArrayCreate"),
+    (16, "SafeArrayDestroy"),
+    (17, "SafeArrayGetDim"),
+    (18, "SafeArrayGetElemsize"),
+    (19, "SafeArrayGetUBound"),
+    (20, "SafeArrayGetLBound"),
+    (21, "SafeArrayLock"),
+    (22, "SafeArrayUnlock"),
+    (23, "SafeArrayAccessData"),
+    (24, "SafeArrayUnaccessData"),
+    (25, "SafeArrayGetElement"),
+    (26, "SafeArrayPutElement"),
+    (27, "SafeArrayCopy"),
+    (28, "DispGetParam"),
+    (29, "DispGetIDsOfNames"),
+    (30, "DispInvoke"),
+    (31, "CreateDispTypeInfo"),
+    (32, "CreateStdDispatch"),
+    (33, "RegisterActiveObject"),
+    (34, "RevokeActiveObject"),
+    (35, "GetActiveObject"),
+    (36, "SafeArrayAllocDescriptor"),
+    (37, "SafeArrayAllocData"),
+    (38, "SafeArrayDestroyDescriptor"),
+    (39, "SafeArrayDestroyData"),
+    (40, "SafeArrayRedim"),
+    (41, "SafeArrayAllocDescriptorEx"),
+    (42, "SafeArrayCreateEx"),
+    (43, "SafeArrayCreateVectorEx"),
+    (44, "SafeArraySetRecordInfo"),
+    (45, "SafeArrayGetRecordInfo"),
+    (46, "VarParseNumFromStr"),
+    (47, "VarNumFromParseNum"),
+    (48, "VarI2FromUI1"),
+    (49, "VarI2FromI4"),
+    (50, "VarI2FromR4"),
+    (51, "VarI2FromR8"),
+    (52, "VarI2FromCy"),
+    (53, "VarI2FromDate"),
+    (54, "VarI2FromStr"),
+    (55, "VarI2FromDisp"),
+    (56, "VarI2FromBool"),
+    (57, "SafeArraySetIID"),
+    (58, "VarI4FromUI1"),
+    (59, "VarI4FromI2"),
+    (60, "VarI4FromR4"),
+    (61, "VarI4FromR8"),
+    (62, "VarI4FromCy"),
+    (63, "VarI4FromDate"),
+    (64, "VarI4FromStr"),
+    (65, "VarI4FromDisp"),
+    (66, "VarI4FromBool"),
+    (67, "SafeArrayGetIID"),
+    (68, "VarR4FromUI1"),
+    (69, "VarR4FromI2"),
+    (70, "VarR4FromI4"),
+    (71, "VarR4FromR8"),
+    (72, "VarR4FromCy"),
+    (73, "VarR4FromDate"),
+    (74, "VarR4FromStr"),
+    (75, "VarR4FromDisp"),
+    (76, "VarR4FromBool"),
+    (77, "SafeArrayGetVartype"),
+    (78, "VarR8FromUI1"),
+    (79, "VarR8FromI2"),
+    (80, "VarR8FromI4"),
+    (81, "VarR8FromR4"),
+    (82, "VarR8FromCy"),
+    (83, "VarR8FromDate"),
+    (84, "VarR8FromStr"),
+    (85, "VarR8FromDisp"),
+    (86, "VarR8FromBool"),
+    (87, "VarFormat"),
+    (88, "VarDateFromUI1"),
+    (89, "VarDateFromI2"),
+    (90, "VarDateFromI4"),
+    (91, "VarDateFromR4"),
+    (92, "VarDateFromR8"),
+    (93, "VarDateFromCy"),
+    (94, "VarDateFromStr"),
+    (95, "VarDateFromDisp"),
+    (96, "VarDateFromBool"),
+    (97, "VarFormatDateTime"),
+    (98, "VarCyFromUI1"),
+    (99, "VarCyFromI2"),
+    (100, "VarCyFromI4"),
+    (101, "VarCyFromR4"),
+    (102, "VarCyFromR8"),
+    (103, "VarCyFromDate"),
+    (104, "VarCyFromStr"),
+    (105, "VarCyFromDisp"),
+    (106, "VarCyFromBool"),
+    (107, "VarFormatNumber"),
+    (108, "VarBstrFromUI1"),
+    (109, "VarBstrFromI2"),
+    (110, "VarBstrFromI4"),
+    (111, "VarBstrFromR4"),
+    (112, "VarBstrFromR8"),
+    (113, "VarBstrFromCy"),
+    (114, "VarBstrFromDate"),
+    (115, "VarBstrFromDisp"),
+    (116, "VarBstrFromBool"),
+    (117, "VarFormatPercent"),
+    (118, "VarBoolFromUI1"),
+    (119, "VarBoolFromI2"),
+    (120, "VarBoolFromI4"),
+    (121, "VarBoolFromR4"),
+    (122, "VarBoolFromR8"),
+    (123, "VarBoolFromDate"),
+    (124, "VarBoolFromCy"),
+    (125, "VarBoolFromStr"),
+    (126, "VarBoolFromDisp"),
+    (127, "VarFormatCurrency"),
+    (128, "VarWeekdayName"),
+    (129, "VarMonthName"),
+    (130, "VarUI1FromI2"),
+    (131, "VarUI1FromI4"),
+    (132, "VarUI1FromR4"),
+    (133, "VarUI1FromR8"),
+    (134, "VarUI1FromCy"),
+    (135, "VarUI1FromDate"),
+    (136, "VarUI1FromStr"),
+    (137, "VarUI1FromDisp"),
+    (138, "VarUI1FromBool"),
+    (139, "VarFormatFromTokens"),
+    (140, "VarTokenizeFormatString"),
+    (141, "VarAdd"),
+    (142, "VarAnd"),
+    (143, "VarDiv"),
+    (144, "DllCanUnloadNow"),
+    (145, "DllGetClassObject"),
+    (146, "DispCallFunc"),
+    (147, "VariantChangeTypeEx"),
+    (148, "SafeArrayPtrOfIndex"),
+    (149, "SysStringByteLen"),
+    (150, "SysAllocStringByteLen"),
+    (151, "DllRegisterServer"),
+    (152, "VarEqv"),
+    (153, "VarIdiv"),
+    (154, "VarImp"),
+    (155, "VarMod"),
+    (156, "VarMul"),
+    (157, "VarOr"),
+    (158, "VarPow"),
+    (159, "VarSub"),
+    (160, "CreateTypeLib"),
+    (161, "LoadTypeLib"),
+    (162, "LoadRegTypeLib"),
+    (163, "RegisterTypeLib"),
+    (164, "QueryPathOfRegTypeLib"),
+    (165, "LHashValOfNameSys"),
+    (166, "LHashValOfNameSysA"),
+    (167, "VarXor"),
+    (168, "VarAbs"),
+    (169, "VarFix"),
+    (170, "OaBuildVersion"),
+    (171, "ClearCustData"),
+    (172, "VarInt"),
+    (173, "VarNeg"),
+    (174, "VarNot"),
+    (175, "VarRound"),
+    (176, "VarCmp"),
+    (177, "VarDecAdd"),
+    (178, "VarDecDiv"),
+    (179, "VarDecMul"),
+    (180, "CreateTypeLib2"),
+    (181, "VarDecSub"),
+    (182, "VarDecAbs"),
+    (183, "LoadTypeLibEx"),
+    (184, "SystemTimeToVariantTime"),
+    (185, "VariantTimeToSystemTime"),
+    (186, "UnRegisterTypeLib"),
+    (187, "VarDecFix"),
+    (188, "VarDecInt"),
+    (189, "VarDecNeg"),
+    (190, "VarDecFromUI1"),
+    (191, "VarDecFromI2"),
+    (192, "VarDecFromI4"),
+    (193, "VarDecFromR4"),
+    (194, "VarDecFromR8"),
+    (195, "VarDecFromDate"),
+    (196, "VarDecFromCy"),
+    (197, "VarDecFromStr"),
+    (198, "VarDecFromDisp"),
+    (199, "VarDecFromBool"),
+    (200, "GetErrorInfo"),
+    (201, "SetErrorInfo"),
+    (202, "CreateErrorInfo"),
+    (203, "VarDecRound"),
+    (204, "VarDecCmp"),
+    (205, "VarI2FromI1"),
+    (206, "VarI2FromUI2"),
+    (207, "VarI2FromUI4"),
+    (208, "VarI2FromDec"),
+    (209, "VarI4FromI1"),
+    (210, "VarI4FromUI2"),
+    (211, "VarI4FromUI4"),
+    (212, "VarI4FromDec"),
+    (213, "VarR4FromI1"),
+    (214, "VarR4FromUI2"),
+    (215, "VarR4FromUI4"),
+    (216, "VarR4FromDec"),
+    (217, "VarR8FromI1"),
+    (218, "VarR8FromUI2"),
+    (219, "VarR8FromUI4"),
+    (220, "VarR8FromDec"),
+    (221, "VarDateFromI1"),
+    (222, "VarDateFromUI2"),
+    (223, "VarDateFromUI4"),
+    (224, "VarDateFromDec"),
+    (225, "VarCyFromI1"),
+    (226, "VarCyFromUI2"),
+    (227, "VarCyFromUI4"),
+    (228, "VarCyFromDec"),
+    (229, "VarBstrFromI1"),
+    (230, "VarBstrFromUI2"),
+    (231, "VarBstrFromUI4"),
+    (232, "VarBstrFromDec"),
+    (233, "VarBoolFromI1"),
+    (234, "VarBoolFromUI2"),
+    (235, "VarBoolFromUI4"),
+    (236, "VarBoolFromDec"),
+    (237, "VarUI1FromI1"),
+    (238, "VarUI1FromUI2"),
+    (239, "VarUI1FromUI4"),
+    (240, "VarUI1FromDec"),
+    (241, "VarDecFromI1"),
+    (242, "VarDecFromUI2"),
+    (243, "VarDecFromUI4"),
+    (244, "VarI1FromUI1"),
+    (245, "VarI1FromI2"),
+    (246, "VarI1FromI4"),
+    (247, "VarI1FromR4"),
+    (248, "VarI1FromR8"),
+    (249, "VarI1FromDate"),
+    (250, "VarI1FromCy"),
+    (251, "VarI1FromStr"),
+    (252, "VarI1FromDisp"),
+    (253, "VarI1FromBool"),
+    (254, "VarI1FromUI2"),
+    (255, "VarI1FromUI4"),
+    (256, "VarI1FromDec"),
+    (257, "VarUI2FromUI1"),
+    (258, "VarUI2FromI2"),
+    (259, "VarUI2FromI4"),
+    (260, "VarUI2FromR4"),
+    (261, "VarUI2FromR8"),
+    (262, "VarUI2FromDate"),
+    (263, "VarUI2FromCy"),
+    (264, "VarUI2FromStr"),
+    (265, "VarUI2FromDisp"),
+    (266, "VarUI2FromBool"),
+    (267, "VarUI2FromI1"),
+    (268, "VarUI2FromUI4"),
+    (269, "VarUI2FromDec"),
+    (270, "VarUI4FromUI1"),
+    (271, "VarUI4FromI2"),
+    (272, "VarUI4FromI4"),
+    (273, "VarUI4FromR4"),
+    (274, "VarUI4FromR8"),
+    (275, "VarUI4FromDate"),
+    (276, "VarUI4FromCy"),
+    (277, "VarUI4FromStr"),
+    (278, "VarUI4FromDisp"),
+    (279, "VarUI4FromBool"),
+    (280, "VarUI4FromI1"),
+    (281, "VarUI4FromUI2"),
+    (282, "VarUI4FromDec"),
+    (283, "BSTR_UserSize"),
+    (284, "BSTR_UserMarshal"),
+    (285, "BSTR_UserUnmarshal"),
+    (286, "BSTR_UserFree"),
+    (287, "VARIANT_UserSize"),
+    (288, "VARIANT_UserMarshal"),
+    (289, "VARIANT_UserUnmarshal"),
+    (290, "VARIANT_UserFree"),
+    (291, "LPSAFEARRAY_UserSize"),
+    (292, "LPSAFEARRAY_UserMarshal"),
+    (293, "LPSAFEARRAY_UserUnmarshal"),
+    (294, "LPSAFEARRAY_UserFree"),
+    (295, "LPSAFEARRAY_Size"),
+    (296, "LPSAFEARRAY_Marshal"),
+    (297, "LPSAFEARRAY_Unmarshal"),
+    (298, "VarDecCmpR8"),
+    (299, "VarCyAdd"),
+    (300, "DllUnregisterServer"),
+    (301, "OACreateTypeLib2"),
+    (303, "VarCyMul"),
+    (304, "VarCyMulI4"),
+    (305, "VarCySub"),
+    (306, "VarCyAbs"),
+    (307, "VarCyFix"),
+    (308, "VarCyInt"),
+    (309, "VarCyNeg"),
+    (310, "VarCyRound"),
+    (311, "VarCyCmp"),
+    (312, "VarCyCmpR8"),
+    (313, "VarBstrCat"),
+    (314, "VarBstrCmp"),
+    (315, "VarR8Pow"),
+    (316, "VarR4CmpR8"),
+    (317, "VarR8Round"),
+    (318, "VarCat"),
+    (319, "VarDateFromUdateEx"),
+    (322, "GetRecordInfoFromGuids"),
+    (323, "GetRecordInfoFromTypeInfo"),
+    (325, "SetVarConversionLocaleSetting"),
+    (326, "GetVarConversionLocaleSetting"),
+    (327, "SetOaNoCache"),
+    (329, "VarCyMulI8"),
+    (330, "VarDateFromUdate"),
+    (331, "VarUdateFromDate"),
+    (332, "GetAltMonthNames"),
+    (333, "VarI8FromUI1"),
+    (334, "VarI8FromI2"),
+    (335, "VarI8FromR4"),
+    (336, "VarI8FromR8"),
+    (337, "VarI8FromCy"),
+    (338, "VarI8FromDate"),
+    (339, "VarI8FromStr"),
+    (340, "VarI8FromDisp"),
+    (341, "VarI8FromBool"),
+    (342, "VarI8FromI1"),
+    (343, "VarI8FromUI2"),
+    (344, "VarI8FromUI4"),
+    (345, "VarI8FromDec"),
+    (346, "VarI2FromI8"),
+    (347, "VarI2FromUI8"),
+    (348, "VarI4FromI8"),
+    (349, "VarI4FromUI8"),
+    (360, "VarR4FromI8"),
+    (361, "VarR4FromUI8"),
+    (362, "VarR8FromI8"),
+    (363, "VarR8FromUI8"),
+    (364, "VarDateFromI8"),
+    (365, "VarDateFromUI8"),
+    (366, "VarCyFromI8"),
+    (367, "VarCyFromUI8"),
+    (368, "VarBstrFromI8"),
+    (369, "VarBstrFromUI8"),
+    (370, "VarBoolFromI8"),
+    (371, "VarBoolFromUI8"),
+    (372, "VarUI1FromI8"),
+    (373, "VarUI1FromUI8"),
+    (374, "VarDecFromI8"),
+    (375, "VarDecFromUI8"),
+    (376, "VarI1FromI8"),
+    (377, "VarI1FromUI8"),
+    (378, "VarUI2FromI8"),
+    (379, "VarUI2FromUI8"),
+    (401, "OleLoadPictureEx"),
+    (402, "OleLoadPictureFileEx"),
+    (411, "SafeArrayCreateVector"),
+    (412, "SafeArrayCopyData"),
+    (413, "VectorFromBstr"),
+    (414, "BstrFromVector"),
+    (415, "OleIconToCursor"),
+    (416, "OleCreatePropertyFrameIndirect"),
+    (417, "OleCreatePropertyFrame"),
+    (418, "OleLoadPicture"),
+    (419, "OleCreatePictureIndirect"),
+    (420, "OleCreateFontIndirect"),
+    (421, "OleTranslateColor"),
+    (422, "OleLoadPictureFile"),
+    (423, "OleSavePictureFile"),
+    (424, "OleLoadPicturePath"),
+    (425, "VarUI4FromI8"),
+    (426, "VarUI4FromUI8"),
+    (427, "VarI8FromUI8"),
+    (428, "VarUI8FromI8"),
+    (429, "VarUI8FromUI1"),
+    (430, "VarUI8FromI2"),
+    (431, "VarUI8FromR4"),
+    (432, "VarUI8FromR8"),
+    (433, "VarUI8FromCy"),
+    (434, "VarUI8FromDate"),
+    (435, "VarUI8FromStr"),
+    (436, "VarUI8FromDisp"),
+    (437, "VarUI8FromBool"),
+    (438, "VarUI8FromI1"),
+    (439, "VarUI8FromUI2"),
+    (440, "VarUI8FromUI4"),
+    (441, "VarUI8FromDec"),
+    (442, "RegisterTypeLibForUser"),
+    (443, "UnRegisterTypeLibForUser"),
+];
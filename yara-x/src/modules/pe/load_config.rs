@@ -0,0 +1,67 @@
+/*! Parser for the load configuration directory.
+
+The directory has settings for the loader, most of them related to
+exploit mitigations: the security cookie used by the stack protection
+(/GS), the table of safe exception handlers (/SAFESEH), and the tables
+used by Control Flow Guard. The structure has grown over time, and its
+first field is its size, so fields that don't fit in it are not present.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#load-configuration-layout
+*/
+
+use super::tls::address_at;
+use crate::modules::utils::pe::{Pe, DIRECTORY_LOAD_CONFIG};
+
+/// Fields of the load configuration directory. Fields that don't fit in
+/// the directory's size are `None`.
+pub(super) struct LoadConfig {
+    pub size: u32,
+    pub timestamp: Option<u32>,
+    /// Virtual address of the security cookie.
+    pub security_cookie: Option<u64>,
+    /// Virtual address and number of entries of the safe exception handler
+    /// table. Only 32-bit files use this table.
+    pub se_handler_table: Option<u64>,
+    pub se_handler_count: Option<u64>,
+    pub guard_cf_check_function_pointer: Option<u64>,
+    pub guard_cf_dispatch_function_pointer: Option<u64>,
+    pub guard_cf_function_table: Option<u64>,
+    pub guard_cf_function_count: Option<u64>,
+    pub guard_flags: Option<u32>,
+}
+
+/// Parses the load configuration directory. Returns `None` if the file
+/// doesn't have one.
+pub(super) fn parse(pe: &Pe) -> Option<LoadConfig> {
+    let directory = pe.directory_data(DIRECTORY_LOAD_CONFIG)?;
+    let size = address_at(directory, 0, 4)? as u32;
+    let directory = directory.get(..size as usize).unwrap_or(directory);
+
+    // Offsets of the fields in 32-bit and 64-bit files. Fields with
+    // addresses and sizes are 8 bytes long in 64-bit files.
+    let address = |offset_32: usize, offset_64: usize| {
+        if pe.is_64bit {
+            address_at(directory, offset_64, 8)
+        } else {
+            address_at(directory, offset_32, 4)
+        }
+    };
+
+    let u32_at = |offset_32: usize, offset_64: usize| {
+        let offset = if pe.is_64bit { offset_64 } else { offset_32 };
+        address_at(directory, offset, 4).map(|value| value as u32)
+    };
+
+    Some(LoadConfig {
+        size,
+        timestamp: u32_at(0x04, 0x04),
+        security_cookie: address(0x3c, 0x58),
+        se_handler_table: address(0x40, 0x60),
+        se_handler_count: address(0x44, 0x68),
+        guard_cf_check_function_pointer: address(0x48, 0x70),
+        guard_cf_dispatch_function_pointer: address(0x4c, 0x78),
+        guard_cf_function_table: address(0x50, 0x80),
+        guard_cf_function_count: address(0x54, 0x88),
+        guard_flags: u32_at(0x58, 0x90),
+    })
+}
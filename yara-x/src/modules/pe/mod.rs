@@ -0,0 +1,1897 @@
+/*! YARA module that parses PE (Portable Executable) files.
+
+The headers, the section table and the data directories are read with
+the PE reader in `modules::utils::pe`, which is shared with the `dotnet`
+module. For .NET assemblies this module exposes only the basic fields of
+the CLR header, which are enough for telling managed and native files
+apart. The metadata is parsed by the `dotnet` module.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format
+*/
+
+use protobuf::{EnumOrUnknown, MessageField};
+use sha2::{Digest, Sha256};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::pe::*;
+use crate::modules::utils::digest::{md5_hex, sha1_hex};
+use crate::modules::utils::pe::{
+    Pe, COMIMAGE_FLAGS_32BITPREFERRED, COMIMAGE_FLAGS_32BITREQUIRED,
+    COMIMAGE_FLAGS_ILONLY, COMIMAGE_FLAGS_NATIVE_ENTRYPOINT,
+    COMIMAGE_FLAGS_STRONGNAMESIGNED,
+};
+use crate::modules::utils::{entropy, hex};
+
+mod anomalies;
+mod authenticode;
+mod exceptions;
+mod exports;
+mod imports;
+mod load_config;
+mod packers;
+mod resources;
+mod rich;
+mod tls;
+
+/// IMAGE_FILE_DLL flag in the file header's characteristics.
+const IMAGE_FILE_DLL: i64 = 0x2000;
+
+/// Maximum number of bytes returned by `pe.resource_data`.
+const MAX_RESOURCE_DATA: usize = 16 * 1024 * 1024;
+
+/// Maximum number of bytes returned by `pe.entry_point_bytes`.
+const MAX_ENTRY_POINT_BYTES: i64 = 4096;
+
+/// Size of the DOS header, which is followed by the DOS stub.
+const DOS_HEADER_SIZE: usize = 0x40;
+
+/// Magic number in the optional header of PE32 files.
+const PE32_MAGIC: i64 = 0x10b;
+
+/// IMAGE_SCN_MEM_EXECUTE and IMAGE_SCN_MEM_WRITE flags in the sections'
+/// characteristics.
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+const IMAGE_SCN_MEM_WRITE: u32 = 0x80000000;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> PE {
+    let mut pe = PE::new();
+
+    match Pe::parse(ctx.scanned_data()) {
+        Some(parsed) => {
+            parse(&parsed, &mut pe);
+            let trust_store = authenticode::trust_store(
+                ctx.module_data("pe").unwrap_or_default(),
+            );
+            for s in authenticode::parse(&parsed, &trust_store) {
+                pe.signatures.push(signature(parsed.data, &s));
+            }
+            pe.set_is_signed(!pe.signatures.is_empty());
+            pe.set_number_of_signatures(pe.signatures.len() as i64);
+        }
+        None => pe.set_is_pe(false),
+    }
+
+    pe
+}
+
+/// Returns true if the file is a DLL.
+#[module_export]
+fn is_dll(ctx: &ScanContext) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    Some(pe.characteristics? & IMAGE_FILE_DLL != 0)
+}
+
+/// Returns true if the file is a PE32 file.
+#[module_export]
+fn is_32bit(ctx: &ScanContext) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    Some(pe.opthdr_magic? == PE32_MAGIC)
+}
+
+/// Returns true if the file is a PE32+ file.
+#[module_export]
+fn is_64bit(ctx: &ScanContext) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    Some(pe.opthdr_magic? != PE32_MAGIC)
+}
+
+/// Returns the offset within the scanned data that corresponds to the
+/// given RVA, or undefined if the RVA is not backed by the file.
+#[module_export]
+fn rva_to_offset(ctx: &ScanContext, rva: i64) -> Option<i64> {
+    let parsed = Pe::parse(ctx.scanned_data())?;
+    let offset = parsed.rva_to_offset(rva.try_into().ok()?)?;
+    Some(offset as i64)
+}
+
+/// Returns the checksum of the file, computed in the same way as the
+/// `checksum` field in the optional header.
+#[module_export]
+fn calculate_checksum(ctx: &ScanContext) -> Option<i64> {
+    let parsed = Pe::parse(ctx.scanned_data())?;
+    Some(checksum(&parsed).into())
+}
+
+/// Returns up to `n` bytes starting at the entry point, or undefined if
+/// the entry point is not in the scanned data. Fewer bytes are returned
+/// when the data ends before, and at most 4096 bytes are returned.
+///
+/// The entry point is located in the same way in files and in images
+/// loaded in memory, like those dumped from a process, where the entry
+/// point is at its RVA instead of its file offset.
+#[module_export]
+fn entry_point_bytes(ctx: &ScanContext, n: i64) -> Option<RuntimeString> {
+    if n <= 0 {
+        return None;
+    }
+
+    let parsed = Pe::parse(ctx.scanned_data())?;
+
+    let offset = if parsed.is_mapped() {
+        Some(parsed.entry_point as usize)
+            .filter(|offset| *offset < parsed.data.len())?
+    } else {
+        parsed.entry_point_offset()?
+    };
+
+    let length = (n.min(MAX_ENTRY_POINT_BYTES) as usize)
+        .min(parsed.data.len() - offset);
+
+    Some(RuntimeString::ScannedDataSlice { offset, length })
+}
+
+/// Returns the index of the first section with the given name, or
+/// undefined if there's no such section.
+#[module_export(name = "section_index")]
+fn section_index_name(ctx: &ScanContext, name: RuntimeString) -> Option<i64> {
+    let pe = ctx.module_output::<PE>()?;
+    let name = name.as_bstr(ctx);
+
+    pe.sections
+        .iter()
+        .position(|section| section.name() == name.as_bytes())
+        .map(|index| index as i64)
+}
+
+/// Returns the index of the section that contains the given offset within
+/// the scanned data, or undefined if the offset is not inside a section.
+#[module_export(name = "section_index")]
+fn section_index_offset(ctx: &ScanContext, offset: i64) -> Option<i64> {
+    let pe = ctx.module_output::<PE>()?;
+
+    pe.sections
+        .iter()
+        .position(|section| {
+            let start = section.raw_data_offset();
+            start <= offset && offset < start + section.raw_data_size()
+        })
+        .map(|index| index as i64)
+}
+
+/// Returns true if the file imports the given function from the given
+/// DLL. DLL and function names are case-insensitive.
+#[module_export(name = "imports")]
+fn imports_function(
+    ctx: &ScanContext,
+    dll_name: RuntimeString,
+    function_name: RuntimeString,
+) -> Option<bool> {
+    let function_name = function_name.as_bstr(ctx);
+    Some(imported_functions(ctx, dll_name)?.any(|function| {
+        function.name.as_ref().map_or(false, |name| {
+            name.as_bytes().eq_ignore_ascii_case(function_name.as_bytes())
+        })
+    }))
+}
+
+/// Returns true if the file imports the function with the given ordinal
+/// from the given DLL. The DLL name is case-insensitive.
+#[module_export(name = "imports")]
+fn imports_ordinal(
+    ctx: &ScanContext,
+    dll_name: RuntimeString,
+    ordinal: i64,
+) -> Option<bool> {
+    Some(
+        imported_functions(ctx, dll_name)?
+            .any(|function| function.ordinal == Some(ordinal)),
+    )
+}
+
+/// Returns the number of functions imported from the given DLL. The DLL
+/// name is case-insensitive.
+#[module_export(name = "imports")]
+fn imports_dll(ctx: &ScanContext, dll_name: RuntimeString) -> Option<i64> {
+    Some(imported_functions(ctx, dll_name)?.count() as i64)
+}
+
+/// Returns an iterator over the functions imported from the given DLL.
+/// The functions imported from API sets are also imported from the DLLs
+/// that host them, so `imports("kernelbase.dll", ...)` includes the ones
+/// imported from `api-ms-win-core-*` API sets.
+fn imported_functions<'a>(
+    ctx: &'a ScanContext,
+    dll_name: RuntimeString,
+) -> Option<impl Iterator<Item = &'a PeImportedFunction>> {
+    let pe = ctx.module_output::<PE>()?;
+    let dll_name = dll_name.as_bstr(ctx);
+    Some(
+        pe.import_details
+            .iter()
+            .filter(move |dll| {
+                [dll.library_name.as_ref(), dll.host_library_name.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .any(|name| {
+                        name.as_bytes()
+                            .eq_ignore_ascii_case(dll_name.as_bytes())
+                    })
+            })
+            .flat_map(|dll| dll.functions.iter()),
+    )
+}
+
+/// Returns true if the file exports a function with the given name.
+#[module_export(name = "exports")]
+fn exports_name(
+    ctx: &ScanContext,
+    function_name: RuntimeString,
+) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    let function_name = function_name.as_bstr(ctx);
+    Some(
+        pe.export_details
+            .iter()
+            .filter_map(|export| export.name.as_ref())
+            .any(|name| name.as_bytes() == function_name.as_bytes()),
+    )
+}
+
+/// Returns true if the file exports a function with the given ordinal.
+#[module_export(name = "exports")]
+fn exports_ordinal(ctx: &ScanContext, ordinal: i64) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    Some(
+        pe.export_details.iter().any(|export| export.ordinal == Some(ordinal)),
+    )
+}
+
+/// Returns the import hash (imphash) of the file, as a hex string. The
+/// hash is the same computed by `pefile` and YARA 4.x. Undefined if the
+/// file doesn't import any function.
+#[module_export]
+fn imphash(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let pe = ctx.module_output::<PE>()?;
+    let digest = imports::imphash(&pe.import_details)?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns the MD5 of a comma-separated list with the names of the
+/// exported functions, in lowercase, as a hex string. Undefined if the
+/// file doesn't export any function by name.
+#[module_export]
+fn exphash(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let pe = ctx.module_output::<PE>()?;
+    let digest = exports::exphash(&pe.export_details)?;
+    Some(RuntimeString::from_bytes(ctx, digest))
+}
+
+/// Returns true if any resource has the given language ID (e.g: 0x0409
+/// for English, United States).
+#[module_export]
+fn locale(ctx: &ScanContext, locale_id: i64) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    Some(pe.resource_languages.contains(&locale_id))
+}
+
+/// Returns true if any resource has the given primary language, which is
+/// the lowest 10 bits of the language ID (e.g: 0x09 for English).
+#[module_export]
+fn language(ctx: &ScanContext, language_id: i64) -> Option<bool> {
+    let pe = ctx.module_output::<PE>()?;
+    Some(
+        pe.resource_languages
+            .iter()
+            .any(|locale_id| locale_id & 0x3ff == language_id),
+    )
+}
+
+/// Returns the data of the first resource with the given type and ID,
+/// regardless of its language. Undefined if there's no such resource, or
+/// its data is not in the file. Only the first 16MB are returned for
+/// larger resources.
+#[module_export(name = "resource_data")]
+fn resource_data_type_id(
+    ctx: &ScanContext,
+    type_: i64,
+    id: i64,
+) -> Option<RuntimeString> {
+    resource_data(ctx, type_, id, None)
+}
+
+/// Like `resource_data(type, id)`, but for the resource with the given
+/// language ID.
+#[module_export(name = "resource_data")]
+fn resource_data_type_id_language(
+    ctx: &ScanContext,
+    type_: i64,
+    id: i64,
+    language: i64,
+) -> Option<RuntimeString> {
+    resource_data(ctx, type_, id, Some(language))
+}
+
+fn resource_data(
+    ctx: &ScanContext,
+    type_: i64,
+    id: i64,
+    language: Option<i64>,
+) -> Option<RuntimeString> {
+    let pe = ctx.module_output::<PE>()?;
+
+    let resource = pe.resources.iter().find(|resource| {
+        resource.type_ == Some(type_)
+            && resource.id == Some(id)
+            && language
+                .map_or(true, |language| resource.language() == language)
+    })?;
+
+    let offset: usize = resource.offset?.try_into().ok()?;
+    let length: usize = resource.length?.try_into().ok()?;
+
+    Some(RuntimeString::ScannedDataSlice {
+        offset,
+        length: length.min(MAX_RESOURCE_DATA),
+    })
+}
+
+/// Returns the total number of objects produced by the tool with the given
+/// product ID, according to the Rich header.
+#[module_export(name = "rich_signature.toolid")]
+fn rich_toolid(ctx: &ScanContext, toolid: i64) -> Option<i64> {
+    rich_count(ctx, Some(toolid), None)
+}
+
+/// Returns the number of objects produced by the tool with the given
+/// product ID and build number, according to the Rich header.
+#[module_export(name = "rich_signature.toolid")]
+fn rich_toolid_version(
+    ctx: &ScanContext,
+    toolid: i64,
+    version: i64,
+) -> Option<i64> {
+    rich_count(ctx, Some(toolid), Some(version))
+}
+
+/// Returns the total number of objects produced by tools with the given
+/// build number, according to the Rich header.
+#[module_export(name = "rich_signature.version")]
+fn rich_version(ctx: &ScanContext, version: i64) -> Option<i64> {
+    rich_count(ctx, None, Some(version))
+}
+
+/// Returns the number of objects produced by the tool with the given
+/// build number and product ID, according to the Rich header.
+#[module_export(name = "rich_signature.version")]
+fn rich_version_toolid(
+    ctx: &ScanContext,
+    version: i64,
+    toolid: i64,
+) -> Option<i64> {
+    rich_count(ctx, Some(toolid), Some(version))
+}
+
+/// Adds up the number of objects produced by the tools in the Rich header
+/// that have the given product ID and build number, if any.
+fn rich_count(
+    ctx: &ScanContext,
+    toolid: Option<i64>,
+    version: Option<i64>,
+) -> Option<i64> {
+    let rich = ctx.module_output::<PE>()?.rich_signature.as_ref()?;
+    Some(
+        rich.tools
+            .iter()
+            .filter(|tool| toolid.map_or(true, |id| tool.toolid() == id))
+            .filter(|tool| version.map_or(true, |v| tool.version() == v))
+            .map(|tool| tool.times())
+            .sum(),
+    )
+}
+
+fn parse(parsed: &Pe, pe: &mut PE) {
+    pe.set_is_pe(true);
+    pe.machine = Some(EnumOrUnknown::from_i32(parsed.machine.into()));
+    pe.subsystem = Some(EnumOrUnknown::from_i32(parsed.subsystem.into()));
+    pe.set_timestamp(parsed.timestamp.into());
+    pe.set_characteristics(parsed.characteristics.into());
+    pe.set_number_of_sections(parsed.number_of_sections.into());
+    pe.set_pointer_to_symbol_table(parsed.pointer_to_symbol_table.into());
+    pe.set_number_of_symbols(parsed.number_of_symbols.into());
+    pe.set_size_of_optional_header(parsed.optional_header_size.into());
+    pe.set_opthdr_magic(if parsed.is_64bit { 0x20b } else { PE32_MAGIC });
+    pe.linker_version = version(
+        parsed.linker_version.0.into(),
+        parsed.linker_version.1.into(),
+    );
+    pe.os_version = version(parsed.os_version.0, parsed.os_version.1);
+    pe.image_version = version(parsed.image_version.0, parsed.image_version.1);
+    pe.subsystem_version =
+        version(parsed.subsystem_version.0, parsed.subsystem_version.1);
+    pe.entry_point = parsed.entry_point_offset().map(|offset| offset as i64);
+    pe.set_entry_point_raw(parsed.entry_point.into());
+    pe.set_image_base(parsed.image_base as i64);
+    pe.set_section_alignment(parsed.section_alignment.into());
+    pe.set_file_alignment(parsed.file_alignment.into());
+    pe.set_size_of_image(parsed.size_of_image.into());
+    pe.set_size_of_headers(parsed.size_of_headers.into());
+    pe.set_checksum(parsed.checksum.into());
+    pe.set_dll_characteristics(parsed.dll_characteristics.into());
+    pe.set_number_of_rva_and_sizes(parsed.number_of_rva_and_sizes.into());
+
+    for (rva, size) in &parsed.directories {
+        let mut directory = DataDirectory::new();
+        directory.set_virtual_address((*rva).into());
+        directory.set_size((*size).into());
+        pe.data_directories.push(directory);
+    }
+
+    for s in &parsed.sections {
+        let mut section = PeSection::new();
+        section.set_name(s.name().to_vec());
+        section.set_normalized_name(packers::normalized_name(s.name()));
+        section.set_virtual_address(s.virtual_address.into());
+        section.set_virtual_size(s.virtual_size.into());
+        section.set_raw_data_offset(s.raw_data_offset.into());
+        section.set_raw_data_size(s.raw_data_size.into());
+        section.set_characteristics(s.characteristics.into());
+        section.entropy = parsed
+            .data
+            .get(s.raw_data_offset as usize..)
+            .map(|data| &data[..data.len().min(s.raw_data_size as usize)])
+            .filter(|data| !data.is_empty())
+            .map(entropy);
+        section.set_virtual_size_exceeds_raw(s.virtual_size > s.raw_data_size);
+        section.set_raw_size_exceeds_virtual(
+            s.virtual_size != 0
+                && s.raw_data_size as u64
+                    > align(s.virtual_size, parsed.file_alignment),
+        );
+        section.set_is_writable_executable(
+            s.characteristics & IMAGE_SCN_MEM_WRITE != 0
+                && s.characteristics & IMAGE_SCN_MEM_EXECUTE != 0,
+        );
+        pe.sections.push(section);
+    }
+
+    pe.packer_hint = packers::packer_hint(
+        pe.sections.iter().map(|section| section.normalized_name()),
+    )
+    .map(|packer| packer.to_string());
+
+    pe.set_number_of_writable_executable_sections(
+        pe.sections
+            .iter()
+            .filter(|section| section.is_writable_executable())
+            .count() as i64,
+    );
+
+    pe.set_checksum_correct(checksum(parsed) == parsed.checksum);
+
+    if let Some(header) = rich::parse(parsed) {
+        let mut rich = RichSignature::new();
+        rich.set_offset(header.offset as i64);
+        rich.set_length(header.raw_data.len() as i64);
+        rich.set_key(header.key.into());
+        rich.set_raw_data(header.raw_data.to_vec());
+        rich.set_hash(md5_hex(&header.clear_data));
+        rich.set_pv_hash(md5_hex(&header.pv_data()));
+        for (toolid, version, times) in &header.tools {
+            let mut tool = RichTool::new();
+            tool.set_toolid((*toolid).into());
+            tool.set_version((*version).into());
+            tool.set_times((*times).into());
+            rich.tools.push(tool);
+        }
+        rich.set_clear_data(header.clear_data);
+        pe.rich_signature = MessageField::some(rich);
+    }
+
+    let dlls = imports::parse(parsed);
+
+    pe.import_details = dlls.iter().map(import).collect();
+    pe.set_number_of_imports(dlls.len() as i64);
+    pe.set_number_of_imported_functions(
+        dlls.iter().map(|dll| dll.functions.len() as i64).sum(),
+    );
+
+    let delayed_dlls = imports::parse_delayed(parsed);
+
+    pe.delayed_import_details = delayed_dlls.iter().map(import).collect();
+    pe.set_number_of_delayed_imports(delayed_dlls.len() as i64);
+    pe.set_number_of_delayed_imported_functions(
+        delayed_dlls.iter().map(|dll| dll.functions.len() as i64).sum(),
+    );
+
+    for b in imports::parse_bound(parsed) {
+        let mut bound_import = PeBoundImport::new();
+        bound_import.set_library_name(b.name);
+        bound_import.set_timestamp(b.timestamp.into());
+        bound_import.forwarders = b.forwarders;
+        pe.bound_imports.push(bound_import);
+    }
+
+    if let Some(exports) = exports::parse(parsed) {
+        pe.dll_name = exports.dll_name;
+        pe.set_export_timestamp(exports.timestamp.into());
+        for function in exports.functions {
+            let mut export = PeExport::new();
+            export.name = function.name;
+            export.forward_name = function.forward_name;
+            export.set_ordinal(function.ordinal.into());
+            export.set_rva(function.rva.into());
+            export.offset =
+                parsed.rva_to_offset(function.rva).map(|offset| offset as i64);
+            pe.export_details.push(export);
+        }
+    }
+
+    pe.set_number_of_exports(pe.export_details.len() as i64);
+
+    if let Some(tree) = resources::parse(parsed) {
+        pe.set_resource_timestamp(tree.timestamp.into());
+        pe.resource_version = version(tree.version.0, tree.version.1);
+        for r in &tree.resources {
+            let mut resource = PeResource::new();
+            resource.set_rva(r.rva.into());
+            resource.offset = r.offset.map(|offset| offset as i64);
+            resource.set_length(r.size.into());
+            resource.type_ = r.type_id.map(|id| id.into());
+            resource.id = r.id.map(|id| id.into());
+            resource.set_language(r.language.into());
+            resource.type_string = r.type_name.map(|name| name.to_vec());
+            resource.name_string = r.name.map(|name| name.to_vec());
+            pe.resources.push(resource);
+        }
+        pe.version_info =
+            resources::version_info(parsed, &tree).into_iter().collect();
+        pe.icon_hash = resources::icon_hash(parsed, &tree);
+    }
+
+    pe.set_number_of_resources(pe.resources.len() as i64);
+
+    let mut languages: Vec<i64> =
+        pe.resources.iter().map(|resource| resource.language()).collect();
+
+    languages.sort_unstable();
+    languages.dedup();
+
+    pe.set_number_of_resource_languages(languages.len() as i64);
+    pe.resource_languages = languages;
+
+    for f in exceptions::parse(parsed) {
+        let mut function = PeRuntimeFunction::new();
+        function.set_begin_address(f.begin.into());
+        function.end_address = f.end.map(|end| end.into());
+        function.set_unwind_info(f.unwind_info.into());
+        pe.exception_functions.push(function);
+    }
+
+    pe.set_number_of_exception_functions(pe.exception_functions.len() as i64);
+
+    pe.tls_callbacks =
+        tls::callbacks(parsed).into_iter().map(|va| va as i64).collect();
+    pe.set_number_of_tls_callbacks(pe.tls_callbacks.len() as i64);
+
+    if let Some(l) = load_config::parse(parsed) {
+        let mut load_config = LoadConfig::new();
+        load_config.set_size(l.size.into());
+        load_config.timestamp = l.timestamp.map(|t| t.into());
+        load_config.security_cookie = l.security_cookie.map(|va| va as i64);
+        load_config.se_handler_table = l.se_handler_table.map(|va| va as i64);
+        load_config.se_handler_count = l.se_handler_count.map(|n| n as i64);
+        load_config.guard_cf_check_function_pointer =
+            l.guard_cf_check_function_pointer.map(|va| va as i64);
+        load_config.guard_cf_dispatch_function_pointer =
+            l.guard_cf_dispatch_function_pointer.map(|va| va as i64);
+        load_config.guard_cf_function_table =
+            l.guard_cf_function_table.map(|va| va as i64);
+        load_config.guard_cf_function_count =
+            l.guard_cf_function_count.map(|n| n as i64);
+        load_config.guard_flags = l.guard_flags.map(|flags| flags.into());
+        pe.load_config = MessageField::some(load_config);
+    }
+
+    // The DOS stub ends where the Rich header starts, if any.
+    let stub_end = pe
+        .rich_signature
+        .offset
+        .map_or(parsed.pe_offset, |offset| offset as usize);
+
+    pe.set_e_lfanew(parsed.pe_offset as i64);
+    pe.dos_stub_hash = parsed
+        .data
+        .get(DOS_HEADER_SIZE..stub_end)
+        .filter(|stub| !stub.is_empty())
+        .map(md5_hex);
+    pe.set_header_anomalies(anomalies::header_anomalies(parsed).into());
+
+    let clr_header = parsed.clr_header();
+
+    pe.set_is_dotnet(clr_header.is_some());
+
+    if let Some(header) = clr_header {
+        let mut clr = ClrHeader::new();
+        clr.runtime_version = version(
+            header.major_runtime_version,
+            header.minor_runtime_version,
+        );
+        clr.metadata_version =
+            parsed.metadata_root(&header).map(|root| root.version);
+        clr.set_flags(header.flags.into());
+        clr.set_is_il_only(header.flags & COMIMAGE_FLAGS_ILONLY != 0);
+        clr.set_is_32bit_required(
+            header.flags & COMIMAGE_FLAGS_32BITREQUIRED != 0,
+        );
+        clr.set_is_32bit_preferred(
+            header.flags & COMIMAGE_FLAGS_32BITPREFERRED != 0,
+        );
+        clr.set_is_strong_name_signed(
+            header.flags & COMIMAGE_FLAGS_STRONGNAMESIGNED != 0,
+        );
+        clr.set_entry_point_token(header.entry_point_token.into());
+        clr.set_has_native_entry_point(
+            header.flags & COMIMAGE_FLAGS_NATIVE_ENTRYPOINT != 0,
+        );
+        pe.clr_header = MessageField::some(clr);
+    }
+}
+
+/// Computes the checksum of the file, which is the sum of all the 16-bit
+/// words in the file, excluding the checksum field, with the carries
+/// folded into the lowest 16 bits, plus the file's size. This is the same
+/// algorithm used by `CheckSumMappedFile` and `pefile`.
+fn checksum(parsed: &Pe) -> u32 {
+    let checksum_offset = parsed.optional_header_offset + 64;
+    let mut sum: u64 = 0;
+
+    for (i, chunk) in parsed.data.chunks(4).enumerate() {
+        if i == checksum_offset / 4 {
+            continue;
+        }
+        // The last chunk is padded with zeroes.
+        let mut dword = [0; 4];
+        dword[..chunk.len()].copy_from_slice(chunk);
+        sum = (sum & 0xffffffff)
+            + u32::from_le_bytes(dword) as u64
+            + (sum >> 32);
+        if sum > 1 << 32 {
+            sum = (sum & 0xffffffff) + (sum >> 32);
+        }
+    }
+
+    sum = (sum & 0xffff) + (sum >> 16);
+    sum += sum >> 16;
+    sum &= 0xffff;
+
+    (sum as u32).wrapping_add(parsed.data.len() as u32)
+}
+
+/// Rounds `value` up to a multiple of `alignment`.
+fn align(value: u32, alignment: u32) -> u64 {
+    if alignment == 0 {
+        return value.into();
+    }
+    let alignment = alignment as u64;
+    (value as u64 + alignment - 1) / alignment * alignment
+}
+
+fn import(dll: &imports::ImportedDll) -> PeImport {
+    let mut import = PeImport::new();
+    import.set_library_name(dll.name.clone());
+    import.host_library_name = imports::api_set_host(&dll.name);
+    import.set_number_of_functions(dll.functions.len() as i64);
+    for f in &dll.functions {
+        let mut function = PeImportedFunction::new();
+        function.name = f.name.clone();
+        function.ordinal = f.ordinal.map(|ordinal| ordinal.into());
+        function.set_rva(f.rva.into());
+        import.functions.push(function);
+    }
+    import
+}
+
+fn signature(data: &[u8], s: &authenticode::Signature) -> PeSignature {
+    let mut signature = PeSignature::new();
+    if let Some(cert) = s.chain.first() {
+        signature.set_issuer(cert.issuer.clone());
+        signature.set_subject(cert.subject.clone());
+        signature.set_version(cert.version.into());
+        signature.set_algorithm(cert.algorithm.clone());
+        signature.set_algorithm_oid(cert.algorithm_oid.clone());
+        signature.set_serial(serial(cert.serial));
+        signature.not_before = cert.not_before;
+        signature.not_after = cert.not_after;
+    }
+    signature.digest_alg =
+        s.digest_algorithm.map(|algorithm| algorithm.name().to_string());
+    signature.set_digest(hex(s.digest));
+    signature.set_file_digest(hex(&s.file_digest));
+    signature.set_digest_matches(s.digest_matches);
+    signature.set_signature_valid(s.signature_valid);
+    signature.set_trusted(s.trusted);
+    signature.set_verified(s.verified);
+    signature.certificates =
+        s.certificates.iter().map(|cert| certificate(data, cert)).collect();
+    signature.set_number_of_certificates(signature.certificates.len() as i64);
+    signature.chain =
+        s.chain.iter().map(|cert| certificate(data, cert)).collect();
+    for c in &s.countersignatures {
+        let mut countersignature = PeCountersignature::new();
+        countersignature.set_verified(c.verified);
+        countersignature.sign_time = c.sign_time;
+        countersignature.digest_alg =
+            c.digest_algorithm.map(|algorithm| algorithm.name().to_string());
+        countersignature.set_digest(hex(c.digest));
+        countersignature.chain =
+            c.chain.iter().map(|cert| certificate(data, cert)).collect();
+        signature.countersignatures.push(countersignature);
+    }
+    signature.set_number_of_countersignatures(
+        signature.countersignatures.len() as i64,
+    );
+    signature
+}
+
+/// Returns the information about a certificate. `data` is the scanned
+/// data, which usually contains the certificate.
+fn certificate(
+    data: &[u8],
+    cert: &authenticode::Certificate,
+) -> PeCertificate {
+    let mut certificate = PeCertificate::new();
+    certificate.set_issuer(cert.issuer.clone());
+    certificate.set_subject(cert.subject.clone());
+    certificate.set_version(cert.version.into());
+    certificate.set_algorithm(cert.algorithm.clone());
+    certificate.set_algorithm_oid(cert.algorithm_oid.clone());
+    certificate.set_serial(serial(cert.serial));
+    certificate.not_before = cert.not_before;
+    certificate.not_after = cert.not_after;
+    let reversed: Vec<u8> = cert.serial.iter().rev().copied().collect();
+    certificate.set_serial_reversed(serial(&reversed));
+    certificate.set_thumbprint_sha1(sha1_hex(cert.raw));
+    certificate.set_thumbprint_sha256(hex(&Sha256::digest(cert.raw)));
+    certificate.offset = offset_in(data, cert.raw).map(|offset| offset as i64);
+    certificate.set_length(cert.raw.len() as i64);
+    certificate
+}
+
+/// Returns the offset of `slice` within `data`, or `None` if `slice` is
+/// not a part of `data`.
+fn offset_in(data: &[u8], slice: &[u8]) -> Option<usize> {
+    let offset =
+        (slice.as_ptr() as usize).checked_sub(data.as_ptr() as usize)?;
+    (offset + slice.len() <= data.len()).then_some(offset)
+}
+
+/// Formats a serial number as colon-separated hex bytes, like YARA does.
+fn serial(serial: &[u8]) -> String {
+    serial.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+fn version(major: u16, minor: u16) -> MessageField<Version> {
+    let mut version = Version::new();
+    version.set_major(major.into());
+    version.set_minor(minor.into());
+    MessageField::some(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use crate::modules::utils::digest::sha1_hex;
+    use crate::modules::utils::hex;
+    use crate::modules::utils::pe::{TestPe, TestSection};
+
+    /// Builds a .NET assembly, with the CLR header and the metadata root
+    /// at the start of the .text section.
+    fn dotnet() -> Vec<u8> {
+        let mut metadata = b"BSJB\x01\x00\x01\x00\x00\x00\x00\x00".to_vec();
+        metadata.extend(12_u32.to_le_bytes());
+        metadata.extend(b"v4.0.30319\0\0");
+        metadata.extend([0; 4]);
+
+        let mut text = Vec::new();
+        text.extend(72_u32.to_le_bytes());
+        text.extend(2_u16.to_le_bytes());
+        text.extend(5_u16.to_le_bytes());
+        text.extend(0x2048_u32.to_le_bytes());
+        text.extend((metadata.len() as u32).to_le_bytes());
+        text.extend(0x20001_u32.to_le_bytes());
+        text.extend(0x06000001_u32.to_le_bytes());
+        text.resize(72, 0);
+        text.extend(metadata);
+
+        TestPe {
+            entry_point: 0x2100,
+            directories: vec![(14, 0x2000, 72)],
+            sections: vec![TestSection::new(b".text", 0x2000, text)],
+            ..Default::default()
+        }
+        .build()
+    }
+
+    /// Writes `values` at the given offset, as little-endian 32-bit
+    /// integers.
+    fn put(data: &mut [u8], offset: usize, values: &[u32]) {
+        for (i, value) in values.iter().enumerate() {
+            let offset = offset + i * 4;
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Builds a DLL with an import directory and an export directory, both
+    /// in a .rdata section at RVA 0x1000.
+    fn imports_exports() -> Vec<u8> {
+        const RVA: u32 = 0x1000;
+
+        fn string(data: &mut Vec<u8>, s: &[u8]) -> u32 {
+            let rva = RVA + data.len() as u32;
+            data.extend(s);
+            data.push(0);
+            rva
+        }
+
+        let mut rdata = vec![0; 0x180];
+
+        let create_file = string(&mut rdata, b"\x00\x00CreateFileA");
+        let exit_process = string(&mut rdata, b"\x00\x00ExitProcess");
+        let kernel32 = string(&mut rdata, b"KERNEL32.dll");
+        let ws2_32 = string(&mut rdata, b"WS2_32.dll");
+        let dll_name = string(&mut rdata, b"evil.dll");
+        let alpha = string(&mut rdata, b"Alpha");
+        let beta = string(&mut rdata, b"beta");
+        let forwarder = string(&mut rdata, b"NTDLL.RtlFoo");
+
+        // Import descriptors. The second one doesn't have a lookup table,
+        // so the import address table is used instead.
+        put(&mut rdata, 0x00, &[RVA + 0x40, 0, 0, kernel32, RVA + 0x60]);
+        put(&mut rdata, 0x14, &[0, 0, 0, ws2_32, RVA + 0x50]);
+        // Lookup table and import address table of KERNEL32.dll.
+        put(&mut rdata, 0x40, &[create_file, exit_process, 0]);
+        put(&mut rdata, 0x60, &[create_file, exit_process, 0]);
+        // Import address table of WS2_32.dll, with functions imported by
+        // ordinal: socket (23), WSAStartup (115) and an unknown one.
+        put(&mut rdata, 0x50, &[0x80000017, 0x80000073, 0x80000bb8, 0]);
+
+        // Export directory, with three functions starting at ordinal 5. The
+        // second one is exported only by ordinal, and it's forwarded to
+        // NTDLL.RtlFoo. The directory's size covers the forwarder's name.
+        put(&mut rdata, 0x80, &[0, 0x5f5e1000, 0, dll_name, 5, 3, 2]);
+        put(&mut rdata, 0x9c, &[RVA + 0xb0, RVA + 0xc0, RVA + 0xd0]);
+        put(&mut rdata, 0xb0, &[0x1010, forwarder, 0x1030]);
+        let exports_size = forwarder + 13 - (RVA + 0x80);
+        put(&mut rdata, 0xc0, &[alpha, beta]);
+        put(&mut rdata, 0xd0, &[0x00020000]);
+
+        // Delay-load import descriptor for user32.dll, with a function
+        // imported by name and another by ordinal.
+        let user32 = string(&mut rdata, b"user32.dll");
+        let message_box = string(&mut rdata, b"\x00\x00MessageBoxA");
+        put(&mut rdata, 0xe0, &[1, user32, 0, RVA + 0x120, RVA + 0x130]);
+        put(&mut rdata, 0x130, &[message_box, 0x80000005, 0]);
+
+        // Bound import directory, with KERNEL32.dll forwarding functions to
+        // NTDLL.DLL. The names are at offsets relative to the directory.
+        put(
+            &mut rdata,
+            0x140,
+            &[0x12345678, 0x18 | 1 << 16, 0x9abcdef0, 0x25],
+        );
+        rdata[0x158..0x165].copy_from_slice(b"KERNEL32.dll\0");
+        rdata[0x165..0x16f].copy_from_slice(b"NTDLL.DLL\0");
+
+        TestPe {
+            characteristics: 0x2102,
+            directories: vec![
+                (0, RVA + 0x80, exports_size),
+                (1, RVA, 40),
+                (11, RVA + 0x140, 0x40),
+                (13, RVA + 0xe0, 64),
+            ],
+            sections: vec![TestSection::new(b".rdata", RVA, rdata)],
+            ..Default::default()
+        }
+        .build()
+    }
+
+    /// Builds a file with a resource directory in a .rsrc section at RVA
+    /// 0x1000. There's a single resource for each type.
+    fn resources() -> Vec<u8> {
+        const RVA: u32 = 0x1000;
+
+        // Block in the version information, with a header, a key, a value
+        // and children.
+        fn block(
+            key: &str,
+            text: bool,
+            value: &[u8],
+            children: &[u8],
+        ) -> Vec<u8> {
+            let mut block = vec![0; 6];
+            block.extend(
+                key.encode_utf16().chain([0]).flat_map(u16::to_le_bytes),
+            );
+            block.resize((block.len() + 3) & !3, 0);
+            block.extend(value);
+            block.resize((block.len() + 3) & !3, 0);
+            block.extend(children);
+            let value_len = if text { value.len() / 2 } else { value.len() };
+            let len = block.len() as u16;
+            block[0..2].copy_from_slice(&len.to_le_bytes());
+            block[2..4].copy_from_slice(&(value_len as u16).to_le_bytes());
+            block[4..6].copy_from_slice(&(text as u16).to_le_bytes());
+            block
+        }
+
+        fn table(language: &str, strings: &[(&str, &str)]) -> Vec<u8> {
+            let mut children = Vec::new();
+            for (key, value) in strings {
+                let value: Vec<u8> = value
+                    .encode_utf16()
+                    .chain([0])
+                    .flat_map(u16::to_le_bytes)
+                    .collect();
+                children.extend(block(key, true, &value, &[]));
+                children.resize((children.len() + 3) & !3, 0);
+            }
+            block(language, true, &[], &children)
+        }
+
+        // The first table wins when a key appears in both of them.
+        let mut tables = table(
+            "040904b0",
+            &[("CompanyName", "ACME"), ("ProductName", "Road Runner")],
+        );
+        tables.extend(table("040c04b0", &[("CompanyName", "ACME France")]));
+
+        let version_info = block(
+            "VS_VERSION_INFO",
+            false,
+            &[0; 52],
+            &block("StringFileInfo", true, &[], &tables),
+        );
+
+        // Icon group with two icons, where the second icon comes first.
+        let mut group = vec![0, 0, 1, 0, 2, 0];
+        for id in [2_u16, 1] {
+            group.extend([0; 12]);
+            group.extend(id.to_le_bytes());
+        }
+
+        // Type, ID, language and data of each resource. The type of the
+        // first one is the string at offset 0x200.
+        let leaves: [(u32, u32, u32, &[u8]); 5] = [
+            (0x80000200, 7, 0xc09, b"custom"),
+            (3, 1, 0x409, b"first icon"),
+            (3, 2, 0x409, b"second icon"),
+            (14, 101, 0x409, &group),
+            (16, 1, 0x409, &version_info),
+        ];
+
+        let mut rsrc = vec![0; 0x240];
+
+        // Root directory, with one named entry and four entries with IDs.
+        put(&mut rsrc, 0, &[0, 0x5f000000, 4, 1 | 4 << 16]);
+
+        for (i, (type_, id, language, data)) in leaves.iter().enumerate() {
+            // Each type has a directory with the IDs, followed by a
+            // directory with the languages and the data entry.
+            let dir = 0x40 + i * 0x40;
+            let rva = RVA + rsrc.len() as u32;
+            put(&mut rsrc, 16 + i * 8, &[*type_, 0x80000000 | dir as u32]);
+            put(
+                &mut rsrc,
+                dir + 12,
+                &[1 << 16, *id, 0x80000000 | (dir as u32 + 0x18)],
+            );
+            put(
+                &mut rsrc,
+                dir + 0x18 + 12,
+                &[1 << 16, *language, dir as u32 + 0x30],
+            );
+            put(&mut rsrc, dir + 0x30, &[rva, data.len() as u32]);
+            rsrc.extend(*data);
+            rsrc.resize((rsrc.len() + 7) & !7, 0);
+        }
+
+        rsrc[0x200..0x202].copy_from_slice(&6_u16.to_le_bytes());
+        for (i, unit) in "MYTYPE".encode_utf16().enumerate() {
+            rsrc[0x202 + i * 2..0x204 + i * 2]
+                .copy_from_slice(&unit.to_le_bytes());
+        }
+
+        let size = rsrc.len() as u32;
+
+        TestPe {
+            directories: vec![(2, RVA, size)],
+            sections: vec![TestSection::new(b".rsrc", RVA, rsrc)],
+            ..Default::default()
+        }
+        .build()
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "pe"
+                rule headers {
+                  condition:
+                    pe.is_pe and
+                    pe.machine == pe.Machine.AMD64 and
+                    pe.subsystem == pe.Subsystem.WINDOWS_CUI and
+                    pe.timestamp == 1700000000 and
+                    pe.is_64bit() and not pe.is_32bit() and
+                    pe.is_dll() and
+                    pe.image_base == 0x140000000 and
+                    pe.size_of_image == 0x3004 and
+                    pe.linker_version.major == 14 and
+                    pe.os_version.major == 6 and
+                    pe.number_of_rva_and_sizes == 16 and
+                    pe.entry_point_raw == 0x1010 and
+                    pe.entry_point == 0x410 and
+                    uint8(pe.entry_point) == 0xc3 and
+                    pe.e_lfanew == 0xa8 and
+                    pe.dos_stub_hash == "3b5d3c7d207e37dceeedd301e35e2e58" and
+                    pe.header_anomalies == 0
+                }
+                rule sections {
+                  condition:
+                    pe.number_of_sections == 2 and
+                    pe.sections[0].name == ".text" and
+                    pe.sections[0].virtual_address == 0x1000 and
+                    pe.sections[0].raw_data_offset == 0x400 and
+                    pe.sections[0].raw_data_size == 0x20 and
+                    pe.sections[0].characteristics == 0x60000020 and
+                    pe.sections[0].normalized_name == ".text" and
+                    pe.sections[1].name == ".data" and
+                    not defined pe.packer_hint and
+                    pe.sections[1].virtual_size == 0x1004 and
+                    pe.section_index(".data") == 1 and
+                    pe.section_index(0x605) == 1 and
+                    not defined pe.section_index(".rsrc") and
+                    pe.rva_to_offset(0x2002) == 0x602 and
+                    not defined pe.rva_to_offset(0x4000)
+                }
+                rule native { condition: not pe.is_dotnet }
+                rule exceptions {
+                  condition:
+                    pe.number_of_exception_functions == 2 and
+                    pe.exception_functions[0].begin_address == 0x1000 and
+                    pe.exception_functions[0].end_address == 0x1010 and
+                    pe.exception_functions[1].begin_address == 0x1010 and
+                    pe.exception_functions[1].unwind_info == 0x2100
+                }
+                rule checksum_sections {
+                  condition:
+                    pe.checksum == 0x26e5 and
+                    pe.calculate_checksum() == 0x26e5 and
+                    pe.checksum_correct and
+                    pe.sections[0].entropy > 0.2 and
+                    pe.sections[0].entropy < 0.21 and
+                    not pe.sections[0].virtual_size_exceeds_raw and
+                    pe.sections[1].virtual_size_exceeds_raw and
+                    not pe.sections[0].raw_size_exceeds_virtual and
+                    not pe.sections[0].is_writable_executable and
+                    pe.sections[1].is_writable_executable and
+                    pe.number_of_writable_executable_sections == 1
+                }
+                rule tls_load_config {
+                  condition:
+                    pe.number_of_tls_callbacks == 2 and
+                    pe.tls_callbacks[0] == 0x140001000 and
+                    pe.tls_callbacks[1] == 0x140001008 and
+                    pe.load_config.size == 0x94 and
+                    pe.load_config.security_cookie == 0x140002100 and
+                    pe.load_config.se_handler_count == 0 and
+                    pe.load_config.guard_flags & pe.GuardFlags.GUARD_CF_INSTRUMENTED != 0 and
+                    pe.load_config.guard_flags & pe.GuardFlags.GUARD_CF_LONGJUMP_TABLE_PRESENT != 0 and
+                    pe.load_config.guard_flags & pe.GuardFlags.GUARD_RF_ENABLE == 0
+                }
+                rule no_tls_load_config {
+                  condition:
+                    pe.number_of_tls_callbacks == 0 and
+                    not defined pe.load_config.size
+                }
+                rule rich {
+                  condition:
+                    pe.rich_signature.offset == 0x80 and
+                    pe.rich_signature.length == 32 and
+                    pe.rich_signature.key == 0x1234abcd and
+                    uint32(pe.rich_signature.offset) == 0x536e6144 ^ 0x1234abcd and
+                    pe.rich_signature.clear_data startswith "DanS\x00\x00\x00\x00" and
+                    pe.rich_signature.raw_data startswith "\x89\xca\x5a\x41" and
+                    pe.rich_signature.tools[0].toolid == 0x104 and
+                    pe.rich_signature.tools[0].version == 30319 and
+                    pe.rich_signature.tools[0].times == 5 and
+                    pe.rich_signature.toolid(0x104) == 5 and
+                    pe.rich_signature.toolid(0xff, 30319) == 1 and
+                    pe.rich_signature.toolid(0x105) == 0 and
+                    pe.rich_signature.version(30319) == 6 and
+                    pe.rich_signature.version(30319, 0x104) == 5 and
+                    pe.rich_signature.hash == "19d59319bcd5bdecdd612e16d48cb549" and
+                    pe.rich_signature.pv_hash == "8ad0a420ec796ea1e717154b41ea71f8"
+                }
+                rule dotnet {
+                  condition:
+                    pe.is_dotnet and
+                    pe.data_directories[pe.DirectoryEntry.CLR].virtual_address == 0x2000 and
+                    pe.clr_header.runtime_version.major == 2 and
+                    pe.clr_header.runtime_version.minor == 5 and
+                    pe.clr_header.metadata_version == "v4.0.30319" and
+                    pe.clr_header.is_il_only and
+                    pe.clr_header.is_32bit_preferred and
+                    not pe.clr_header.is_32bit_required and
+                    not pe.clr_header.has_native_entry_point and
+                    pe.clr_header.entry_point_token == 0x06000001
+                }
+                rule imports {
+                  condition:
+                    pe.number_of_imports == 2 and
+                    pe.number_of_imported_functions == 5 and
+                    pe.import_details[0].library_name == "KERNEL32.dll" and
+                    pe.import_details[0].number_of_functions == 2 and
+                    pe.import_details[0].functions[0].name == "CreateFileA" and
+                    pe.import_details[0].functions[0].rva == 0x1060 and
+                    not defined pe.import_details[0].functions[0].ordinal and
+                    pe.import_details[1].functions[1].ordinal == 115 and
+                    not defined pe.import_details[1].functions[1].name and
+                    not defined pe.import_details[0].host_library_name and
+                    pe.imports("kernel32.dll", "createfilea") and
+                    not pe.imports("kernel32.dll", "CreateFileW") and
+                    pe.imports("ws2_32.dll", 23) and
+                    not pe.imports("kernel32.dll", 23) and
+                    pe.imports("WS2_32.DLL") == 3 and
+                    pe.imports("user32.dll") == 0 and
+                    pe.imphash() == "2e2b55e9ab247fe8c8e1d8b8b74631b6" and
+                    pe.number_of_delayed_imports == 1 and
+                    pe.number_of_delayed_imported_functions == 2 and
+                    pe.delayed_import_details[0].library_name == "user32.dll" and
+                    pe.delayed_import_details[0].functions[0].name == "MessageBoxA" and
+                    pe.delayed_import_details[0].functions[0].rva == 0x1120 and
+                    pe.delayed_import_details[0].functions[1].ordinal == 5 and
+                    pe.bound_imports[0].library_name == "KERNEL32.dll" and
+                    pe.bound_imports[0].timestamp == 0x12345678 and
+                    pe.bound_imports[0].forwarders[0] == "NTDLL.DLL" and
+                    not pe.imports("user32.dll", "MessageBoxA")
+                }
+                rule exports {
+                  condition:
+                    pe.dll_name == "evil.dll" and
+                    pe.export_timestamp == 0x5f5e1000 and
+                    pe.number_of_exports == 3 and
+                    pe.export_details[0].name == "Alpha" and
+                    pe.export_details[0].ordinal == 5 and
+                    pe.export_details[0].rva == 0x1010 and
+                    pe.export_details[0].offset == 0x410 and
+                    not defined pe.export_details[0].forward_name and
+                    not defined pe.export_details[1].name and
+                    pe.export_details[1].ordinal == 6 and
+                    pe.export_details[1].forward_name == "NTDLL.RtlFoo" and
+                    pe.export_details[2].name == "beta" and
+                    pe.export_details[2].ordinal == 7 and
+                    pe.exports("Alpha") and
+                    not pe.exports("alpha") and
+                    not pe.exports("") and
+                    pe.exports(6) and
+                    pe.exports(7) and
+                    not pe.exports(8) and
+                    pe.exphash() == "c8fbb1c3b7e8f755c60cac6b63724700"
+                }
+                rule no_imports_exports {
+                  condition:
+                    pe.number_of_imports == 0 and
+                    pe.number_of_exports == 0 and
+                    pe.number_of_delayed_imports == 0 and
+                    not defined pe.bound_imports[0].library_name and
+                    not defined pe.imphash() and
+                    not defined pe.exphash()
+                }
+                rule resources {
+                  condition:
+                    pe.number_of_resources == 5 and
+                    pe.resource_timestamp == 0x5f000000 and
+                    pe.resource_version.major == 4 and
+                    pe.resource_version.minor == 0 and
+                    pe.resources[0].type_string == "M\x00Y\x00T\x00Y\x00P\x00E\x00" and
+                    not defined pe.resources[0].type and
+                    pe.resources[0].id == 7 and
+                    not defined pe.resources[0].name_string and
+                    pe.resources[1].type == 3 and
+                    pe.resources[1].id == 1 and
+                    pe.resources[1].language == 0x409 and
+                    pe.resources[1].rva == 0x1248 and
+                    pe.resources[1].offset == 0x648 and
+                    pe.resources[1].length == 10 and
+                    uint32(pe.resources[1].offset) == 0x73726966 and
+                    pe.version_info["CompanyName"] == "ACME" and
+                    pe.version_info["ProductName"] == "Road Runner" and
+                    not defined pe.version_info["FileVersion"] and
+                    pe.icon_hash == "9cc45d8c15646f2eef1cb50c372b61fd" and
+                    pe.number_of_resource_languages == 2 and
+                    pe.resource_languages[0] == 0x409 and
+                    pe.resource_languages[1] == 0xc09 and
+                    pe.locale(0xc09) and
+                    not pe.locale(0x809) and
+                    pe.language(9) and
+                    not pe.language(7) and
+                    pe.resource_data(3, 2) == "second icon" and
+                    pe.resource_data(3, 1, 0x409) == "first icon" and
+                    not defined pe.resource_data(3, 1, 0x809) and
+                    not defined pe.resource_data(3, 3)
+                }
+                rule no_resources {
+                  condition:
+                    pe.number_of_resources == 0 and
+                    not defined pe.resource_timestamp and
+                    not defined pe.icon_hash
+                }
+                rule not_pe { condition: not pe.is_pe }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut text = vec![0x90; 0x20];
+        text[0x10] = 0xc3;
+
+        // DOS stub followed by a Rich header with two tools, encrypted with
+        // the key 0x1234abcd.
+        let key = 0x1234abcd_u32;
+        let mut dos_stub = vec![0; 0x40];
+        for value in [0x536e6144, 0, 0, 0, 0x0104766f, 5, 0x00ff766f, 1] {
+            dos_stub.extend((value ^ key).to_le_bytes());
+        }
+        dos_stub.extend(b"Rich");
+        dos_stub.extend(key.to_le_bytes());
+
+        // Exception directory with two functions, at the start of .data,
+        // followed by the TLS directory, the TLS callbacks and the load
+        // configuration directory.
+        let mut data = vec![0; 0x114];
+        put(&mut data, 0, &[0x1000, 0x1010, 0x2100, 0x1010, 0x1020, 0x2100]);
+        put(&mut data, 0x20 + 24, &[0x40002060, 1]);
+        put(&mut data, 0x60, &[0x40001000, 1, 0x40001008, 1]);
+        put(&mut data, 0x80, &[0x94]);
+        put(&mut data, 0x80 + 0x58, &[0x40002100, 1]);
+        put(&mut data, 0x80 + 0x90, &[0x10500]);
+
+        let mut native = TestPe {
+            machine: 0x8664,
+            is_64bit: true,
+            timestamp: 1700000000,
+            characteristics: 0x2022,
+            entry_point: 0x1010,
+            subsystem: 3,
+            dos_stub,
+            sections: vec![
+                TestSection {
+                    characteristics: 0x60000020,
+                    ..TestSection::new(b".text", 0x1000, text)
+                },
+                TestSection {
+                    virtual_size: 0x1004,
+                    characteristics: 0xe0000040,
+                    ..TestSection::new(b".data", 0x2000, data)
+                },
+            ],
+            directories: vec![
+                (3, 0x2000, 24),
+                (9, 0x2020, 40),
+                (10, 0x2080, 0x94),
+            ],
+            ..Default::default()
+        }
+        .build();
+
+        // Set the checksum in the optional header, which is at offset 0x100.
+        native[0x100..0x104].copy_from_slice(&0x26e5_u32.to_le_bytes());
+
+        assert_eq!(
+            matching_rules(&native),
+            [
+                "headers",
+                "sections",
+                "native",
+                "exceptions",
+                "checksum_sections",
+                "tls_load_config",
+                "rich",
+                "no_imports_exports",
+                "no_resources"
+            ]
+        );
+        assert_eq!(
+            matching_rules(&dotnet()),
+            [
+                "no_tls_load_config",
+                "dotnet",
+                "no_imports_exports",
+                "no_resources"
+            ]
+        );
+        assert_eq!(
+            matching_rules(&imports_exports()),
+            [
+                "native",
+                "no_tls_load_config",
+                "imports",
+                "exports",
+                "no_resources"
+            ]
+        );
+        assert_eq!(
+            matching_rules(&resources()),
+            [
+                "native",
+                "no_tls_load_config",
+                "no_imports_exports",
+                "resources"
+            ]
+        );
+
+        // Files without the PE signature are not PE files, even if they
+        // start with "MZ".
+        assert_eq!(matching_rules(&native[..0x40]), ["not_pe"]);
+        assert_eq!(matching_rules(b"\x7fELF"), ["not_pe"]);
+    }
+
+    #[test]
+    fn api_sets() {
+        use super::imports::api_set_host;
+
+        assert_eq!(
+            api_set_host("api-ms-win-core-file-l1-2-0.dll").as_deref(),
+            Some("kernelbase.dll")
+        );
+        assert_eq!(
+            api_set_host("API-MS-WIN-CORE-COM-L1-1-0.DLL").as_deref(),
+            Some("combase.dll")
+        );
+        assert_eq!(
+            api_set_host("api-ms-win-crt-runtime-l1-1-0.dll").as_deref(),
+            Some("ucrtbase.dll")
+        );
+        assert_eq!(
+            api_set_host("api-ms-win-downlevel-advapi32-l1-1-0.dll")
+                .as_deref(),
+            Some("advapi32.dll")
+        );
+        assert_eq!(api_set_host("api-ms-win-unknown-l1-1-0.dll"), None);
+        assert_eq!(api_set_host("kernel32.dll"), None);
+    }
+
+    #[test]
+    fn header_anomalies() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "pe"
+                rule anomalies {
+                  condition:
+                    pe.e_lfanew == 0x40 and
+                    not defined pe.dos_stub_hash and
+                    pe.header_anomalies ==
+                        pe.HeaderAnomaly.SECTION_TABLE_OVERLAPS_DATA |
+                        pe.HeaderAnomaly.NONSTANDARD_FILE_ALIGNMENT
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut pe = TestPe {
+            sections: vec![TestSection::new(b".text", 0x1000, vec![0xc3])],
+            ..Default::default()
+        }
+        .build();
+
+        // The optional header is at offset 0x58. Set the file alignment to
+        // 0x300 and the size of the headers to 0x100, which is less than
+        // the end of the section table.
+        pe[0x7c..0x80].copy_from_slice(&0x300_u32.to_le_bytes());
+        pe[0x94..0x98].copy_from_slice(&0x100_u32.to_le_bytes());
+
+        assert_eq!(scanner.scan(&pe).unwrap().matching_rules().len(), 1);
+    }
+
+    #[test]
+    fn packers() {
+        use super::packers::{normalized_name, packer_hint};
+
+        assert_eq!(normalized_name(b".text"), ".text");
+        assert_eq!(normalized_name(b"a\x01\xff\\"), "a\\x01\\xff\\x5c");
+
+        assert_eq!(packer_hint([".text", "UPX1"]), Some("UPX"));
+        assert_eq!(packer_hint([".vmp0", ".themida"]), Some("VMProtect"));
+        assert_eq!(packer_hint([".text", ".data", "upx1"]), None);
+    }
+
+    #[test]
+    fn entry_point_bytes() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "pe"
+                rule entry_point {
+                  condition:
+                    pe.entry_point_bytes(3) == "\xc3\x90\x90" and
+                    pe.entry_point_bytes(1) == "\xc3" and
+                    not defined pe.entry_point_bytes(0)
+                }
+                rule truncated {
+                  condition:
+                    pe.entry_point_bytes(0x100) == "\xc3\x90\x90\x90"
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut text = vec![0x90; 0x14];
+        text[0x10] = 0xc3;
+
+        let mut file = TestPe {
+            entry_point: 0x1010,
+            sections: vec![TestSection::new(b".text", 0x1000, text.clone())],
+            ..Default::default()
+        }
+        .build();
+
+        // The raw data is padded to the file alignment, the last bytes are
+        // removed for testing that the result is truncated.
+        file.truncate(0x414);
+
+        assert_eq!(matching_rules(&file), ["entry_point", "truncated"]);
+
+        // The same file loaded in memory, with the section at its RVA.
+        let mut image = file[..0x400].to_vec();
+        image.resize(0x1000, 0);
+        image.extend(&text);
+
+        assert_eq!(matching_rules(&image), ["entry_point", "truncated"]);
+    }
+
+    /// Encodes a DER value with the given tag and the concatenation of
+    /// `parts` as its content.
+    fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let content = parts.concat();
+        let len = content.len();
+        let mut value = vec![tag];
+        if len < 0x80 {
+            value.push(len as u8);
+        } else {
+            let bytes: Vec<u8> = len
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|b| *b == 0)
+                .collect();
+            value.push(0x80 | bytes.len() as u8);
+            value.extend(bytes);
+        }
+        value.extend(content);
+        value
+    }
+
+    fn oid(oid: &str) -> Vec<u8> {
+        let arcs: Vec<u64> =
+            oid.split('.').map(|arc| arc.parse().unwrap()).collect();
+        let mut content = vec![(arcs[0] * 40 + arcs[1]) as u8];
+        for arc in &arcs[2..] {
+            let mut bytes = vec![(arc & 0x7f) as u8];
+            let mut arc = arc >> 7;
+            while arc > 0 {
+                bytes.insert(0, (arc & 0x7f) as u8 | 0x80);
+                arc >>= 7;
+            }
+            content.extend(bytes);
+        }
+        der(0x06, &[&content])
+    }
+
+    fn integer(bytes: &[u8]) -> Vec<u8> {
+        if bytes[0] & 0x80 != 0 {
+            der(0x02, &[&[0], bytes])
+        } else {
+            der(0x02, &[bytes])
+        }
+    }
+
+    fn algorithm(algorithm: &str) -> Vec<u8> {
+        der(0x30, &[&oid(algorithm), &[0x05, 0x00]])
+    }
+
+    fn name(cn: &str) -> Vec<u8> {
+        let attribute =
+            der(0x30, &[&oid("2.5.4.3"), &der(0x0c, &[cn.as_bytes()])]);
+        der(0x30, &[&der(0x31, &[&attribute])])
+    }
+
+    fn attribute(type_: &str, value: &[u8]) -> Vec<u8> {
+        der(0x30, &[&oid(type_), &der(0x31, &[value])])
+    }
+
+    /// An RSA key, with its modulus and private exponent.
+    struct Key {
+        n: &'static str,
+        d: &'static str,
+    }
+
+    impl Key {
+        fn modulus(&self) -> Vec<u8> {
+            num::BigUint::parse_bytes(self.n.as_bytes(), 16)
+                .unwrap()
+                .to_bytes_be()
+        }
+
+        /// Signs the SHA-256 of `message` with RSASSA-PKCS1-v1_5.
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            let digest_info = der(
+                0x30,
+                &[
+                    &algorithm("2.16.840.1.101.3.4.2.1"),
+                    &der(0x04, &[&Sha256::digest(message)]),
+                ],
+            );
+            let mut em = vec![0x00, 0x01];
+            em.resize(64 - digest_info.len() - 1, 0xff);
+            em.push(0);
+            em.extend(digest_info);
+            let n = num::BigUint::parse_bytes(self.n.as_bytes(), 16).unwrap();
+            let d = num::BigUint::parse_bytes(self.d.as_bytes(), 16).unwrap();
+            let signature =
+                num::BigUint::from_bytes_be(&em).modpow(&d, &n).to_bytes_be();
+            let mut padded = vec![0; 64 - signature.len()];
+            padded.extend(signature);
+            padded
+        }
+    }
+
+    const ROOT: Key = Key {
+        n: "bd1a30b247110858a0e2cc47f3f17958f83b6aa650da83a5411ca5a5cf042ba99ce5e4e6034e76c064888b8d8da1709169dd1fcb2d7dbd96db6e110c27be4681",
+        d: "261670587817e62673c13a84038e4f72ac0ccb9e540aaf1949d40b492a391cd1e431c5a9bfb6758e3e3e33f247494c42004ebd18e84edaa6d832f80097245341",
+    };
+
+    const LEAF: Key = Key {
+        n: "cc2eb9241d2d089faf7da49b76c95516e4487bf95ba91fe2d5648b380c22553bed661e7a7f47d6d08b771a9af5f8bcce33d652bb5497b0e3d8cbaefd1155d687",
+        d: "4f907d70c735bf408aa0093ed3810cf948a7be37a3323a4ca81eefe93d413bb8d6170d36a7e476868a9a335ca73dc62e84c15f3b1c40ea3ae16a8418e7d62ae1",
+    };
+
+    /// Builds a certificate for `subject` and `key`, issued and signed by
+    /// `issuer` and `issuer_key`. Valid from 2020 to 2030.
+    fn certificate(
+        serial: &[u8],
+        issuer: &str,
+        issuer_key: &Key,
+        subject: &str,
+        key: &Key,
+    ) -> Vec<u8> {
+        let public_key =
+            der(0x30, &[&integer(&key.modulus()), &integer(&[1, 0, 1])]);
+        let tbs = der(
+            0x30,
+            &[
+                &der(0xa0, &[&integer(&[2])]),
+                &integer(serial),
+                &algorithm("1.2.840.113549.1.1.11"),
+                &name(issuer),
+                &der(
+                    0x30,
+                    &[
+                        &der(0x17, &[b"200101000000Z"]),
+                        &der(0x17, &[b"300101000000Z"]),
+                    ],
+                ),
+                &name(subject),
+                &der(
+                    0x30,
+                    &[
+                        &algorithm("1.2.840.113549.1.1.1"),
+                        &der(0x03, &[&[0], &public_key]),
+                    ],
+                ),
+            ],
+        );
+        der(
+            0x30,
+            &[
+                &tbs,
+                &algorithm("1.2.840.113549.1.1.11"),
+                &der(0x03, &[&[0], &issuer_key.sign(&tbs)]),
+            ],
+        )
+    }
+
+    /// Builds a `SignerInfo` for the leaf certificate, which signs the
+    /// given authenticated attributes.
+    fn signer_info(attributes: &[u8], unauthenticated: &[u8]) -> Vec<u8> {
+        let mut signer = vec![
+            integer(&[1]),
+            der(0x30, &[&name("Root"), &integer(&[1, 2])]),
+            algorithm("2.16.840.1.101.3.4.2.1"),
+            der(0xa0, &[attributes]),
+            algorithm("1.2.840.113549.1.1.1"),
+            der(0x04, &[&LEAF.sign(&der(0x31, &[attributes]))]),
+        ];
+        if !unauthenticated.is_empty() {
+            signer.push(der(0xa1, &[unauthenticated]));
+        }
+        der(
+            0x30,
+            &signer.iter().map(|part| part.as_slice()).collect::<Vec<_>>(),
+        )
+    }
+
+    /// Builds a file signed with a leaf certificate, issued by a root
+    /// certificate, and countersigned with the leaf certificate. Returns
+    /// the file and the root certificate.
+    fn signed() -> (Vec<u8>, Vec<u8>) {
+        let mut pe = TestPe {
+            sections: vec![TestSection::new(
+                b".text",
+                0x1000,
+                vec![0xc3; 0x10],
+            )],
+            ..Default::default()
+        }
+        .build();
+
+        // The optional header is at offset 0x58, the checksum is at 0x98 and
+        // the security directory entry at 0xd8. The certificate table goes
+        // at the end of the file.
+        let mut hasher = Sha256::new();
+        hasher.update(&pe[..0x98]);
+        hasher.update(&pe[0x9c..0xd8]);
+        hasher.update(&pe[0xe0..]);
+        let digest = hasher.finalize();
+
+        let root = certificate(&[1], "Root", &ROOT, "Root", &ROOT);
+        let leaf = certificate(&[1, 2], "Root", &ROOT, "Leaf", &LEAF);
+
+        let content = der(
+            0x30,
+            &[
+                &der(0x30, &[&oid("1.3.6.1.4.1.311.2.1.15"), &der(0x30, &[])]),
+                &der(
+                    0x30,
+                    &[
+                        &algorithm("2.16.840.1.101.3.4.2.1"),
+                        &der(0x04, &[&digest]),
+                    ],
+                ),
+            ],
+        );
+
+        // The digest of the content excludes its tag and length.
+        let attributes = [
+            attribute("1.2.840.113549.1.9.3", &oid("1.3.6.1.4.1.311.2.1.4")),
+            attribute(
+                "1.2.840.113549.1.9.4",
+                &der(0x04, &[&Sha256::digest(&content[2..])]),
+            ),
+        ]
+        .concat();
+
+        let signature = LEAF.sign(&der(0x31, &[&attributes]));
+
+        let countersignature = signer_info(
+            &[
+                attribute(
+                    "1.2.840.113549.1.9.3",
+                    &oid("1.2.840.113549.1.7.1"),
+                ),
+                attribute(
+                    "1.2.840.113549.1.9.5",
+                    &der(0x17, &[b"230101000000Z"]),
+                ),
+                attribute(
+                    "1.2.840.113549.1.9.4",
+                    &der(0x04, &[&Sha256::digest(&signature)]),
+                ),
+            ]
+            .concat(),
+            &[],
+        );
+
+        let signer = signer_info(
+            &attributes,
+            &attribute("1.2.840.113549.1.9.6", &countersignature),
+        );
+
+        let signed_data = der(
+            0x30,
+            &[
+                &oid("1.2.840.113549.1.7.2"),
+                &der(
+                    0xa0,
+                    &[&der(
+                        0x30,
+                        &[
+                            &integer(&[1]),
+                            &der(
+                                0x31,
+                                &[&algorithm("2.16.840.1.101.3.4.2.1")],
+                            ),
+                            &der(
+                                0x30,
+                                &[
+                                    &oid("1.3.6.1.4.1.311.2.1.4"),
+                                    &der(0xa0, &[&content]),
+                                ],
+                            ),
+                            &der(0xa0, &[&leaf, &root]),
+                            &der(0x31, &[&signer]),
+                        ],
+                    )],
+                ),
+            ],
+        );
+
+        let offset = pe.len() as u32;
+        let len = 8 + signed_data.len() as u32;
+        let size = (len + 7) & !7;
+
+        pe.extend(len.to_le_bytes());
+        pe.extend(0x200_u16.to_le_bytes());
+        pe.extend(2_u16.to_le_bytes());
+        pe.extend(signed_data);
+        pe.resize((offset + size) as usize, 0);
+
+        pe[0xd8..0xdc].copy_from_slice(&offset.to_le_bytes());
+        pe[0xdc..0xe0].copy_from_slice(&size.to_le_bytes());
+
+        (pe, root)
+    }
+
+    #[test]
+    fn authenticode() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "pe"
+                rule signed {
+                  condition:
+                    pe.is_signed and
+                    pe.number_of_signatures == 1 and
+                    pe.signatures[0].subject == "/CN=Leaf" and
+                    pe.signatures[0].issuer == "/CN=Root" and
+                    pe.signatures[0].serial == "01:02" and
+                    pe.signatures[0].version == 3 and
+                    pe.signatures[0].algorithm == "sha256WithRSAEncryption" and
+                    pe.signatures[0].not_before == 1577836800 and
+                    pe.signatures[0].not_after == 1893456000 and
+                    pe.signatures[0].digest_alg == "sha256" and
+                    pe.signatures[0].digest == pe.signatures[0].file_digest and
+                    pe.signatures[0].number_of_certificates == 2 and
+                    pe.signatures[0].chain[0].subject == "/CN=Leaf" and
+                    pe.signatures[0].chain[1].subject == "/CN=Root" and
+                    pe.signatures[0].number_of_countersignatures == 1 and
+                    pe.signatures[0].countersignatures[0].sign_time == 1672531200 and
+                    pe.signatures[0].countersignatures[0].digest_alg == "sha256"
+                }
+                rule thumbprints {
+                  condition:
+                    pe.signatures[0].chain[0].serial_reversed == "02:01" and
+                    pe.signatures[0].chain[0].length > 0 and
+                    uint8(pe.signatures[0].chain[0].offset) == 0x30
+                }
+                rule valid {
+                  condition:
+                    pe.signatures[0].digest_matches and
+                    pe.signatures[0].signature_valid
+                }
+                rule verified {
+                  condition:
+                    pe.signatures[0].trusted and
+                    pe.signatures[0].verified and
+                    pe.signatures[0].countersignatures[0].verified
+                }
+                rule not_verified { condition: not pe.signatures[0].verified }
+                rule untrusted { condition: not pe.signatures[0].trusted }
+                rule not_signed {
+                  condition:
+                    not pe.is_signed and pe.number_of_signatures == 0
+                }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let (mut pe, root) = signed();
+
+        let matching_rules = |scanner: &mut crate::scanner::Scanner,
+                              data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        // Without trusted root certificates, the signature is valid but
+        // not verified.
+        assert_eq!(
+            matching_rules(&mut scanner, &pe),
+            ["signed", "thumbprints", "valid", "not_verified", "untrusted"]
+        );
+
+        scanner.set_module_data("pe", &root);
+
+        assert_eq!(
+            matching_rules(&mut scanner, &pe),
+            ["signed", "thumbprints", "valid", "verified"]
+        );
+
+        // Modifying the file, outside the excluded ranges, changes its
+        // digest.
+        pe[0x400] = 0x90;
+
+        assert_eq!(
+            matching_rules(&mut scanner, &pe),
+            ["thumbprints", "not_verified"]
+        );
+
+        // The thumbprints are the hashes of the certificates, which are
+        // in the file.
+        let results = scanner.scan(&pe).unwrap();
+        let (_, output) = results.module_outputs().next().unwrap();
+        let output: &super::PE = output.downcast_ref().unwrap();
+
+        for cert in &output.signatures[0].certificates {
+            let offset = cert.offset() as usize;
+            let raw = &pe[offset..offset + cert.length() as usize];
+            assert_eq!(cert.thumbprint_sha1(), sha1_hex(raw));
+            assert_eq!(cert.thumbprint_sha256(), hex(&Sha256::digest(raw)));
+        }
+
+        let unsigned = TestPe::default().build();
+
+        assert_eq!(matching_rules(&mut scanner, &unsigned), ["not_signed"]);
+    }
+}
@@ -0,0 +1,91 @@
+/*! Identification of packers by their section names.
+
+Many packers and protectors give distinctive names to the sections they
+add to the packed files. These names are easy to change, so they are just
+a hint that the file is packed, but they are enough for triaging files
+that were not modified after being packed.
+*/
+
+/// Section names, as returned by [`normalized_name`], and the packers that
+/// use them.
+const PACKER_SECTIONS: &[(&str, &str)] = &[
+    ("!EPack", "EPack"),
+    (".ASPack", "ASPack"),
+    (".ByDwing", "Upack"),
+    (".MPRESS1", "MPRESS"),
+    (".MPRESS2", "MPRESS"),
+    (".MaskPE", "MaskPE"),
+    (".RLPack", "RLPack"),
+    (".Upack", "Upack"),
+    (".WWP32", "WWPack32"),
+    (".WWPACK", "WWPack32"),
+    (".adata", "ASPack"),
+    (".aspack", "ASPack"),
+    (".boom", "The Boomerang"),
+    (".ccg", "CCG"),
+    (".enigma1", "Enigma Protector"),
+    (".enigma2", "Enigma Protector"),
+    (".neolit", "NeoLite"),
+    (".neolite", "NeoLite"),
+    (".nsp0", "NsPack"),
+    (".nsp1", "NsPack"),
+    (".nsp2", "NsPack"),
+    (".packed", "RLPack"),
+    (".pelock", "PELock"),
+    (".perplex", "Perplex"),
+    (".petite", "Petite"),
+    (".sforce3", "StarForce"),
+    (".spack", "Simple Pack"),
+    (".svkp", "SVKP"),
+    (".taz", "PESpin"),
+    (".themida", "Themida"),
+    (".vmp0", "VMProtect"),
+    (".vmp1", "VMProtect"),
+    (".vmp2", "VMProtect"),
+    (".winlice", "WinLicense"),
+    (".y0da", "Y0da Protector"),
+    (".yP", "Y0da Protector"),
+    ("BitArts", "Crunch"),
+    ("FSG!", "FSG"),
+    ("MEW", "MEW"),
+    ("PEC2", "PECompact"),
+    ("PEC2TO", "PECompact"),
+    ("PECompact2", "PECompact"),
+    ("PELOCKnt", "PELock"),
+    ("PEPACK!!", "PEPack"),
+    ("ProCrypt", "ProCrypt"),
+    ("UPX0", "UPX"),
+    ("UPX1", "UPX"),
+    ("UPX2", "UPX"),
+    ("kkrunchy", "kkrunchy"),
+    ("nsp0", "NsPack"),
+    ("nsp1", "NsPack"),
+    ("nsp2", "NsPack"),
+];
+
+/// Returns a section name as a printable string. Bytes that are not
+/// printable ASCII characters, and backslashes, are escaped as `\xNN`.
+/// The name must not have the trailing null characters.
+pub(super) fn normalized_name(name: &[u8]) -> String {
+    name.iter()
+        .map(|b| match b {
+            b'\\' => "\\x5c".to_string(),
+            0x20..=0x7e => (*b as char).to_string(),
+            _ => format!("\\x{:02x}", b),
+        })
+        .collect()
+}
+
+/// Returns the name of the packer that uses the first section whose name
+/// is in the built-in table, or `None` if no section has a known name.
+pub(super) fn packer_hint<'a, I>(names: I) -> Option<&'static str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    names.into_iter().find_map(|name| {
+        PACKER_SECTIONS
+            .binary_search_by_key(&name, |(section, _)| section)
+            .ok()
+            .map(|index| PACKER_SECTIONS[index].1)
+    })
+}
@@ -0,0 +1,328 @@
+/*! Parser for the resource directory.
+
+Resources are organized in a tree with three levels: the type of the
+resource (e.g: icon, version information), its name or ID, and its
+language. The leaves point to the resources' data.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#the-rsrc-section
+*/
+
+use crate::modules::utils::digest::Md5;
+use crate::modules::utils::pe::{Pe, DIRECTORY_RESOURCE};
+use crate::modules::utils::{u16_at, u32_at, utf16_string};
+
+/// Maximum number of resources.
+const MAX_RESOURCES: usize = 16384;
+
+/// Maximum number of strings read from the version information.
+const MAX_VERSION_INFO_STRINGS: usize = 256;
+
+/// Resource types.
+pub(super) const RT_ICON: u32 = 3;
+pub(super) const RT_GROUP_ICON: u32 = 14;
+pub(super) const RT_VERSION: u32 = 16;
+
+/// The resource directory.
+pub(super) struct Resources<'a> {
+    pub timestamp: u32,
+    pub version: (u16, u16),
+    pub resources: Vec<Resource<'a>>,
+}
+
+/// A resource, which is a leaf in the resource tree.
+pub(super) struct Resource<'a> {
+    /// The resource's type, either as a number or as a string.
+    pub type_id: Option<u32>,
+    pub type_name: Option<&'a [u8]>,
+    /// The resource's ID or name.
+    pub id: Option<u32>,
+    pub name: Option<&'a [u8]>,
+    pub language: u32,
+    pub rva: u32,
+    pub size: u32,
+    /// Offset of the resource's data within the file. `None` if the data
+    /// is not fully contained in the file.
+    pub offset: Option<usize>,
+}
+
+/// A name or ID in the resource tree.
+enum EntryName<'a> {
+    Id(u32),
+    /// A name encoded in UTF-16LE, without the length prefix.
+    Name(&'a [u8]),
+}
+
+/// Parses the resource directory. Returns `None` if the file doesn't have
+/// one.
+pub(super) fn parse<'a>(pe: &Pe<'a>) -> Option<Resources<'a>> {
+    let (rva, _) = pe.directory(DIRECTORY_RESOURCE)?;
+    let root = pe.data_at_rva(rva)?;
+
+    let mut resources = Vec::new();
+
+    for (type_, types) in entries(root, 0) {
+        let types = match types {
+            Entry::Directory(offset) => offset,
+            Entry::Data(_) => continue,
+        };
+        for (name, names) in entries(root, types) {
+            let names = match names {
+                Entry::Directory(offset) => offset,
+                Entry::Data(_) => continue,
+            };
+            for (language, data) in entries(root, names) {
+                if resources.len() >= MAX_RESOURCES {
+                    break;
+                }
+                let data = match data {
+                    Entry::Data(offset) => offset,
+                    Entry::Directory(_) => continue,
+                };
+                let (rva, size) = match (
+                    u32_at(root, data),
+                    u32_at(root, data.saturating_add(4)),
+                ) {
+                    (Some(rva), Some(size)) => (rva, size),
+                    _ => continue,
+                };
+                let offset = pe.rva_to_offset(rva).filter(|offset| {
+                    offset.saturating_add(size as usize) <= pe.data.len()
+                });
+                let (type_id, type_name) = split(&type_);
+                let (id, name) = split(&name);
+                resources.push(Resource {
+                    type_id,
+                    type_name,
+                    id,
+                    name,
+                    language: split(&language).0.unwrap_or(0),
+                    rva,
+                    size,
+                    offset,
+                });
+            }
+        }
+    }
+
+    Some(Resources {
+        timestamp: u32_at(root, 4)?,
+        version: (u16_at(root, 8)?, u16_at(root, 10)?),
+        resources,
+    })
+}
+
+/// An entry in a directory of the resource tree.
+enum Entry {
+    /// Offset of a subdirectory, relative to the root directory.
+    Directory(usize),
+    /// Offset of a data entry, relative to the root directory.
+    Data(usize),
+}
+
+/// Returns the entries in the directory at the given offset, relative to
+/// the root directory.
+fn entries(root: &[u8], offset: usize) -> Vec<(EntryName<'_>, Entry)> {
+    let directory = match root.get(offset..) {
+        Some(directory) => directory,
+        None => return Vec::new(),
+    };
+
+    let num_entries = match (u16_at(directory, 12), u16_at(directory, 14)) {
+        (Some(named), Some(ids)) => named as usize + ids as usize,
+        _ => return Vec::new(),
+    };
+
+    directory
+        .get(16..)
+        .unwrap_or_default()
+        .chunks_exact(8)
+        .take(num_entries.min(MAX_RESOURCES))
+        .filter_map(|entry| {
+            let name = u32_at(entry, 0).unwrap();
+            let target = u32_at(entry, 4).unwrap();
+
+            let name = if name & 0x80000000 != 0 {
+                // The name is a UTF-16 string prefixed by its length in
+                // characters.
+                let offset = (name & 0x7fffffff) as usize;
+                let len = u16_at(root, offset)? as usize;
+                EntryName::Name(root.get(offset + 2..offset + 2 + len * 2)?)
+            } else {
+                EntryName::Id(name)
+            };
+
+            let target = if target & 0x80000000 != 0 {
+                let offset = (target & 0x7fffffff) as usize;
+                // Subdirectories that point to their parent, or earlier
+                // directories, could produce infinite loops.
+                if offset <= offset_of(root, directory) {
+                    return None;
+                }
+                Entry::Directory(offset)
+            } else {
+                Entry::Data(target as usize)
+            };
+
+            Some((name, target))
+        })
+        .collect()
+}
+
+/// Returns the offset of `directory` within `root`.
+fn offset_of(root: &[u8], directory: &[u8]) -> usize {
+    directory.as_ptr() as usize - root.as_ptr() as usize
+}
+
+fn split<'a>(name: &EntryName<'a>) -> (Option<u32>, Option<&'a [u8]>) {
+    match name {
+        EntryName::Id(id) => (Some(*id), None),
+        EntryName::Name(name) => (None, Some(name)),
+    }
+}
+
+/// Returns the strings in the version information, which is the content
+/// of the first `RT_VERSION` resource. The strings are the pairs of keys
+/// and values in the `StringFileInfo` block (e.g: `CompanyName`,
+/// `ProductVersion`). When there are multiple string tables, which is the
+/// case when the strings are translated into multiple languages, the first
+/// value found for each key is kept.
+pub(super) fn version_info(
+    pe: &Pe,
+    resources: &Resources,
+) -> Vec<(String, String)> {
+    let mut strings = Vec::new();
+
+    let data = resources
+        .resources
+        .iter()
+        .filter(|resource| resource.type_id == Some(RT_VERSION))
+        .find_map(|resource| resource_data(pe, resource));
+
+    let version_info = match data.and_then(block) {
+        Some(block) if block.key == "VS_VERSION_INFO" => block,
+        _ => return strings,
+    };
+
+    for file_info in blocks(version_info.children) {
+        if file_info.key != "StringFileInfo" {
+            continue;
+        }
+        for table in blocks(file_info.children) {
+            for string in blocks(table.children) {
+                if strings.len() >= MAX_VERSION_INFO_STRINGS {
+                    return strings;
+                }
+                if !strings.iter().any(|(key, _)| *key == string.key) {
+                    strings.push((string.key, utf16_string(string.value)));
+                }
+            }
+        }
+    }
+
+    strings
+}
+
+/// Returns the MD5 of the images in the main icon group, which is the
+/// first `RT_GROUP_ICON` resource, as a hex string. The images are the
+/// `RT_ICON` resources referenced by the group, and they are hashed in the
+/// same order as in the group. Returns `None` if the file doesn't have
+/// icons.
+pub(super) fn icon_hash(pe: &Pe, resources: &Resources) -> Option<String> {
+    let group = resources
+        .resources
+        .iter()
+        .filter(|resource| resource.type_id == Some(RT_GROUP_ICON))
+        .find_map(|resource| resource_data(pe, resource))?;
+
+    // The group starts with a 6-byte header, whose last field is the
+    // number of icons, followed by a 14-byte entry for each icon. The
+    // last field in each entry is the ID of the RT_ICON resource.
+    let count = u16_at(group, 4)? as usize;
+
+    let mut md5 = Md5::new();
+    let mut found = false;
+
+    for entry in group.get(6..)?.chunks_exact(14).take(count) {
+        let id = u16_at(entry, 12).unwrap() as u32;
+        if let Some(image) = resources
+            .resources
+            .iter()
+            .filter(|resource| {
+                resource.type_id == Some(RT_ICON) && resource.id == Some(id)
+            })
+            .find_map(|resource| resource_data(pe, resource))
+        {
+            md5.update(image);
+            found = true;
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    Some(crate::modules::utils::hex(&md5.finalize()))
+}
+
+/// Returns the data of a resource, or `None` if it's not in the file.
+pub(super) fn resource_data<'a>(
+    pe: &Pe<'a>,
+    resource: &Resource,
+) -> Option<&'a [u8]> {
+    let offset = resource.offset?;
+    pe.data.get(offset..offset + resource.size as usize)
+}
+
+/// A block in the version information, which has a key, a value and
+/// nested blocks.
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    children: &'a [u8],
+    /// Size of the block, including its children.
+    len: usize,
+}
+
+/// Parses the block at the start of `data`.
+///
+/// Each block starts with its length, the length of its value, and the
+/// type of value (1 for text, where the length is in 16-bit words, and 0
+/// for binary data). The header is followed by the key, a null-terminated
+/// UTF-16 string, the value and the children, all of them aligned to 4
+/// bytes.
+fn block(data: &[u8]) -> Option<Block<'_>> {
+    let len = u16_at(data, 0)? as usize;
+    let value_len = u16_at(data, 2)? as usize;
+    let value_type = u16_at(data, 4)?;
+
+    let data = data.get(..len)?;
+
+    let key_len = data.get(6..)?.chunks_exact(2).position(|c| c == [0, 0])?;
+
+    let key = utf16_string(&data[6..6 + key_len * 2]);
+
+    let value_start = align4(6 + key_len * 2 + 2);
+    let value_len = if value_type == 1 { value_len * 2 } else { value_len };
+    let value_end = value_start.saturating_add(value_len).min(len);
+    let value = data.get(value_start..value_end).unwrap_or_default();
+    let children = data.get(align4(value_end)..).unwrap_or_default();
+
+    Some(Block { key, value, children, len })
+}
+
+/// Returns the blocks in `data`, one after the other.
+fn blocks(mut data: &[u8]) -> impl Iterator<Item = Block<'_>> {
+    std::iter::from_fn(move || {
+        let block = block(data)?;
+        // Blocks with a zero length would produce an infinite loop.
+        if block.len == 0 {
+            return None;
+        }
+        data = data.get(align4(block.len)..).unwrap_or_default();
+        Some(block)
+    })
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
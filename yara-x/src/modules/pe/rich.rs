@@ -0,0 +1,87 @@
+/*! Parser for the Rich header.
+
+The Rich header is an undocumented structure written by Microsoft's
+linker between the DOS stub and the PE header. It lists the tools that
+produced the object files linked into the image, each one identified by
+a product ID and a build number, together with the number of objects
+produced by each tool. The header is XOR-encrypted with a key that
+follows the "Rich" signature, and the decrypted data starts with "DanS".
+*/
+
+use crate::modules::utils::pe::Pe;
+use crate::modules::utils::u32_at;
+
+/// "DanS", which is the start of the decrypted data.
+const DANS: u32 = 0x536e6144;
+
+/// A parsed Rich header.
+pub(super) struct RichHeader<'a> {
+    /// Offset of the header within the file.
+    pub offset: usize,
+    pub key: u32,
+    /// Encrypted data, from the start of the header to the "Rich"
+    /// signature, exclusive.
+    pub raw_data: &'a [u8],
+    /// Same as `raw_data`, but decrypted.
+    pub clear_data: Vec<u8>,
+    /// Product ID, build number and number of objects of each tool.
+    pub tools: Vec<(u16, u16, u32)>,
+}
+
+/// Parses the Rich header. Returns `None` if the file doesn't have one.
+pub(super) fn parse<'a>(pe: &Pe<'a>) -> Option<RichHeader<'a>> {
+    // The header is between the DOS header and the PE header. Its size is
+    // a multiple of 4, and so is its offset.
+    let stub = pe.data.get(..pe.pe_offset)?;
+
+    let rich = (0x40..stub.len().saturating_sub(7))
+        .step_by(4)
+        .find(|offset| &stub[*offset..*offset + 4] == b"Rich")?;
+
+    let key = u32_at(stub, rich + 4)?;
+
+    let start = (0x40..rich)
+        .step_by(4)
+        .rev()
+        .find(|offset| u32_at(stub, *offset) == Some(DANS ^ key))?;
+
+    let raw_data = &stub[start..rich];
+
+    let clear_data: Vec<u8> = raw_data
+        .chunks_exact(4)
+        .flat_map(|chunk| {
+            (u32::from_le_bytes(chunk.try_into().unwrap()) ^ key).to_le_bytes()
+        })
+        .collect();
+
+    // "DanS" is followed by three zeroes used as padding, and then by the
+    // tools, each one with two values: the product ID and build number,
+    // and the number of objects.
+    let tools = clear_data
+        .get(16..)
+        .unwrap_or_default()
+        .chunks_exact(8)
+        .map(|entry| {
+            let id = u32_at(entry, 0).unwrap();
+            let count = u32_at(entry, 4).unwrap();
+            ((id >> 16) as u16, id as u16, count)
+        })
+        .collect();
+
+    Some(RichHeader { offset: start, key, raw_data, clear_data, tools })
+}
+
+impl RichHeader<'_> {
+    /// Returns the data that the "RichPV" hash is computed from, which is
+    /// the same as the decrypted data, but without the number of objects
+    /// produced by each tool. This hash identifies the toolchain, without
+    /// depending on the size of the project.
+    pub fn pv_data(&self) -> Vec<u8> {
+        let mut data = self.clear_data.get(..16).unwrap_or_default().to_vec();
+        for (product_id, build, _) in &self.tools {
+            data.extend(build.to_le_bytes());
+            data.extend(product_id.to_le_bytes());
+        }
+        data
+    }
+}
@@ -0,0 +1,52 @@
+/*! Parser for the TLS directory.
+
+The directory points to a null-terminated array with the virtual
+addresses of the TLS callbacks, which are functions called by the loader
+when the process or a thread starts or exits. They run before the entry
+point.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#the-tls-section
+*/
+
+use crate::modules::utils::pe::{Pe, DIRECTORY_TLS};
+
+/// Maximum number of TLS callbacks.
+const MAX_CALLBACKS: usize = 256;
+
+/// Returns the virtual addresses of the TLS callbacks.
+pub(super) fn callbacks(pe: &Pe) -> Vec<u64> {
+    let directory = match pe.directory_data(DIRECTORY_TLS) {
+        Some(directory) => directory,
+        None => return Vec::new(),
+    };
+
+    let (offset, size) = if pe.is_64bit { (24, 8) } else { (12, 4) };
+
+    let callbacks = match address_at(directory, offset, size)
+        .and_then(|va| pe.va_to_offset(va))
+        .and_then(|offset| pe.data.get(offset..))
+    {
+        Some(callbacks) => callbacks,
+        None => return Vec::new(),
+    };
+
+    (0..MAX_CALLBACKS)
+        .map_while(|i| address_at(callbacks, i * size, size))
+        .take_while(|va| *va != 0)
+        .collect()
+}
+
+/// Reads a virtual address, which is 8 bytes long in 64-bit files, and 4
+/// bytes long in 32-bit files.
+pub(super) fn address_at(
+    data: &[u8],
+    offset: usize,
+    size: usize,
+) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(size)?)?;
+    Some(if size == 8 {
+        u64::from_le_bytes(bytes.try_into().unwrap())
+    } else {
+        u32::from_le_bytes(bytes.try_into().unwrap()).into()
+    })
+}
@@ -0,0 +1,317 @@
+/*! Reader for objects serialized with Python's `marshal` module.
+
+Compiled Python files contain a code object serialized with `marshal`.
+Each object starts with a type byte, where bit 7 indicates that the object
+is added to a table of references, which later objects can point to with
+the `r` type. The layout of code objects changes with the Python version.
+
+See: https://github.com/python/cpython/blob/main/Python/marshal.c
+*/
+
+use std::rc::Rc;
+
+/// Maximum nesting depth of objects.
+const MAX_DEPTH: usize = 64;
+
+/// Maximum total size of the strings collected from code objects. Strings
+/// can be referenced many times, this prevents crafted files from making
+/// the output much larger than the file.
+const MAX_STRINGS_SIZE: usize = 16 * 1024 * 1024;
+
+/// Flag in the type byte that indicates that the object can be referenced
+/// by later objects.
+const FLAG_REF: u8 = 0x80;
+
+/// A code object.
+#[derive(Default)]
+pub(crate) struct Code {
+    pub name: Option<Rc<[u8]>>,
+    /// Qualified name, only available in Python 3.11 and later.
+    pub qualname: Option<Rc<[u8]>>,
+    pub filename: Option<Rc<[u8]>>,
+    pub first_line_number: u32,
+    /// String and bytes constants in `co_consts`. Strings within nested
+    /// tuples and frozensets are included.
+    pub constants: Vec<Rc<[u8]>>,
+    /// Names in `co_names`, which are the global variables and attributes
+    /// used by the code.
+    pub names: Vec<Rc<[u8]>>,
+}
+
+/// An unmarshalled object. Only the objects relevant for extracting
+/// information about code objects are kept, the rest are `Other`.
+#[derive(Clone)]
+enum Object {
+    String(Rc<[u8]>),
+    Tuple(Vec<Object>),
+    /// A code object, which is added to `Reader::code_objects`.
+    Code,
+    Other,
+}
+
+/// Reads the code object at the start of `data`, returning it and all the
+/// nested code objects, in the order they appear. `version` is the Python
+/// version as (major, minor).
+pub(crate) fn code_objects(data: &[u8], version: (u8, u8)) -> Vec<Code> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        version,
+        refs: Vec::new(),
+        interned: Vec::new(),
+        code_objects: Vec::new(),
+        strings_budget: MAX_STRINGS_SIZE,
+    };
+
+    reader.object(0);
+    reader.code_objects
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    version: (u8, u8),
+    /// Objects that can be referenced with the `r` type.
+    refs: Vec<Object>,
+    /// Interned strings that can be referenced with the `R` type, used by
+    /// Python 2 only.
+    interned: Vec<Rc<[u8]>>,
+    code_objects: Vec<Code>,
+    /// Remaining size of the strings that can be collected.
+    strings_budget: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.bytes(4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Reads a sequence of `n` objects.
+    fn objects(&mut self, n: usize, depth: usize) -> Option<Vec<Object>> {
+        let mut objects = Vec::new();
+        for _ in 0..n {
+            objects.push(self.object(depth + 1)?);
+        }
+        Some(objects)
+    }
+
+    fn object(&mut self, depth: usize) -> Option<Object> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        let b = self.u8()?;
+        let object_type = b & !FLAG_REF;
+
+        // Objects with the flag are added to the references before reading
+        // their content, as it may contain objects with the flag too.
+        let ref_index = if b & FLAG_REF != 0 {
+            self.refs.push(Object::Other);
+            Some(self.refs.len() - 1)
+        } else {
+            None
+        };
+
+        let object = match object_type {
+            b'0' | b'N' | b'F' | b'T' | b'S' | b'.' => Object::Other,
+            b'i' => {
+                self.bytes(4)?;
+                Object::Other
+            }
+            b'I' | b'g' => {
+                self.bytes(8)?;
+                Object::Other
+            }
+            b'y' => {
+                self.bytes(16)?;
+                Object::Other
+            }
+            // Floats and complex numbers represented as strings.
+            b'f' => {
+                let len = self.u8()?;
+                self.bytes(len as usize)?;
+                Object::Other
+            }
+            b'x' => {
+                for _ in 0..2 {
+                    let len = self.u8()?;
+                    self.bytes(len as usize)?;
+                }
+                Object::Other
+            }
+            // Long integers, stored as a signed number of 15-bit digits.
+            b'l' => {
+                let n = self.u32()? as i32;
+                self.bytes(n.unsigned_abs() as usize * 2)?;
+                Object::Other
+            }
+            b's' | b't' | b'u' | b'a' | b'A' => {
+                let len = self.u32()? as usize;
+                let s: Rc<[u8]> = self.bytes(len)?.into();
+                if object_type == b't' && self.version.0 < 3 {
+                    self.interned.push(s.clone());
+                }
+                Object::String(s)
+            }
+            b'z' | b'Z' => {
+                let len = self.u8()?;
+                Object::String(self.bytes(len as usize)?.into())
+            }
+            b'R' => {
+                let index = self.u32()? as usize;
+                Object::String(self.interned.get(index)?.clone())
+            }
+            b'r' => {
+                let index = self.u32()? as usize;
+                self.refs.get(index)?.clone()
+            }
+            b'(' => {
+                let n = self.u32()? as usize;
+                Object::Tuple(self.objects(n, depth)?)
+            }
+            b')' => {
+                let n = self.u8()? as usize;
+                Object::Tuple(self.objects(n, depth)?)
+            }
+            // Frozensets are treated as tuples, as they can appear in
+            // `co_consts` too.
+            b'>' => {
+                let n = self.u32()? as usize;
+                Object::Tuple(self.objects(n, depth)?)
+            }
+            b'[' | b'<' => {
+                let n = self.u32()? as usize;
+                self.objects(n, depth)?;
+                Object::Other
+            }
+            // Dictionaries are terminated by a NULL key.
+            b'{' => {
+                loop {
+                    let key = self.data.get(self.pos)?;
+                    if *key & !FLAG_REF == b'0' {
+                        self.pos += 1;
+                        break;
+                    }
+                    self.object(depth + 1)?;
+                    self.object(depth + 1)?;
+                }
+                Object::Other
+            }
+            b'c' => {
+                self.code(depth)?;
+                Object::Code
+            }
+            _ => return None,
+        };
+
+        // Only strings are kept in the references, as cloning other
+        // objects could be expensive.
+        if let (Some(index), Object::String(s)) = (ref_index, &object) {
+            self.refs[index] = Object::String(s.clone());
+        }
+
+        Some(object)
+    }
+
+    fn code(&mut self, depth: usize) -> Option<()> {
+        // Number of 32-bit integers at the start of the code object,
+        // which are the number of arguments, stack size, flags, etc.
+        let num_integers = match self.version {
+            (2, _) => 4,
+            (3, 0..=7) => 5,
+            (3, 8..=10) => 6,
+            _ => 5,
+        };
+
+        self.bytes(num_integers * 4)?;
+
+        let mut code = Code::default();
+
+        // The bytecode.
+        self.object(depth + 1)?;
+
+        // The nested code objects are in the constants, and are added to
+        // the list before this one is complete. This one is inserted
+        // before them.
+        let index = self.code_objects.len();
+
+        let consts = self.object(depth + 1)?;
+        let names = self.object(depth + 1)?;
+
+        self.collect_strings(&consts, &mut code.constants);
+        self.collect_strings(&names, &mut code.names);
+
+        // The names of local variables. From Python 3.11 they are followed
+        // by their kinds, before they were followed by the names of free
+        // and cell variables.
+        let num_objects = if self.version >= (3, 11) { 2 } else { 3 };
+
+        for _ in 0..num_objects {
+            self.object(depth + 1)?;
+        }
+
+        if let Object::String(filename) = self.object(depth + 1)? {
+            code.filename = Some(filename);
+        }
+
+        if let Object::String(name) = self.object(depth + 1)? {
+            code.name = Some(name);
+        }
+
+        if self.version >= (3, 11) {
+            if let Object::String(qualname) = self.object(depth + 1)? {
+                code.qualname = Some(qualname);
+            }
+        }
+
+        code.first_line_number = self.u32()?;
+
+        // The line number table, and from Python 3.11 the exception table.
+        self.object(depth + 1)?;
+
+        if self.version >= (3, 11) {
+            self.object(depth + 1)?;
+        }
+
+        self.code_objects.insert(index, code);
+
+        Some(())
+    }
+
+    /// Appends to `strings` the strings in `object` and the tuples nested
+    /// in it, until the budget is exhausted.
+    fn collect_strings(
+        &mut self,
+        object: &Object,
+        strings: &mut Vec<Rc<[u8]>>,
+    ) {
+        match object {
+            Object::String(s) => {
+                if let Some(budget) = self.strings_budget.checked_sub(s.len())
+                {
+                    self.strings_budget = budget;
+                    strings.push(s.clone());
+                }
+            }
+            Object::Tuple(objects) => {
+                for object in objects {
+                    self.collect_strings(object, strings);
+                }
+            }
+            _ => {}
+        }
+    }
+}
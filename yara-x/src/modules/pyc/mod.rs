@@ -0,0 +1,254 @@
+use bstr::ByteSlice;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::pyc::*;
+
+mod marshal;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Pyc {
+    let data = ctx.scanned_data();
+    let mut pyc = Pyc::new();
+
+    let (magic, version) = match data
+        .get(..4)
+        .filter(|header| header.ends_with(b"\r\n"))
+        .map(|header| u16::from_le_bytes([header[0], header[1]]))
+        .and_then(|magic| Some((magic, python_version(magic)?)))
+    {
+        Some(version) => version,
+        None => {
+            pyc.set_is_pyc(false);
+            return pyc;
+        }
+    };
+
+    pyc.set_is_pyc(true);
+    pyc.set_magic(magic.into());
+    pyc.set_python_version(format!("{}.{}", version.0, version.1));
+
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+
+    // Since Python 3.7 the magic is followed by flags, where bit 0
+    // indicates that the file is hash-based. Before, the magic is followed
+    // by the timestamp, and since Python 3.3 by the source's size too.
+    let header_size = if version >= (3, 7) {
+        let flags = u32_at(4).unwrap_or(0);
+        pyc.set_flags(flags.into());
+        pyc.set_is_hash_based(flags & 0x1 != 0);
+        if flags & 0x1 != 0 {
+            pyc.source_hash = data.get(8..16).map(hex);
+        } else {
+            pyc.timestamp = u32_at(8).map(|t| t.into());
+            pyc.source_size = u32_at(12).map(|s| s.into());
+        }
+        16
+    } else if version >= (3, 3) {
+        pyc.set_is_hash_based(false);
+        pyc.timestamp = u32_at(4).map(|t| t.into());
+        pyc.source_size = u32_at(8).map(|s| s.into());
+        12
+    } else {
+        pyc.set_is_hash_based(false);
+        pyc.timestamp = u32_at(4).map(|t| t.into());
+        8
+    };
+
+    let code_objects =
+        marshal::code_objects(data.get(header_size..).unwrap_or(&[]), version);
+
+    for c in code_objects {
+        let mut code = CodeObject::new();
+        code.name = c.name.map(|name| name.to_str_lossy().into_owned());
+        code.qualname =
+            c.qualname.map(|name| name.to_str_lossy().into_owned());
+        code.filename =
+            c.filename.map(|name| name.to_str_lossy().into_owned());
+        code.set_first_line_number(c.first_line_number.into());
+        code.constants = c.constants.iter().map(|s| s.to_vec()).collect();
+        code.names =
+            c.names.iter().map(|s| s.to_str_lossy().into_owned()).collect();
+        pyc.code_objects.push(code);
+    }
+
+    pyc
+}
+
+/// Returns true if any of the code objects has a string or bytes constant
+/// equal to the given one.
+#[module_export]
+fn has_constant(ctx: &ScanContext, constant: RuntimeString) -> Option<bool> {
+    let pyc = ctx.module_output::<Pyc>()?;
+    let constant = constant.as_bstr(ctx);
+
+    Some(pyc.code_objects.iter().any(|code| {
+        code.constants.iter().any(|c| c.as_slice() == constant.as_bytes())
+    }))
+}
+
+/// Returns true if any of the code objects uses a global variable or
+/// attribute with the given name (e.g: `pyc.uses_name("b64decode")`).
+#[module_export]
+fn uses_name(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let pyc = ctx.module_output::<Pyc>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(pyc.code_objects.iter().any(|code| {
+        code.names.iter().any(|n| n.as_bytes() == name.as_bytes())
+    }))
+}
+
+/// Returns the Python version (major, minor) corresponding to a magic
+/// number, or `None` if the magic number is not known.
+///
+/// The magic numbers are listed in `Lib/importlib/_bootstrap_external.py`
+/// in CPython's source code. Development versions used magic numbers in
+/// the ranges below too.
+fn python_version(magic: u16) -> Option<(u8, u8)> {
+    let version = match magic {
+        62171..=62211 => (2, 7),
+        3000..=3131 => (3, 0),
+        3141..=3151 => (3, 1),
+        3160..=3180 => (3, 2),
+        3190..=3230 => (3, 3),
+        3250..=3310 => (3, 4),
+        3320..=3351 => (3, 5),
+        3360..=3379 => (3, 6),
+        3390..=3394 => (3, 7),
+        3400..=3413 => (3, 8),
+        3420..=3425 => (3, 9),
+        3430..=3439 => (3, 10),
+        3450..=3495 => (3, 11),
+        3500..=3531 => (3, 12),
+        3550..=3571 => (3, 13),
+        3600..=3649 => (3, 14),
+        _ => return None,
+    };
+    Some(version)
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    /// Serializes a code object in the format used by Python 3.11.
+    fn code(name: &[u8], consts: &[&[u8]], names: &[&[u8]]) -> Vec<u8> {
+        let mut code = vec![b'c' | 0x80];
+        code.extend([0; 20]);
+        // The bytecode, which is not parsed.
+        code.extend(b"s\x02\x00\x00\x00\x97\x00");
+        code.extend([b')', consts.len() as u8]);
+        for c in consts {
+            code.extend(*c);
+        }
+        code.extend([b')', names.len() as u8]);
+        for n in names {
+            code.extend(*n);
+        }
+        code.extend(b")\x00s\x00\x00\x00\x00");
+        code.extend(b"z\x08t_mod.py");
+        code.extend(name);
+        code.extend(name);
+        code.extend(1_u32.to_le_bytes());
+        code.extend(b"s\x00\x00\x00\x00s\x00\x00\x00\x00");
+        code
+    }
+
+    fn short_str(s: &str) -> Vec<u8> {
+        [&[b'z', s.len() as u8][..], s.as_bytes()].concat()
+    }
+
+    fn build_pyc() -> Vec<u8> {
+        // The `run` function's name is stored as a reference, which is
+        // the third object with FLAG_REF after the two code objects.
+        let run = code(
+            &[&[b'Z' | 0x80, 3][..], b"run"].concat(),
+            &[b"N", &short_str("cHJpbnQoMSk=")],
+            &[
+                &short_str("exec"),
+                &short_str("base64"),
+                &short_str("b64decode"),
+            ],
+        );
+
+        let module = code(
+            &short_str("<module>"),
+            &[&short_str("http://evil.example/payload"), &run, b"N"],
+            &[&short_str("base64"), &short_str("URL"), b"r\x02\x00\x00\x00"],
+        );
+
+        [
+            &b"\xa7\x0d\x0d\x0a"[..],
+            &0_u32.to_le_bytes(),
+            &1690885800_u32.to_le_bytes(),
+            &104_u32.to_le_bytes(),
+            &module,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "pyc"
+                rule rule_1 {
+                  condition:
+                    pyc.is_pyc and
+                    pyc.magic == 3495 and
+                    pyc.python_version == "3.11" and
+                    not pyc.is_hash_based and
+                    pyc.timestamp == 1690885800 and
+                    pyc.source_size == 104 and
+                    not defined pyc.source_hash
+                }
+                rule rule_2 {
+                  condition:
+                    pyc.code_objects[0].name == "<module>" and
+                    pyc.code_objects[0].filename == "t_mod.py" and
+                    pyc.code_objects[0].constants[0] == "http://evil.example/payload" and
+                    pyc.code_objects[0].names[2] == "run" and
+                    pyc.code_objects[1].name == "run" and
+                    pyc.code_objects[1].qualname == "run" and
+                    pyc.code_objects[1].first_line_number == 1 and
+                    pyc.code_objects[1].constants[0] == "cHJpbnQoMSk="
+                }
+                rule rule_3 {
+                  condition:
+                    pyc.has_constant("cHJpbnQoMSk=") and
+                    not pyc.has_constant("foo") and
+                    pyc.uses_name("b64decode") and
+                    not pyc.uses_name("eval")
+                }
+                rule rule_4 { condition: not pyc.is_pyc }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_pyc()),
+            ["rule_1", "rule_2", "rule_3"]
+        );
+        assert_eq!(matching_rules(b"\x00\x00\x0d\x0a"), ["rule_4"]);
+    }
+}
@@ -0,0 +1,306 @@
+/*! Parser for Windows registry hive files.
+
+A hive file starts with a 4KB base block, followed by hive bins that contain
+cells. Each cell starts with its size as a signed 32-bits integer, which is
+negative for allocated cells, followed by the cell's data. Cells are
+referenced by their offset relative to the start of the first hive bin.
+*/
+
+use std::collections::HashSet;
+
+use crate::modules::utils::{u16_at, u32_at, u64_at, utf16_string};
+
+/// Offset of the first hive bin.
+const HIVE_BINS_START: usize = 4096;
+
+/// Maximum depth of the key tree. Deeper keys are ignored.
+const MAX_DEPTH: usize = 512;
+
+/// Maximum number of subkey lists referenced by an index root ("ri").
+const MAX_INDEX_ROOT_LISTS: usize = 1024;
+
+/// Size of the segments in big data records ("db").
+const BIG_DATA_SEGMENT_SIZE: usize = 16344;
+
+/// Offset that indicates the absence of a cell.
+const NO_CELL: u32 = 0xffffffff;
+
+/// The name of a key is stored in ASCII (Latin-1) instead of UTF-16.
+const KEY_COMP_NAME: u16 = 0x20;
+
+/// The name of a value is stored in ASCII (Latin-1) instead of UTF-16.
+const VALUE_COMP_NAME: u16 = 0x1;
+
+/// A registry hive.
+pub(crate) struct Hive<'a> {
+    data: &'a [u8],
+    pub major_version: u32,
+    pub minor_version: u32,
+    /// Last written time, as a Windows FILETIME.
+    pub timestamp: u64,
+    /// Name of the hive file, as stored in the base block.
+    pub file_name: String,
+    root_offset: u32,
+}
+
+/// A registry key.
+pub(crate) struct Key {
+    /// Path of the key relative to the root key, with components separated
+    /// by backslashes. The path of the root key is empty.
+    pub path: String,
+    /// Last written time, as a Windows FILETIME.
+    pub timestamp: u64,
+    pub values: Vec<Value>,
+}
+
+/// A value in a registry key.
+pub(crate) struct Value {
+    /// Name of the value, which is empty for the key's default value.
+    pub name: String,
+    pub value_type: u32,
+    /// The value's data, truncated to the maximum size passed to
+    /// [`Hive::keys`].
+    pub data: Vec<u8>,
+    /// Size of the value's data before truncating it.
+    pub data_size: usize,
+}
+
+impl<'a> Hive<'a> {
+    /// Parses the base block of a hive. Returns `None` if the data is not
+    /// a registry hive.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let base_block = data.get(..HIVE_BINS_START)?;
+
+        if !base_block.starts_with(b"regf") {
+            return None;
+        }
+
+        Some(Self {
+            data,
+            major_version: u32_at(base_block, 20)?,
+            minor_version: u32_at(base_block, 24)?,
+            timestamp: u64_at(base_block, 12)?,
+            file_name: utf16_string(&base_block[48..112]),
+            root_offset: u32_at(base_block, 36)?,
+        })
+    }
+
+    /// Returns all the keys in the hive, in depth-first order starting at
+    /// the root key. Values' data is truncated to `max_data_size` bytes.
+    pub fn keys(&self, max_data_size: usize) -> Vec<Key> {
+        let mut keys = Vec::new();
+        // Offsets of the keys already visited, used for avoiding infinite
+        // loops in corrupt hives.
+        let mut visited = HashSet::new();
+        let mut stack = vec![(self.root_offset, None::<String>, 0)];
+
+        while let Some((offset, parent_path, depth)) = stack.pop() {
+            if depth > MAX_DEPTH || !visited.insert(offset) {
+                continue;
+            }
+
+            let nk = match self.cell(offset) {
+                Some(cell) if cell.starts_with(b"nk") && cell.len() >= 76 => {
+                    cell
+                }
+                _ => continue,
+            };
+
+            let flags = u16_at(nk, 2).unwrap();
+            let name_len = u16_at(nk, 72).unwrap() as usize;
+            let name = match nk.get(76..76 + name_len) {
+                Some(name) if flags & KEY_COMP_NAME != 0 => {
+                    latin1_string(name)
+                }
+                Some(name) => utf16_string(name),
+                None => continue,
+            };
+
+            // The root key's name is not part of the paths.
+            let path = match parent_path {
+                None => String::new(),
+                Some(parent) if parent.is_empty() => name,
+                Some(parent) => format!("{}\\{}", parent, name),
+            };
+
+            let mut subkeys = Vec::new();
+
+            if u32_at(nk, 20).unwrap() > 0 {
+                self.subkeys(u32_at(nk, 28).unwrap(), &mut subkeys, 0);
+            }
+
+            // Subkeys are pushed in reverse order, so that they are visited
+            // in the order they appear in the list.
+            for subkey in subkeys.into_iter().rev() {
+                stack.push((subkey, Some(path.clone()), depth + 1));
+            }
+
+            keys.push(Key {
+                path,
+                timestamp: u64_at(nk, 4).unwrap(),
+                values: self.values(
+                    u32_at(nk, 40).unwrap(),
+                    u32_at(nk, 36).unwrap() as usize,
+                    max_data_size,
+                ),
+            });
+        }
+
+        keys
+    }
+
+    /// Appends to `subkeys` the offsets of the keys in the subkey list at
+    /// `offset`.
+    fn subkeys(&self, offset: u32, subkeys: &mut Vec<u32>, depth: usize) {
+        let list = match self.cell(offset) {
+            Some(list) if list.len() >= 4 => list,
+            _ => return,
+        };
+
+        let count = u16_at(list, 2).unwrap() as usize;
+        let items = &list[4..];
+
+        match &list[..2] {
+            // Fast leaf and hash leaf, each item contains the key offset
+            // followed by a hint or a hash of the name.
+            b"lf" | b"lh" => subkeys.extend(
+                items.chunks_exact(8).take(count).filter_map(|i| u32_at(i, 0)),
+            ),
+            // Index leaf, each item is a key offset.
+            b"li" => subkeys.extend(
+                items.chunks_exact(4).take(count).filter_map(|i| u32_at(i, 0)),
+            ),
+            // Index root, each item is the offset of another subkey list.
+            // Index roots can't be nested.
+            b"ri" if depth == 0 => {
+                for list_offset in items
+                    .chunks_exact(4)
+                    .take(count.min(MAX_INDEX_ROOT_LISTS))
+                    .filter_map(|i| u32_at(i, 0))
+                {
+                    self.subkeys(list_offset, subkeys, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the values in the value list at `offset`.
+    fn values(
+        &self,
+        offset: u32,
+        count: usize,
+        max_data_size: usize,
+    ) -> Vec<Value> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let list = match self.cell(offset) {
+            Some(list) => list,
+            None => return Vec::new(),
+        };
+
+        list.chunks_exact(4)
+            .take(count)
+            .filter_map(|item| self.value(u32_at(item, 0)?, max_data_size))
+            .collect()
+    }
+
+    /// Parses the value at `offset`.
+    fn value(&self, offset: u32, max_data_size: usize) -> Option<Value> {
+        let vk = self.cell(offset)?;
+
+        if !vk.starts_with(b"vk") || vk.len() < 20 {
+            return None;
+        }
+
+        let name_len = u16_at(vk, 2)? as usize;
+        let data_size = u32_at(vk, 4)?;
+        let data_offset = u32_at(vk, 8)?;
+        let flags = u16_at(vk, 16)?;
+        let name = vk.get(20..20 + name_len)?;
+
+        let name = if flags & VALUE_COMP_NAME != 0 {
+            latin1_string(name)
+        } else {
+            utf16_string(name)
+        };
+
+        // When the most significant bit of the size is set, the data is
+        // stored in the place of the data offset, and it's 4 bytes at most.
+        let (mut data, data_size) = if data_size & 0x80000000 != 0 {
+            let size = (data_size & 0x7fffffff) as usize;
+            (vk[8..8 + size.min(4)].to_vec(), size)
+        } else {
+            let size = data_size as usize;
+            (self.data(data_offset, size, max_data_size), size)
+        };
+
+        data.truncate(max_data_size);
+
+        Some(Value { name, value_type: u32_at(vk, 12)?, data, data_size })
+    }
+
+    /// Returns the first `max_size` bytes of the data at `offset`, which
+    /// has a total size of `size` bytes.
+    fn data(&self, offset: u32, size: usize, max_size: usize) -> Vec<u8> {
+        let cell = match self.cell(offset) {
+            Some(cell) => cell,
+            None => return Vec::new(),
+        };
+
+        // Starting with version 1.4, data larger than a segment is stored
+        // in multiple segments, described by a big data record.
+        if size > BIG_DATA_SEGMENT_SIZE
+            && self.minor_version >= 4
+            && cell.starts_with(b"db")
+            && cell.len() >= 8
+        {
+            let count = u16_at(cell, 2).unwrap() as usize;
+            let mut data = Vec::new();
+
+            if let Some(segments) = self.cell(u32_at(cell, 4).unwrap()) {
+                for segment in segments
+                    .chunks_exact(4)
+                    .take(count)
+                    .filter_map(|s| self.cell(u32_at(s, 0)?))
+                {
+                    let remaining = size.min(max_size) - data.len();
+                    let len = segment
+                        .len()
+                        .min(BIG_DATA_SEGMENT_SIZE)
+                        .min(remaining);
+                    data.extend_from_slice(&segment[..len]);
+                    if data.len() == size.min(max_size) {
+                        break;
+                    }
+                }
+            }
+
+            return data;
+        }
+
+        cell[..cell.len().min(size).min(max_size)].to_vec()
+    }
+
+    /// Returns the data of the cell at `offset`.
+    fn cell(&self, offset: u32) -> Option<&'a [u8]> {
+        if offset == NO_CELL {
+            return None;
+        }
+
+        let start = HIVE_BINS_START.checked_add(offset as usize)?;
+        let size = i32::from_le_bytes(
+            self.data.get(start..start + 4)?.try_into().unwrap(),
+        )
+        .unsigned_abs() as usize;
+
+        // The size includes the 4 bytes of the size itself.
+        self.data.get(start + 4..start.checked_add(size)?)
+    }
+}
+
+fn latin1_string(data: &[u8]) -> String {
+    data.iter().map(|b| *b as char).collect()
+}
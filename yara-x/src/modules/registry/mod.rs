@@ -0,0 +1,342 @@
+use protobuf::{Enum, EnumOrUnknown};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::registry::*;
+use crate::modules::utils::{filetime_to_epoch, utf16_string};
+
+mod hive;
+
+/// Maximum size of the data exposed for each value.
+const MAX_VALUE_DATA_SIZE: usize = 4096;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> RegistryHive {
+    let mut registry = RegistryHive::new();
+
+    let hive = match hive::Hive::parse(ctx.scanned_data()) {
+        Some(hive) => hive,
+        None => {
+            registry.set_is_hive(false);
+            return registry;
+        }
+    };
+
+    registry.set_is_hive(true);
+    registry.set_major_version(hive.major_version.into());
+    registry.set_minor_version(hive.minor_version.into());
+    registry.timestamp = filetime_to_epoch(hive.timestamp);
+    registry.set_file_name(hive.file_name.clone());
+
+    for k in hive.keys(MAX_VALUE_DATA_SIZE) {
+        let mut key = Key::new();
+        key.set_path(k.path);
+        key.timestamp = filetime_to_epoch(k.timestamp);
+
+        for v in k.values {
+            let mut value = Value::new();
+            value.set_name(v.name);
+            value.type_ = Some(EnumOrUnknown::from_i32(v.value_type as i32));
+            value.set_data_size(v.data_size as i64);
+            value.string_data = string_data(v.value_type, &v.data);
+            value.integer_data = integer_data(v.value_type, &v.data);
+            value.set_data(v.data);
+            key.values.push(value);
+        }
+
+        registry.keys.push(key);
+    }
+
+    registry
+}
+
+/// Returns true if the hive contains a key with the given path. The
+/// comparison is case-insensitive, and the leading backslash is optional.
+#[module_export]
+fn has_key(ctx: &ScanContext, path: RuntimeString) -> Option<bool> {
+    let registry = ctx.module_output::<RegistryHive>()?;
+    let path = path.to_str(ctx).ok()?;
+
+    Some(find_key(registry, path).is_some())
+}
+
+/// Returns the data of the value with the given name in the key with the
+/// given path. The data is truncated to 4KB.
+#[module_export]
+fn value_data(
+    ctx: &mut ScanContext,
+    path: RuntimeString,
+    name: RuntimeString,
+) -> Option<RuntimeString> {
+    let registry = ctx.module_output::<RegistryHive>()?;
+    let path = path.to_str(ctx).ok()?;
+    let name = name.to_str(ctx).ok()?;
+
+    let data = find_key(registry, path)?
+        .values
+        .iter()
+        .find(|value| value.name().eq_ignore_ascii_case(name))?
+        .data()
+        .to_vec();
+
+    Some(RuntimeString::from_bytes(ctx, data))
+}
+
+fn find_key<'a>(registry: &'a RegistryHive, path: &str) -> Option<&'a Key> {
+    let path = path.strip_prefix('\\').unwrap_or(path);
+
+    registry.keys.iter().find(|key| key.path().eq_ignore_ascii_case(path))
+}
+
+/// Decodes the data of values that contain strings.
+fn string_data(value_type: u32, data: &[u8]) -> Option<String> {
+    match ValueType::from_i32(value_type as i32)? {
+        ValueType::REG_SZ | ValueType::REG_EXPAND_SZ | ValueType::REG_LINK => {
+            Some(utf16_string(data))
+        }
+        ValueType::REG_MULTI_SZ => {
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            let strings = String::from_utf16_lossy(&units);
+            Some(
+                strings
+                    .split('\0')
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Decodes the data of values that contain integers.
+fn integer_data(value_type: u32, data: &[u8]) -> Option<i64> {
+    match ValueType::from_i32(value_type as i32)? {
+        ValueType::REG_DWORD => {
+            Some(u32::from_le_bytes(data.get(..4)?.try_into().unwrap()).into())
+        }
+        ValueType::REG_DWORD_BIG_ENDIAN => {
+            Some(u32::from_be_bytes(data.get(..4)?.try_into().unwrap()).into())
+        }
+        ValueType::REG_QWORD => {
+            Some(i64::from_le_bytes(data.get(..8)?.try_into().unwrap()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// FILETIME corresponding to 2023-08-01 10:30:00 UTC.
+    const FILETIME: u64 = (1690885800 + 11644473600) * 10_000_000;
+
+    /// Builds the hive bins of a registry hive.
+    struct HiveBins {
+        data: Vec<u8>,
+    }
+
+    impl HiveBins {
+        fn new() -> Self {
+            let mut data = b"hbin".to_vec();
+            data.resize(32, 0);
+            Self { data }
+        }
+
+        /// Appends an allocated cell and returns its offset.
+        fn cell(&mut self, content: &[u8]) -> u32 {
+            let offset = self.data.len();
+            let size = (content.len() + 4 + 7) & !7;
+            self.data.extend((-(size as i32)).to_le_bytes());
+            self.data.extend(content);
+            self.data.resize(offset + size, 0);
+            offset as u32
+        }
+
+        fn key(
+            &mut self,
+            name: &[u8],
+            flags: u16,
+            subkeys: (u32, u32),
+            values: &[u32],
+        ) -> u32 {
+            let value_list = if values.is_empty() {
+                0xffffffff
+            } else {
+                let list: Vec<u8> =
+                    values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                self.cell(&list)
+            };
+
+            let mut nk = b"nk".to_vec();
+            nk.extend(flags.to_le_bytes());
+            nk.extend(FILETIME.to_le_bytes());
+            nk.resize(20, 0);
+            nk.extend(subkeys.0.to_le_bytes());
+            nk.extend(0_u32.to_le_bytes());
+            nk.extend(subkeys.1.to_le_bytes());
+            nk.extend(0xffffffff_u32.to_le_bytes());
+            nk.extend((values.len() as u32).to_le_bytes());
+            nk.extend(value_list.to_le_bytes());
+            nk.resize(72, 0);
+            nk.extend((name.len() as u16).to_le_bytes());
+            nk.extend(0_u16.to_le_bytes());
+            nk.extend(name);
+            self.cell(&nk)
+        }
+
+        fn value(&mut self, name: &str, value_type: u32, data: &[u8]) -> u32 {
+            let (size, offset) = if data.len() <= 4 {
+                let mut inline = data.to_vec();
+                inline.resize(4, 0);
+                (
+                    data.len() as u32 | 0x80000000,
+                    u32::from_le_bytes(inline.try_into().unwrap()),
+                )
+            } else {
+                (data.len() as u32, self.cell(data))
+            };
+
+            let mut vk = b"vk".to_vec();
+            vk.extend((name.len() as u16).to_le_bytes());
+            vk.extend(size.to_le_bytes());
+            vk.extend(offset.to_le_bytes());
+            vk.extend(value_type.to_le_bytes());
+            vk.extend(1_u16.to_le_bytes());
+            vk.extend(0_u16.to_le_bytes());
+            vk.extend(name.as_bytes());
+            self.cell(&vk)
+        }
+    }
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn build_hive() -> Vec<u8> {
+        let mut bins = HiveBins::new();
+
+        let values = [
+            bins.value("Updater", 1, &utf16("C:\\evil.exe\0")),
+            bins.value("Count", 4, &42_u32.to_le_bytes()),
+            bins.value("Paths", 7, &utf16("a\0b\0\0")),
+            bins.value("", 3, b"abc"),
+        ];
+
+        // The name of this key is stored in UTF-16.
+        let microsoft = bins.key(&utf16("Microsoft"), 0, (0, 0), &values);
+        let classes = bins.key(b"Classes", 0x20, (0, 0), &[]);
+
+        // The subkeys of `Software` are in two lists, referenced by an
+        // index root.
+        let mut li = b"li".to_vec();
+        li.extend(1_u16.to_le_bytes());
+        li.extend(microsoft.to_le_bytes());
+        let li = bins.cell(&li);
+
+        let mut lf = b"lf".to_vec();
+        lf.extend(1_u16.to_le_bytes());
+        lf.extend(classes.to_le_bytes());
+        lf.extend(b"Clas");
+        let lf = bins.cell(&lf);
+
+        let mut ri = b"ri".to_vec();
+        ri.extend(2_u16.to_le_bytes());
+        ri.extend(li.to_le_bytes());
+        ri.extend(lf.to_le_bytes());
+        let ri = bins.cell(&ri);
+
+        let software = bins.key(b"Software", 0x20, (2, ri), &[]);
+
+        let mut lh = b"lh".to_vec();
+        lh.extend(1_u16.to_le_bytes());
+        lh.extend(software.to_le_bytes());
+        lh.extend(0_u32.to_le_bytes());
+        let lh = bins.cell(&lh);
+
+        let root = bins.key(b"ROOT", 0x20 | 0x4, (1, lh), &[]);
+
+        let mut hive = b"regf".to_vec();
+        hive.extend(1_u32.to_le_bytes());
+        hive.extend(1_u32.to_le_bytes());
+        hive.extend(FILETIME.to_le_bytes());
+        hive.extend(1_u32.to_le_bytes());
+        hive.extend(5_u32.to_le_bytes());
+        hive.extend(0_u32.to_le_bytes());
+        hive.extend(1_u32.to_le_bytes());
+        hive.extend(root.to_le_bytes());
+        hive.extend((bins.data.len() as u32).to_le_bytes());
+        hive.extend(1_u32.to_le_bytes());
+        hive.extend(utf16("\\??\\C:\\Users\\John\\ntuser.dat"));
+        hive.resize(4096, 0);
+        hive.extend(bins.data);
+        hive
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "registry"
+                rule rule_1 {
+                  condition:
+                    registry.is_hive and
+                    registry.major_version == 1 and
+                    registry.minor_version == 5 and
+                    registry.timestamp == 1690885800 and
+                    registry.file_name == "\\??\\C:\\Users\\John\\ntuser.dat" and
+                    registry.keys[0].path == "" and
+                    registry.keys[1].path == "Software" and
+                    registry.keys[2].path == "Software\\Microsoft" and
+                    registry.keys[3].path == "Software\\Classes" and
+                    registry.keys[3].timestamp == 1690885800
+                }
+                rule rule_2 {
+                  condition:
+                    for any v in registry.keys[2].values : (
+                      v.name == "Updater" and
+                      v.type == registry.ValueType.REG_SZ and
+                      v.string_data == "C:\\evil.exe" and
+                      v.data_size == 24
+                    ) and
+                    registry.keys[2].values[1].integer_data == 42 and
+                    registry.keys[2].values[2].string_data == "a\nb" and
+                    registry.keys[2].values[3].name == "" and
+                    registry.keys[2].values[3].data == "abc"
+                }
+                rule rule_3 {
+                  condition:
+                    registry.has_key("software\\microsoft") and
+                    registry.has_key("\\Software") and
+                    not registry.has_key("Software\\Foo") and
+                    registry.value_data("Software\\Microsoft", "count") == "\x2a\x00\x00\x00" and
+                    not defined registry.value_data("Software", "Count")
+                }
+                rule rule_4 { condition: not registry.is_hive }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_hive()),
+            ["rule_1", "rule_2", "rule_3"]
+        );
+
+        assert_eq!(matching_rules(b"regf"), ["rule_4"]);
+    }
+}
@@ -0,0 +1,154 @@
+use bstr::ByteSlice;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::rtf::*;
+
+mod parser;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Rtf {
+    let mut rtf = Rtf::new();
+
+    let doc = match parser::parse(ctx.scanned_data()) {
+        Some(doc) => doc,
+        None => {
+            rtf.set_is_rtf(false);
+            return rtf;
+        }
+    };
+
+    rtf.set_is_rtf(true);
+    rtf.set_malformed_header(doc.malformed_header);
+    rtf.set_unbalanced_groups(doc.unbalanced_groups);
+    rtf.set_num_groups(doc.num_groups as i64);
+    rtf.set_max_group_depth(doc.max_group_depth as i64);
+    rtf.set_num_control_words(doc.num_control_words as i64);
+
+    for (name, count) in doc.control_words {
+        let mut control_word = ControlWord::new();
+        control_word.set_name(name.to_str_lossy().into_owned());
+        control_word.set_count(count);
+        rtf.control_words.push(control_word);
+    }
+
+    for object in doc.objects {
+        let mut obj = Object::new();
+        obj.type_ = object.object_type;
+        obj.class_name = object
+            .class_name
+            .map(|class_name| class_name.to_str_lossy().trim().to_string());
+        if let Some((class_name, native_size)) =
+            parser::ole1_header(&object.objdata)
+        {
+            obj.set_ole_class_name(class_name.to_str_lossy().into_owned());
+            obj.ole_native_size = native_size.map(|size| size.into());
+        }
+        obj.set_objdata(object.objdata);
+        rtf.objects.push(obj);
+    }
+
+    rtf
+}
+
+/// Returns the number of times a control word appears in the document. The
+/// name can be specified with or without the leading backslash.
+#[module_export]
+fn control_word_count(ctx: &ScanContext, name: RuntimeString) -> Option<i64> {
+    let rtf = ctx.module_output::<Rtf>()?;
+    let name = name.as_bstr(ctx);
+    let name = name.strip_prefix(b"\\").unwrap_or(name);
+
+    Some(
+        rtf.control_words
+            .iter()
+            .find(|control_word| control_word.name().as_bytes() == name)
+            .map(|control_word| control_word.count())
+            .unwrap_or(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    /// Returns an OLE1 embedded object with the given class name and
+    /// native data, encoded in hex.
+    fn ole1_object(class_name: &str, native_data: &[u8]) -> String {
+        let mut object = Vec::new();
+        object.extend(0x0501_u32.to_le_bytes());
+        object.extend(2_u32.to_le_bytes());
+        object.extend((class_name.len() as u32 + 1).to_le_bytes());
+        object.extend(class_name.as_bytes());
+        object.push(0);
+        object.extend(0_u32.to_le_bytes());
+        object.extend(0_u32.to_le_bytes());
+        object.extend((native_data.len() as u32).to_le_bytes());
+        object.extend(native_data);
+        object.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn end2end() {
+        let hex = ole1_object("Equation.3", b"\x1c\x00\x00\x00");
+
+        // The object data is split in multiple lines, and contains an
+        // ignorable destination that must be skipped.
+        let rtf = format!(
+            "{{\\rtf1\\ansi{{\\fonttbl{{\\f0 Arial;}}}}\n\
+             {{\\object\\objemb\\objw100{{\\*\\objclass Equation.3}}\
+             {{\\*\\objdata {}\n{}{{\\*\\foo 4142}}{}}}}}\\bin3 {{}}\\par}}",
+            &hex[..10],
+            &hex[10..20],
+            &hex[20..],
+        );
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "rtf"
+                rule rule_1 {
+                  condition:
+                    rtf.is_rtf and
+                    not rtf.malformed_header and
+                    not rtf.unbalanced_groups and
+                    rtf.num_groups == 7 and
+                    rtf.max_group_depth == 4 and
+                    rtf.control_word_count("\\object") == 1 and
+                    rtf.control_word_count("bin") == 1 and
+                    rtf.control_word_count("pard") == 0
+                }
+                rule rule_2 {
+                  condition:
+                    rtf.objects[0].type == "objemb" and
+                    rtf.objects[0].class_name == "Equation.3" and
+                    rtf.objects[0].ole_class_name == "Equation.3" and
+                    rtf.objects[0].ole_native_size == 4 and
+                    rtf.objects[0].objdata endswith "\x1c\x00\x00\x00"
+                }
+                rule rule_3 {
+                  condition:
+                    rtf.is_rtf and
+                    rtf.malformed_header and
+                    rtf.unbalanced_groups
+                }
+                rule rule_4 { condition: not rtf.is_rtf }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(rtf.as_bytes()), ["rule_1", "rule_2"]);
+        assert_eq!(matching_rules(b"{\\rtxyz{\\par}"), ["rule_3"]);
+        assert_eq!(matching_rules(b"not rtf"), ["rule_4"]);
+    }
+}
@@ -0,0 +1,257 @@
+/*! Tokenizer-based parser for RTF documents.
+
+The parser doesn't interpret the document's formatting, it only keeps track
+of groups and control words, and collects the data of the destinations
+that describe embedded objects (`\objclass` and `\objdata`).
+*/
+
+use std::collections::BTreeMap;
+
+use crate::modules::utils::u32_at;
+
+/// Maximum size of the decoded data of an object.
+const MAX_OBJDATA_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum length of a control word, longer sequences of letters are split
+/// in multiple control words, as done by Microsoft Word.
+const MAX_CONTROL_WORD_LEN: usize = 255;
+
+/// Control words that indicate an object's type.
+const OBJECT_TYPES: &[&[u8]] = &[
+    b"objemb",
+    b"objlink",
+    b"objautlink",
+    b"objsub",
+    b"objpub",
+    b"objicemb",
+    b"objhtml",
+    b"objocx",
+];
+
+#[derive(Default)]
+pub(crate) struct Object {
+    pub object_type: Option<String>,
+    pub class_name: Option<Vec<u8>>,
+    pub objdata: Vec<u8>,
+    /// Whether the next hex digit in `objdata` is the high nibble of a byte
+    /// that was already pushed.
+    pending_nibble: bool,
+}
+
+impl Object {
+    /// Appends the hex digits in `text` to the object's data, ignoring any
+    /// other character.
+    fn push_hex(&mut self, text: &[u8]) {
+        for digit in text.iter().filter_map(|b| (*b as char).to_digit(16)) {
+            if self.pending_nibble {
+                *self.objdata.last_mut().unwrap() |= digit as u8;
+                self.pending_nibble = false;
+            } else if self.objdata.len() < MAX_OBJDATA_SIZE {
+                self.objdata.push((digit as u8) << 4);
+                self.pending_nibble = true;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Document {
+    pub malformed_header: bool,
+    pub unbalanced_groups: bool,
+    pub num_groups: usize,
+    pub max_group_depth: usize,
+    pub num_control_words: usize,
+    pub control_words: BTreeMap<Vec<u8>, i64>,
+    pub objects: Vec<Object>,
+}
+
+/// Destinations whose content is collected.
+#[derive(Clone, Copy, PartialEq)]
+enum Destination {
+    Other,
+    ObjClass,
+    ObjData,
+}
+
+/// State of a group, which is inherited by the groups nested in it.
+#[derive(Clone, Copy)]
+struct Group {
+    destination: Destination,
+    /// Index in `Document::objects` of the object described by the group.
+    object: Option<usize>,
+}
+
+/// Parses a RTF document. Returns `None` if the data doesn't start with
+/// `{\rt`.
+pub(crate) fn parse(data: &[u8]) -> Option<Document> {
+    if !data.starts_with(b"{\\rt") {
+        return None;
+    }
+
+    let mut doc = Document {
+        malformed_header: !data.starts_with(b"{\\rtf1")
+            || data.get(6).map_or(false, |b| b.is_ascii_digit()),
+        ..Default::default()
+    };
+
+    let mut stack: Vec<Group> = Vec::new();
+    let mut current = Group { destination: Destination::Other, object: None };
+    let mut pos = 0;
+
+    while let Some(&b) = data.get(pos) {
+        pos += 1;
+        match b {
+            b'{' => {
+                stack.push(current);
+                doc.num_groups += 1;
+                doc.max_group_depth = doc.max_group_depth.max(stack.len());
+            }
+            b'}' => match stack.pop() {
+                Some(group) => current = group,
+                None => doc.unbalanced_groups = true,
+            },
+            b'\\' => {
+                let start = pos;
+                while pos < data.len()
+                    && pos - start < MAX_CONTROL_WORD_LEN
+                    && data[pos].is_ascii_alphabetic()
+                {
+                    pos += 1;
+                }
+
+                // A backslash followed by something that is not a letter
+                // is a control symbol, like `\'hh` or `\*`.
+                if pos == start {
+                    if data.get(pos) == Some(&b'\'') {
+                        pos += 3;
+                    } else {
+                        pos += 1;
+                    }
+                    continue;
+                }
+
+                let word = &data[start..pos];
+
+                // The control word can be followed by a numeric parameter,
+                // and a space that is part of the control word.
+                let param_start = pos;
+                if data.get(pos) == Some(&b'-') {
+                    pos += 1;
+                }
+                while data.get(pos).map_or(false, |b| b.is_ascii_digit()) {
+                    pos += 1;
+                }
+                let param = std::str::from_utf8(&data[param_start..pos])
+                    .ok()
+                    .and_then(|param| param.parse::<i64>().ok());
+                if data.get(pos) == Some(&b' ') {
+                    pos += 1;
+                }
+
+                doc.num_control_words += 1;
+                *doc.control_words.entry(word.to_vec()).or_default() += 1;
+
+                match word {
+                    // `\binN` is followed by N bytes of binary data.
+                    b"bin" => {
+                        let len = param
+                            .and_then(|len| usize::try_from(len).ok())
+                            .unwrap_or(0);
+                        pos = pos.saturating_add(len);
+                    }
+                    b"object" => {
+                        doc.objects.push(Object::default());
+                        current.object = Some(doc.objects.len() - 1);
+                    }
+                    b"objclass" => {
+                        current.destination = Destination::ObjClass;
+                    }
+                    b"objdata" => {
+                        if current.object.is_none() {
+                            doc.objects.push(Object::default());
+                            current.object = Some(doc.objects.len() - 1);
+                        }
+                        current.destination = Destination::ObjData;
+                    }
+                    word if OBJECT_TYPES.contains(&word) => {
+                        if let Some(object) = current.object {
+                            doc.objects[object].object_type =
+                                Some(String::from_utf8_lossy(word).into());
+                        }
+                    }
+                    _ => {
+                        // Other control words that start a destination end
+                        // the collection of object data. Words that only
+                        // change formatting don't.
+                        if current.destination != Destination::Other
+                            && data[..start - 1].ends_with(b"\\*")
+                        {
+                            current.destination = Destination::Other;
+                        }
+                    }
+                }
+            }
+            _ => {
+                let start = pos - 1;
+                while pos < data.len()
+                    && !matches!(data[pos], b'{' | b'}' | b'\\')
+                {
+                    pos += 1;
+                }
+                let text = &data[start..pos];
+                let object = match current.object {
+                    Some(object) => &mut doc.objects[object],
+                    None => continue,
+                };
+                match current.destination {
+                    Destination::ObjData => object.push_hex(text),
+                    Destination::ObjClass => object
+                        .class_name
+                        .get_or_insert_with(Vec::new)
+                        .extend(text.iter().filter(|b| !b"\r\n".contains(b))),
+                    Destination::Other => {}
+                }
+            }
+        }
+    }
+
+    if !stack.is_empty() {
+        doc.unbalanced_groups = true;
+    }
+
+    Some(doc)
+}
+
+/// Parses the header of an OLE1 object, returning the object's class name
+/// and the size of its native data.
+///
+/// ```text
+/// OLEVersion (4 bytes)
+/// FormatID (4 bytes), 1 for linked objects, 2 for embedded objects
+/// ClassName (length-prefixed string)
+/// TopicName (length-prefixed string)
+/// ItemName (length-prefixed string)
+/// NativeDataSize (4 bytes), only in embedded objects
+/// ```
+pub(crate) fn ole1_header(data: &[u8]) -> Option<(Vec<u8>, Option<u32>)> {
+    let format_id = u32_at(data, 4)?;
+
+    if format_id != 1 && format_id != 2 {
+        return None;
+    }
+
+    let mut pos = 8;
+    let mut strings = Vec::new();
+
+    for _ in 0..3 {
+        let len = u32_at(data, pos)? as usize;
+        let s = data.get(pos + 4..(pos + 4).checked_add(len)?)?;
+        strings.push(s.strip_suffix(b"\0").unwrap_or(s));
+        pos += 4 + len;
+    }
+
+    let native_size =
+        if format_id == 2 { Some(u32_at(data, pos)?) } else { None };
+
+    Some((strings[0].to_vec(), native_size))
+}
@@ -0,0 +1,305 @@
+/*! Reader for SQLite database files.
+
+A database is a sequence of pages of the same size. The first page starts
+with a 100 bytes header, followed by the root of the table B-tree that
+contains the schema. Tables are stored as B-trees where leaf pages contain
+the rows, encoded as records. Rows that don't fit in a page are continued
+in a linked list of overflow pages.
+*/
+
+use std::collections::HashSet;
+
+use crate::modules::utils::{u16_be_at, u32_be_at};
+
+const HEADER_SIZE: usize = 100;
+
+/// Maximum size of the payload read for each row. Larger payloads are
+/// truncated, and the values that don't fit are ignored.
+const MAX_PAYLOAD_SIZE: usize = 65536;
+
+/// The database header.
+pub(crate) struct Header {
+    pub page_size: usize,
+    pub reserved_space: u8,
+    pub file_change_counter: u32,
+    /// Size of the database in pages.
+    pub num_pages: u32,
+    pub num_freelist_pages: u32,
+    pub schema_format: u32,
+    /// Text encoding: 1 for UTF-8, 2 for UTF-16LE, 3 for UTF-16BE.
+    pub text_encoding: u32,
+    pub user_version: u32,
+    pub application_id: u32,
+    /// Version of SQLite that last modified the database (e.g: 3045001).
+    pub sqlite_version: u32,
+}
+
+/// A value in a record.
+pub(crate) enum Value<'a> {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(&'a [u8]),
+    Blob(&'a [u8]),
+}
+
+/// A SQLite database.
+pub(crate) struct Database<'a> {
+    data: &'a [u8],
+    pub header: Header,
+    /// Number of usable bytes in each page.
+    usable_size: usize,
+}
+
+impl<'a> Database<'a> {
+    /// Parses the database header. Returns `None` if the data is not a
+    /// SQLite database.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let header = data.get(..HEADER_SIZE)?;
+
+        if !header.starts_with(b"SQLite format 3\0") {
+            return None;
+        }
+
+        // A page size of 1 represents 65536.
+        let page_size = match u16_be_at(header, 16)? {
+            1 => 65536,
+            size if size.is_power_of_two() && size >= 512 => size as usize,
+            _ => return None,
+        };
+
+        let header = Header {
+            page_size,
+            reserved_space: header[20],
+            file_change_counter: u32_be_at(header, 24)?,
+            num_pages: u32_be_at(header, 28)?,
+            num_freelist_pages: u32_be_at(header, 36)?,
+            schema_format: u32_be_at(header, 44)?,
+            text_encoding: u32_be_at(header, 56)?,
+            user_version: u32_be_at(header, 60)?,
+            application_id: u32_be_at(header, 68)?,
+            sqlite_version: u32_be_at(header, 96)?,
+        };
+
+        let usable_size =
+            page_size.checked_sub(header.reserved_space.into())?;
+
+        // The usable size must be at least 480 bytes.
+        if usable_size < 480 {
+            return None;
+        }
+
+        Some(Self { data, header, usable_size })
+    }
+
+    /// Visits the rows in the table B-tree whose root is `root_page`, in
+    /// order, calling `f` with the payload of each row until it returns
+    /// false. Returns the number of rows in the table.
+    pub fn visit_rows<F>(&self, root_page: u32, mut f: F) -> usize
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let mut num_rows = 0;
+        let mut visit = true;
+        let mut visited = HashSet::new();
+        let mut stack = vec![root_page];
+
+        while let Some(page_number) = stack.pop() {
+            // Pages visited before indicate a loop in a corrupt database.
+            if !visited.insert(page_number) {
+                continue;
+            }
+
+            let (page, header_offset) = match self.page(page_number) {
+                Some(page) => page,
+                None => continue,
+            };
+
+            let btree = &page[header_offset..];
+            let num_cells = match u16_be_at(btree, 3) {
+                Some(n) => n as usize,
+                None => continue,
+            };
+
+            match btree[0] {
+                // Interior page of a table B-tree. Each cell contains a
+                // pointer to a child page, and the right-most pointer is in
+                // the page header. Children are pushed in reverse order, so
+                // that they are visited in order.
+                0x05 => {
+                    if let Some(right_most) = u32_be_at(btree, 8) {
+                        stack.push(right_most);
+                    }
+                    for i in (0..num_cells).rev() {
+                        if let Some(child) = u16_be_at(btree, 12 + 2 * i)
+                            .and_then(|offset| {
+                                u32_be_at(page, offset as usize)
+                            })
+                        {
+                            stack.push(child);
+                        }
+                    }
+                }
+                // Leaf page of a table B-tree.
+                0x0d => {
+                    num_rows += num_cells;
+                    if !visit {
+                        continue;
+                    }
+                    for i in 0..num_cells {
+                        let payload = u16_be_at(btree, 8 + 2 * i)
+                            .and_then(|offset| self.payload(page, offset));
+                        if let Some(payload) = payload {
+                            if !f(&payload) {
+                                visit = false;
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        num_rows
+    }
+
+    /// Returns the payload of the table leaf cell at `offset` within
+    /// `page`, following the overflow pages if necessary.
+    fn payload(&self, page: &[u8], offset: u16) -> Option<Vec<u8>> {
+        let mut pos = offset as usize;
+        let payload_size = varint(page, &mut pos)? as usize;
+        // The rowid, not used.
+        varint(page, &mut pos)?;
+
+        // Size of the part of the payload stored in the page, as described
+        // in the file format documentation.
+        let u = self.usable_size;
+        let x = u - 35;
+        let local_size = if payload_size <= x {
+            payload_size
+        } else {
+            let m = ((u - 12) * 32 / 255) - 23;
+            let k = m + ((payload_size - m) % (u - 4));
+            if k <= x {
+                k
+            } else {
+                m
+            }
+        };
+
+        let max_size = payload_size.min(MAX_PAYLOAD_SIZE);
+        let local = page.get(pos..pos + local_size)?;
+        let mut payload = local[..local.len().min(max_size)].to_vec();
+
+        let mut next = if local_size < payload_size {
+            u32_be_at(page, pos + local_size)?
+        } else {
+            0
+        };
+
+        let mut visited = HashSet::new();
+
+        while next != 0 && payload.len() < max_size && visited.insert(next) {
+            let (overflow, _) = self.page(next)?;
+            next = u32_be_at(overflow, 0)?;
+            let content = overflow.get(4..u)?;
+            let len = content.len().min(max_size - payload.len());
+            payload.extend_from_slice(&content[..len]);
+        }
+
+        Some(payload)
+    }
+
+    /// Returns the page with the given number, and the offset of the B-tree
+    /// header within the page, which is 100 for the first page and 0 for
+    /// the rest.
+    fn page(&self, page_number: u32) -> Option<(&'a [u8], usize)> {
+        let start = (page_number as usize)
+            .checked_sub(1)?
+            .checked_mul(self.header.page_size)?;
+
+        let page = self.data.get(start..start + self.header.page_size)?;
+        let header_offset = if page_number == 1 { HEADER_SIZE } else { 0 };
+
+        Some((page, header_offset))
+    }
+}
+
+/// Parses a record, returning its values. Values that don't fit in the
+/// record, because it was truncated, are not returned.
+pub(crate) fn record(payload: &[u8]) -> Vec<Value<'_>> {
+    let mut values = Vec::new();
+    let mut pos = 0;
+
+    let header_size = match varint(payload, &mut pos) {
+        Some(size) => size as usize,
+        None => return values,
+    };
+
+    let mut data_pos = header_size;
+
+    while pos < header_size.min(payload.len()) {
+        let serial_type = match varint(payload, &mut pos) {
+            Some(serial_type) => serial_type,
+            None => break,
+        };
+
+        let size = match serial_type {
+            0 | 8 | 9 => 0,
+            1..=4 => serial_type as usize,
+            5 => 6,
+            6 | 7 => 8,
+            n if n >= 12 => ((n - 12) / 2) as usize,
+            _ => break,
+        };
+
+        let data = match payload.get(data_pos..data_pos + size) {
+            Some(data) => data,
+            None => break,
+        };
+
+        data_pos += size;
+
+        values.push(match serial_type {
+            0 => Value::Null,
+            1..=6 => {
+                // Big-endian signed integer.
+                let value = data.iter().fold(
+                    if data[0] & 0x80 != 0 { -1_i64 } else { 0 },
+                    |acc, b| (acc << 8) | *b as i64,
+                );
+                Value::Integer(value)
+            }
+            7 => Value::Real(f64::from_be_bytes(data.try_into().unwrap())),
+            8 => Value::Integer(0),
+            9 => Value::Integer(1),
+            n if n % 2 == 0 => Value::Blob(data),
+            _ => Value::Text(data),
+        });
+    }
+
+    values
+}
+
+/// Reads a variable-length integer, as used in SQLite records and B-tree
+/// cells. The integer is encoded in 1 to 9 bytes, big-endian, where all the
+/// bytes except the last one have the high-order bit set. The 9th byte, if
+/// present, contributes all its 8 bits.
+fn varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0_u64;
+
+    for i in 0..9 {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        if i == 8 {
+            return Some((value << 8) | b as u64);
+        }
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    unreachable!()
+}
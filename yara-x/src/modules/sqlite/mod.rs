@@ -0,0 +1,369 @@
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::sqlite::*;
+
+mod btree;
+
+use btree::Value;
+
+/// Maximum number of rows included in the sample of each table.
+const MAX_SAMPLED_ROWS: usize = 16;
+
+/// Maximum size of the values in the sampled rows.
+const MAX_VALUE_SIZE: usize = 1024;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Sqlite {
+    let mut sqlite = Sqlite::new();
+
+    let db = match btree::Database::parse(ctx.scanned_data()) {
+        Some(db) => db,
+        None => {
+            sqlite.set_is_sqlite(false);
+            return sqlite;
+        }
+    };
+
+    let header = &db.header;
+    let encoding = header.text_encoding;
+
+    sqlite.set_is_sqlite(true);
+    sqlite.set_page_size(header.page_size as i64);
+    sqlite.set_num_pages(header.num_pages.into());
+    sqlite.set_num_freelist_pages(header.num_freelist_pages.into());
+    sqlite.set_file_change_counter(header.file_change_counter.into());
+    sqlite.set_schema_format(header.schema_format.into());
+    sqlite.text_encoding =
+        Some(EnumOrUnknown::from_i32(header.text_encoding as i32));
+    sqlite.set_user_version(header.user_version.into());
+    sqlite.set_application_id(header.application_id.into());
+    sqlite.set_sqlite_version(header.sqlite_version.into());
+
+    // The schema is a table with the columns `type`, `name`, `tbl_name`,
+    // `rootpage` and `sql`, whose root is the first page.
+    db.visit_rows(1, |payload| {
+        let values = btree::record(payload);
+        let text = |i: usize| match values.get(i) {
+            Some(Value::Text(s)) => Some(decode_text(s, encoding)),
+            _ => None,
+        };
+        let root_page = match values.get(3) {
+            Some(Value::Integer(root_page)) => Some(*root_page),
+            _ => None,
+        };
+
+        match text(0).as_deref() {
+            Some("table") => {
+                let mut table = Table::new();
+                table.name = text(1);
+                table.root_page = root_page;
+                table.sql = text(4);
+                sqlite.tables.push(table);
+            }
+            Some("index") => {
+                let mut index = Index::new();
+                index.name = text(1);
+                index.table = text(2);
+                index.root_page = root_page;
+                index.sql = text(4);
+                sqlite.indexes.push(index);
+            }
+            _ => {}
+        }
+
+        true
+    });
+
+    for table in sqlite.tables.iter_mut() {
+        // Virtual tables have no root page.
+        let root_page = match table.root_page {
+            Some(root_page) if root_page > 0 => root_page as u32,
+            _ => continue,
+        };
+
+        let num_rows = db.visit_rows(root_page, |payload| {
+            let mut row = Row::new();
+            for value in btree::record(payload) {
+                row.values.push(render_value(value, encoding));
+            }
+            table.rows.push(row);
+            table.rows.len() < MAX_SAMPLED_ROWS
+        });
+
+        table.set_num_rows(num_rows as i64);
+    }
+
+    sqlite
+}
+
+/// Returns true if the database has a table with the given name. The
+/// comparison is case-insensitive.
+#[module_export]
+fn has_table(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let sqlite = ctx.module_output::<Sqlite>()?;
+    let name = name.to_str(ctx).ok()?;
+
+    Some(
+        sqlite
+            .tables
+            .iter()
+            .any(|table| table.name().eq_ignore_ascii_case(name)),
+    )
+}
+
+/// Decodes text stored in the database with the given encoding.
+fn decode_text(data: &[u8], encoding: u32) -> String {
+    let units = data.chunks_exact(2);
+    match encoding {
+        2 => String::from_utf16_lossy(
+            &units
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        3 => String::from_utf16_lossy(
+            &units
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Converts a value in a row to the bytes exposed in the module's output.
+fn render_value(value: Value, encoding: u32) -> Vec<u8> {
+    let mut bytes = match value {
+        Value::Null => Vec::new(),
+        Value::Integer(i) => i.to_string().into_bytes(),
+        Value::Real(f) => f.to_string().into_bytes(),
+        Value::Text(s) if encoding == 1 => s.to_vec(),
+        Value::Text(s) => decode_text(s, encoding).into_bytes(),
+        Value::Blob(b) => b.to_vec(),
+    };
+
+    bytes.truncate(MAX_VALUE_SIZE);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    const PAGE_SIZE: usize = 512;
+
+    enum TestValue<'a> {
+        Null,
+        Integer(i8),
+        Text(&'a str),
+    }
+
+    fn varint(value: usize) -> Vec<u8> {
+        if value < 0x80 {
+            vec![value as u8]
+        } else {
+            vec![0x80 | (value >> 7) as u8, (value & 0x7f) as u8]
+        }
+    }
+
+    fn record(values: &[TestValue]) -> Vec<u8> {
+        let mut header = Vec::new();
+        let mut body = Vec::new();
+
+        for value in values {
+            match value {
+                TestValue::Null => header.push(0),
+                TestValue::Integer(i) => {
+                    header.push(1);
+                    body.push(*i as u8);
+                }
+                TestValue::Text(s) => {
+                    header.extend(varint(s.len() * 2 + 13));
+                    body.extend(s.as_bytes());
+                }
+            }
+        }
+
+        [varint(header.len() + 1), header, body].concat()
+    }
+
+    /// Builds a leaf page of a table B-tree with the given rows. Rows that
+    /// don't fit in the page are continued in overflow pages, which are
+    /// appended to `overflow_pages`. `first_page_number` is the number of
+    /// the first overflow page.
+    fn leaf_page(
+        rows: &[Vec<u8>],
+        header_offset: usize,
+        first_page_number: usize,
+        overflow_pages: &mut Vec<u8>,
+    ) -> Vec<u8> {
+        let mut page = vec![0; PAGE_SIZE];
+        let mut content_start = PAGE_SIZE;
+
+        page[header_offset] = 0x0d;
+        page[header_offset + 3..header_offset + 5]
+            .copy_from_slice(&(rows.len() as u16).to_be_bytes());
+
+        for (i, payload) in rows.iter().enumerate() {
+            let x = PAGE_SIZE - 35;
+            let m = ((PAGE_SIZE - 12) * 32 / 255) - 23;
+
+            let local_size = if payload.len() <= x {
+                payload.len()
+            } else {
+                let k = m + ((payload.len() - m) % (PAGE_SIZE - 4));
+                if k <= x {
+                    k
+                } else {
+                    m
+                }
+            };
+
+            let mut cell = varint(payload.len());
+            cell.extend(varint(i + 1));
+            cell.extend(&payload[..local_size]);
+
+            if local_size < payload.len() {
+                let chunks: Vec<_> =
+                    payload[local_size..].chunks(PAGE_SIZE - 4).collect();
+                let first =
+                    first_page_number + overflow_pages.len() / PAGE_SIZE;
+                cell.extend((first as u32).to_be_bytes());
+                for (j, chunk) in chunks.iter().enumerate() {
+                    let next =
+                        if j + 1 < chunks.len() { first + j + 1 } else { 0 };
+                    let mut overflow = (next as u32).to_be_bytes().to_vec();
+                    overflow.extend(*chunk);
+                    overflow.resize(PAGE_SIZE, 0);
+                    overflow_pages.extend(overflow);
+                }
+            }
+
+            content_start -= cell.len();
+            page[content_start..content_start + cell.len()]
+                .copy_from_slice(&cell);
+
+            let pointer = header_offset + 8 + 2 * i;
+            page[pointer..pointer + 2]
+                .copy_from_slice(&(content_start as u16).to_be_bytes());
+        }
+
+        page
+    }
+
+    fn build_database() -> Vec<u8> {
+        let long_url = format!("https://example.com/{}", "a".repeat(1000));
+
+        let schema = [
+            record(&[
+                TestValue::Text("table"),
+                TestValue::Text("urls"),
+                TestValue::Text("urls"),
+                TestValue::Integer(2),
+                TestValue::Text("CREATE TABLE urls(id, url, title)"),
+            ]),
+            record(&[
+                TestValue::Text("index"),
+                TestValue::Text("sqlite_autoindex_urls_1"),
+                TestValue::Text("urls"),
+                TestValue::Integer(3),
+                TestValue::Null,
+            ]),
+        ];
+
+        let rows = [
+            record(&[
+                TestValue::Null,
+                TestValue::Text("https://example.org"),
+                TestValue::Text("Example"),
+            ]),
+            record(&[
+                TestValue::Integer(-2),
+                TestValue::Text(&long_url),
+                TestValue::Null,
+            ]),
+        ];
+
+        let mut overflow_pages = Vec::new();
+        let mut db = leaf_page(&schema, 100, 4, &mut overflow_pages);
+
+        db[..16].copy_from_slice(b"SQLite format 3\0");
+        db[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+        db[44..48].copy_from_slice(&4_u32.to_be_bytes());
+        db[56..60].copy_from_slice(&1_u32.to_be_bytes());
+        db[68..72].copy_from_slice(&0x0f055112_u32.to_be_bytes());
+        db[96..100].copy_from_slice(&3045001_u32.to_be_bytes());
+
+        db.extend(leaf_page(&rows, 0, 4, &mut overflow_pages));
+
+        // Leaf page of the index B-tree, without entries.
+        let mut index = vec![0; PAGE_SIZE];
+        index[0] = 0x0a;
+        db.extend(index);
+        db.extend(overflow_pages);
+
+        let num_pages = (db.len() / PAGE_SIZE) as u32;
+        db[28..32].copy_from_slice(&num_pages.to_be_bytes());
+        db
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "sqlite"
+                rule rule_1 {
+                  condition:
+                    sqlite.is_sqlite and
+                    sqlite.page_size == 512 and
+                    sqlite.num_pages == 5 and
+                    sqlite.schema_format == 4 and
+                    sqlite.text_encoding == sqlite.TextEncoding.UTF8 and
+                    sqlite.application_id == 0x0f055112 and
+                    sqlite.sqlite_version == 3045001
+                }
+                rule rule_2 {
+                  condition:
+                    sqlite.tables[0].name == "urls" and
+                    sqlite.tables[0].root_page == 2 and
+                    sqlite.tables[0].sql == "CREATE TABLE urls(id, url, title)" and
+                    sqlite.tables[0].num_rows == 2 and
+                    sqlite.indexes[0].name == "sqlite_autoindex_urls_1" and
+                    sqlite.indexes[0].table == "urls" and
+                    not defined sqlite.indexes[0].sql and
+                    sqlite.has_table("URLS") and
+                    not sqlite.has_table("cookies")
+                }
+                rule rule_3 {
+                  condition:
+                    sqlite.tables[0].rows[0].values[0] == "" and
+                    sqlite.tables[0].rows[0].values[1] == "https://example.org" and
+                    sqlite.tables[0].rows[0].values[2] == "Example" and
+                    sqlite.tables[0].rows[1].values[0] == "-2" and
+                    sqlite.tables[0].rows[1].values[1] startswith "https://example.com/aaaa" and
+                    sqlite.tables[0].rows[1].values[1] endswith "aaaa"
+                }
+                rule rule_4 { condition: not sqlite.is_sqlite }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_database()),
+            ["rule_1", "rule_2", "rule_3"]
+        );
+
+        assert_eq!(matching_rules(b"SQLite format 3\0"), ["rule_4"]);
+    }
+}
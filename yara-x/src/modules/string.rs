@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::string::*;
+
+#[module_main]
+fn main(_ctx: &ScanContext) -> String {
+    // Nothing to do, but we have to return our protobuf
+    String::new()
+}
+
+/// Returns the length of a string.
+#[module_export]
+fn length(ctx: &ScanContext, s: RuntimeString) -> i64 {
+    s.as_bstr(ctx).len() as i64
+}
+
+/// Converts a string to an integer. The string can have a `+` or `-` sign,
+/// and the base is determined by its prefix, like in C's `strtoll`: `0x` for
+/// hexadecimal, `0` for octal, and decimal otherwise.
+///
+/// Returns undefined if the string is not a valid integer.
+#[module_export(name = "to_int")]
+fn to_int(ctx: &ScanContext, s: RuntimeString) -> Option<i64> {
+    parse_int(s.to_str(ctx).ok()?, 0)
+}
+
+/// Converts a string to an integer in the given base, which must be between
+/// 2 and 36. A base of 0 means that the base is determined by the prefix, as
+/// in the single-argument version of this function.
+///
+/// Returns undefined if the string is not a valid integer in that base.
+#[module_export(name = "to_int")]
+fn to_int_base(ctx: &ScanContext, s: RuntimeString, base: i64) -> Option<i64> {
+    parse_int(s.to_str(ctx).ok()?, base.try_into().ok()?)
+}
+
+/// Returns a copy of the string with ASCII letters converted to lowercase.
+#[module_export]
+fn to_lower(ctx: &mut ScanContext, s: RuntimeString) -> RuntimeString {
+    RuntimeString::from_bytes(ctx, s.as_bstr(ctx).to_ascii_lowercase())
+}
+
+/// Returns a copy of the string with ASCII letters converted to uppercase.
+#[module_export]
+fn to_upper(ctx: &mut ScanContext, s: RuntimeString) -> RuntimeString {
+    RuntimeString::from_bytes(ctx, s.as_bstr(ctx).to_ascii_uppercase())
+}
+
+/// Returns the `length` bytes of the string that start at `offset`. If the
+/// string is shorter than `offset + length`, the result is truncated.
+///
+/// Returns undefined if `offset` or `length` are negative, or `offset` is
+/// larger than the string's length.
+#[module_export]
+fn substr(
+    ctx: &mut ScanContext,
+    s: RuntimeString,
+    offset: i64,
+    length: i64,
+) -> Option<RuntimeString> {
+    let s = s.as_bstr(ctx);
+    let start: usize = offset.try_into().ok()?;
+    let length: usize = length.try_into().ok()?;
+
+    if start > s.len() {
+        return None;
+    }
+
+    let end = start.saturating_add(length).min(s.len());
+    let substr = s[start..end].to_vec();
+
+    Some(RuntimeString::from_bytes(ctx, substr))
+}
+
+/// Returns true if the string starts with `prefix`.
+#[module_export]
+fn starts_with(
+    ctx: &ScanContext,
+    s: RuntimeString,
+    prefix: RuntimeString,
+) -> bool {
+    s.as_bstr(ctx).starts_with(prefix.as_bstr(ctx))
+}
+
+/// Returns true if the string ends with `suffix`.
+#[module_export]
+fn ends_with(
+    ctx: &ScanContext,
+    s: RuntimeString,
+    suffix: RuntimeString,
+) -> bool {
+    s.as_bstr(ctx).ends_with(suffix.as_bstr(ctx))
+}
+
+/// Compares two strings byte by byte, and returns -1, 0 or 1 if the first
+/// string is lower, equal or greater than the second one, respectively.
+#[module_export(name = "compare")]
+fn compare(ctx: &ScanContext, s1: RuntimeString, s2: RuntimeString) -> i64 {
+    ordering_to_int(s1.as_bstr(ctx).cmp(s2.as_bstr(ctx)))
+}
+
+/// Like the two-argument version of `compare`, but compares only the first
+/// `n` bytes of each string, like C's `strncmp`. Strings shorter than `n`
+/// bytes are compared in full.
+///
+/// Returns undefined if `n` is negative.
+#[module_export(name = "compare")]
+fn compare_n(
+    ctx: &ScanContext,
+    s1: RuntimeString,
+    s2: RuntimeString,
+    n: i64,
+) -> Option<i64> {
+    let n: usize = n.try_into().ok()?;
+    let s1 = s1.as_bstr(ctx);
+    let s2 = s2.as_bstr(ctx);
+    let s1 = &s1[..n.min(s1.len())];
+    let s2 = &s2[..n.min(s2.len())];
+    Some(ordering_to_int(s1.cmp(s2)))
+}
+
+fn ordering_to_int(ordering: Ordering) -> i64 {
+    match ordering {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+/// Parses an integer in the given base, in the same way as C's `strtoll`,
+/// except that the whole string must be a valid integer.
+fn parse_int(s: &str, base: u32) -> Option<i64> {
+    let s = s.trim_start();
+
+    let (negative, s) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let hex_digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+
+    let (base, digits) = match (base, hex_digits) {
+        (0 | 16, Some(digits)) => (16, digits),
+        (0, None) if s.len() > 1 && s.starts_with('0') => (8, &s[1..]),
+        (0, None) => (10, s),
+        (2..=36, _) => (base, s),
+        _ => return None,
+    };
+
+    // `from_str_radix` accepts a sign, but it was already handled above.
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+
+    let value = i128::from_str_radix(digits, base).ok()?;
+
+    if negative { -value } else { value }.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::parse_int;
+
+    #[test]
+    fn end2end() {
+        let data = b"Hello World";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "string"
+                rule rule_1 { condition: string.length("foo") == 3 }
+                rule rule_2 { condition: string.to_int("123") == 123 }
+                rule rule_3 { condition: string.to_int("-0x10") == -16 }
+                rule rule_4 { condition: string.to_int("777", 8) == 511 }
+                rule rule_5 { condition: not defined string.to_int("12a") }
+                rule rule_6 { condition: not defined string.to_int("1", 37) }
+                rule rule_7 { condition: string.to_lower("FoO") == "foo" }
+                rule rule_8 { condition: string.to_upper("FoO") == "FOO" }
+                rule rule_9 { condition: string.substr("foobar", 3, 3) == "bar" }
+                rule rule_10 { condition: string.substr("foobar", 3, 100) == "bar" }
+                rule rule_11 { condition: not defined string.substr("foo", 4, 1) }
+                rule rule_12 { condition: string.starts_with("foobar", "foo") }
+                rule rule_13 { condition: string.ends_with("foobar", "bar") }
+                rule rule_14 { condition: not string.ends_with("bar", "foobar") }
+                rule rule_15 { condition: string.compare("abc", "abd") == -1 }
+                rule rule_16 { condition: string.compare("abc", "abd", 2) == 0 }
+                rule rule_17 { condition: string.compare("abc", "ab", 3) == 1 }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(data).unwrap().matching_rules().len(), 17);
+    }
+
+    #[test]
+    fn parse_integers() {
+        assert_eq!(parse_int("10", 0), Some(10));
+        assert_eq!(parse_int("  +10", 0), Some(10));
+        assert_eq!(parse_int("010", 0), Some(8));
+        assert_eq!(parse_int("0", 0), Some(0));
+        assert_eq!(parse_int("0xff", 0), Some(255));
+        assert_eq!(parse_int("0XFF", 16), Some(255));
+        assert_eq!(parse_int("ff", 16), Some(255));
+        assert_eq!(parse_int("z", 36), Some(35));
+        assert_eq!(parse_int("-9223372036854775808", 10), Some(i64::MIN));
+        assert_eq!(parse_int("9223372036854775808", 10), None);
+        assert_eq!(parse_int("0x", 0), None);
+        assert_eq!(parse_int("--1", 0), None);
+        assert_eq!(parse_int("", 0), None);
+        assert_eq!(parse_int("1", 1), None);
+    }
+}
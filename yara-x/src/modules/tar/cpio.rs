@@ -0,0 +1,179 @@
+/*! Parser for cpio archives.
+
+Each entry in a cpio archive has a header, followed by the entry's name
+and its content. The archive ends with an entry named `TRAILER!!!`. There
+are three formats, which differ in the encoding of the header: the "new
+ASCII" format uses hexadecimal strings and aligns names and contents to 4
+bytes, the "old ASCII" format uses octal strings without alignment, and the
+old binary format uses 16-bit integers, in the byte order of the machine
+that created the archive, aligned to 2 bytes.
+
+See: https://man.freebsd.org/cgi/man.cgi?query=cpio&sektion=5
+*/
+
+use crate::modules::protos::tar::{EntryType, Format};
+
+use super::{cstr, entry_type, string, Entry, Listing, MAX_NAME_SIZE};
+
+/// Fields of a header that are needed for parsing the archive.
+struct Header {
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    mtime: u64,
+    name_size: usize,
+    file_size: usize,
+}
+
+/// Parses a cpio archive. Returns `None` if the data doesn't start with
+/// a valid cpio header.
+pub(crate) fn parse(data: &[u8]) -> Option<(Format, Listing)> {
+    // Size of the header, and alignment of names and contents.
+    let (format, header_size, alignment) = if data.starts_with(b"070701")
+        || data.starts_with(b"070702")
+    {
+        (Format::CPIO_NEWC, 110, 4)
+    } else if data.starts_with(b"070707") {
+        (Format::CPIO_ODC, 76, 1)
+    } else if data.starts_with(b"\xc7\x71") || data.starts_with(b"\x71\xc7") {
+        (Format::CPIO_BINARY, 26, 2)
+    } else {
+        return None;
+    };
+
+    let big_endian = data.starts_with(b"\x71\xc7");
+    let align = |pos: usize| {
+        pos.checked_add(alignment - 1).map(|pos| pos / alignment * alignment)
+    };
+
+    let mut listing = Listing::default();
+    let mut pos = 0;
+
+    while let Some(raw) = data.get(pos..pos + header_size) {
+        let header = match format {
+            Format::CPIO_NEWC => newc_header(raw),
+            Format::CPIO_ODC => odc_header(raw),
+            _ => Some(binary_header(raw, big_endian)),
+        };
+
+        let header = match header {
+            Some(header) => header,
+            None if pos == 0 => return None,
+            None => break,
+        };
+
+        let name_offset = pos + header_size;
+        let name = match name_offset
+            .checked_add(header.name_size)
+            .and_then(|end| data.get(name_offset..end))
+        {
+            Some(name) => cstr(name),
+            None => break,
+        };
+
+        if name == b"TRAILER!!!" {
+            break;
+        }
+
+        let content_offset = match align(name_offset + header.name_size) {
+            Some(offset) => offset,
+            None => break,
+        };
+
+        let entry_type = entry_type(header.mode);
+
+        // The content of symbolic links is the link's target.
+        let link_target = match entry_type {
+            EntryType::SYMLINK => data.get(content_offset..).map(|content| {
+                let len = header.file_size.min(MAX_NAME_SIZE);
+                string(&content[..len.min(content.len())])
+            }),
+            _ => None,
+        };
+
+        let entry = Entry {
+            name: string(name),
+            entry_type,
+            mode: header.mode & 0o7777,
+            uid: header.uid,
+            gid: header.gid,
+            size: header.file_size as u64,
+            mtime: Some(header.mtime as i64),
+            link_target,
+            offset: content_offset as u64,
+            ..Default::default()
+        };
+
+        if !listing.push(entry) {
+            break;
+        }
+
+        pos =
+            match content_offset.checked_add(header.file_size).and_then(align)
+            {
+                Some(pos) => pos,
+                None => break,
+            };
+    }
+
+    Some((format, listing))
+}
+
+/// Parses a header in the "new ASCII" format, where fields are 8-digit
+/// hexadecimal numbers.
+fn newc_header(raw: &[u8]) -> Option<Header> {
+    let field = |i: usize| {
+        let digits = std::str::from_utf8(&raw[6 + i * 8..14 + i * 8]).ok()?;
+        u32::from_str_radix(digits, 16).ok()
+    };
+
+    Some(Header {
+        mode: field(1)?,
+        uid: field(2)?.into(),
+        gid: field(3)?.into(),
+        mtime: field(5)?.into(),
+        file_size: field(6)? as usize,
+        name_size: field(11)? as usize,
+    })
+}
+
+/// Parses a header in the "old ASCII" format, where fields are octal
+/// numbers of different lengths.
+fn odc_header(raw: &[u8]) -> Option<Header> {
+    let field = |start: usize, end: usize| {
+        let digits = std::str::from_utf8(&raw[start..end]).ok()?;
+        u64::from_str_radix(digits, 8).ok()
+    };
+
+    Some(Header {
+        mode: field(18, 24)? as u32,
+        uid: field(24, 30)?,
+        gid: field(30, 36)?,
+        mtime: field(48, 59)?,
+        name_size: field(59, 65)? as usize,
+        file_size: usize::try_from(field(65, 76)?).ok()?,
+    })
+}
+
+/// Parses a header in the old binary format, where fields are 16-bit
+/// integers, and 32-bit integers are stored as two 16-bit integers with
+/// the most significant one first.
+fn binary_header(raw: &[u8], big_endian: bool) -> Header {
+    let word = |i: usize| {
+        let bytes = [raw[i * 2], raw[i * 2 + 1]];
+        if big_endian {
+            u16::from_be_bytes(bytes) as u32
+        } else {
+            u16::from_le_bytes(bytes) as u32
+        }
+    };
+
+    Header {
+        mode: word(3),
+        uid: word(4).into(),
+        gid: word(5).into(),
+        mtime: (word(8) << 16 | word(9)).into(),
+        name_size: word(10) as usize,
+        file_size: (word(11) << 16 | word(12)) as usize,
+    }
+}
@@ -0,0 +1,304 @@
+/*! YARA module that parses tar and cpio archives.
+
+The module lists the entries in the archive with their metadata, like the
+mode, the owner and the link target, without extracting them. It also
+flags the entries that are commonly used in attacks: the ones that would
+be extracted outside the destination directory, and setuid executables.
+*/
+
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::tar::*;
+
+mod cpio;
+mod ustar;
+
+/// Maximum number of entries.
+const MAX_ENTRIES: usize = 16384;
+
+/// Maximum size of the link targets and names stored in the content of
+/// entries.
+pub(crate) const MAX_NAME_SIZE: usize = 64 * 1024;
+
+/// Bits in the mode.
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+
+/// An entry in the archive.
+#[derive(Default)]
+pub(crate) struct Entry {
+    pub name: String,
+    pub entry_type: EntryType,
+    pub mode: u32,
+    pub uid: u64,
+    pub gid: u64,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+    pub size: u64,
+    pub mtime: Option<i64>,
+    pub link_target: Option<String>,
+    pub offset: u64,
+}
+
+/// The entries in an archive.
+#[derive(Default)]
+pub(crate) struct Listing {
+    pub entries: Vec<Entry>,
+    pub truncated: bool,
+}
+
+impl Listing {
+    /// Adds an entry, unless the listing is full. Returns false if the
+    /// entry was not added.
+    pub fn push(&mut self, entry: Entry) -> bool {
+        if self.entries.len() == MAX_ENTRIES {
+            self.truncated = true;
+            return false;
+        }
+        self.entries.push(entry);
+        true
+    }
+}
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Tar {
+    let data = ctx.scanned_data();
+    let mut tar = Tar::new();
+
+    let (format, listing) = if let Some(listing) = ustar::parse(data) {
+        (Format::TAR, listing)
+    } else if let Some((format, listing)) = cpio::parse(data) {
+        (format, listing)
+    } else {
+        tar.set_is_archive(false);
+        return tar;
+    };
+
+    tar.set_is_archive(true);
+    tar.format = Some(EnumOrUnknown::new(format));
+    tar.set_entries_truncated(listing.truncated);
+
+    for entry in listing.entries {
+        let is_path_traversal = is_path_traversal(&entry.name)
+            || entry.link_target.as_deref().map_or(false, |target| {
+                entry.entry_type == EntryType::SYMLINK
+                    && is_path_traversal(target)
+            });
+
+        let is_setuid = entry.entry_type == EntryType::FILE
+            && entry.mode & (S_ISUID | S_ISGID) != 0;
+
+        let mut e = TarEntry::new();
+        e.set_name(entry.name);
+        e.type_ = Some(EnumOrUnknown::new(entry.entry_type));
+        e.set_mode(entry.mode.into());
+        e.set_uid(entry.uid as i64);
+        e.set_gid(entry.gid as i64);
+        e.user_name = entry.user_name;
+        e.group_name = entry.group_name;
+        e.set_size(entry.size as i64);
+        e.mtime = entry.mtime;
+        e.link_target = entry.link_target;
+        e.set_offset(entry.offset as i64);
+        e.set_is_path_traversal(is_path_traversal);
+        e.set_is_setuid(is_setuid);
+        tar.entries.push(e);
+    }
+
+    tar.set_has_path_traversal(
+        tar.entries.iter().any(|entry| entry.is_path_traversal()),
+    );
+
+    tar.set_has_setuid(tar.entries.iter().any(|entry| entry.is_setuid()));
+
+    tar
+}
+
+/// Returns true if a path is absolute or has `..` components. Backslashes
+/// are treated as separators, as some extractors do.
+fn is_path_traversal(path: &str) -> bool {
+    path.starts_with(['/', '\\'])
+        || path.get(1..3) == Some(":\\")
+        || path.split(['/', '\\']).any(|component| component == "..")
+}
+
+/// Returns the bytes before the first null character.
+pub(crate) fn cstr(bytes: &[u8]) -> &[u8] {
+    bytes.split(|b| *b == 0).next().unwrap_or_default()
+}
+
+/// Converts a name to a string, replacing invalid UTF-8 sequences.
+pub(crate) fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Returns the type of an entry from the file type bits in its mode.
+pub(crate) fn entry_type(mode: u32) -> EntryType {
+    match mode & 0o170000 {
+        0o100000 => EntryType::FILE,
+        0o040000 => EntryType::DIRECTORY,
+        0o120000 => EntryType::SYMLINK,
+        0o020000 => EntryType::CHAR_DEVICE,
+        0o060000 => EntryType::BLOCK_DEVICE,
+        0o010000 => EntryType::FIFO,
+        0o140000 => EntryType::SOCKET,
+        _ => EntryType::UNKNOWN_TYPE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Returns a tar header, with its checksum.
+    fn tar_header(
+        name: &str,
+        flag: u8,
+        mode: u32,
+        link: &str,
+        size: usize,
+    ) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[100..108].copy_from_slice(format!("{:07o}\0", mode).as_bytes());
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000012\0");
+        header[124..136]
+            .copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        header[136..148].copy_from_slice(b"14536610560\0");
+        header[156] = flag;
+        header[257..265].copy_from_slice(b"ustar\x0000");
+        header[265..269].copy_from_slice(b"root");
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+        header[148..156]
+            .copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        header
+    }
+
+    /// Returns a tar entry, with its header and its content padded to
+    /// 512 bytes.
+    fn tar_entry(
+        name: &str,
+        flag: u8,
+        mode: u32,
+        link: &str,
+        content: &[u8],
+    ) -> Vec<u8> {
+        let mut entry = tar_header(name, flag, mode, link, content.len());
+        entry.extend(content);
+        entry.resize(entry.len() + (512 - content.len() % 512) % 512, 0);
+        entry
+    }
+
+    /// Returns a cpio entry in the "new ASCII" format.
+    fn newc_entry(name: &str, mode: u32, content: &[u8]) -> Vec<u8> {
+        let mut entry = format!(
+            "070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{}{:08x}{:08x}",
+            1,
+            mode,
+            0,
+            0,
+            1,
+            0x5f5e1000,
+            content.len(),
+            "0".repeat(32),
+            name.len() + 1,
+            0,
+        )
+        .into_bytes();
+        entry.extend(name.as_bytes());
+        entry.push(0);
+        entry.resize((entry.len() + 3) / 4 * 4, 0);
+        entry.extend(content);
+        entry.resize((entry.len() + 3) / 4 * 4, 0);
+        entry
+    }
+
+    #[test]
+    fn end2end() {
+        let pax = b"29 path=../../etc/cron.d/job\n";
+
+        let tar = [
+            tar_entry("bin/", b'5', 0o755, "", b""),
+            tar_entry("bin/su", b'0', 0o4755, "", b"\x7fELF"),
+            tar_entry("PaxHeaders/job", b'x', 0o644, "", pax),
+            tar_entry("job", b'0', 0o644, "", b"* * * * * root sh\n"),
+            tar_entry("link", b'2', 0o777, "/etc/shadow", b""),
+            vec![0; 1024],
+        ]
+        .concat();
+
+        let cpio = [
+            newc_entry("etc", 0o040755, b""),
+            newc_entry("etc/passwd", 0o100644, b"root:x:0:0::/root:/bin/sh\n"),
+            newc_entry("lib", 0o120777, b"/usr/lib"),
+            newc_entry("TRAILER!!!", 0, b""),
+        ]
+        .concat();
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "tar"
+                rule tar_entries {
+                  condition:
+                    tar.format == tar.Format.TAR and
+                    tar.entries[0].name == "bin/" and
+                    tar.entries[0].type == tar.EntryType.DIRECTORY and
+                    tar.entries[1].name == "bin/su" and
+                    tar.entries[1].mode == 0o4755 and
+                    tar.entries[1].user_name == "root" and
+                    tar.entries[1].gid == 10 and
+                    tar.entries[1].size == 4 and
+                    tar.entries[1].offset == 1024 and
+                    tar.entries[1].mtime == 1702564208 and
+                    tar.entries[2].name == "../../etc/cron.d/job" and
+                    tar.entries[3].type == tar.EntryType.SYMLINK and
+                    tar.entries[3].link_target == "/etc/shadow"
+                }
+                rule tar_flags {
+                  condition:
+                    tar.has_setuid and
+                    tar.has_path_traversal and
+                    tar.entries[1].is_setuid and
+                    tar.entries[2].is_path_traversal and
+                    tar.entries[3].is_path_traversal and
+                    not tar.entries[0].is_path_traversal
+                }
+                rule cpio_entries {
+                  condition:
+                    tar.format == tar.Format.CPIO_NEWC and
+                    tar.entries[0].type == tar.EntryType.DIRECTORY and
+                    tar.entries[1].name == "etc/passwd" and
+                    tar.entries[1].mode == 0o644 and
+                    tar.entries[1].offset == 240 and
+                    tar.entries[1].size == 26 and
+                    tar.entries[2].link_target == "/usr/lib" and
+                    tar.entries[2].is_path_traversal and
+                    not tar.has_setuid
+                }
+                rule not_archive {
+                  condition:
+                    not tar.is_archive
+                }"#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            let results = scanner.scan(data).unwrap();
+            results
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&tar), ["tar_entries", "tar_flags"]);
+        assert_eq!(matching_rules(&cpio), ["cpio_entries"]);
+        assert_eq!(matching_rules(&[0; 1024]), ["not_archive"]);
+    }
+}
@@ -0,0 +1,282 @@
+/*! Parser for tar archives.
+
+Tar archives are sequences of 512-byte headers, each one followed by the
+entry's content padded to a multiple of 512 bytes. The archive ends with
+two blocks of zeroes. Numbers in headers are octal strings, except for big
+values, which are stored in binary with the highest bit of the first byte
+set.
+
+Names longer than 100 characters are supported by ustar archives with the
+`prefix` field, by GNU archives with `L` and `K` entries whose content is
+the name of the next entry, and by PAX archives with extended headers,
+which are `x` entries with `key=value` records that override the fields of
+the next entry (or all entries, in the case of `g` entries).
+
+See: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/pax.html
+*/
+
+use std::collections::HashMap;
+
+use crate::modules::protos::tar::EntryType;
+
+use super::{cstr, string, Entry, Listing, MAX_NAME_SIZE};
+
+const BLOCK_SIZE: usize = 512;
+
+/// Maximum size of the content of extended headers.
+const MAX_EXTENDED_HEADER_SIZE: usize = 1024 * 1024;
+
+/// Parses a tar archive. Returns `None` if the first header is not valid.
+pub(crate) fn parse(data: &[u8]) -> Option<Listing> {
+    if !is_valid(data.get(..BLOCK_SIZE)?) {
+        return None;
+    }
+
+    let mut listing = Listing::default();
+    let mut global = HashMap::new();
+    let mut local = HashMap::new();
+    let mut pos = 0;
+
+    while let Some(header) = data.get(pos..pos + BLOCK_SIZE) {
+        if !is_valid(header) {
+            break;
+        }
+
+        let content_offset = pos + BLOCK_SIZE;
+        let mut size = match number(&header[124..136]) {
+            Some(size) => size,
+            None => break,
+        };
+
+        let content = |size: u64, max_size: usize| {
+            let len = usize::try_from(size).unwrap_or(usize::MAX);
+            let end = content_offset.saturating_add(len.min(max_size));
+            &data[content_offset..end.min(data.len())]
+        };
+
+        match header[156] {
+            b'x' => {
+                records(content(size, MAX_EXTENDED_HEADER_SIZE), &mut local)
+            }
+            b'g' => {
+                records(content(size, MAX_EXTENDED_HEADER_SIZE), &mut global)
+            }
+            // GNU long names are stored with the same keys used by PAX.
+            b'L' => {
+                local.insert(
+                    "path".to_string(),
+                    string(cstr(content(size, MAX_NAME_SIZE))),
+                );
+            }
+            b'K' => {
+                local.insert(
+                    "linkpath".to_string(),
+                    string(cstr(content(size, MAX_NAME_SIZE))),
+                );
+            }
+            flag => {
+                let mut entry = entry(header, flag);
+
+                entry.size = size;
+                entry.offset = content_offset as u64;
+
+                // Local records override the global ones.
+                for (key, value) in global.iter().chain(local.iter()) {
+                    apply(&mut entry, key, value);
+                }
+
+                local.clear();
+
+                // Links and directories have no content, even if their size
+                // is not zero.
+                size = match entry.entry_type {
+                    EntryType::HARDLINK
+                    | EntryType::SYMLINK
+                    | EntryType::DIRECTORY => 0,
+                    _ => entry.size,
+                };
+
+                if !listing.push(entry) {
+                    break;
+                }
+            }
+        }
+
+        let blocks =
+            size / BLOCK_SIZE as u64 + (size % BLOCK_SIZE as u64 != 0) as u64;
+
+        pos = match usize::try_from(blocks)
+            .ok()
+            .and_then(|blocks| blocks.checked_mul(BLOCK_SIZE))
+            .and_then(|len| content_offset.checked_add(len))
+        {
+            Some(pos) => pos,
+            None => break,
+        };
+    }
+
+    Some(listing)
+}
+
+/// Returns true if the header's checksum is correct. The checksum is the
+/// sum of the header's bytes, with the checksum field filled with spaces.
+/// Some old implementations computed the sum with signed bytes, both are
+/// accepted.
+fn is_valid(header: &[u8]) -> bool {
+    let checksum = match number(&header[148..156]) {
+        Some(checksum) => checksum as i64,
+        None => return false,
+    };
+
+    let spaces = 8 * b' ' as i64;
+    let (unsigned, signed) = header
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !(148..156).contains(i))
+        .fold((spaces, spaces), |(unsigned, signed), (_, b)| {
+            (unsigned + *b as i64, signed + *b as i8 as i64)
+        });
+
+    checksum == unsigned || checksum == signed
+}
+
+/// Parses the fields of a header.
+fn entry(header: &[u8], flag: u8) -> Entry {
+    let mut name = cstr(&header[..100]).to_vec();
+
+    // In ustar archives the prefix is prepended to the name. GNU archives
+    // have a different magic (`ustar  `), and use the space of the prefix
+    // for other fields.
+    if &header[257..263] == b"ustar\0" {
+        let prefix = cstr(&header[345..500]);
+        if !prefix.is_empty() {
+            name = [prefix, b"/", &name].concat();
+        }
+    }
+
+    let entry_type = match flag {
+        b'0' | b'\0' | b'7' => EntryType::FILE,
+        b'1' => EntryType::HARDLINK,
+        b'2' => EntryType::SYMLINK,
+        b'3' => EntryType::CHAR_DEVICE,
+        b'4' => EntryType::BLOCK_DEVICE,
+        b'5' => EntryType::DIRECTORY,
+        b'6' => EntryType::FIFO,
+        _ => EntryType::UNKNOWN_TYPE,
+    };
+
+    // Names ending with a slash are directories in pre-POSIX archives.
+    let entry_type = if flag == b'\0' && name.ends_with(b"/") {
+        EntryType::DIRECTORY
+    } else {
+        entry_type
+    };
+
+    let link_target = cstr(&header[157..257]);
+    let user_name = cstr(&header[265..297]);
+    let group_name = cstr(&header[297..329]);
+
+    Entry {
+        name: string(&name),
+        entry_type,
+        mode: number(&header[100..108]).unwrap_or_default() as u32 & 0o7777,
+        uid: number(&header[108..116]).unwrap_or_default(),
+        gid: number(&header[116..124]).unwrap_or_default(),
+        user_name: (!user_name.is_empty()).then(|| string(user_name)),
+        group_name: (!group_name.is_empty()).then(|| string(group_name)),
+        mtime: number(&header[136..148]).map(|mtime| mtime as i64),
+        link_target: (!link_target.is_empty()).then(|| string(link_target)),
+        ..Default::default()
+    }
+}
+
+/// Parses a numeric field, which is an octal string terminated by a space
+/// or a null character, or a big-endian binary number if the highest bit
+/// of the first byte is set.
+fn number(field: &[u8]) -> Option<u64> {
+    if field.first()? & 0x80 != 0 {
+        // Negative numbers are not supported.
+        if field[0] & 0x40 != 0 {
+            return None;
+        }
+        return field[1..]
+            .iter()
+            .try_fold((field[0] & 0x3f) as u64, |value, b| {
+                value.checked_mul(256).map(|value| value | *b as u64)
+            });
+    }
+
+    let digits = field
+        .iter()
+        .skip_while(|b| **b == b' ')
+        .take_while(|b| (b'0'..=b'7').contains(*b));
+
+    let mut value: u64 = 0;
+    let mut count = 0;
+
+    for digit in digits {
+        value = value.checked_mul(8)? | (digit - b'0') as u64;
+        count += 1;
+    }
+
+    (count > 0).then_some(value)
+}
+
+/// Parses the records in a PAX extended header. Each record is formed by
+/// its length in decimal, a space, the key, an equal sign, the value and a
+/// newline. The length includes the whole record.
+fn records(mut content: &[u8], records: &mut HashMap<String, String>) {
+    while let Some(space) = content.iter().position(|b| *b == b' ') {
+        let len = match std::str::from_utf8(&content[..space])
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+        {
+            Some(len) if len > space && len <= content.len() => len,
+            _ => return,
+        };
+
+        let record = &content[space + 1..len];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+
+        if let Some(equal) = record.iter().position(|b| *b == b'=') {
+            records.insert(
+                string(&record[..equal]),
+                string(&record[equal + 1..]),
+            );
+        }
+
+        content = &content[len..];
+    }
+}
+
+/// Overrides a field of an entry with the value of a PAX record.
+fn apply(entry: &mut Entry, key: &str, value: &str) {
+    match key {
+        "path" => entry.name = value.to_string(),
+        "linkpath" => entry.link_target = Some(value.to_string()),
+        "uname" => entry.user_name = Some(value.to_string()),
+        "gname" => entry.group_name = Some(value.to_string()),
+        "size" => {
+            if let Ok(size) = value.parse() {
+                entry.size = size;
+            }
+        }
+        "uid" => {
+            if let Ok(uid) = value.parse() {
+                entry.uid = uid;
+            }
+        }
+        "gid" => {
+            if let Ok(gid) = value.parse() {
+                entry.gid = gid;
+            }
+        }
+        // The modification time can have a fractional part.
+        "mtime" => {
+            if let Some(Ok(mtime)) = value.split('.').next().map(str::parse) {
+                entry.mtime = Some(mtime);
+            }
+        }
+        _ => {}
+    }
+}
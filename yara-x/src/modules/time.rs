@@ -1,7 +1,11 @@
 use crate::modules::prelude::*;
 use crate::modules::protos::time::*;
+use std::fmt::Write;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
 #[module_main]
 fn main(_ctx: &ScanContext) -> Time {
     // Nothing to do, but we have to return our protobuf
@@ -13,8 +17,112 @@ fn now(_ctx: &ScanContext) -> Option<i64> {
     Some(SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
 }
 
+/// Parses a date in ISO 8601 format and returns the corresponding epoch in
+/// seconds.
+///
+/// Accepts dates like `2023-08-01`, `2023-08-01T10:30:00`,
+/// `2023-08-01T10:30:00Z` and `2023-08-01T10:30:00+02:00`. Dates without
+/// a timezone are interpreted as UTC. Returns undefined if the date is not
+/// valid.
+#[module_export]
+fn from_iso8601(ctx: &ScanContext, date: RuntimeString) -> Option<i64> {
+    parse_iso8601(date.to_str(ctx).ok()?)
+}
+
+/// Returns the date corresponding to an epoch in seconds, in ISO 8601
+/// format (e.g: `2023-08-01T10:30:00Z`).
+///
+/// Returns undefined if the epoch is out of the range of valid dates.
+#[module_export(name = "to_string")]
+fn to_string(ctx: &mut ScanContext, epoch: i64) -> Option<RuntimeString> {
+    let date = format_epoch(epoch, "%Y-%m-%dT%H:%M:%SZ")?;
+    Some(RuntimeString::from_bytes(ctx, date))
+}
+
+/// Returns the date corresponding to an epoch in seconds, formatted
+/// according to `format`, which uses the same specifiers as C's `strftime`
+/// (e.g: `%Y-%m-%d`). The date is in UTC.
+///
+/// Returns undefined if the epoch is out of the range of valid dates, or
+/// the format is not valid.
+#[module_export(name = "to_string")]
+fn to_string_format(
+    ctx: &mut ScanContext,
+    epoch: i64,
+    format: RuntimeString,
+) -> Option<RuntimeString> {
+    let date = format_epoch(epoch, format.to_str(ctx).ok()?)?;
+    Some(RuntimeString::from_bytes(ctx, date))
+}
+
+/// Returns the number of seconds in `n` minutes.
+#[module_export]
+fn minutes(_ctx: &ScanContext, n: i64) -> Option<i64> {
+    n.checked_mul(60)
+}
+
+/// Returns the number of seconds in `n` hours.
+#[module_export]
+fn hours(_ctx: &ScanContext, n: i64) -> Option<i64> {
+    n.checked_mul(60 * 60)
+}
+
+/// Returns the number of seconds in `n` days.
+#[module_export]
+fn days(_ctx: &ScanContext, n: i64) -> Option<i64> {
+    n.checked_mul(24 * 60 * 60)
+}
+
+/// Returns the number of seconds in `n` weeks.
+#[module_export]
+fn weeks(_ctx: &ScanContext, n: i64) -> Option<i64> {
+    n.checked_mul(7 * 24 * 60 * 60)
+}
+
+fn parse_iso8601(date: &str) -> Option<i64> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.timestamp());
+    }
+
+    if let Ok(date) = DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f%z")
+    {
+        return Some(date.timestamp());
+    }
+
+    if let Ok(date) =
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f")
+    {
+        return Some(date.and_utc().timestamp());
+    }
+
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+}
+
+fn format_epoch(epoch: i64, format: &str) -> Option<String> {
+    let date = DateTime::from_timestamp(epoch, 0)?;
+    let items = StrftimeItems::new(format).collect::<Vec<Item>>();
+
+    // An invalid format would make `format_with_items` fail, so it must be
+    // checked beforehand.
+    if items.contains(&Item::Error) {
+        return None;
+    }
+
+    let mut result = String::new();
+
+    write!(result, "{}", date.format_with_items(items.into_iter())).ok()?;
+
+    Some(result)
+}
+
 #[cfg(test)]
 mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{format_epoch, parse_iso8601};
+
     #[test]
     fn end2end() {
         let mut compiler = crate::compiler::Compiler::new();
@@ -31,8 +139,57 @@ mod tests {
             .unwrap();
 
         let rules = compiler.build();
-        let mut scanner = crate::scanner::Scanner::new(&rules);
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
 
         assert_eq!(scanner.scan(&[]).unwrap().matching_rules().len(), 2);
     }
+
+    #[test]
+    fn parsing_and_formatting() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "time"
+                rule rule_1 { condition: time.from_iso8601("1970-01-02") == 86400 }
+                rule rule_2 { condition: time.from_iso8601("1970-01-01T01:00:00Z") == time.hours(1) }
+                rule rule_3 { condition: not defined time.from_iso8601("foo") }
+                rule rule_4 { condition: time.to_string(0) == "1970-01-01T00:00:00Z" }
+                rule rule_5 { condition: time.to_string(time.days(1), "%d/%m/%Y") == "02/01/1970" }
+                rule rule_6 { condition: not defined time.to_string(0, "%Q") }
+                rule rule_7 { condition: time.now() - time.weeks(1) < time.now() - time.minutes(1) }
+                rule rule_8 { condition: not defined time.days(0x7fffffffffffffff) }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        assert_eq!(scanner.scan(&[]).unwrap().matching_rules().len(), 8);
+    }
+
+    #[test]
+    fn iso8601() {
+        assert_eq!(parse_iso8601("2023-08-01"), Some(1690848000));
+        assert_eq!(parse_iso8601("2023-08-01T10:30:00"), Some(1690885800));
+        assert_eq!(parse_iso8601("2023-08-01T10:30:00Z"), Some(1690885800));
+        assert_eq!(parse_iso8601("2023-08-01T10:30:00.5Z"), Some(1690885800));
+        assert_eq!(
+            parse_iso8601("2023-08-01T12:30:00+02:00"),
+            Some(1690885800)
+        );
+        assert_eq!(
+            parse_iso8601("2023-08-01T12:30:00+0200"),
+            Some(1690885800)
+        );
+        assert_eq!(parse_iso8601("2023-13-01"), None);
+        assert_eq!(parse_iso8601(""), None);
+
+        assert_eq!(
+            format_epoch(1690885800, "%Y-%m-%d %H:%M:%S").as_deref(),
+            Some("2023-08-01 10:30:00")
+        );
+        assert_eq!(format_epoch(i64::MAX, "%Y"), None);
+    }
 }
@@ -0,0 +1,254 @@
+/*! Minimal reader for data encoded with ASN.1 DER.
+
+The reader splits the data into its TLV (tag, length, value) components,
+which is enough for locating structures like certificates inside larger
+ones. A few helpers decode the types that appear in certificates, like
+object identifiers, names and times.
+*/
+
+use std::fmt::Write;
+
+/// A DER-encoded value.
+#[derive(Clone, Copy)]
+pub(crate) struct Tlv<'a> {
+    /// The value's tag (e.g: 0x30 for a SEQUENCE).
+    pub tag: u8,
+    /// The value's content, without the tag and length.
+    pub content: &'a [u8],
+    /// The value's complete encoding, including the tag and length.
+    pub raw: &'a [u8],
+}
+
+impl<'a> Tlv<'a> {
+    /// Parses the value at the start of `data`, returning the value and the
+    /// data that follows it. Returns `None` if the data is not a valid DER
+    /// value, or its length exceeds the end of the data.
+    ///
+    /// Only single-byte tags are supported, as multi-byte tags are not used
+    /// in the structures read with this function.
+    pub fn parse(data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let tag = *data.first()?;
+        let first = *data.get(1)? as usize;
+
+        let (len, header_len) = if first < 0x80 {
+            (first, 2)
+        } else {
+            // Long form, the lower 7 bits are the number of bytes in the
+            // length. The indefinite form (0x80) is not valid in DER.
+            let num_bytes = first & 0x7f;
+            if num_bytes == 0 || num_bytes > 4 {
+                return None;
+            }
+            let len = data
+                .get(2..2 + num_bytes)?
+                .iter()
+                .fold(0_usize, |len, b| len << 8 | *b as usize);
+            (len, 2 + num_bytes)
+        };
+
+        let end = header_len.checked_add(len)?;
+        let raw = data.get(..end)?;
+
+        Some((Self { tag, content: &raw[header_len..], raw }, &data[end..]))
+    }
+
+    /// Returns an iterator over the values contained in this one, which
+    /// must be a constructed value, like a SEQUENCE or SET.
+    pub fn children(&self) -> impl Iterator<Item = Tlv<'a>> {
+        let mut data = self.content;
+        std::iter::from_fn(move || {
+            let (tlv, rest) = Tlv::parse(data)?;
+            data = rest;
+            Some(tlv)
+        })
+    }
+}
+
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const UTC_TIME: u8 = 0x17;
+pub(crate) const GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const SEQUENCE: u8 = 0x30;
+pub(crate) const SET: u8 = 0x31;
+
+/// Returns an object identifier in dotted notation (e.g: "2.5.4.3").
+pub(crate) fn oid_string(content: &[u8]) -> String {
+    let mut s = String::new();
+    let mut value = 0_u64;
+
+    for b in content {
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 != 0 {
+            continue;
+        }
+        // The first two components are encoded together in the first
+        // value, as 40 * X + Y.
+        if s.is_empty() {
+            let first = (value / 40).min(2);
+            write!(s, "{}.{}", first, value - first * 40).unwrap();
+        } else {
+            write!(s, ".{}", value).unwrap();
+        }
+        value = 0;
+    }
+
+    s
+}
+
+/// Returns the name of the algorithm in an `AlgorithmIdentifier`, or its
+/// OID if the name is not known.
+pub(crate) fn algorithm_name(algorithm: &Tlv) -> String {
+    let oid = match algorithm.children().next() {
+        Some(oid) if oid.tag == OID => oid_string(oid.content),
+        _ => return String::new(),
+    };
+
+    let name = match oid.as_str() {
+        "1.2.840.113549.1.1.1" => "rsa",
+        "1.2.840.10040.4.1" => "dsa",
+        "1.2.840.10045.2.1" => "ec",
+        "1.3.101.110" => "x25519",
+        "1.3.101.111" => "x448",
+        "1.3.101.112" => "ed25519",
+        "1.3.101.113" => "ed448",
+        "1.2.840.113549.1.1.4" => "md5WithRSAEncryption",
+        "1.2.840.113549.1.1.5" => "sha1WithRSAEncryption",
+        "1.2.840.113549.1.1.10" => "rsassaPss",
+        "1.2.840.113549.1.1.11" => "sha256WithRSAEncryption",
+        "1.2.840.113549.1.1.12" => "sha384WithRSAEncryption",
+        "1.2.840.113549.1.1.13" => "sha512WithRSAEncryption",
+        "1.2.840.10040.4.3" => "dsaWithSHA1",
+        "2.16.840.1.101.3.4.3.2" => "dsaWithSHA256",
+        "1.2.840.10045.4.1" => "ecdsa-with-SHA1",
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256",
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384",
+        "1.2.840.10045.4.3.4" => "ecdsa-with-SHA512",
+        _ => return oid,
+    };
+
+    name.to_string()
+}
+
+/// Returns a distinguished name in the format used by OpenSSL's one-line
+/// format (e.g: "/C=US/O=Example/CN=example.com"), which is also the one
+/// used by the `pe` module in YARA.
+pub(crate) fn distinguished_name(name: &Tlv) -> String {
+    let mut s = String::new();
+
+    for attribute in name.children().flat_map(|rdn| rdn.children()) {
+        let mut parts = attribute.children();
+        let (oid, value) = match (parts.next(), parts.next()) {
+            (Some(oid), Some(value)) if oid.tag == OID => (oid, value),
+            _ => continue,
+        };
+
+        let oid = oid_string(oid.content);
+        let key = match oid.as_str() {
+            "2.5.4.3" => "CN",
+            "2.5.4.5" => "serialNumber",
+            "2.5.4.6" => "C",
+            "2.5.4.7" => "L",
+            "2.5.4.8" => "ST",
+            "2.5.4.9" => "street",
+            "2.5.4.10" => "O",
+            "2.5.4.11" => "OU",
+            "2.5.4.12" => "title",
+            "1.2.840.113549.1.9.1" => "emailAddress",
+            "0.9.2342.19200300.100.1.25" => "DC",
+            oid => oid,
+        };
+
+        let value = match value.tag {
+            // BMPString, encoded in UTF-16BE.
+            0x1e => String::from_utf16_lossy(
+                &value
+                    .content
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>(),
+            ),
+            _ => String::from_utf8_lossy(value.content).into_owned(),
+        };
+
+        write!(s, "/{}={}", key, value).unwrap();
+    }
+
+    s
+}
+
+/// Decodes a UTCTime or GeneralizedTime, returning a UNIX timestamp.
+pub(crate) fn time(time: &Tlv) -> Option<i64> {
+    let s = std::str::from_utf8(time.content).ok()?;
+    let s = s.strip_suffix('Z').unwrap_or(s);
+
+    let (year, rest) = match time.tag {
+        // Years from 50 to 99 are 1950 to 1999, the rest are 2000 to 2049.
+        UTC_TIME => {
+            let year: i64 = s.get(..2)?.parse().ok()?;
+            (if year >= 50 { 1900 + year } else { 2000 + year }, s.get(2..)?)
+        }
+        GENERALIZED_TIME => (s.get(..4)?.parse().ok()?, s.get(4..)?),
+        _ => return None,
+    };
+
+    let field = |i: usize| -> Option<i64> { rest.get(i..i + 2)?.parse().ok() };
+
+    let (month, day) = (field(0)?, field(2)?);
+    let (hour, minute, second) = (field(4)?, field(6)?, field(8).unwrap_or(0));
+
+    let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if is_leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+
+    if day < 1
+        || day > days_in_month
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    Some(
+        days_from_civil(year, month, day) * 86400
+            + hour * 3600
+            + minute * 60
+            + second,
+    )
+}
+
+/// Returns the number of days since January 1, 1970, for a date in the
+/// proleptic Gregorian calendar.
+///
+/// See: https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the value of a small non-negative integer.
+pub(crate) fn integer_value(content: &[u8]) -> Option<u64> {
+    if content.len() > 8 {
+        return None;
+    }
+    Some(content.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+}
+
+/// Removes the leading zero bytes from a positive integer.
+pub(crate) fn strip_leading_zero(content: &[u8]) -> &[u8] {
+    let zeros = content.iter().take_while(|b| **b == 0).count();
+    &content[zeros.min(content.len().saturating_sub(1))..]
+}
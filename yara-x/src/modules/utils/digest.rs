@@ -0,0 +1,301 @@
+/*! Message digests that are not provided by the `sha2` crate.
+
+These are needed for computing hashes that are defined in terms of MD5
+or SHA-1, like the import hash of PE files, or for verifying signatures
+that use them. They must not be used for anything else.
+*/
+
+/// Incremental MD5 hasher.
+///
+/// See: RFC 1321.
+pub(crate) struct Md5 {
+    state: [u32; 4],
+    block: Block,
+}
+
+impl Md5 {
+    pub fn new() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            block: Block::new(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.block.update(data, |block| md5_compress(state, block));
+    }
+
+    pub fn finalize(mut self) -> [u8; 16] {
+        let len = self.block.len_bits().to_le_bytes();
+        let state = &mut self.state;
+        self.block.pad(len, |block| md5_compress(state, block));
+
+        let mut digest = [0; 16];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+/// Returns the MD5 of `data`.
+pub(crate) fn md5(data: &[u8]) -> [u8; 16] {
+    let mut hasher = Md5::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Returns the MD5 of `data`, as a hex string.
+pub(crate) fn md5_hex(data: &[u8]) -> String {
+    super::hex(&md5(data))
+}
+
+/// Incremental SHA-1 hasher.
+///
+/// See: RFC 3174.
+pub(crate) struct Sha1 {
+    state: [u32; 5],
+    block: Block,
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Self {
+            state: [
+                0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0,
+            ],
+            block: Block::new(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.block.update(data, |block| sha1_compress(state, block));
+    }
+
+    pub fn finalize(mut self) -> [u8; 20] {
+        let len = self.block.len_bits().to_be_bytes();
+        let state = &mut self.state;
+        self.block.pad(len, |block| sha1_compress(state, block));
+
+        let mut digest = [0; 20];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+/// Returns the SHA-1 of `data`.
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Returns the SHA-1 of `data`, as a hex string.
+pub(crate) fn sha1_hex(data: &[u8]) -> String {
+    super::hex(&sha1(data))
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20,
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4,
+    11, 16, 23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6,
+    10, 15, 21,
+];
+
+const MD5_CONSTANTS: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a,
+    0xa8304613, 0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be,
+    0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340,
+    0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8,
+    0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c,
+    0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+    0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92,
+    0xffeff47d, 0x85845dd1, 0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1,
+    0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+fn md5_compress(state: &mut [u32; 4], block: &[u8; 64]) {
+    let mut m = [0_u32; 16];
+    for (word, chunk) in m.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+
+    let [mut a, mut b, mut c, mut d] = *state;
+
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let f = f
+            .wrapping_add(a)
+            .wrapping_add(MD5_CONSTANTS[i])
+            .wrapping_add(m[g]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(f.rotate_left(MD5_SHIFTS[i]));
+    }
+
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+}
+
+fn sha1_compress(state: &mut [u32; 5], block: &[u8; 64]) {
+    let mut w = [0_u32; 80];
+    for (word, chunk) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(chunk.try_into().unwrap());
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+
+    for (i, word) in w.iter().enumerate() {
+        let (f, k) = match i / 20 {
+            0 => ((b & c) | (!b & d), 0x5a827999),
+            1 => (b ^ c ^ d, 0x6ed9eba1),
+            2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(*word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// Buffers the input of hash functions that work with 64-byte blocks,
+/// like MD5 and SHA-1.
+struct Block {
+    buf: [u8; 64],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl Block {
+    fn new() -> Self {
+        Self { buf: [0; 64], buf_len: 0, total_len: 0 }
+    }
+
+    /// Returns the length of the input in bits.
+    fn len_bits(&self) -> u64 {
+        self.total_len.wrapping_mul(8)
+    }
+
+    /// Adds `data` to the input, calling `compress` for each complete
+    /// block.
+    fn update(
+        &mut self,
+        mut data: &[u8],
+        mut compress: impl FnMut(&[u8; 64]),
+    ) {
+        self.total_len = self.total_len.wrapping_add(data.len() as u64);
+
+        if self.buf_len > 0 {
+            let n = (64 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + n]
+                .copy_from_slice(&data[..n]);
+            self.buf_len += n;
+            data = &data[n..];
+            if self.buf_len < 64 {
+                return;
+            }
+            compress(&self.buf);
+            self.buf_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            compress(block.try_into().unwrap());
+        }
+
+        let rest = blocks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    /// Pads the input with a 1 bit, zeroes and the encoded length, and
+    /// processes the remaining blocks.
+    fn pad(&mut self, len: [u8; 8], mut compress: impl FnMut(&[u8; 64])) {
+        self.buf[self.buf_len] = 0x80;
+        self.buf[self.buf_len + 1..].fill(0);
+
+        if self.buf_len >= 56 {
+            compress(&self.buf);
+            self.buf.fill(0);
+        }
+
+        self.buf[56..].copy_from_slice(&len);
+        compress(&self.buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{md5_hex, sha1_hex};
+
+    #[test]
+    fn md5() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            md5_hex(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        // Input that needs an extra block for the padding.
+        assert_eq!(md5_hex(&[b'a'; 60]), "cc7ed669cf88f201c3297c6a91e1d18d");
+
+        let mut hasher = super::Md5::new();
+        for chunk in [b'a'; 200].chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(
+            crate::modules::utils::hex(&hasher.finalize()),
+            md5_hex(&[b'a'; 200])
+        );
+    }
+
+    #[test]
+    fn sha1() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            sha1_hex(b"The quick brown fox jumps over the lazy dog"),
+            "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12"
+        );
+        assert_eq!(
+            sha1_hex(&[b'a'; 60]),
+            "13d956033d9af449bfe2c4ef78c17c20469c4bf1"
+        );
+
+        let mut hasher = super::Sha1::new();
+        for chunk in [b'a'; 200].chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(
+            crate::modules::utils::hex(&hasher.finalize()),
+            sha1_hex(&[b'a'; 200])
+        );
+    }
+}
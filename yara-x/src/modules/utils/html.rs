@@ -0,0 +1,204 @@
+/*! Lightweight scanner for HTML documents.
+
+This is not a complete HTML parser, it locates tags and parses their
+attributes, which is enough for finding the scripts in HTML pages and HTML
+applications (HTA). Malformed documents are handled on a best effort basis.
+*/
+
+use bstr::ByteSlice;
+
+/// Maximum length of attribute values.
+const MAX_VALUE_LEN: usize = 4096;
+
+/// A start tag.
+pub(crate) struct Tag {
+    /// Name of the tag, in lowercase (e.g: `script`, `hta:application`).
+    pub name: String,
+    /// Names and values of the attributes. Names are in lowercase.
+    pub attributes: Vec<(String, String)>,
+    /// Offset where the tag starts, i.e: the offset of the `<` character.
+    pub start: usize,
+    /// Offset right after the end of the tag.
+    pub end: usize,
+}
+
+impl Tag {
+    /// Returns the value of the attribute with the given name, which must
+    /// be in lowercase.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A script in an HTML document.
+pub(crate) struct Script {
+    /// Language of the script, from the `language` or `type` attributes
+    /// (e.g: `VBScript`, `text/javascript`).
+    pub language: Option<String>,
+    /// URL of external scripts.
+    pub src: Option<String>,
+    /// Offset and size of the script's code within the document.
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// Returns the first start tag found at or after `from`. Comments, end
+/// tags and declarations (e.g: `<!DOCTYPE html>`) are skipped.
+pub(crate) fn next_tag(html: &[u8], from: usize) -> Option<Tag> {
+    let mut pos = from;
+
+    loop {
+        let start = pos + html.get(pos..)?.find_byte(b'<')?;
+        let rest = &html[start + 1..];
+
+        if rest.starts_with(b"!--") {
+            pos = match rest.find(b"-->") {
+                Some(end) => start + 1 + end + 3,
+                None => return None,
+            };
+            continue;
+        }
+
+        let name_len = rest
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || b":-_".contains(c))
+            .count();
+
+        if name_len == 0 || !rest[0].is_ascii_alphabetic() {
+            pos = start + 1;
+            continue;
+        }
+
+        let name = rest[..name_len].to_ascii_lowercase();
+        let (attributes, len) = attributes(&rest[name_len..]);
+
+        return Some(Tag {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            attributes,
+            start,
+            end: start + 1 + name_len + len,
+        });
+    }
+}
+
+/// Parses the attributes of a tag, starting right after the tag's name.
+/// Returns the attributes and the number of bytes until the end of the
+/// tag.
+fn attributes(tag: &[u8]) -> (Vec<(String, String)>, usize) {
+    let mut attributes = Vec::new();
+    let mut pos = 0;
+
+    let string = |s: &[u8]| -> String {
+        String::from_utf8_lossy(&s[..s.len().min(MAX_VALUE_LEN)]).into_owned()
+    };
+
+    loop {
+        while pos < tag.len()
+            && (tag[pos].is_ascii_whitespace() || tag[pos] == b'/')
+        {
+            pos += 1;
+        }
+
+        match tag.get(pos) {
+            Some(b'>') => return (attributes, pos + 1),
+            None => return (attributes, tag.len()),
+            _ => {}
+        }
+
+        let name_start = pos;
+        while pos < tag.len()
+            && !tag[pos].is_ascii_whitespace()
+            && !b"=>/".contains(&tag[pos])
+        {
+            pos += 1;
+        }
+
+        // Characters that can't start a name, like a stray `=`, are
+        // skipped.
+        if pos == name_start {
+            pos += 1;
+            continue;
+        }
+
+        let name = string(&tag[name_start..pos]).to_ascii_lowercase();
+
+        while pos < tag.len() && tag[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        if tag.get(pos) != Some(&b'=') {
+            attributes.push((name, String::new()));
+            continue;
+        }
+
+        pos += 1;
+        while pos < tag.len() && tag[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let value = match tag.get(pos) {
+            Some(quote @ (b'"' | b'\'')) => {
+                let value_start = pos + 1;
+                let len = tag[value_start..]
+                    .find_byte(*quote)
+                    .unwrap_or(tag.len() - value_start);
+                pos = value_start + len + 1;
+                &tag[value_start..value_start + len]
+            }
+            _ => {
+                let value_start = pos;
+                while pos < tag.len()
+                    && !tag[pos].is_ascii_whitespace()
+                    && tag[pos] != b'>'
+                {
+                    pos += 1;
+                }
+                &tag[value_start..pos]
+            }
+        };
+
+        attributes.push((name, string(value)));
+    }
+}
+
+/// Returns the scripts in an HTML document.
+pub(crate) fn scripts(html: &[u8]) -> Vec<Script> {
+    let mut scripts = Vec::new();
+    let mut pos = 0;
+
+    while let Some(tag) = next_tag(html, pos) {
+        pos = tag.end;
+
+        if tag.name != "script" {
+            continue;
+        }
+
+        // The script ends at the closing tag, or at the end of the document
+        // if there's no closing tag.
+        let code = html.get(tag.end..).unwrap_or_default();
+        let size = code
+            .find_iter(b"</")
+            .find(|i| {
+                code.get(i + 2..i + 8)
+                    .map_or(false, |name| name.eq_ignore_ascii_case(b"script"))
+            })
+            .unwrap_or(code.len());
+
+        scripts.push(Script {
+            language: tag
+                .attribute("language")
+                .or_else(|| tag.attribute("type"))
+                .map(String::from),
+            src: tag.attribute("src").map(String::from),
+            offset: tag.end,
+            size,
+        });
+
+        pos = tag.end + size;
+    }
+
+    scripts
+}
@@ -0,0 +1,380 @@
+/*! Decompression of data compressed with the DEFLATE algorithm.
+
+This implements the decompression of raw DEFLATE streams (RFC 1951) and
+streams with a zlib wrapper (RFC 1950). These formats are used by many file
+formats parsed by modules, like ZIP archives and PDF streams.
+*/
+
+use thiserror::Error;
+
+/// Error returned when the compressed data is not valid.
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid compressed data")]
+pub(crate) struct InflateError;
+
+/// Decompresses a stream in zlib format.
+///
+/// Decompression stops after producing `max_size` bytes, in that case the
+/// output is truncated. The Adler-32 checksum at the end of the stream is
+/// not verified.
+pub(crate) fn zlib_decompress(
+    data: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>, InflateError> {
+    let (cmf, flg) = match data {
+        [cmf, flg, ..] => (*cmf, *flg),
+        _ => return Err(InflateError),
+    };
+    // The compression method must be 8 (deflate), the header checksum must
+    // be valid and no preset dictionary is supported.
+    if cmf & 0x0f != 8
+        || (cmf as u16 * 256 + flg as u16) % 31 != 0
+        || flg & 0x20 != 0
+    {
+        return Err(InflateError);
+    }
+    inflate(&data[2..], max_size)
+}
+
+/// Decompresses a raw DEFLATE stream.
+///
+/// Decompression stops after producing `max_size` bytes, in that case the
+/// output is truncated.
+pub(crate) fn inflate(
+    data: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>, InflateError> {
+    let mut inflater = Inflater {
+        input: BitReader { data, pos: 0, bit_buf: 0, bit_count: 0 },
+        output: Vec::new(),
+        max_size,
+    };
+    inflater.run()?;
+    Ok(inflater.output)
+}
+
+/// Base lengths for length codes 257..285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+    67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+
+/// Extra bits for length codes 257..285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5,
+    5, 5, 5, 0,
+];
+
+/// Base distances for distance codes 0..29.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+    769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+/// Extra bits for distance codes 0..29.
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+    11, 11, 12, 12, 13, 13,
+];
+
+/// Order in which the code lengths of the code length alphabet are stored
+/// in dynamic blocks.
+const CODE_LENGTH_ORDER: [usize; 19] =
+    [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const MAX_BITS: usize = 15;
+
+/// Reads the input one bit at a time, starting with the least significant
+/// bit of each byte.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+        while self.bit_count < n {
+            let byte = *self.data.get(self.pos).ok_or(InflateError)?;
+            self.pos += 1;
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buf & ((1_u64 << n) - 1) as u32;
+        self.bit_buf >>= n;
+        self.bit_count -= n;
+        Ok(value)
+    }
+
+    /// Discards the remaining bits in the current byte.
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// A canonical Huffman code, represented by the number of codes of each
+/// length and the symbols sorted by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0_u16; MAX_BITS + 1];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+
+        // Make sure that the code is not over-subscribed.
+        let mut left = 1_i32;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(InflateError);
+            }
+        }
+
+        let mut offsets = [0_u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut BitReader) -> Result<u16, InflateError> {
+        let mut code = 0_i32;
+        let mut first = 0_i32;
+        let mut index = 0_i32;
+        for len in 1..=MAX_BITS {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return self
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .copied()
+                    .ok_or(InflateError);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError)
+    }
+}
+
+struct Inflater<'a> {
+    input: BitReader<'a>,
+    output: Vec<u8>,
+    max_size: usize,
+}
+
+impl Inflater<'_> {
+    fn run(&mut self) -> Result<(), InflateError> {
+        loop {
+            let is_last = self.input.bits(1)? == 1;
+            match self.input.bits(2)? {
+                0 => self.stored()?,
+                1 => self.fixed()?,
+                2 => self.dynamic()?,
+                _ => return Err(InflateError),
+            }
+            if is_last || self.output.len() >= self.max_size {
+                self.output.truncate(self.max_size);
+                return Ok(());
+            }
+        }
+    }
+
+    fn stored(&mut self) -> Result<(), InflateError> {
+        self.input.align();
+        let input = &mut self.input;
+        let header =
+            input.data.get(input.pos..input.pos + 4).ok_or(InflateError)?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        let nlen = u16::from_le_bytes([header[2], header[3]]);
+        if len != !nlen {
+            return Err(InflateError);
+        }
+        input.pos += 4;
+        let block = input
+            .data
+            .get(input.pos..input.pos + len as usize)
+            .ok_or(InflateError)?;
+        input.pos += len as usize;
+        self.output.extend_from_slice(block);
+        Ok(())
+    }
+
+    fn fixed(&mut self) -> Result<(), InflateError> {
+        let mut lengths = [0_u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        let lit_len = Huffman::new(&lengths)?;
+        let dist = Huffman::new(&[5; 30])?;
+        self.codes(&lit_len, &dist)
+    }
+
+    fn dynamic(&mut self) -> Result<(), InflateError> {
+        let num_lit_len = self.input.bits(5)? as usize + 257;
+        let num_dist = self.input.bits(5)? as usize + 1;
+        let num_code_len = self.input.bits(4)? as usize + 4;
+
+        if num_lit_len > 286 || num_dist > 30 {
+            return Err(InflateError);
+        }
+
+        let mut lengths = [0_u8; 19];
+        for i in CODE_LENGTH_ORDER.iter().take(num_code_len) {
+            lengths[*i] = self.input.bits(3)? as u8;
+        }
+
+        let code_len = Huffman::new(&lengths)?;
+        let mut lengths = vec![0_u8; num_lit_len + num_dist];
+        let mut i = 0;
+
+        while i < lengths.len() {
+            let symbol = code_len.decode(&mut self.input)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let prev =
+                        *lengths.get(i.wrapping_sub(1)).ok_or(InflateError)?;
+                    (prev, 3 + self.input.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.input.bits(3)? as usize),
+                _ => (0, 11 + self.input.bits(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(InflateError);
+            }
+            lengths[i..i + repeat].fill(value);
+            i += repeat;
+        }
+
+        // The end-of-block symbol must have a code.
+        if lengths[256] == 0 {
+            return Err(InflateError);
+        }
+
+        let lit_len = Huffman::new(&lengths[..num_lit_len])?;
+        let dist = Huffman::new(&lengths[num_lit_len..])?;
+
+        self.codes(&lit_len, &dist)
+    }
+
+    /// Decodes the literals and length/distance pairs of a compressed
+    /// block.
+    fn codes(
+        &mut self,
+        lit_len: &Huffman,
+        dist: &Huffman,
+    ) -> Result<(), InflateError> {
+        loop {
+            let symbol = lit_len.decode(&mut self.input)? as usize;
+            match symbol {
+                0..=255 => self.output.push(symbol as u8),
+                256 => return Ok(()),
+                _ => {
+                    let symbol = symbol - 257;
+                    if symbol >= LENGTH_BASE.len() {
+                        return Err(InflateError);
+                    }
+                    let len = LENGTH_BASE[symbol] as usize
+                        + self.input.bits(LENGTH_EXTRA[symbol] as u32)?
+                            as usize;
+
+                    let symbol = dist.decode(&mut self.input)? as usize;
+                    if symbol >= DIST_BASE.len() {
+                        return Err(InflateError);
+                    }
+                    let distance = DIST_BASE[symbol] as usize
+                        + self.input.bits(DIST_EXTRA[symbol] as u32)? as usize;
+
+                    if distance > self.output.len() {
+                        return Err(InflateError);
+                    }
+
+                    // The copied data can overlap with the data being
+                    // produced, so it must be copied byte by byte.
+                    let start = self.output.len() - distance;
+                    for i in 0..len {
+                        self.output.push(self.output[start + i]);
+                    }
+                }
+            }
+            if self.output.len() >= self.max_size {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{inflate, zlib_decompress, InflateError};
+
+    #[test]
+    fn stored_and_fixed() {
+        // Stored block.
+        assert_eq!(
+            inflate(b"\x01\x03\x00\xfc\xffabc", 100),
+            Ok(b"abc".to_vec())
+        );
+
+        // Fixed Huffman codes, produced by zlib for "hello hello hello".
+        assert_eq!(
+            zlib_decompress(
+                b"\x78\x9c\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x00\x3a\x2e\x06\x7d",
+                100
+            ),
+            Ok(b"hello hello hello".to_vec())
+        );
+
+        // The output is truncated at `max_size`.
+        assert_eq!(
+            zlib_decompress(
+                b"\x78\x9c\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x00\x3a\x2e\x06\x7d",
+                8
+            ),
+            Ok(b"hello he".to_vec())
+        );
+
+        assert_eq!(inflate(b"\x07", 100), Err(InflateError));
+        assert_eq!(zlib_decompress(b"\x78\x00", 100), Err(InflateError));
+    }
+
+    #[test]
+    fn dynamic() {
+        // Dynamic Huffman codes, produced by zlib for the squares of the
+        // numbers from 0 to 19, separated by spaces.
+        let expected = (0..20)
+            .map(|i: i32| i.pow(2).to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        assert_eq!(
+            inflate(
+                b"\x0d\xca\x31\x11\x00\x30\x0c\xc3\x40\x2a\x82\x10\xa7\x6e\xae\xe1\x4f\xac\x9e\x34\xe8\x0b\x61\x16\x0d\x7d\x39\x83\x97\x31\x4f\xa8\x0a\x75\x6a\xe7\x46\x6c\x48\x4c\xdf\xf4\x2d\xa7\x1d\xaf\x0f",
+                1000
+            ),
+            Ok(expected.into_bytes())
+        );
+    }
+}
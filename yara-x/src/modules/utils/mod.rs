@@ -0,0 +1,119 @@
+/*! Utilities for parsing file formats, shared by multiple modules. */
+
+// Some utilities are not used when the modules that need them are disabled.
+#![allow(dead_code)]
+
+pub(crate) mod der;
+pub(crate) mod digest;
+pub(crate) mod html;
+pub(crate) mod inflate;
+pub(crate) mod ole;
+pub(crate) mod pe;
+pub(crate) mod tlsh;
+pub(crate) mod zip;
+
+/// Returns the number of occurrences of each byte value in `data`.
+pub(crate) fn histogram(data: &[u8]) -> [u64; 256] {
+    let mut histogram = [0_u64; 256];
+    for byte in data {
+        histogram[*byte as usize] += 1;
+    }
+    histogram
+}
+
+/// Returns the Shannon entropy of `data`, in bits per byte.
+pub(crate) fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let len = data.len() as f64;
+
+    histogram(data)
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Returns the `N` bytes at the given offset, or `None` if `data` is too
+/// short.
+fn bytes_at<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
+/// Reads a little-endian `u16` at the given offset.
+pub(crate) fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    bytes_at(data, offset).map(u16::from_le_bytes)
+}
+
+/// Reads a little-endian `u32` at the given offset.
+pub(crate) fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    bytes_at(data, offset).map(u32::from_le_bytes)
+}
+
+/// Reads a little-endian `u64` at the given offset.
+pub(crate) fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    bytes_at(data, offset).map(u64::from_le_bytes)
+}
+
+/// Reads a big-endian `u16` at the given offset.
+pub(crate) fn u16_be_at(data: &[u8], offset: usize) -> Option<u16> {
+    bytes_at(data, offset).map(u16::from_be_bytes)
+}
+
+/// Reads a big-endian `u32` at the given offset.
+pub(crate) fn u32_be_at(data: &[u8], offset: usize) -> Option<u32> {
+    bytes_at(data, offset).map(u32::from_be_bytes)
+}
+
+/// Reads a big-endian `u64` at the given offset.
+pub(crate) fn u64_be_at(data: &[u8], offset: usize) -> Option<u64> {
+    bytes_at(data, offset).map(u64::from_be_bytes)
+}
+
+/// Converts a Windows FILETIME, which is the number of 100-nanosecond
+/// intervals since January 1, 1601, to a UNIX timestamp. Returns `None`
+/// for zero, which means that the time is not set.
+pub(crate) fn filetime_to_epoch(filetime: u64) -> Option<i64> {
+    if filetime == 0 {
+        return None;
+    }
+    Some((filetime / 10_000_000) as i64 - 11_644_473_600)
+}
+
+/// Formats a GUID stored in its binary form, where the first three
+/// components are little-endian integers.
+///
+/// # Panics
+///
+/// If `guid` is shorter than 16 bytes.
+pub(crate) fn format_guid(guid: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{}-{}",
+        u32::from_le_bytes(guid[0..4].try_into().unwrap()),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        hex(&guid[8..10]),
+        hex(&guid[10..16]),
+    )
+}
+
+/// Decodes a UTF-16LE string, which ends at the first null character, if
+/// any.
+pub(crate) fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|c| *c != 0)
+        .collect();
+
+    String::from_utf16_lossy(&units)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
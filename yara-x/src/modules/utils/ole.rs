@@ -0,0 +1,300 @@
+/*! Reader for OLE2 compound files.
+
+Compound files (also known as Compound File Binary or CFB) are a file
+system inside a file, used by legacy Office documents, MSI installers and
+many other formats. They contain storages (directories) and streams (files)
+whose data is split into sectors, the sectors used by each stream are
+chained together in the File Allocation Table (FAT). Small streams are
+stored in the mini stream, a stream divided into smaller sectors that are
+chained in the mini FAT.
+*/
+
+use std::collections::HashSet;
+
+use super::{u16_at, u32_at};
+
+/// Signature at the start of every compound file.
+pub(crate) const SIGNATURE: &[u8] = b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1";
+
+/// Special values in the FAT and in directory entry IDs.
+const MAX_REG_SECT: u32 = 0xfffffffa;
+const NO_STREAM: u32 = 0xffffffff;
+
+/// Type of a directory entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum EntryType {
+    Empty,
+    Storage,
+    Stream,
+    Root,
+}
+
+/// An entry in the compound file's directory.
+pub(crate) struct DirEntry {
+    pub name: String,
+    /// Path of the entry, with the names of the storages that contain it
+    /// separated by slashes (e.g: `Macros/VBA/dir`). The root entry has an
+    /// empty path.
+    pub path: String,
+    pub entry_type: EntryType,
+    pub clsid: [u8; 16],
+    pub size: u64,
+    start_sector: u32,
+    left: u32,
+    right: u32,
+    child: u32,
+}
+
+/// A compound file.
+pub(crate) struct Ole<'a> {
+    data: &'a [u8],
+    sector_size: usize,
+    mini_sector_size: usize,
+    mini_stream_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    /// Entries in the directory, indexed by their IDs. Entries that are not
+    /// reachable from the root entry don't have a path.
+    pub entries: Vec<DirEntry>,
+}
+
+impl<'a> Ole<'a> {
+    /// Parses a compound file. Returns `None` if the data doesn't start
+    /// with the compound file signature or the header is not valid.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if !data.starts_with(SIGNATURE) || data.len() < 512 {
+            return None;
+        }
+
+        let sector_shift = u16_at(data, 0x1e)?;
+        let mini_sector_shift = u16_at(data, 0x20)?;
+
+        if !(9..=16).contains(&sector_shift)
+            || mini_sector_shift > sector_shift
+        {
+            return None;
+        }
+
+        let mut ole = Self {
+            data,
+            sector_size: 1 << sector_shift,
+            mini_sector_size: 1 << mini_sector_shift,
+            mini_stream_cutoff: u32_at(data, 0x38)? as u64,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            entries: Vec::new(),
+        };
+
+        // The header contains the first 109 entries of the DIFAT, which is
+        // the list of sectors that contain the FAT. The remaining entries
+        // are in a chain of DIFAT sectors, where the last entry in each
+        // sector is the next sector in the chain.
+        let mut fat_sectors = (0..109)
+            .filter_map(|i| u32_at(data, 0x4c + i * 4))
+            .collect::<Vec<_>>();
+
+        let mut difat_sector = u32_at(data, 0x44)?;
+        let mut visited = HashSet::new();
+
+        while difat_sector <= MAX_REG_SECT && visited.insert(difat_sector) {
+            let sector = match ole.sector(difat_sector) {
+                Some(sector) if sector.len() >= 4 => sector,
+                _ => break,
+            };
+            let entries = sector.len() / 4;
+            fat_sectors.extend(
+                (0..entries - 1).filter_map(|i| u32_at(sector, i * 4)),
+            );
+            difat_sector = u32_at(sector, (entries - 1) * 4)?;
+        }
+
+        for fat_sector in fat_sectors {
+            if fat_sector > MAX_REG_SECT {
+                continue;
+            }
+            if let Some(sector) = ole.sector(fat_sector) {
+                ole.fat.extend(sector.chunks_exact(4).map(|entry| {
+                    u32::from_le_bytes(entry.try_into().unwrap())
+                }));
+            }
+        }
+
+        let directory = ole.chain(u32_at(data, 0x30)?, usize::MAX);
+
+        ole.entries =
+            directory.chunks_exact(128).map_while(DirEntry::parse).collect();
+
+        let mini_fat = ole.chain(u32_at(data, 0x3c)?, usize::MAX);
+
+        ole.mini_fat = mini_fat
+            .chunks_exact(4)
+            .map(|entry| u32::from_le_bytes(entry.try_into().unwrap()))
+            .collect();
+
+        // The mini stream is the data of the root entry.
+        if let Some(root) = ole.entries.first() {
+            ole.mini_stream = ole.chain(
+                root.start_sector,
+                usize::try_from(root.size).unwrap_or(usize::MAX),
+            );
+        }
+
+        ole.set_paths();
+
+        Some(ole)
+    }
+
+    /// Returns the stream at the given path. The comparison is
+    /// case-insensitive, as in the compound file format.
+    pub fn stream(&self, path: &str) -> Option<&DirEntry> {
+        self.entries.iter().find(|entry| {
+            entry.entry_type == EntryType::Stream
+                && entry.path.eq_ignore_ascii_case(path)
+        })
+    }
+
+    /// Returns the data of a stream, truncated to `max_size` bytes.
+    pub fn read(&self, entry: &DirEntry, max_size: usize) -> Vec<u8> {
+        let size =
+            usize::try_from(entry.size).unwrap_or(usize::MAX).min(max_size);
+
+        if entry.size < self.mini_stream_cutoff
+            && entry.entry_type != EntryType::Root
+        {
+            self.mini_chain(entry.start_sector, size)
+        } else {
+            self.chain(entry.start_sector, size)
+        }
+    }
+
+    /// Returns the data in the sector with the given number.
+    fn sector(&self, sector: u32) -> Option<&'a [u8]> {
+        // Sector 0 starts right after the header, which occupies a sector
+        // with the same size as the others (at least 512 bytes).
+        let offset =
+            (sector as usize).checked_add(1)?.checked_mul(self.sector_size)?;
+        let data = self.data.get(offset..)?;
+        Some(&data[..data.len().min(self.sector_size)])
+    }
+
+    /// Returns the data in the chain of sectors that starts at `start`,
+    /// truncated to `max_size` bytes.
+    fn chain(&self, start: u32, max_size: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut sector = start;
+        let mut visited = HashSet::new();
+
+        while sector <= MAX_REG_SECT
+            && data.len() < max_size
+            && visited.insert(sector)
+        {
+            match self.sector(sector) {
+                Some(content) => data.extend(content),
+                None => break,
+            }
+            sector = match self.fat.get(sector as usize) {
+                Some(next) => *next,
+                None => break,
+            };
+        }
+
+        data.truncate(max_size);
+        data
+    }
+
+    /// Like [`Ole::chain`], but for chains of sectors in the mini stream.
+    fn mini_chain(&self, start: u32, max_size: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut sector = start;
+        let mut visited = HashSet::new();
+
+        while sector <= MAX_REG_SECT
+            && data.len() < max_size
+            && visited.insert(sector)
+        {
+            let offset = sector as usize * self.mini_sector_size;
+            match self.mini_stream.get(offset..offset + self.mini_sector_size)
+            {
+                Some(content) => data.extend(content),
+                None => break,
+            }
+            sector = match self.mini_fat.get(sector as usize) {
+                Some(next) => *next,
+                None => break,
+            };
+        }
+
+        data.truncate(max_size);
+        data
+    }
+
+    /// Sets the path of every entry reachable from the root entry.
+    ///
+    /// The children of a storage form a red-black tree, where the storage
+    /// points to the root of the tree, and each child points to its left
+    /// and right siblings.
+    fn set_paths(&mut self) {
+        let root_child = match self.entries.first() {
+            Some(root) if root.entry_type == EntryType::Root => root.child,
+            _ => return,
+        };
+
+        let mut visited = HashSet::new();
+        let mut pending = vec![(root_child, String::new())];
+
+        while let Some((id, parent_path)) = pending.pop() {
+            if id == NO_STREAM || !visited.insert(id) {
+                continue;
+            }
+            let entry = match self.entries.get_mut(id as usize) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            entry.path = if parent_path.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", parent_path, entry.name)
+            };
+            pending.push((entry.left, parent_path.clone()));
+            pending.push((entry.right, parent_path));
+            if entry.entry_type == EntryType::Storage {
+                pending.push((entry.child, entry.path.clone()));
+            }
+        }
+    }
+}
+
+impl DirEntry {
+    /// Parses a 128-byte directory entry.
+    fn parse(entry: &[u8]) -> Option<Self> {
+        let entry_type = match entry[0x42] {
+            1 => EntryType::Storage,
+            2 => EntryType::Stream,
+            5 => EntryType::Root,
+            _ => EntryType::Empty,
+        };
+
+        // The name length is in bytes, and includes the null terminator.
+        let name_len = (u16_at(entry, 0x40)? as usize).min(64);
+        let name = String::from_utf16_lossy(
+            &entry[..name_len.saturating_sub(2)]
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>(),
+        );
+
+        Some(Self {
+            name,
+            path: String::new(),
+            entry_type,
+            clsid: entry[0x50..0x60].try_into().unwrap(),
+            size: u64::from_le_bytes(entry[0x78..0x80].try_into().unwrap()),
+            start_sector: u32_at(entry, 0x74)?,
+            left: u32_at(entry, 0x44)?,
+            right: u32_at(entry, 0x48)?,
+            child: u32_at(entry, 0x4c)?,
+        })
+    }
+}
@@ -0,0 +1,645 @@
+/*! Minimal reader for PE (Portable Executable) files.
+
+This parses the headers, the section table and the data directories, and
+translates RVAs (relative virtual addresses) into file offsets. What's
+inside each data directory is left to the modules that need it, with the
+exception of the CLR header and the metadata root, which are used both by
+the `pe` and the `dotnet` modules.
+
+See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format
+*/
+
+use super::{u16_at, u32_at, u64_at};
+
+/// Indexes of the data directories in the optional header.
+pub(crate) const DIRECTORY_EXPORT: usize = 0;
+pub(crate) const DIRECTORY_IMPORT: usize = 1;
+pub(crate) const DIRECTORY_RESOURCE: usize = 2;
+pub(crate) const DIRECTORY_EXCEPTION: usize = 3;
+pub(crate) const DIRECTORY_SECURITY: usize = 4;
+pub(crate) const DIRECTORY_BASERELOC: usize = 5;
+pub(crate) const DIRECTORY_DEBUG: usize = 6;
+pub(crate) const DIRECTORY_TLS: usize = 9;
+pub(crate) const DIRECTORY_LOAD_CONFIG: usize = 10;
+pub(crate) const DIRECTORY_BOUND_IMPORT: usize = 11;
+pub(crate) const DIRECTORY_IAT: usize = 12;
+pub(crate) const DIRECTORY_DELAY_IMPORT: usize = 13;
+pub(crate) const DIRECTORY_CLR: usize = 14;
+
+/// Maximum number of data directories, some files claim to have more than
+/// the 16 defined by the format.
+const MAX_DIRECTORIES: usize = 16;
+
+/// Maximum number of sections, as enforced by the Windows loader.
+const MAX_SECTIONS: usize = 96;
+
+/// Magic number in the optional header of PE32+ files.
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// Maximum length of the null-terminated strings read by `string_at_rva`,
+/// like the names of imported and exported functions.
+const MAX_STRING_LEN: usize = 512;
+
+/// A section in the section table.
+pub(crate) struct Section<'a> {
+    /// The name as it appears in the section table, including any trailing
+    /// null characters.
+    pub raw_name: &'a [u8],
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_data_offset: u32,
+    pub raw_data_size: u32,
+    pub characteristics: u32,
+}
+
+/// A parsed PE file.
+pub(crate) struct Pe<'a> {
+    /// The whole file.
+    pub data: &'a [u8],
+    /// Offset of the "PE\0\0" signature, as indicated by `e_lfanew`.
+    pub pe_offset: usize,
+    pub machine: u16,
+    pub timestamp: u32,
+    pub characteristics: u16,
+    pub pointer_to_symbol_table: u32,
+    pub number_of_symbols: u32,
+    /// Number of sections, as indicated by the file header. The number of
+    /// entries in `sections` may be lower.
+    pub number_of_sections: u16,
+    /// Offset and size of the optional header.
+    pub optional_header_offset: usize,
+    pub optional_header_size: u16,
+    pub is_64bit: bool,
+    pub linker_version: (u8, u8),
+    pub entry_point: u32,
+    pub image_base: u64,
+    pub section_alignment: u32,
+    pub file_alignment: u32,
+    pub os_version: (u16, u16),
+    pub image_version: (u16, u16),
+    pub subsystem_version: (u16, u16),
+    pub size_of_image: u32,
+    pub size_of_headers: u32,
+    pub checksum: u32,
+    pub subsystem: u16,
+    pub dll_characteristics: u16,
+    /// Number of data directories, as indicated by the optional header.
+    /// The number of entries in `directories` may be lower.
+    pub number_of_rva_and_sizes: u32,
+    /// RVA (or file offset, for the security directory) and size of each
+    /// data directory.
+    pub directories: Vec<(u32, u32)>,
+    pub sections: Vec<Section<'a>>,
+}
+
+impl<'a> Pe<'a> {
+    /// Parses the headers of a PE file. Returns `None` if the data is not a
+    /// PE file, or is too truncated for the headers to be read.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if !data.starts_with(b"MZ") {
+            return None;
+        }
+
+        let pe_offset = u32_at(data, 0x3c)? as usize;
+
+        if data.get(pe_offset..pe_offset.checked_add(4)?)? != b"PE\0\0" {
+            return None;
+        }
+
+        let file_header = pe_offset + 4;
+        let optional_header_offset = file_header + 20;
+        let optional_header_size = u16_at(data, file_header + 16)?;
+        let magic = u16_at(data, optional_header_offset)?;
+        let is_64bit = magic == PE32_PLUS_MAGIC;
+
+        let oh = data.get(optional_header_offset..)?;
+
+        // Offsets of the fields that differ between PE32 and PE32+.
+        let (image_base, directories_offset, count_offset) = if is_64bit {
+            (u64_at(oh, 24)?, 112, 108)
+        } else {
+            (u32_at(oh, 28)?.into(), 96, 92)
+        };
+
+        let number_of_rva_and_sizes = u32_at(oh, count_offset)?;
+
+        let directories = (0..(number_of_rva_and_sizes as usize)
+            .min(MAX_DIRECTORIES))
+            .map_while(|i| {
+                let offset = directories_offset + i * 8;
+                Some((u32_at(oh, offset)?, u32_at(oh, offset + 4)?))
+            })
+            .collect();
+
+        let number_of_sections = u16_at(data, file_header + 2)?;
+        let section_table =
+            optional_header_offset + optional_header_size as usize;
+
+        let sections = data
+            .get(section_table..)
+            .unwrap_or_default()
+            .chunks_exact(40)
+            .take((number_of_sections as usize).min(MAX_SECTIONS))
+            .map(|entry| Section {
+                raw_name: &entry[0..8],
+                virtual_size: u32_at(entry, 8).unwrap(),
+                virtual_address: u32_at(entry, 12).unwrap(),
+                raw_data_size: u32_at(entry, 16).unwrap(),
+                raw_data_offset: u32_at(entry, 20).unwrap(),
+                characteristics: u32_at(entry, 36).unwrap(),
+            })
+            .collect();
+
+        Some(Self {
+            data,
+            pe_offset,
+            machine: u16_at(data, file_header)?,
+            number_of_sections,
+            timestamp: u32_at(data, file_header + 4)?,
+            pointer_to_symbol_table: u32_at(data, file_header + 8)?,
+            number_of_symbols: u32_at(data, file_header + 12)?,
+            characteristics: u16_at(data, file_header + 18)?,
+            optional_header_offset,
+            optional_header_size,
+            is_64bit,
+            linker_version: (*oh.get(2)?, *oh.get(3)?),
+            entry_point: u32_at(oh, 16)?,
+            image_base,
+            section_alignment: u32_at(oh, 32)?,
+            file_alignment: u32_at(oh, 36)?,
+            os_version: (u16_at(oh, 40)?, u16_at(oh, 42)?),
+            image_version: (u16_at(oh, 44)?, u16_at(oh, 46)?),
+            subsystem_version: (u16_at(oh, 48)?, u16_at(oh, 50)?),
+            size_of_image: u32_at(oh, 56)?,
+            size_of_headers: u32_at(oh, 60)?,
+            checksum: u32_at(oh, 64)?,
+            subsystem: u16_at(oh, 68)?,
+            dll_characteristics: u16_at(oh, 70)?,
+            number_of_rva_and_sizes,
+            directories,
+            sections,
+        })
+    }
+
+    /// Returns the RVA and size of a data directory, or `None` if the
+    /// directory doesn't exist or is empty.
+    pub fn directory(&self, index: usize) -> Option<(u32, u32)> {
+        self.directories
+            .get(index)
+            .copied()
+            .filter(|(rva, size)| *rva != 0 && *size != 0)
+    }
+
+    /// Returns the content of a data directory. If the directory exceeds
+    /// the end of the file, it's truncated.
+    ///
+    /// This doesn't work for the security directory, whose location is a
+    /// file offset instead of an RVA.
+    pub fn directory_data(&self, index: usize) -> Option<&'a [u8]> {
+        let (rva, size) = self.directory(index)?;
+        let start = self.rva_to_offset(rva)?;
+        let end = start.saturating_add(size as usize).min(self.data.len());
+        self.data.get(start..end)
+    }
+
+    /// Returns the data that starts at the given RVA and extends up to the
+    /// end of the file.
+    pub fn data_at_rva(&self, rva: u32) -> Option<&'a [u8]> {
+        self.data.get(self.rva_to_offset(rva)?..)
+    }
+
+    /// Returns the null-terminated string at the given RVA, without the
+    /// null character. Returns `None` if the string is not terminated, or
+    /// is too long.
+    pub fn string_at_rva(&self, rva: u32) -> Option<String> {
+        let data = self.data_at_rva(rva)?;
+        let len = data.iter().take(MAX_STRING_LEN).position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&data[..len]).into_owned())
+    }
+
+    /// Translates an RVA into a file offset. Returns `None` if the RVA
+    /// doesn't correspond to any data in the file.
+    ///
+    /// The section that contains the RVA is the one with the highest
+    /// virtual address that is not above the RVA, as the Windows loader
+    /// maps sections in order and later sections overwrite earlier ones.
+    /// Like the loader, raw data offsets are rounded down to a multiple of
+    /// 512 bytes when the file alignment allows it.
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        let section = self
+            .sections
+            .iter()
+            .filter(|s| {
+                let size = s.virtual_size.max(s.raw_data_size);
+                s.virtual_address <= rva
+                    && rva - s.virtual_address < size.max(1)
+            })
+            .max_by_key(|s| s.virtual_address);
+
+        let offset = match section {
+            Some(section) => {
+                let raw_offset = if self.file_alignment >= 0x200 {
+                    section.raw_data_offset & !0x1ff
+                } else {
+                    section.raw_data_offset
+                };
+                raw_offset as usize + (rva - section.virtual_address) as usize
+            }
+            // RVAs that are not inside any section are inside the headers,
+            // which are mapped as they are in the file.
+            None if rva
+                < self.size_of_headers.max(self.first_section_rva()) =>
+            {
+                rva as usize
+            }
+            None => return None,
+        };
+
+        (offset < self.data.len()).then_some(offset)
+    }
+
+    /// Translates a virtual address into a file offset.
+    pub fn va_to_offset(&self, va: u64) -> Option<usize> {
+        let rva = va.checked_sub(self.image_base)?;
+        self.rva_to_offset(rva.try_into().ok()?)
+    }
+
+    /// Returns the file offset of the entry point, if any.
+    pub fn entry_point_offset(&self) -> Option<usize> {
+        self.rva_to_offset(self.entry_point)
+    }
+
+    /// Returns true if the data looks like an image loaded in memory, for
+    /// instance, one dumped from a process, where each section is at its
+    /// RVA instead of its raw data offset.
+    ///
+    /// In a loaded image the gap between the headers and the first section
+    /// is filled with zeroes, while in a file that's where the raw data of
+    /// the first section usually is.
+    pub fn is_mapped(&self) -> bool {
+        if self.data.len() < self.size_of_image as usize {
+            return false;
+        }
+
+        let first = match self
+            .sections
+            .iter()
+            .filter(|s| s.raw_data_size > 0)
+            .min_by_key(|s| s.virtual_address)
+        {
+            Some(first) => first,
+            None => return false,
+        };
+
+        let raw_start = first.raw_data_offset as usize;
+        let virtual_start = first.virtual_address as usize;
+
+        if raw_start >= virtual_start {
+            return false;
+        }
+
+        let is_zero = |start: usize, end: usize| {
+            self.data
+                .get(start..end.min(self.data.len()))
+                .map_or(false, |data| data.iter().all(|b| *b == 0))
+        };
+
+        let size = first.raw_data_size as usize;
+
+        is_zero(raw_start, virtual_start.min(raw_start + size))
+            && !is_zero(virtual_start, virtual_start + size)
+    }
+
+    /// Returns the CLR header of .NET files, `None` for native ones.
+    pub fn clr_header(&self) -> Option<ClrHeader> {
+        let header = self.directory_data(DIRECTORY_CLR)?;
+
+        Some(ClrHeader {
+            major_runtime_version: u16_at(header, 4)?,
+            minor_runtime_version: u16_at(header, 6)?,
+            metadata: (u32_at(header, 8)?, u32_at(header, 12)?),
+            flags: u32_at(header, 16)?,
+            entry_point_token: u32_at(header, 20)?,
+            resources: (u32_at(header, 24)?, u32_at(header, 28)?),
+            strong_name_signature: (u32_at(header, 32)?, u32_at(header, 36)?),
+        })
+    }
+
+    /// Returns the metadata root of .NET files.
+    pub fn metadata_root(
+        &self,
+        clr_header: &ClrHeader,
+    ) -> Option<MetadataRoot<'a>> {
+        let (rva, size) = clr_header.metadata;
+        let start = self.rva_to_offset(rva)?;
+        let end = start.saturating_add(size as usize).min(self.data.len());
+        MetadataRoot::parse(self.data.get(start..end)?)
+    }
+
+    /// Returns the lowest virtual address among all sections.
+    fn first_section_rva(&self) -> u32 {
+        self.sections
+            .iter()
+            .map(|s| s.virtual_address)
+            .min()
+            .unwrap_or(u32::MAX)
+    }
+}
+
+impl Section<'_> {
+    /// Returns the section's name, without the trailing null characters.
+    pub fn name(&self) -> &[u8] {
+        let len = self
+            .raw_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(self.raw_name.len());
+        &self.raw_name[..len]
+    }
+}
+
+/// The CLR header (IMAGE_COR20_HEADER) of .NET files.
+pub(crate) struct ClrHeader {
+    pub major_runtime_version: u16,
+    pub minor_runtime_version: u16,
+    /// RVA and size of the metadata.
+    pub metadata: (u32, u32),
+    pub flags: u32,
+    pub entry_point_token: u32,
+    /// RVA and size of the managed resources.
+    pub resources: (u32, u32),
+    pub strong_name_signature: (u32, u32),
+}
+
+/// Flags in [`ClrHeader::flags`].
+pub(crate) const COMIMAGE_FLAGS_ILONLY: u32 = 0x1;
+pub(crate) const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x2;
+pub(crate) const COMIMAGE_FLAGS_STRONGNAMESIGNED: u32 = 0x8;
+pub(crate) const COMIMAGE_FLAGS_NATIVE_ENTRYPOINT: u32 = 0x10;
+pub(crate) const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x20000;
+
+/// The metadata root of .NET files, which contains the metadata version
+/// and the location of the metadata streams (#~, #Strings, #US, etc).
+pub(crate) struct MetadataRoot<'a> {
+    /// Version of the runtime the assembly was built for (e.g:
+    /// "v4.0.30319").
+    pub version: String,
+    pub streams: Vec<MetadataStream<'a>>,
+}
+
+/// A stream in the metadata of .NET files.
+pub(crate) struct MetadataStream<'a> {
+    pub name: &'a [u8],
+    /// Offset of the stream relative to the metadata root.
+    pub offset: u32,
+    /// Size of the stream, as indicated by the stream header.
+    pub size: u32,
+    /// Content of the stream. Streams that exceed the end of the metadata
+    /// are truncated.
+    pub content: &'a [u8],
+}
+
+impl<'a> MetadataRoot<'a> {
+    /// Maximum number of streams. Valid files have 5 at most, but some
+    /// obfuscators add fake ones.
+    const MAX_STREAMS: usize = 64;
+
+    fn parse(metadata: &'a [u8]) -> Option<Self> {
+        if !metadata.starts_with(b"BSJB") {
+            return None;
+        }
+
+        let version_len = u32_at(metadata, 12)? as usize;
+        let version = metadata.get(16..16_usize.checked_add(version_len)?)?;
+        let version = version.split(|b| *b == 0).next().unwrap_or_default();
+
+        let mut offset = 16 + version_len;
+        let num_streams = u16_at(metadata, offset + 2)? as usize;
+        let mut streams = Vec::new();
+
+        offset += 4;
+
+        for _ in 0..num_streams.min(Self::MAX_STREAMS) {
+            let (stream_offset, stream_size) =
+                match (u32_at(metadata, offset), u32_at(metadata, offset + 4))
+                {
+                    (Some(o), Some(s)) => (o, s),
+                    _ => break,
+                };
+
+            // The name is null-terminated and padded to a multiple of 4
+            // bytes, and can't be longer than 32 bytes.
+            let name = match metadata.get(offset + 8..) {
+                Some(name) => name,
+                None => break,
+            };
+            let name_len = match name.iter().take(32).position(|b| *b == 0) {
+                Some(len) => len,
+                None => break,
+            };
+
+            let content = metadata
+                .get(stream_offset as usize..)
+                .map(|content| {
+                    &content[..(stream_size as usize).min(content.len())]
+                })
+                .unwrap_or_default();
+
+            streams.push(MetadataStream {
+                name: &name[..name_len],
+                offset: stream_offset,
+                size: stream_size,
+                content,
+            });
+
+            offset += 8 + (name_len + 4) / 4 * 4;
+        }
+
+        Some(Self {
+            version: String::from_utf8_lossy(version).into_owned(),
+            streams,
+        })
+    }
+
+    /// Returns the content of the stream with the given name. If there
+    /// are multiple streams with the same name the first one is returned.
+    pub fn stream(&self, name: &[u8]) -> Option<&'a [u8]> {
+        self.streams
+            .iter()
+            .find(|stream| stream.name == name)
+            .map(|stream| stream.content)
+    }
+}
+
+#[cfg(test)]
+pub(crate) struct TestSection<'a> {
+    pub name: &'a [u8],
+    pub virtual_address: u32,
+    /// Size of the section in memory. If lower than the size of the
+    /// content, the content's size is used instead.
+    pub virtual_size: u32,
+    pub content: Vec<u8>,
+    pub characteristics: u32,
+}
+
+#[cfg(test)]
+impl<'a> TestSection<'a> {
+    /// Returns a readable section with the given content.
+    pub fn new(
+        name: &'a [u8],
+        virtual_address: u32,
+        content: Vec<u8>,
+    ) -> Self {
+        Self {
+            name,
+            virtual_address,
+            virtual_size: 0,
+            content,
+            characteristics: 0x40000040,
+        }
+    }
+}
+
+/// A PE file built for testing modules that parse PE files.
+#[cfg(test)]
+pub(crate) struct TestPe<'a> {
+    pub machine: u16,
+    pub is_64bit: bool,
+    pub timestamp: u32,
+    pub characteristics: u16,
+    pub entry_point: u32,
+    pub subsystem: u16,
+    pub dll_characteristics: u16,
+    /// Content of the DOS stub, which goes between the DOS header and the
+    /// PE signature.
+    pub dos_stub: Vec<u8>,
+    /// RVA and size of each data directory, indexed by the directory's
+    /// position in the optional header.
+    pub directories: Vec<(usize, u32, u32)>,
+    pub sections: Vec<TestSection<'a>>,
+    /// Data appended after the last section.
+    pub overlay: Vec<u8>,
+}
+
+#[cfg(test)]
+impl Default for TestPe<'_> {
+    fn default() -> Self {
+        Self {
+            machine: 0x14c,
+            is_64bit: false,
+            timestamp: 0,
+            characteristics: 0x102,
+            entry_point: 0,
+            subsystem: 2,
+            dll_characteristics: 0,
+            dos_stub: Vec::new(),
+            directories: Vec::new(),
+            sections: Vec::new(),
+            overlay: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl TestPe<'_> {
+    pub const FILE_ALIGNMENT: u32 = 0x200;
+    pub const SECTION_ALIGNMENT: u32 = 0x1000;
+
+    /// Offset of the first section's raw data, which is also the size of
+    /// the headers.
+    pub const HEADERS_SIZE: u32 = 0x400;
+
+    /// Builds the file. The raw data of each section is placed after the
+    /// previous one's, aligned to [`Self::FILE_ALIGNMENT`].
+    pub fn build(&self) -> Vec<u8> {
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x3c, 0);
+        let pe_offset = 0x40 + self.dos_stub.len() as u32;
+        pe.extend(pe_offset.to_le_bytes());
+        pe.extend(&self.dos_stub);
+
+        let optional_header_size: u16 = if self.is_64bit { 240 } else { 224 };
+
+        pe.extend(b"PE\0\0");
+        pe.extend(self.machine.to_le_bytes());
+        pe.extend((self.sections.len() as u16).to_le_bytes());
+        pe.extend(self.timestamp.to_le_bytes());
+        pe.extend([0; 8]);
+        pe.extend(optional_header_size.to_le_bytes());
+        pe.extend(self.characteristics.to_le_bytes());
+
+        let size_of_image = self
+            .sections
+            .iter()
+            .map(|s| {
+                let size = s.virtual_size.max(s.content.len() as u32);
+                s.virtual_address + size
+            })
+            .max()
+            .unwrap_or(Self::HEADERS_SIZE);
+
+        let mut oh = Vec::new();
+        oh.extend(if self.is_64bit { 0x20b_u16 } else { 0x10b }.to_le_bytes());
+        oh.extend([14, 0]);
+        oh.extend([0; 12]);
+        oh.extend(self.entry_point.to_le_bytes());
+        oh.extend(0x1000_u32.to_le_bytes());
+        if self.is_64bit {
+            oh.extend(0x140000000_u64.to_le_bytes());
+        } else {
+            oh.extend(0_u32.to_le_bytes());
+            oh.extend(0x400000_u32.to_le_bytes());
+        }
+        oh.extend(Self::SECTION_ALIGNMENT.to_le_bytes());
+        oh.extend(Self::FILE_ALIGNMENT.to_le_bytes());
+        for version in [6_u16, 0, 0, 0, 6, 0] {
+            oh.extend(version.to_le_bytes());
+        }
+        oh.extend([0; 4]);
+        oh.extend(size_of_image.to_le_bytes());
+        oh.extend(Self::HEADERS_SIZE.to_le_bytes());
+        oh.extend([0; 4]);
+        oh.extend(self.subsystem.to_le_bytes());
+        oh.extend(self.dll_characteristics.to_le_bytes());
+        // Stack and heap sizes, and loader flags.
+        oh.resize(if self.is_64bit { 108 } else { 92 }, 0);
+        oh.extend(16_u32.to_le_bytes());
+        let mut directories = [(0_u32, 0_u32); 16];
+        for (index, rva, size) in &self.directories {
+            directories[*index] = (*rva, *size);
+        }
+        for (rva, size) in directories {
+            oh.extend(rva.to_le_bytes());
+            oh.extend(size.to_le_bytes());
+        }
+        pe.extend(oh);
+
+        let mut raw_data = Vec::new();
+
+        for section in &self.sections {
+            let offset = Self::HEADERS_SIZE + raw_data.len() as u32;
+            let raw_size = section.content.len() as u32;
+            let mut name = section.name.to_vec();
+            name.resize(8, 0);
+            pe.extend(name);
+            pe.extend(section.virtual_size.max(raw_size).to_le_bytes());
+            pe.extend(section.virtual_address.to_le_bytes());
+            pe.extend(raw_size.to_le_bytes());
+            pe.extend(offset.to_le_bytes());
+            pe.extend([0; 12]);
+            pe.extend(section.characteristics.to_le_bytes());
+
+            raw_data.extend(&section.content);
+            let alignment = Self::FILE_ALIGNMENT as usize;
+            let aligned = (raw_data.len() + alignment - 1) / alignment;
+            raw_data.resize(aligned * alignment, 0);
+        }
+
+        assert!(pe.len() <= Self::HEADERS_SIZE as usize);
+
+        pe.resize(Self::HEADERS_SIZE as usize, 0);
+        pe.extend(raw_data);
+        pe.extend(&self.overlay);
+        pe
+    }
+}
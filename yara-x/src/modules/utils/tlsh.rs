@@ -0,0 +1,270 @@
+/*! Implementation of TLSH, the Trend Micro Locality Sensitive Hash.
+
+This implements the default variant of TLSH: 128 buckets and a 1-byte
+checksum, producing digests of 72 characters that start with the `T1`
+version prefix. The distance between digests is computed including the
+data length, like `tlsh.diff` does.
+*/
+
+const MIN_DATA_LENGTH: usize = 50;
+const EFF_BUCKETS: usize = 128;
+const CODE_SIZE: usize = EFF_BUCKETS / 4;
+
+/// Pearson's permutation table used by TLSH.
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14,
+    197, 213, 181, 161, 85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200, 110,
+    177, 104, 103, 141, 253, 255, 50, 77, 101, 81, 18, 45, 96, 31, 222, 25,
+    107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227, 149, 235, 97,
+    234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174,
+    169, 211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243, 132,
+    56, 148, 75, 128, 133, 158, 100, 130, 126, 91, 13, 153, 246, 216, 219,
+    119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92, 32, 136, 114, 52, 10, 138,
+    30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152, 170, 7,
+    115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131, 125, 173,
+    15, 238, 79, 95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123, 118, 73,
+    2, 157, 46, 116, 9, 145, 134, 228, 207, 212, 202, 215, 69, 229, 27, 188,
+    67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39, 203, 233, 40,
+    186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76, 140,
+    36, 210, 172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120, 51,
+    65, 28, 144, 254, 221, 93, 189, 194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+/// Maps a triplet of bytes to a bucket using Pearson's hashing.
+#[inline]
+fn pearson(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let mut h = V_TABLE[salt as usize];
+    h = V_TABLE[(h ^ i) as usize];
+    h = V_TABLE[(h ^ j) as usize];
+    V_TABLE[(h ^ k) as usize]
+}
+
+/// Computes the TLSH digest for `data`.
+///
+/// Returns `None` if the data is shorter than 50 bytes, or if it doesn't
+/// have enough variability. This is the case when at least half of the
+/// buckets are empty.
+pub(crate) fn digest(data: &[u8]) -> Option<String> {
+    if data.len() < MIN_DATA_LENGTH {
+        return None;
+    }
+
+    let mut buckets = [0_u32; 256];
+    let mut checksum = 0_u8;
+
+    // Each window contains the current byte and the previous four bytes,
+    // `c0` is the current byte, `c1` the previous one, and so on.
+    for window in data.windows(5) {
+        let (c0, c1, c2, c3, c4) =
+            (window[4], window[3], window[2], window[1], window[0]);
+
+        checksum = pearson(0, c0, c1, checksum);
+
+        buckets[pearson(2, c0, c1, c2) as usize] += 1;
+        buckets[pearson(3, c0, c1, c3) as usize] += 1;
+        buckets[pearson(5, c0, c2, c3) as usize] += 1;
+        buckets[pearson(7, c0, c2, c4) as usize] += 1;
+        buckets[pearson(11, c0, c1, c4) as usize] += 1;
+        buckets[pearson(13, c0, c3, c4) as usize] += 1;
+    }
+
+    let buckets = &buckets[..EFF_BUCKETS];
+
+    if buckets.iter().filter(|b| **b > 0).count() <= EFF_BUCKETS / 2 {
+        return None;
+    }
+
+    let mut sorted = buckets.to_vec();
+    sorted.sort_unstable();
+
+    let q1 = sorted[CODE_SIZE - 1];
+    let q2 = sorted[2 * CODE_SIZE - 1];
+    let q3 = sorted[3 * CODE_SIZE - 1];
+
+    if q3 == 0 {
+        return None;
+    }
+
+    let q1_ratio = (q1.wrapping_mul(100) as f32 / q3 as f32) as u32 % 16;
+    let q2_ratio = (q2.wrapping_mul(100) as f32 / q3 as f32) as u32 % 16;
+
+    let mut digest = String::with_capacity(2 + 2 * (3 + CODE_SIZE));
+
+    digest.push_str("T1");
+    push_hex(&mut digest, swap_nibbles(checksum));
+    push_hex(&mut digest, swap_nibbles(l_capturing(data.len())));
+    push_hex(&mut digest, (q1_ratio << 4 | q2_ratio) as u8);
+
+    // Each byte in the body encodes the quartile of four buckets, the body
+    // starts with the last buckets.
+    for chunk in buckets.chunks(4).rev() {
+        let mut h = 0_u8;
+        for (j, count) in chunk.iter().enumerate() {
+            let quartile = if *count > q3 {
+                3
+            } else if *count > q2 {
+                2
+            } else if *count > q1 {
+                1
+            } else {
+                0
+            };
+            h |= quartile << (j * 2);
+        }
+        push_hex(&mut digest, h);
+    }
+
+    Some(digest)
+}
+
+/// Returns the distance between two TLSH digests.
+///
+/// Returns `None` if any of the digests is not valid.
+pub(crate) fn diff(digest1: &str, digest2: &str) -> Option<i64> {
+    let a = parse(digest1)?;
+    let b = parse(digest2)?;
+
+    let mut diff = 0;
+
+    // Byte 0 is the checksum, byte 1 the length (with the nibbles swapped)
+    // and byte 2 the quartile ratios.
+    let l_diff = mod_diff(swap_nibbles(a[1]), swap_nibbles(b[1]), 256);
+
+    diff += if l_diff <= 1 { l_diff } else { l_diff * 12 };
+
+    let q1_diff = mod_diff(a[2] >> 4, b[2] >> 4, 16);
+    let q2_diff = mod_diff(a[2] & 0xf, b[2] & 0xf, 16);
+
+    for q_diff in [q1_diff, q2_diff] {
+        diff += if q_diff <= 1 { q_diff } else { (q_diff - 1) * 12 };
+    }
+
+    if a[0] != b[0] {
+        diff += 1;
+    }
+
+    for (x, y) in a[3..].iter().zip(&b[3..]) {
+        for shift in (0..8).step_by(2) {
+            let d = ((x >> shift) & 3).abs_diff((y >> shift) & 3) as i64;
+            diff += if d == 3 { 6 } else { d };
+        }
+    }
+
+    Some(diff)
+}
+
+/// Parses a TLSH digest, with or without the `T1` prefix, and returns its
+/// bytes.
+fn parse(digest: &str) -> Option<Vec<u8>> {
+    let digest = digest
+        .strip_prefix("T1")
+        .or_else(|| digest.strip_prefix("t1"))
+        .unwrap_or(digest);
+
+    if digest.len() != 2 * (3 + CODE_SIZE) || !digest.is_ascii() {
+        return None;
+    }
+
+    (0..digest.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digest[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Encodes the data length in a single byte, using a logarithmic scale.
+fn l_capturing(len: usize) -> u8 {
+    const LOG_1_5: f64 = 0.4054651;
+    const LOG_1_3: f64 = 0.26236426;
+    const LOG_1_1: f64 = 0.095310180;
+
+    let log = (len as f32 as f64).ln();
+
+    let l = if len <= 656 {
+        (log / LOG_1_5).floor()
+    } else if len <= 3199 {
+        (log / LOG_1_3 - 8.72777).floor()
+    } else {
+        (log / LOG_1_1 - 62.5472).floor()
+    };
+
+    (l as i64 & 0xff) as u8
+}
+
+fn mod_diff(x: u8, y: u8, range: i64) -> i64 {
+    let d = (x as i64 - y as i64).abs();
+    d.min(range - d)
+}
+
+#[inline]
+fn swap_nibbles(b: u8) -> u8 {
+    b.rotate_left(4)
+}
+
+fn push_hex(s: &mut String, b: u8) {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    s.push(DIGITS[(b >> 4) as usize] as char);
+    s.push(DIGITS[(b & 0xf) as usize] as char);
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{diff, digest, l_capturing, V_TABLE};
+
+    #[test]
+    fn tlsh_table_is_permutation() {
+        let mut table = V_TABLE.to_vec();
+        table.sort_unstable();
+        assert!(table.iter().enumerate().all(|(i, v)| i == *v as usize));
+    }
+
+    #[test]
+    fn tlsh_digest() {
+        assert_eq!(digest(b"too short"), None);
+        assert_eq!(digest(&[0; 1024]), None);
+
+        assert_eq!(
+            digest(
+                b"The quick brown fox jumps over the lazy dog. "
+                    .repeat(30)
+                    .as_slice()
+            ),
+            Some(
+                "T15321024A311C1794658A1888438D95B2D2C9C910612114116570604219482359CD8551"
+                    .to_string()
+            )
+        );
+
+        let data: Vec<u8> = (0..1024_u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+
+        let d = digest(&data).unwrap();
+
+        assert_eq!(d.len(), 72);
+        assert!(d.starts_with("T1"));
+        assert_eq!(diff(&d, &d), Some(0));
+        assert_eq!(diff(&d, &d[2..]), Some(0));
+    }
+
+    #[test]
+    fn tlsh_diff() {
+        let a = format!("T1{}", "00".repeat(35));
+        let b = format!("T1013000{}", "03".repeat(32));
+
+        // The length differs by 3 (diff 36), the checksum differs (diff 1),
+        // and each of the 32 body bytes has a pair with a difference of 3
+        // (diff 6).
+        assert_eq!(diff(&a, &b), Some(36 + 1 + 32 * 6));
+        assert_eq!(diff(&a, "T1"), None);
+        assert_eq!(diff(&a, &format!("T1{}", "zz".repeat(35))), None);
+    }
+
+    #[test]
+    fn tlsh_l_capturing() {
+        assert_eq!(l_capturing(50), 9);
+        assert_eq!(l_capturing(1000), 17);
+        assert_eq!(l_capturing(100_000), 58);
+    }
+}
@@ -0,0 +1,244 @@
+/*! Reader for ZIP archives.
+
+The reader obtains the list of entries from the archive's central directory,
+and can decompress entries that are stored without compression or compressed
+with the DEFLATE algorithm. ZIP64 archives are not supported.
+*/
+
+use crate::modules::utils::inflate::inflate;
+
+/// An entry in a ZIP archive, as described by the central directory.
+pub(crate) struct Entry<'a> {
+    pub name: &'a [u8],
+    pub comment: &'a [u8],
+    pub flags: u16,
+    pub compression_method: u16,
+    /// Last modification time in MS-DOS format.
+    pub mod_time: u16,
+    /// Last modification date in MS-DOS format.
+    pub mod_date: u16,
+    pub crc32: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub local_header_offset: u32,
+}
+
+impl Entry<'_> {
+    /// Returns true if the entry is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+}
+
+/// A ZIP archive.
+pub(crate) struct Archive<'a> {
+    data: &'a [u8],
+    /// Offset where the central directory starts.
+    pub central_directory_offset: u32,
+    pub comment: &'a [u8],
+    pub entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Archive<'a> {
+    /// Parses a ZIP archive. Returns `None` if the data is not a ZIP
+    /// archive, or its central directory can't be found.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        // The end of central directory record is at the end of the file,
+        // followed by a comment of up to 65535 bytes.
+        let search_start = data.len().saturating_sub(22 + 0xffff);
+        let eocd_offset = data[search_start..]
+            .windows(4)
+            .rposition(|window| window == b"PK\x05\x06")?
+            + search_start;
+
+        let eocd = data.get(eocd_offset..eocd_offset + 22)?;
+        let num_entries = u16_at(eocd, 10);
+        let central_directory_offset = u32_at(eocd, 16);
+        let comment_len = u16_at(eocd, 20) as usize;
+        let comment = data
+            .get(eocd_offset + 22..eocd_offset + 22 + comment_len)
+            .unwrap_or_default();
+
+        let mut entries = Vec::new();
+        let mut pos = central_directory_offset as usize;
+
+        for _ in 0..num_entries {
+            let header = match data.get(pos..pos + 46) {
+                Some(header) if header.starts_with(b"PK\x01\x02") => header,
+                _ => break,
+            };
+
+            let name_len = u16_at(header, 28) as usize;
+            let extra_len = u16_at(header, 30) as usize;
+            let comment_len = u16_at(header, 32) as usize;
+
+            let name_start = pos + 46;
+            let comment_start = name_start + name_len + extra_len;
+
+            let name = match data.get(name_start..name_start + name_len) {
+                Some(name) => name,
+                None => break,
+            };
+
+            entries.push(Entry {
+                name,
+                comment: data
+                    .get(comment_start..comment_start + comment_len)
+                    .unwrap_or_default(),
+                flags: u16_at(header, 8),
+                compression_method: u16_at(header, 10),
+                mod_time: u16_at(header, 12),
+                mod_date: u16_at(header, 14),
+                crc32: u32_at(header, 16),
+                compressed_size: u32_at(header, 20),
+                uncompressed_size: u32_at(header, 24),
+                local_header_offset: u32_at(header, 42),
+            });
+
+            pos = comment_start + comment_len;
+        }
+
+        Some(Self { data, central_directory_offset, comment, entries })
+    }
+
+    /// Returns the entry with the given name.
+    pub fn entry(&self, name: &[u8]) -> Option<&Entry<'a>> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Returns the decompressed content of an entry, truncated to
+    /// `max_size` bytes. Returns `None` if the entry is encrypted, uses an
+    /// unsupported compression method, or its data is corrupt.
+    pub fn read(&self, entry: &Entry, max_size: usize) -> Option<Vec<u8>> {
+        if entry.is_encrypted() {
+            return None;
+        }
+
+        let offset = entry.local_header_offset as usize;
+        let header = self.data.get(offset..offset + 30)?;
+
+        if !header.starts_with(b"PK\x03\x04") {
+            return None;
+        }
+
+        // The size of the name and extra field in the local header may be
+        // different from the ones in the central directory.
+        let data_start = offset
+            + 30
+            + u16_at(header, 26) as usize
+            + u16_at(header, 28) as usize;
+
+        let compressed = self
+            .data
+            .get(data_start..)?
+            .get(..entry.compressed_size as usize)?;
+
+        match entry.compression_method {
+            0 => Some(compressed[..compressed.len().min(max_size)].to_vec()),
+            8 => inflate(compressed, max_size).ok(),
+            _ => None,
+        }
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// An entry in a ZIP archive created with [`build_zip`].
+#[cfg(test)]
+pub(crate) struct TestEntry<'a> {
+    pub name: &'a str,
+    pub flags: u16,
+    pub compression_method: u16,
+    pub mod_time: u16,
+    pub mod_date: u16,
+    pub content: &'a [u8],
+    pub uncompressed_size: u32,
+    pub comment: &'a str,
+}
+
+#[cfg(test)]
+impl<'a> TestEntry<'a> {
+    /// Returns an entry for a file stored without compression.
+    pub fn stored(name: &'a str, content: &'a [u8]) -> Self {
+        Self {
+            name,
+            flags: 0,
+            compression_method: 0,
+            mod_time: 0,
+            mod_date: 0,
+            content,
+            uncompressed_size: content.len() as u32,
+            comment: "",
+        }
+    }
+}
+
+/// Builds a ZIP archive with the given entries and comment, for testing
+/// modules that parse ZIP archives.
+///
+/// The content of each entry is stored as is, no matter its compression
+/// method. `extra` is inserted right before the central directory, which
+/// is where APK files have their signing block, for instance.
+#[cfg(test)]
+pub(crate) fn build_zip(
+    entries: &[TestEntry],
+    extra: &[u8],
+    comment: &str,
+) -> Vec<u8> {
+    let mut zip = Vec::new();
+    let mut central_directory = Vec::new();
+
+    for entry in entries {
+        let offset = zip.len() as u32;
+
+        let fields = [
+            &20_u16.to_le_bytes()[..],
+            &entry.flags.to_le_bytes(),
+            &entry.compression_method.to_le_bytes(),
+            &entry.mod_time.to_le_bytes(),
+            &entry.mod_date.to_le_bytes(),
+            &crc32fast::hash(entry.content).to_le_bytes(),
+            &(entry.content.len() as u32).to_le_bytes(),
+            &entry.uncompressed_size.to_le_bytes(),
+            &(entry.name.len() as u16).to_le_bytes(),
+            &0_u16.to_le_bytes(),
+        ]
+        .concat();
+
+        zip.extend(b"PK\x03\x04");
+        zip.extend(&fields);
+        zip.extend(entry.name.as_bytes());
+        zip.extend(entry.content);
+
+        central_directory.extend(b"PK\x01\x02");
+        central_directory.extend(20_u16.to_le_bytes());
+        central_directory.extend(&fields);
+        central_directory.extend((entry.comment.len() as u16).to_le_bytes());
+        // Disk number and file attributes.
+        central_directory.extend([0; 8]);
+        central_directory.extend(offset.to_le_bytes());
+        central_directory.extend(entry.name.as_bytes());
+        central_directory.extend(entry.comment.as_bytes());
+    }
+
+    zip.extend(extra);
+
+    let central_directory_offset = zip.len() as u32;
+
+    zip.extend(&central_directory);
+    zip.extend(b"PK\x05\x06");
+    zip.extend([0; 4]);
+    zip.extend((entries.len() as u16).to_le_bytes());
+    zip.extend((entries.len() as u16).to_le_bytes());
+    zip.extend((central_directory.len() as u32).to_le_bytes());
+    zip.extend(central_directory_offset.to_le_bytes());
+    zip.extend((comment.len() as u16).to_le_bytes());
+    zip.extend(comment.as_bytes());
+    zip
+}
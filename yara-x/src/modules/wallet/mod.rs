@@ -0,0 +1,306 @@
+/*! YARA module for cryptocurrency wallet artifacts.
+
+The module recognizes the files that information stealers look for when
+stealing cryptocurrency wallets, exposing their relevant fields:
+
+* Bitcoin Core `wallet.dat` files, which are Berkeley DB databases. The
+  module doesn't walk the database's pages, it finds the wallet records by
+  their keys, which start with the record type as a length-prefixed string
+  (e.g: `\x04name` for entries in the address book, `\x03key` for private
+  keys).
+* Ethereum keystore files, which are JSON documents with an encrypted
+  private key.
+* Mnemonic phrases (BIP-39 seeds) stored as text, which are 12 to 24
+  lowercase words of 3 to 8 letters. The words are not checked against the
+  BIP-39 word lists, phrases are identified by their shape only.
+*/
+
+use std::collections::HashSet;
+
+use bstr::ByteSlice;
+use serde_json::Value;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::wallet::*;
+
+/// Magic number of Berkeley DB B-tree databases, at offset 12.
+const BTREE_MAGIC: u32 = 0x00053162;
+
+/// Maximum size of keystore files.
+const MAX_KEYSTORE_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of addresses and mnemonic phrases.
+const MAX_ENTRIES: usize = 16384;
+
+/// Keys of the records in `wallet.dat` files.
+const NAME: &[u8] = b"\x04name";
+const KEY: &[u8] = b"\x03key";
+const CKEY: &[u8] = b"\x04ckey";
+const MKEY: &[u8] = b"\x04mkey";
+const VERSION: &[u8] = b"\x07version";
+const HDCHAIN: &[u8] = b"\x07hdchain";
+
+/// Number of words in mnemonic phrases.
+const MNEMONIC_LENGTHS: [usize; 5] = [12, 15, 18, 21, 24];
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Wallet {
+    let data = ctx.scanned_data();
+    let mut wallet = Wallet::new();
+
+    let is_wallet_dat = is_berkeley_db(data)
+        && [NAME, KEY, CKEY, MKEY, VERSION, HDCHAIN]
+            .iter()
+            .any(|record| data.find(record).is_some());
+
+    wallet.set_is_wallet_dat(is_wallet_dat);
+
+    if is_wallet_dat {
+        wallet.set_is_encrypted(data.find(MKEY).is_some());
+        wallet.set_num_keys(data.find_iter(KEY).count() as i64);
+        wallet.set_num_encrypted_keys(data.find_iter(CKEY).count() as i64);
+
+        // The key of address book entries is followed by the address, as
+        // a string prefixed by its length.
+        for offset in data.find_iter(NAME) {
+            let address = data
+                .get(offset + NAME.len()..)
+                .and_then(|s| s.get(1..=*s.first()? as usize))
+                .filter(|s| is_address(s));
+
+            let address = match address {
+                Some(address) => String::from_utf8_lossy(address).into_owned(),
+                None => continue,
+            };
+
+            if wallet.addresses.contains(&address) {
+                continue;
+            }
+            if wallet.addresses.len() == MAX_ENTRIES {
+                wallet.set_addresses_truncated(true);
+                break;
+            }
+            wallet.addresses.push(address);
+        }
+    }
+
+    let keystore = keystore(data);
+
+    wallet.set_is_keystore(keystore.is_some());
+    wallet.keystore = keystore.into();
+
+    let (mnemonics, truncated) = mnemonics(data);
+
+    wallet.mnemonics = mnemonics;
+
+    if truncated {
+        wallet.set_mnemonics_truncated(true);
+    }
+
+    wallet
+}
+
+/// Returns true if the data starts with the metadata page of a Berkeley
+/// DB B-tree database, in any byte order.
+fn is_berkeley_db(data: &[u8]) -> bool {
+    match data.get(12..16) {
+        Some(magic) => {
+            let magic = magic.try_into().unwrap();
+            u32::from_le_bytes(magic) == BTREE_MAGIC
+                || u32::from_be_bytes(magic) == BTREE_MAGIC
+        }
+        None => false,
+    }
+}
+
+/// Returns true if the string looks like a Bitcoin address, either in
+/// base58 or bech32.
+fn is_address(s: &[u8]) -> bool {
+    (26..=90).contains(&s.len()) && s.iter().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Parses an Ethereum keystore. Returns `None` if the data is not a JSON
+/// document with an encrypted key.
+fn keystore(data: &[u8]) -> Option<Keystore> {
+    if data.len() > MAX_KEYSTORE_SIZE
+        || data.trim_start().first() != Some(&b'{')
+    {
+        return None;
+    }
+
+    let json: Value = serde_json::from_slice(data).ok()?;
+
+    // Version 3 uses `crypto`, some old implementations used `Crypto`.
+    let crypto = json.get("crypto").or_else(|| json.get("Crypto"))?;
+    let ciphertext = crypto.get("ciphertext")?.as_str()?;
+    let kdf = crypto.get("kdf")?.as_str()?;
+
+    let string = |value: Option<&Value>| {
+        value.and_then(Value::as_str).map(String::from)
+    };
+
+    let kdf_params = crypto.get("kdfparams");
+    let kdf_cost = match kdf {
+        "scrypt" => kdf_params.and_then(|params| params.get("n")),
+        "pbkdf2" => kdf_params.and_then(|params| params.get("c")),
+        _ => None,
+    };
+
+    let mut keystore = Keystore::new();
+
+    keystore.version = json.get("version").and_then(Value::as_i64);
+    keystore.id = string(json.get("id"));
+    keystore.address = string(json.get("address"));
+    keystore.cipher = string(crypto.get("cipher"));
+    keystore.set_kdf(kdf.to_string());
+    keystore.kdf_cost = kdf_cost.and_then(Value::as_i64);
+    keystore.set_ciphertext(ciphertext.to_string());
+
+    Some(keystore)
+}
+
+/// Finds the mnemonic phrases in the data, which are sequences of 12, 15,
+/// 18, 21 or 24 words separated by whitespaces. Returns the phrases, and
+/// whether there were more than `MAX_ENTRIES`.
+fn mnemonics(data: &[u8]) -> (Vec<Mnemonic>, bool) {
+    let mut mnemonics = Vec::new();
+    let mut words: Vec<&[u8]> = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        while pos < data.len() && data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let token_start = pos;
+
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let token = &data[token_start..pos];
+        let is_word = (3..=8).contains(&token.len())
+            && token.iter().all(|c| c.is_ascii_lowercase());
+
+        if is_word {
+            if words.is_empty() {
+                start = token_start;
+            }
+            words.push(token);
+            end = pos;
+        }
+
+        // The phrase ends at the first token that is not a word, or at
+        // the end of the data.
+        if !is_word || pos == data.len() {
+            if MNEMONIC_LENGTHS.contains(&words.len()) {
+                if mnemonics.len() == MAX_ENTRIES {
+                    return (mnemonics, true);
+                }
+                let unique: HashSet<&[u8]> = words.iter().copied().collect();
+                let mut mnemonic = Mnemonic::new();
+                mnemonic.set_offset(start as i64);
+                mnemonic.set_size((end - start) as i64);
+                mnemonic.set_num_words(words.len() as i64);
+                mnemonic.set_num_unique_words(unique.len() as i64);
+                mnemonics.push(mnemonic);
+            }
+            words.clear();
+        }
+    }
+
+    (mnemonics, false)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn end2end() {
+        // Metadata page of a Berkeley DB database, followed by some records.
+        let mut wallet_dat = vec![0; 12];
+        wallet_dat.extend(0x00053162_u32.to_le_bytes());
+        wallet_dat.resize(4096, 0);
+        wallet_dat.extend(b"\x04name\x221BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2");
+        wallet_dat.extend(b"\x04mkey\x01\x00\x00\x00");
+        wallet_dat.extend(b"\x04ckey\x21\x02\x03");
+        wallet_dat.extend(b"\x04ckey\x21\x02\x04");
+        wallet_dat.extend(b"\x07version");
+
+        let keystore = br#"
+            {
+              "address": "008aeeda4d805471df9b2a5b0f38a0c3bcba786b",
+              "crypto": {
+                "cipher": "aes-128-ctr",
+                "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+                "kdf": "scrypt",
+                "kdfparams": { "dklen": 32, "n": 262144, "p": 8, "r": 1 },
+                "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+              },
+              "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+              "version": 3
+            }"#;
+
+        let text = b"seed: abandon ability able about above absent absorb \
+                     abstract absurd abuse access accident\n\
+                     2023-10-17: too short a phrase";
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "wallet"
+                rule wallet_dat {
+                  condition:
+                    wallet.is_wallet_dat and
+                    wallet.is_encrypted and
+                    wallet.num_keys == 0 and
+                    wallet.num_encrypted_keys == 2 and
+                    wallet.addresses[0] == "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"
+                }
+                rule keystore {
+                  condition:
+                    wallet.is_keystore and
+                    wallet.keystore.version == 3 and
+                    wallet.keystore.address == "008aeeda4d805471df9b2a5b0f38a0c3bcba786b" and
+                    wallet.keystore.cipher == "aes-128-ctr" and
+                    wallet.keystore.kdf == "scrypt" and
+                    wallet.keystore.kdf_cost == 262144 and
+                    wallet.keystore.id == "3198bc9c-6672-5ab3-d995-4942343ae5b6"
+                }
+                rule mnemonic {
+                  condition:
+                    wallet.mnemonics[0].offset == 6 and
+                    wallet.mnemonics[0].num_words == 12 and
+                    wallet.mnemonics[0].num_unique_words == 12 and
+                    wallet.mnemonics[0].size == 84 and
+                    not defined wallet.mnemonics[1].offset
+                }
+                rule nothing {
+                  condition:
+                    not wallet.is_wallet_dat and
+                    not wallet.is_keystore and
+                    not defined wallet.mnemonics[0].offset
+                }"#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            let results = scanner.scan(data).unwrap();
+            results
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&wallet_dat), ["wallet_dat"]);
+        assert_eq!(matching_rules(keystore), ["keystore"]);
+        assert_eq!(matching_rules(text), ["mnemonic"]);
+        assert_eq!(matching_rules(b"{\"crypto\": 1}"), ["nothing"]);
+    }
+}
@@ -0,0 +1,217 @@
+use bstr::ByteSlice;
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::wasm::*;
+
+mod parser;
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Wasm {
+    let mut wasm = Wasm::new();
+
+    let module = match parser::parse(ctx.scanned_data()) {
+        Some(module) => module,
+        None => {
+            wasm.set_is_wasm(false);
+            return wasm;
+        }
+    };
+
+    wasm.set_is_wasm(true);
+    wasm.set_version(module.version.into());
+    wasm.set_num_functions(module.num_functions.into());
+    wasm.set_num_types(module.num_types.into());
+    wasm.set_has_start_function(module.has_start_function);
+
+    for s in module.sections.iter() {
+        let mut section = Section::new();
+        section.type_ = Some(EnumOrUnknown::from_i32(s.id.into()));
+        section.name = s.name.map(|name| name.to_str_lossy().into_owned());
+        section.set_offset(s.offset as i64);
+        section.set_size(s.size as i64);
+        wasm.sections.push(section);
+    }
+
+    for i in module.imports.iter() {
+        let mut import = Import::new();
+        import.set_module(i.module.to_str_lossy().into_owned());
+        import.set_name(i.name.to_str_lossy().into_owned());
+        import.kind = Some(EnumOrUnknown::from_i32(i.kind.into()));
+        import.limits = i.limits.as_ref().map(limits).into();
+        wasm.imports.push(import);
+    }
+
+    for e in module.exports.iter() {
+        let mut export = Export::new();
+        export.set_name(e.name.to_str_lossy().into_owned());
+        export.kind = Some(EnumOrUnknown::from_i32(e.kind.into()));
+        export.set_index(e.index.into());
+        wasm.exports.push(export);
+    }
+
+    wasm.memories = module.memories.iter().map(limits).collect();
+    wasm.tables = module.tables.iter().map(limits).collect();
+    wasm
+}
+
+/// Returns true if the module imports an item with the given module and
+/// name (e.g: `wasm.imports_item("env", "memory")`).
+#[module_export]
+fn imports_item(
+    ctx: &ScanContext,
+    module: RuntimeString,
+    name: RuntimeString,
+) -> Option<bool> {
+    let wasm = ctx.module_output::<Wasm>()?;
+    let module = module.as_bstr(ctx);
+    let name = name.as_bstr(ctx);
+
+    Some(wasm.imports.iter().any(|import| {
+        import.module().as_bytes() == module.as_bytes()
+            && import.name().as_bytes() == name.as_bytes()
+    }))
+}
+
+/// Returns true if the module exports an item with the given name.
+#[module_export]
+fn exports_item(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let wasm = ctx.module_output::<Wasm>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(
+        wasm.exports
+            .iter()
+            .any(|export| export.name().as_bytes() == name.as_bytes()),
+    )
+}
+
+fn limits(l: &parser::Limits) -> Limits {
+    let mut limits = Limits::new();
+    limits.min = l.min.try_into().ok();
+    limits.max = l.max.and_then(|max| max.try_into().ok());
+    limits.set_is_shared(l.is_shared);
+    limits.set_is_64bit(l.is_64bit);
+    limits
+}
+
+#[cfg(test)]
+mod tests {
+    fn section(id: u8, content: &[u8]) -> Vec<u8> {
+        assert!(content.len() < 0x80);
+        [&[id, content.len() as u8][..], content].concat()
+    }
+
+    fn name(s: &str) -> Vec<u8> {
+        [&[s.len() as u8][..], s.as_bytes()].concat()
+    }
+
+    fn build_module() -> Vec<u8> {
+        let imports = [
+            &[2][..],
+            &name("env"),
+            &name("memory"),
+            // Memory with limits 256..=65536, using a 3-byte LEB128 for
+            // the maximum.
+            &[0x02, 0x01, 0x80, 0x02, 0x80, 0x80, 0x04],
+            &name("env"),
+            &name("crypto_hash"),
+            &[0x00, 0x00],
+        ]
+        .concat();
+
+        let exports = [
+            &[2][..],
+            &name("run"),
+            &[0x00, 0x01],
+            &name("table"),
+            &[0x01, 0x00],
+        ]
+        .concat();
+
+        [
+            &b"\0asm\x01\x00\x00\x00"[..],
+            // One type, with signature () -> ().
+            &section(1, &[0x01, 0x60, 0x00, 0x00]),
+            &section(2, &imports),
+            // One function, of type 0.
+            &section(3, &[0x01, 0x00]),
+            // A table of funcref with 2 elements at least.
+            &section(4, &[0x01, 0x70, 0x00, 0x02]),
+            &section(7, &exports),
+            &section(8, &[0x01]),
+            // The body of the function: no locals, and an `end` opcode.
+            &section(10, &[0x01, 0x02, 0x00, 0x0b]),
+            &section(0, &[name("producers"), b"\x00".to_vec()].concat()),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn end2end() {
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "wasm"
+                rule rule_1 {
+                  condition:
+                    wasm.is_wasm and
+                    wasm.version == 1 and
+                    wasm.num_types == 1 and
+                    wasm.num_functions == 1 and
+                    wasm.has_start_function and
+                    wasm.sections[0].type == wasm.SectionType.TYPE and
+                    wasm.sections[0].offset == 10 and
+                    wasm.sections[0].size == 4 and
+                    wasm.sections[7].type == wasm.SectionType.CUSTOM and
+                    wasm.sections[7].name == "producers" and
+                    not defined wasm.sections[0].name
+                }
+                rule rule_2 {
+                  condition:
+                    wasm.imports[0].module == "env" and
+                    wasm.imports[0].name == "memory" and
+                    wasm.imports[0].kind == wasm.ExternalKind.MEMORY_KIND and
+                    wasm.imports[0].limits.min == 256 and
+                    wasm.imports[0].limits.max == 65536 and
+                    wasm.imports[1].kind == wasm.ExternalKind.FUNCTION_KIND and
+                    not defined wasm.imports[1].limits.min and
+                    wasm.imports_item("env", "crypto_hash") and
+                    not wasm.imports_item("env", "run")
+                }
+                rule rule_3 {
+                  condition:
+                    wasm.exports[0].name == "run" and
+                    wasm.exports[0].index == 1 and
+                    wasm.exports[1].kind == wasm.ExternalKind.TABLE_KIND and
+                    wasm.exports_item("table") and
+                    wasm.tables[0].min == 2 and
+                    not defined wasm.tables[0].max and
+                    not defined wasm.memories[0].min
+                }
+                rule rule_4 { condition: not wasm.is_wasm }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matching_rules(&build_module()),
+            ["rule_1", "rule_2", "rule_3"]
+        );
+
+        assert_eq!(matching_rules(b"\0asm\x0d\x00\x01\x00"), ["rule_4"]);
+    }
+}
@@ -0,0 +1,254 @@
+/*! Parser for the WebAssembly binary format.
+
+A module starts with the `\0asm` magic and a version number, followed by a
+sequence of sections. Each section has an id, its size and its content,
+where integers are encoded as LEB128 and vectors are prefixed with their
+number of items.
+
+See: https://webassembly.github.io/spec/core/binary/modules.html
+*/
+
+const SECTION_CUSTOM: u8 = 0;
+const SECTION_TYPE: u8 = 1;
+const SECTION_IMPORT: u8 = 2;
+const SECTION_FUNCTION: u8 = 3;
+const SECTION_TABLE: u8 = 4;
+const SECTION_MEMORY: u8 = 5;
+const SECTION_EXPORT: u8 = 7;
+const SECTION_START: u8 = 8;
+
+const KIND_FUNCTION: u8 = 0;
+const KIND_TABLE: u8 = 1;
+const KIND_MEMORY: u8 = 2;
+const KIND_GLOBAL: u8 = 3;
+const KIND_TAG: u8 = 4;
+
+/// A WebAssembly module.
+#[derive(Default)]
+pub(crate) struct Module<'a> {
+    pub version: u32,
+    pub sections: Vec<Section<'a>>,
+    pub imports: Vec<Import<'a>>,
+    pub exports: Vec<Export<'a>>,
+    pub memories: Vec<Limits>,
+    pub tables: Vec<Limits>,
+    pub num_functions: u32,
+    pub num_types: u32,
+    pub has_start_function: bool,
+}
+
+pub(crate) struct Section<'a> {
+    pub id: u8,
+    /// Name of custom sections.
+    pub name: Option<&'a [u8]>,
+    pub offset: usize,
+    pub size: usize,
+}
+
+pub(crate) struct Import<'a> {
+    pub module: &'a [u8],
+    pub name: &'a [u8],
+    pub kind: u8,
+    /// Limits of imported tables and memories.
+    pub limits: Option<Limits>,
+}
+
+pub(crate) struct Export<'a> {
+    pub name: &'a [u8],
+    pub kind: u8,
+    pub index: u32,
+}
+
+pub(crate) struct Limits {
+    pub min: u64,
+    pub max: Option<u64>,
+    pub is_shared: bool,
+    pub is_64bit: bool,
+}
+
+/// Parses a WebAssembly module. Returns `None` if the data is not a
+/// module. Sections that can't be parsed are listed, but their content is
+/// ignored.
+pub(crate) fn parse(data: &[u8]) -> Option<Module<'_>> {
+    let mut reader = Reader { data: data.strip_prefix(b"\0asm")?, pos: 0 };
+
+    let mut module = Module {
+        version: u32::from_le_bytes(reader.bytes(4)?.try_into().unwrap()),
+        ..Default::default()
+    };
+
+    // Layers other than 1 are used by the component model, which has a
+    // different structure.
+    if module.version & 0xffff != 1 {
+        return None;
+    }
+
+    while let Some(id) = reader.u8() {
+        let size = match reader.u32() {
+            Some(size) => size as usize,
+            None => break,
+        };
+
+        // The offset within the scanned data, which includes the magic.
+        let offset = reader.pos + 4;
+
+        let content = match reader.bytes(size) {
+            Some(content) => content,
+            None => break,
+        };
+
+        let mut section = Reader { data: content, pos: 0 };
+        let mut name = None;
+
+        match id {
+            SECTION_CUSTOM => name = section.name(),
+            SECTION_TYPE => {
+                module.num_types = section.u32().unwrap_or(0);
+            }
+            SECTION_IMPORT => {
+                section.vec(|r| {
+                    module.imports.push(r.import()?);
+                    Some(())
+                });
+            }
+            SECTION_FUNCTION => {
+                module.num_functions = section.u32().unwrap_or(0);
+            }
+            SECTION_TABLE => {
+                section.vec(|r| {
+                    // The element type precedes the limits.
+                    r.u8()?;
+                    module.tables.push(r.limits()?);
+                    Some(())
+                });
+            }
+            SECTION_MEMORY => {
+                section.vec(|r| {
+                    module.memories.push(r.limits()?);
+                    Some(())
+                });
+            }
+            SECTION_EXPORT => {
+                section.vec(|r| {
+                    module.exports.push(Export {
+                        name: r.name()?,
+                        kind: r.u8()?,
+                        index: r.u32()?,
+                    });
+                    Some(())
+                });
+            }
+            SECTION_START => module.has_start_function = true,
+            _ => {}
+        }
+
+        module.sections.push(Section { id, name, offset, size });
+    }
+
+    Some(module)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Reads an unsigned LEB128 integer of up to 64 bits.
+    fn u64(&mut self) -> Option<u64> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.u8()?;
+            value |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.u64()?.try_into().ok()
+    }
+
+    /// Reads a name, which is a vector of bytes that should contain UTF-8.
+    fn name(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// Reads a vector, calling `f` for reading each item. Stops at the
+    /// first item that can't be read.
+    fn vec<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Self) -> Option<()>,
+    {
+        let len = self.u32().unwrap_or(0);
+        for _ in 0..len {
+            if f(self).is_none() {
+                break;
+            }
+        }
+    }
+
+    fn limits(&mut self) -> Option<Limits> {
+        // Bit 0 indicates that there's a maximum, bit 1 that the memory is
+        // shared, and bit 2 that it uses 64-bit addresses.
+        let flags = self.u8()?;
+        if flags > 0x07 {
+            return None;
+        }
+        let min = self.u64()?;
+        let max = if flags & 0x01 != 0 { Some(self.u64()?) } else { None };
+        Some(Limits {
+            min,
+            max,
+            is_shared: flags & 0x02 != 0,
+            is_64bit: flags & 0x04 != 0,
+        })
+    }
+
+    fn import(&mut self) -> Option<Import<'a>> {
+        let module = self.name()?;
+        let name = self.name()?;
+        let kind = self.u8()?;
+
+        let limits = match kind {
+            KIND_FUNCTION => {
+                self.u32()?;
+                None
+            }
+            KIND_TABLE => {
+                self.u8()?;
+                Some(self.limits()?)
+            }
+            KIND_MEMORY => Some(self.limits()?),
+            KIND_GLOBAL => {
+                // Value type and mutability.
+                self.bytes(2)?;
+                None
+            }
+            KIND_TAG => {
+                // Attribute and type index.
+                self.u8()?;
+                self.u32()?;
+                None
+            }
+            _ => return None,
+        };
+
+        Some(Import { module, name, kind, limits })
+    }
+}
@@ -0,0 +1,270 @@
+/*! Decoding of the ASN.1 structures used in certificates and keys.
+
+The structures are described in RFC 5280 (certificates), RFC 2986
+(certificate requests), RFC 8017 (RSA keys), RFC 5208 (PKCS#8 keys) and
+RFC 5915 (EC keys).
+*/
+
+use std::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::modules::utils::der::{
+    algorithm_name, distinguished_name, integer_value, oid_string,
+    strip_leading_zero, time, Tlv, BIT_STRING, INTEGER, OCTET_STRING, OID,
+    SEQUENCE,
+};
+
+/// Public key information.
+#[derive(Default)]
+pub(crate) struct KeyInfo {
+    /// Name of the algorithm (e.g: "rsa", "ec"), or its OID if unknown.
+    pub algorithm: String,
+    /// Size of the key in bits, if known.
+    pub size: Option<u32>,
+}
+
+/// A certificate.
+pub(crate) struct Certificate {
+    pub version: u32,
+    pub serial: String,
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: Option<i64>,
+    pub not_after: Option<i64>,
+    pub signature_algorithm: String,
+    pub key: KeyInfo,
+    /// SHA-256 of the SubjectPublicKeyInfo, in hex.
+    pub key_sha256: String,
+    /// SHA-256 of the whole certificate, in hex.
+    pub sha256: String,
+    pub is_self_signed: bool,
+}
+
+/// A certificate request.
+pub(crate) struct CertificateRequest {
+    pub subject: String,
+    pub key: KeyInfo,
+    /// SHA-256 of the SubjectPublicKeyInfo, in hex.
+    pub key_sha256: String,
+}
+
+/// Parses a certificate. Returns `None` if the data doesn't start with a
+/// certificate.
+pub(crate) fn certificate(data: &[u8]) -> Option<(Certificate, usize)> {
+    let (cert, _) = Tlv::parse(data)?;
+
+    if cert.tag != SEQUENCE {
+        return None;
+    }
+
+    let mut parts = cert.children();
+    let tbs = parts.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let signature_algorithm =
+        parts.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    parts.next().filter(|tlv| tlv.tag == BIT_STRING)?;
+
+    let mut fields = tbs.children().peekable();
+
+    // The version is optional, and explicitly tagged with [0]. The
+    // default version is v1, encoded as 0.
+    let version = match fields.next_if(|tlv| tlv.tag == 0xa0) {
+        Some(version) => {
+            let (version, _) = Tlv::parse(version.content)?;
+            integer_value(version.content)? + 1
+        }
+        None => 1,
+    };
+
+    let serial = fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+    // Signature algorithm, repeated in the TBS structure.
+    fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let issuer = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let validity = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let subject = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let key = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+
+    let mut times = validity.children();
+
+    Some((
+        Certificate {
+            version: version as u32,
+            serial: hex(strip_leading_zero(serial.content)),
+            subject: distinguished_name(&subject),
+            issuer: distinguished_name(&issuer),
+            not_before: times.next().and_then(|t| time(&t)),
+            not_after: times.next().and_then(|t| time(&t)),
+            signature_algorithm: algorithm_name(&signature_algorithm),
+            key: key_info(&key),
+            key_sha256: sha256(key.raw),
+            sha256: sha256(cert.raw),
+            is_self_signed: subject.raw == issuer.raw,
+        },
+        cert.raw.len(),
+    ))
+}
+
+/// Parses a certificate request.
+pub(crate) fn certificate_request(data: &[u8]) -> Option<CertificateRequest> {
+    let (request, _) = Tlv::parse(data)?;
+    let info = request.children().next().filter(|tlv| tlv.tag == SEQUENCE)?;
+
+    let mut fields = info.children();
+    fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+    let subject = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+    let key = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+
+    Some(CertificateRequest {
+        subject: distinguished_name(&subject),
+        key: key_info(&key),
+        key_sha256: sha256(key.raw),
+    })
+}
+
+/// Parses a private key in PKCS#1 (RSA), SEC1 (EC), DSA or PKCS#8 format.
+/// `label` is the label of the PEM block that contained the key, which
+/// identifies the format.
+pub(crate) fn private_key(label: &str, data: &[u8]) -> Option<KeyInfo> {
+    let (key, _) = Tlv::parse(data)?;
+    let mut fields = key.children();
+
+    match label {
+        "RSA PRIVATE KEY" => {
+            // The version is followed by the modulus.
+            fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+            let modulus = fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+            Some(KeyInfo {
+                algorithm: "rsa".to_string(),
+                size: Some(integer_bits(modulus.content)),
+            })
+        }
+        "DSA PRIVATE KEY" => {
+            // The version is followed by the prime `p`.
+            fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+            let p = fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+            Some(KeyInfo {
+                algorithm: "dsa".to_string(),
+                size: Some(integer_bits(p.content)),
+            })
+        }
+        "EC PRIVATE KEY" => {
+            // The curve is in the optional field tagged with [0].
+            let curve = fields
+                .find(|tlv| tlv.tag == 0xa0)
+                .and_then(|params| Tlv::parse(params.content))
+                .map(|(oid, _)| oid_string(oid.content));
+            Some(KeyInfo {
+                algorithm: "ec".to_string(),
+                size: curve.as_deref().and_then(curve_size),
+            })
+        }
+        "PRIVATE KEY" => {
+            fields.next().filter(|tlv| tlv.tag == INTEGER)?;
+            let algorithm = fields.next().filter(|tlv| tlv.tag == SEQUENCE)?;
+            let key = fields.next().filter(|tlv| tlv.tag == OCTET_STRING)?;
+            let mut info =
+                KeyInfo { algorithm: algorithm_name(&algorithm), size: None };
+            info.size = match info.algorithm.as_str() {
+                "rsa" => private_key("RSA PRIVATE KEY", key.content)?.size,
+                "dsa" => algorithm
+                    .children()
+                    .nth(1)
+                    .and_then(|params| params.children().next())
+                    .map(|p| integer_bits(p.content)),
+                "ec" => algorithm_curve_size(&algorithm),
+                _ => fixed_key_size(&info.algorithm),
+            };
+            Some(info)
+        }
+        _ => None,
+    }
+}
+
+/// Returns information about a `SubjectPublicKeyInfo` structure.
+fn key_info(spki: &Tlv) -> KeyInfo {
+    let mut fields = spki.children();
+    let algorithm = match fields.next() {
+        Some(algorithm) => algorithm,
+        None => return KeyInfo::default(),
+    };
+
+    let name = algorithm_name(&algorithm);
+    let size = match name.as_str() {
+        // The bit string contains the sequence with the modulus and the
+        // exponent. The first byte of the bit string is the number of
+        // unused bits.
+        "rsa" => fields
+            .next()
+            .filter(|tlv| tlv.tag == BIT_STRING)
+            .and_then(|bits| Tlv::parse(bits.content.get(1..)?))
+            .and_then(|(key, _)| key.children().next())
+            .map(|modulus| integer_bits(modulus.content)),
+        "dsa" => algorithm
+            .children()
+            .nth(1)
+            .and_then(|params| params.children().next())
+            .map(|p| integer_bits(p.content)),
+        "ec" => algorithm_curve_size(&algorithm),
+        _ => fixed_key_size(&name),
+    };
+
+    KeyInfo { algorithm: name, size }
+}
+
+/// Returns the size of the curve in the parameters of an EC algorithm
+/// identifier.
+fn algorithm_curve_size(algorithm: &Tlv) -> Option<u32> {
+    let curve = algorithm.children().nth(1).filter(|tlv| tlv.tag == OID)?;
+    curve_size(&oid_string(curve.content))
+}
+
+/// Returns the size of the named curve with the given OID.
+fn curve_size(oid: &str) -> Option<u32> {
+    match oid {
+        // prime256v1 (P-256) and secp256k1.
+        "1.2.840.10045.3.1.7" | "1.3.132.0.10" => Some(256),
+        // secp384r1 (P-384).
+        "1.3.132.0.34" => Some(384),
+        // secp521r1 (P-521).
+        "1.3.132.0.35" => Some(521),
+        // prime192v1 (P-192) and secp224r1 (P-224).
+        "1.2.840.10045.3.1.1" => Some(192),
+        "1.3.132.0.33" => Some(224),
+        _ => None,
+    }
+}
+
+/// Returns the key size of algorithms where it's fixed.
+fn fixed_key_size(algorithm: &str) -> Option<u32> {
+    match algorithm {
+        "ed25519" | "x25519" => Some(256),
+        "ed448" => Some(456),
+        "x448" => Some(448),
+        _ => None,
+    }
+}
+
+/// Returns the number of significant bits in a positive integer.
+pub(crate) fn integer_bits(content: &[u8]) -> u32 {
+    let content = strip_leading_zero(content);
+    match content.first() {
+        Some(first) => {
+            (content.len() as u32 - 1) * 8 + (8 - first.leading_zeros())
+        }
+        None => 0,
+    }
+}
+
+/// Returns the SHA-256 of the data, in lowercase hex.
+fn sha256(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// Returns the data in lowercase hex.
+fn hex(data: &[u8]) -> String {
+    let mut s = String::with_capacity(2 * data.len());
+    for b in data {
+        write!(s, "{:02x}", b).unwrap();
+    }
+    s
+}
@@ -0,0 +1,97 @@
+/*! Reader for PEM-encoded data, as described in RFC 7468.
+
+A PEM block looks like this:
+
+```text
+-----BEGIN CERTIFICATE-----
+MIIBszCCAVmgAwIBAgIUR3...
+-----END CERTIFICATE-----
+```
+
+Keys encrypted with the legacy OpenSSL format have headers between the
+`BEGIN` line and the base64-encoded data, like `Proc-Type: 4,ENCRYPTED`.
+*/
+
+use base64::Engine;
+use bstr::ByteSlice;
+
+/// A PEM block.
+pub(crate) struct Block<'a> {
+    /// Offset of the block within the scanned data.
+    pub offset: usize,
+    /// Length of the block, from the `BEGIN` line to the end of the `END`
+    /// line.
+    pub length: usize,
+    /// The label in the `BEGIN` and `END` lines (e.g: "CERTIFICATE").
+    pub label: &'a str,
+    /// Headers that appear before the data, if any.
+    pub headers: &'a [u8],
+    /// The decoded data.
+    pub data: Vec<u8>,
+}
+
+/// Returns an iterator over the PEM blocks in the data. Blocks whose data
+/// is not valid base64 are ignored.
+pub(crate) fn blocks(data: &[u8]) -> impl Iterator<Item = Block<'_>> {
+    let mut pos = 0;
+
+    std::iter::from_fn(move || loop {
+        let offset = pos + data.get(pos..)?.find(b"-----BEGIN ")?;
+        let label_start = offset + 11;
+        pos = label_start;
+
+        // The label is followed by five dashes, and can't span multiple
+        // lines.
+        let label_len = match data[label_start..].find(b"-----") {
+            Some(len) => len,
+            None => return None,
+        };
+
+        let label = match data[label_start..label_start + label_len].to_str() {
+            Ok(label) if !label.contains(['\r', '\n']) => label,
+            _ => continue,
+        };
+
+        let body_start = label_start + label_len + 5;
+        let end_line = [b"-----END ", label.as_bytes(), b"-----"].concat();
+
+        let body_len = match data[body_start..].find(&end_line) {
+            Some(len) => len,
+            None => continue,
+        };
+
+        let body = &data[body_start..body_start + body_len];
+        pos = body_start + body_len + end_line.len();
+
+        // Headers are separated from the data by an empty line.
+        let (headers, body) = match body.find(b":") {
+            Some(_) => {
+                match body.find(b"\n\n").or_else(|| body.find(b"\r\n\r\n")) {
+                    Some(end) => body.split_at(end),
+                    None => continue,
+                }
+            }
+            None => (&body[..0], body),
+        };
+
+        let encoded: Vec<u8> = body
+            .iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .copied()
+            .collect();
+
+        let data =
+            match base64::engine::general_purpose::STANDARD.decode(encoded) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+
+        return Some(Block {
+            offset,
+            length: pos - offset,
+            label,
+            headers,
+            data,
+        });
+    })
+}
@@ -0,0 +1,72 @@
+/*! Reader for OpenSSH private keys.
+
+The format is described in the PROTOCOL.key file distributed with OpenSSH.
+The decoded PEM data starts with the `openssh-key-v1` magic, followed by
+the cipher and key derivation function used for protecting the keys, and
+the public keys, which are never encrypted.
+*/
+
+use super::asn1::{integer_bits, KeyInfo};
+
+/// Information about an OpenSSH private key.
+pub(crate) struct OpenSshKey {
+    pub key: KeyInfo,
+    pub is_encrypted: bool,
+}
+
+/// Parses the decoded data in an `OPENSSH PRIVATE KEY` PEM block. Only the
+/// first key in the file is considered.
+pub(crate) fn parse(data: &[u8]) -> Option<OpenSshKey> {
+    let mut reader = Reader(data.strip_prefix(b"openssh-key-v1\0")?);
+
+    let cipher = reader.string()?;
+    // The KDF name and options.
+    reader.string()?;
+    reader.string()?;
+
+    if reader.u32()? == 0 {
+        return None;
+    }
+
+    let mut public_key = Reader(reader.string()?);
+    let key_type = std::str::from_utf8(public_key.string()?).ok()?;
+
+    let (algorithm, size) = match key_type {
+        // The public exponent is followed by the modulus.
+        "ssh-rsa" => {
+            public_key.string()?;
+            ("rsa", Some(integer_bits(public_key.string()?)))
+        }
+        "ssh-dss" => ("dsa", Some(integer_bits(public_key.string()?))),
+        "ecdsa-sha2-nistp256" => ("ec", Some(256)),
+        "ecdsa-sha2-nistp384" => ("ec", Some(384)),
+        "ecdsa-sha2-nistp521" => ("ec", Some(521)),
+        "ssh-ed25519" => ("ed25519", Some(256)),
+        "ssh-ed448" => ("ed448", Some(456)),
+        key_type => (key_type, None),
+    };
+
+    Some(OpenSshKey {
+        key: KeyInfo { algorithm: algorithm.to_string(), size },
+        is_encrypted: cipher != b"none",
+    })
+}
+
+/// Reads the big-endian integers and length-prefixed strings used in the
+/// SSH wire format.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let value = u32::from_be_bytes(self.0.get(..4)?.try_into().unwrap());
+        self.0 = &self.0[4..];
+        Some(value)
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        let string = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(string)
+    }
+}
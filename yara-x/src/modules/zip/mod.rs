@@ -0,0 +1,194 @@
+use bstr::ByteSlice;
+use chrono::NaiveDate;
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::zip::*;
+use crate::modules::utils::zip::Archive;
+
+/// Extensions of executable files and scripts, used for detecting file
+/// names with double extensions.
+const EXECUTABLE_EXTENSIONS: &[&[u8]] = &[
+    b"bat", b"cmd", b"com", b"cpl", b"dll", b"exe", b"hta", b"jar", b"js",
+    b"jse", b"lnk", b"msi", b"pif", b"ps1", b"scr", b"vbe", b"vbs", b"wsf",
+];
+
+#[module_main]
+fn main(ctx: &ScanContext) -> Zip {
+    let mut zip = Zip::new();
+
+    let archive = match Archive::parse(ctx.scanned_data()) {
+        Some(archive) => archive,
+        None => {
+            zip.set_is_zip(false);
+            return zip;
+        }
+    };
+
+    zip.set_is_zip(true);
+    zip.set_comment(archive.comment.to_vec());
+    zip.set_central_directory_offset(archive.central_directory_offset.into());
+
+    for e in archive.entries.iter() {
+        let mut entry = ZipEntry::new();
+        entry.set_name(e.name.to_vec());
+        entry.set_comment(e.comment.to_vec());
+        entry.compression_method =
+            Some(EnumOrUnknown::from_i32(e.compression_method.into()));
+        entry.set_compressed_size(e.compressed_size.into());
+        entry.set_uncompressed_size(e.uncompressed_size.into());
+        entry.set_crc32(e.crc32.into());
+        entry.timestamp = dos_timestamp(e.mod_date, e.mod_time);
+        entry.set_flags(e.flags.into());
+        entry.set_is_encrypted(e.is_encrypted());
+        entry.set_is_directory(e.name.ends_with(b"/"));
+        entry.set_has_double_extension(has_double_extension(e.name));
+        entry.set_local_header_offset(e.local_header_offset.into());
+        zip.entries.push(entry);
+    }
+
+    zip
+}
+
+/// Converts a date and time in MS-DOS format to a UNIX timestamp.
+fn dos_timestamp(date: u16, time: u16) -> Option<i64> {
+    let year = 1980 + i32::from(date >> 9);
+    let month = u32::from((date >> 5) & 0xf);
+    let day = u32::from(date & 0x1f);
+    let hour = u32::from(time >> 11);
+    let minute = u32::from((time >> 5) & 0x3f);
+    let second = u32::from(time & 0x1f) * 2;
+
+    Some(
+        NaiveDate::from_ymd_opt(year, month, day)?
+            .and_hms_opt(hour, minute, second)?
+            .and_utc()
+            .timestamp(),
+    )
+}
+
+/// Returns true if the file name in `path` has two extensions, and the last
+/// one is used by executable files. The extension before it must contain
+/// at least one letter, so that version numbers like in `setup-1.2.exe`
+/// are not considered extensions.
+fn has_double_extension(path: &[u8]) -> bool {
+    let file_name =
+        path.rsplit(|c| *c == b'/' || *c == b'\\').next().unwrap_or(path);
+
+    let mut parts = file_name.rsplit(|c| *c == b'.');
+
+    let (last, previous) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(last), Some(previous), Some(stem)) if !stem.is_empty() => {
+            (last, previous.trim_end())
+        }
+        _ => return false,
+    };
+
+    !previous.is_empty()
+        && previous.len() <= 5
+        && previous.iter().all(|c| c.is_ascii_alphanumeric())
+        && previous.iter().any(|c| c.is_ascii_alphabetic())
+        && EXECUTABLE_EXTENSIONS
+            .iter()
+            .any(|ext| last.eq_ignore_ascii_case(ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::modules::utils::zip::{build_zip, TestEntry};
+
+    #[test]
+    fn double_extension() {
+        use super::has_double_extension;
+
+        assert!(has_double_extension(b"invoice.pdf.exe"));
+        assert!(has_double_extension(b"docs/Invoice.PDF.JS"));
+        assert!(has_double_extension(b"invoice.doc   .scr"));
+        assert!(!has_double_extension(b"invoice.exe"));
+        assert!(!has_double_extension(b"setup-1.2.exe"));
+        assert!(!has_double_extension(b".pdf.exe"));
+        assert!(!has_double_extension(b"invoice.pdf.txt"));
+        assert!(!has_double_extension(b"foo.pdf/bar.exe"));
+    }
+
+    #[test]
+    fn end2end() {
+        let zip = build_zip(
+            &[
+                TestEntry {
+                    name: "docs/",
+                    flags: 0,
+                    compression_method: 0,
+                    // 2023-08-01 10:30:00
+                    mod_time: (10 << 11) | (30 << 5),
+                    mod_date: (43 << 9) | (8 << 5) | 1,
+                    content: b"",
+                    uncompressed_size: 0,
+                    comment: "",
+                },
+                TestEntry {
+                    name: "docs/invoice.pdf.exe",
+                    flags: 0x1,
+                    compression_method: 8,
+                    mod_time: 0,
+                    mod_date: 0,
+                    content: b"\x00\x01\x02\x03",
+                    uncompressed_size: 0,
+                    comment: "open me",
+                },
+            ],
+            b"",
+            "archive comment",
+        );
+
+        let mut compiler = crate::compiler::Compiler::new();
+
+        compiler
+            .add_source(
+                r#"import "zip"
+                rule rule_1 {
+                  condition:
+                    zip.is_zip and
+                    zip.comment == "archive comment" and
+                    zip.central_directory_offset == 89 and
+                    zip.entries[0].name == "docs/" and
+                    zip.entries[0].is_directory and
+                    zip.entries[0].timestamp == 1690885800 and
+                    zip.entries[0].compression_method == zip.CompressionMethod.STORED and
+                    zip.entries[0].crc32 == 0 and
+                    not zip.entries[0].is_encrypted
+                }
+                rule rule_2 {
+                  condition:
+                    for any entry in zip.entries : (
+                      entry.is_encrypted and
+                      entry.uncompressed_size == 0 and
+                      entry.compressed_size == 4 and
+                      entry.crc32 == 0x8bb98613 and
+                      entry.has_double_extension and
+                      entry.compression_method == zip.CompressionMethod.DEFLATED and
+                      entry.comment == "open me" and
+                      not defined entry.timestamp
+                    )
+                }
+                rule rule_3 { condition: not zip.is_zip }
+                "#,
+            )
+            .unwrap();
+
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+
+        let mut matching_rules = |data: &[u8]| {
+            scanner
+                .scan(data)
+                .unwrap()
+                .matching_rules()
+                .map(|rule| rule.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching_rules(&zip), ["rule_1", "rule_2"]);
+        assert_eq!(matching_rules(b"PK\x03\x04"), ["rule_3"]);
+    }
+}
@@ -18,6 +18,7 @@ use regex_syntax::hir::literal::Seq;
 use regex_syntax::hir::{
     visit, Class, ClassBytes, Hir, HirKind, Literal, Look, Repetition,
 };
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use yara_x_parser::ast::HexByte;
@@ -38,7 +39,9 @@ pub enum Error {
     TooLarge,
 }
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[derive(
+    Eq, PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize,
+)]
 pub(crate) struct Location {
     pub fwd: usize,
     pub bck_seq_id: u64,
@@ -63,7 +66,7 @@ pub(crate) struct Offset {
     bck: re::instr::Offset,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct RegexpAtom {
     pub atom: Atom,
     pub code_loc: Location,
@@ -424,92 +424,123 @@ impl Display for InstrSeq {
         loop {
             let addr = instr.ip();
             match instr.next() {
-                Instr::AnyByte => {
-                    writeln!(f, "{:05x}: ANY_BYTE", addr)?;
-                }
-                Instr::Byte(byte) => {
-                    writeln!(f, "{:05x}: LIT {:#04x}", addr, byte)?;
-                }
-                Instr::MaskedByte { byte, mask } => {
-                    writeln!(
-                        f,
-                        "{:05x}: MASKED_BYTE {:#04x} {:#04x}",
-                        addr, byte, mask
-                    )?;
-                }
-                Instr::CaseInsensitiveChar(c) => {
-                    writeln!(f, "{:05x}: CASE_INSENSITIVE {:#04x}", addr, c)?;
-                }
-                Instr::ClassRanges(class) => {
-                    write!(f, "{:05x}: CLASS_RANGES ", addr)?;
-                    for range in class.ranges() {
-                        write!(f, "[{:#04x}-{:#04x}] ", range.0, range.1)?;
-                    }
-                    writeln!(f)?;
-                }
-                Instr::ClassBitmap(class) => {
-                    write!(f, "{:05x}: CLASS_BITMAP ", addr)?;
-                    for byte in class.bytes() {
-                        write!(f, "{:#04x} ", byte)?;
-                    }
-                    writeln!(f)?;
-                }
-                Instr::Jump(offset) => {
-                    writeln!(
-                        f,
-                        "{:05x}: JUMP {:05x}",
-                        addr,
-                        addr as isize + offset as isize,
-                    )?;
-                }
-                Instr::SplitA(offset) => {
-                    writeln!(
-                        f,
-                        "{:05x}: SPLIT_A {:05x}",
-                        addr,
-                        addr as isize + offset as isize,
-                    )?;
-                }
-                Instr::SplitB(offset) => {
-                    writeln!(
-                        f,
-                        "{:05x}: SPLIT_B {:05x}",
-                        addr,
-                        addr as isize + offset as isize,
-                    )?;
-                }
-                Instr::SplitN(split) => {
-                    write!(f, "{:05x}: SPLIT_N", addr)?;
-                    for offset in split.offsets() {
-                        write!(f, " {:05x}", addr as isize + offset as isize)?;
-                    }
-                    writeln!(f)?;
-                }
-                Instr::Start => {
-                    writeln!(f, "{:05x}: START", addr)?;
-                }
-                Instr::End => {
-                    writeln!(f, "{:05x}: END", addr)?;
-                }
-                Instr::WordBoundary => {
-                    writeln!(f, "{:05x}: WORD_BOUNDARY", addr)?;
-                }
-                Instr::WordBoundaryNeg => {
-                    writeln!(f, "{:05x}: WORD_BOUNDARY_NEG", addr)?;
-                }
-                Instr::Match => {
-                    writeln!(f, "{:05x}: MATCH", addr)?;
-                }
-                Instr::Eoi => {
-                    break;
-                }
-            };
+                Instr::Eoi => break,
+                i => write_instr(f, addr, i)?,
+            }
         }
-
         Ok(())
     }
 }
 
+/// Returns a human-readable listing of the code that starts at `start`
+/// within `code`, up to the first `MATCH` instruction.
+///
+/// Addresses in the listing are relative to the start of `code`.
+pub(crate) fn disassemble(code: &[u8], start: usize) -> String {
+    let mut output = String::new();
+    let mut instr = InstrParser::new(&code[start..]);
+    loop {
+        let addr = start + instr.ip();
+        match instr.next() {
+            Instr::Eoi => break,
+            Instr::Match => {
+                write_instr(&mut output, addr, Instr::Match).unwrap();
+                break;
+            }
+            i => write_instr(&mut output, addr, i).unwrap(),
+        }
+    }
+    output
+}
+
+/// Writes a single instruction located at `addr`, followed by a newline.
+fn write_instr<W: std::fmt::Write>(
+    f: &mut W,
+    addr: usize,
+    instr: Instr,
+) -> std::fmt::Result {
+    match instr {
+        Instr::AnyByte => {
+            writeln!(f, "{:05x}: ANY_BYTE", addr)?;
+        }
+        Instr::Byte(byte) => {
+            writeln!(f, "{:05x}: LIT {:#04x}", addr, byte)?;
+        }
+        Instr::MaskedByte { byte, mask } => {
+            writeln!(
+                f,
+                "{:05x}: MASKED_BYTE {:#04x} {:#04x}",
+                addr, byte, mask
+            )?;
+        }
+        Instr::CaseInsensitiveChar(c) => {
+            writeln!(f, "{:05x}: CASE_INSENSITIVE {:#04x}", addr, c)?;
+        }
+        Instr::ClassRanges(class) => {
+            write!(f, "{:05x}: CLASS_RANGES ", addr)?;
+            for range in class.ranges() {
+                write!(f, "[{:#04x}-{:#04x}] ", range.0, range.1)?;
+            }
+            writeln!(f)?;
+        }
+        Instr::ClassBitmap(class) => {
+            write!(f, "{:05x}: CLASS_BITMAP ", addr)?;
+            for byte in class.bytes() {
+                write!(f, "{:#04x} ", byte)?;
+            }
+            writeln!(f)?;
+        }
+        Instr::Jump(offset) => {
+            writeln!(
+                f,
+                "{:05x}: JUMP {:05x}",
+                addr,
+                addr as isize + offset as isize,
+            )?;
+        }
+        Instr::SplitA(offset) => {
+            writeln!(
+                f,
+                "{:05x}: SPLIT_A {:05x}",
+                addr,
+                addr as isize + offset as isize,
+            )?;
+        }
+        Instr::SplitB(offset) => {
+            writeln!(
+                f,
+                "{:05x}: SPLIT_B {:05x}",
+                addr,
+                addr as isize + offset as isize,
+            )?;
+        }
+        Instr::SplitN(split) => {
+            write!(f, "{:05x}: SPLIT_N", addr)?;
+            for offset in split.offsets() {
+                write!(f, " {:05x}", addr as isize + offset as isize)?;
+            }
+            writeln!(f)?;
+        }
+        Instr::Start => {
+            writeln!(f, "{:05x}: START", addr)?;
+        }
+        Instr::End => {
+            writeln!(f, "{:05x}: END", addr)?;
+        }
+        Instr::WordBoundary => {
+            writeln!(f, "{:05x}: WORD_BOUNDARY", addr)?;
+        }
+        Instr::WordBoundaryNeg => {
+            writeln!(f, "{:05x}: WORD_BOUNDARY_NEG", addr)?;
+        }
+        Instr::Match => {
+            writeln!(f, "{:05x}: MATCH", addr)?;
+        }
+        Instr::Eoi => {}
+    };
+    Ok(())
+}
+
 /// Parses a slice of bytes that contains Pike VM instructions, returning
 /// individual instructions and their arguments.
 pub struct InstrParser<'a> {
@@ -15,10 +15,11 @@ use bitvec::order::Lsb0;
 use bitvec::slice::BitSlice;
 use bitvec::vec::BitVec;
 use bstr::ByteSlice;
+use protobuf::reflect::MessageDescriptor;
 use protobuf::{MessageDyn, MessageFull};
 use regex::bytes::Regex;
 use rustc_hash::FxHashMap;
-use wasmtime::Store;
+use wasmtime::{Global, Store, Val};
 
 use crate::compiler::{
     NamespaceId, PatternId, RegexpId, RuleId, Rules, SubPattern,
@@ -27,7 +28,9 @@ use crate::compiler::{
 use crate::re::pikevm;
 use crate::re::pikevm::PikeVM;
 use crate::scanner::matches::{Match, MatchList, UnconfirmedMatch};
-use crate::scanner::{RuntimeStringId, HEARTBEAT_COUNTER};
+use crate::scanner::{
+    ConsoleLevel, ConsoleMessage, RuntimeStringId, HEARTBEAT_COUNTER,
+};
 use crate::string_pool::BStringPool;
 use crate::types::{Struct, TypeValue};
 use crate::wasm::MATCHING_RULES_BITMAP_BASE;
@@ -77,6 +80,18 @@ pub(crate) struct ScanContext<'r> {
     /// Keys are the fully qualified protobuf message name, and values are
     /// the message returned by the corresponding module.
     pub module_outputs: FxHashMap<String, Box<dyn MessageDyn>>,
+    /// Hash map that contains the data provided by the user for YARA
+    /// modules. Keys are module names, and values are the data that will
+    /// be passed to the corresponding module (e.g. the report for the
+    /// `cuckoo` module).
+    pub module_data: FxHashMap<String, Vec<u8>>,
+    /// Hash map where keys are module names and values are the maximum
+    /// number of items in the arrays produced by the corresponding module.
+    /// Modules that don't appear in this map have no limit.
+    pub module_array_limits: FxHashMap<String, usize>,
+    /// Descriptor of the root message for the `ext` module, if the rules
+    /// define a schema for it.
+    pub ext_descriptor: Option<MessageDescriptor>,
     /// Hash map that tracks the matches occurred during a scan. The keys
     /// are the PatternId of the matching pattern, and values are a list
     /// of matches.
@@ -103,8 +118,17 @@ pub(crate) struct ScanContext<'r> {
     /// is evaluated, it is compiled the first time and stored in this hash
     /// map.
     pub regexp_cache: RefCell<FxHashMap<RegexpId, Regex>>,
+    /// Global variable that contains the ID of the rule whose condition is
+    /// being evaluated, or -1 if no rule is being evaluated.
+    pub current_rule: Option<Global>,
+    /// Callback that receives the messages emitted by the `console` module.
+    pub console_log: Option<ConsoleCallback<'r>>,
 }
 
+/// Type of the callback that receives the messages emitted by the `console`
+/// module.
+pub(crate) type ConsoleCallback<'r> = Box<dyn FnMut(&ConsoleMessage) + 'r>;
+
 impl ScanContext<'_> {
     /// Returns a slice with the data being scanned.
     pub(crate) fn scanned_data<'a>(&self) -> &'a [u8] {
@@ -116,6 +140,39 @@ impl ScanContext<'_> {
         }
     }
 
+    /// Returns the ID of the rule whose condition is being evaluated, if any.
+    pub(crate) fn current_rule(&mut self) -> Option<RuleId> {
+        let wasm_store = unsafe { self.wasm_store.as_mut() };
+        match self.current_rule?.get(wasm_store) {
+            Val::I32(rule_id) if rule_id >= 0 => Some(RuleId::from(rule_id)),
+            _ => None,
+        }
+    }
+
+    /// Passes a message emitted by the `console` module to the callback set
+    /// with [`crate::Scanner::console_log`], if any.
+    pub(crate) fn console_log(&mut self, level: ConsoleLevel, text: &str) {
+        if self.console_log.is_none() {
+            return;
+        }
+
+        let rules = self.compiled_rules;
+        let rule = self.current_rule().map(|rule_id| {
+            let rule = rules.get(rule_id);
+            let ident_pool = rules.ident_pool();
+            (
+                ident_pool.get(rule.namespace_ident_id).unwrap(),
+                ident_pool.get(rule.ident_id).unwrap(),
+            )
+        });
+
+        let message = ConsoleMessage { level, text, rule };
+
+        if let Some(callback) = self.console_log.as_mut() {
+            callback(&message);
+        }
+    }
+
     /// Returns true of the regexp identified by the given [`RegexpId`]
     /// matches `haystack`.
     pub(crate) fn regexp_matches(
@@ -151,6 +208,13 @@ impl ScanContext<'_> {
         <dyn MessageDyn>::downcast_ref(m)
     }
 
+    /// Returns the data provided for the module with the given name, if any.
+    ///
+    /// See [`crate::Scanner::set_module_data`].
+    pub(crate) fn module_data(&self, module_name: &str) -> Option<&[u8]> {
+        self.module_data.get(module_name).map(|data| data.as_slice())
+    }
+
     /// Called during the scan process when a global rule didn't match.
     ///
     /// When this happen any other global rule in the same namespace that
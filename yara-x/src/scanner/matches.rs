@@ -84,8 +84,14 @@ impl MatchList {
         self.matches.get(i)
     }
 
-    /// Returns the number of matches that start within the given range.
-    pub fn matches_in_range(&self, range: RangeInclusive<isize>) -> i64 {
+    /// Returns the number of matches that start within the given range, at
+    /// an offset that is `range.start() + N * step`. The step must be
+    /// greater than zero.
+    pub fn matches_in_range(
+        &self,
+        range: RangeInclusive<isize>,
+        step: usize,
+    ) -> i64 {
         // If the end of the range is negative there can't be any matches in
         // that range.
         if range.end().is_negative() {
@@ -112,7 +118,10 @@ impl MatchList {
                 let mut count = 0;
                 for m in &self.matches.as_slice()[index..] {
                     if (start..=end).contains(&m.range.start) {
-                        count += 1;
+                        let delta = m.range.start as isize - range.start();
+                        if delta as usize % step == 0 {
+                            count += 1;
+                        }
                     } else {
                         break;
                     }
@@ -18,6 +18,7 @@ use std::{fs, thread};
 
 use bitvec::prelude::*;
 use fmmap::{MmapFile, MmapFileExt};
+use protobuf::MessageDyn;
 use rustc_hash::FxHashMap;
 use thiserror::Error;
 use wasmtime::{
@@ -25,7 +26,10 @@ use wasmtime::{
     Store, TypedFunc, Val, ValType,
 };
 
-use crate::compiler::{IdentId, PatternId, RuleId, RuleInfo, Rules};
+use crate::compiler::{
+    IdentId, MetaValue, PatternId, RuleId, RuleInfo, RuleLocation, Rules,
+    EXT_MODULE,
+};
 use crate::string_pool::BStringPool;
 use crate::types::{Struct, TypeValue};
 use crate::variables::VariableError;
@@ -34,17 +38,22 @@ use crate::{modules, wasm, Variable};
 
 pub(crate) use crate::scanner::context::*;
 pub use crate::scanner::matches::*;
+pub use crate::scanner::process::{MemoryRegion, SkipReason, SkippedRegion};
 
 mod context;
 mod matches;
+mod process;
 
 #[cfg(test)]
 mod tests;
 
-/// Error returned by [`Scanner::scan`] and [`Scanner::scan_file`].
+/// Error returned by [`Scanner::new`], [`Scanner::scan`],
+/// [`Scanner::scan_file`] and [`Scanner::scan_process`].
 #[derive(Error, Debug)]
 pub enum ScanError {
-    /// The scan was aborted after the timeout period.
+    /// The scan was aborted after the timeout period. The rule that was
+    /// being evaluated when the timeout was reached, if any, is returned by
+    /// [`Scanner::timeout_rule`].
     #[error("timeout")]
     Timeout,
     /// Could not open the scanned file.
@@ -53,6 +62,64 @@ pub enum ScanError {
     /// Could not map the scanned file into memory.
     #[error("can not map `{path}`: {source}")]
     MapError { path: PathBuf, source: fmmap::error::Error },
+    /// The data provided with [`Scanner::set_module_data`] for a module
+    /// that expects a protobuf message could not be decoded.
+    #[error("invalid data for module `{module}`: {err}")]
+    InvalidModuleData { module: String, err: String },
+    /// Could not read the memory of the scanned process.
+    #[error("can not read the memory of process {pid}: {source}")]
+    ProcessError { pid: u32, source: std::io::Error },
+    /// The compiled rules could not be instantiated, for instance because
+    /// they were deserialized from a file produced by a version of YARA-X
+    /// that used functions not available in the current one.
+    #[error("can not instantiate the rules: {err}")]
+    InstantiationError { err: String },
+}
+
+/// Severity level of a message emitted by the `console` module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleLevel {
+    /// Emitted by `console.log` and `console.hex`.
+    Info,
+    /// Emitted by `console.warn`.
+    Warning,
+    /// Emitted by `console.error`.
+    Error,
+}
+
+/// A message emitted by the `console` module while evaluating the condition
+/// of some rule.
+///
+/// Messages are passed to the callback set with [`Scanner::console_log`].
+pub struct ConsoleMessage<'a> {
+    level: ConsoleLevel,
+    text: &'a str,
+    rule: Option<(&'a str, &'a str)>,
+}
+
+impl<'a> ConsoleMessage<'a> {
+    /// Returns the message's severity level.
+    pub fn level(&self) -> ConsoleLevel {
+        self.level
+    }
+
+    /// Returns the message's text.
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns the name of the rule that emitted the message.
+    ///
+    /// This is `None` if the message was not emitted while evaluating a
+    /// rule condition.
+    pub fn rule_name(&self) -> Option<&'a str> {
+        self.rule.map(|(_, name)| name)
+    }
+
+    /// Returns the namespace of the rule that emitted the message.
+    pub fn rule_namespace(&self) -> Option<&'a str> {
+        self.rule.map(|(namespace, _)| namespace)
+    }
 }
 
 /// Global counter that gets incremented every 1 second by a dedicated thread.
@@ -88,14 +155,21 @@ pub struct Scanner<'r> {
     wasm_store: Pin<Box<Store<ScanContext<'r>>>>,
     wasm_main_func: TypedFunc<(), ()>,
     filesize: Global,
+    current_rule: Global,
     timeout: Option<Duration>,
+    timeout_rule: Option<RuleId>,
+    max_process_memory: usize,
 }
 
 impl<'r> Scanner<'r> {
     const DEFAULT_MAX_MATCHES_PER_PATTERN: usize = 1_000_000;
+    const DEFAULT_MAX_PROCESS_MEMORY: usize = 1 << 31;
 
     /// Creates a new scanner.
-    pub fn new(rules: &'r Rules) -> Self {
+    ///
+    /// Returns [`ScanError::InstantiationError`] if the rules can't be
+    /// instantiated.
+    pub fn new(rules: &'r Rules) -> Result<Self, ScanError> {
         let num_rules = rules.rules().len() as u32;
         let num_patterns = rules.num_patterns() as u32;
 
@@ -124,12 +198,19 @@ impl<'r> Scanner<'r> {
                 main_memory: None,
                 vars_stack: Vec::new(),
                 module_outputs: FxHashMap::default(),
+                module_data: FxHashMap::default(),
+                module_array_limits: FxHashMap::default(),
+                ext_descriptor: rules
+                    .ext_schema()
+                    .and_then(|schema| schema.root_descriptor().ok()),
                 pattern_matches: FxHashMap::default(),
                 unconfirmed_matches: FxHashMap::default(),
                 deadline: 0,
                 limit_reached: BitVec::repeat(false, num_patterns as usize),
                 max_matches_per_pattern: Self::DEFAULT_MAX_MATCHES_PER_PATTERN,
                 regexp_cache: RefCell::new(FxHashMap::default()),
+                current_rule: None,
+                console_log: None,
             },
         ));
 
@@ -157,6 +238,15 @@ impl<'r> Scanner<'r> {
         )
         .unwrap();
 
+        // Global variable that contains the ID of the rule whose condition
+        // is being evaluated, or -1 if no rule is being evaluated.
+        let current_rule = Global::new(
+            wasm_store.as_context_mut(),
+            GlobalType::new(ValType::I32, Mutability::Var),
+            Val::I32(-1),
+        )
+        .unwrap();
+
         // Compute the base offset for the bitmap that contains matching
         // information for patterns. This bitmap has 1 bit per pattern,
         // the N-th bit is set if pattern with PatternId = N matched. The
@@ -186,7 +276,7 @@ impl<'r> Scanner<'r> {
         // Instantiate the module. This takes the wasm code provided by the
         // `wasm_mod` function and links its imported functions with the
         // implementations that YARA provides (see wasm.rs).
-        let wasm_instance = wasm::new_linker()
+        let wasm_instance = wasm::linker()
             .define(wasm_store.as_context(), "yara_x", "filesize", filesize)
             .unwrap()
             .define(
@@ -196,6 +286,13 @@ impl<'r> Scanner<'r> {
                 pattern_search_done,
             )
             .unwrap()
+            .define(
+                wasm_store.as_context(),
+                "yara_x",
+                "current_rule",
+                current_rule,
+            )
+            .unwrap()
             .define(
                 wasm_store.as_context(),
                 "yara_x",
@@ -211,7 +308,9 @@ impl<'r> Scanner<'r> {
             )
             .unwrap()
             .instantiate(wasm_store.as_context_mut(), rules.wasm_mod())
-            .unwrap();
+            .map_err(|err| ScanError::InstantiationError {
+                err: err.to_string(),
+            })?;
 
         // Obtain a reference to the "main" function exported by the module.
         let wasm_main_func = wasm_instance
@@ -219,8 +318,17 @@ impl<'r> Scanner<'r> {
             .unwrap();
 
         wasm_store.data_mut().main_memory = Some(main_memory);
-
-        Self { wasm_store, wasm_main_func, filesize, timeout: None }
+        wasm_store.data_mut().current_rule = Some(current_rule);
+
+        Ok(Self {
+            wasm_store,
+            wasm_main_func,
+            filesize,
+            current_rule,
+            timeout: None,
+            timeout_rule: None,
+            max_process_memory: Self::DEFAULT_MAX_PROCESS_MEMORY,
+        })
     }
 
     /// Sets a timeout for scan operations.
@@ -237,6 +345,20 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Returns the location of the rule that was being evaluated when the
+    /// last scan timed out.
+    ///
+    /// After a scan function returns [`ScanError::Timeout`], this returns
+    /// the rule whose condition was being evaluated when the timeout was
+    /// reached. The result is `None` if the last scan didn't time out, or
+    /// if the timeout was reached while searching for patterns, before
+    /// evaluating any rule.
+    pub fn timeout_rule(&self) -> Option<RuleLocation> {
+        self.timeout_rule.map(|rule_id| {
+            self.wasm_store.data().compiled_rules.rule_location(rule_id)
+        })
+    }
+
     /// Sets the maximum number of matches per pattern.
     ///
     /// When some pattern reaches the maximum number of patterns it won't
@@ -246,6 +368,71 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Sets the maximum number of bytes read from a process scanned with
+    /// [`Scanner::scan_process`]. The default is 2GB.
+    pub fn max_process_memory(&mut self, n: usize) -> &mut Self {
+        self.max_process_memory = n;
+        self
+    }
+
+    /// Sets the data that will be passed to the module with the given name.
+    ///
+    /// Some modules don't obtain their data from the scanned file, but
+    /// from some external source that must be provided by the user. For
+    /// instance, the `cuckoo` module expects a JSON report produced by the
+    /// Cuckoo sandbox, and the `ext` module expects a protobuf message that
+    /// follows the schema passed to [`crate::Compiler::ext_module_schema`].
+    /// The data is used in all subsequent scans, until it is replaced by
+    /// another call to this function.
+    pub fn set_module_data(
+        &mut self,
+        module_name: &str,
+        data: &[u8],
+    ) -> &mut Self {
+        self.wasm_store
+            .data_mut()
+            .module_data
+            .insert(module_name.to_string(), data.to_vec());
+        self
+    }
+
+    /// Sets the maximum number of items in the arrays produced by the module
+    /// with the given name.
+    ///
+    /// Some files can make a module produce huge arrays (e.g. a file with
+    /// hundreds of thousands of imports), which slows down the evaluation of
+    /// conditions that iterate over them. Arrays with more than `n` items,
+    /// including the ones in nested structures, are truncated to their first
+    /// `n` items. When an array `foo` is truncated, the boolean field
+    /// `foo_truncated` is set to true if the module declares it, allowing
+    /// rules to detect the truncation.
+    pub fn max_module_array_len(
+        &mut self,
+        module_name: &str,
+        n: usize,
+    ) -> &mut Self {
+        self.wasm_store
+            .data_mut()
+            .module_array_limits
+            .insert(module_name.to_string(), n);
+        self
+    }
+
+    /// Sets a callback that receives the messages emitted by the `console`
+    /// module.
+    ///
+    /// Functions like `console.log` or `console.warn` don't print anything
+    /// by themselves, their messages are passed to this callback together
+    /// with their severity level and the rule that emitted them. If no
+    /// callback is set, the messages are discarded.
+    pub fn console_log<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(&ConsoleMessage) + 'r,
+    {
+        self.wasm_store.data_mut().console_log = Some(Box::new(callback));
+        self
+    }
+
     /// Scans a file.
     pub fn scan_file<'a, P>(
         &'a mut self,
@@ -291,6 +478,35 @@ impl<'r> Scanner<'r> {
         self.scan_impl(ScannedData::Slice(data))
     }
 
+    /// Scans the memory of a running process.
+    ///
+    /// The readable memory regions of the process are scanned as if they
+    /// were a single file, with each region following the previous one.
+    /// The offsets of the matches are within that data, and they can be
+    /// translated into addresses within the process with the regions
+    /// returned by [`ScanResults::memory_regions`]. Notice that a pattern
+    /// can match data that crosses the boundary between two regions.
+    ///
+    /// At most [`Scanner::max_process_memory`] bytes are read from the
+    /// process. Regions that exceed that limit, or that can't be read, are
+    /// not scanned, and they are returned by [`ScanResults::skipped_regions`].
+    ///
+    /// This is supported only in Linux, and requires privileges for
+    /// attaching to the process with `ptrace`.
+    pub fn scan_process<'a>(
+        &'a mut self,
+        pid: u32,
+    ) -> Result<ScanResults<'a, 'r>, ScanError> {
+        let memory = process::read_memory(pid, self.max_process_memory)
+            .map_err(|source| ScanError::ProcessError { pid, source })?;
+
+        let mut results = self.scan_impl(ScannedData::Vec(memory.data))?;
+        results.memory_regions = memory.regions;
+        results.skipped_regions = memory.skipped;
+
+        Ok(results)
+    }
+
     /// Sets the value of a global variable.
     ///
     /// The variable must has been previously defined by calling
@@ -334,6 +550,7 @@ impl<'r> Scanner<'r> {
     ) -> Result<ScanResults<'a, 'r>, ScanError> {
         // Clear information about matches found in a previous scan, if any.
         self.clear_matches();
+        self.timeout_rule = None;
 
         // If the user specified some timeout, start the heartbeat thread, if
         // not previously started. The heartbeat thread increments the WASM
@@ -359,8 +576,11 @@ impl<'r> Scanner<'r> {
         }
 
         // Timeout in seconds, this is either the value provided by the user or
-        // u64::MAX.
-        let timeout_secs = self.timeout.map_or(u64::MAX, |t| t.as_secs());
+        // u32::MAX. The latter is large enough for being considered infinite,
+        // but unlike u64::MAX, it doesn't overflow when added to the current
+        // epoch, which is non-zero once the heartbeat thread is running.
+        let timeout_secs =
+            self.timeout.map_or(u32::MAX as u64, |t| t.as_secs());
 
         // Sets the deadline for the WASM store. The WASM main function will
         // abort if the deadline is reached while the function is being
@@ -377,6 +597,11 @@ impl<'r> Scanner<'r> {
             )
             .unwrap();
 
+        // No rule is being evaluated yet.
+        self.current_rule
+            .set(self.wasm_store.as_context_mut(), Val::I32(-1))
+            .unwrap();
+
         let ctx = self.wasm_store.data_mut();
 
         ctx.deadline =
@@ -396,27 +621,52 @@ impl<'r> Scanner<'r> {
             // Lookup the module in the list of built-in modules.
             let module = modules::BUILTIN_MODULES.get(module_name).unwrap();
 
+            // The structure of the `ext` module is defined by the user. If
+            // the rules have a schema for the module, it is used instead of
+            // the module's built-in structure.
+            let root_struct_descriptor = match &ctx.ext_descriptor {
+                Some(descriptor) if module_name == EXT_MODULE => {
+                    descriptor.clone()
+                }
+                _ => module.root_struct_descriptor.clone(),
+            };
+
             // Call the module's main function, if any. This function returns
             // a data structure serialized as a protocol buffer. The format of
             // the data is specified by the .proto file associated to the
             // module.
-            let module_output = if let Some(main_fn) = module.main_fn {
+            let mut module_output = if let Some(main_fn) = module.main_fn {
                 main_fn(ctx)
             } else {
-                // Implement the case in which the module doesn't have a main
-                // function and the serialized data should be provided by the
-                // user.
-                todo!()
+                // The module doesn't have a main function, its data is
+                // provided by the user with `Scanner::set_module_data` as a
+                // serialized protobuf. If no data was provided, the module
+                // produces an empty message.
+                match ctx.module_data(module_name) {
+                    Some(data) => root_struct_descriptor
+                        .parse_from_bytes(data)
+                        .map_err(|err| ScanError::InvalidModuleData {
+                            module: module_name.to_string(),
+                            err: err.to_string(),
+                        })?,
+                    None => root_struct_descriptor.new_instance(),
+                }
             };
 
+            // Truncate the arrays in the module's output if the user limited
+            // their size.
+            if let Some(max_len) = ctx.module_array_limits.get(module_name) {
+                modules::truncate_arrays(module_output.as_mut(), *max_len);
+            }
+
             // Make sure that the module is returning a protobuf message of the
             // expected type.
             debug_assert_eq!(
                 module_output.descriptor_dyn().full_name(),
-                module.root_struct_descriptor.full_name(),
+                root_struct_descriptor.full_name(),
                 "main function of module `{}` must return `{}`, but returned `{}`",
                 module_name,
-                module.root_struct_descriptor.full_name(),
+                root_struct_descriptor.full_name(),
                 module_output.descriptor_dyn().full_name(),
             );
 
@@ -427,7 +677,7 @@ impl<'r> Scanner<'r> {
                 module_output.is_initialized_dyn(),
                 "module `{}` returned a protobuf `{}` where some required fields are not initialized ",
                 module_name,
-                module.root_struct_descriptor.full_name()
+                root_struct_descriptor.full_name()
             );
 
             // When constant folding is enabled we don't need to generate
@@ -475,6 +725,16 @@ impl<'r> Scanner<'r> {
         let func_result =
             self.wasm_main_func.call(self.wasm_store.as_context_mut(), ());
 
+        // The rule that was being evaluated when the main function returned.
+        // If the main function was aborted, this is the rule that caused it.
+        let current_rule = match self
+            .current_rule
+            .get(self.wasm_store.as_context_mut())
+        {
+            Val::I32(rule_id) if rule_id >= 0 => Some(RuleId::from(rule_id)),
+            _ => None,
+        };
+
         let ctx = self.wasm_store.data_mut();
 
         // Set pointer to data back to nil. This means that accessing
@@ -502,12 +762,24 @@ impl<'r> Scanner<'r> {
         match func_result {
             Ok(_) => Ok(ScanResults::new(self.wasm_store.data(), data)),
             Err(err) if err.is::<ScanError>() => {
-                Err(err.downcast::<ScanError>().unwrap())
+                let err = err.downcast::<ScanError>().unwrap();
+                if matches!(err, ScanError::Timeout) {
+                    self.timeout_rule = current_rule;
+                }
+                Err(err)
             }
-            Err(err) => panic!(
-                "unexpected error while executing WASM main function: {}",
-                err
-            ),
+            Err(err) => match current_rule.map(|rule_id| {
+                self.wasm_store.data().compiled_rules.rule_location(rule_id)
+            }) {
+                Some(location) => panic!(
+                    "unexpected error while evaluating {}: {}",
+                    location, err
+                ),
+                None => panic!(
+                    "unexpected error while executing WASM main function: {}",
+                    err
+                ),
+            },
         }
     }
 
@@ -570,11 +842,18 @@ impl<'r> Scanner<'r> {
 pub struct ScanResults<'a, 'r> {
     ctx: &'a ScanContext<'r>,
     data: ScannedData<'a>,
+    memory_regions: Vec<MemoryRegion>,
+    skipped_regions: Vec<SkippedRegion>,
 }
 
 impl<'a, 'r> ScanResults<'a, 'r> {
     fn new(ctx: &'a ScanContext<'r>, data: ScannedData<'a>) -> Self {
-        Self { ctx, data }
+        Self {
+            ctx,
+            data,
+            memory_regions: Vec::new(),
+            skipped_regions: Vec::new(),
+        }
     }
 
     /// Returns an iterator that yields the matching rules in arbitrary order.
@@ -586,6 +865,74 @@ impl<'a, 'r> ScanResults<'a, 'r> {
     pub fn non_matching_rules(&'a self) -> NonMatchingRules<'a, 'r> {
         NonMatchingRules::new(self.ctx, &self.data)
     }
+
+    /// Returns an iterator that yields the name of each module imported by
+    /// the rules, together with the data produced by the module for the
+    /// scanned data.
+    ///
+    /// The module's data is a protobuf message, its structure is described
+    /// by the `.proto` file that defines the module.
+    pub fn module_outputs(&'a self) -> ModuleOutputs<'a, 'r> {
+        ModuleOutputs::new(self.ctx)
+    }
+
+    /// Returns the memory regions of the process scanned with
+    /// [`Scanner::scan_process`], sorted by their offset within the scanned
+    /// data. The slice is empty for other kinds of scans.
+    pub fn memory_regions(&self) -> &[MemoryRegion] {
+        self.memory_regions.as_slice()
+    }
+
+    /// Returns the readable memory regions of the process scanned with
+    /// [`Scanner::scan_process`] that were not scanned, sorted by address.
+    pub fn skipped_regions(&self) -> &[SkippedRegion] {
+        self.skipped_regions.as_slice()
+    }
+
+    /// Returns the memory region that contains the given offset within the
+    /// scanned data, if the scanned data is the memory of a process.
+    pub fn memory_region(&self, offset: usize) -> Option<&MemoryRegion> {
+        let index = self
+            .memory_regions
+            .partition_point(|region| region.offset + region.size <= offset);
+        self.memory_regions.get(index).filter(|region| region.offset <= offset)
+    }
+}
+
+/// Iterator that yields the output of the modules imported by the rules.
+pub struct ModuleOutputs<'a, 'r> {
+    ctx: &'a ScanContext<'r>,
+    imports: crate::compiler::Imports<'r>,
+}
+
+impl<'a, 'r> ModuleOutputs<'a, 'r> {
+    fn new(ctx: &'a ScanContext<'r>) -> Self {
+        Self { ctx, imports: ctx.compiled_rules.imports() }
+    }
+}
+
+impl<'a, 'r> Iterator for ModuleOutputs<'a, 'r> {
+    type Item = (&'r str, &'a dyn MessageDyn);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for module_name in self.imports.by_ref() {
+            let descriptor = match &self.ctx.ext_descriptor {
+                Some(descriptor) if module_name == EXT_MODULE => {
+                    descriptor.clone()
+                }
+                _ => modules::BUILTIN_MODULES
+                    .get(module_name)?
+                    .root_struct_descriptor
+                    .clone(),
+            };
+            if let Some(output) =
+                self.ctx.module_outputs.get(descriptor.full_name())
+            {
+                return Some((module_name, output.as_ref()));
+            }
+        }
+        None
+    }
 }
 
 /// Iterator that yields the rules that matched during a scan.
@@ -709,6 +1056,24 @@ impl<'a, 'r> Rule<'a, 'r> {
         self.rules.ident_pool().get(self.rule_info.namespace_ident_id).unwrap()
     }
 
+    /// Returns the tags associated to this rule, in alphabetical order.
+    pub fn tags(&self) -> impl Iterator<Item = &'r str> {
+        let ident_pool = self.rules.ident_pool();
+        self.rule_info.tags.iter().map(|tag| ident_pool.get(*tag).unwrap())
+    }
+
+    /// Returns the metadata associated to this rule, in the same order in
+    /// which they appear in the source code.
+    pub fn metadata(&self) -> impl Iterator<Item = (&'r str, MetaValue<'r>)> {
+        let rules = self.rules;
+        self.rule_info.metadata.iter().map(|(ident_id, value)| {
+            (
+                rules.ident_pool().get(*ident_id).unwrap(),
+                rules.meta_value(value),
+            )
+        })
+    }
+
     /// Returns the patterns defined by this rule.
     pub fn patterns(&self) -> Patterns<'a, 'r> {
         Patterns {
@@ -0,0 +1,185 @@
+/*! Reads the memory of running processes.
+
+The readable regions of the process' address space are copied one after
+the other into a single buffer, which is scanned as any other data. The
+regions are kept in a table that allows translating offsets within the
+buffer into addresses within the process. The total size of the buffer is
+limited, and the regions that don't fit in it are skipped, together with
+the ones that are too large or can't be read.
+
+Only Linux is supported, where the regions are listed in `/proc/<pid>/maps`
+and read from `/proc/<pid>/mem`. Reading the memory of other processes
+requires the same privileges as attaching to them with `ptrace`.
+*/
+
+use std::io;
+
+/// A memory region of a scanned process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Address where the region starts within the process.
+    pub address: u64,
+    /// Offset where the region starts within the scanned data.
+    pub offset: usize,
+    /// Size of the region in bytes.
+    pub size: usize,
+    /// Permissions of the region, like `r-xp`.
+    pub permissions: String,
+    /// Path of the file mapped in the region, or a pseudo-path like
+    /// `[heap]` or `[stack]`. `None` for anonymous regions.
+    pub path: Option<String>,
+}
+
+impl MemoryRegion {
+    /// Returns the address within the process that corresponds to an
+    /// offset within the scanned data, which must be within this region.
+    pub fn address_of(&self, offset: usize) -> u64 {
+        self.address + (offset - self.offset) as u64
+    }
+}
+
+/// Reasons for not scanning a readable memory region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The region is larger than the maximum size of a single region.
+    TooLarge,
+    /// The region doesn't fit in the maximum amount of memory that is read
+    /// from the process.
+    LimitReached,
+    /// Reading the region failed.
+    Unreadable,
+}
+
+/// A readable memory region of a scanned process that was not scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRegion {
+    /// Address where the region starts within the process.
+    pub address: u64,
+    /// Size of the region in bytes.
+    pub size: u64,
+    /// Permissions of the region, like `r-xp`.
+    pub permissions: String,
+    /// Path of the file mapped in the region, or a pseudo-path like
+    /// `[heap]` or `[stack]`. `None` for anonymous regions.
+    pub path: Option<String>,
+    /// Why the region was not scanned.
+    pub reason: SkipReason,
+}
+
+/// The memory read from a process.
+pub(crate) struct ProcessMemory {
+    /// The content of the scanned regions, one after the other.
+    pub data: Vec<u8>,
+    /// The scanned regions, sorted by their offset within `data`.
+    pub regions: Vec<MemoryRegion>,
+    /// The readable regions that were not scanned.
+    pub skipped: Vec<SkippedRegion>,
+}
+
+/// Regions larger than this are not read.
+#[cfg(target_os = "linux")]
+const MAX_REGION_SIZE: u64 = 1 << 30;
+
+/// Returns the content of the readable memory regions of a process, and
+/// the table of regions. At most `max_size` bytes are read, regions that
+/// would exceed that limit are skipped.
+#[cfg(target_os = "linux")]
+pub(crate) fn read_memory(
+    pid: u32,
+    max_size: usize,
+) -> io::Result<ProcessMemory> {
+    use std::fs;
+    use std::os::unix::fs::FileExt;
+
+    let maps = fs::read_to_string(format!("/proc/{}/maps", pid))?;
+    let mem = fs::File::open(format!("/proc/{}/mem", pid))?;
+
+    let mut data = Vec::new();
+    let mut regions = Vec::new();
+    let mut skipped = Vec::new();
+
+    for line in maps.lines() {
+        // Each line looks like:
+        // 7f2d3c000000-7f2d3c021000 rw-p 00000000 00:00 0    [heap]
+        let mut fields = line.splitn(6, ' ');
+
+        let (start, end) = match fields.next().and_then(|r| r.split_once('-'))
+        {
+            Some(range) => range,
+            None => continue,
+        };
+
+        let (start, end) = match (
+            u64::from_str_radix(start, 16),
+            u64::from_str_radix(end, 16),
+        ) {
+            (Ok(start), Ok(end)) if start < end => (start, end),
+            _ => continue,
+        };
+
+        let permissions = fields.next().unwrap_or_default();
+        let path = fields.nth(3).map(str::trim).filter(|p| !p.is_empty());
+
+        // `[vvar]` can't be read, and `[vsyscall]` is not really mapped
+        // into the process.
+        if !permissions.starts_with('r')
+            || matches!(path, Some("[vvar]") | Some("[vsyscall]"))
+        {
+            continue;
+        }
+
+        let mut skip = |reason| {
+            skipped.push(SkippedRegion {
+                address: start,
+                size: end - start,
+                permissions: permissions.to_string(),
+                path: path.map(String::from),
+                reason,
+            })
+        };
+
+        if end - start > MAX_REGION_SIZE {
+            skip(SkipReason::TooLarge);
+            continue;
+        }
+
+        let offset = data.len();
+        let size = (end - start) as usize;
+
+        if size > max_size - offset {
+            skip(SkipReason::LimitReached);
+            continue;
+        }
+
+        data.resize(offset + size, 0);
+
+        // Regions that can't be read, like the ones backed by a truncated
+        // file, are skipped.
+        if mem.read_exact_at(&mut data[offset..], start).is_err() {
+            data.truncate(offset);
+            skip(SkipReason::Unreadable);
+            continue;
+        }
+
+        regions.push(MemoryRegion {
+            address: start,
+            offset,
+            size,
+            permissions: permissions.to_string(),
+            path: path.map(String::from),
+        });
+    }
+
+    Ok(ProcessMemory { data, regions, skipped })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn read_memory(
+    _pid: u32,
+    _max_size: usize,
+) -> io::Result<ProcessMemory> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "scanning processes is supported only in Linux",
+    ))
+}
@@ -1,8 +1,12 @@
+use std::time::Duration;
+
 use pretty_assertions::assert_eq;
+use yara_x_parser::SourceCode;
 
 use crate::scanner;
-use crate::scanner::Scanner;
+use crate::scanner::{ScanError, Scanner, SkipReason};
 use crate::variables::VariableError;
+use crate::MetaValue;
 
 #[test]
 fn iterators() {
@@ -16,7 +20,7 @@ rule rule_4 { condition: false }
     )
     .unwrap();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     let results = scanner.scan(&[]).expect("scan should not fail");
 
     let mut iter = results.matching_rules();
@@ -55,7 +59,7 @@ fn matches() {
     .unwrap();
 
     let mut matches = vec![];
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     let results = scanner.scan(b"foobar").expect("scan should not fail");
 
     for matching_rules in results.matching_rules() {
@@ -74,6 +78,37 @@ fn matches() {
     )
 }
 
+#[test]
+fn tags_and_metadata() {
+    let rules = crate::compile(
+        r#"
+        rule test : foo bar {
+            meta:
+                author = "x"
+                version = 2
+                draft = false
+            condition:
+                true
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+    let results = scanner.scan(b"").expect("scan should not fail");
+    let rule = results.matching_rules().next().unwrap();
+
+    assert_eq!(rule.tags().collect::<Vec<_>>(), ["bar", "foo"]);
+    assert_eq!(
+        rule.metadata().collect::<Vec<_>>(),
+        [
+            ("author", MetaValue::String("x")),
+            ("version", MetaValue::Integer(2)),
+            ("draft", MetaValue::Bool(false)),
+        ]
+    );
+}
+
 #[test]
 fn xor_matches() {
     let rules = crate::compile(
@@ -91,6 +126,7 @@ fn xor_matches() {
     let mut matches = vec![];
 
     for matching_rules in Scanner::new(&rules)
+        .unwrap()
         .scan(b"lhrrhrrhqqh")
         .expect("scan should not fail")
         .matching_rules()
@@ -122,7 +158,7 @@ fn reuse_scanner() {
     )
     .unwrap();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
 
     assert_eq!(
         scanner
@@ -169,7 +205,7 @@ fn variables_1() {
 
     let rules = compiler.build();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
 
     assert_eq!(
         scanner
@@ -236,7 +272,7 @@ fn variables_2() {
 
     let rules = compiler.build();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     assert_eq!(
         scanner
             .scan(&[])
@@ -307,7 +343,7 @@ fn global_rules() {
         .unwrap();
 
     let rules = compiler.build();
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     let results = scanner.scan(&[]).expect("scan should not fail");
 
     assert_eq!(results.matching_rules().len(), 1);
@@ -354,7 +390,7 @@ fn private_rules() {
 
     let rules = compiler.build();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     let scan_results = scanner.scan(&[]).expect("scan should not fail");
 
     // Only the matching non-private rule should be reported.
@@ -383,7 +419,7 @@ fn max_matches_per_pattern() {
 
     let rules = compiler.build();
 
-    let mut scanner = Scanner::new(&rules);
+    let mut scanner = Scanner::new(&rules).unwrap();
     scanner.max_matches_per_pattern(1);
     let scan_results =
         scanner.scan(b"foofoofoo").expect("scan should not fail");
@@ -408,3 +444,179 @@ fn max_matches_per_pattern() {
 
     assert_eq!(matches.next(), None);
 }
+
+#[test]
+fn timeout() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .add_source(
+            SourceCode::from(
+                r#"
+rule fast { condition: filesize == 0 }
+
+rule slow {
+  condition:
+    for any i in (0..0xffffffffffff) : ( i < 0 )
+}"#,
+            )
+            .with_origin("slow.yar"),
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+    scanner.timeout(Duration::from_secs(1));
+
+    let err = scanner.scan(b"").err().unwrap();
+
+    assert!(matches!(err, ScanError::Timeout));
+
+    let location = scanner.timeout_rule().unwrap();
+
+    assert_eq!(location.to_string(), "rule `slow` at slow.yar:4:6");
+
+    assert_eq!(location.namespace(), "default");
+    assert_eq!(location.name(), "slow");
+    assert_eq!(location.origin(), Some("slow.yar"));
+    assert_eq!(location.line(), Some(4));
+    assert_eq!(location.column(), Some(6));
+}
+
+#[cfg(feature = "test_proto2-module")]
+#[test]
+fn max_module_array_len() {
+    let rules = crate::compile(
+        r#"
+import "test_proto2"
+rule truncated {
+  condition:
+    test_proto2.array_int64[1] == 10 and
+    not defined test_proto2.array_int64[2] and
+    test_proto2.array_int64_truncated and
+    not defined test_proto2.array_struct[0].nested_array_int64[2] and
+    test_proto2.array_struct[0].nested_array_int64_truncated and
+    test_proto2.nested.nested_array_int64_truncated
+}
+rule not_truncated {
+  condition:
+    test_proto2.array_int64[2] == 100 and
+    not defined test_proto2.array_int64_truncated and
+    not defined test_proto2.nested.nested_array_int64_truncated
+}
+"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+
+    let matching_rules = |scanner: &mut Scanner| {
+        scanner
+            .scan(b"")
+            .unwrap()
+            .matching_rules()
+            .map(|rule| rule.name().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Without a limit the arrays are not truncated.
+    assert_eq!(matching_rules(&mut scanner), vec!["not_truncated"]);
+
+    scanner.max_module_array_len("test_proto2", 2);
+    assert_eq!(matching_rules(&mut scanner), vec!["truncated"]);
+
+    // Arrays that don't exceed the limit are left untouched.
+    scanner.max_module_array_len("test_proto2", 3);
+    assert_eq!(matching_rules(&mut scanner), vec!["not_truncated"]);
+}
+
+#[test]
+fn module_outputs() {
+    let rules = crate::compile(
+        r#"
+import "test_proto2"
+rule test { condition: test_proto2.int64_one == 1 }
+"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+    let results = scanner.scan(b"").unwrap();
+
+    let outputs = results.module_outputs().collect::<Vec<_>>();
+
+    assert_eq!(outputs.len(), 1);
+
+    let (name, output) = outputs[0];
+    let field = output.descriptor_dyn().field_by_name("int64_one").unwrap();
+
+    assert_eq!(name, "test_proto2");
+    assert_eq!(field.get_singular(output).unwrap().to_i64(), Some(1));
+
+    // Modules that are not imported by the rules don't produce any output.
+    let rules = crate::compile("rule test { condition: true }").unwrap();
+    let mut scanner = Scanner::new(&rules).unwrap();
+    let results = scanner.scan(b"").unwrap();
+
+    assert_eq!(results.module_outputs().count(), 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn scan_process() {
+    let rules = crate::compile(
+        r#"
+rule test {
+  strings:
+    $a = "yara-x process scanning marker"
+  condition:
+    $a
+}
+"#,
+    )
+    .unwrap();
+
+    // The marker is copied into the heap, where it has a known address.
+    let marker =
+        std::hint::black_box(b"yara-x process scanning marker".to_vec());
+
+    let mut scanner = Scanner::new(&rules).unwrap();
+    let results = scanner.scan_process(std::process::id()).unwrap();
+
+    assert!(!results.memory_regions().is_empty());
+
+    let rule = results.matching_rules().next().unwrap();
+    let pattern = rule.patterns().next().unwrap();
+
+    let addresses = pattern
+        .matches()
+        .map(|m| {
+            let region = results.memory_region(m.range.start).unwrap();
+            assert!(region.permissions.starts_with('r'));
+            region.address_of(m.range.start)
+        })
+        .collect::<Vec<_>>();
+
+    assert!(addresses.contains(&(marker.as_ptr() as u64)));
+
+    // Regions that exceed the limit are skipped.
+    let results = scanner
+        .max_process_memory(4096)
+        .scan_process(std::process::id())
+        .unwrap();
+
+    assert!(
+        results.memory_regions().iter().map(|r| r.size).sum::<usize>() <= 4096
+    );
+    assert!(results
+        .skipped_regions()
+        .iter()
+        .any(|r| r.reason == SkipReason::LimitReached));
+
+    // Processes that don't exist can't be scanned.
+    assert!(matches!(
+        scanner.scan_process(u32::MAX),
+        Err(ScanError::ProcessError { pid: u32::MAX, .. })
+    ));
+}
@@ -70,6 +70,22 @@ where
     }
 }
 
+impl<T> Clone for StringPool<T>
+where
+    T: From<u32> + Into<u32>,
+{
+    /// Creates a copy of the pool. Strings are interned in the same order
+    /// they were interned in the original pool, therefore they have the
+    /// same IDs in both pools.
+    fn clone(&self) -> Self {
+        let mut pool = StringPool::new();
+        for string in self.pool.strings() {
+            pool.get_or_intern(string);
+        }
+        pool
+    }
+}
+
 impl<T> Serialize for StringPool<T>
 where
     T: From<u32> + Into<u32>,
@@ -204,6 +220,12 @@ where
             })
     }
 
+    /// Returns the number of strings stored in the pool.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
     /// Returns the total size in bytes of all the strings stored in the pool.
     #[inline]
     pub fn size(&self) -> usize {
@@ -211,6 +233,22 @@ where
     }
 }
 
+impl<T> Clone for BStringPool<T>
+where
+    T: From<u32> + Into<u32>,
+{
+    /// Creates a copy of the pool. Strings are interned in the same order
+    /// they were interned in the original pool, therefore they have the
+    /// same IDs in both pools.
+    fn clone(&self) -> Self {
+        let mut pool = BStringPool::new();
+        for string in self.pool.bytestrings() {
+            pool.get_or_intern(string);
+        }
+        pool
+    }
+}
+
 impl<T> Serialize for BStringPool<T>
 where
     T: From<u32> + Into<u32>,
@@ -23,6 +23,7 @@ macro_rules! test_condition {
         let rules = crate::compile(src.as_str()).unwrap();
 
         let num_matching_rules = crate::scanner::Scanner::new(&rules)
+            .unwrap()
             .scan($data)
             .expect("scan should not fail")
             .matching_rules()
@@ -59,6 +60,7 @@ macro_rules! test_rule {
         let rules = crate::compile($rule).unwrap();
 
         let num_matching_rules = crate::scanner::Scanner::new(&rules)
+            .unwrap()
             .scan($data)
             .expect("scan should not fail")
             .matching_rules()
@@ -120,7 +122,7 @@ macro_rules! pattern_match {
 
         let rules = crate::compile(src.as_str()).unwrap();
 
-        let mut scanner = crate::scanner::Scanner::new(&rules);
+        let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
         let scan_results = scanner.scan($data).expect("scan should not fail");
         let matching_data = scan_results
             .matching_rules()
@@ -179,6 +181,30 @@ fn arithmetic_operations() {
     condition_true!("5 \\ 2 \\ 2 == 1");
     condition_true!("7 \\ 2 \\ 2.0 == 1.5");
     condition_true!("7 % 4 % 2 == 1");
+    condition_true!("1e3 == 1000");
+    condition_true!("1.5e3 == 1500");
+    condition_true!("1E-2 == 0.01");
+    condition_true!("-2.5e+1 == -25");
+    condition_true!("0x7fffffffffffffff + 1 < 0");
+    condition_true!("-0x7fffffffffffffff - 2 > 0");
+}
+
+#[test]
+fn conversion_functions() {
+    condition_true!("to_int(1.9) == 1");
+    condition_true!("to_int(-1.9) == -1");
+    condition_true!("to_int(1e18) == 1000000000000000000");
+    condition_true!(r#"to_int("123") == 123"#);
+    condition_true!(r#"to_int("-123") == -123"#);
+    condition_true!("to_float(2) == 2.0");
+    condition_true!(r#"to_float("1.5") == 1.5"#);
+    condition_true!(r#"to_float("1e3") == 1000"#);
+    condition_false!("to_int(1e19) == 0");
+    condition_false!("to_int(1e19) != 0");
+    condition_false!(r#"to_int("1.5") == 1"#);
+    condition_false!(r#"to_int("foo") != 0"#);
+    condition_false!(r#"to_float("foo") != 0"#);
+    condition_false!(r#"to_float("inf") != 0"#);
 }
 
 #[test]
@@ -412,6 +438,14 @@ fn for_in() {
     condition_false!("for 50% i in (0..10) : ( i >= 6 )");
     condition_true!("for 10% i in (0..9) : ( i == 0 )");
     condition_false!("for 11% i in (0..9) : ( i == 0 )");
+    condition_true!("for all i in (0..10 step 5) : ( i % 5 == 0 )");
+    condition_true!("for 3 i in (0..10 step 5) : ( true )");
+    condition_false!("for 4 i in (0..10 step 5) : ( true )");
+    condition_true!("for any i in (1..10 step 3) : ( i == 10 )");
+    condition_false!("for any i in (1..9 step 3) : ( i == 9 )");
+    condition_true!("for 50% i in (0..9 step 3) : ( i < 5 )");
+    condition_true!("for all i in (0..10 step filesize + 2) : ( i % 2 == 0 )");
+    condition_false!("for any i in (0..10 step filesize) : ( true )");
 
     // If the range's lower bound is greater than the upper bound
     // the `for` loop is always false. The outer loop is only for
@@ -460,6 +494,29 @@ fn for_in() {
     condition_true!(r#"for none x in (1.0, 2.0, 3.0) : (x > 4.0)"#);
 }
 
+#[test]
+fn with() {
+    condition_true!("with a = 1 : ( a == 1 )");
+    condition_false!("with a = 1 : ( a == 2 )");
+    condition_true!("with a = 1, b = a + 1 : ( a + b == 3 )");
+    condition_true!("with a = 1.5, b = true : ( a > 1.0 and b )");
+    condition_true!(r#"with s = "foo" : ( s contains "oo" )"#);
+    condition_true!(r#"with s = "foo" : ( s == "foo" ) and true"#);
+    condition_true!("with a = 2 : ( with a = a * 2 : ( a == 4 ) )");
+
+    condition_true!(
+        "for all i in (0..10) : (
+            with j = i * 2 : ( j >= i and j % 2 == 0 )
+        )"
+    );
+
+    condition_true!(
+        "with n = 3 : (
+            for all i in (1..n) : ( i <= n )
+        )"
+    );
+}
+
 #[test]
 fn text_patterns() {
     pattern_true!(r#""issi""#, b"mississippi");
@@ -1334,6 +1391,65 @@ fn match_in() {
     );
 }
 
+#[test]
+fn match_in_range_with_step() {
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                #a in (0..9 step 6) == 2 and
+                #a in (3..12 step 6) == 2 and
+                #a in (1..12 step 2) == 2 and
+                #a in (0..12 step 1) == 4
+        }
+        "#,
+        b"foofoofoofoo"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                $a in (1..12 step 4) and not $a in (1..8 step 4)
+        }
+        "#,
+        b"foofoofoofoo"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a1 = "foo"
+                $a2 = "bar"
+            condition:
+                all of ($a*) in (0..100 step 3) and
+                50% of ($a*) in (0..100 step 2) and
+                not all of ($a*) in (0..100 step 2)
+        }
+        "#,
+        b"foobar"
+    );
+
+    // If the step is not known at compile time, and it's zero or negative,
+    // the result is undefined.
+    rule_false!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                #a in (0..100 step filesize - 6) >= 0
+        }
+        "#,
+        b"foobar"
+    );
+}
+
 #[test]
 fn match_count() {
     rule_true!(
@@ -2135,6 +2251,46 @@ fn base64() {
     );
 }
 
+#[test]
+fn transformations() {
+    pattern_true!(r#""foobar" rot13"#, b"sbbone");
+    pattern_false!(r#""foobar" rot13"#, b"foobar");
+    pattern_true!(r#""Hello, World!" rot13"#, b"Uryyb, Jbeyq!");
+    pattern_true!(r#""foobar" rot13 nocase"#, b"SBBONE");
+    pattern_true!(r#""foobar" rot13 wide"#, b"s\x00b\x00b\x00o\x00n\x00e\x00");
+    pattern_false!(r#""foobar" rot13 fullword"#, b"xsbbonex");
+
+    pattern_true!(r#""foo" hexascii"#, b"666f6f");
+    pattern_true!(r#""foo" hexascii"#, b"666F6F");
+    pattern_false!(r#""foo" hexascii"#, b"666f6F");
+    pattern_false!(r#""foo" hexascii"#, b"foo");
+    pattern_true!(r#""foo" hexascii nocase"#, b"666f6F");
+
+    pattern_true!(r#""1 + 1 = 2" utf7"#, b"1 +- 1 = 2");
+    pattern_true!(r#""~foo" utf7"#, b"+AH4-foo");
+    pattern_true!(r#""Hi Mom -\xe2\x98\xba-!" utf7"#, b"Hi Mom -+Jjo--!");
+    pattern_true!(r#""foobar" utf7"#, b"foobar");
+    pattern_true!(r#""hello" utf7"#, b"+AGgAZQBsAGwAbw-");
+    pattern_true!(r#""1 + 1" utf7"#, b"+ADEAIAArACAAMQ-");
+
+    // Multiple transformations match any of the transformed texts.
+    pattern_true!(r#""foobar" rot13 hexascii"#, b"sbbone");
+    pattern_true!(r#""foobar" rot13 hexascii"#, b"666f6f626172");
+    pattern_false!(r#""foobar" rot13 hexascii"#, b"foobar");
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo" hexascii
+            condition:
+                $a at 3 and !a == 6
+        }
+        "#,
+        b"xxx666f6fxxx"
+    );
+}
+
 #[test]
 fn filesize() {
     let rules = crate::compile(
@@ -2155,7 +2311,7 @@ fn filesize() {
     )
     .unwrap();
 
-    let mut scanner = crate::scanner::Scanner::new(&rules);
+    let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
 
     assert_eq!(
         scanner
@@ -2374,7 +2530,7 @@ fn rule_reuse_1() {
     )
     .unwrap();
 
-    let mut scanner = crate::scanner::Scanner::new(&rules);
+    let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
 
     assert_eq!(
         scanner
@@ -2406,7 +2562,7 @@ fn rule_reuse_2() {
     )
     .unwrap();
 
-    let mut scanner = crate::scanner::Scanner::new(&rules);
+    let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
 
     assert_eq!(
         scanner
@@ -2418,6 +2574,54 @@ fn rule_reuse_2() {
     );
 }
 
+#[test]
+fn rule_sets() {
+    let rules = crate::compile(
+        r#"
+        rule apt_1 : trojan {
+          condition:
+            true
+        }
+        rule apt_2 {
+          condition:
+            false
+        }
+        rule other : trojan {
+          condition:
+            true
+        }
+        rule any_apt {
+          condition:
+            any of (apt_*)
+        }
+        rule all_apt {
+          condition:
+            all of (apt_*)
+        }
+        rule two_trojans {
+          condition:
+            2 of (tag:trojan)
+        }
+        rule mixed {
+          condition:
+            all of (apt_1, tag:trojan) and none of (apt_2, any_*)
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = crate::scanner::Scanner::new(&rules).unwrap();
+    let scan_results = scanner.scan(&[]).expect("scan should not fail");
+
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|r| r.name().to_string())
+            .collect::<Vec<_>>(),
+        vec!["apt_1", "other", "any_apt", "two_trojans"]
+    );
+}
+
 #[test]
 fn test_defined_1() {
     condition_true!(r#"defined 1"#);
@@ -2462,6 +2666,9 @@ fn test_proto2_module() {
 
     condition_true!(r#"test_proto2.uppercase("foo") == "FOO""#);
     condition_true!(r#"test_proto2.nested.nested_func()"#);
+    condition_true!(
+        r#"test_proto2.nested.nested_func() and test_proto2.int64_one == 1"#
+    );
     condition_true!(
         r#"test_proto2.head(3) == "\x01\x02\x03""#,
         &[0x01, 0x02, 0x03, 0x04]
@@ -2586,6 +2793,34 @@ fn test_proto2_module() {
     condition_true!(r#"for all i in test_proto2.array_int64 : (i < 10000)"#);
     condition_true!(r#"for any s in test_proto2.array_string : (s == "foo")"#);
 
+    condition_true!(
+        r#"for all i, v in test_proto2.array_int64 : (
+            test_proto2.array_int64[i] == v
+          )"#
+    );
+
+    condition_true!(
+        r#"for any i, s in test_proto2.array_string : (
+            i == 2 and s == "baz"
+          )"#
+    );
+
+    condition_true!(
+        r#"for any i, s in test_proto2.array_struct : (
+            i == 0 and s.nested_int32_one == 1
+          )"#
+    );
+
+    condition_true!(
+        r#"with n = test_proto2.array_int64[1] : (
+            n == 10 and n * 10 == test_proto2.array_int64[2]
+          )"#
+    );
+
+    // If the value bound to a variable is undefined, the `with` expression
+    // is undefined too.
+    condition_false!(r#"with n = test_proto2.int64_undef : ( true )"#);
+
     condition_true!(
         r#"for all s in test_proto2.array_string : (
             s == "foo" or s == "bar" or s == "baz"
@@ -19,11 +19,12 @@ use crate::types::{TypeValue, Value};
 ///   b: bool
 ///   s: string
 ///   r: regexp
+///   p: pattern
 ///  ```
 ///
 ///  `<return type>` is also a sequence of one or more of the characters
-///  above, specifying the types returned by the function (except `r`,
-///  because functions can't return regular expressions). For example, a
+///  above, specifying the types returned by the function (except `r` and
+///  `p`, because functions can't return regular expressions or patterns). For example, a
 ///  function `add` with two integer arguments that return another integer
 ///  would have the mangled name `add@ii@i`. A function `foo` that returns
 ///  a tuple of two integers have the mangled name `foo@@ii`.
@@ -71,6 +72,7 @@ impl MangledFnName {
                 'b' => args.push(TypeValue::Bool(Value::Unknown)),
                 's' => args.push(TypeValue::String(Value::Unknown)),
                 'r' => args.push(TypeValue::Regexp(None)),
+                'p' => args.push(TypeValue::Pattern),
                 _ => panic!("unexpected argument type: `{}`", t),
             }
         }
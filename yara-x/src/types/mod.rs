@@ -25,6 +25,7 @@ pub enum Type {
     Bool,
     String,
     Regexp,
+    Pattern,
     Struct,
     Array,
     Map,
@@ -46,6 +47,7 @@ impl Debug for Type {
             Self::Bool => write!(f, "boolean"),
             Self::String => write!(f, "string"),
             Self::Regexp => write!(f, "regexp"),
+            Self::Pattern => write!(f, "pattern"),
             Self::Struct => write!(f, "struct"),
             Self::Array => write!(f, "array"),
             Self::Map => write!(f, "map"),
@@ -161,6 +163,9 @@ pub enum TypeValue {
     Bool(Value<bool>),
     String(Value<BString>),
     Regexp(Option<Regexp>),
+    /// A pattern identifier (e.g: `$a`), used only as the type of function
+    /// arguments that receive a pattern instead of a value.
+    Pattern,
     Struct(Rc<Struct>),
     Array(Rc<Array>),
     Map(Rc<Map>),
@@ -179,6 +184,7 @@ impl TypeValue {
             TypeValue::Bool(value) => value.is_const(),
             TypeValue::String(value) => value.is_const(),
             TypeValue::Regexp(_) => false,
+            TypeValue::Pattern => false,
             TypeValue::Struct(_) => false,
             TypeValue::Array(_) => false,
             TypeValue::Map(_) => false,
@@ -223,6 +229,7 @@ impl TypeValue {
             Self::Bool(_) => Type::Bool,
             Self::String(_) => Type::String,
             Self::Regexp(_) => Type::Regexp,
+            Self::Pattern => Type::Pattern,
             Self::Map(_) => Type::Map,
             Self::Struct(_) => Type::Struct,
             Self::Array(_) => Type::Array,
@@ -238,6 +245,7 @@ impl TypeValue {
             Self::Bool(_) => Self::Bool(Value::Unknown),
             Self::String(_) => Self::String(Value::Unknown),
             Self::Regexp(_) => Self::Regexp(None),
+            Self::Pattern => Self::Pattern,
             Self::Map(v) => Self::Map(v.clone()),
             Self::Struct(v) => Self::Struct(v.clone()),
             Self::Array(v) => Self::Array(v.clone()),
@@ -245,40 +253,6 @@ impl TypeValue {
         }
     }
 
-    /// Casts a [`TypeValue`] to [`TypeValue::Bool`].
-    ///
-    /// # Panics
-    ///
-    /// If the [`TypeValue`] has a type that can't be casted to bool. Only
-    /// integers, floats, and strings and bools can be casted to bool.
-    pub fn cast_to_bool(&self) -> Self {
-        match self {
-            Self::Integer(Value::Unknown) => Self::Bool(Value::Unknown),
-            Self::Integer(Value::Var(i)) => Self::Bool(Value::Var(*i != 0)),
-            Self::Integer(Value::Const(i)) => {
-                Self::Bool(Value::Const(*i != 0))
-            }
-
-            Self::Float(Value::Unknown) => Self::Bool(Value::Unknown),
-            Self::Float(Value::Var(f)) => Self::Bool(Value::Var(*f != 0.0)),
-            Self::Float(Value::Const(f)) => {
-                Self::Bool(Value::Const(*f != 0.0))
-            }
-
-            Self::String(Value::Unknown) => Self::Bool(Value::Unknown),
-            Self::String(Value::Var(s)) => Self::Bool(Value::Var(s.len() > 0)),
-            Self::String(Value::Const(s)) => {
-                Self::Bool(Value::Const(s.len() > 0))
-            }
-
-            Self::Bool(Value::Unknown) => Self::Bool(Value::Unknown),
-            Self::Bool(Value::Var(b)) => Self::Bool(Value::Var(*b)),
-            Self::Bool(Value::Const(b)) => Self::Bool(Value::Const(*b)),
-
-            _ => panic!("can not cast {:?} to bool", self),
-        }
-    }
-
     pub fn as_bstr(&self) -> &BStr {
         if let TypeValue::String(v) = self {
             v.extract()
@@ -360,14 +334,6 @@ impl TypeValue {
         }
     }
 
-    pub fn try_as_bool(&self) -> Option<bool> {
-        if let TypeValue::Bool(value) = self {
-            value.extract().cloned()
-        } else {
-            None
-        }
-    }
-
     pub fn try_as_integer(&self) -> Option<i64> {
         if let TypeValue::Integer(value) = self {
             value.extract().cloned()
@@ -422,6 +388,7 @@ impl Debug for TypeValue {
                     write!(f, "regexp(unknown)")
                 }
             }
+            Self::Pattern => write!(f, "pattern"),
             Self::Map(_) => write!(f, "map"),
             Self::Struct(_) => write!(f, "struct"),
             Self::Array(_) => write!(f, "array"),
@@ -1,6 +1,6 @@
 use rustc_hash::FxHashMap;
 use std::mem;
-use walrus::ir::{Block, InstrSeqId};
+use walrus::ir::Block;
 use walrus::ValType::{F64, I32, I64};
 use walrus::{FunctionBuilder, FunctionId, InstrSeqBuilder};
 
@@ -125,20 +125,27 @@ pub(crate) struct WasmModuleBuilder {
     module: walrus::Module,
     wasm_symbols: WasmSymbols,
     wasm_exports: FxHashMap<String, FunctionId>,
-    main_func: FunctionBuilder,
-    namespace_func: FunctionBuilder,
     rules_func: FunctionBuilder,
     global_rules_func: FunctionBuilder,
-    namespace_block: InstrSeqId,
-    global_rules_block: InstrSeqId,
-    rules_block: InstrSeqId,
+    /// Functions containing the code for global and non-global rules, one
+    /// entry per YARA namespace. The last entry corresponds to the current
+    /// namespace. The `main` function and the namespaces functions are not
+    /// created until the module is emitted, which allows emitting the module
+    /// multiple times while rules keep being added to it.
+    namespaces: Vec<NamespaceFuncs>,
     num_rules: usize,
     num_global_rules: usize,
-    num_namespaces: usize,
     namespaces_per_func: usize,
     rules_per_func: usize,
 }
 
+/// Functions that contain the code for the rules in a YARA namespace.
+#[derive(Default)]
+struct NamespaceFuncs {
+    global_rules: Vec<FunctionId>,
+    rules: Vec<FunctionId>,
+}
+
 impl WasmModuleBuilder {
     const GLOBAL_RULES_FUNC_RET: [walrus::ValType; 1] = [I32; 1];
     const RULES_FUNC_RET: [walrus::ValType; 0] = [];
@@ -166,6 +173,7 @@ impl WasmModuleBuilder {
         global_const!(module, matching_patterns_bitmap_base, I32);
         global_var!(module, filesize, I64);
         global_var!(module, pattern_search_done, I32);
+        global_var!(module, current_rule, I32);
 
         let (main_memory, _) =
             module.add_import_memory("yara_x", "main_memory", false, 1, None);
@@ -175,6 +183,7 @@ impl WasmModuleBuilder {
             matching_patterns_bitmap_base,
             filesize,
             pattern_search_done,
+            current_rule,
             i64_tmp: module.locals.add(I64),
             i32_tmp: module.locals.add(I32),
             f64_tmp: module.locals.add(F64),
@@ -186,38 +195,21 @@ impl WasmModuleBuilder {
             &Self::GLOBAL_RULES_FUNC_RET,
         );
 
-        let mut namespace_func =
-            FunctionBuilder::new(&mut module.types, &[], &[]);
-
         let rules_func = FunctionBuilder::new(
             &mut module.types,
             &[],
             &Self::RULES_FUNC_RET,
         );
 
-        let mut main_func = FunctionBuilder::new(&mut module.types, &[], &[]);
-
-        main_func.func_body().i32_const(0);
-        main_func.func_body().global_set(pattern_search_done);
-
-        let namespace_block = namespace_func.dangling_instr_seq(None).id();
-        let global_rules_block = namespace_func.dangling_instr_seq(None).id();
-        let rules_block = namespace_func.dangling_instr_seq(None).id();
-
         Self {
             module,
             wasm_symbols,
             wasm_exports,
-            main_func,
             global_rules_func,
-            namespace_func,
             rules_func,
-            namespace_block,
-            global_rules_block,
-            rules_block,
+            namespaces: vec![NamespaceFuncs::default()],
             num_rules: 0,
             num_global_rules: 0,
-            num_namespaces: 0,
             namespaces_per_func: 10,
             rules_per_func: 10,
         }
@@ -247,7 +239,6 @@ impl WasmModuleBuilder {
     pub fn new_global_rule(&mut self) -> InstrSeqBuilder {
         if self.num_global_rules == self.rules_per_func {
             self.finish_global_rule_func();
-            self.num_global_rules = 0;
         }
         self.num_global_rules += 1;
         self.global_rules_func.func_body()
@@ -258,7 +249,6 @@ impl WasmModuleBuilder {
     pub fn new_rule(&mut self) -> InstrSeqBuilder {
         if self.num_rules == self.rules_per_func {
             self.finish_rule_func();
-            self.num_rules = 0;
         }
         self.num_rules += 1;
         self.rules_func.func_body()
@@ -267,97 +257,135 @@ impl WasmModuleBuilder {
     pub fn new_namespace(&mut self) {
         self.finish_global_rule_func();
         self.finish_rule_func();
-        self.finish_namespace_block();
-        if self.num_namespaces == self.namespaces_per_func {
-            self.finish_namespace_func();
-            self.num_namespaces = 0;
-        }
-        self.num_namespaces += 1;
-        self.num_rules = 0;
-        self.num_global_rules = 0;
+        self.namespaces.push(NamespaceFuncs::default());
     }
 
     /// Builds the WASM module and consumes the builder.
     pub fn build(mut self) -> walrus::Module {
         self.finish_global_rule_func();
         self.finish_rule_func();
-        self.finish_namespace_block();
-        self.finish_namespace_func();
+        self.add_main_func();
+        self.module
+    }
 
-        let main_func =
-            self.main_func.finish(Vec::new(), &mut self.module.funcs);
+    /// Emits the WASM module with the rules added so far, without consuming
+    /// the builder.
+    ///
+    /// More rules can be added to the builder after calling this function,
+    /// and the module can be emitted again. Rules added to the current
+    /// namespace after this call are put in the same namespace as the rules
+    /// that were added before.
+    pub fn emit_wasm(&mut self) -> Vec<u8> {
+        self.finish_global_rule_func();
+        self.finish_rule_func();
 
-        self.module.exports.add("main", main_func);
-        self.module
+        let (main_func, namespace_funcs) = self.add_main_func();
+        let wasm = self.module.emit_wasm();
+
+        // The `main` function and the namespaces functions are re-created
+        // each time the module is emitted, so they must be removed.
+        let export_id = self.module.exports.get_exported_func(main_func);
+        self.module.exports.delete(export_id.unwrap().id());
+        self.module.funcs.delete(main_func);
+
+        for func_id in namespace_funcs {
+            self.module.funcs.delete(func_id);
+        }
+
+        wasm
     }
 }
 
 impl WasmModuleBuilder {
-    fn finish_namespace_block(&mut self) {
-        let global_rules = !self
-            .namespace_func
-            .instr_seq(self.global_rules_block)
-            .instrs()
-            .is_empty();
-
-        let rules = !self
-            .namespace_func
-            .instr_seq(self.rules_block)
-            .instrs()
-            .is_empty();
+    /// Creates the namespaces functions and the `main` function that calls
+    /// them, and exports `main`. Returns the [`FunctionId`] for `main` and
+    /// the ones for all the namespaces functions.
+    fn add_main_func(&mut self) -> (FunctionId, Vec<FunctionId>) {
+        let mut main_func =
+            FunctionBuilder::new(&mut self.module.types, &[], &[]);
+
+        main_func.func_body().i32_const(0);
+        main_func
+            .func_body()
+            .global_set(self.wasm_symbols.pattern_search_done);
+
+        let mut namespace_funcs = Vec::new();
+
+        // Namespaces without rules don't produce any code, they are ignored.
+        let namespaces = self
+            .namespaces
+            .iter()
+            .filter(|n| !n.global_rules.is_empty() || !n.rules.is_empty())
+            .collect::<Vec<_>>();
+
+        for namespaces in namespaces.chunks(self.namespaces_per_func) {
+            let mut namespace_func =
+                FunctionBuilder::new(&mut self.module.types, &[], &[]);
+
+            for namespace in namespaces {
+                Self::emit_namespace_block(&mut namespace_func, namespace);
+            }
+
+            let func_id = self
+                .module
+                .funcs
+                .add_local(namespace_func.local_func(Vec::new()));
+
+            main_func.func_body().call(func_id);
+            namespace_funcs.push(func_id);
+        }
+
+        let main_func = main_func.finish(Vec::new(), &mut self.module.funcs);
+
+        self.module.exports.add("main", main_func);
+
+        (main_func, namespace_funcs)
+    }
+
+    fn emit_namespace_block(
+        namespace_func: &mut FunctionBuilder,
+        namespace: &NamespaceFuncs,
+    ) {
+        let global_rules = !namespace.global_rules.is_empty();
+        let rules = !namespace.rules.is_empty();
+
+        let namespace_block = namespace_func.dangling_instr_seq(None).id();
+        let global_rules_block = namespace_func.dangling_instr_seq(None).id();
+        let rules_block = namespace_func.dangling_instr_seq(None).id();
+
+        for func_id in namespace.global_rules.iter() {
+            let mut block = namespace_func.instr_seq(global_rules_block);
+            block.call(*func_id);
+            block.br_if(namespace_block);
+        }
+
+        for func_id in namespace.rules.iter() {
+            namespace_func.instr_seq(rules_block).call(*func_id);
+        }
 
         if global_rules {
-            self.namespace_func
-                .instr_seq(self.namespace_block)
-                .instr(Block { seq: self.global_rules_block });
+            namespace_func
+                .instr_seq(namespace_block)
+                .instr(Block { seq: global_rules_block });
         }
 
         if rules {
-            self.namespace_func
-                .instr_seq(self.namespace_block)
-                .instr(Block { seq: self.rules_block });
+            namespace_func
+                .instr_seq(namespace_block)
+                .instr(Block { seq: rules_block });
         }
 
         match (global_rules, rules) {
             (true, true) | (true, false) => {
-                self.namespace_func
+                namespace_func
                     .func_body()
-                    .instr(Block { seq: self.namespace_block });
+                    .instr(Block { seq: namespace_block });
             }
             (false, true) => {
-                self.namespace_func
-                    .func_body()
-                    .instr(Block { seq: self.rules_block });
+                namespace_func.func_body().instr(Block { seq: rules_block });
             }
             (false, false) => {}
         }
-
-        self.namespace_block =
-            self.namespace_func.dangling_instr_seq(None).id();
-
-        self.global_rules_block =
-            self.namespace_func.dangling_instr_seq(None).id();
-
-        self.rules_block = self.namespace_func.dangling_instr_seq(None).id();
-    }
-
-    fn finish_namespace_func(&mut self) {
-        let namespace_func = mem::replace(
-            &mut self.namespace_func,
-            FunctionBuilder::new(&mut self.module.types, &[], &[]),
-        );
-
-        self.namespace_block =
-            self.namespace_func.dangling_instr_seq(None).id();
-
-        self.global_rules_block =
-            self.namespace_func.dangling_instr_seq(None).id();
-
-        self.rules_block = self.namespace_func.dangling_instr_seq(None).id();
-
-        self.main_func.func_body().call(
-            self.module.funcs.add_local(namespace_func.local_func(Vec::new())),
-        );
     }
 
     fn finish_global_rule_func(&mut self) {
@@ -370,6 +398,8 @@ impl WasmModuleBuilder {
             ),
         );
 
+        self.num_global_rules = 0;
+
         if !global_rules_func.func_body().instrs().is_empty() {
             // The last instruction in a global rules function leaves a
             // 0 in the stack as its return value. This is reached only
@@ -377,16 +407,12 @@ impl WasmModuleBuilder {
             // match, the function exits early with a return value of 1.
             global_rules_func.func_body().i32_const(0);
 
-            let mut block =
-                self.namespace_func.instr_seq(self.global_rules_block);
+            let func_id = self
+                .module
+                .funcs
+                .add_local(global_rules_func.local_func(Vec::new()));
 
-            block.call(
-                self.module
-                    .funcs
-                    .add_local(global_rules_func.local_func(Vec::new())),
-            );
-
-            block.br_if(self.namespace_block);
+            self.namespaces.last_mut().unwrap().global_rules.push(func_id);
         }
     }
 
@@ -400,12 +426,13 @@ impl WasmModuleBuilder {
             ),
         );
 
+        self.num_rules = 0;
+
         if !rule_func.func_body().instrs().is_empty() {
-            let mut block_2 = self.namespace_func.instr_seq(self.rules_block);
+            let func_id =
+                self.module.funcs.add_local(rule_func.local_func(Vec::new()));
 
-            block_2.call(
-                self.module.funcs.add_local(rule_func.local_func(Vec::new())),
-            );
+            self.namespaces.last_mut().unwrap().rules.push(func_id);
         }
     }
 }
@@ -415,6 +442,7 @@ mod tests {
     use crate::wasm::builder::WasmModuleBuilder;
     use itertools::Itertools;
     use pretty_assertions::assert_eq;
+    use regex::{Captures, Regex};
 
     #[test]
     fn module_builder() {
@@ -437,6 +465,18 @@ mod tests {
         let wasm = module.emit_wasm();
         let text = wasmprinter::print_bytes(wasm).unwrap();
 
+        // Imported functions come before the ones defined in the module,
+        // so the indexes of the latter change every time a function is
+        // exported to WASM. Indexes are made relative to the first function
+        // defined in the module.
+        let num_imported_funcs = text
+            .lines()
+            .filter(|l| l.starts_with("  (import") && l.contains("(func"))
+            .count();
+
+        let func_index =
+            Regex::new(r"(\(func \(;|call |\(func )(\d+)").unwrap();
+
         // Remove all lines that start with "  (type" or "  (import". These lines
         // are not relevant to this test, we are interested in the functions
         // only. Also the order of imports is not stable across compiler
@@ -446,40 +486,46 @@ mod tests {
             .filter(|l| {
                 !l.starts_with("  (type") && !l.starts_with("  (import")
             })
+            .map(|l| {
+                func_index.replace_all(l, |caps: &Captures| {
+                    let index: usize = caps[2].parse().unwrap();
+                    format!("{}{}", &caps[1], index - num_imported_funcs)
+                })
+            })
             .join("\n");
 
         assert_eq!(
             text,
             r#"(module
-  (func (;77;) (type 0)
+  (func (;0;) (type 0)
     block ;; label = @1
-      call 80
+      call 3
     end
     block ;; label = @1
-      call 81
+      call 4
     end
   )
-  (func (;78;) (type 0)
+  (func (;1;) (type 0)
     i32.const 0
     global.set 2
-    call 77
-    call 79
+    call 0
+    call 2
   )
-  (func (;79;) (type 0)
+  (func (;2;) (type 0)
     block ;; label = @1
-      call 82
+      call 5
     end
   )
-  (func (;80;) (type 0)
+  (func (;3;) (type 0)
     i32.const 4
   )
-  (func (;81;) (type 0)
+  (func (;4;) (type 0)
     i32.const 5
   )
-  (func (;82;) (type 0)
+  (func (;5;) (type 0)
     i32.const 6
   )
-  (export "main" (func 78))
+  (export "main" (func 1))
 )"#
         );
     }
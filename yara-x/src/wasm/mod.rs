@@ -139,15 +139,31 @@ impl WasmExport {
     /// The fully qualified name includes not only the function's name, but
     /// also the module's name (e.g: `my_module.my_struct.my_func@ii@i`)
     pub fn fully_qualified_mangled_name(&self) -> String {
+        // The path looks like `yara_x::modules::my_module::...`. The Rust
+        // module's name must match the component that follows `modules`
+        // exactly, otherwise the `wasm` module would claim the functions
+        // in `yara_x::wasm`.
+        let rust_module = self
+            .rust_module_path
+            .split("::")
+            .skip_while(|component| *component != "modules")
+            .nth(1);
+
         for (module_name, module) in BUILTIN_MODULES.iter() {
             if let Some(rust_module_name) = module.rust_module_name {
-                if self.rust_module_path.contains(rust_module_name) {
+                if rust_module == Some(rust_module_name) {
                     return format!("{}.{}", module_name, self.mangled_name);
                 }
             }
         }
         self.mangled_name.to_owned()
     }
+
+    /// Returns true if this is a built-in function, like `uint8`, `uint16`,
+    /// etc, instead of a function exported by some YARA module.
+    pub fn builtin(&self) -> bool {
+        self.rust_module_path == module_path!()
+    }
 }
 
 /// Trait implemented for all types that represent a function exported to WASM.
@@ -531,6 +547,11 @@ pub(crate) struct WasmSymbols {
     /// evaluated and some of them needs to know if a pattern matched or not.
     pub pattern_search_done: walrus::GlobalId,
 
+    /// Global variable that contains the [`RuleId`] of the rule whose
+    /// condition is being evaluated. This allows attributing errors that
+    /// occur at scan time, like timeouts, to a specific rule.
+    pub current_rule: walrus::GlobalId,
+
     /// Local variables used for temporary storage.
     pub i64_tmp: walrus::LocalId,
     pub i32_tmp: walrus::LocalId,
@@ -548,7 +569,24 @@ lazy_static! {
     pub(crate) static ref LINKER: Linker<ScanContext<'static>> = new_linker();
 }
 
-pub(crate) fn new_linker<'r>() -> Linker<ScanContext<'r>> {
+/// Returns a linker that contains all the functions exported to WASM.
+///
+/// Defining the exported functions is expensive, as a trampoline must be
+/// generated for each of them, so this is done only once in [`LINKER`],
+/// and the returned linker is a clone of it.
+pub(crate) fn linker<'r>() -> Linker<ScanContext<'r>> {
+    // SAFETY: `Linker<ScanContext<'static>>` and `Linker<ScanContext<'r>>`
+    // differ only in a lifetime, so they have the same layout. The linker
+    // doesn't hold any `ScanContext`, only functions that receive one, and
+    // these functions accept a `ScanContext` with any lifetime.
+    unsafe {
+        mem::transmute::<Linker<ScanContext<'static>>, Linker<ScanContext<'r>>>(
+            LINKER.clone(),
+        )
+    }
+}
+
+fn new_linker<'r>() -> Linker<ScanContext<'r>> {
     let mut linker = Linker::<ScanContext<'r>>::new(&ENGINE);
     for export in WASM_EXPORTS {
         let func_type = FuncType::new(
@@ -569,9 +607,72 @@ pub(crate) fn new_linker<'r>() -> Linker<ScanContext<'r>> {
         }
     }
 
+    add_legacy_exports(&mut linker);
+
     linker
 }
 
+/// Adds to the linker the functions that are imported by rules compiled
+/// with previous versions of YARA-X, but that don't exist anymore with the
+/// same name and signature.
+///
+/// Before pattern identifiers had their own type in mangled names they were
+/// mangled as integers (`i` instead of `p`), and `is_pat_match_in` and
+/// `pat_matches_in` didn't receive the `step` argument, which is equivalent
+/// to a step of 1.
+fn add_legacy_exports(linker: &mut Linker<ScanContext<'_>>) {
+    let module = module_path!();
+
+    for (export, legacy_name) in [
+        (&export__is_pat_match_at, "is_pat_match_at@ii@b"),
+        (&export__pat_matches, "pat_matches@i@i"),
+        (&export__pat_length, "pat_length@ii@iu"),
+        (&export__pat_offset, "pat_offset@ii@iu"),
+    ] {
+        linker
+            .alias(module, export.mangled_name, module, legacy_name)
+            .unwrap();
+    }
+
+    linker
+        .func_wrap(
+            module,
+            "is_pat_match_in@iii@b",
+            |caller: Caller<'_, ScanContext>,
+             pattern_id: i32,
+             lower_bound: i64,
+             upper_bound: i64| {
+                is_pat_match_in(
+                    caller,
+                    pattern_id.into(),
+                    lower_bound,
+                    upper_bound,
+                    1,
+                ) as i32
+            },
+        )
+        .unwrap();
+
+    linker
+        .func_wrap(
+            module,
+            "pat_matches_in@iii@i",
+            |caller: Caller<'_, ScanContext>,
+             pattern_id: i32,
+             lower_bound: i64,
+             upper_bound: i64| {
+                pat_matches_in(
+                    caller,
+                    pattern_id.into(),
+                    lower_bound,
+                    upper_bound,
+                    1,
+                )
+            },
+        )
+        .unwrap();
+}
+
 /// Invoked from WASM for triggering the pattern search phase.
 ///
 /// Returns `true` on success and `false` when a timeout occurs.
@@ -630,17 +731,22 @@ pub(crate) fn is_pat_match_at(
 /// a given range.
 ///
 /// Returns true if the pattern identified by `pattern_id` matches at some
-/// offset in the range [`lower_bound`, `upper_bound`], both inclusive.
+/// offset in the range [`lower_bound`, `upper_bound`], both inclusive. Only
+/// the offsets that are `lower_bound + N * step` are taken into account.
 #[wasm_export]
 pub(crate) fn is_pat_match_in(
     caller: Caller<'_, ScanContext>,
     pattern_id: PatternId,
     lower_bound: i64,
     upper_bound: i64,
+    step: i64,
 ) -> bool {
     if let Some(matches) = caller.data().pattern_matches.get(&pattern_id) {
         matches
-            .matches_in_range(lower_bound as isize..=upper_bound as isize)
+            .matches_in_range(
+                lower_bound as isize..=upper_bound as isize,
+                step as usize,
+            )
             .is_positive()
     } else {
         false
@@ -665,15 +771,21 @@ pub(crate) fn pat_matches(
 ///
 /// Returns the number of matches for the pattern identified by `pattern_id`
 /// that start in the range [`lower_bound`, `upper_bound`], both inclusive.
+/// Only the offsets that are `lower_bound + N * step` are taken into
+/// account.
 #[wasm_export]
 pub(crate) fn pat_matches_in(
     caller: Caller<'_, ScanContext>,
     pattern_id: PatternId,
     lower_bound: i64,
     upper_bound: i64,
+    step: i64,
 ) -> i64 {
     if let Some(matches) = caller.data().pattern_matches.get(&pattern_id) {
-        matches.matches_in_range(lower_bound as isize..=upper_bound as isize)
+        matches.matches_in_range(
+            lower_bound as isize..=upper_bound as isize,
+            step as usize,
+        )
     } else {
         0
     }
@@ -1405,6 +1517,76 @@ gen_xint_fn!(int8be, i8, from_be_bytes);
 gen_xint_fn!(int16be, i16, from_be_bytes);
 gen_xint_fn!(int32be, i32, from_be_bytes);
 
+macro_rules! gen_int_arithmetic_fn {
+    ($name:ident, $op:ident, $return_type:ty) => {
+        #[wasm_export]
+        pub(crate) fn $name(
+            _caller: Caller<'_, ScanContext>,
+            lhs: i64,
+            rhs: i64,
+        ) -> $return_type {
+            lhs.$op(rhs)
+        }
+    };
+}
+
+gen_int_arithmetic_fn!(saturating_add, saturating_add, i64);
+gen_int_arithmetic_fn!(saturating_sub, saturating_sub, i64);
+gen_int_arithmetic_fn!(saturating_mul, saturating_mul, i64);
+gen_int_arithmetic_fn!(checked_add, checked_add, Option<i64>);
+gen_int_arithmetic_fn!(checked_sub, checked_sub, Option<i64>);
+gen_int_arithmetic_fn!(checked_mul, checked_mul, Option<i64>);
+
+/// Converts a float to integer, truncating the fractional part. The result
+/// is undefined if the float is NaN or out of the range of integers.
+#[wasm_export(name = "to_int", public = true)]
+pub(crate) fn float_to_int(
+    _caller: Caller<'_, ScanContext>,
+    value: f64,
+) -> Option<i64> {
+    // i64::MAX can't be represented exactly as f64, it is rounded up to
+    // 2^63, which is out of range.
+    if value.is_nan() || value < i64::MIN as f64 || value >= i64::MAX as f64 {
+        return None;
+    }
+    Some(value as i64)
+}
+
+/// Parses a string containing a decimal integer. The result is undefined
+/// if the string is not a valid integer.
+#[wasm_export(name = "to_int", public = true)]
+pub(crate) fn str_to_int(
+    caller: Caller<'_, ScanContext>,
+    value: RuntimeString,
+) -> Option<i64> {
+    value.to_str(caller.data()).ok()?.parse::<i64>().ok()
+}
+
+/// Converts an integer to float. Integers with an absolute value larger
+/// than 2^53 are rounded to the nearest float.
+#[wasm_export(name = "to_float", public = true)]
+pub(crate) fn int_to_float(
+    _caller: Caller<'_, ScanContext>,
+    value: i64,
+) -> f64 {
+    value as f64
+}
+
+/// Parses a string containing a float. The result is undefined if the
+/// string is not a valid float, or if it represents infinity or NaN.
+#[wasm_export(name = "to_float", public = true)]
+pub(crate) fn str_to_float(
+    caller: Caller<'_, ScanContext>,
+    value: RuntimeString,
+) -> Option<f64> {
+    let value = value.to_str(caller.data()).ok()?.parse::<f64>().ok()?;
+    if value.is_finite() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::wasm::WasmResult;